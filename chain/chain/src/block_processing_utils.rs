@@ -29,6 +29,10 @@ pub(crate) struct BlockPreprocessInfo {
     pub(crate) apply_chunks_done: Arc<OnceCell<()>>,
     /// This is used to calculate block processing time metric
     pub(crate) block_start_processing_time: Instant,
+    /// Span in which the processing of the block started.  Applying chunks
+    /// and postprocessing happen in its children so that all the processing
+    /// of the block is part of the same trace.
+    pub(crate) span: tracing::Span,
 }
 
 /// Blocks which finished pre-processing and are now being applied asynchronously
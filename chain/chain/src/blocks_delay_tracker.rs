@@ -132,12 +132,13 @@ impl ChunkTrackingStats {
 }
 
 impl BlocksDelayTracker {
+    /// Returns whether this is the first time the block was received.
     pub fn mark_block_received(
         &mut self,
         block: &Block,
         timestamp: Instant,
         utc_timestamp: DateTime<chrono::Utc>,
-    ) {
+    ) -> bool {
         let block_hash = block.header().hash();
 
         if let Entry::Vacant(entry) = self.blocks.entry(*block_hash) {
@@ -171,6 +172,9 @@ impl BlocksDelayTracker {
                 chunks,
             });
             self.blocks_height_map.entry(height).or_insert(vec![]).push(*block_hash);
+            true
+        } else {
+            false
         }
     }
 
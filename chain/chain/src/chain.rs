@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use std::sync::Arc;
 use std::time::{Duration as TimeDuration, Instant};
@@ -25,18 +25,20 @@ use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::merkle::{
     combine_hash, merklize, verify_path, Direction, MerklePath, MerklePathItem, PartialMerkleTree,
 };
-use near_primitives::receipt::Receipt;
+use near_primitives::network::PeerId;
+use near_primitives::receipt::{Receipt, ReceiptEnum};
 use near_primitives::sharding::{
     ChunkHash, ChunkHashHeight, EncodedShardChunk, ReceiptList, ReceiptProof, ShardChunk,
     ShardChunkHeader, ShardInfo, ShardProof, StateSyncInfo,
 };
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::{
-    get_num_state_parts, ReceiptProofResponse, RootProof, ShardStateSyncResponseHeader,
-    ShardStateSyncResponseHeaderV1, ShardStateSyncResponseHeaderV2, StateHeaderKey, StatePartKey,
+    get_num_state_parts, ReceiptProofResponse, RootProof, ShardStateDelta,
+    ShardStateSyncResponseHeader, ShardStateSyncResponseHeaderV1, ShardStateSyncResponseHeaderV2,
+    StateHeaderKey, StatePartKey,
 };
 use near_primitives::transaction::{
-    ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, SignedTransaction,
+    Action, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, SignedTransaction,
 };
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
@@ -44,7 +46,7 @@ use near_primitives::types::{
     NumBlocks, NumShards, ShardId, StateChangesForSplitStates, StateRoot,
 };
 use near_primitives::unwrap_or_return;
-use near_primitives::utils::MaybeValidated;
+use near_primitives::utils::{to_timestamp, MaybeValidated};
 use near_primitives::views::{
     BlockStatusView, DroppedReason, ExecutionOutcomeWithIdView, ExecutionStatusView,
     FinalExecutionOutcomeView, FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus,
@@ -52,7 +54,7 @@ use near_primitives::views::{
 };
 #[cfg(feature = "protocol_feature_flat_state")]
 use near_store::{flat_state, StorageError};
-use near_store::{DBCol, ShardTries, StoreUpdate, WrappedTrieChanges};
+use near_store::{DBCol, ShardTries, StoreUpdate, TrieChanges, WrappedTrieChanges};
 
 use crate::block_processing_utils::{
     BlockPreprocessInfo, BlockProcessingArtifact, BlocksInProcessing, DoneApplyChunkCallback,
@@ -61,13 +63,15 @@ use crate::blocks_delay_tracker::BlocksDelayTracker;
 use crate::crypto_hash_timer::CryptoHashTimer;
 use crate::lightclient::get_epoch_block_producers_view;
 use crate::migrations::check_if_block_is_first_with_chunk_of_version;
-use crate::missing_chunks::{BlockLike, MissingChunksPool};
+use crate::missing_chunks::{block_to_evict, BlockLike, MissingChunksPool, PoolLimits, PoolUsage};
+use crate::state_parts_cache::StatePartsCache;
 use crate::state_request_tracker::StateRequestTracker;
 use crate::store::{ChainStore, ChainStoreAccess, ChainStoreUpdate, GCMode};
 use crate::types::{
     AcceptedBlock, ApplySplitStateResult, ApplySplitStateResultOrStateChanges,
-    ApplyTransactionResult, Block, BlockEconomicsConfig, BlockHeader, BlockHeaderInfo, BlockStatus,
-    ChainConfig, ChainGenesis, Provenance, RuntimeWithEpochManagerAdapter,
+    ApplyTransactionResult, ArchivalDataKind, Block, BlockEconomicsConfig, BlockHeader,
+    BlockHeaderInfo, BlockStatus, ChainConfig, ChainGenesis, Provenance, ReorgEvent,
+    RuntimeWithEpochManagerAdapter,
 };
 use crate::validate::{
     validate_challenge, validate_chunk_proofs, validate_chunk_with_chunk_extra,
@@ -81,7 +85,7 @@ use delay_detector::DelayDetector;
 use lru::LruCache;
 use near_client_primitives::types::StateSplitApplyingStatus;
 use near_primitives::shard_layout::{
-    account_id_to_shard_id, account_id_to_shard_uid, ShardLayout, ShardUId,
+    account_id_to_shard_id, account_id_to_shard_uid, get_block_shard_uid, ShardLayout, ShardUId,
 };
 use near_primitives::version::PROTOCOL_VERSION;
 #[cfg(feature = "protocol_feature_flat_state")]
@@ -96,6 +100,14 @@ pub const MAX_ORPHAN_SIZE: usize = 1024;
 /// Maximum age of orphan to store in the chain.
 const MAX_ORPHAN_AGE_SECS: u64 = 300;
 
+/// Limits on the orphans stored in the chain.
+const ORPHAN_POOL_LIMITS: PoolLimits = PoolLimits {
+    max_blocks: MAX_ORPHAN_SIZE,
+    max_bytes: 128 * 1024 * 1024,
+    max_blocks_per_peer: MAX_ORPHAN_SIZE / 4,
+    max_bytes_per_peer: 32 * 1024 * 1024,
+};
+
 // Number of orphan ancestors should be checked to request chunks
 // Orphans for which we will request for missing chunks must satisfy,
 // its NUM_ORPHAN_ANCESTORS_CHECK'th ancestor has been accepted
@@ -125,6 +137,10 @@ const ACCEPTABLE_TIME_DIFFERENCE: i64 = 12 * 10;
 /// Over this block height delta in advance if we are not chunk producer - route tx to upcoming validators.
 pub const TX_ROUTING_HEIGHT_HORIZON: BlockHeightDelta = 4;
 
+/// Maximum total size of the trie nodes in a state delta.  Larger deltas
+/// aren't served, the requesting node downloads state parts instead.
+pub const MAX_STATE_DELTA_SIZE: u64 = 128 * 1024 * 1024;
+
 /// Private constant for 1 NEAR (copy from near/config.rs) used for reporting.
 const NEAR_BASE: Balance = 1_000_000_000_000_000_000_000_000;
 
@@ -148,6 +164,10 @@ pub struct Orphan {
     block: MaybeValidated<Block>,
     provenance: Provenance,
     added: Instant,
+    /// Peer which sent the block, unless it was requested.
+    peer_id: Option<PeerId>,
+    /// Serialized size of the block.
+    size: u64,
 }
 
 impl BlockLike for Orphan {
@@ -158,9 +178,27 @@ impl BlockLike for Orphan {
     fn height(&self) -> u64 {
         self.block.header().height()
     }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn peer_id(&self) -> Option<&PeerId> {
+        self.peer_id.as_ref()
+    }
 }
 
 impl Orphan {
+    fn new(
+        block: MaybeValidated<Block>,
+        provenance: Provenance,
+        peer_id: Option<PeerId>,
+        added: Instant,
+    ) -> Self {
+        let size = block.get_inner().try_to_vec().map_or(0, |bytes| bytes.len() as u64);
+        Orphan { block, provenance, added, peer_id, size }
+    }
+
     fn prev_hash(&self) -> &CryptoHash {
         self.block.header().prev_hash()
     }
@@ -171,8 +209,11 @@ impl Orphan {
 /// A block is removed from the pool if
 /// 1) it is ready to be processed
 /// or
-/// 2) size of the pool exceeds MAX_ORPHAN_SIZE and the orphan was added a long time ago
-///    or the height is high
+/// 2) the pool exceeds ORPHAN_POOL_LIMITS and the orphan was added a long time ago
+///    or its height is far from the head
+/// or
+/// 3) orphans sent by its peer exceed the per peer limits of ORPHAN_POOL_LIMITS and its
+///    height is the farthest from the head among them
 pub struct OrphanBlockPool {
     /// A map from block hash to a orphan block
     orphans: HashMap<CryptoHash, Orphan>,
@@ -189,6 +230,8 @@ pub struct OrphanBlockPool {
     prev_hash_idx: HashMap<CryptoHash, Vec<CryptoHash>>,
     /// number of orphans that were evicted
     evicted: usize,
+    /// Number and size of the orphans, in total and per peer which sent them
+    usage: PoolUsage,
 }
 
 impl OrphanBlockPool {
@@ -199,6 +242,7 @@ impl OrphanBlockPool {
             height_idx: HashMap::default(),
             prev_hash_idx: HashMap::default(),
             evicted: 0,
+            usage: PoolUsage::default(),
         }
     }
 
@@ -210,52 +254,97 @@ impl OrphanBlockPool {
         self.evicted
     }
 
+    /// Approximate size in bytes of the orphan blocks, based on their
+    /// serialized size.
+    fn approximate_size(&self) -> u64 {
+        self.usage.bytes()
+    }
+
     /// Add a block to the orphan pool
     /// `requested_missing_chunks`: whether missing chunks has been requested for the orphan
-    fn add(&mut self, orphan: Orphan, requested_missing_chunks: bool) {
+    /// `head_height`: height of the head, orphans farthest from it are evicted first when the
+    ///                pool exceeds its limits, which may include the added orphan
+    fn add(&mut self, orphan: Orphan, requested_missing_chunks: bool, head_height: BlockHeight) {
         let block_hash = *orphan.block.hash();
+        if self.orphans.contains_key(&block_hash) {
+            return;
+        }
+        let peer_id = orphan.peer_id.clone();
         let height_hashes = self.height_idx.entry(orphan.block.header().height()).or_default();
         height_hashes.push(*orphan.block.hash());
         let prev_hash_entries =
             self.prev_hash_idx.entry(*orphan.block.header().prev_hash()).or_default();
         prev_hash_entries.push(block_hash);
+        self.usage.add(&orphan);
         self.orphans.insert(block_hash, orphan);
         if requested_missing_chunks {
             self.orphans_requested_missing_chunks.insert(block_hash);
         }
 
-        if self.orphans.len() > MAX_ORPHAN_SIZE {
-            let old_len = self.orphans.len();
-
-            let mut removed_hashes: HashSet<CryptoHash> = HashSet::default();
-            self.orphans.retain(|_, ref mut x| {
-                let keep = x.added.elapsed() < TimeDuration::from_secs(MAX_ORPHAN_AGE_SECS);
-                if !keep {
-                    removed_hashes.insert(*x.block.hash());
-                }
-                keep
-            });
-            let mut heights = self.height_idx.keys().cloned().collect::<Vec<u64>>();
-            heights.sort_unstable();
-            for h in heights.iter().rev() {
-                if let Some(hash) = self.height_idx.remove(h) {
-                    for h in hash {
-                        let _ = self.orphans.remove(&h);
-                        removed_hashes.insert(h);
-                    }
-                }
-                if self.orphans.len() < MAX_ORPHAN_SIZE {
-                    break;
+        if let Some(peer_id) = peer_id {
+            while self.usage.peer_exceeds(&peer_id, &ORPHAN_POOL_LIMITS) {
+                let orphans_of_peer = self
+                    .orphans
+                    .values()
+                    .filter(|orphan| orphan.peer_id.as_ref() == Some(&peer_id));
+                match block_to_evict(orphans_of_peer, head_height) {
+                    Some(hash) => self.evict(&hash, "peer_quota"),
+                    None => break,
                 }
             }
-            self.height_idx.retain(|_, ref mut xs| xs.iter().any(|x| !removed_hashes.contains(x)));
-            self.prev_hash_idx
-                .retain(|_, ref mut xs| xs.iter().any(|x| !removed_hashes.contains(x)));
-            self.orphans_requested_missing_chunks.retain(|x| !removed_hashes.contains(x));
-
-            self.evicted += old_len - self.orphans.len();
+        }
+        if self.usage.exceeds(&ORPHAN_POOL_LIMITS) {
+            let expired_hashes: Vec<CryptoHash> = self
+                .orphans
+                .values()
+                .filter(|orphan| {
+                    orphan.added.elapsed() >= TimeDuration::from_secs(MAX_ORPHAN_AGE_SECS)
+                })
+                .map(|orphan| *orphan.block.hash())
+                .collect();
+            for hash in expired_hashes {
+                self.evict(&hash, "expired");
+            }
+        }
+        while self.usage.exceeds(&ORPHAN_POOL_LIMITS) {
+            match block_to_evict(self.orphans.values(), head_height) {
+                Some(hash) => self.evict(&hash, "pool_full"),
+                None => break,
+            }
         }
         metrics::NUM_ORPHANS.set(self.orphans.len() as i64);
+        metrics::BLOCK_POOL_SIZE_BYTES
+            .with_label_values(&["orphans"])
+            .set(self.usage.bytes() as i64);
+    }
+
+    fn evict(&mut self, hash: &CryptoHash, reason: &str) {
+        if self.remove(hash).is_some() {
+            self.evicted += 1;
+            metrics::BLOCK_POOL_EVICTIONS.with_label_values(&["orphans", reason]).inc();
+        }
+    }
+
+    /// Removes the orphan from the pool and all its indices.
+    fn remove(&mut self, hash: &CryptoHash) -> Option<Orphan> {
+        let orphan = self.orphans.remove(hash)?;
+        self.usage.remove(&orphan);
+        self.orphans_requested_missing_chunks.remove(hash);
+        let height = orphan.block.header().height();
+        if let Some(hashes) = self.height_idx.get_mut(&height) {
+            hashes.retain(|h| h != hash);
+            if hashes.is_empty() {
+                self.height_idx.remove(&height);
+            }
+        }
+        let prev_hash = orphan.prev_hash();
+        if let Some(hashes) = self.prev_hash_idx.get_mut(prev_hash) {
+            hashes.retain(|h| h != hash);
+            if hashes.is_empty() {
+                self.prev_hash_idx.remove(prev_hash);
+            }
+        }
+        Some(orphan)
     }
 
     pub fn contains(&self, hash: &CryptoHash) -> bool {
@@ -279,20 +368,15 @@ impl OrphanBlockPool {
     /// This function is called when `prev_hash` is accepted, thus its children can be removed
     /// from the orphan pool and be processed.
     pub fn remove_by_prev_hash(&mut self, prev_hash: CryptoHash) -> Option<Vec<Orphan>> {
-        let mut removed_hashes: HashSet<CryptoHash> = HashSet::default();
-        let ret = self.prev_hash_idx.remove(&prev_hash).map(|hs| {
-            hs.iter()
-                .filter_map(|h| {
-                    removed_hashes.insert(*h);
-                    self.orphans_requested_missing_chunks.remove(h);
-                    self.orphans.remove(h)
-                })
-                .collect()
-        });
-
-        self.height_idx.retain(|_, ref mut xs| xs.iter().any(|x| !removed_hashes.contains(x)));
+        let ret = self
+            .prev_hash_idx
+            .remove(&prev_hash)
+            .map(|hs| hs.iter().filter_map(|h| self.remove(h)).collect());
 
         metrics::NUM_ORPHANS.set(self.orphans.len() as i64);
+        metrics::BLOCK_POOL_SIZE_BYTES
+            .with_label_values(&["orphans"])
+            .set(self.usage.bytes() as i64);
         ret
     }
 
@@ -470,6 +554,18 @@ pub struct Chain {
     /// Used to store state parts already requested along with elapsed time
     /// to create the parts. This information is used for debugging
     pub(crate) requested_state_parts: StateRequestTracker,
+
+    /// New chunks whose application takes longer than this are logged.
+    slow_chunk_threshold: Option<TimeDuration>,
+
+    /// Thread pool applying chunks.  If not set, chunks are applied in the
+    /// global rayon thread pool.
+    apply_chunks_thread_pool: Option<rayon::ThreadPool>,
+
+    /// Bounds the size of the state parts generated by
+    /// `get_state_response_part`.  If not set, they are only removed by
+    /// garbage collection.
+    state_parts_cache: Option<Arc<StatePartsCache>>,
 }
 
 impl Drop for Chain {
@@ -506,6 +602,12 @@ impl Chain {
         ))
     }
 
+    /// Bounds the size of the state parts generated for serving state sync by
+    /// this chain with `cache`, which may be shared with other chains.
+    pub fn set_state_parts_cache(&mut self, cache: Arc<StatePartsCache>) {
+        self.state_parts_cache = Some(cache);
+    }
+
     pub fn new_for_view_client(
         runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
         chain_genesis: &ChainGenesis,
@@ -534,6 +636,9 @@ impl Chain {
             invalid_blocks: LruCache::new(INVALID_CHUNKS_POOL_SIZE),
             pending_state_patch: Default::default(),
             requested_state_parts: StateRequestTracker::new(),
+            slow_chunk_threshold: None,
+            apply_chunks_thread_pool: None,
+            state_parts_cache: None,
         })
     }
 
@@ -547,6 +652,7 @@ impl Chain {
         let (store, state_roots) = runtime_adapter.genesis_state();
         let mut store =
             ChainStore::new(store, chain_genesis.height, chain_config.save_trie_changes);
+        store.set_save_account_outcome_ids(chain_config.save_account_outcome_ids);
         let genesis_chunks = genesis_chunks(
             state_roots.clone(),
             runtime_adapter.num_shards(&EpochId::default())?,
@@ -662,6 +768,19 @@ impl Chain {
         metrics::CHUNK_TAIL_HEIGHT.set(store.chunk_tail()? as i64);
         metrics::FORK_TAIL_HEIGHT.set(store.fork_tail()? as i64);
 
+        let apply_chunks_thread_pool = match chain_config.apply_chunks_threads {
+            Some(num_threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .thread_name(|i| format!("apply_chunks_{}", i))
+                    .build()
+                    .map_err(|err| {
+                        Error::Other(format!("Failed to create apply chunks thread pool: {}", err))
+                    })?,
+            ),
+            None => None,
+        };
+
         // Even though the channel is unbounded, the channel size is practically bounded by the size
         // of blocks_in_processing, which is set to 5 now.
         let (sc, rc) = unbounded();
@@ -683,6 +802,9 @@ impl Chain {
             last_time_head_updated: Clock::instant(),
             pending_state_patch: Default::default(),
             requested_state_parts: StateRequestTracker::new(),
+            slow_chunk_threshold: chain_config.slow_chunk_threshold,
+            apply_chunks_thread_pool,
+            state_parts_cache: None,
         })
     }
 
@@ -779,9 +901,11 @@ impl Chain {
             byzantine_assert!(false);
             return Err(e);
         }
+        let head_height = self.head()?.height;
         self.orphans.add(
-            Orphan { block, provenance: Provenance::NONE, added: Clock::instant() },
+            Orphan::new(block, Provenance::NONE, None, Clock::instant()),
             requested_missing_chunks,
+            head_height,
         );
         Ok(())
     }
@@ -951,6 +1075,72 @@ impl Chain {
         Ok(())
     }
 
+    /// Deletes the state and the flat storage of the shards of the head's
+    /// epoch which `me` cares about neither in this epoch nor in the next
+    /// one.  Used by chunk-only producers in minimal mode, which sync the
+    /// state of such a shard again if they get assigned to it later.
+    ///
+    /// Returns the ids of the shards whose state was deleted.
+    pub fn clear_untracked_shards_state(
+        &self,
+        me: &Option<AccountId>,
+    ) -> Result<Vec<ShardId>, Error> {
+        let head = self.head()?;
+        let shard_layout = self.runtime_adapter.get_shard_layout(&head.epoch_id)?;
+        // Going through the tries drops the deleted nodes from the shard
+        // caches on commit.
+        let mut store_update = StoreUpdate::new_with_tries(self.runtime_adapter.get_tries());
+        let mut cleared = vec![];
+        for shard_uid in shard_layout.get_shard_uids() {
+            let shard_id = shard_uid.shard_id();
+            if self.runtime_adapter.cares_about_shard(
+                me.as_ref(),
+                &head.last_block_hash,
+                shard_id,
+                true,
+            ) || self.runtime_adapter.will_care_about_shard(
+                me.as_ref(),
+                &head.last_block_hash,
+                shard_id,
+                true,
+            ) {
+                continue;
+            }
+            self.runtime_adapter.remove_flat_storage_state_for_shard(shard_id, &head.epoch_id)?;
+            // The keys of the shard are prefixed with its uid, delete everything
+            // up to the next possible prefix.
+            let from = shard_uid.to_bytes();
+            let mut to = from.to_vec();
+            while let Some(byte) = to.pop() {
+                if byte < u8::MAX {
+                    to.push(byte + 1);
+                    break;
+                }
+            }
+            store_update.delete_range(DBCol::State, &from, &to);
+            cleared.push(shard_uid);
+        }
+        // Garbage collecting a block applies its trie changes to the refcounts
+        // of the nodes deleted above.  Those of the nodes synced later, if the
+        // shard gets tracked again, would end up wrong.  The keys are suffixed
+        // with the shard uid, so they can't be deleted by range, but a single
+        // pass covers all the cleared shards.
+        if !cleared.is_empty() {
+            let suffixes: HashSet<Vec<u8>> =
+                cleared.iter().map(|shard_uid| shard_uid.to_bytes().to_vec()).collect();
+            for item in self.store.store().iter(DBCol::TrieChanges) {
+                let (key, _) = item?;
+                let suffix = key.len().checked_sub(8).map(|at| &key[at..]);
+                if suffix.map_or(false, |suffix| suffixes.contains(suffix)) {
+                    store_update.delete(DBCol::TrieChanges, &key);
+                }
+            }
+        }
+        store_update.commit()?;
+        let cleared = cleared.iter().map(ShardUId::shard_id).collect();
+        Ok(cleared)
+    }
+
     /// Garbage collect data which archival node doesn’t need to keep.
     ///
     /// Normally, archival nodes keep all the data from the genesis block and
@@ -959,8 +1149,16 @@ impl Chain {
     /// columns can be recomputed from data in different columns.  To save on
     /// storage, archival nodes do garbage collect that data.
     ///
-    /// `gc_height_limit` limits how many heights will the function process.
-    pub fn clear_archive_data(&mut self, gc_height_limit: BlockHeightDelta) -> Result<(), Error> {
+    /// The data `archival_policy` doesn't keep forever is garbage collected
+    /// too, once it's older than the number of epochs it's kept for.
+    ///
+    /// `gc_config.gc_blocks_limit` limits how many heights will the function
+    /// process.
+    pub fn clear_archive_data(
+        &mut self,
+        gc_config: &near_chain_configs::GCConfig,
+        archival_policy: &near_chain_configs::ArchivalPolicy,
+    ) -> Result<(), Error> {
         let _d = DelayDetector::new(|| "GC".into());
 
         let head = self.store.head()?;
@@ -970,12 +1168,72 @@ impl Chain {
         }
 
         let mut chain_store_update = self.store.store_update();
-        chain_store_update.clear_redundant_chunk_data(gc_stop_height, gc_height_limit)?;
+        chain_store_update.clear_redundant_chunk_data(gc_stop_height, gc_config.gc_blocks_limit)?;
         metrics::CHUNK_TAIL_HEIGHT.set(chain_store_update.chunk_tail()? as i64);
         metrics::GC_STOP_HEIGHT.set(gc_stop_height as i64);
+        chain_store_update.commit()?;
+
+        if archival_policy.keeps_everything() {
+            return Ok(());
+        }
+        let mut stop_heights = vec![];
+        for (kind, retention) in [
+            (ArchivalDataKind::Blocks, archival_policy.blocks),
+            (ArchivalDataKind::Chunks, archival_policy.chunks),
+            (ArchivalDataKind::StateChanges, archival_policy.state_changes),
+            (ArchivalDataKind::Outcomes, archival_policy.outcomes),
+        ] {
+            if let near_chain_configs::Retention::Epochs(num_epochs) = retention {
+                // The node needs the data of the latest epochs to process
+                // blocks, regardless of the policy.
+                let num_epochs = std::cmp::max(num_epochs, gc_config.gc_num_epochs_to_keep());
+                let stop_height =
+                    self.get_archival_gc_stop_height(&head.last_block_hash, num_epochs)?;
+                stop_heights.push((kind, std::cmp::min(stop_height, gc_stop_height)));
+            }
+        }
+
+        let mut tails = self.store.get_archival_tails()?;
+        let mut chain_store_update = self.store.store_update();
+        for (kind, stop_height) in stop_heights {
+            let tail = match kind {
+                ArchivalDataKind::Blocks => &mut tails.blocks,
+                ArchivalDataKind::Chunks => &mut tails.chunks,
+                ArchivalDataKind::StateChanges => &mut tails.state_changes,
+                ArchivalDataKind::Outcomes => &mut tails.outcomes,
+            };
+            *tail = chain_store_update.clear_archival_data(
+                kind,
+                *tail,
+                stop_height,
+                gc_config.gc_blocks_limit,
+            )?;
+        }
+        chain_store_update.save_archival_tails(&tails)?;
         chain_store_update.commit()
     }
 
+    /// Returns the height of the last block before the latest `num_epochs`
+    /// epochs, up to the epoch of `block_hash`.  Like `clear_data`, archival
+    /// garbage collection keeps that block.
+    fn get_archival_gc_stop_height(
+        &self,
+        block_hash: &CryptoHash,
+        num_epochs: u64,
+    ) -> Result<BlockHeight, Error> {
+        let genesis_height = self.genesis.header().height();
+        let mut block_hash = *block_hash;
+        for _ in 0..num_epochs {
+            let start_height = self.runtime_adapter.get_epoch_start_height(&block_hash)?;
+            if start_height <= genesis_height {
+                return Ok(genesis_height);
+            }
+            let epoch_first_block = self.store.get_block_hash_by_height(start_height)?;
+            block_hash = *self.get_block_header(&epoch_first_block)?.prev_hash();
+        }
+        Ok(self.get_block_header(&block_hash)?.height())
+    }
+
     pub fn clear_forks_data(
         &mut self,
         tries: ShardTries,
@@ -1618,6 +1876,9 @@ impl Chain {
     /// This function is used in conjunction with the function postprocess_ready_blocks, which checks
     /// if any of the blocks in processing has finished applying chunks to finish postprocessing
     /// these blocks that are ready.
+    /// `peer_id`: The peer which sent the block, unless it was produced or requested by the node.
+    ///              Blocks which end up in the orphan or missing chunks pools count towards
+    ///              the peer's limits there.
     /// `block_processing_artifacts`: Callers can pass an empty object or an existing BlockProcessingArtifact.
     ///              This function will add the effect from processing this block to there.
     /// `apply_chunks_done_callback`: This callback will be called after apply_chunks are finished
@@ -1629,6 +1890,7 @@ impl Chain {
         me: &Option<AccountId>,
         block: MaybeValidated<Block>,
         provenance: Provenance,
+        peer_id: Option<PeerId>,
         block_processing_artifacts: &mut BlockProcessingArtifact,
         apply_chunks_done_callback: DoneApplyChunkCallback,
     ) -> Result<(), Error> {
@@ -1641,6 +1903,7 @@ impl Chain {
             me,
             block,
             provenance,
+            peer_id,
             block_processing_artifacts,
             apply_chunks_done_callback,
             block_received_time,
@@ -1921,6 +2184,7 @@ impl Chain {
         me: &Option<AccountId>,
         block: MaybeValidated<Block>,
         provenance: Provenance,
+        peer_id: Option<PeerId>,
         block_processing_artifact: &mut BlockProcessingArtifact,
         apply_chunks_done_callback: DoneApplyChunkCallback,
         block_received_time: Instant,
@@ -1975,8 +2239,9 @@ impl Chain {
 
                             let time = Clock::instant();
                             self.blocks_delay_tracker.mark_block_orphaned(block.hash(), time);
-                            let orphan = Orphan { block, provenance, added: time };
-                            self.orphans.add(orphan, requested_missing_chunks);
+                            let head_height = self.head()?.height;
+                            let orphan = Orphan::new(block, provenance, peer_id, time);
+                            self.orphans.add(orphan, requested_missing_chunks, head_height);
 
                             debug!(
                                 target: "chain",
@@ -2001,9 +2266,13 @@ impl Chain {
                         });
                         let time = Clock::instant();
                         self.blocks_delay_tracker.mark_block_has_missing_chunks(block.hash(), time);
-                        let orphan = Orphan { block, provenance, added: time };
-                        self.blocks_with_missing_chunks
-                            .add_block_with_missing_chunks(orphan, missing_chunk_hashes.clone());
+                        let head_height = self.head()?.height;
+                        let orphan = Orphan::new(block, provenance, peer_id, time);
+                        self.blocks_with_missing_chunks.add_block_with_missing_chunks(
+                            orphan,
+                            missing_chunk_hashes.clone(),
+                            head_height,
+                        );
                         debug!(
                             target: "chain",
                             "Process block: missing chunks. Block hash: {:?}. Missing chunks: {:?}",
@@ -2033,6 +2302,7 @@ impl Chain {
         let block_hash = *block.hash();
         let block_height = block.header().height();
         let apply_chunks_done_marker = block_preprocess_info.apply_chunks_done.clone();
+        let span = block_preprocess_info.span.clone();
         self.blocks_in_processing.add(block, block_preprocess_info)?;
 
         // 2) schedule apply chunks, which will be executed in the rayon thread pool.
@@ -2042,6 +2312,7 @@ impl Chain {
             apply_chunk_work,
             apply_chunks_done_marker,
             apply_chunks_done_callback.clone(),
+            span,
         );
 
         Ok(())
@@ -2050,6 +2321,7 @@ impl Chain {
     /// Applying chunks async by starting the work at the rayon thread pool
     /// `apply_chunks_done_marker`: a marker that will be set to true once applying chunks is finished
     /// `apply_chunks_done_callback`: a callback that will be called once applying chunks is finished
+    /// `span`: the span of the block processing, which becomes the parent of the work
     fn schedule_apply_chunks(
         &self,
         block_hash: CryptoHash,
@@ -2057,11 +2329,12 @@ impl Chain {
         work: Vec<Box<dyn FnOnce(&Span) -> Result<ApplyChunkResult, Error> + Send>>,
         apply_chunks_done_marker: Arc<OnceCell<()>>,
         apply_chunks_done_callback: DoneApplyChunkCallback,
+        span: Span,
     ) {
         let sc = self.apply_chunks_sender.clone();
-        spawn(move || {
+        spawn(self.apply_chunks_thread_pool.as_ref(), move || {
             // do_apply_chunks runs `work` parallelly, but still waits for all of them to finish
-            let res = do_apply_chunks(block_hash, block_height, work);
+            let res = span.in_scope(|| do_apply_chunks(block_hash, block_height, work));
             // If we encounter error here, that means the receiver is deallocated and the client
             // thread is already shut down. The node is already crashed, so we can unwrap here
             sc.send((block_hash.clone(), res)).unwrap();
@@ -2069,14 +2342,19 @@ impl Chain {
                 // This should never happen, if it does, it means there is a bug in our code.
                 log_assert!(false, "apply chunks are called twice for block {block_hash:?}");
             }
-            apply_chunks_done_callback(block_hash);
+            // The callback notifies the client actor, which continues the trace of the block.
+            span.in_scope(|| apply_chunks_done_callback(block_hash));
         });
 
         /// `rayon::spawn` decorated to propagate `tracing` context across
-        /// threads.
-        fn spawn(f: impl FnOnce() + Send + 'static) {
+        /// threads.  The parallel work spawned by `f` runs in the same pool.
+        fn spawn(pool: Option<&rayon::ThreadPool>, f: impl FnOnce() + Send + 'static) {
             let dispatcher = tracing::dispatcher::get_default(|it| it.clone());
-            rayon::spawn(move || tracing::dispatcher::with_default(&dispatcher, f))
+            let f = move || tracing::dispatcher::with_default(&dispatcher, f);
+            match pool {
+                Some(pool) => pool.spawn(f),
+                None => rayon::spawn(f),
+            }
         }
     }
 
@@ -2160,9 +2438,10 @@ impl Chain {
                 block_hash
             ));
         // We want to include block height here, so we didn't put this line at the beginning of the
-        // function.
+        // function.  The span continues the trace in which the processing of the block started.
         let _span = tracing::debug_span!(
             target: "chain",
+            parent: &block_preprocess_info.span,
             "postprocess_block",
             height = block.header().height())
         .entered();
@@ -2467,6 +2746,7 @@ impl Chain {
                 provenance: provenance.clone(),
                 apply_chunks_done: Arc::new(OnceCell::new()),
                 block_start_processing_time: block_received_time,
+                span: Span::current(),
             },
         ))
     }
@@ -2605,6 +2885,7 @@ impl Chain {
                 me,
                 block.block,
                 block.provenance,
+                block.peer_id,
                 block_processing_artifact,
                 apply_chunks_done_callback.clone(),
             );
@@ -2659,6 +2940,7 @@ impl Chain {
                     me,
                     orphan.block,
                     orphan.provenance,
+                    orphan.peer_id,
                     block_processing_artifacts,
                     apply_chunks_done_callback.clone(),
                 );
@@ -2889,8 +3171,13 @@ impl Chain {
         // Check cache
         let key = StatePartKey(sync_hash, shard_id, part_id).try_to_vec()?;
         if let Ok(Some(state_part)) = self.store.store().get(DBCol::StateParts, &key) {
+            metrics::STATE_PARTS_CACHE_REQUESTS.with_label_values(&["hit"]).inc();
+            if let Some(cache) = &self.state_parts_cache {
+                cache.touch(&key);
+            }
             return Ok(state_part.into());
         }
+        metrics::STATE_PARTS_CACHE_REQUESTS.with_label_values(&["miss"]).inc();
 
         let sync_block = self
             .get_block(&sync_hash)
@@ -2941,11 +3228,73 @@ impl Chain {
         // Saving the part data
         let mut store_update = self.store.store().store_update();
         store_update.set(DBCol::StateParts, &key, &state_part);
+        if let Some(cache) = &self.state_parts_cache {
+            for evicted in cache.insert(key, state_part.len() as u64) {
+                store_update.delete(DBCol::StateParts, &evicted);
+            }
+        }
         store_update.commit()?;
 
         Ok(state_part)
     }
 
+    /// Returns the trie nodes and values which the chunks of given shard
+    /// added to its state after the block `from_hash` to get the state for
+    /// syncing to `sync_hash`.  `from_hash` must be the last block of the
+    /// epoch before the epoch of `sync_hash`'s previous block, or that
+    /// previous block itself.
+    ///
+    /// The delta is built from the saved trie changes, so it can only be
+    /// served by nodes which tracked the shard and haven't garbage collected
+    /// the blocks yet.
+    pub fn get_state_response_delta(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        from_hash: CryptoHash,
+    ) -> Result<ShardStateDelta, Error> {
+        let _span = tracing::debug_span!(
+            target: "sync",
+            "get_state_response_delta",
+            shard_id,
+            %sync_hash,
+            %from_hash)
+        .entered();
+        let sync_prev_header =
+            self.get_block_header(self.get_block_header(&sync_hash)?.prev_hash())?;
+        let epoch_id = sync_prev_header.epoch_id().clone();
+        let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &epoch_id)?;
+
+        let mut changes = vec![];
+        let mut size = 0;
+        let mut header = sync_prev_header;
+        while *header.hash() != from_hash {
+            if header.epoch_id() != &epoch_id {
+                return Err(Error::InvalidStateRequest(
+                    "from_hash is not the end of the previous epoch".into(),
+                ));
+            }
+            let trie_changes: TrieChanges = self
+                .store
+                .store()
+                .get_ser(DBCol::TrieChanges, &get_block_shard_uid(header.hash(), &shard_uid))?
+                .ok_or_else(|| {
+                    Error::InvalidStateRequest(format!(
+                        "trie changes of block {} are not available",
+                        header.hash()
+                    ))
+                })?;
+            size += trie_changes.insertions().iter().map(|i| i.payload().len() as u64).sum::<u64>();
+            if size > MAX_STATE_DELTA_SIZE {
+                return Err(Error::InvalidStateRequest("state delta is too large".into()));
+            }
+            changes.push(trie_changes);
+            header = self.get_block_header(header.prev_hash())?;
+        }
+        changes.reverse();
+        Ok(TrieChanges::state_delta(&changes))
+    }
+
     pub fn set_state_header(
         &mut self,
         shard_id: ShardId,
@@ -3132,6 +3481,92 @@ impl Chain {
         Ok(())
     }
 
+    /// Returns the block at the end of an epoch for which the node has the
+    /// state of given shard, such that it can sync the shard to `sync_hash`
+    /// by downloading a state delta from it instead of state parts.  The end
+    /// of the previous epoch is checked first, then the end of the epoch
+    /// before it.
+    pub fn get_state_delta_base(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+    ) -> Result<Option<CryptoHash>, Error> {
+        // Flat storage is filled from state parts, a delta only updates the trie.
+        if cfg!(feature = "protocol_feature_flat_state") {
+            return Ok(None);
+        }
+        let prev_hash = *self.get_block_header(&sync_hash)?.prev_hash();
+        let epoch_id = self.get_block_header(&prev_hash)?.epoch_id().clone();
+        let shard_layout = self.runtime_adapter.get_shard_layout(&epoch_id)?;
+        let epoch_start_height = self.runtime_adapter.get_epoch_start_height(&prev_hash)?;
+        let epoch_start_header =
+            self.get_block_header_on_chain_by_height(&prev_hash, epoch_start_height)?;
+        let tries = self.runtime_adapter.get_tries();
+        for base_hash in [prev_hash, *epoch_start_header.prev_hash()] {
+            let base_epoch_id = match self.get_block_header(&base_hash) {
+                Ok(header) => header.epoch_id().clone(),
+                Err(_) => continue,
+            };
+            if self.runtime_adapter.get_shard_layout(&base_epoch_id)? != shard_layout {
+                continue;
+            }
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &base_epoch_id)?;
+            let state_root = match self.get_chunk_extra(&base_hash, &shard_uid) {
+                Ok(chunk_extra) => *chunk_extra.state_root(),
+                Err(_) => continue,
+            };
+            if tries.get_trie_for_shard(shard_uid, state_root).retrieve_root_node().is_ok() {
+                return Ok(Some(base_hash));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Adds the nodes of a downloaded state delta to the state of given
+    /// shard at `base_hash`, as returned by `get_state_delta_base`.  The state
+    /// header must have been set before.
+    ///
+    /// The trie changes are saved for the previous block of `sync_hash`, so
+    /// that the references of the base state which the new state no longer
+    /// has are removed once that block is garbage collected.
+    pub fn set_state_delta(
+        &mut self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        base_hash: CryptoHash,
+        delta: &ShardStateDelta,
+    ) -> Result<(), Error> {
+        let shard_state_header = self.get_state_header(shard_id, sync_hash)?;
+        let state_root = shard_state_header.chunk_prev_state_root();
+        let sync_block_header = self.get_block_header(&sync_hash)?;
+        let epoch_id = sync_block_header.epoch_id().clone();
+        let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &epoch_id)?;
+        let base_state_root = *self.get_chunk_extra(&base_hash, &shard_uid)?.state_root();
+
+        let tries = self.runtime_adapter.get_tries();
+        let trie_changes =
+            match tries.apply_state_delta(shard_uid, &base_state_root, &state_root, delta) {
+                Ok(trie_changes) => trie_changes,
+                Err(err) => {
+                    byzantine_assert!(false);
+                    return Err(Error::Other(format!("set_state_delta failed: {}", err)));
+                }
+            };
+        let mut store_update = tries.store_update();
+        tries.apply_insertions(&trie_changes, shard_uid, &mut store_update);
+        let key = get_block_shard_uid(sync_block_header.prev_hash(), &shard_uid);
+        // If the base is the previous block, its own trie changes are already
+        // there and the delta is empty.
+        if !self.store.store().exists(DBCol::TrieChanges, &key)? {
+            store_update.set_ser(DBCol::TrieChanges, &key, &trie_changes)?;
+        }
+        store_update.commit()?;
+
+        // Like before applying state parts, remove existing flat storage data.
+        self.runtime_adapter.remove_flat_storage_state_for_shard(shard_id, &epoch_id)?;
+        Ok(())
+    }
+
     pub fn schedule_apply_state_parts(
         &self,
         shard_id: ShardId,
@@ -3704,6 +4139,7 @@ impl Chain {
         let will_shard_layout_change =
             self.runtime_adapter.will_shard_layout_change_next_epoch(prev_hash)?;
         let prev_chunk_headers = Chain::get_prev_chunk_headers(&*self.runtime_adapter, prev_block)?;
+        let shard_layout = self.runtime_adapter.get_shard_layout(block.header().epoch_id())?;
         let mut process_one_chunk = |shard_id: usize,
                                      chunk_header: &ShardChunkHeader,
                                      prev_chunk_header: &ShardChunkHeader|
@@ -3711,11 +4147,12 @@ impl Chain {
             Option<Box<dyn FnOnce(&Span) -> Result<ApplyChunkResult, Error> + Send + 'static>>,
             Error,
         > {
-            // XXX: This is a bit questionable -- sandbox state patching works
-            // only for a single shard. This so far has been enough.
-            let state_patch = state_patch.take();
-
             let shard_id = shard_id as ShardId;
+            // Sandbox state patching: every shard gets the changes of the
+            // accounts which belong to it.
+            let state_patch = state_patch.take_accounts(|account_id| {
+                account_id_to_shard_id(account_id, &shard_layout) == shard_id
+            });
             let cares_about_shard_this_epoch =
                 self.runtime_adapter.cares_about_shard(me.as_ref(), prev_hash, shard_id, true);
             let cares_about_shard_next_epoch =
@@ -3867,6 +4304,7 @@ impl Chain {
                     let random_seed = *block.header().random_value();
                     let height = chunk_header.height_included();
                     let prev_block_hash = chunk_header.prev_block_hash().clone();
+                    let slow_chunk_threshold = self.slow_chunk_threshold;
 
                     Ok(Some(Box::new(move |parent_span| -> Result<ApplyChunkResult, Error> {
                         let _span = tracing::debug_span!(
@@ -3876,6 +4314,7 @@ impl Chain {
                             shard_id)
                         .entered();
                         let _timer = CryptoHashTimer::new(chunk.chunk_hash().0);
+                        let started = Clock::instant();
                         match runtime_adapter.apply_transactions(
                             shard_id,
                             chunk_inner.prev_state_root(),
@@ -3896,6 +4335,14 @@ impl Chain {
                             cares_about_shard_this_epoch,
                         ) {
                             Ok(apply_result) => {
+                                observe_new_chunk_application(
+                                    &chunk,
+                                    &receipts,
+                                    gas_limit,
+                                    &apply_result,
+                                    started.elapsed(),
+                                    slow_chunk_threshold,
+                                );
                                 let apply_split_result_or_state_changes =
                                     if will_shard_layout_change {
                                         Some(ChainUpdate::apply_split_state_changes(
@@ -4451,6 +4898,11 @@ impl Chain {
         self.orphans.len_evicted()
     }
 
+    /// Returns approximate size in bytes of the blocks in the orphan pool.
+    pub fn orphans_approximate_size(&self) -> u64 {
+        self.orphans.approximate_size()
+    }
+
     /// Check if hash is for a known orphan.
     #[inline]
     pub fn is_orphan(&self, hash: &CryptoHash) -> bool {
@@ -4543,6 +4995,24 @@ impl Chain {
         Ok(headers)
     }
 
+    /// Returns the headers of the canonical chain at the given heights, skipping the heights
+    /// without blocks.  At most `max_headers_returned` heights are looked up.
+    pub fn retrieve_headers_at_heights(
+        &self,
+        heights: &[BlockHeight],
+        max_headers_returned: u64,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        let mut headers = vec![];
+        for height in heights.iter().take(max_headers_returned as usize) {
+            match self.get_block_header_by_height(*height) {
+                Ok(header) => headers.push(header),
+                Err(Error::DBNotFoundErr(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(headers)
+    }
+
     /// Returns a vector of chunk headers, each of which corresponds to the previous chunk of
     /// a chunk in the block after `prev_block`
     /// This function is important when the block after `prev_block` has different number of chunks
@@ -4984,6 +5454,7 @@ impl<'a> ChainUpdate<'a> {
                 // Save receipt and transaction results.
                 self.chain_store_update.save_outcomes_with_proofs(
                     &block_hash,
+                    height,
                     shard_id,
                     apply_result.outcomes,
                     outcome_paths,
@@ -5236,6 +5707,19 @@ impl<'a> ChainUpdate<'a> {
         let head = self.chain_store_update.head()?;
         if header.height() > head.height {
             let tip = Tip::from_header(header);
+            if header.prev_hash() != &head.last_block_hash {
+                // Failing to describe the reorg must not stop the head update.
+                match self.reorg_event(&head, header) {
+                    Ok(event) => {
+                        info!(target: "chain", old_head = %event.old_head_hash, new_head = %event.new_head_hash, depth = event.depth, common_ancestor_height = event.common_ancestor_height, "Head reorg");
+                        metrics::REORG_DEPTH.observe(event.depth as f64);
+                        self.chain_store_update.save_reorg_event(event);
+                    }
+                    Err(err) => {
+                        warn!(target: "chain", ?err, "Failed to find common ancestor of reorg")
+                    }
+                }
+            }
 
             self.chain_store_update.save_body_head(&tip)?;
             metrics::BLOCK_HEIGHT_HEAD.set(tip.height as i64);
@@ -5247,6 +5731,37 @@ impl<'a> ChainUpdate<'a> {
         }
     }
 
+    /// Describes the switch of the head from `head` to `header`, which is on
+    /// another fork.
+    fn reorg_event(&self, head: &Tip, header: &BlockHeader) -> Result<ReorgEvent, Error> {
+        // Walks both forks back until they meet.  The height index can't be
+        // used for this since it follows the header head, which may have
+        // switched to the new fork already.
+        let old_head = self.chain_store_update.get_block_header(&head.last_block_hash)?;
+        let mut old_block = old_head.clone();
+        let mut new_block = self.chain_store_update.get_block_header(header.prev_hash())?;
+        let mut depth = 0;
+        while old_block.hash() != new_block.hash() {
+            if old_block.height() >= new_block.height() {
+                old_block = self.chain_store_update.get_block_header(old_block.prev_hash())?;
+                depth += 1;
+            } else {
+                new_block = self.chain_store_update.get_block_header(new_block.prev_hash())?;
+            }
+        }
+        Ok(ReorgEvent {
+            old_head_hash: *old_head.hash(),
+            old_head_height: old_head.height(),
+            old_head_timestamp: old_head.raw_timestamp(),
+            new_head_hash: *header.hash(),
+            new_head_height: header.height(),
+            new_head_timestamp: header.raw_timestamp(),
+            common_ancestor_height: old_block.height(),
+            depth,
+            seen: to_timestamp(Clock::utc()),
+        })
+    }
+
     /// Marks a block as invalid,
     fn mark_block_as_challenged(
         &mut self,
@@ -5407,6 +5922,7 @@ impl<'a> ChainUpdate<'a> {
         // Saving transaction results.
         self.chain_store_update.save_outcomes_with_proofs(
             block_header.hash(),
+            block_header.height(),
             shard_id,
             apply_result.outcomes,
             outcome_proofs,
@@ -5483,6 +5999,10 @@ impl<'a> ChainUpdate<'a> {
     }
 }
 
+/// Applies the chunks in parallel in the current rayon thread pool.  The
+/// results are in the order of `work` regardless of the order in which the
+/// chunks finish applying, so the changes of the shards are committed to the
+/// store in the same order on every node.
 pub fn do_apply_chunks(
     block_hash: CryptoHash,
     block_height: BlockHeight,
@@ -5500,6 +6020,59 @@ pub fn do_apply_chunks(
         .collect::<Vec<_>>()
 }
 
+/// Exports metrics of an applied new chunk and logs it if its application
+/// took longer than `slow_chunk_threshold`.
+fn observe_new_chunk_application(
+    chunk: &ShardChunk,
+    receipts: &[Receipt],
+    gas_limit: Gas,
+    apply_result: &ApplyTransactionResult,
+    elapsed: TimeDuration,
+    slow_chunk_threshold: Option<TimeDuration>,
+) {
+    let shard_id = chunk.shard_id();
+    let shard_label = shard_id.to_string();
+    if gas_limit > 0 {
+        metrics::CHUNK_GAS_USED_RATIO
+            .with_label_values(&[&shard_label])
+            .observe(apply_result.total_gas_burnt as f64 / gas_limit as f64);
+    }
+    // Every transaction and every processed receipt (incoming, delayed or
+    // local) has an outcome.
+    let num_receipts = apply_result.outcomes.len().saturating_sub(chunk.transactions().len());
+    metrics::CHUNK_RECEIPTS_PROCESSED
+        .with_label_values(&[&shard_label])
+        .observe(num_receipts as f64);
+
+    if slow_chunk_threshold.map_or(true, |threshold| elapsed <= threshold) {
+        return;
+    }
+    let is_function_call =
+        |actions: &[Action]| actions.iter().any(|action| matches!(action, Action::FunctionCall(_)));
+    let mut contracts = BTreeSet::new();
+    for transaction in chunk.transactions() {
+        if is_function_call(&transaction.transaction.actions) {
+            contracts.insert(&transaction.transaction.receiver_id);
+        }
+    }
+    for receipt in receipts.iter().chain(&apply_result.processed_delayed_receipts) {
+        if let ReceiptEnum::Action(action_receipt) = &receipt.receipt {
+            if is_function_call(&action_receipt.actions) {
+                contracts.insert(&receipt.receiver_id);
+            }
+        }
+    }
+    warn!(
+        target: "chain",
+        shard_id,
+        chunk_hash = ?chunk.chunk_hash(),
+        ?elapsed,
+        gas_burnt = apply_result.total_gas_burnt,
+        num_receipts,
+        ?contracts,
+        "Slow chunk application");
+}
+
 pub fn collect_receipts<'a, T>(receipt_proofs: T) -> Vec<Receipt>
 where
     T: IntoIterator<Item = &'a ReceiptProof>,
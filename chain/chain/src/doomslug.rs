@@ -5,8 +5,8 @@ use std::time::{Duration, Instant};
 use near_client_primitives::debug::{ApprovalAtHeightStatus, ApprovalHistoryEntry};
 use near_crypto::Signature;
 use near_primitives::block::{Approval, ApprovalInner};
+use near_primitives::clock;
 use near_primitives::hash::CryptoHash;
-use near_primitives::time::Clock;
 use near_primitives::types::{AccountId, ApprovalStake, Balance, BlockHeight, BlockHeightDelta};
 use near_primitives::validator_signer::ValidatorSigner;
 use tracing::info;
@@ -118,6 +118,7 @@ pub struct Doomslug {
     /// Approvals that were created by this doomslug instance (for debugging only).
     /// Keeps up to MAX_HISTORY_SIZE entries.
     history: VecDeque<ApprovalHistoryEntry>,
+    clock: clock::Clock,
 }
 
 impl DoomslugTimer {
@@ -161,6 +162,7 @@ impl DoomslugApprovalsTracker {
     ///
     /// # Arguments
     /// * now      - the current timestamp
+    /// * now_utc  - the current wall clock time, recorded as the arrival time of the approval
     /// * approval - the approval to process
     ///
     /// # Returns
@@ -168,12 +170,13 @@ impl DoomslugApprovalsTracker {
     fn process_approval(
         &mut self,
         now: Instant,
+        now_utc: chrono::DateTime<chrono::Utc>,
         approval: &Approval,
     ) -> DoomslugBlockProductionReadiness {
         let mut increment_approved_stake = false;
         self.witness.entry(approval.account_id.clone()).or_insert_with(|| {
             increment_approved_stake = true;
-            (approval.clone(), chrono::Utc::now())
+            (approval.clone(), now_utc)
         });
 
         if increment_approved_stake {
@@ -247,6 +250,7 @@ impl DoomslugApprovalsTrackersAtHeight {
     ///
     /// # Arguments
     /// * `now`      - the current timestamp
+    /// * `now_utc`  - the current wall clock time
     /// * `approval` - the approval to be processed
     /// * `stakes`   - all the stakes of all the block producers in the current epoch
     /// * `threshold_mode` - how many approvals are needed to produce a block. Is used to compute
@@ -257,6 +261,7 @@ impl DoomslugApprovalsTrackersAtHeight {
     fn process_approval(
         &mut self,
         now: Instant,
+        now_utc: chrono::DateTime<chrono::Utc>,
         approval: &Approval,
         stakes: &[(ApprovalStake, bool)],
         threshold_mode: DoomslugThresholdMode,
@@ -297,12 +302,12 @@ impl DoomslugApprovalsTrackersAtHeight {
         self.approval_trackers
             .entry(approval.inner.clone())
             .or_insert_with(|| DoomslugApprovalsTracker::new(account_id_to_stakes, threshold_mode))
-            .process_approval(now, approval)
+            .process_approval(now, now_utc, approval)
     }
 
     /// Returns the current approvals status for the trackers at this height.
     /// Status contains information about which account voted (and for what) and whether the doomslug voting threshold was reached.
-    pub fn status(&self) -> ApprovalAtHeightStatus {
+    pub fn status(&self, clock: &clock::Clock) -> ApprovalAtHeightStatus {
         let approvals = self
             .approval_trackers
             .iter()
@@ -320,8 +325,9 @@ impl DoomslugApprovalsTrackersAtHeight {
             .filter_map(|(_, tracker)| tracker.time_passed_threshold)
             .min()
             .map(|ts| {
-                chrono::Utc::now()
-                    - chrono::Duration::from_std(ts.elapsed()).unwrap_or(chrono::Duration::days(1))
+                clock.now_utc_chrono()
+                    - chrono::Duration::from_std(clock.now_std().saturating_duration_since(ts))
+                        .unwrap_or(chrono::Duration::days(1))
             });
         ApprovalAtHeightStatus { approvals, ready_at: threshold_approval }
     }
@@ -329,6 +335,7 @@ impl DoomslugApprovalsTrackersAtHeight {
 
 impl Doomslug {
     pub fn new(
+        clock: clock::Clock,
         largest_target_height: BlockHeight,
         endorsement_delay: Duration,
         min_delay: Duration,
@@ -346,8 +353,8 @@ impl Doomslug {
             tip: DoomslugTip { block_hash: CryptoHash::default(), height: 0 },
             endorsement_pending: false,
             timer: DoomslugTimer {
-                started: Clock::instant(),
-                last_endorsement_sent: Clock::instant(),
+                started: clock.now_std(),
+                last_endorsement_sent: clock.now_std(),
                 height: 0,
                 endorsement_delay,
                 min_delay,
@@ -357,6 +364,7 @@ impl Doomslug {
             signer,
             threshold_mode,
             history: VecDeque::new(),
+            clock,
         }
     }
 
@@ -452,13 +460,11 @@ impl Doomslug {
                     self.update_history(ApprovalHistoryEntry {
                         parent_height: tip_height,
                         target_height: tip_height + 1,
-                        timer_started_ago_millis: self
-                            .timer
-                            .last_endorsement_sent
-                            .elapsed()
+                        timer_started_ago_millis: cur_time
+                            .saturating_duration_since(self.timer.last_endorsement_sent)
                             .as_millis() as u64,
                         expected_delay_millis: self.timer.endorsement_delay.as_millis() as u64,
-                        approval_creation_time: chrono::Utc::now(),
+                        approval_creation_time: self.clock.now_utc_chrono(),
                     });
                 }
 
@@ -478,9 +484,11 @@ impl Doomslug {
                 self.update_history(ApprovalHistoryEntry {
                     parent_height: tip_height,
                     target_height: self.timer.height + 1,
-                    timer_started_ago_millis: self.timer.started.elapsed().as_millis() as u64,
+                    timer_started_ago_millis: cur_time
+                        .saturating_duration_since(self.timer.started)
+                        .as_millis() as u64,
                     expected_delay_millis: skip_delay.as_millis() as u64,
-                    approval_creation_time: chrono::Utc::now(),
+                    approval_creation_time: self.clock.now_utc_chrono(),
                 });
 
                 // Restart the timer
@@ -602,7 +610,7 @@ impl Doomslug {
             .approval_tracking
             .entry(approval.target_height)
             .or_insert_with(|| DoomslugApprovalsTrackersAtHeight::new())
-            .process_approval(now, approval, stakes, threshold_mode);
+            .process_approval(now, self.clock.now_utc_chrono(), approval, stakes, threshold_mode);
 
         if approval.target_height > self.largest_approval_height {
             self.largest_approval_height = approval.target_height;
@@ -637,7 +645,7 @@ impl Doomslug {
     /// It will only work for heights that we have in memory, that is that are not older than MAX_HEIGHTS_BEFORE_TO_STORE_APPROVALS
     /// blocks from the head.
     pub fn approval_status_at_height(&self, height: &BlockHeight) -> ApprovalAtHeightStatus {
-        self.approval_tracking.get(height).map(|it| it.status()).unwrap_or_default()
+        self.approval_tracking.get(height).map(|it| it.status(&self.clock)).unwrap_or_default()
     }
 
     /// Returns whether we can produce a block for this height. The check for whether `me` is the
@@ -712,6 +720,7 @@ mod tests {
 
     use near_crypto::{KeyType, SecretKey};
     use near_primitives::block::{Approval, ApprovalInner};
+    use near_primitives::clock;
     use near_primitives::hash::hash;
     use near_primitives::test_utils::create_test_signer;
     use near_primitives::time::Clock;
@@ -725,6 +734,7 @@ mod tests {
     #[test]
     fn test_endorsements_and_skips_basic() {
         let mut ds = Doomslug::new(
+            clock::Clock::real(),
             0,
             Duration::from_millis(400),
             Duration::from_millis(1000),
@@ -870,7 +880,9 @@ mod tests {
             .collect::<Vec<_>>();
 
         let signer = Arc::new(create_test_signer("test"));
+        let fake_clock = clock::FakeClock::default();
         let mut ds = Doomslug::new(
+            fake_clock.clock(),
             0,
             Duration::from_millis(400),
             Duration::from_millis(1000),
@@ -946,6 +958,12 @@ mod tests {
             DoomslugBlockProductionReadiness::ReadySince(now),
         );
 
+        // The arrival times of the approvals are read from the injected clock.
+        let witness = ds.get_witness(&hash(&[1]), 1, 4);
+        assert_eq!(witness.len(), 4);
+        let approval_time = fake_clock.clock().now_utc_chrono();
+        assert!(witness.values().all(|(_, time)| *time == approval_time));
+
         // "test4", 2 -> 3
         assert_eq!(
             ds.on_approval_message_internal(
@@ -981,6 +999,7 @@ mod tests {
 
     #[test]
     fn test_doomslug_one_approval_per_target_height() {
+        let clock = clock::Clock::real();
         let accounts = vec![("test1", 2, 0), ("test2", 1, 2), ("test3", 3, 3), ("test4", 2, 2)];
         let signers = accounts
             .iter()
@@ -1009,6 +1028,7 @@ mod tests {
         // Process first approval, and then process it again and make sure it works
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a1_1,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
@@ -1034,6 +1054,7 @@ mod tests {
 
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a1_1,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
@@ -1060,12 +1081,14 @@ mod tests {
         // Process the remaining two approvals on the first block
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a1_2,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
         );
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a1_3,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
@@ -1092,6 +1115,7 @@ mod tests {
         // Process new approvals one by one, expect the approved and endorsed stake to slowly decrease
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a2_1,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
@@ -1117,6 +1141,7 @@ mod tests {
 
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a2_2,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
@@ -1143,6 +1168,7 @@ mod tests {
         // As we update the last of the three approvals, the tracker for the first block should be completely removed
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a2_3,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
@@ -1173,6 +1199,7 @@ mod tests {
 
         tracker.process_approval(
             Clock::instant(),
+            clock.now_utc_chrono(),
             &a2_3,
             &stakes,
             DoomslugThresholdMode::TwoThirds,
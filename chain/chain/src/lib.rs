@@ -21,6 +21,7 @@ mod lightclient;
 mod metrics;
 pub mod migrations;
 pub mod missing_chunks;
+pub mod state_parts_cache;
 mod state_request_tracker;
 mod store;
 pub mod store_validator;
@@ -1,6 +1,8 @@
 use near_o11y::metrics::{
-    exponential_buckets, try_create_histogram, try_create_histogram_vec, try_create_int_counter,
-    try_create_int_gauge, Histogram, HistogramVec, IntCounter, IntGauge,
+    exponential_buckets, linear_buckets, try_create_histogram, try_create_histogram_vec,
+    try_create_histogram_with_buckets, try_create_int_counter, try_create_int_counter_vec,
+    try_create_int_gauge, try_create_int_gauge_vec, Histogram, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec,
 };
 use once_cell::sync::Lazy;
 
@@ -28,6 +30,24 @@ pub static APPLYING_CHUNKS_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static CHUNK_GAS_USED_RATIO: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_chunk_gas_used_ratio",
+        "Ratio of gas burnt to the gas limit of applied new chunks per shard",
+        &["shard_id"],
+        Some(linear_buckets(0.05, 0.05, 20).unwrap()),
+    )
+    .unwrap()
+});
+pub static CHUNK_RECEIPTS_PROCESSED: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_chunk_receipts_processed",
+        "Number of receipts processed when applying new chunks per shard",
+        &["shard_id"],
+        Some(exponential_buckets(1.0, 2.0, 14).unwrap()),
+    )
+    .unwrap()
+});
 pub static BLOCK_PREPROCESSING_TIME: Lazy<Histogram> = Lazy::new(|| {
     try_create_histogram("near_block_preprocessing_time", "Time taken to preprocess blocks, only include the time when the preprocessing is successful")
         .unwrap()
@@ -60,6 +80,22 @@ pub static VALIDATOR_ACTIVE_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
 });
 pub static NUM_ORPHANS: Lazy<IntGauge> =
     Lazy::new(|| try_create_int_gauge("near_num_orphans", "Number of orphan blocks.").unwrap());
+pub static BLOCK_POOL_SIZE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_block_pool_size_bytes",
+        "Approximate size of the blocks in the orphan and missing chunks pools",
+        &["pool"],
+    )
+    .unwrap()
+});
+pub static BLOCK_POOL_EVICTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_block_pool_evictions_total",
+        "Number of blocks evicted from the orphan and missing chunks pools, by reason",
+        &["pool", "reason"],
+    )
+    .unwrap()
+});
 pub static HEADER_HEAD_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_header_head_height", "Height of the header head").unwrap()
 });
@@ -87,6 +123,14 @@ pub static CHUNK_RECEIVED_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static REORG_DEPTH: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_reorg_depth",
+        "Number of blocks of the old fork dropped from the canonical chain on reorgs of the head",
+        vec![1.0, 2.0, 3.0, 4.0, 5.0, 10.0, 20.0, 50.0],
+    )
+    .unwrap()
+});
 pub static BLOCK_ORPHANED_DELAY: Lazy<Histogram> = Lazy::new(|| {
     try_create_histogram("near_block_orphaned_delay", "How long blocks stay in the orphan pool")
         .unwrap()
@@ -107,6 +151,28 @@ pub static STATE_PART_ELAPSED: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static STATE_PARTS_CACHE_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_state_parts_cache_requests_total",
+        "Number of state parts requested by peers, by whether they were already stored",
+        &["result"],
+    )
+    .unwrap()
+});
+pub static STATE_PARTS_CACHE_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_state_parts_cache_size_bytes",
+        "Estimated total size of the state parts generated for serving state sync",
+    )
+    .unwrap()
+});
+pub static STATE_PARTS_CACHE_EVICTED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_state_parts_cache_evicted_total",
+        "Number of generated state parts deleted to keep their total size within the limit",
+    )
+    .unwrap()
+});
 pub static NUM_INVALID_BLOCKS: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_num_invalid_blocks", "Number of invalid blocks").unwrap()
 });
@@ -1,4 +1,6 @@
+use crate::metrics;
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::BlockHeight;
 use std::cmp::Ordering;
@@ -13,9 +15,93 @@ type BlockHash = CryptoHash;
 
 const MAX_BLOCKS_MISSING_CHUNKS: usize = 1024;
 
+const MISSING_CHUNKS_POOL_LIMITS: PoolLimits = PoolLimits {
+    max_blocks: MAX_BLOCKS_MISSING_CHUNKS,
+    max_bytes: 128 * 1024 * 1024,
+    max_blocks_per_peer: MAX_BLOCKS_MISSING_CHUNKS / 4,
+    max_bytes_per_peer: 32 * 1024 * 1024,
+};
+
 pub trait BlockLike {
     fn hash(&self) -> BlockHash;
     fn height(&self) -> BlockHeight;
+    /// Approximate size of the block in bytes.
+    fn size(&self) -> u64;
+    /// Peer which sent the block, unless the node produced or requested it.
+    fn peer_id(&self) -> Option<&PeerId>;
+}
+
+/// Limits on the blocks held by a pool of blocks.  Blocks sent by a single
+/// peer may only take a part of the pool, so that a peer feeding it bogus
+/// blocks can't push out the blocks sent by others.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PoolLimits {
+    pub max_blocks: usize,
+    pub max_bytes: u64,
+    pub max_blocks_per_peer: usize,
+    pub max_bytes_per_peer: u64,
+}
+
+/// Number and size of the blocks in a pool, in total and for every peer which
+/// sent some of them.
+#[derive(Debug, Default)]
+pub(crate) struct PoolUsage {
+    blocks: usize,
+    bytes: u64,
+    per_peer: HashMap<PeerId, (usize, u64)>,
+}
+
+impl PoolUsage {
+    pub fn add(&mut self, block: &impl BlockLike) {
+        self.blocks += 1;
+        self.bytes += block.size();
+        if let Some(peer_id) = block.peer_id() {
+            let (blocks, bytes) = self.per_peer.entry(peer_id.clone()).or_default();
+            *blocks += 1;
+            *bytes += block.size();
+        }
+    }
+
+    pub fn remove(&mut self, block: &impl BlockLike) {
+        self.blocks -= 1;
+        self.bytes -= block.size();
+        if let Some(peer_id) = block.peer_id() {
+            if let hash_map::Entry::Occupied(mut entry) = self.per_peer.entry(peer_id.clone()) {
+                let (blocks, bytes) = entry.get_mut();
+                *blocks -= 1;
+                *bytes -= block.size();
+                if *blocks == 0 {
+                    entry.remove_entry();
+                }
+            }
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn exceeds(&self, limits: &PoolLimits) -> bool {
+        self.blocks > limits.max_blocks || self.bytes > limits.max_bytes
+    }
+
+    pub fn peer_exceeds(&self, peer_id: &PeerId, limits: &PoolLimits) -> bool {
+        self.per_peer.get(peer_id).map_or(false, |(blocks, bytes)| {
+            *blocks > limits.max_blocks_per_peer || *bytes > limits.max_bytes_per_peer
+        })
+    }
+}
+
+/// Returns the block which should be evicted first from a pool over its
+/// limits: the one farthest from the head, as it's the least likely to be
+/// processed soon, and the highest one among equally far ones.
+pub(crate) fn block_to_evict<'a, Block: BlockLike + 'a>(
+    blocks: impl Iterator<Item = &'a Block>,
+    head_height: BlockHeight,
+) -> Option<BlockHash> {
+    blocks
+        .max_by_key(|block| (block.height().abs_diff(head_height), block.height(), block.hash()))
+        .map(|block| block.hash())
 }
 
 #[derive(Debug)]
@@ -49,6 +135,8 @@ pub struct MissingChunksPool<Block: BlockLike> {
     blocks_waiting_for_chunks: HashMap<BlockHash, Block>,
     blocks_ready_to_process: BinaryHeap<HeightOrdered<Block>>,
     height_idx: BTreeMap<BlockHeight, HashSet<BlockHash>>,
+    /// Usage of the blocks waiting for chunks.
+    usage: PoolUsage,
 }
 
 impl<Block: BlockLike> MissingChunksPool<Block> {
@@ -59,6 +147,7 @@ impl<Block: BlockLike> MissingChunksPool<Block> {
             blocks_waiting_for_chunks: Default::default(),
             blocks_ready_to_process: BinaryHeap::new(),
             height_idx: Default::default(),
+            usage: Default::default(),
         }
     }
 
@@ -82,19 +171,17 @@ impl<Block: BlockLike> MissingChunksPool<Block> {
         heap.into_sorted_vec().into_iter().map(|x| x.0).collect()
     }
 
-    pub fn add_block_with_missing_chunks(&mut self, block: Block, missing_chunks: Vec<ChunkHash>) {
+    /// Adds a block waiting for the missing chunks.  If the pool gets over
+    /// its limits, the blocks farthest from `head_height` are evicted, which
+    /// may include the added block.
+    pub fn add_block_with_missing_chunks(
+        &mut self,
+        block: Block,
+        missing_chunks: Vec<ChunkHash>,
+        head_height: BlockHeight,
+    ) {
         let block_hash = block.hash();
-        // This case can only happen when missing chunks are not being eventually received and
-        // thus removing blocks from the HashMap. It means the this node has severely stalled out.
-        // It is ok to ignore further blocks because either (a) we will start receiving chunks
-        // again, work through the backlog of the pool, then naturally sync the later blocks
-        // which were not added initially, or (b) someone will restart the node because something
-        // has gone horribly wrong, in which case these HashMaps will be lost anyways.
-        if self.blocks_missing_chunks.len() >= MAX_BLOCKS_MISSING_CHUNKS {
-            warn!(target: "chunks", "Not recording block with hash {} even though it is missing chunks. The missing chunks pool is full.", block_hash);
-            return;
-        }
-
+        let peer_id = block.peer_id().cloned();
         for chunk_hash in missing_chunks.iter().cloned() {
             let blocks_for_chunk =
                 self.missing_chunks.entry(chunk_hash).or_insert_with(HashSet::new);
@@ -120,7 +207,71 @@ impl<Block: BlockLike> MissingChunksPool<Block> {
         let height = block.height();
         let blocks_at_height = self.height_idx.entry(height).or_insert_with(HashSet::new);
         blocks_at_height.insert(block_hash);
-        self.blocks_waiting_for_chunks.insert(block_hash, block);
+        self.usage.add(&block);
+        if let Some(previous_block) = self.blocks_waiting_for_chunks.insert(block_hash, block) {
+            self.usage.remove(&previous_block);
+        }
+
+        // The pool can only get full when missing chunks are not being eventually received. It
+        // means that this node has severely stalled out, or that peers are feeding it blocks
+        // whose chunks it can't get. It is ok to drop blocks because either (a) we will start
+        // receiving chunks again, work through the backlog of the pool, then naturally sync the
+        // blocks which were dropped, or (b) someone will restart the node because something has
+        // gone horribly wrong, in which case the pool will be lost anyways.
+        if let Some(peer_id) = peer_id {
+            while self.usage.peer_exceeds(&peer_id, &MISSING_CHUNKS_POOL_LIMITS) {
+                let blocks_of_peer = self
+                    .blocks_waiting_for_chunks
+                    .values()
+                    .filter(|block| block.peer_id() == Some(&peer_id));
+                match block_to_evict(blocks_of_peer, head_height) {
+                    Some(block_hash) => self.evict_block(&block_hash, "peer_quota"),
+                    None => break,
+                }
+            }
+        }
+        while self.usage.exceeds(&MISSING_CHUNKS_POOL_LIMITS) {
+            match block_to_evict(self.blocks_waiting_for_chunks.values(), head_height) {
+                Some(block_hash) => self.evict_block(&block_hash, "pool_full"),
+                None => break,
+            }
+        }
+        metrics::BLOCK_POOL_SIZE_BYTES
+            .with_label_values(&["missing_chunks"])
+            .set(self.usage.bytes() as i64);
+    }
+
+    fn evict_block(&mut self, block_hash: &BlockHash, reason: &str) {
+        if let Some(block) = self.remove_block(block_hash) {
+            debug!(target: "chunks", %block_hash, height = block.height(), reason, "Evicted block with missing chunks from the pool.");
+            metrics::BLOCK_POOL_EVICTIONS.with_label_values(&["missing_chunks", reason]).inc();
+        }
+    }
+
+    /// Removes a block waiting for chunks from the pool.
+    fn remove_block(&mut self, block_hash: &BlockHash) -> Option<Block> {
+        let block = self.blocks_waiting_for_chunks.remove(block_hash)?;
+        self.usage.remove(&block);
+        if let btree_map::Entry::Occupied(mut entry) = self.height_idx.entry(block.height()) {
+            let blocks_at_height = entry.get_mut();
+            blocks_at_height.remove(block_hash);
+            if blocks_at_height.is_empty() {
+                entry.remove_entry();
+            }
+        }
+        if let Some(chunk_hashes) = self.blocks_missing_chunks.remove(block_hash) {
+            for chunk_hash in chunk_hashes {
+                if let hash_map::Entry::Occupied(mut entry) = self.missing_chunks.entry(chunk_hash)
+                {
+                    let blocks_for_chunk = entry.get_mut();
+                    blocks_for_chunk.remove(block_hash);
+                    if blocks_for_chunk.is_empty() {
+                        entry.remove_entry();
+                    }
+                }
+            }
+        }
+        Some(block)
     }
 
     pub fn accept_chunk(&mut self, chunk_hash: &ChunkHash) {
@@ -149,49 +300,38 @@ impl<Block: BlockLike> MissingChunksPool<Block> {
     }
 
     fn mark_block_as_ready(&mut self, block_hash: &BlockHash) {
-        if let Some(block) = self.blocks_waiting_for_chunks.remove(block_hash) {
-            let height = block.height();
-            if let btree_map::Entry::Occupied(mut entry) = self.height_idx.entry(height) {
-                let blocks_at_height = entry.get_mut();
-                blocks_at_height.remove(block_hash);
-                if blocks_at_height.is_empty() {
-                    entry.remove_entry();
-                }
-            }
+        if let Some(block) = self.remove_block(block_hash) {
             self.blocks_ready_to_process.push(HeightOrdered(block));
         }
+        metrics::BLOCK_POOL_SIZE_BYTES
+            .with_label_values(&["missing_chunks"])
+            .set(self.usage.bytes() as i64);
     }
 
     pub fn prune_blocks_below_height(&mut self, height: BlockHeight) {
         let heights_to_remove: Vec<BlockHeight> =
             self.height_idx.keys().copied().take_while(|h| *h < height).collect();
         for h in heights_to_remove {
-            if let Some(block_hashes) = self.height_idx.remove(&h) {
+            if let Some(block_hashes) = self.height_idx.get(&h).cloned() {
                 for block_hash in block_hashes {
-                    self.blocks_waiting_for_chunks.remove(&block_hash);
-                    if let Some(chunk_hashes) = self.blocks_missing_chunks.remove(&block_hash) {
-                        for chunk_hash in chunk_hashes {
-                            if let hash_map::Entry::Occupied(mut entry) =
-                                self.missing_chunks.entry(chunk_hash)
-                            {
-                                let blocks_for_chunk = entry.get_mut();
-                                blocks_for_chunk.remove(&block_hash);
-                                if blocks_for_chunk.is_empty() {
-                                    entry.remove_entry();
-                                }
-                            }
-                        }
-                    }
+                    self.remove_block(&block_hash);
                 }
             }
         }
+        metrics::BLOCK_POOL_SIZE_BYTES
+            .with_label_values(&["missing_chunks"])
+            .set(self.usage.bytes() as i64);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{BlockHash, BlockLike, MissingChunksPool, MAX_BLOCKS_MISSING_CHUNKS};
+    use super::{
+        BlockHash, BlockLike, MissingChunksPool, MAX_BLOCKS_MISSING_CHUNKS,
+        MISSING_CHUNKS_POOL_LIMITS,
+    };
     use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::network::PeerId;
     use near_primitives::sharding::ChunkHash;
     use near_primitives::types::BlockHeight;
 
@@ -203,14 +343,19 @@ mod test {
         ChunkHash(get_hash(idx))
     }
 
-    #[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
     struct MockBlock {
         hash: BlockHash,
         height: BlockHeight,
+        peer_id: Option<PeerId>,
     }
     impl MockBlock {
         fn new(height: BlockHeight) -> Self {
-            Self { hash: get_hash(height), height }
+            Self { hash: get_hash(height), height, peer_id: None }
+        }
+
+        fn from_peer(height: BlockHeight, peer_id: &PeerId) -> Self {
+            Self { peer_id: Some(peer_id.clone()), ..Self::new(height) }
         }
     }
     impl BlockLike for MockBlock {
@@ -221,6 +366,14 @@ mod test {
         fn height(&self) -> u64 {
             self.height
         }
+
+        fn size(&self) -> u64 {
+            1000
+        }
+
+        fn peer_id(&self) -> Option<&PeerId> {
+            self.peer_id.as_ref()
+        }
     }
 
     #[test]
@@ -231,7 +384,7 @@ mod test {
         let block = MockBlock::new(block_height);
         let chunk_hashes: Vec<ChunkHash> = (101..105).map(get_chunk_hash).collect();
 
-        pool.add_block_with_missing_chunks(block, chunk_hashes.clone());
+        pool.add_block_with_missing_chunks(block.clone(), chunk_hashes.clone(), 0);
         assert!(pool.contains(&block.hash));
 
         for chunk_hash in chunk_hashes.iter().skip(1) {
@@ -246,7 +399,7 @@ mod test {
     }
 
     #[test]
-    fn should_not_add_new_blocks_after_size_limit() {
+    fn should_evict_blocks_farthest_from_head_after_size_limit() {
         let mut pool: MissingChunksPool<MockBlock> = MissingChunksPool::default();
         let mut chunk_hash_idx = MAX_BLOCKS_MISSING_CHUNKS as BlockHeight;
 
@@ -256,17 +409,50 @@ mod test {
             chunk_hash_idx += 1;
             let missing_chunk_hash = get_chunk_hash(chunk_hash_idx);
             let block_hash = block.hash;
-            pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash]);
+            pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash], 0);
             assert!(pool.contains(&block_hash));
         }
 
+        // The new block is the farthest from the head, so it's evicted right away.
         let block_height = MAX_BLOCKS_MISSING_CHUNKS as BlockHeight;
         let block = MockBlock::new(block_height);
         chunk_hash_idx += 1;
         let missing_chunk_hash = get_chunk_hash(chunk_hash_idx);
         let block_hash = block.hash;
-        pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash]);
+        pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash], 0);
         assert!(!pool.contains(&block_hash));
+        assert_eq!(pool.len(), MAX_BLOCKS_MISSING_CHUNKS);
+
+        // Once the head moves, the lowest block is the farthest one.
+        let block = MockBlock::new(block_height + 1);
+        chunk_hash_idx += 1;
+        let missing_chunk_hash = get_chunk_hash(chunk_hash_idx);
+        let block_hash = block.hash;
+        pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash], block_height);
+        assert!(pool.contains(&block_hash));
+        assert!(!pool.contains(&get_hash(0)));
+        assert_eq!(pool.len(), MAX_BLOCKS_MISSING_CHUNKS);
+    }
+
+    #[test]
+    fn should_limit_blocks_from_single_peer() {
+        let mut pool: MissingChunksPool<MockBlock> = MissingChunksPool::default();
+        let spammer = PeerId::random();
+        let max_blocks_per_peer = MISSING_CHUNKS_POOL_LIMITS.max_blocks_per_peer as BlockHeight;
+
+        let honest_block = MockBlock::from_peer(10, &PeerId::random());
+        let honest_block_hash = honest_block.hash;
+        pool.add_block_with_missing_chunks(honest_block, vec![get_chunk_hash(0)], 0);
+
+        // The spammer sends far-future blocks, only its own blocks get evicted.
+        for block_height in 1000..1000 + 2 * max_blocks_per_peer {
+            let block = MockBlock::from_peer(block_height, &spammer);
+            pool.add_block_with_missing_chunks(block, vec![get_chunk_hash(block_height)], 0);
+        }
+        assert!(pool.contains(&honest_block_hash));
+        assert_eq!(pool.len(), MISSING_CHUNKS_POOL_LIMITS.max_blocks_per_peer + 1);
+        assert!(pool.contains(&get_hash(1000)));
+        assert!(!pool.contains(&get_hash(1000 + max_blocks_per_peer)));
     }
 
     #[test]
@@ -276,16 +462,16 @@ mod test {
         let block = MockBlock::new(0);
         let early_block_hash = block.hash;
         let missing_chunk_hash = get_chunk_hash(100);
-        pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash]);
+        pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash], 0);
 
         let block_height = 1;
         let block = MockBlock::new(block_height);
         let missing_chunk_hash = get_chunk_hash(200);
-        pool.add_block_with_missing_chunks(block, vec![missing_chunk_hash.clone()]);
+        pool.add_block_with_missing_chunks(block.clone(), vec![missing_chunk_hash.clone()], 0);
 
         let later_block = MockBlock::new(block_height + 1);
         let later_block_hash = later_block.hash;
-        pool.add_block_with_missing_chunks(later_block, vec![get_chunk_hash(300)]);
+        pool.add_block_with_missing_chunks(later_block, vec![get_chunk_hash(300)], 0);
 
         pool.accept_chunk(&missing_chunk_hash);
         pool.prune_blocks_below_height(block_height);
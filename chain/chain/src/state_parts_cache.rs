@@ -0,0 +1,84 @@
+//! Size bound of the state parts generated for serving state sync.
+//!
+//! Nodes serving state sync store the parts they generate in
+//! `DBCol::StateParts` so that a part requested by many syncing peers is only
+//! generated once.  Garbage collection only removes them together with their
+//! sync block, by which time the column may hold the state of every shard.
+//! [`StatePartsCache`] tracks the generated parts and evicts the least
+//! recently requested ones once their total size exceeds a limit.  A single
+//! cache is shared by the view client and the view call pool, so that the
+//! limit bounds the parts stored by the node as a whole.
+//!
+//! The cache only knows about parts generated since the node started and
+//! doesn't notice parts removed by garbage collection, so its size is only an
+//! estimate.  Parts stored by a syncing node aren't tracked and never evicted.
+use std::sync::Mutex;
+
+use lru::LruCache;
+
+use crate::metrics;
+
+pub struct StatePartsCache {
+    /// Maximum total size of the tracked parts, in bytes.
+    max_size: u64,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    /// Total size of `parts`.
+    size: u64,
+    /// Size of every tracked part by its `DBCol::StateParts` key.
+    parts: LruCache<Vec<u8>, u64>,
+}
+
+impl StatePartsCache {
+    pub fn new(max_size: u64) -> Self {
+        Self { max_size, inner: Mutex::new(Inner { size: 0, parts: LruCache::unbounded() }) }
+    }
+
+    /// Marks the part with given key as recently requested.
+    pub(crate) fn touch(&self, key: &[u8]) {
+        self.inner.lock().unwrap().parts.get(&key.to_vec());
+    }
+
+    /// Tracks a newly stored part.  Returns the keys of the parts which need
+    /// to be deleted to stay within the limit, which may include the new part
+    /// itself if it's larger than the limit.
+    pub(crate) fn insert(&self, key: Vec<u8>, size: u64) -> Vec<Vec<u8>> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(old_size) = inner.parts.put(key, size) {
+            inner.size -= old_size;
+        }
+        inner.size += size;
+        let mut evicted = Vec::new();
+        while inner.size > self.max_size {
+            match inner.parts.pop_lru() {
+                Some((key, size)) => {
+                    inner.size -= size;
+                    evicted.push(key);
+                }
+                None => break,
+            }
+        }
+        metrics::STATE_PARTS_CACHE_SIZE.set(inner.size as i64);
+        metrics::STATE_PARTS_CACHE_EVICTED_TOTAL.inc_by(evicted.len() as u64);
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_parts_cache_eviction() {
+        let cache = StatePartsCache::new(100);
+        assert!(cache.insert(b"a".to_vec(), 40).is_empty());
+        assert!(cache.insert(b"b".to_vec(), 40).is_empty());
+        cache.touch(b"a");
+        assert_eq!(cache.insert(b"c".to_vec(), 40), vec![b"b".to_vec()]);
+        assert_eq!(cache.insert(b"d".to_vec(), 90), vec![b"a".to_vec(), b"c".to_vec()]);
+        assert_eq!(cache.insert(b"e".to_vec(), 200), vec![b"d".to_vec(), b"e".to_vec()]);
+        assert_eq!(cache.inner.lock().unwrap().size, 0);
+    }
+}
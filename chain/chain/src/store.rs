@@ -28,23 +28,24 @@ use near_primitives::transaction::{
 use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
-    BlockExtra, BlockHeight, BlockHeightDelta, EpochId, NumBlocks, ShardId, StateChanges,
-    StateChangesExt, StateChangesForSplitStates, StateChangesKinds, StateChangesKindsExt,
-    StateChangesRequest,
+    AccountId, BlockExtra, BlockHeight, BlockHeightDelta, EpochId, NumBlocks, ShardId,
+    StateChanges, StateChangesExt, StateChangesForSplitStates, StateChangesKindFilter,
+    StateChangesKinds, StateChangesKindsExt, StateChangesRequest, StoreKey,
 };
 use near_primitives::utils::{
-    get_block_shard_id, get_outcome_id_block_hash, get_outcome_id_block_hash_rev, index_to_bytes,
-    to_timestamp,
+    get_account_outcome_ids_key, get_account_outcome_ids_prefix, get_block_shard_id,
+    get_outcome_id_block_hash, get_outcome_id_block_hash_rev, index_to_bytes, to_timestamp,
 };
 use near_primitives::views::LightClientBlockView;
 use near_store::{
-    DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate, WrappedTrieChanges, CHUNK_TAIL_KEY,
-    FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
-    LATEST_KNOWN_KEY, TAIL_KEY,
+    DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate, WrappedTrieChanges,
+    ACCOUNT_OUTCOME_IDS_KEY, ARCHIVAL_TAILS_KEY, CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY,
+    HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, REORG_LOG_KEY,
+    TAIL_KEY,
 };
 
 use crate::chunks_store::ReadOnlyChunksStore;
-use crate::types::{Block, BlockHeader, LatestKnown};
+use crate::types::{ArchivalDataKind, ArchivalTails, Block, BlockHeader, LatestKnown, ReorgEvent};
 use crate::{byzantine_assert, RuntimeWithEpochManagerAdapter};
 use near_store::db::StoreStatistics;
 use near_store::flat_state::{BlockInfo, ChainAccessForFlatStorage};
@@ -61,6 +62,9 @@ const CACHE_SIZE: usize = 1;
 #[cfg(feature = "no_cache")]
 const CHUNK_CACHE_SIZE: usize = 1;
 
+/// Number of most recent reorgs kept in the reorg log.
+const REORG_LOG_SIZE: usize = 1000;
+
 #[derive(Clone)]
 pub enum GCMode {
     Fork(ShardTries),
@@ -310,6 +314,17 @@ pub trait ChainStoreAccess {
     }
 }
 
+/// Progress of backfilling `DBCol::AccountOutcomeIds`, saved under
+/// `ACCOUNT_OUTCOME_IDS_KEY` in `DBCol::BlockMisc`.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+struct AccountOutcomeIdsBackfill {
+    /// Height of the next block to index.
+    next_height: BlockHeight,
+    /// Height of the head when the backfill started.  The blocks above it
+    /// have been indexed as they were processed.
+    end_height: BlockHeight,
+}
+
 /// All chain-related database operations.
 pub struct ChainStore {
     store: Store,
@@ -366,6 +381,8 @@ pub struct ChainStore {
     /// - archive is true, cold_store is configured and migration to split_storage is finished - node
     /// working in split storage mode needs trie changes in order to do garbage collection on hot.
     save_trie_changes: bool,
+    /// Whether to maintain the `DBCol::AccountOutcomeIds` index of outcomes by executor account.
+    save_account_outcome_ids: bool,
 }
 
 fn option_to_not_found<T, F>(res: io::Result<Option<T>>, field_name: F) -> Result<T, Error>
@@ -408,9 +425,28 @@ impl ChainStore {
             block_ordinal_to_hash: CellLruCache::new(CACHE_SIZE),
             processed_block_heights: CellLruCache::new(CACHE_SIZE),
             save_trie_changes,
+            save_account_outcome_ids: false,
         }
     }
 
+    /// Enables maintaining the index of outcome ids by executor account in
+    /// `DBCol::AccountOutcomeIds`.
+    pub fn set_save_account_outcome_ids(&mut self, save_account_outcome_ids: bool) {
+        self.save_account_outcome_ids = save_account_outcome_ids;
+    }
+
+    /// Drops the cached data which depends on the canonical chain, for a
+    /// store whose chain is updated through another `ChainStore`.
+    pub fn reset_canonical_chain_caches(&mut self) {
+        self.latest_known = once_cell::unsync::OnceCell::new();
+        self.head = None;
+        self.tail = None;
+        self.height = CellLruCache::new(CACHE_SIZE);
+        self.block_hash_per_height = CellLruCache::new(CACHE_SIZE);
+        self.next_block_hashes = CellLruCache::new(CACHE_SIZE);
+        self.block_refcounts = CellLruCache::new(CACHE_SIZE);
+    }
+
     pub fn new_read_only_chunks_store(&self) -> ReadOnlyChunksStore {
         ReadOnlyChunksStore::new(self.store.clone())
     }
@@ -620,6 +656,135 @@ impl ChainStore {
             .unwrap_or_default())
     }
 
+    /// Returns ids of the outcomes executed by `account_id` in blocks at heights
+    /// starting from `from_height`, ordered by height, for at most `limit`
+    /// blocks.  Blocks which are not on the canonical chain are skipped, and so
+    /// are blocks below the tail since those have been garbage collected.
+    ///
+    /// The index is only maintained if `save_account_outcome_ids` is enabled.
+    pub fn get_account_outcome_ids(
+        &self,
+        account_id: &AccountId,
+        from_height: BlockHeight,
+        limit: usize,
+    ) -> Result<Vec<(BlockHeight, CryptoHash, Vec<CryptoHash>)>, Error> {
+        let prefix = get_account_outcome_ids_prefix(account_id);
+        let mut lower_bound = prefix.clone();
+        lower_bound.extend_from_slice(&std::cmp::max(from_height, self.tail()?).to_be_bytes());
+        // The prefix ends with a `,` which cannot appear in account ids, so all
+        // the keys of the account sort before the prefix with `,` replaced by
+        // the next byte.
+        let mut upper_bound = prefix.clone();
+        *upper_bound.last_mut().unwrap() += 1;
+        let mut result = vec![];
+        for item in
+            self.store.iter_range(DBCol::AccountOutcomeIds, Some(&lower_bound), Some(&upper_bound))
+        {
+            let (key, value) = item?;
+            let key = &key[prefix.len()..];
+            let height = BlockHeight::from_be_bytes(key[..8].try_into().unwrap());
+            let block_hash = CryptoHash::try_from(&key[8..])
+                .map_err(|err| Error::Other(format!("Invalid AccountOutcomeIds key: {}", err)))?;
+            if self.get_block_hash_by_height(height).ok() != Some(block_hash) {
+                continue;
+            }
+            result.push((height, block_hash, Vec::<CryptoHash>::try_from_slice(&value)?));
+            if result.len() >= limit {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Indexes the outcomes of at most `max_blocks` canonical blocks in
+    /// `DBCol::AccountOutcomeIds` and returns whether the whole index has been
+    /// backfilled.
+    ///
+    /// Blocks are only indexed as they are processed while
+    /// `save_account_outcome_ids` is enabled, so the blocks saved before that
+    /// need to be backfilled.  The progress is saved along with each batch so
+    /// that the backfill resumes where it stopped after a restart.  While the
+    /// index is disabled, it's cleared along with the progress so that the
+    /// blocks processed in the meantime get backfilled once it's enabled
+    /// again.
+    ///
+    /// Garbage collection may remove a block while it's being backfilled which
+    /// leaves its entries behind, those are below the tail and thus ignored by
+    /// [`Self::get_account_outcome_ids`].
+    pub fn backfill_account_outcome_ids(&mut self, max_blocks: u64) -> Result<bool, Error> {
+        let progress = self
+            .store
+            .get_ser::<AccountOutcomeIdsBackfill>(DBCol::BlockMisc, ACCOUNT_OUTCOME_IDS_KEY)?;
+        if !self.save_account_outcome_ids {
+            let mut store_update = self.store.store_update();
+            store_update.delete_all(DBCol::AccountOutcomeIds);
+            store_update.delete(DBCol::BlockMisc, ACCOUNT_OUTCOME_IDS_KEY);
+            store_update.commit()?;
+            return Ok(true);
+        }
+        let mut progress = match progress {
+            Some(progress) => progress,
+            None => {
+                let progress = AccountOutcomeIdsBackfill {
+                    next_height: self.tail()?,
+                    end_height: self.head()?.height,
+                };
+                tracing::info!(
+                    target: "chain",
+                    tail = progress.next_height,
+                    head = progress.end_height,
+                    "Backfilling the outcomes by account index"
+                );
+                progress
+            }
+        };
+        if progress.next_height > progress.end_height {
+            return Ok(true);
+        }
+        let mut height = std::cmp::max(progress.next_height, self.tail()?);
+        let batch_end = std::cmp::min(progress.end_height, height.saturating_add(max_blocks - 1));
+        let mut account_outcome_ids = HashMap::<Vec<u8>, Vec<CryptoHash>>::new();
+        while height <= batch_end {
+            let block_hash = match self.get_block_hash_by_height(height) {
+                Ok(block_hash) => block_hash,
+                Err(Error::DBNotFoundErr(_)) => {
+                    height += 1;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            for item in self
+                .store
+                .iter_prefix_ser::<Vec<CryptoHash>>(DBCol::OutcomeIds, block_hash.as_ref())
+            {
+                let (_, outcome_ids) = item?;
+                for outcome_id in outcome_ids {
+                    if let Some(outcome) =
+                        self.get_outcome_by_id_and_block_hash(&outcome_id, &block_hash)?
+                    {
+                        account_outcome_ids
+                            .entry(get_account_outcome_ids_key(
+                                &outcome.outcome.executor_id,
+                                height,
+                                &block_hash,
+                            ))
+                            .or_default()
+                            .push(outcome_id);
+                    }
+                }
+            }
+            height += 1;
+        }
+        progress.next_height = height;
+        let mut store_update = self.store.store_update();
+        for (key, outcome_ids) in account_outcome_ids {
+            store_update.set_ser(DBCol::AccountOutcomeIds, &key, &outcome_ids)?;
+        }
+        store_update.set_ser(DBCol::BlockMisc, ACCOUNT_OUTCOME_IDS_KEY, &progress)?;
+        store_update.commit()?;
+        Ok(progress.next_height > progress.end_height)
+    }
+
     /// Returns a hashmap of epoch id -> set of all blocks got for current (height, epoch_id)
     pub fn get_all_block_hashes_by_height(
         &self,
@@ -669,6 +834,20 @@ impl ChainStore {
         }
     }
 
+    /// Returns the most recent reorgs of the head, oldest first.
+    pub fn get_reorg_log(&self) -> Result<Vec<ReorgEvent>, Error> {
+        Ok(self.store.get_ser(DBCol::BlockMisc, REORG_LOG_KEY)?.unwrap_or_default())
+    }
+
+    /// Returns the heights up to which the archival node garbage collected
+    /// the data its archival policy doesn't keep forever.
+    pub fn get_archival_tails(&self) -> Result<ArchivalTails, Error> {
+        Ok(self
+            .store
+            .get_ser(DBCol::BlockMisc, ARCHIVAL_TAILS_KEY)?
+            .unwrap_or_else(|| ArchivalTails::new(self.genesis_height)))
+    }
+
     /// Returns latest known height and time it was seen.
     pub fn get_latest_known(&self) -> Result<LatestKnown, Error> {
         self.latest_known
@@ -764,10 +943,7 @@ impl ChainStore {
             StateChangesRequest::AccountChanges { account_ids } => {
                 let mut changes = StateChanges::new();
                 for account_id in account_ids {
-                    let data_key = TrieKey::Account { account_id: account_id.clone() };
-                    let storage_key = KeyForStateChanges::from_trie_key(block_hash, &data_key);
-                    let changes_per_key = storage_key.find_exact_iter(&self.store);
-                    changes.extend(StateChanges::from_account_changes(changes_per_key)?);
+                    changes.extend(self.get_account_changes(block_hash, account_id)?);
                 }
                 changes
             }
@@ -787,39 +963,99 @@ impl ChainStore {
             StateChangesRequest::AllAccessKeyChanges { account_ids } => {
                 let mut changes = StateChanges::new();
                 for account_id in account_ids {
-                    let data_key = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
-                    let storage_key = KeyForStateChanges::from_raw_key(block_hash, &data_key);
-                    let changes_per_key_prefix = storage_key.find_iter(&self.store);
-                    changes.extend(StateChanges::from_access_key_changes(changes_per_key_prefix)?);
+                    changes.extend(self.get_all_access_key_changes(block_hash, account_id)?);
                 }
                 changes
             }
             StateChangesRequest::ContractCodeChanges { account_ids } => {
                 let mut changes = StateChanges::new();
                 for account_id in account_ids {
-                    let data_key = TrieKey::ContractCode { account_id: account_id.clone() };
-                    let storage_key = KeyForStateChanges::from_trie_key(block_hash, &data_key);
-                    let changes_per_key = storage_key.find_exact_iter(&self.store);
-                    changes.extend(StateChanges::from_contract_code_changes(changes_per_key)?);
+                    changes.extend(self.get_contract_code_changes(block_hash, account_id)?);
                 }
                 changes
             }
             StateChangesRequest::DataChanges { account_ids, key_prefix } => {
                 let mut changes = StateChanges::new();
                 for account_id in account_ids {
-                    let data_key = trie_key_parsers::get_raw_prefix_for_contract_data(
-                        account_id,
-                        key_prefix.as_ref(),
-                    );
-                    let storage_key = KeyForStateChanges::from_raw_key(block_hash, &data_key);
-                    let changes_per_key_prefix = storage_key.find_iter(&self.store);
-                    changes.extend(StateChanges::from_data_changes(changes_per_key_prefix)?);
+                    changes.extend(self.get_data_changes(block_hash, account_id, key_prefix)?);
+                }
+                changes
+            }
+            StateChangesRequest::FilteredChanges { account_ids, kinds, key_prefix } => {
+                // Every kind of changes of an account is stored under its own key prefix, so the
+                // filters only determine which prefixes are iterated.
+                let kinds: &[StateChangesKindFilter] =
+                    if kinds.is_empty() { &StateChangesKindFilter::ALL } else { kinds };
+                let mut changes = StateChanges::new();
+                for account_id in account_ids {
+                    for kind in kinds {
+                        changes.extend(match kind {
+                            StateChangesKindFilter::Account => {
+                                self.get_account_changes(block_hash, account_id)?
+                            }
+                            StateChangesKindFilter::AccessKey => {
+                                self.get_all_access_key_changes(block_hash, account_id)?
+                            }
+                            StateChangesKindFilter::Data => {
+                                self.get_data_changes(block_hash, account_id, key_prefix)?
+                            }
+                            StateChangesKindFilter::ContractCode => {
+                                self.get_contract_code_changes(block_hash, account_id)?
+                            }
+                        });
+                    }
                 }
                 changes
             }
         })
     }
 
+    fn get_account_changes(
+        &self,
+        block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<StateChanges, Error> {
+        let data_key = TrieKey::Account { account_id: account_id.clone() };
+        let storage_key = KeyForStateChanges::from_trie_key(block_hash, &data_key);
+        let changes_per_key = storage_key.find_exact_iter(&self.store);
+        Ok(StateChanges::from_account_changes(changes_per_key)?)
+    }
+
+    fn get_all_access_key_changes(
+        &self,
+        block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<StateChanges, Error> {
+        let data_key = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
+        let storage_key = KeyForStateChanges::from_raw_key(block_hash, &data_key);
+        let changes_per_key_prefix = storage_key.find_iter(&self.store);
+        Ok(StateChanges::from_access_key_changes(changes_per_key_prefix)?)
+    }
+
+    fn get_contract_code_changes(
+        &self,
+        block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<StateChanges, Error> {
+        let data_key = TrieKey::ContractCode { account_id: account_id.clone() };
+        let storage_key = KeyForStateChanges::from_trie_key(block_hash, &data_key);
+        let changes_per_key = storage_key.find_exact_iter(&self.store);
+        Ok(StateChanges::from_contract_code_changes(changes_per_key)?)
+    }
+
+    fn get_data_changes(
+        &self,
+        block_hash: &CryptoHash,
+        account_id: &AccountId,
+        key_prefix: &StoreKey,
+    ) -> Result<StateChanges, Error> {
+        let data_key =
+            trie_key_parsers::get_raw_prefix_for_contract_data(account_id, key_prefix.as_ref());
+        let storage_key = KeyForStateChanges::from_raw_key(block_hash, &data_key);
+        let changes_per_key_prefix = storage_key.find_iter(&self.store);
+        Ok(StateChanges::from_data_changes(changes_per_key_prefix)?)
+    }
+
     pub fn get_store_statistics(&self) -> Option<StoreStatistics> {
         self.store.get_store_statistics()
     }
@@ -1164,6 +1400,7 @@ struct ChainStoreCacheUpdate {
     incoming_receipts: HashMap<(CryptoHash, ShardId), Arc<Vec<ReceiptProof>>>,
     outcomes: HashMap<(CryptoHash, CryptoHash), ExecutionOutcomeWithProof>,
     outcome_ids: HashMap<(CryptoHash, ShardId), Vec<CryptoHash>>,
+    account_outcome_ids: HashMap<Vec<u8>, Vec<CryptoHash>>,
     invalid_chunks: HashMap<ChunkHash, Arc<EncodedShardChunk>>,
     receipt_id_to_shard_id: HashMap<CryptoHash, ShardId>,
     transactions: HashMap<CryptoHash, Arc<SignedTransaction>>,
@@ -1188,6 +1425,7 @@ pub struct ChainStoreUpdate<'a> {
     header_head: Option<Tip>,
     final_head: Option<Tip>,
     largest_target_height: Option<BlockHeight>,
+    reorg_event: Option<ReorgEvent>,
     trie_changes: Vec<WrappedTrieChanges>,
     // All state changes made by a chunk, this is only used for splitting states
     add_state_changes_for_split_states: HashMap<(CryptoHash, ShardId), StateChangesForSplitStates>,
@@ -1215,6 +1453,7 @@ impl<'a> ChainStoreUpdate<'a> {
             header_head: None,
             final_head: None,
             largest_target_height: None,
+            reorg_event: None,
             trie_changes: vec![],
             add_state_changes_for_split_states: HashMap::new(),
             remove_state_changes_for_split_states: HashSet::new(),
@@ -1671,6 +1910,12 @@ impl<'a> ChainStoreUpdate<'a> {
         self.largest_target_height = Some(height);
     }
 
+    /// Appends the reorg to the reorg log, dropping the oldest entries beyond
+    /// `REORG_LOG_SIZE`.
+    pub fn save_reorg_event(&mut self, event: ReorgEvent) {
+        self.reorg_event = Some(event);
+    }
+
     /// Save new height if it's above currently latest known.
     pub fn try_save_latest_known(&mut self, height: BlockHeight) -> Result<(), Error> {
         let latest_known = self.chain_store.get_latest_known().ok();
@@ -1811,6 +2056,7 @@ impl<'a> ChainStoreUpdate<'a> {
     pub fn save_outcomes_with_proofs(
         &mut self,
         block_hash: &CryptoHash,
+        height: BlockHeight,
         shard_id: ShardId,
         outcomes: Vec<ExecutionOutcomeWithId>,
         proofs: Vec<MerklePath>,
@@ -1818,6 +2064,17 @@ impl<'a> ChainStoreUpdate<'a> {
         let mut outcome_ids = Vec::with_capacity(outcomes.len());
         for (outcome_with_id, proof) in outcomes.into_iter().zip(proofs.into_iter()) {
             outcome_ids.push(outcome_with_id.id);
+            if self.chain_store.save_account_outcome_ids {
+                self.chain_store_cache_update
+                    .account_outcome_ids
+                    .entry(get_account_outcome_ids_key(
+                        &outcome_with_id.outcome.executor_id,
+                        height,
+                        block_hash,
+                    ))
+                    .or_default()
+                    .push(outcome_with_id.id);
+            }
             self.chain_store_cache_update.outcomes.insert(
                 (outcome_with_id.id, *block_hash),
                 ExecutionOutcomeWithProof { outcome: outcome_with_id.outcome, proof },
@@ -1946,21 +2203,11 @@ impl<'a> ChainStoreUpdate<'a> {
         for height in chunk_tail..min_chunk_height {
             let chunk_hashes = self.chain_store.get_all_chunk_hashes_by_height(height)?;
             for chunk_hash in chunk_hashes {
-                // 1. Delete chunk-related data
                 let chunk = self.get_chunk(&chunk_hash)?.clone();
                 debug_assert_eq!(chunk.cloned_header().height_created(), height);
-                for transaction in chunk.transactions() {
-                    self.gc_col(DBCol::Transactions, transaction.get_hash().as_bytes());
-                }
-                for receipt in chunk.receipts() {
-                    self.gc_col(DBCol::Receipts, receipt.get_hash().as_bytes());
-                }
-
+                // 1. Delete chunk-related data
                 // 2. Delete chunk_hash-indexed data
-                let chunk_hash = chunk_hash.as_bytes();
-                self.gc_col(DBCol::Chunks, chunk_hash);
-                self.gc_col(DBCol::PartialChunks, chunk_hash);
-                self.gc_col(DBCol::InvalidChunks, chunk_hash);
+                self.gc_chunk(&chunk);
             }
 
             let header_hashes = self.chain_store.get_all_header_hashes_by_height(height)?;
@@ -1979,6 +2226,84 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Deletes the chunk with its transactions and receipts.
+    fn gc_chunk(&mut self, chunk: &ShardChunk) {
+        for transaction in chunk.transactions() {
+            self.gc_col(DBCol::Transactions, transaction.get_hash().as_bytes());
+        }
+        for receipt in chunk.receipts() {
+            self.gc_col(DBCol::Receipts, receipt.get_hash().as_bytes());
+        }
+        let chunk_hash = chunk.chunk_hash();
+        let chunk_hash = chunk_hash.as_bytes();
+        self.gc_col(DBCol::Chunks, chunk_hash);
+        self.gc_col(DBCol::PartialChunks, chunk_hash);
+        self.gc_col(DBCol::InvalidChunks, chunk_hash);
+    }
+
+    /// Clears the data of canonical blocks archival nodes don't keep forever.
+    ///
+    /// Processes the blocks, or chunks created, at heights from `tail` up to
+    /// `gc_stop_height`, exclusive, and at most `gc_height_limit` non-empty
+    /// heights.  Returns the new tail of `kind`.
+    pub fn clear_archival_data(
+        &mut self,
+        kind: ArchivalDataKind,
+        tail: BlockHeight,
+        gc_stop_height: BlockHeight,
+        gc_height_limit: BlockHeightDelta,
+    ) -> Result<BlockHeight, Error> {
+        let mut height = tail;
+        let mut remaining = gc_height_limit;
+        while height < gc_stop_height && remaining > 0 {
+            let current_height = height;
+            height += 1;
+            if kind == ArchivalDataKind::Chunks {
+                let chunk_hashes =
+                    self.chain_store.get_all_chunk_hashes_by_height(current_height)?;
+                if !chunk_hashes.is_empty() {
+                    remaining -= 1;
+                }
+                for chunk_hash in chunk_hashes {
+                    match self.chain_store.get_chunk(&chunk_hash) {
+                        Ok(chunk) => self.gc_chunk(&chunk),
+                        // Chunks of shards the node doesn't track.
+                        Err(Error::ChunkMissing(_)) => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                continue;
+            }
+            let block_hash = match self.chain_store.get_block_hash_by_height(current_height) {
+                Ok(block_hash) => block_hash,
+                Err(Error::DBNotFoundErr(_)) => continue,
+                Err(err) => return Err(err),
+            };
+            remaining -= 1;
+            match kind {
+                ArchivalDataKind::Blocks => self.gc_col(DBCol::Block, block_hash.as_bytes()),
+                ArchivalDataKind::StateChanges => self.gc_state_changes(&block_hash)?,
+                ArchivalDataKind::Outcomes => {
+                    let num_shards = self.get_block_header(&block_hash)?.chunk_mask().len();
+                    self.gc_outcomes_of_shards(
+                        &block_hash,
+                        current_height,
+                        0..num_shards as ShardId,
+                    )?;
+                }
+                ArchivalDataKind::Chunks => unreachable!(),
+            }
+        }
+        Ok(height)
+    }
+
+    pub fn save_archival_tails(&mut self, tails: &ArchivalTails) -> Result<(), Error> {
+        let mut store_update = self.store().store_update();
+        store_update.set_ser(DBCol::BlockMisc, ARCHIVAL_TAILS_KEY, tails)?;
+        self.merge(store_update);
+        Ok(())
+    }
+
     /// Clears chunk data which can be computed from other data in the storage.
     ///
     /// We are storing PartialEncodedChunk objects in the DBCol::PartialChunks in
@@ -2141,16 +2466,7 @@ impl<'a> ChainStoreUpdate<'a> {
         self.gc_col(DBCol::NextBlockHashes, block_hash.as_bytes());
         self.gc_col(DBCol::ChallengedBlocks, block_hash.as_bytes());
         self.gc_col(DBCol::BlocksToCatchup, block_hash.as_bytes());
-        let storage_key = KeyForStateChanges::for_block(&block_hash);
-        let stored_state_changes: Vec<Box<[u8]>> = self
-            .chain_store
-            .store()
-            .iter_prefix(DBCol::StateChanges, storage_key.as_ref())
-            .map(|item| item.map(|(key, _)| key))
-            .collect::<io::Result<Vec<_>>>()?;
-        for key in stored_state_changes {
-            self.gc_col(DBCol::StateChanges, &key);
-        }
+        self.gc_state_changes(&block_hash)?;
         self.gc_col(DBCol::BlockRefCount, block_hash.as_bytes());
         self.gc_outcomes(&block)?;
         match gc_mode {
@@ -2263,16 +2579,59 @@ impl<'a> ChainStoreUpdate<'a> {
         self.merge(store_update);
     }
 
+    fn gc_state_changes(&mut self, block_hash: &CryptoHash) -> Result<(), Error> {
+        let storage_key = KeyForStateChanges::for_block(block_hash);
+        let stored_state_changes: Vec<Box<[u8]>> = self
+            .chain_store
+            .store()
+            .iter_prefix(DBCol::StateChanges, storage_key.as_ref())
+            .map(|item| item.map(|(key, _)| key))
+            .collect::<io::Result<Vec<_>>>()?;
+        for key in stored_state_changes {
+            self.gc_col(DBCol::StateChanges, &key);
+        }
+        Ok(())
+    }
+
     pub fn gc_outcomes(&mut self, block: &Block) -> Result<(), Error> {
-        let block_hash = block.hash();
+        let height = block.header().height();
+        let shard_ids = block
+            .chunks()
+            .iter()
+            .filter(|h| h.height_included() == height)
+            .map(|h| h.shard_id())
+            .collect::<Vec<_>>();
+        self.gc_outcomes_of_shards(block.hash(), height, shard_ids)
+    }
+
+    fn gc_outcomes_of_shards(
+        &mut self,
+        block_hash: &CryptoHash,
+        height: BlockHeight,
+        shard_ids: impl IntoIterator<Item = ShardId>,
+    ) -> Result<(), Error> {
         let store_update = self.store().store_update();
-        for chunk_header in
-            block.chunks().iter().filter(|h| h.height_included() == block.header().height())
-        {
-            let shard_id = chunk_header.shard_id();
+        for shard_id in shard_ids {
             let outcome_ids =
                 self.chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
             for outcome_id in outcome_ids {
+                // The index is cleared when it gets disabled, see
+                // `ChainStore::backfill_account_outcome_ids`.
+                if self.chain_store.save_account_outcome_ids {
+                    if let Some(outcome) = self
+                        .chain_store
+                        .get_outcome_by_id_and_block_hash(&outcome_id, block_hash)?
+                    {
+                        self.gc_col(
+                            DBCol::AccountOutcomeIds,
+                            &get_account_outcome_ids_key(
+                                &outcome.outcome.executor_id,
+                                height,
+                                block_hash,
+                            ),
+                        );
+                    }
+                }
                 self.gc_col(
                     DBCol::TransactionResultForBlock,
                     &get_outcome_id_block_hash(&outcome_id, block_hash),
@@ -2377,6 +2736,9 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::OutcomeIds => {
                 store_update.delete(col, key);
             }
+            DBCol::AccountOutcomeIds => {
+                store_update.delete(col, key);
+            }
             DBCol::StateDlInfos => {
                 store_update.delete(col, key);
             }
@@ -2412,6 +2774,7 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::_TransactionRefCount
             | DBCol::_TransactionResult
             | DBCol::StateChangesForSplitStates
+            | DBCol::RecentPartialChunks
             | DBCol::CachedContractCode => {
                 unreachable!();
             }
@@ -2567,6 +2930,13 @@ impl<'a> ChainStoreUpdate<'a> {
             LARGEST_TARGET_HEIGHT_KEY,
             &mut self.largest_target_height,
         )?;
+        if let Some(event) = self.reorg_event.take() {
+            let mut reorg_log = self.chain_store.get_reorg_log()?;
+            reorg_log.push(event);
+            let excess = reorg_log.len().saturating_sub(REORG_LOG_SIZE);
+            reorg_log.drain(..excess);
+            store_update.set_ser(DBCol::BlockMisc, REORG_LOG_KEY, &reorg_log)?;
+        }
         debug_assert!(self.chain_store_cache_update.blocks.len() <= 1);
         for (hash, block) in self.chain_store_cache_update.blocks.iter() {
             let mut map =
@@ -2727,6 +3097,9 @@ impl<'a> ChainStoreUpdate<'a> {
                 &ids,
             )?;
         }
+        for (key, ids) in self.chain_store_cache_update.account_outcome_ids.iter() {
+            store_update.set_ser(DBCol::AccountOutcomeIds, key, ids)?;
+        }
         for (receipt_id, shard_id) in self.chain_store_cache_update.receipt_id_to_shard_id.iter() {
             let data = shard_id.try_to_vec()?;
             store_update.increment_refcount(DBCol::ReceiptIdToShardId, receipt_id.as_ref(), &data);
@@ -2892,6 +3265,7 @@ impl<'a> ChainStoreUpdate<'a> {
 
             outcomes: _,
             outcome_ids: _,
+            account_outcome_ids: _,
         } = self.chain_store_cache_update;
         for (hash, block) in blocks {
             self.chain_store.blocks.put(hash.into(), block);
@@ -2983,11 +3357,12 @@ mod tests {
     use near_primitives::hash::hash;
     use near_primitives::test_utils::create_test_signer;
     use near_primitives::test_utils::TestBlockBuilder;
+    use near_primitives::transaction::{ExecutionOutcome, ExecutionOutcomeWithId};
     use near_primitives::types::{BlockHeight, EpochId, NumBlocks};
-    use near_primitives::utils::index_to_bytes;
+    use near_primitives::utils::{get_account_outcome_ids_key, index_to_bytes};
     use near_primitives::validator_signer::InMemoryValidatorSigner;
     use near_store::test_utils::create_test_store;
-    use near_store::DBCol;
+    use near_store::{DBCol, ACCOUNT_OUTCOME_IDS_KEY};
 
     use crate::store::{ChainStoreAccess, GCMode};
     use crate::store_validator::StoreValidator;
@@ -3203,6 +3578,139 @@ mod tests {
         assert_ne!(epoch_id_to_hash, epoch_id_to_hash1);
     }
 
+    #[test]
+    fn test_account_outcome_ids() {
+        let mut chain = get_chain();
+        chain.mut_store().set_save_account_outcome_ids(true);
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(create_test_signer("test1"));
+        let block1 = TestBlockBuilder::new(&genesis, signer.clone()).build();
+        let mut fork1 = block1.clone();
+        fork1.mut_header().get_mut().inner_lite.epoch_id = EpochId(hash(&[1, 2, 3]));
+        fork1.mut_header().resign(&*signer);
+
+        let outcome = |id: &[u8], executor_id: &str| ExecutionOutcomeWithId {
+            id: hash(id),
+            outcome: ExecutionOutcome {
+                executor_id: executor_id.parse().unwrap(),
+                ..Default::default()
+            },
+        };
+        let mut store_update = chain.mut_store().store_update();
+        store_update.save_outcomes_with_proofs(
+            block1.hash(),
+            1,
+            0,
+            vec![outcome(&[1], "alice"), outcome(&[2], "bob"), outcome(&[3], "alice")],
+            vec![vec![]; 3],
+        );
+        store_update.save_outcomes_with_proofs(
+            fork1.hash(),
+            1,
+            0,
+            vec![outcome(&[4], "alice")],
+            vec![vec![]],
+        );
+        store_update.chain_store_cache_update.height_to_hashes.insert(1, Some(*block1.hash()));
+        store_update.commit().unwrap();
+
+        // Outcomes of blocks off the canonical chain are not returned.
+        let alice = "alice".parse().unwrap();
+        assert_eq!(
+            chain.store().get_account_outcome_ids(&alice, 0, 10).unwrap(),
+            vec![(1, *block1.hash(), vec![hash(&[1]), hash(&[3])])]
+        );
+        assert_eq!(chain.store().get_account_outcome_ids(&alice, 2, 10).unwrap(), vec![]);
+        assert_eq!(
+            chain.store().get_account_outcome_ids(&"bob".parse().unwrap(), 0, 10).unwrap(),
+            vec![(1, *block1.hash(), vec![hash(&[2])])]
+        );
+        assert_eq!(
+            chain.store().get_account_outcome_ids(&"carol".parse().unwrap(), 0, 10).unwrap(),
+            vec![]
+        );
+
+        // Garbage collection cleans up the index.
+        let mut store_update = chain.mut_store().store_update();
+        store_update.gc_outcomes_of_shards(block1.hash(), 1, [0]).unwrap();
+        store_update.commit().unwrap();
+        let key = get_account_outcome_ids_key(&alice, 1, block1.hash());
+        assert!(!chain.store().store().exists(DBCol::AccountOutcomeIds, &key).unwrap());
+    }
+
+    #[test]
+    fn test_account_outcome_ids_backfill() {
+        let mut chain = get_chain();
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(create_test_signer("test1"));
+        let block1 = TestBlockBuilder::new(&genesis, signer.clone()).build();
+        let block2 = TestBlockBuilder::new(&block1, signer).build();
+
+        // The outcomes are saved before the index is enabled.
+        let mut store_update = chain.mut_store().store_update();
+        for block in [&block1, &block2] {
+            let height = block.header().height();
+            store_update.save_outcomes_with_proofs(
+                block.hash(),
+                height,
+                0,
+                vec![ExecutionOutcomeWithId {
+                    id: hash(block.hash().as_ref()),
+                    outcome: ExecutionOutcome {
+                        executor_id: "alice".parse().unwrap(),
+                        ..Default::default()
+                    },
+                }],
+                vec![vec![]],
+            );
+            store_update
+                .chain_store_cache_update
+                .height_to_hashes
+                .insert(height, Some(*block.hash()));
+        }
+        store_update.save_body_head(&Tip::from_header(block2.header())).unwrap();
+        store_update.commit().unwrap();
+        let alice = "alice".parse().unwrap();
+        assert_eq!(chain.store().get_account_outcome_ids(&alice, 0, 10).unwrap(), vec![]);
+
+        // The backfill proceeds in batches and skips heights below the tail.
+        let mut store_update = chain.mut_store().store_update();
+        store_update.update_tail(1).unwrap();
+        store_update.commit().unwrap();
+        chain.mut_store().set_save_account_outcome_ids(true);
+        assert!(!chain.mut_store().backfill_account_outcome_ids(1).unwrap());
+        assert_eq!(
+            chain.store().get_account_outcome_ids(&alice, 0, 10).unwrap(),
+            vec![(1, *block1.hash(), vec![hash(block1.hash().as_ref())])]
+        );
+        assert!(chain.mut_store().backfill_account_outcome_ids(1).unwrap());
+        assert!(chain.mut_store().backfill_account_outcome_ids(1).unwrap());
+        assert_eq!(
+            chain.store().get_account_outcome_ids(&alice, 0, 10).unwrap(),
+            vec![
+                (1, *block1.hash(), vec![hash(block1.hash().as_ref())]),
+                (2, *block2.hash(), vec![hash(block2.hash().as_ref())]),
+            ]
+        );
+
+        // Heights below the tail are ignored.
+        let mut store_update = chain.mut_store().store_update();
+        store_update.update_tail(2).unwrap();
+        store_update.commit().unwrap();
+        assert_eq!(
+            chain.store().get_account_outcome_ids(&alice, 0, 10).unwrap(),
+            vec![(2, *block2.hash(), vec![hash(block2.hash().as_ref())])]
+        );
+
+        // Disabling the index clears it along with the backfill progress.
+        chain.mut_store().set_save_account_outcome_ids(false);
+        assert!(chain.mut_store().backfill_account_outcome_ids(1).unwrap());
+        let store = chain.store().store();
+        let key = get_account_outcome_ids_key(&alice, 2, block2.hash());
+        assert!(!store.exists(DBCol::AccountOutcomeIds, &key).unwrap());
+        assert!(!store.exists(DBCol::BlockMisc, ACCOUNT_OUTCOME_IDS_KEY).unwrap());
+    }
+
     /// Test that garbage collection works properly. The blocks behind gc head should be garbage
     /// collected while the blocks that are ahead of it should not.
     #[test]
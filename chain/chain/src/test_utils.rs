@@ -67,6 +67,7 @@ pub fn process_block_sync(
         me,
         block,
         provenance,
+        None,
         block_processing_artifacts,
         Arc::new(|_| {}),
     )?;
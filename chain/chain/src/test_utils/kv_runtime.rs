@@ -29,7 +29,7 @@ use near_primitives::shard_layout::{ShardLayout, ShardUId};
 use near_primitives::sharding::ChunkHash;
 use near_primitives::transaction::{
     Action, ExecutionMetadata, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionStatus,
-    SignedTransaction, TransferAction,
+    SignedTransaction, Transaction, TransferAction,
 };
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
@@ -39,7 +39,8 @@ use near_primitives::types::{
 use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 use near_primitives::views::{
     AccessKeyInfoView, AccessKeyList, CallResult, ContractCodeView, EpochValidatorInfo,
-    QueryRequest, QueryResponse, QueryResponseKind, ViewStateResult,
+    ProtocolVersionVotingView, QueryRequest, QueryResponse, QueryResponseKind,
+    ValidatorProjectionView, ViewStateResult,
 };
 use near_store::{
     DBCol, PartialStorage, ShardTries, Store, StoreUpdate, Trie, TrieChanges, WrappedTrieChanges,
@@ -47,6 +48,7 @@ use near_store::{
 
 use crate::types::{
     ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, RuntimeAdapter,
+    SimulationLimits, SimulationResult,
 };
 use crate::{BlockHeader, RuntimeWithEpochManagerAdapter};
 
@@ -652,6 +654,21 @@ impl EpochManagerAdapter for KeyValueRuntime {
         })
     }
 
+    fn get_validator_projection(
+        &self,
+        _last_block_hash: &CryptoHash,
+        _account_id: &AccountId,
+    ) -> Result<ValidatorProjectionView, Error> {
+        Err(Error::NotAValidator)
+    }
+
+    fn get_protocol_version_voting(
+        &self,
+        _last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolVersionVotingView, Error> {
+        Err(Error::Other("protocol version voting isn't tracked".to_string()))
+    }
+
     fn get_epoch_minted_amount(&self, _epoch_id: &EpochId) -> Result<Balance, Error> {
         Ok(0)
     }
@@ -1161,6 +1178,7 @@ impl RuntimeAdapter for KeyValueRuntime {
         block_hash: &CryptoHash,
         _epoch_id: &EpochId,
         request: &QueryRequest,
+        _deadline: Option<std::time::Instant>,
     ) -> Result<QueryResponse, near_chain_primitives::error::QueryError> {
         match request {
             QueryRequest::ViewAccount { account_id, .. } => Ok(QueryResponse {
@@ -1193,6 +1211,7 @@ impl RuntimeAdapter for KeyValueRuntime {
                         public_key: PublicKey::empty(KeyType::ED25519),
                         access_key: AccessKey::full_access().into(),
                     }],
+                    next_public_key: None,
                 }),
                 block_height,
                 block_hash: *block_hash,
@@ -1206,6 +1225,7 @@ impl RuntimeAdapter for KeyValueRuntime {
                 kind: QueryResponseKind::ViewState(ViewStateResult {
                     values: Default::default(),
                     proof: vec![],
+                    next_key: None,
                 }),
                 block_height,
                 block_hash: *block_hash,
@@ -1335,6 +1355,17 @@ impl RuntimeAdapter for KeyValueRuntime {
         unreachable!("get_protocol_config should not be called in KeyValueRuntime");
     }
 
+    fn simulate_transaction(
+        &self,
+        _header: &BlockHeader,
+        _state_roots: &HashMap<ShardId, StateRoot>,
+        _transaction: Transaction,
+        _signature: Option<Signature>,
+        _limits: &SimulationLimits,
+    ) -> Result<Result<SimulationResult, InvalidTxError>, Error> {
+        Err(Error::Other("simulation of transactions isn't supported".to_string()))
+    }
+
     fn will_shard_layout_change_next_epoch(
         &self,
         _parent_hash: &CryptoHash,
@@ -1,3 +1,4 @@
+use near_primitives::clock;
 use near_primitives::test_utils::create_test_signer;
 use near_primitives::time::Clock;
 use rand::{thread_rng, Rng};
@@ -54,6 +55,7 @@ fn one_iter(
         .iter()
         .map(|signer| {
             Doomslug::new(
+                clock::Clock::real(),
                 0,
                 Duration::from_millis(200),
                 Duration::from_millis(1000),
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::chain::Chain;
+use crate::store::ChainStoreAccess;
 use crate::test_utils::{KeyValueRuntime, ValidatorSchedule};
 use crate::types::{ChainConfig, ChainGenesis, Tip};
 use crate::DoomslugThresholdMode;
@@ -10,9 +11,9 @@ use near_primitives::block::Block;
 use near_primitives::merkle::PartialMerkleTree;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::test_utils::{create_test_signer, TestBlockBuilder};
-use near_primitives::types::{NumBlocks, NumShards, StateRoot};
+use near_primitives::types::{AccountId, NumBlocks, NumShards, StateRoot};
 use near_store::test_utils::{create_test_store, gen_changes};
-use near_store::{ShardTries, Trie, WrappedTrieChanges};
+use near_store::{DBCol, ShardTries, Trie, WrappedTrieChanges};
 use rand::Rng;
 
 fn get_chain(num_shards: NumShards) -> Chain {
@@ -711,3 +712,65 @@ fn test_fork_far_away_from_epoch_end() {
         );
     }
 }
+
+// Stops tracking the shard, garbage collects the blocks whose trie changes
+// touched its deleted state and then tracks the shard again, as if it were
+// synced anew.
+#[test]
+fn test_gc_after_clearing_untracked_shard() {
+    let max_changes = 5;
+    let mut chain = get_chain(1);
+    let tries = chain.runtime_adapter.get_tries();
+    let shard_uid = ShardUId::single_shard();
+    let genesis = chain.get_block_by_height(0).unwrap();
+    let mut states = vec![(genesis, vec![Trie::EMPTY_ROOT], vec![Vec::new()])];
+    do_fork(
+        states[0].0.clone(),
+        states[0].1.clone(),
+        tries.clone(),
+        &mut chain,
+        101,
+        &mut states,
+        max_changes,
+        false,
+    );
+    // A fork which is still pending when the state gets cleared.
+    let (fork_block, fork_roots, _) = states[95].clone();
+    do_fork(fork_block, fork_roots, tries.clone(), &mut chain, 3, &mut states, max_changes, false);
+    let (head_block, head_roots, _) = states[101].clone();
+    let head_state: Vec<_> = tries
+        .get_trie_for_shard(shard_uid, head_roots[0])
+        .iter()
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+
+    let me: Option<AccountId> = Some("test2".parse().unwrap());
+    assert_eq!(chain.clear_untracked_shards_state(&me).unwrap(), vec![0]);
+    let store = chain.store().store().clone();
+    assert_eq!(store.iter(DBCol::State).count(), 0);
+    assert_eq!(store.iter(DBCol::TrieChanges).count(), 0);
+
+    chain
+        .clear_data(tries.clone(), &GCConfig { gc_blocks_limit: 1000, ..GCConfig::default() })
+        .unwrap();
+    assert_eq!(store.iter(DBCol::State).count(), 0);
+
+    // Sync the state of the head again.
+    let trie_changes = tries
+        .get_trie_for_shard(shard_uid, Trie::EMPTY_ROOT)
+        .update(head_state.iter().map(|(key, value)| (key.clone(), Some(value.clone()))))
+        .unwrap();
+    let mut store_update = tries.store_update();
+    assert_eq!(tries.apply_all(&trie_changes, shard_uid, &mut store_update), head_roots[0]);
+    store_update.commit().unwrap();
+
+    do_fork(head_block, head_roots, tries.clone(), &mut chain, 60, &mut states, max_changes, false);
+    chain
+        .clear_data(tries.clone(), &GCConfig { gc_blocks_limit: 1000, ..GCConfig::default() })
+        .unwrap();
+    let (_, roots, _) = states.last().unwrap();
+    for item in tries.get_trie_for_shard(shard_uid, roots[0]).iter().unwrap() {
+        item.unwrap();
+    }
+}
@@ -248,6 +248,21 @@ fn blocks_at_height() {
         assert!(chain.get_block_header_by_height(h).is_err());
     }
     assert_eq!(chain.get_block_header_by_height(7).unwrap().hash(), &e_7_hash);
+
+    // Each switch to another fork is in the reorg log.
+    let reorgs: Vec<_> = chain
+        .store()
+        .get_reorg_log()
+        .unwrap()
+        .into_iter()
+        .map(|event| {
+            (event.old_head_hash, event.new_head_hash, event.common_ancestor_height, event.depth)
+        })
+        .collect();
+    assert_eq!(
+        reorgs,
+        vec![(b_2_hash, c_3_hash, 0, 2), (c_4_hash, d_5_hash, 0, 3), (d_6_hash, e_7_hash, 1, 4)]
+    );
 }
 
 #[test]
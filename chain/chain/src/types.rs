@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use chrono::DateTime;
@@ -11,6 +12,7 @@ use crate::metrics;
 use near_chain_configs::{Genesis, ProtocolConfig};
 use near_chain_primitives::Error;
 use near_client_primitives::types::StateSplitApplyingStatus;
+use near_crypto::Signature;
 use near_pool::types::PoolIterator;
 use near_primitives::challenge::{ChallengesResult, SlashedValidator};
 use near_primitives::checked_feature;
@@ -20,11 +22,11 @@ use near_primitives::merkle::{merklize, MerklePath};
 use near_primitives::receipt::Receipt;
 use near_primitives::shard_layout::{ShardLayout, ShardUId};
 use near_primitives::state_part::PartId;
-use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
+use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction, Transaction};
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
     AccountId, Balance, BlockHeight, BlockHeightDelta, EpochId, Gas, MerkleHash, NumBlocks,
-    ShardId, StateChangesForSplitStates, StateRoot, StateRootNode,
+    RawStateChangesWithTrieKey, ShardId, StateChangesForSplitStates, StateRoot, StateRootNode,
 };
 use near_primitives::version::{
     ProtocolVersion, MIN_GAS_PRICE_NEP_92, MIN_GAS_PRICE_NEP_92_FIX, MIN_PROTOCOL_VERSION_NEP_92,
@@ -119,6 +121,40 @@ impl ApplyTransactionResult {
     }
 }
 
+/// Result of `RuntimeAdapter::simulate_transaction`.
+#[derive(Default)]
+pub struct SimulationResult {
+    /// Outcomes of the transaction and of the receipts it produced, in the
+    /// order in which they were executed.
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    /// Changes of the state made by the transaction and its receipts.
+    pub state_changes: Vec<RawStateChangesWithTrieKey>,
+    /// Receipts which weren't executed, either because they were sent to a
+    /// shard whose state isn't available or because the simulation reached
+    /// one of its `SimulationLimits`.
+    pub pending_receipts: Vec<Receipt>,
+}
+
+/// Limits on the work done by `RuntimeAdapter::simulate_transaction`.  Once
+/// one of them is reached, the receipts which weren't executed yet are
+/// returned as pending.
+#[derive(Clone, Debug)]
+pub struct SimulationLimits {
+    /// Maximum number of blocks in which the receipts get applied.
+    pub max_blocks: BlockHeightDelta,
+    /// Maximum gas burnt by the transaction and its receipts, in total.
+    pub max_gas: Gas,
+    /// Time after which no more chunks get applied.
+    pub deadline: Option<Instant>,
+}
+
+impl Default for SimulationLimits {
+    fn default() -> Self {
+        // The gas limit of a chunk on mainnet.
+        Self { max_blocks: 16, max_gas: 1_000_000_000_000_000, deadline: None }
+    }
+}
+
 /// Compressed information about block.
 /// Useful for epoch manager.
 #[derive(Default, Clone, Debug)]
@@ -243,11 +279,26 @@ pub struct ChainConfig {
     /// Number of threads to execute background migration work.
     /// Currently used for flat storage background creation.
     pub background_migration_threads: usize,
+    /// Whether to maintain an index of transaction and receipt outcomes by
+    /// executor account.
+    pub save_account_outcome_ids: bool,
+    /// If set, new chunks whose application takes longer than this are
+    /// logged together with the contracts they invoked.
+    pub slow_chunk_threshold: Option<Duration>,
+    /// Number of threads applying the chunks of a block in parallel. If not
+    /// set, the global rayon thread pool is used.
+    pub apply_chunks_threads: Option<usize>,
 }
 
 impl ChainConfig {
     pub fn test() -> Self {
-        Self { save_trie_changes: true, background_migration_threads: 1 }
+        Self {
+            save_trie_changes: true,
+            background_migration_threads: 1,
+            save_account_outcome_ids: false,
+            slow_chunk_threshold: None,
+            apply_chunks_threads: None,
+        }
     }
 }
 
@@ -501,7 +552,8 @@ pub trait RuntimeAdapter: Send + Sync {
         is_first_block_with_chunk_of_version: bool,
     ) -> Result<ApplyTransactionResult, Error>;
 
-    /// Query runtime with given `path` and `data`.
+    /// Query runtime with given `path` and `data`.  State iteration and
+    /// function calls are aborted once `deadline` has passed.
     fn query(
         &self,
         shard_uid: ShardUId,
@@ -512,6 +564,7 @@ pub trait RuntimeAdapter: Send + Sync {
         block_hash: &CryptoHash,
         epoch_id: &EpochId,
         request: &QueryRequest,
+        deadline: Option<Instant>,
     ) -> Result<QueryResponse, near_chain_primitives::error::QueryError>;
 
     /// Get the part of the state from given state root.
@@ -579,6 +632,22 @@ pub trait RuntimeAdapter: Send + Sync {
     ) -> Result<bool, Error>;
 
     fn get_protocol_config(&self, epoch_id: &EpochId) -> Result<ProtocolConfig, Error>;
+
+    /// Applies `transaction` on top of the state of the block `header`, given
+    /// by `state_roots` of the shards available on the node, as if it was
+    /// included in the next block, and then applies the receipts it produces
+    /// in the following blocks, within `limits`.  Nothing is persisted.
+    /// Without a `signature` the transaction is executed on behalf of its
+    /// signer regardless of its public key.  Returns the error of the
+    /// transaction if it is invalid.
+    fn simulate_transaction(
+        &self,
+        header: &BlockHeader,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        transaction: Transaction,
+        signature: Option<Signature>,
+        limits: &SimulationLimits,
+    ) -> Result<Result<SimulationResult, InvalidTxError>, Error>;
 }
 
 pub trait RuntimeWithEpochManagerAdapter: RuntimeAdapter + EpochManagerAdapter {}
@@ -591,6 +660,55 @@ pub struct LatestKnown {
     pub seen: u64,
 }
 
+/// Kinds of data archival nodes garbage collect when their `ArchivalPolicy`
+/// doesn't keep them forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchivalDataKind {
+    Blocks,
+    Chunks,
+    StateChanges,
+    Outcomes,
+}
+
+/// Heights up to which archival nodes garbage collected each kind of data,
+/// exclusive.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchivalTails {
+    pub blocks: BlockHeight,
+    pub chunks: BlockHeight,
+    pub state_changes: BlockHeight,
+    pub outcomes: BlockHeight,
+}
+
+impl ArchivalTails {
+    /// Tails of a node which didn't garbage collect anything.  The genesis
+    /// block is always kept.
+    pub fn new(genesis_height: BlockHeight) -> Self {
+        let height = genesis_height + 1;
+        Self { blocks: height, chunks: height, state_changes: height, outcomes: height }
+    }
+}
+
+/// A switch of the head to a block on another fork, i.e. one that doesn't
+/// descend from the previous head.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReorgEvent {
+    pub old_head_hash: CryptoHash,
+    pub old_head_height: BlockHeight,
+    /// Timestamp of the old head block, in nanoseconds.
+    pub old_head_timestamp: u64,
+    pub new_head_hash: CryptoHash,
+    pub new_head_height: BlockHeight,
+    /// Timestamp of the new head block, in nanoseconds.
+    pub new_head_timestamp: u64,
+    /// Height of the last block the two forks have in common.
+    pub common_ancestor_height: BlockHeight,
+    /// Number of blocks of the old fork that are no longer canonical.
+    pub depth: u64,
+    /// When the node switched, in nanoseconds.
+    pub seen: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use near_primitives::test_utils::{create_test_signer, TestBlockBuilder};
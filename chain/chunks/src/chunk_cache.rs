@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
+use borsh::BorshSerialize;
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::{
     ChunkHash, PartialEncodedChunkPart, PartialEncodedChunkV2, ReceiptProof, ShardChunkHeader,
@@ -117,6 +118,25 @@ impl EncodedChunksCache {
         self.encoded_chunks.get(chunk_hash)
     }
 
+    /// Returns the number of cached chunks and the approximate size in bytes
+    /// of the parts and receipts held for them.
+    pub fn get_size(&self) -> (usize, u64) {
+        let size = self
+            .encoded_chunks
+            .values()
+            .map(|entry| {
+                let parts_size: usize = entry.parts.values().map(|part| part.part.len()).sum();
+                let receipts_size: usize = entry
+                    .receipts
+                    .values()
+                    .map(|receipts| receipts.try_to_vec().map_or(0, |bytes| bytes.len()))
+                    .sum();
+                (parts_size + receipts_size) as u64
+            })
+            .sum();
+        (self.encoded_chunks.len(), size)
+    }
+
     /// Mark an entry as complete, which means it has all parts and receipts needed
     pub fn mark_entry_complete(&mut self, chunk_hash: &ChunkHash) {
         if let Some(entry) = self.encoded_chunks.get_mut(chunk_hash) {
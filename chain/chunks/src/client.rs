@@ -73,6 +73,15 @@ impl ShardedTransactionPool {
         Self { tx_pools: HashMap::new(), rng_seed }
     }
 
+    /// Returns the number of transactions and their total size in bytes in the
+    /// pool of every shard.
+    pub fn get_pool_sizes(&self) -> Vec<(ShardId, usize, u64)> {
+        self.tx_pools
+            .iter()
+            .map(|(shard_id, pool)| (*shard_id, pool.len(), pool.transactions_size()))
+            .collect()
+    }
+
     pub fn get_pool_iterator(&mut self, shard_id: ShardId) -> Option<PoolIteratorWrapper<'_>> {
         self.tx_pools.get_mut(&shard_id).map(|pool| pool.pool_iterator())
     }
@@ -41,6 +41,8 @@
 //! Before a chunk can be reconstructed fully, parts and receipts in the chunk are stored in
 //! `encoded_chunks`. Full chunks will be persisted in the database storage after they are
 //! reconstructed.
+//! If enabled, all the parts and receipts of a completed chunk are also kept in the database for
+//! a few heights, to serve requests from peers which fell behind (see the `recent_chunks` module).
 //!
 //! ** Forwarding chunks
 //! To save messages and time for chunks to propagate among validators, we implemented a feature
@@ -50,12 +52,17 @@
 //! part can only be processed after the node has the corresponding chunk header, either from blocks
 //! or partial chunk requests. Before that, they are temporarily stored in `chunk_forwards_cache`.
 //! After that, they are processed as a PartialEncodedChunk message only containing one part.
+//! With the ChunkPartMulticast protocol feature, a part owner only forwards its parts to a few
+//! validators, which forward them further, so that the part reaches all validators tracking the
+//! shard through a tree of forwards rooted at the owner (see the `multicast` module).
 //!
 //! ** Processing chunks
 //! Function `process_partial_encoded_chunk` processes a partial encoded chunk message.
 //! 1) validates the parts and receipts in the message
 //! 2) merges the parts and receipts are into `encoded_chunks`.
-//! 3) forwards newly received owned parts to other validators, if any.
+//! 3) forwards newly received owned parts to other validators, if any (or with the
+//!    ChunkPartMulticast protocol feature, newly received parts to the children of the node in
+//!    the multicast trees of the parts).
 //! 4) checks if there are any forwarded chunk parts in `chunk_forwards_cache` that can be processed.
 //! 5) checks if all needed parts and receipts are received and tries to reconstruct the full chunk.
 //!    If successful, removes request for the chunk from the request pool.
@@ -98,6 +105,7 @@ use tracing::{debug, error, warn};
 use near_chain::{byzantine_assert, RuntimeWithEpochManagerAdapter};
 use near_network::types::{NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest};
 use near_primitives::block::Tip;
+use near_primitives::checked_feature;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{verify_path, MerklePath};
 use near_primitives::receipt::Receipt;
@@ -116,9 +124,12 @@ use near_primitives::unwrap_or_return;
 use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::ProtocolVersion;
+use near_store::Store;
 
 use crate::chunk_cache::{EncodedChunksCache, EncodedChunksCacheEntry};
 use crate::logic::cares_about_shard_this_or_next_epoch;
+use crate::multicast::chunk_part_forward_targets;
+use crate::recent_chunks::RecentPartialChunks;
 use near_chain::near_chain_primitives::error::Error::DBNotFoundErr;
 pub use near_chunks_primitives::Error;
 use near_network::types::{
@@ -132,6 +143,8 @@ mod chunk_cache;
 pub mod client;
 pub mod logic;
 mod metrics;
+mod multicast;
+mod recent_chunks;
 pub mod test_utils;
 
 const CHUNK_PRODUCER_BLACKLIST_SIZE: usize = 100;
@@ -226,14 +239,15 @@ impl RequestPool {
     pub fn fetch(&mut self) -> Vec<(ChunkHash, ChunkRequestInfo)> {
         let mut removed_requests = HashSet::<ChunkHash>::default();
         let mut requests = Vec::new();
+        let now = Clock::instant();
         for (chunk_hash, mut chunk_request) in self.requests.iter_mut() {
-            if chunk_request.added.elapsed() > self.max_duration {
+            if now.saturating_duration_since(chunk_request.added) > self.max_duration {
                 debug!(target: "chunks", "Evicted chunk requested that was never fetched {} (shard_id: {})", chunk_hash.0, chunk_request.shard_id);
                 removed_requests.insert(chunk_hash.clone());
                 continue;
             }
-            if chunk_request.last_requested.elapsed() > self.retry_duration {
-                chunk_request.last_requested = Clock::instant();
+            if now.saturating_duration_since(chunk_request.last_requested) > self.retry_duration {
+                chunk_request.last_requested = now;
                 requests.push((chunk_hash.clone(), chunk_request.clone()));
             }
         }
@@ -485,6 +499,8 @@ pub struct ShardsManager {
     encoded_chunks: EncodedChunksCache,
     requested_partial_encoded_chunks: RequestPool,
     chunk_forwards_cache: lru::LruCache<ChunkHash, HashMap<u64, PartialEncodedChunkPart>>,
+    /// Parts and receipts of the chunks completed at the last few heights, if enabled.
+    recent_partial_chunks: Option<RecentPartialChunks>,
 
     // This is a best-effort cache of the chain's head, not the source of truth. The source
     // of truth is in the chain store and written to by the Client.
@@ -520,16 +536,39 @@ impl ShardsManager {
                 Duration::from_millis(CHUNK_REQUEST_RETRY_MAX_MS),
             ),
             chunk_forwards_cache: lru::LruCache::new(CHUNK_FORWARD_CACHE_SIZE),
+            recent_partial_chunks: None,
             chain_head: initial_chain_head,
             seals_mgr: SealsManager::new(me, runtime_adapter),
         }
     }
 
+    /// Keeps the parts and receipts of the chunks created at the last
+    /// `num_heights` heights in the store, to serve requests for them after
+    /// they are gone from the in-memory cache.  Zero disables the cache.
+    pub fn with_recent_partial_chunks(
+        mut self,
+        store: Store,
+        num_heights: BlockHeightDelta,
+    ) -> Self {
+        self.recent_partial_chunks =
+            (num_heights > 0).then(|| RecentPartialChunks::new(store, num_heights));
+        self
+    }
+
+    /// Returns the number of chunks in the cache of chunks being assembled
+    /// and the approximate size in bytes of their parts and receipts.
+    pub fn get_chunk_cache_size(&self) -> (usize, u64) {
+        self.encoded_chunks.get_size()
+    }
+
     pub fn update_chain_head(&mut self, tip: Tip) {
         self.encoded_chunks.update_largest_seen_height(
             tip.height,
             &self.requested_partial_encoded_chunks.requests,
         );
+        if let Some(recent_partial_chunks) = &mut self.recent_partial_chunks {
+            recent_partial_chunks.prune(tip.height);
+        }
         self.chain_head = Some(tip);
     }
 
@@ -927,6 +966,7 @@ impl ShardsManager {
         .entered();
         // Process chunk one part requests.
         let requests = self.requested_partial_encoded_chunks.fetch();
+        let now = Clock::instant();
         for (chunk_hash, chunk_request) in requests {
             let fetch_from_archival = self.runtime_adapter
                 .chunk_needs_to_be_fetched_from_archival(&chunk_request.ancestor_hash, &header_head.last_block_hash).unwrap_or_else(|err| {
@@ -942,10 +982,10 @@ impl ShardsManager {
                 &chunk_request.ancestor_hash,
                 chunk_request.shard_id,
                 &chunk_hash,
-                chunk_request.added.elapsed()
+                now.saturating_duration_since(chunk_request.added)
                     > self.requested_partial_encoded_chunks.switch_to_full_fetch_duration,
                 old_block
-                    || chunk_request.added.elapsed()
+                    || now.saturating_duration_since(chunk_request.added)
                         > self.requested_partial_encoded_chunks.switch_to_others_duration,
                 fetch_from_archival,
             ) {
@@ -1049,6 +1089,22 @@ impl ShardsManager {
             return (PartialEncodedChunkResponseSource::InMemoryCache, response);
         }
 
+        // Try fetching the parts and receipts of recently completed chunks
+        // from storage.
+        if let Some(partial_chunk) =
+            self.recent_partial_chunks.as_ref().and_then(|recent| recent.get(&chunk_hash))
+        {
+            Self::lookup_partial_encoded_chunk_from_recent_partial_chunks(
+                &mut part_ords,
+                &mut tracking_shards,
+                &mut response,
+                &partial_chunk,
+            );
+            if part_ords.is_empty() && tracking_shards.is_empty() {
+                return (PartialEncodedChunkResponseSource::RecentPartialChunkOnDisk, response);
+            }
+        }
+
         // Try fetching partial encoded chunk from storage.
         if let Ok(partial_chunk) = self.store.get_partial_chunk(&chunk_hash) {
             Self::lookup_partial_encoded_chunk_from_partial_chunk_storage(
@@ -1108,6 +1164,27 @@ impl ShardsManager {
         });
     }
 
+    /// Looks up the given part_ords and tracking_shards from a recent partial chunk,
+    /// appending any we have found into the response, and deleting those we have found
+    /// from part_ords and tracking_shards.
+    fn lookup_partial_encoded_chunk_from_recent_partial_chunks(
+        part_ords: &mut HashSet<u64>,
+        tracking_shards: &mut HashSet<ShardId>,
+        response: &mut PartialEncodedChunkResponseMsg,
+        partial_chunk: &PartialEncodedChunk,
+    ) {
+        for part in partial_chunk.parts() {
+            if part_ords.remove(&part.part_ord) {
+                response.parts.push(part.clone());
+            }
+        }
+        for receipt in partial_chunk.receipts() {
+            if tracking_shards.remove(&receipt.1.to_shard_id) {
+                response.receipts.push(receipt.clone());
+            }
+        }
+    }
+
     /// Looks up the given part_ords and tracking_shards from the partial chunks
     /// storage, appending any we have found into the response, and deleting those we
     /// have found from part_ords and tracking_shards.
@@ -1872,6 +1949,19 @@ impl ShardsManager {
     ) {
         let chunk_hash = partial_chunk.chunk_hash();
         self.encoded_chunks.mark_entry_complete(&chunk_hash);
+        if let (Some(recent_partial_chunks), Some(entry)) =
+            (&mut self.recent_partial_chunks, self.encoded_chunks.get(&chunk_hash))
+        {
+            let head_height = self.chain_head.as_ref().map_or(0, |tip| tip.height);
+            recent_partial_chunks.save(
+                &PartialEncodedChunk::new(
+                    entry.header.clone(),
+                    entry.parts.values().cloned().collect(),
+                    entry.receipts.values().cloned().collect(),
+                ),
+                head_height,
+            );
+        }
         self.encoded_chunks.remove_from_cache_if_outside_horizon(&chunk_hash);
         self.requested_partial_encoded_chunks.remove(&chunk_hash);
         debug!(target: "chunks", "Completed chunk {:?}", chunk_hash);
@@ -1902,6 +1992,9 @@ impl ShardsManager {
 
     /// Send the parts of the partial_encoded_chunk that are owned by `self.me` to the
     /// other validators that are tracking the shard.
+    /// With the `ChunkPartMulticast` protocol feature, the parts are instead sent to the
+    /// children of `self.me` in the multicast trees of the parts, whether they are owned by
+    /// `self.me` or not (see the `multicast` module).
     fn send_partial_encoded_chunk_to_chunk_trackers(
         &mut self,
         partial_encoded_chunk: &PartialEncodedChunkV2,
@@ -1913,34 +2006,28 @@ impl ShardsManager {
             Some(me) => me,
             None => return Ok(()),
         };
-        let owned_parts: Vec<_> = partial_encoded_chunk
+        let new_parts: Vec<_> = partial_encoded_chunk
             .parts
             .iter()
-            .filter(|part| {
-                part_ords.contains(&part.part_ord)
-                    && self
-                        .runtime_adapter
-                        .get_part_owner(epoch_id, part.part_ord)
-                        .map_or(false, |owner| &owner == me)
-            })
-            .cloned()
+            .filter(|part| part_ords.contains(&part.part_ord))
             .collect();
-
-        if owned_parts.is_empty() {
+        if new_parts.is_empty() {
             return Ok(());
         }
 
-        let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
-            &partial_encoded_chunk.header,
-            owned_parts,
-        );
-
+        // Technically, here we should check if the block producers actually care about the
+        // shard. We don't because with the current implementation, we force all validators to
+        // track all shards by making their config tracking all shards.
+        // See https://github.com/near/nearcore/issues/7388
+        // We also forward chunk parts to incoming chunk producers because we want them to be able
+        // to produce the next chunk without delays. For the same reason as above, we don't check
+        // if they actually track this shard.
         let block_producers = self
             .runtime_adapter
             .get_epoch_block_producers_ordered(&epoch_id, lastest_block_hash)?;
         let current_chunk_height = partial_encoded_chunk.header.height_created();
         let num_shards = self.runtime_adapter.num_shards(&epoch_id)?;
-        let mut next_chunk_producers = (0..num_shards)
+        let mut chunk_trackers = (0..num_shards)
             .map(|shard_id| {
                 self.runtime_adapter.get_chunk_producer(
                     &epoch_id,
@@ -1949,40 +2036,52 @@ impl ShardsManager {
                 )
             })
             .collect::<Result<HashSet<_>, _>>()?;
-        next_chunk_producers.remove(me);
-        for (bp, _) in block_producers {
-            let bp_account_id = bp.take_account_id();
-            // no need to send anything to myself
-            if me == &bp_account_id {
-                continue;
-            }
-            next_chunk_producers.remove(&bp_account_id);
+        chunk_trackers.extend(block_producers.into_iter().map(|(bp, _)| bp.take_account_id()));
 
-            // Technically, here we should check if the block producer actually cares about the shard.
-            // We don't because with the current implementation, we force all validators to track all
-            // shards by making their config tracking all shards.
-            // See https://github.com/near/nearcore/issues/7388
-            self.peer_manager_adapter.do_send(
-                PeerManagerMessageRequest::NetworkRequests(
-                    NetworkRequests::PartialEncodedChunkForward {
-                        account_id: bp_account_id,
-                        forward: forward.clone(),
-                    },
-                )
-                .with_span_context(),
-            );
+        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&epoch_id)?;
+        let mut forwards: HashMap<AccountId, Vec<PartialEncodedChunkPart>> = HashMap::new();
+        if checked_feature!(
+            "protocol_feature_chunk_part_multicast",
+            ChunkPartMulticast,
+            protocol_version
+        ) {
+            for part in new_parts {
+                let owner = self.runtime_adapter.get_part_owner(&epoch_id, part.part_ord)?;
+                for account_id in
+                    chunk_part_forward_targets(&owner, me, part.part_ord, &chunk_trackers)
+                {
+                    forwards.entry(account_id).or_default().push(part.clone());
+                }
+            }
+        } else {
+            let owned_parts: Vec<_> = new_parts
+                .into_iter()
+                .filter(|part| {
+                    self.runtime_adapter
+                        .get_part_owner(epoch_id, part.part_ord)
+                        .map_or(false, |owner| &owner == me)
+                })
+                .cloned()
+                .collect();
+            if owned_parts.is_empty() {
+                return Ok(());
+            }
+            for account_id in chunk_trackers {
+                // no need to send anything to myself
+                if &account_id != me {
+                    forwards.insert(account_id, owned_parts.clone());
+                }
+            }
         }
 
-        // We also forward chunk parts to incoming chunk producers because we want them to be able
-        // to produce the next chunk without delays. For the same reason as above, we don't check if they
-        // actually track this shard.
-        for next_chunk_producer in next_chunk_producers {
+        for (account_id, parts) in forwards {
+            let forward = PartialEncodedChunkForwardMsg::from_header_and_parts(
+                &partial_encoded_chunk.header,
+                parts,
+            );
             self.peer_manager_adapter.do_send(
                 PeerManagerMessageRequest::NetworkRequests(
-                    NetworkRequests::PartialEncodedChunkForward {
-                        account_id: next_chunk_producer,
-                        forward: forward.clone(),
-                    },
+                    NetworkRequests::PartialEncodedChunkForward { account_id, forward },
                 )
                 .with_span_context(),
             );
@@ -2168,6 +2267,8 @@ pub enum PartialEncodedChunkResponseSource {
     None,
     /// We only had to look into the in-memory partial chunk cache.
     InMemoryCache,
+    /// We had to look at the RecentPartialChunks column on disk.
+    RecentPartialChunkOnDisk,
     /// We had to look at the PartialChunk column on disk.
     PartialChunkOnDisk,
     /// We had to look at the ShardChunk column on disk, and if we found it,
@@ -2181,6 +2282,7 @@ impl PartialEncodedChunkResponseSource {
         match self {
             PartialEncodedChunkResponseSource::None => "none",
             PartialEncodedChunkResponseSource::InMemoryCache => "cache",
+            PartialEncodedChunkResponseSource::RecentPartialChunkOnDisk => "recent_partial",
             PartialEncodedChunkResponseSource::PartialChunkOnDisk => "partial",
             PartialEncodedChunkResponseSource::ShardChunkOnDisk => "chunk",
         }
@@ -2197,7 +2299,7 @@ mod test {
     use std::time::Duration;
 
     use near_chain::test_utils::{KeyValueRuntime, ValidatorSchedule};
-    use near_chain::{Chain, ChainStore};
+    use near_chain::{Chain, ChainStore, ChainStoreAccess};
     use near_network::test_utils::MockPeerManagerAdapter;
     use near_network::types::NetworkRequests;
     use near_o11y::testonly::init_test_logger;
@@ -3064,6 +3166,61 @@ mod test {
         assert_eq!(response.parts.len(), fixture.all_part_ords.len());
     }
 
+    #[test]
+    fn test_chunk_response_for_recent_partial_chunk() {
+        let fixture = ChunkTestFixture::default();
+        let store = fixture.chain_store.store().clone();
+        let mut shards_manager = ShardsManager::new(
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.clone(),
+            fixture.mock_client_adapter.clone(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            Some(fixture.mock_chain_head.clone()),
+        )
+        .with_recent_partial_chunks(store.clone(), 5);
+
+        let result = shards_manager
+            .process_partial_encoded_chunk(
+                fixture.make_partial_encoded_chunk(&fixture.all_part_ords).into(),
+            )
+            .unwrap();
+        assert_matches!(result, ProcessPartialEncodedChunkResult::HaveAllPartsAndReceipts);
+
+        // A restarted node no longer has the chunk in its in-memory cache, but
+        // serves all the parts from the recent partial chunks.
+        let mut shards_manager = ShardsManager::new(
+            Some(fixture.mock_shard_tracker.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.clone(),
+            fixture.mock_client_adapter.clone(),
+            fixture.chain_store.new_read_only_chunks_store(),
+            Some(fixture.mock_chain_head.clone()),
+        )
+        .with_recent_partial_chunks(store, 5);
+        let request = PartialEncodedChunkRequestMsg {
+            chunk_hash: fixture.mock_chunk_header.chunk_hash(),
+            part_ords: fixture.all_part_ords.clone(),
+            tracking_shards: HashSet::new(),
+        };
+        let (source, response) = shards_manager.prepare_partial_encoded_chunk_response(request);
+        assert_eq!(source, PartialEncodedChunkResponseSource::RecentPartialChunkOnDisk);
+        assert_eq!(response.parts.len(), fixture.all_part_ords.len());
+
+        // Once the head moves far enough, the chunk is pruned.
+        let mut tip = fixture.mock_chain_head.clone();
+        tip.height = fixture.mock_chunk_header.height_created() + 6;
+        shards_manager.update_chain_head(tip);
+        let request = PartialEncodedChunkRequestMsg {
+            chunk_hash: fixture.mock_chunk_header.chunk_hash(),
+            part_ords: fixture.all_part_ords.clone(),
+            tracking_shards: HashSet::new(),
+        };
+        let (source, response) = shards_manager.prepare_partial_encoded_chunk_response(request);
+        assert_eq!(source, PartialEncodedChunkResponseSource::ShardChunkOnDisk);
+        assert!(response.parts.is_empty());
+    }
+
     #[test]
     fn test_chunk_response_combining_cache_and_partial_chunks() {
         let mut fixture = ChunkTestFixture::default();
@@ -0,0 +1,95 @@
+//! Multicast of chunk parts among validators.
+//!
+//! With the `ChunkPartMulticast` protocol feature, the owner of a part doesn't
+//! forward it to every validator tracking the chunk's shard.  Instead, the
+//! validators receiving the part form a tree rooted at the owner, in which
+//! every validator forwards the part to at most `CHUNK_PART_MULTICAST_FANOUT`
+//! others.  The tree is derived deterministically from the part ordinal and
+//! the set of receivers, so every validator knows its place in it without
+//! coordination, and the validators with children differ between the parts
+//! of a chunk, spreading the forwarding work evenly.
+use near_primitives::types::AccountId;
+use std::collections::HashSet;
+
+/// Number of validators every validator forwards a part to.
+pub(crate) const CHUNK_PART_MULTICAST_FANOUT: usize = 4;
+
+/// Returns the validators `me` forwards the part with ordinal `part_ord` to,
+/// given the owner of the part and the set of validators which should
+/// receive it.
+pub(crate) fn chunk_part_forward_targets(
+    owner: &AccountId,
+    me: &AccountId,
+    part_ord: u64,
+    receivers: &HashSet<AccountId>,
+) -> Vec<AccountId> {
+    let mut tree: Vec<&AccountId> =
+        receivers.iter().filter(|account_id| *account_id != owner).collect();
+    if tree.is_empty() {
+        return vec![];
+    }
+    tree.sort();
+    let len = tree.len();
+    tree.rotate_left((part_ord % len as u64) as usize);
+
+    // The owner is the root of the tree and the children of the validator at
+    // position `i` are at positions `(i + 1) * fanout .. (i + 2) * fanout`,
+    // taking the owner's position to be -1.
+    let first_child = if me == owner {
+        0
+    } else {
+        match tree.iter().position(|account_id| *account_id == me) {
+            Some(position) => (position + 1) * CHUNK_PART_MULTICAST_FANOUT,
+            None => return vec![],
+        }
+    };
+    tree.into_iter().skip(first_child).take(CHUNK_PART_MULTICAST_FANOUT).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(i: usize) -> AccountId {
+        format!("test{:03}", i).parse().unwrap()
+    }
+
+    #[test]
+    fn test_forward_targets_cover_receivers_once() {
+        let receivers: HashSet<AccountId> = (0..50).map(account).collect();
+        let owner = account(7);
+        for part_ord in 0..10 {
+            let mut received = HashSet::new();
+            let mut queue = vec![owner.clone()];
+            while let Some(sender) = queue.pop() {
+                let targets = chunk_part_forward_targets(&owner, &sender, part_ord, &receivers);
+                assert!(targets.len() <= CHUNK_PART_MULTICAST_FANOUT);
+                for target in targets {
+                    assert_ne!(target, owner);
+                    assert!(received.insert(target.clone()), "{} received the part twice", target);
+                    queue.push(target);
+                }
+            }
+            let expected: HashSet<AccountId> =
+                receivers.iter().filter(|account_id| *account_id != &owner).cloned().collect();
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[test]
+    fn test_forward_targets_differ_between_parts() {
+        let receivers: HashSet<AccountId> = (0..50).map(account).collect();
+        let owner = account(0);
+        assert_ne!(
+            chunk_part_forward_targets(&owner, &owner, 0, &receivers),
+            chunk_part_forward_targets(&owner, &owner, 1, &receivers)
+        );
+    }
+
+    #[test]
+    fn test_no_forward_targets_outside_of_tree() {
+        let receivers: HashSet<AccountId> = (0..10).map(account).collect();
+        assert!(chunk_part_forward_targets(&account(0), &account(100), 0, &receivers).is_empty());
+        assert!(chunk_part_forward_targets(&account(0), &account(0), 0, &HashSet::new()).is_empty());
+    }
+}
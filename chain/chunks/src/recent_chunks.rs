@@ -0,0 +1,111 @@
+//! Disk-backed cache of the partial encoded chunks of the last few heights.
+//!
+//! Once a chunk is complete, all the parts and receipt proofs the node has
+//! for it are saved in `DBCol::RecentPartialChunks`.  Unlike
+//! `DBCol::PartialChunks`, which only holds the parts the node owns and the
+//! receipts for the shards it tracks, this lets the node answer requests from
+//! peers which fell a few blocks behind, even after a restart, without
+//! reconstructing the parts from the full chunk.  Chunks created more than
+//! `num_heights` below the head are deleted.
+use std::collections::HashMap;
+
+use near_primitives::sharding::{ChunkHash, PartialEncodedChunk};
+use near_primitives::types::{BlockHeight, BlockHeightDelta};
+use near_store::{DBCol, Store};
+use tracing::warn;
+
+pub(crate) struct RecentPartialChunks {
+    store: Store,
+    num_heights: BlockHeightDelta,
+    /// Heights at which the saved chunks were created, needed to find their
+    /// keys.
+    heights: HashMap<ChunkHash, BlockHeight>,
+}
+
+impl RecentPartialChunks {
+    pub(crate) fn new(store: Store, num_heights: BlockHeightDelta) -> Self {
+        let mut heights = HashMap::new();
+        for item in store.iter(DBCol::RecentPartialChunks) {
+            match item.map(|(key, _)| parse_key(&key)) {
+                Ok(Some((height, chunk_hash))) => {
+                    heights.insert(chunk_hash, height);
+                }
+                Ok(None) => warn!(target: "chunks", "Invalid RecentPartialChunks key"),
+                Err(err) => {
+                    warn!(target: "chunks", ?err, "Failed to read recent partial chunks");
+                    break;
+                }
+            }
+        }
+        Self { store, num_heights, heights }
+    }
+
+    pub(crate) fn get(&self, chunk_hash: &ChunkHash) -> Option<PartialEncodedChunk> {
+        let height = *self.heights.get(chunk_hash)?;
+        match self.store.get_ser(DBCol::RecentPartialChunks, &get_key(height, chunk_hash)) {
+            Ok(partial_chunk) => partial_chunk,
+            Err(err) => {
+                warn!(target: "chunks", ?chunk_hash, ?err, "Failed to read recent partial chunk");
+                None
+            }
+        }
+    }
+
+    /// Saves the chunk, unless it was created too far below the head.
+    pub(crate) fn save(&mut self, partial_chunk: &PartialEncodedChunk, head_height: BlockHeight) {
+        let height = partial_chunk.height_created();
+        if height + self.num_heights < head_height {
+            return;
+        }
+        let chunk_hash = partial_chunk.chunk_hash();
+        let mut update = self.store.store_update();
+        let result = update
+            .set_ser(DBCol::RecentPartialChunks, &get_key(height, &chunk_hash), partial_chunk)
+            .and_then(|()| update.commit());
+        match result {
+            Ok(()) => {
+                self.heights.insert(chunk_hash, height);
+            }
+            Err(err) => {
+                warn!(target: "chunks", ?chunk_hash, ?err, "Failed to save recent partial chunk")
+            }
+        }
+    }
+
+    /// Deletes the chunks created more than `num_heights` below the head.
+    pub(crate) fn prune(&mut self, head_height: BlockHeight) {
+        let min_height = head_height.saturating_sub(self.num_heights);
+        if !self.heights.values().any(|height| *height < min_height) {
+            return;
+        }
+        let mut update = self.store.store_update();
+        update.delete_range(
+            DBCol::RecentPartialChunks,
+            &0u64.to_be_bytes(),
+            &min_height.to_be_bytes(),
+        );
+        match update.commit() {
+            Ok(()) => self.heights.retain(|_, height| *height >= min_height),
+            Err(err) => warn!(target: "chunks", ?err, "Failed to prune recent partial chunks"),
+        }
+    }
+}
+
+/// Big endian height so that the keys are sorted by height and the chunks
+/// of old heights can be deleted with a single range deletion.
+fn get_key(height: BlockHeight, chunk_hash: &ChunkHash) -> Vec<u8> {
+    let mut key = Vec::with_capacity(8 + 32);
+    key.extend_from_slice(&height.to_be_bytes());
+    key.extend_from_slice(chunk_hash.as_ref());
+    key
+}
+
+fn parse_key(key: &[u8]) -> Option<(BlockHeight, ChunkHash)> {
+    if key.len() < 8 {
+        return None;
+    }
+    let (height, chunk_hash) = key.split_at(8);
+    let height = BlockHeight::from_be_bytes(height.try_into().unwrap());
+    let chunk_hash = ChunkHash(chunk_hash.try_into().ok()?);
+    Some((height, chunk_hash))
+}
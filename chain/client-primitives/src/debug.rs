@@ -26,6 +26,22 @@ pub struct TrackedShardsView {
     pub shards_tracked_next_epoch: Vec<bool>,
 }
 
+// Approximate memory held by a subsystem of the node.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MemoryUsageEntry {
+    // Name of the subsystem, e.g. `trie_cache.s0.v1`.
+    pub subsystem: String,
+    // Number of entries (blocks, transactions, chunks etc.) held.
+    pub entries: u64,
+    // Approximate size of the entries in bytes.
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MemoryUsageView {
+    pub subsystems: Vec<MemoryUsageEntry>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EpochInfoView {
     pub epoch_id: CryptoHash,
@@ -64,6 +80,52 @@ pub struct DebugBlockStatus {
     pub gas_price_ratio: f64,
 }
 
+// Heights of the last final blocks of a block on the canonical chain.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlockFinalityView {
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub doomslug_final_height: BlockHeight,
+    pub final_height: BlockHeight,
+}
+
+// How far finality lags behind the head.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FinalityView {
+    pub head_height: BlockHeight,
+    pub doomslug_final_height: BlockHeight,
+    pub final_height: BlockHeight,
+    // The most recent blocks of the canonical chain, newest first.
+    pub blocks: Vec<BlockFinalityView>,
+}
+
+// Delays between the production of blocks of a producer and their arrival at
+// the node, in milliseconds.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProducerPropagationView {
+    pub account_id: AccountId,
+    pub num_blocks: u64,
+    pub avg_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub last_delay_ms: u64,
+    pub last_height: BlockHeight,
+}
+
+// A switch of the head to another fork, from the reorg log.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReorgEventView {
+    pub old_head_hash: CryptoHash,
+    pub old_head_height: BlockHeight,
+    pub old_head_timestamp: u64,
+    pub new_head_hash: CryptoHash,
+    pub new_head_height: BlockHeight,
+    pub new_head_timestamp: u64,
+    pub common_ancestor_height: BlockHeight,
+    // Number of blocks of the old fork dropped from the canonical chain.
+    pub depth: u64,
+    pub seen: DateTime<chrono::Utc>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MissedHeightInfo {
     pub block_height: u64,
@@ -188,6 +250,14 @@ pub enum DebugStatus {
     ChainProcessingStatus,
     // The state parts already requested.
     RequestedStateParts,
+    // Approximate memory held by the major subsystems.
+    MemoryUsage,
+    // The most recent reorgs of the head.
+    Reorgs,
+    // Lag of doomslug and BFT finality behind the head.
+    Finality,
+    // Propagation delays of blocks by producer.
+    BlockPropagation,
 }
 
 impl Message for DebugStatus {
@@ -209,4 +279,12 @@ pub enum DebugStatusResponse {
     ChainProcessingStatus(ChainProcessingInfo),
     // The state parts already requested.
     RequestedStateParts(Vec<RequestedStatePartsView>),
+    // Approximate memory held by the major subsystems.
+    MemoryUsage(MemoryUsageView),
+    // The most recent reorgs of the head, newest first.
+    Reorgs(Vec<ReorgEventView>),
+    // Lag of doomslug and BFT finality behind the head.
+    Finality(FinalityView),
+    // Propagation delays of blocks by producer, slowest first.
+    BlockPropagation(Vec<ProducerPropagationView>),
 }
@@ -8,20 +8,23 @@ use chrono::DateTime;
 use near_primitives::time::Utc;
 
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
+use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::network::PeerId;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
-    TransactionOrReceiptId,
+    AccountId, BlockHeight, BlockReference, BlockTimestampMatch, EpochId, EpochReference,
+    MaybeBlockId, ShardId, TransactionOrReceiptId,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, DownloadStatusView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView, ShardSyncDownloadView,
-    StateChangesKindsView, StateChangesRequestView, StateChangesView, SyncStatusView,
+    AccountOutcomeIdsView, BlockView, ChunkView, DownloadStatusView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, FinalExecutionOutcomeViewEnum, FinalExecutionOutcomeWithProofsView,
+    GasPriceEstimateView, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
+    MaintenanceWindowsView, ProtocolVersionVotingView, QueryRequest, QueryResponse, ReceiptView,
+    ShardSyncDownloadView, StateChangesKindsView, StateChangesRequestView, StateChangesView,
+    SyncStatusView, TransactionSimulationView, ValidatorProjectionView, ValidatorScheduleView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
 use serde::{Deserialize, Serialize};
@@ -77,6 +80,9 @@ impl Clone for DownloadStatus {
 #[derive(Clone, Debug)]
 pub enum ShardSyncStatus {
     StateDownloadHeader,
+    /// Downloading the changes since the end of an earlier epoch, for which
+    /// the node has the state, instead of the whole state.
+    StateDownloadDelta(CryptoHash),
     StateDownloadParts,
     StateDownloadScheduling,
     StateDownloadApplying,
@@ -99,6 +105,7 @@ impl ToString for ShardSyncStatus {
     fn to_string(&self) -> String {
         match self {
             ShardSyncStatus::StateDownloadHeader => "header".to_string(),
+            ShardSyncStatus::StateDownloadDelta(_) => "delta".to_string(),
             ShardSyncStatus::StateDownloadParts => "parts".to_string(),
             ShardSyncStatus::StateDownloadScheduling => "scheduling".to_string(),
             ShardSyncStatus::StateDownloadApplying => "applying".to_string(),
@@ -307,6 +314,18 @@ impl Message for GetBlock {
     type Result = Result<BlockView, GetBlockError>;
 }
 
+/// Get the block on the canonical chain by its timestamp.
+#[derive(Debug)]
+pub struct GetBlockByTimestamp {
+    /// Unix timestamp in nanoseconds.
+    pub timestamp: u64,
+    pub timestamp_match: BlockTimestampMatch,
+}
+
+impl Message for GetBlockByTimestamp {
+    type Result = Result<BlockView, GetBlockError>;
+}
+
 /// Get block with the block merkle tree. Used for testing
 pub struct GetBlockWithMerkleTree(pub BlockReference);
 
@@ -374,11 +393,19 @@ impl From<near_chain_primitives::Error> for GetChunkError {
 pub struct Query {
     pub block_reference: BlockReference,
     pub request: QueryRequest,
+    /// The query fails with `QueryError::TimeoutError` if it doesn't finish
+    /// before the deadline.  State iteration and function calls are aborted
+    /// rather than executed to the end for a client which gave up waiting.
+    pub deadline: Option<std::time::Instant>,
 }
 
 impl Query {
     pub fn new(block_reference: BlockReference, request: QueryRequest) -> Self {
-        Query { block_reference, request }
+        Query { block_reference, request, deadline: None }
+    }
+
+    pub fn with_deadline(self, deadline: Option<std::time::Instant>) -> Self {
+        Query { deadline, ..self }
     }
 }
 
@@ -449,6 +476,8 @@ pub enum QueryError {
     // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
     #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {error_message}")]
     Unreachable { error_message: String },
+    #[error("The deadline of the request passed before the query finished")]
+    TimeoutError,
 }
 
 pub struct Status {
@@ -457,6 +486,46 @@ pub struct Status {
     pub detailed: bool,
 }
 
+/// Criteria checked by the health check of the node in addition to it
+/// receiving new blocks and not syncing.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    /// Maximum number of blocks the head may be behind the highest block
+    /// reported by peers.
+    pub max_blocks_behind: Option<near_primitives::types::BlockHeightDelta>,
+    /// Minimum number of connected peers.
+    pub min_peers: Option<usize>,
+    /// Whether to check that the database accepts writes.
+    pub check_db_writable: bool,
+    /// Whether the node is unhealthy while a background database migration
+    /// (e.g. flat storage creation) is in progress.
+    pub check_no_migration: bool,
+}
+
+/// Runs the health check of the node with the given criteria.
+pub struct GetHealth {
+    pub config: HealthCheckConfig,
+}
+
+/// Result of a single criterion of the health check.
+#[derive(Debug, Serialize)]
+pub struct HealthCheckResult {
+    pub name: &'static str,
+    pub healthy: bool,
+    pub details: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub checks: Vec<HealthCheckResult>,
+}
+
+impl Message for GetHealth {
+    type Result = Result<HealthReport, StatusError>;
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum StatusError {
     #[error("Node is syncing")]
@@ -538,6 +607,32 @@ impl Message for GetNextLightClientBlock {
     type Result = Result<Option<Arc<LightClientBlockView>>, GetNextLightClientBlockError>;
 }
 
+/// Light client block of the last final block of an epoch, recorded once the
+/// epoch ends.  `None` while the epoch is still in progress.
+pub struct GetEpochLightClientBlock {
+    pub epoch_id: EpochId,
+}
+
+pub struct EpochLightClientBlock {
+    pub light_block: Arc<LightClientBlockView>,
+    /// Epoch following the requested one.  Not necessarily the next epoch of
+    /// the light client block, whose block is in the previous epoch if no
+    /// block of the requested epoch became final.
+    pub next_epoch_id: EpochId,
+}
+
+impl Message for GetEpochLightClientBlock {
+    type Result = Result<Option<EpochLightClientBlock>, GetNextLightClientBlockError>;
+}
+
+/// Subscribes to the updates of the head of the chain, sent as blocks are
+/// accepted.
+pub struct SubscribeHead {}
+
+impl Message for SubscribeHead {
+    type Result = tokio::sync::watch::Receiver<Tip>;
+}
+
 pub struct GetNetworkInfo {}
 
 impl Message for GetNetworkInfo {
@@ -552,6 +647,17 @@ impl Message for GetGasPrice {
     type Result = Result<GasPriceView, GetGasPriceError>;
 }
 
+/// Estimate the gas price to attach to transactions for each of the
+/// `percentiles`, given in percents, of the gas prices of recent blocks.
+#[derive(Debug)]
+pub struct GetGasPriceEstimate {
+    pub percentiles: Vec<u8>,
+}
+
+impl Message for GetGasPriceEstimate {
+    type Result = Result<GasPriceEstimateView, GetGasPriceError>;
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GetGasPriceError {
     #[error("Internal error: {error_message}")]
@@ -631,6 +737,19 @@ impl Message for TxStatus {
     type Result = Result<Option<FinalExecutionOutcomeViewEnum>, TxStatusError>;
 }
 
+/// Status of given transaction including all the subsequent receipts and the
+/// proofs of all the outcomes for light clients.
+pub struct TxStatusExtended {
+    pub tx_hash: CryptoHash,
+    pub signer_account_id: AccountId,
+    /// Block to prove the outcomes against, the last final block if not set.
+    pub light_client_head: Option<CryptoHash>,
+}
+
+impl Message for TxStatusExtended {
+    type Result = Result<Option<FinalExecutionOutcomeWithProofsView>, TxStatusError>;
+}
+
 pub struct GetValidatorInfo {
     pub epoch_reference: EpochReference,
 }
@@ -677,6 +796,9 @@ impl Message for GetValidatorOrdered {
 pub struct GetStateChanges {
     pub block_hash: CryptoHash,
     pub state_changes_request: StateChangesRequestView,
+    /// If set, the changes are read one account at a time and the request
+    /// fails with `TimeoutError` if the deadline passes in between.
+    pub deadline: Option<std::time::Instant>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -693,6 +815,8 @@ pub enum GetStateChangesError {
     // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
     #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {error_message}")]
     Unreachable { error_message: String },
+    #[error("The deadline of the request passed before the changes were read")]
+    TimeoutError,
 }
 
 impl From<near_chain_primitives::Error> for GetStateChangesError {
@@ -885,18 +1009,64 @@ impl Message for GetReceipt {
     type Result = Result<Option<ReceiptView>, GetReceiptError>;
 }
 
+/// Ids of the outcomes executed on an account, in blocks of the canonical
+/// chain starting at `from_height`.
+pub struct GetAccountOutcomes {
+    pub account_id: AccountId,
+    pub from_height: BlockHeight,
+    pub limit: usize,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetAccountOutcomesError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Indexing of outcomes by account is disabled on this node")]
+    IndexDisabled,
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetAccountOutcomesError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+impl Message for GetAccountOutcomes {
+    type Result = Result<Vec<AccountOutcomeIdsView>, GetAccountOutcomesError>;
+}
+
 pub struct GetProtocolConfig(pub BlockReference);
 
 impl Message for GetProtocolConfig {
     type Result = Result<ProtocolConfigView, GetProtocolConfigError>;
 }
 
+/// Get the protocol config which was in effect in an epoch.  Unlike
+/// `GetProtocolConfig` it works for epochs whose blocks have been garbage
+/// collected, since the protocol version of every epoch is kept.
+pub struct GetEpochProtocolConfig(pub EpochReference);
+
+impl Message for GetEpochProtocolConfig {
+    type Result = Result<(EpochId, ProtocolConfigView), GetProtocolConfigError>;
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GetProtocolConfigError {
     #[error("IO Error: {0}")]
     IOError(String),
     #[error("Block has never been observed: {0}")]
     UnknownBlock(String),
+    #[error("Epoch has never been observed: {0}")]
+    UnknownEpoch(String),
     // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
     // expected cases, we cannot statically guarantee that no other errors will be returned
     // in the future.
@@ -910,11 +1080,67 @@ impl From<near_chain_primitives::Error> for GetProtocolConfigError {
         match error {
             near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
             near_chain_primitives::Error::DBNotFoundErr(s) => Self::UnknownBlock(s),
+            near_chain_primitives::Error::EpochOutOfBounds(epoch_id) => {
+                Self::UnknownEpoch(format!("{:?}", epoch_id))
+            }
             _ => Self::Unreachable(error.to_string()),
         }
     }
 }
 
+/// Simulate a transaction on top of the state of a block without
+/// broadcasting it.  Without a `signature` the transaction is executed on
+/// behalf of its signer regardless of its public key.
+#[derive(Debug)]
+pub struct SimulateTransaction {
+    pub block_reference: BlockReference,
+    pub transaction: near_primitives::transaction::Transaction,
+    pub signature: Option<near_crypto::Signature>,
+    /// The receipts which haven't been executed by the deadline are returned
+    /// as pending.
+    pub deadline: Option<std::time::Instant>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SimulateTransactionError {
+    #[error("IO Error: {error_message}")]
+    IOError { error_message: String },
+    #[error("Block either has never been observed on the node or has been garbage collected: {error_message}")]
+    UnknownBlock { error_message: String },
+    #[error("There are no fully synchronized blocks yet")]
+    NotSyncedYet,
+    #[error("The node does not track the shard ID {requested_shard_id}")]
+    UnavailableShard { requested_shard_id: ShardId },
+    #[error("Transaction is invalid: {error:?}")]
+    InvalidTransaction { error: near_primitives::errors::InvalidTxError },
+    #[error("Internal error: {error_message}")]
+    InternalError { error_message: String },
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {error_message}")]
+    Unreachable { error_message: String },
+}
+
+impl From<near_chain_primitives::Error> for SimulateTransactionError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => {
+                Self::IOError { error_message: error.to_string() }
+            }
+            near_chain_primitives::Error::DBNotFoundErr(error_message) => {
+                Self::UnknownBlock { error_message }
+            }
+            _ => Self::Unreachable { error_message: error.to_string() },
+        }
+    }
+}
+
+impl Message for SimulateTransaction {
+    type Result = Result<TransactionSimulationView, SimulateTransactionError>;
+}
+
 pub struct GetMaintenanceWindows {
     pub account_id: AccountId,
 }
@@ -940,6 +1166,98 @@ impl From<near_chain_primitives::Error> for GetMaintenanceWindowsError {
     }
 }
 
+/// Returns the upcoming block and chunk production slots of `account_id`, or
+/// of the validator of this node if not given.
+pub struct GetValidatorSchedule {
+    pub account_id: Option<AccountId>,
+}
+
+impl Message for GetValidatorSchedule {
+    type Result = Result<ValidatorScheduleView, GetValidatorScheduleError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetValidatorScheduleError {
+    #[error("Account id must be given, the node isn't a validator")]
+    NoAccountId,
+    #[error("There are no fully synchronized blocks yet")]
+    NotSyncedYet,
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetValidatorScheduleError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::DBNotFoundErr(_) => Self::NotSyncedYet,
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+/// Returns the projected seat, stake and reward of `account_id`, or of the
+/// validator of this node if not given, as of the head of the chain.
+pub struct GetValidatorProjection {
+    pub account_id: Option<AccountId>,
+}
+
+impl Message for GetValidatorProjection {
+    type Result = Result<ValidatorProjectionView, GetValidatorProjectionError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetValidatorProjectionError {
+    #[error("Account id must be given, the node isn't a validator")]
+    NoAccountId,
+    #[error("There are no fully synchronized blocks yet")]
+    NotSyncedYet,
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetValidatorProjectionError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::DBNotFoundErr(_) => Self::NotSyncedYet,
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+/// Returns the state of the vote on the protocol version as of the head of
+/// the chain.
+pub struct GetProtocolVersionVoting {}
+
+impl Message for GetProtocolVersionVoting {
+    type Result = Result<ProtocolVersionVotingView, GetProtocolVersionVotingError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetProtocolVersionVotingError {
+    #[error("There are no fully synchronized blocks yet")]
+    NotSyncedYet,
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetProtocolVersionVotingError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            near_chain_primitives::Error::DBNotFoundErr(_) => Self::NotSyncedYet,
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
 pub struct GetClientConfig {}
 
 impl Message for GetClientConfig {
@@ -1007,10 +1325,65 @@ impl From<std::io::Error> for GetSplitStorageInfoError {
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum CreateDbSnapshotError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+}
+
+impl From<std::io::Error> for CreateDbSnapshotError {
+    fn from(error: std::io::Error) -> Self {
+        Self::IOError(error.to_string())
+    }
+}
+
+/// Returns a state part of a shard as of `sync_hash`, the first block of an
+/// epoch, for streaming it to another node of the same operator.  These
+/// requests count towards the same limits as `StateRequestPart` from peers.
+pub struct GetStateSnapshotPart {
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+    pub part_id: u64,
+}
+
+pub struct StateSnapshotPart {
+    /// Number of parts the state of the shard is split into.
+    pub num_parts: u64,
+    pub data: Vec<u8>,
+}
+
+impl Message for GetStateSnapshotPart {
+    type Result = Result<StateSnapshotPart, GetStateSnapshotPartError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetStateSnapshotPartError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Block {0} is not known")]
+    UnknownBlock(CryptoHash),
+    #[error("Too many state requests are being served")]
+    Throttled,
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
+
+impl From<near_chain_primitives::Error> for GetStateSnapshotPartError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::InvalidStateRequest(msg) => Self::InvalidRequest(msg),
+            _ => Self::InternalError(error.to_string()),
+        }
+    }
+}
+
 #[cfg(feature = "sandbox")]
 #[derive(Debug)]
 pub enum SandboxMessage {
-    SandboxPatchState(Vec<near_primitives::state_record::StateRecord>),
+    SandboxPatchState(
+        Vec<near_primitives::state_record::StateRecord>,
+        Vec<near_primitives::sandbox::SandboxBalancePatch>,
+    ),
     SandboxPatchStateStatus,
     SandboxFastForward(near_primitives::types::BlockHeightDelta),
     SandboxFastForwardStatus,
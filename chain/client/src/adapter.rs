@@ -1,9 +1,13 @@
 use crate::client_actor::ClientActor;
+use crate::message_log::{MessageRecorder, RecordedMessage};
+use crate::metrics;
 use crate::view_client::ViewClientActor;
+use near_network::client::DataResponse;
 use near_network::time;
 use near_network::types::{
-    NetworkInfo, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
-    PartialEncodedChunkResponseMsg, ReasonForBan, StateResponseInfo,
+    GarbageCollectedData, NetworkInfo, PartialEncodedChunkForwardMsg,
+    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, ReasonForBan,
+    StateDeltaResponseInfo, StateResponseInfo,
 };
 use near_o11y::WithSpanContextExt;
 use near_primitives::block::{Approval, Block, BlockHeader};
@@ -13,8 +17,10 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::PartialEncodedChunk;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, EpochId, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 /// Transaction status query
 #[derive(actix::Message)]
@@ -31,7 +37,7 @@ pub(crate) struct TxStatusResponse(pub Box<FinalExecutionOutcomeView>);
 
 /// Request a block.
 #[derive(actix::Message)]
-#[rtype(result = "Option<Box<Block>>")]
+#[rtype(result = "Option<DataResponse<Box<Block>>>")]
 pub(crate) struct BlockRequest(pub CryptoHash);
 
 /// Block response.
@@ -52,6 +58,11 @@ pub struct BlockApproval(pub Approval, pub PeerId);
 #[rtype(result = "Option<Vec<BlockHeader>>")]
 pub(crate) struct BlockHeadersRequest(pub Vec<CryptoHash>);
 
+/// Request headers of the canonical chain at given heights.
+#[derive(actix::Message)]
+#[rtype(result = "Option<Vec<BlockHeader>>")]
+pub(crate) struct BlockHeadersAtHeightsRequest(pub Vec<BlockHeight>);
+
 /// Headers response.
 #[derive(actix::Message, Debug)]
 #[rtype(result = "Result<(),ReasonForBan>")]
@@ -59,19 +70,23 @@ pub(crate) struct BlockHeadersResponse(pub Vec<BlockHeader>, pub PeerId);
 
 /// State request header.
 #[derive(actix::Message)]
-#[rtype(result = "Option<StateResponse>")]
+#[rtype(result = "Option<DataResponse<StateResponse>>")]
 pub(crate) struct StateRequestHeader {
     pub shard_id: ShardId,
     pub sync_hash: CryptoHash,
+    /// Peer which requested the header.
+    pub peer_id: PeerId,
 }
 
 /// State request part.
 #[derive(actix::Message)]
-#[rtype(result = "Option<StateResponse>")]
+#[rtype(result = "Option<DataResponse<StateResponse>>")]
 pub(crate) struct StateRequestPart {
     pub shard_id: ShardId,
     pub sync_hash: CryptoHash,
     pub part_id: u64,
+    /// Peer which requested the part.
+    pub peer_id: PeerId,
 }
 
 /// Response to state request.
@@ -79,6 +94,32 @@ pub(crate) struct StateRequestPart {
 #[rtype(result = "()")]
 pub(crate) struct StateResponse(pub Box<StateResponseInfo>);
 
+/// Response of a peer to a request for a block or for state, which it has
+/// garbage collected.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct DataGarbageCollected {
+    pub data: GarbageCollectedData,
+    pub peer_id: PeerId,
+}
+
+/// State request delta.
+#[derive(actix::Message)]
+#[rtype(result = "Option<StateDeltaResponse>")]
+pub(crate) struct StateRequestDelta {
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+    /// End of the epoch since which the delta is requested.
+    pub from_hash: CryptoHash,
+    /// Peer which requested the delta.
+    pub peer_id: PeerId,
+}
+
+/// Response to state delta request.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct StateDeltaResponse(pub Box<StateDeltaResponseInfo>);
+
 /// Account announcements that needs to be validated before being processed.
 /// They are paired with last epoch id known to this announcement, in order to accept only
 /// newer announcements.
@@ -136,11 +177,68 @@ pub enum ProcessTxResponse {
     DoesNotTrackShard,
 }
 
+/// Whether a network message may be dropped when the queue of its actor is
+/// full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DropPolicy {
+    /// Blocks, chunks, approvals and the like, which peers don't send again.
+    Never,
+    /// Requests which peers retry, transactions which clients resubmit and
+    /// messages superseded by later ones.
+    WhenFull,
+}
+
+/// Number of network messages sent to an actor and not handled yet.
+///
+/// Only messages from the network are accounted, since bursts of them are
+/// what makes the mailboxes grow; messages from RPC and from the actors
+/// themselves are bounded in other ways.
+struct MessageQueue {
+    actor: &'static str,
+    len: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl MessageQueue {
+    fn new(actor: &'static str, capacity: usize) -> Self {
+        Self { actor, len: Arc::new(AtomicUsize::new(0)), capacity }
+    }
+
+    /// Accounts a message as queued until the returned value is dropped.
+    /// Returns `None` if the message should be dropped instead.
+    fn try_push(&self, message: &'static str, policy: DropPolicy) -> Option<QueuedMessage> {
+        let len = self.len.fetch_add(1, Ordering::Relaxed);
+        if len >= self.capacity && policy == DropPolicy::WhenFull {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            metrics::NETWORK_MESSAGES_DROPPED.with_label_values(&[self.actor, message]).inc();
+            return None;
+        }
+        metrics::NETWORK_MESSAGES_QUEUE_LENGTH.with_label_values(&[self.actor]).inc();
+        Some(QueuedMessage { actor: self.actor, len: self.len.clone() })
+    }
+}
+
+struct QueuedMessage {
+    actor: &'static str,
+    len: Arc<AtomicUsize>,
+}
+
+impl Drop for QueuedMessage {
+    fn drop(&mut self) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        metrics::NETWORK_MESSAGES_QUEUE_LENGTH.with_label_values(&[self.actor]).dec();
+    }
+}
+
 pub struct Adapter {
     /// Address of the client actor.
     client_addr: actix::Addr<ClientActor>,
     /// Address of the view client actor.
     view_client_addr: actix::Addr<ViewClientActor>,
+    client_queue: MessageQueue,
+    view_client_queue: MessageQueue,
+    /// Records the messages passed to the client, see `ClientConfig::message_log`.
+    recorder: Option<MessageRecorder>,
 }
 
 impl Adapter {
@@ -148,7 +246,36 @@ impl Adapter {
         client_addr: actix::Addr<ClientActor>,
         view_client_addr: actix::Addr<ViewClientActor>,
     ) -> Self {
-        Self { client_addr, view_client_addr }
+        Self {
+            client_addr,
+            view_client_addr,
+            client_queue: MessageQueue::new("client", usize::MAX),
+            view_client_queue: MessageQueue::new("view_client", usize::MAX),
+            recorder: None,
+        }
+    }
+
+    /// Limits the number of network messages queued for the client and the
+    /// view client.  Once a limit is reached, further messages of types which
+    /// may be dropped are, see `ClientConfig::client_queue_capacity`.
+    pub fn with_queue_capacity(self, client: usize, view_client: usize) -> Self {
+        Self {
+            client_queue: MessageQueue::new("client", client),
+            view_client_queue: MessageQueue::new("view_client", view_client),
+            ..self
+        }
+    }
+
+    /// Records the blocks, approvals, chunk parts and transactions passed to
+    /// the client with `recorder`.
+    pub fn with_recorder(self, recorder: MessageRecorder) -> Self {
+        Self { recorder: Some(recorder), ..self }
+    }
+
+    fn record(&self, message: impl FnOnce() -> RecordedMessage) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(message());
+        }
     }
 }
 
@@ -159,6 +286,11 @@ impl near_network::client::Client for Adapter {
         account_id: AccountId,
         tx_hash: CryptoHash,
     ) -> Option<Box<FinalExecutionOutcomeView>> {
+        let _queued =
+            match self.view_client_queue.try_push("tx_status_request", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return None,
+            };
         match self
             .view_client_addr
             .send(
@@ -176,6 +308,11 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn tx_status_response(&self, tx_result: FinalExecutionOutcomeView) {
+        let _queued =
+            match self.view_client_queue.try_push("tx_status_response", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return,
+            };
         match self
             .view_client_addr
             .send(TxStatusResponse(Box::new(tx_result.clone())).with_span_context())
@@ -192,16 +329,22 @@ impl near_network::client::Client for Adapter {
         &self,
         shard_id: ShardId,
         sync_hash: CryptoHash,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
+        let _queued =
+            match self.view_client_queue.try_push("state_request_header", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return Ok(None),
+            };
         match self
             .view_client_addr
             .send(
-                StateRequestHeader { shard_id: shard_id, sync_hash: sync_hash }.with_span_context(),
+                StateRequestHeader { shard_id: shard_id, sync_hash: sync_hash, peer_id }
+                    .with_span_context(),
             )
             .await
         {
-            Ok(Some(StateResponse(resp))) => Ok(Some(*resp)),
-            Ok(None) => Ok(None),
+            Ok(response) => Ok(response.map(|response| response.map(|StateResponse(resp)| *resp))),
             Err(err) => {
                 tracing::error!("mailbox error: {err}");
                 Ok(None)
@@ -214,17 +357,27 @@ impl near_network::client::Client for Adapter {
         shard_id: ShardId,
         sync_hash: CryptoHash,
         part_id: u64,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
+        let _queued =
+            match self.view_client_queue.try_push("state_request_part", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return Ok(None),
+            };
         match self
             .view_client_addr
             .send(
-                StateRequestPart { shard_id: shard_id, sync_hash: sync_hash, part_id: part_id }
-                    .with_span_context(),
+                StateRequestPart {
+                    shard_id: shard_id,
+                    sync_hash: sync_hash,
+                    part_id: part_id,
+                    peer_id,
+                }
+                .with_span_context(),
             )
             .await
         {
-            Ok(Some(StateResponse(resp))) => Ok(Some(*resp)),
-            Ok(None) => Ok(None),
+            Ok(response) => Ok(response.map(|response| response.map(|StateResponse(resp)| *resp))),
             Err(err) => {
                 tracing::error!("mailbox error: {err}");
                 Ok(None)
@@ -233,13 +386,65 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn state_response(&self, info: StateResponseInfo) {
+        let _queued = self.client_queue.try_push("state_response", DropPolicy::Never);
         match self.client_addr.send(StateResponse(Box::new(info)).with_span_context()).await {
             Ok(()) => {}
             Err(err) => tracing::error!("mailbox error: {err}"),
         }
     }
 
+    async fn data_garbage_collected(&self, data: GarbageCollectedData, peer_id: PeerId) {
+        let _queued = self.client_queue.try_push("data_garbage_collected", DropPolicy::Never);
+        match self
+            .client_addr
+            .send(DataGarbageCollected { data, peer_id }.with_span_context())
+            .await
+        {
+            Ok(()) => {}
+            Err(err) => tracing::error!("mailbox error: {err}"),
+        }
+    }
+
+    async fn state_request_delta(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        from_hash: CryptoHash,
+        peer_id: PeerId,
+    ) -> Result<Option<StateDeltaResponseInfo>, ReasonForBan> {
+        let _queued =
+            match self.view_client_queue.try_push("state_request_delta", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return Ok(None),
+            };
+        match self
+            .view_client_addr
+            .send(StateRequestDelta { shard_id, sync_hash, from_hash, peer_id }.with_span_context())
+            .await
+        {
+            Ok(Some(StateDeltaResponse(resp))) => Ok(Some(*resp)),
+            Ok(None) => Ok(None),
+            Err(err) => {
+                tracing::error!("mailbox error: {err}");
+                Ok(None)
+            }
+        }
+    }
+
+    async fn state_delta_response(&self, info: StateDeltaResponseInfo) {
+        let _queued = self.client_queue.try_push("state_delta_response", DropPolicy::Never);
+        match self.client_addr.send(StateDeltaResponse(Box::new(info)).with_span_context()).await {
+            Ok(()) => {}
+            Err(err) => tracing::error!("mailbox error: {err}"),
+        }
+    }
+
     async fn block_approval(&self, approval: Approval, peer_id: PeerId) {
+        let _queued = self.client_queue.try_push("block_approval", DropPolicy::Never);
+        self.record(|| RecordedMessage::BlockApproval {
+            approval: approval.clone(),
+            peer_id: peer_id.clone(),
+        });
         match self.client_addr.send(BlockApproval(approval, peer_id).with_span_context()).await {
             Ok(()) => {}
             Err(err) => tracing::error!("mailbox error: {err}"),
@@ -247,6 +452,14 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn transaction(&self, transaction: SignedTransaction, is_forwarded: bool) {
+        let _queued = match self.client_queue.try_push("transaction", DropPolicy::WhenFull) {
+            Some(queued) => queued,
+            None => return,
+        };
+        self.record(|| RecordedMessage::Transaction {
+            transaction: transaction.clone(),
+            is_forwarded,
+        });
         match self
             .client_addr
             .send(
@@ -271,6 +484,12 @@ impl near_network::client::Client for Adapter {
         req: PartialEncodedChunkRequestMsg,
         msg_hash: CryptoHash,
     ) {
+        let _queued =
+            match self.client_queue.try_push("partial_encoded_chunk_request", DropPolicy::WhenFull)
+            {
+                Some(queued) => queued,
+                None => return,
+            };
         match self
             .client_addr
             .send(RecvPartialEncodedChunkRequest(req, msg_hash).with_span_context())
@@ -285,7 +504,12 @@ impl near_network::client::Client for Adapter {
         &self,
         resp: PartialEncodedChunkResponseMsg,
         timestamp: time::Instant,
+        _author: PeerId,
+        _msg_len: usize,
     ) {
+        let _queued =
+            self.client_queue.try_push("partial_encoded_chunk_response", DropPolicy::Never);
+        self.record(|| RecordedMessage::PartialEncodedChunkResponse(resp.clone()));
         match self
             .client_addr
             .send(RecvPartialEncodedChunkResponse(resp, timestamp.into()).with_span_context())
@@ -297,6 +521,8 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn partial_encoded_chunk(&self, chunk: PartialEncodedChunk) {
+        let _queued = self.client_queue.try_push("partial_encoded_chunk", DropPolicy::Never);
+        self.record(|| RecordedMessage::PartialEncodedChunk(chunk.clone()));
         match self.client_addr.send(RecvPartialEncodedChunk(chunk).with_span_context()).await {
             Ok(()) => {}
             Err(err) => tracing::error!("mailbox error: {err}"),
@@ -304,13 +530,20 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn partial_encoded_chunk_forward(&self, msg: PartialEncodedChunkForwardMsg) {
+        let _queued =
+            self.client_queue.try_push("partial_encoded_chunk_forward", DropPolicy::Never);
+        self.record(|| RecordedMessage::PartialEncodedChunkForward(msg.clone()));
         match self.client_addr.send(RecvPartialEncodedChunkForward(msg).with_span_context()).await {
             Ok(()) => {}
             Err(err) => tracing::error!("mailbox error: {err}"),
         }
     }
 
-    async fn block_request(&self, hash: CryptoHash) -> Option<Box<Block>> {
+    async fn block_request(&self, hash: CryptoHash) -> Option<DataResponse<Box<Block>>> {
+        let _queued = match self.view_client_queue.try_push("block_request", DropPolicy::WhenFull) {
+            Some(queued) => queued,
+            None => return None,
+        };
         match self.view_client_addr.send(BlockRequest(hash).with_span_context()).await {
             Ok(res) => res,
             Err(err) => {
@@ -321,6 +554,11 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn block_headers_request(&self, hashes: Vec<CryptoHash>) -> Option<Vec<BlockHeader>> {
+        let _queued =
+            match self.view_client_queue.try_push("block_headers_request", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return None,
+            };
         match self.view_client_addr.send(BlockHeadersRequest(hashes).with_span_context()).await {
             Ok(headers) => headers,
             Err(err) => {
@@ -330,7 +568,37 @@ impl near_network::client::Client for Adapter {
         }
     }
 
+    async fn block_headers_at_heights_request(
+        &self,
+        heights: Vec<BlockHeight>,
+    ) -> Option<Vec<BlockHeader>> {
+        let _queued = match self
+            .view_client_queue
+            .try_push("block_headers_at_heights_request", DropPolicy::WhenFull)
+        {
+            Some(queued) => queued,
+            None => return None,
+        };
+        match self
+            .view_client_addr
+            .send(BlockHeadersAtHeightsRequest(heights).with_span_context())
+            .await
+        {
+            Ok(headers) => headers,
+            Err(err) => {
+                tracing::error!("mailbox error: {err}");
+                None
+            }
+        }
+    }
+
     async fn block(&self, block: Block, peer_id: PeerId, was_requested: bool) {
+        let _queued = self.client_queue.try_push("block", DropPolicy::Never);
+        self.record(|| RecordedMessage::Block {
+            block: block.clone(),
+            peer_id: peer_id.clone(),
+            was_requested,
+        });
         match self
             .client_addr
             .send(BlockResponse { block, peer_id, was_requested }.with_span_context())
@@ -346,6 +614,7 @@ impl near_network::client::Client for Adapter {
         headers: Vec<BlockHeader>,
         peer_id: PeerId,
     ) -> Result<(), ReasonForBan> {
+        let _queued = self.client_queue.try_push("block_headers", DropPolicy::Never);
         match self
             .client_addr
             .send(BlockHeadersResponse(headers, peer_id).with_span_context())
@@ -360,6 +629,7 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn challenge(&self, challenge: Challenge) {
+        let _queued = self.client_queue.try_push("challenge", DropPolicy::Never);
         match self.client_addr.send(RecvChallenge(challenge).with_span_context()).await {
             Ok(()) => {}
             Err(err) => tracing::error!("mailbox error: {err}"),
@@ -367,6 +637,10 @@ impl near_network::client::Client for Adapter {
     }
 
     async fn network_info(&self, info: NetworkInfo) {
+        let _queued = match self.client_queue.try_push("network_info", DropPolicy::WhenFull) {
+            Some(queued) => queued,
+            None => return,
+        };
         match self.client_addr.send(SetNetworkInfo(info).with_span_context()).await {
             Ok(()) => {}
             Err(err) => tracing::error!("mailbox error: {err}"),
@@ -377,6 +651,11 @@ impl near_network::client::Client for Adapter {
         &self,
         accounts: Vec<(AnnounceAccount, Option<EpochId>)>,
     ) -> Result<Vec<AnnounceAccount>, ReasonForBan> {
+        let _queued =
+            match self.view_client_queue.try_push("announce_account", DropPolicy::WhenFull) {
+                Some(queued) => queued,
+                None => return Ok(vec![]),
+            };
         match self.view_client_addr.send(AnnounceAccountRequest(accounts).with_span_context()).await
         {
             Ok(res) => res,
@@ -387,3 +666,22 @@ impl near_network::client::Client for Adapter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_queue() {
+        let queue = MessageQueue::new("test", 1);
+        let first = queue.try_push("request", DropPolicy::WhenFull);
+        assert!(first.is_some());
+        assert!(queue.try_push("request", DropPolicy::WhenFull).is_none());
+        let block = queue.try_push("block", DropPolicy::Never);
+        assert!(block.is_some());
+        assert_eq!(queue.len.load(Ordering::Relaxed), 2);
+        drop((first, block));
+        assert_eq!(queue.len.load(Ordering::Relaxed), 0);
+        assert!(queue.try_push("request", DropPolicy::WhenFull).is_some());
+    }
+}
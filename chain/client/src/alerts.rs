@@ -0,0 +1,289 @@
+//! Alerts about problems with the operation of the node.
+//!
+//! The node is checked every `log_summary_period`.  An alert is raised when
+//! its validator missed block or chunk production slots since the previous
+//! check, when the share of slots it produced in the epoch is less than
+//! `kickout_warning_margin` percentage points above the kickout threshold,
+//! when the head is more than `max_blocks_behind` blocks behind the
+//! highest height reported by peers, when it is more than `max_finality_lag`
+//! blocks above its last final block, or when fewer than `min_num_peers`
+//! peers are connected.  Every alert is logged and delivered to the configured
+//! webhook and command, at most once per `min_interval` for each kind.
+use std::collections::HashMap;
+use std::process::Command;
+
+use awc::Client as HttpClient;
+use futures::FutureExt;
+use near_chain_configs::AlertsConfig;
+use near_network::types::NetworkInfo;
+use near_primitives::time::{Clock, Instant};
+use near_primitives::types::{BlockHeight, EpochId, NumBlocks, ValidatorInfoIdentifier};
+use tracing::{debug, warn};
+
+use crate::client::Client;
+use crate::info::{kickout_margins, ValidatorProductionStats};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+enum AlertKind {
+    MissedBlock,
+    MissedChunk,
+    KickoutRisk,
+    BlocksBehind,
+    FinalityLag,
+    LowPeers,
+}
+
+/// Numbers of block and chunk production slots missed by the validator of
+/// the node in an epoch.
+struct MissedSlots {
+    epoch_id: EpochId,
+    blocks: NumBlocks,
+    chunks: NumBlocks,
+}
+
+pub(crate) struct Alerter {
+    config: AlertsConfig,
+    http_client: HttpClient,
+    last_sent: HashMap<AlertKind, Instant>,
+    missed_slots: Option<MissedSlots>,
+}
+
+impl Alerter {
+    pub(crate) fn new(config: AlertsConfig) -> Self {
+        Self {
+            config,
+            http_client: HttpClient::default(),
+            last_sent: HashMap::new(),
+            missed_slots: None,
+        }
+    }
+
+    /// Checks the node for problems and sends alerts about the ones found.
+    pub(crate) fn check(&mut self, client: &Client, network_info: &NetworkInfo) {
+        let head = match client.chain.head() {
+            Ok(head) => head,
+            Err(err) => {
+                warn!(target: "client", ?err, "Failed to get the head for alerts");
+                return;
+            }
+        };
+
+        // Computing validator info is expensive while syncing, and the node
+        // isn't expected to produce anything then anyway.
+        if !client.sync_status.is_syncing() {
+            if let Some(signer) = &client.validator_signer {
+                let identifier = ValidatorInfoIdentifier::BlockHash(head.last_block_hash);
+                let validator =
+                    client.runtime_adapter.get_validator_info(identifier).ok().and_then(|info| {
+                        info.current_validators
+                            .into_iter()
+                            .find(|validator| &validator.account_id == signer.validator_id())
+                    });
+                if let Some(validator) = validator {
+                    if let Ok(epoch_config) =
+                        client.runtime_adapter.get_epoch_config(&head.epoch_id)
+                    {
+                        let margins = kickout_margins(
+                            &ValidatorProductionStats::validator(validator.clone()),
+                            epoch_config.block_producer_kickout_threshold,
+                            epoch_config.chunk_producer_kickout_threshold,
+                        );
+                        for margin in margins {
+                            if margin.is_at_risk(client.config.kickout_warning_margin) {
+                                self.send(
+                                    client,
+                                    AlertKind::KickoutRisk,
+                                    head.height,
+                                    margin.to_string(),
+                                );
+                            }
+                        }
+                    }
+                    let missed = MissedSlots {
+                        epoch_id: head.epoch_id.clone(),
+                        blocks: validator
+                            .num_expected_blocks
+                            .saturating_sub(validator.num_produced_blocks),
+                        chunks: validator
+                            .num_expected_chunks
+                            .saturating_sub(validator.num_produced_chunks),
+                    };
+                    self.check_missed_slots(client, head.height, missed);
+                }
+            }
+        }
+
+        if let Some(max_blocks_behind) = self.config.max_blocks_behind {
+            let highest_height = network_info
+                .highest_height_peers
+                .iter()
+                .map(|peer| peer.highest_block_height)
+                .max()
+                .unwrap_or(head.height);
+            let blocks_behind = highest_height.saturating_sub(head.height);
+            if blocks_behind > max_blocks_behind {
+                self.send(
+                    client,
+                    AlertKind::BlocksBehind,
+                    head.height,
+                    format!(
+                        "Head is {} blocks behind the highest height {} reported by peers",
+                        blocks_behind, highest_height
+                    ),
+                );
+            }
+        }
+
+        if let Some(max_finality_lag) = self.config.max_finality_lag {
+            let final_height = client
+                .chain
+                .head_header()
+                .and_then(|header| client.chain.get_block_header(header.last_final_block()))
+                .map_or(0, |header| header.height());
+            let finality_lag = head.height.saturating_sub(final_height);
+            if finality_lag > max_finality_lag {
+                self.send(
+                    client,
+                    AlertKind::FinalityLag,
+                    head.height,
+                    format!(
+                        "Head is {} blocks above the last final block at height {}",
+                        finality_lag, final_height
+                    ),
+                );
+            }
+        }
+
+        let min_num_peers = client.config.min_num_peers;
+        if network_info.num_connected_peers < min_num_peers {
+            self.send(
+                client,
+                AlertKind::LowPeers,
+                head.height,
+                format!(
+                    "{} peers connected, fewer than the minimum of {}",
+                    network_info.num_connected_peers, min_num_peers
+                ),
+            );
+        }
+    }
+
+    fn check_missed_slots(&mut self, client: &Client, height: BlockHeight, missed: MissedSlots) {
+        // Slots missed before the first check, e.g. while the node was down,
+        // aren't reported.
+        let (prev_blocks, prev_chunks) = match &self.missed_slots {
+            Some(prev) if prev.epoch_id == missed.epoch_id => (prev.blocks, prev.chunks),
+            Some(_) => (0, 0),
+            None => (missed.blocks, missed.chunks),
+        };
+        if missed.blocks > prev_blocks {
+            self.send(
+                client,
+                AlertKind::MissedBlock,
+                height,
+                format!(
+                    "Missed {} block production slots, {} in the current epoch",
+                    missed.blocks - prev_blocks,
+                    missed.blocks
+                ),
+            );
+        }
+        if missed.chunks > prev_chunks {
+            self.send(
+                client,
+                AlertKind::MissedChunk,
+                height,
+                format!(
+                    "Missed {} chunk production slots, {} in the current epoch",
+                    missed.chunks - prev_chunks,
+                    missed.chunks
+                ),
+            );
+        }
+        self.missed_slots = Some(missed);
+    }
+
+    fn send(&mut self, client: &Client, kind: AlertKind, height: BlockHeight, message: String) {
+        let alert: &'static str = kind.into();
+        let now = Clock::instant();
+        if let Some(last_sent) = self.last_sent.get(&kind) {
+            if now.duration_since(*last_sent) < self.config.min_interval {
+                debug!(target: "client", alert, "Alert suppressed by rate limit: {}", message);
+                return;
+            }
+        }
+        self.last_sent.insert(kind, now);
+        warn!(target: "client", alert, "{}", message);
+
+        let account_id =
+            client.validator_signer.as_ref().map(|signer| signer.validator_id().to_string());
+        let payload = render_payload(
+            &self.config.payload_template,
+            &[
+                ("alert", alert),
+                ("message", &message),
+                ("chain_id", &client.config.chain_id),
+                ("account_id", account_id.as_deref().unwrap_or_default()),
+                ("height", &height.to_string()),
+            ],
+        );
+
+        if let Some(url) = &self.config.webhook_url {
+            let url = url.clone();
+            near_performance_metrics::actix::spawn(
+                "alerts",
+                self.http_client
+                    .post(url.clone())
+                    .insert_header(("Content-Type", "application/json"))
+                    .send_body(payload.clone())
+                    .map(move |response| match response {
+                        Ok(response) if response.status().is_success() => {}
+                        response => {
+                            let result = response.map(|response| response.status());
+                            warn!(target: "client", %url, ?result, "Alert webhook failed");
+                        }
+                    }),
+            );
+        }
+        if let Some((program, args)) = self.config.command.as_ref().and_then(|c| c.split_first()) {
+            match Command::new(program).args(args).arg(&payload).spawn() {
+                // Reap the process without blocking the client.
+                Ok(mut child) => {
+                    std::thread::spawn(move || child.wait());
+                }
+                Err(err) => warn!(target: "client", %program, ?err, "Failed to run alert command"),
+            }
+        }
+    }
+}
+
+/// Replaces the `{name}` placeholders in the template with the values,
+/// escaped to be embeddable in JSON strings.
+fn render_payload(template: &str, values: &[(&str, &str)]) -> String {
+    let mut payload = template.to_string();
+    for (name, value) in values {
+        let escaped = serde_json::to_string(value).unwrap();
+        payload = payload.replace(&format!("{{{}}}", name), &escaped[1..escaped.len() - 1]);
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_payload() {
+        let payload = render_payload(
+            r#"{"alert":"{alert}","message":"{message}","height":{height}}"#,
+            &[("alert", "low_peers"), ("message", "a \"quoted\" message"), ("height", "42")],
+        );
+        assert_eq!(
+            payload,
+            r#"{"alert":"low_peers","message":"a \"quoted\" message","height":42}"#
+        );
+        let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(payload["message"], "a \"quoted\" message");
+    }
+}
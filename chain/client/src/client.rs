@@ -12,7 +12,7 @@ use near_chunks::logic::{
     cares_about_shard_this_or_next_epoch, decode_encoded_chunk, persist_chunk,
 };
 use near_client_primitives::debug::ChunkProduction;
-use near_primitives::time::Clock;
+use near_network::time;
 use near_store::metadata::DbKind;
 use tracing::{debug, error, info, trace, warn};
 
@@ -28,7 +28,7 @@ use near_chain::{
     DoneApplyChunkCallback, Doomslug, DoomslugThresholdMode, Provenance,
     RuntimeWithEpochManagerAdapter,
 };
-use near_chain_configs::{ClientConfig, UpdateableClientConfig};
+use near_chain_configs::{ClientConfig, EmptyBlocksPolicy, UpdateableClientConfig};
 use near_chunks::ShardsManager;
 use near_network::types::{
     HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter, ReasonForBan,
@@ -50,11 +50,11 @@ use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
 
 use crate::adapter::ProcessTxResponse;
-use crate::debug::BlockProductionTracker;
 use crate::debug::PRODUCTION_TIMES_CACHE_SIZE;
-use crate::sync::block::BlockSync;
+use crate::debug::{BlockProductionTracker, BlockPropagationTracker};
+use crate::dry_run::DryRunProducer;
+use crate::epoch_snapshot::EpochSnapshotter;
 use crate::sync::epoch::EpochSync;
-use crate::sync::header::HeaderSync;
 use crate::sync::state::{StateSync, StateSyncResult};
 use crate::{metrics, SyncStatus};
 use near_client_primitives::types::{Error, ShardSyncDownload, ShardSyncStatus};
@@ -82,6 +82,10 @@ const BLOCK_HORIZON: u64 = 500;
 /// number of blocks at the epoch start for which we will log more detailed info
 pub const EPOCH_START_INFO_BLOCKS: u64 = 500;
 
+/// Number of distinct chunk producers of the upcoming heights that a transaction
+/// is forwarded to, among the producers which the network knows a route to.
+const TX_FORWARDING_TARGETS: usize = 3;
+
 pub struct Client {
     /// Adversarial controls
     #[cfg(feature = "test_features")]
@@ -97,6 +101,8 @@ pub struct Client {
     #[cfg(feature = "sandbox")]
     pub(crate) accrued_fastforward_delta: near_primitives::types::BlockHeightDelta,
 
+    /// Clock the time is read from, faked in tests to control it.
+    pub clock: time::Clock,
     pub config: ClientConfig,
     pub sync_status: SyncStatus,
     pub chain: Chain,
@@ -122,10 +128,6 @@ pub struct Client {
         HashMap<CryptoHash, (StateSync, HashMap<u64, ShardSyncDownload>, BlocksCatchUpState)>,
     /// Keeps track of information needed to perform the initial Epoch Sync
     pub epoch_sync: EpochSync,
-    /// Keeps track of syncing headers.
-    pub header_sync: HeaderSync,
-    /// Keeps track of syncing block.
-    pub block_sync: BlockSync,
     /// Keeps track of syncing state.
     pub state_sync: StateSync,
     /// List of currently accumulated challenges.
@@ -141,6 +143,9 @@ pub struct Client {
     /// Block production timing information. Used only for debug purposes.
     /// Stores approval information and production time of the block
     pub block_production_info: BlockProductionTracker,
+    /// Delays of blocks received from the network, by producer.  Used only
+    /// for debug purposes.
+    pub block_propagation_info: BlockPropagationTracker,
     /// Chunk production timing information. Used only for debug purposes.
     pub chunk_production_info: lru::LruCache<(BlockHeight, ShardId), ChunkProduction>,
 
@@ -149,11 +154,24 @@ pub struct Client {
     tier1_accounts_cache: Option<(EpochId, Arc<AccountKeys>)>,
     /// Used when it is needed to create flat storage in background for some shards.
     flat_storage_creator: Option<FlatStorageCreator>,
+    /// Shadows production of `config.dry_run_validator`, if set.
+    pub(crate) dry_run_producer: Option<DryRunProducer>,
+    /// Snapshots the database at epoch boundaries if `config.epoch_snapshot`
+    /// is set.
+    pub(crate) epoch_snapshotter: Option<EpochSnapshotter>,
+    /// Epoch in which the state of untracked shards was last deleted, if
+    /// `config.minimal_chunk_producer` is set.
+    untracked_state_cleared_epoch: Option<EpochId>,
+    /// Block and chunk producers which the network knows a route to
+    /// (i.e. which have announced their accounts), as of the last `NetworkInfo`.
+    /// Used to route forwarded transactions.
+    pub known_producers: HashSet<AccountId>,
 }
 
 impl Client {
     pub(crate) fn update_client_config(&self, update_client_config: UpdateableClientConfig) {
         self.config.expected_shutdown.update(update_client_config.expected_shutdown);
+        self.config.produce_empty_blocks.update(update_client_config.produce_empty_blocks);
     }
 }
 
@@ -183,6 +201,7 @@ pub struct BlockDebugStatus {
 
 impl Client {
     pub fn new(
+        clock: time::Clock,
         config: ClientConfig,
         chain_genesis: ChainGenesis,
         runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
@@ -200,6 +219,9 @@ impl Client {
         let chain_config = ChainConfig {
             save_trie_changes: config.save_trie_changes,
             background_migration_threads: config.client_background_migration_threads,
+            save_account_outcome_ids: config.save_account_outcome_ids,
+            slow_chunk_threshold: config.slow_chunk_threshold,
+            apply_chunks_threads: config.apply_chunks_threads,
         };
         let chain = Chain::new(
             runtime_adapter.clone(),
@@ -222,11 +244,16 @@ impl Client {
             client_adapter.clone(),
             chain.store().new_read_only_chunks_store(),
             chain.head().ok(),
+        )
+        .with_recent_partial_chunks(
+            chain.store().store().clone(),
+            config.recent_partial_chunks_num_heights,
         );
         let sharded_tx_pool = ShardedTransactionPool::new(rng_seed);
         let sync_status = SyncStatus::AwaitingPeers;
         let genesis_block = chain.genesis_block();
         let epoch_sync = EpochSync::new(
+            &clock,
             network_adapter.clone(),
             genesis_block.header().epoch_id().clone(),
             genesis_block.header().next_epoch_id().clone(),
@@ -241,21 +268,20 @@ impl Client {
             EPOCH_SYNC_REQUEST_TIMEOUT,
             EPOCH_SYNC_PEER_TIMEOUT,
         );
-        let header_sync = HeaderSync::new(
+        let state_sync = StateSync::new(
+            clock.clone(),
             network_adapter.clone(),
-            config.header_sync_initial_timeout,
-            config.header_sync_progress_timeout,
-            config.header_sync_stall_ban_timeout,
-            config.header_sync_expected_height_per_second,
+            config.state_sync_timeout,
+            config.state_handover_addr.clone(),
         );
-        let block_sync =
-            BlockSync::new(network_adapter.clone(), config.block_fetch_horizon, config.archive);
-        let state_sync = StateSync::new(network_adapter.clone(), config.state_sync_timeout);
         let num_block_producer_seats = config.num_block_producer_seats as usize;
+        let dry_run_producer = config.dry_run_validator.clone().map(DryRunProducer::new);
+        let epoch_snapshotter = config.epoch_snapshot.clone().map(EpochSnapshotter::new);
         let data_parts = runtime_adapter.num_data_parts();
         let parity_parts = runtime_adapter.num_total_parts() - data_parts;
 
         let doomslug = Doomslug::new(
+            clock.clone(),
             chain.store().largest_target_height()?,
             config.min_block_production_delay,
             config.max_block_production_delay,
@@ -275,6 +301,8 @@ impl Client {
             produce_invalid_tx_in_chunks: false,
             #[cfg(feature = "sandbox")]
             accrued_fastforward_delta: 0,
+            last_time_head_progress_made: clock.now_std(),
+            clock,
             config,
             sync_status,
             chain,
@@ -293,24 +321,26 @@ impl Client {
             pending_approvals: lru::LruCache::new(num_block_producer_seats),
             catchup_state_syncs: HashMap::new(),
             epoch_sync,
-            header_sync,
-            block_sync,
             state_sync,
             challenges: Default::default(),
             rs_for_chunk_production: ReedSolomonWrapper::new(data_parts, parity_parts),
             rebroadcasted_blocks: lru::LruCache::new(NUM_REBROADCAST_BLOCKS),
-            last_time_head_progress_made: Clock::instant(),
             block_production_info: BlockProductionTracker::new(),
+            block_propagation_info: BlockPropagationTracker::new(),
             chunk_production_info: lru::LruCache::new(PRODUCTION_TIMES_CACHE_SIZE),
             tier1_accounts_cache: None,
             flat_storage_creator,
+            dry_run_producer,
+            epoch_snapshotter,
+            untracked_state_cleared_epoch: None,
+            known_producers: HashSet::new(),
         })
     }
 
     // Checks if it's been at least `stall_timeout` since the last time the head was updated, or
     // this method was called. If yes, rebroadcasts the current head.
     pub fn check_head_progress_stalled(&mut self, stall_timeout: Duration) -> Result<(), Error> {
-        if Clock::instant() > self.last_time_head_progress_made + stall_timeout
+        if self.clock.now_std() > self.last_time_head_progress_made + stall_timeout
             && !self.sync_status.is_syncing()
         {
             let block = self.chain.get_block(&self.chain.head()?.last_block_hash)?;
@@ -318,7 +348,7 @@ impl Client {
                 PeerManagerMessageRequest::NetworkRequests(NetworkRequests::Block { block: block })
                     .with_span_context(),
             );
-            self.last_time_head_progress_made = Clock::instant();
+            self.last_time_head_progress_made = self.clock.now_std();
         }
         Ok(())
     }
@@ -492,6 +522,21 @@ impl Client {
             .count()
     }
 
+    /// Whether to produce a block without new chunks on top of `prev`, see
+    /// `ClientConfig::produce_empty_blocks`.
+    fn should_produce_empty_block(&self, prev: &BlockHeader) -> bool {
+        match self.config.produce_empty_blocks.get() {
+            EmptyBlocksPolicy::Always => true,
+            EmptyBlocksPolicy::Never => false,
+            EmptyBlocksPolicy::IfIdleLongerThan(max_idle) => self
+                .clock
+                .now_utc_chrono()
+                .signed_duration_since(prev.timestamp())
+                .to_std()
+                .map_or(false, |idle| idle >= max_idle),
+        }
+    }
+
     /// Produce block if we are block producer for given `next_height` block height.
     /// Either returns produced block (not applied) or error.
     pub fn produce_block(&mut self, next_height: BlockHeight) -> Result<Option<Block>, Error> {
@@ -519,8 +564,6 @@ impl Client {
         let prev_hash = head.last_block_hash;
         let prev_height = head.height;
         let prev_prev_hash = *prev.prev_hash();
-        let prev_epoch_id = prev.epoch_id().clone();
-        let prev_next_bp_hash = *prev.next_bp_hash();
 
         // Check and update the doomslug tip here. This guarantees that our endorsement will be in the
         // doomslug witness. Have to do it before checking the ability to produce a block.
@@ -558,8 +601,7 @@ impl Client {
         debug!(target: "client", "{:?} Producing block at height {}, parent {} @ {}, {} new chunks", validator_signer.validator_id(),
                next_height, prev.height(), format_hash(head.last_block_hash), new_chunks.len());
 
-        // If we are producing empty blocks and there are no transactions.
-        if !self.config.produce_empty_blocks && new_chunks.is_empty() {
+        if new_chunks.is_empty() && !self.should_produce_empty_block(&prev) {
             debug!(target: "client", "Empty blocks, skipping block production");
             return Ok(None);
         }
@@ -594,10 +636,55 @@ impl Client {
 
         debug_assert_eq!(approvals_map.len(), 0);
 
-        let next_epoch_id = self
-            .runtime_adapter
-            .get_next_epoch_id_from_prev_block(&head.last_block_hash)
-            .expect("Epoch hash should exist at this point");
+        let prev_block = self.chain.get_block(&prev_hash)?;
+
+        // Add debug information about the block production (and info on when did the chunks arrive).
+        self.block_production_info.record_block_production(
+            next_height,
+            BlockProductionTracker::construct_chunk_collection_info(
+                next_height,
+                &epoch_id,
+                prev_block.chunks().len() as ShardId,
+                &new_chunks,
+                &*self.runtime_adapter,
+            )?,
+        );
+
+        let block = self.assemble_block(
+            &prev_block,
+            next_height,
+            new_chunks,
+            approvals,
+            &*validator_signer,
+        )?;
+
+        // Update latest known even before returning block out, to prevent race conditions.
+        self.chain.mut_store().save_latest_known(LatestKnown {
+            height: next_height,
+            seen: block.header().raw_timestamp(),
+        })?;
+
+        metrics::BLOCK_PRODUCED_TOTAL.inc();
+
+        Ok(Some(block))
+    }
+
+    /// Builds and signs a block on top of `prev_block` with the given chunks and approvals.
+    ///
+    /// Unlike `produce_block`, this performs no checks on whether we are the producer for
+    /// `next_height` and has no side effects, so it is also used for dry-run production.
+    pub(crate) fn assemble_block(
+        &self,
+        prev_block: &Block,
+        next_height: BlockHeight,
+        new_chunks: HashMap<ShardId, (ShardChunkHeader, chrono::DateTime<chrono::Utc>, AccountId)>,
+        approvals: Vec<Option<near_crypto::Signature>>,
+        validator_signer: &dyn ValidatorSigner,
+    ) -> Result<Block, Error> {
+        let prev_hash = *prev_block.hash();
+        let prev_header = prev_block.header();
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&prev_hash)?;
+        let next_epoch_id = self.runtime_adapter.get_next_epoch_id_from_prev_block(&prev_hash)?;
 
         let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&epoch_id)?;
         let gas_price_adjustment_rate =
@@ -605,19 +692,19 @@ impl Client {
         let min_gas_price = self.chain.block_economics_config.min_gas_price(protocol_version);
         let max_gas_price = self.chain.block_economics_config.max_gas_price(protocol_version);
 
-        let next_bp_hash = if prev_epoch_id != epoch_id {
+        let next_bp_hash = if prev_header.epoch_id() != &epoch_id {
             Chain::compute_bp_hash(
                 &*self.runtime_adapter,
-                next_epoch_id,
+                next_epoch_id.clone(),
                 epoch_id.clone(),
                 &prev_hash,
             )?
         } else {
-            prev_next_bp_hash
+            *prev_header.next_bp_hash()
         };
 
         #[cfg(feature = "sandbox")]
-        let timestamp_override = Some(Clock::utc() + self.sandbox_delta_time());
+        let timestamp_override = Some(self.clock.now_utc_chrono() + self.sandbox_delta_time());
         #[cfg(not(feature = "sandbox"))]
         let timestamp_override = None;
 
@@ -630,20 +717,7 @@ impl Client {
         // The ordinal of the next Block will be equal to this amount plus one.
         let block_ordinal: NumBlocks = block_merkle_tree.size() + 1;
         let prev_block_extra = self.chain.get_block_extra(&prev_hash)?;
-        let prev_block = self.chain.get_block(&prev_hash)?;
-        let mut chunks = Chain::get_prev_chunk_headers(&*self.runtime_adapter, &prev_block)?;
-
-        // Add debug information about the block production (and info on when did the chunks arrive).
-        self.block_production_info.record_block_production(
-            next_height,
-            BlockProductionTracker::construct_chunk_collection_info(
-                next_height,
-                &epoch_id,
-                chunks.len() as ShardId,
-                &new_chunks,
-                &*self.runtime_adapter,
-            )?,
-        );
+        let mut chunks = Chain::get_prev_chunk_headers(&*self.runtime_adapter, prev_block)?;
 
         // Collect new chunks.
         for (shard_id, (mut chunk_header, _, _)) in new_chunks {
@@ -651,28 +725,21 @@ impl Client {
             chunks[shard_id as usize] = chunk_header;
         }
 
-        let prev_header = &prev_block.header();
-
-        let next_epoch_id =
-            self.runtime_adapter.get_next_epoch_id_from_prev_block(&head.last_block_hash)?;
-
-        let minted_amount =
-            if self.runtime_adapter.is_next_block_epoch_start(&head.last_block_hash)? {
-                Some(self.runtime_adapter.get_epoch_minted_amount(&next_epoch_id)?)
-            } else {
-                None
-            };
+        let minted_amount = if self.runtime_adapter.is_next_block_epoch_start(&prev_hash)? {
+            Some(self.runtime_adapter.get_epoch_minted_amount(&next_epoch_id)?)
+        } else {
+            None
+        };
 
-        let epoch_sync_data_hash =
-            if self.runtime_adapter.is_next_block_epoch_start(&head.last_block_hash)? {
-                Some(self.runtime_adapter.get_epoch_sync_data_hash(
-                    prev_block.hash(),
-                    &epoch_id,
-                    &next_epoch_id,
-                )?)
-            } else {
-                None
-            };
+        let epoch_sync_data_hash = if self.runtime_adapter.is_next_block_epoch_start(&prev_hash)? {
+            Some(self.runtime_adapter.get_epoch_sync_data_hash(
+                &prev_hash,
+                &epoch_id,
+                &next_epoch_id,
+            )?)
+        } else {
+            None
+        };
 
         // Get all the current challenges.
         // TODO(2445): Enable challenges when they are working correctly.
@@ -682,7 +749,7 @@ impl Client {
         let next_epoch_protocol_version =
             self.runtime_adapter.get_epoch_protocol_version(&next_epoch_id)?;
 
-        let block = Block::produce(
+        Ok(Block::produce(
             this_epoch_protocol_version,
             next_epoch_protocol_version,
             prev_header,
@@ -699,21 +766,11 @@ impl Client {
             minted_amount,
             prev_block_extra.challenges_result.clone(),
             vec![],
-            &*validator_signer,
+            validator_signer,
             next_bp_hash,
             block_merkle_root,
             timestamp_override,
-        );
-
-        // Update latest known even before returning block out, to prevent race conditions.
-        self.chain.mut_store().save_latest_known(LatestKnown {
-            height: next_height,
-            seen: block.header().raw_timestamp(),
-        })?;
-
-        metrics::BLOCK_PRODUCED_TOTAL.inc();
-
-        Ok(Some(block))
+        ))
     }
 
     pub fn produce_chunk(
@@ -761,6 +818,40 @@ impl Client {
             validator_signer.validator_id()
         );
 
+        let (encoded_chunk, merkle_paths, outgoing_receipts) = self.assemble_chunk(
+            prev_block_hash,
+            epoch_id,
+            last_header,
+            next_height,
+            shard_id,
+            &*validator_signer,
+        )?;
+
+        metrics::CHUNK_PRODUCED_TOTAL.inc();
+        self.chunk_production_info.put(
+            (next_height, shard_id),
+            ChunkProduction {
+                chunk_production_time: Some(self.clock.now_utc_chrono()),
+                chunk_production_duration_millis: Some(timer.elapsed().as_millis() as u64),
+            },
+        );
+        Ok(Some((encoded_chunk, merkle_paths, outgoing_receipts)))
+    }
+
+    /// Builds and signs the chunk for `shard_id` at `next_height` on top of `prev_block_hash`.
+    ///
+    /// Unlike `produce_chunk`, this does not check whether we are the chunk producer, so it is
+    /// also used for dry-run production.  Transactions are taken from the pool but immediately
+    /// reintroduced, so the pool is left unchanged.
+    pub(crate) fn assemble_chunk(
+        &mut self,
+        prev_block_hash: CryptoHash,
+        epoch_id: &EpochId,
+        last_header: ShardChunkHeader,
+        next_height: BlockHeight,
+        shard_id: ShardId,
+        validator_signer: &dyn ValidatorSigner,
+    ) -> Result<(EncodedShardChunk, Vec<MerklePath>, Vec<Receipt>), Error> {
         let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, epoch_id)?;
         let chunk_extra = self
             .chain
@@ -819,7 +910,7 @@ impl Client {
             &outgoing_receipts,
             outgoing_receipts_root,
             tx_root,
-            &*validator_signer,
+            validator_signer,
             &mut self.rs_for_chunk_production,
             protocol_version,
         )?;
@@ -836,15 +927,7 @@ impl Client {
             outgoing_receipts.len(),
         );
 
-        metrics::CHUNK_PRODUCED_TOTAL.inc();
-        self.chunk_production_info.put(
-            (next_height, shard_id),
-            ChunkProduction {
-                chunk_production_time: Some(Clock::utc()),
-                chunk_production_duration_millis: Some(timer.elapsed().as_millis() as u64),
-            },
-        );
-        Ok(Some((encoded_chunk, merkle_paths, outgoing_receipts)))
+        Ok((encoded_chunk, merkle_paths, outgoing_receipts))
     }
 
     #[cfg(feature = "test_features")]
@@ -993,7 +1076,12 @@ impl Client {
         was_requested: bool,
         apply_chunks_done_callback: DoneApplyChunkCallback,
     ) -> Result<(), near_chain::Error> {
-        self.chain.blocks_delay_tracker.mark_block_received(&block, Clock::instant(), Clock::utc());
+        let received_at = self.clock.now_utc_chrono();
+        let first_received = self.chain.blocks_delay_tracker.mark_block_received(
+            &block,
+            self.clock.now_std(),
+            received_at,
+        );
         // To protect ourselves from spamming, we do some pre-check on block height before we do any
         // real processing.
         if !self.check_block_height(&block, was_requested)? {
@@ -1004,10 +1092,26 @@ impl Client {
         }
         let prev_hash = *block.header().prev_hash();
         let block = block.into();
-        self.verify_and_rebroadcast_block(&block, was_requested, &peer_id)?;
+        let validated = self.verify_and_rebroadcast_block(&block, was_requested, &peer_id)?;
+        // Only blocks with a valid header are recorded, so that peers can't
+        // skew the delays of a producer with made up blocks.  Requested blocks
+        // are old, their delays say nothing about propagation.
+        if first_received && validated && !was_requested {
+            if let Ok(producer) = self
+                .runtime_adapter
+                .get_block_producer(block.header().epoch_id(), block.header().height())
+            {
+                let delay = (received_at - block.header().timestamp()).to_std().unwrap_or_default();
+                self.block_propagation_info.record(&producer, block.header().height(), delay);
+            }
+        }
         let provenance =
             if was_requested { near_chain::Provenance::SYNC } else { near_chain::Provenance::NONE };
-        let res = self.start_process_block(block, provenance, apply_chunks_done_callback);
+        // Requested blocks don't count towards the limits of the peer in the orphan and
+        // missing chunks pools, as the peer didn't send them on its own.
+        let source_peer_id = if was_requested { None } else { Some(peer_id.clone()) };
+        let res =
+            self.start_process_block(block, provenance, source_peer_id, apply_chunks_done_callback);
         match &res {
             Err(near_chain::Error::Orphan) => {
                 if !self.chain.is_orphan(&prev_hash) {
@@ -1058,12 +1162,13 @@ impl Client {
     /// Ignore all other errors because the full block will be processed later.
     /// Note that this happens before the full block processing logic because we want blocks to be
     /// propagated in the network fast.
+    /// Returns whether the block could be verified.
     fn verify_and_rebroadcast_block(
         &mut self,
         block: &MaybeValidated<Block>,
         was_requested: bool,
         peer_id: &PeerId,
-    ) -> Result<(), near_chain::Error> {
+    ) -> Result<bool, near_chain::Error> {
         let res = self.chain.process_block_header(block.header(), &mut vec![]);
         let res = res.and_then(|_| self.chain.validate_block(block));
         match res {
@@ -1077,7 +1182,7 @@ impl Client {
                 {
                     self.rebroadcast_block(block.as_ref().into_inner());
                 }
-                Ok(())
+                Ok(true)
             }
             Err(e) if e.is_bad_data() => {
                 // We don't ban a peer if the block timestamp is too much in the future since it's possible
@@ -1094,7 +1199,7 @@ impl Client {
                 // previous block) than we will get MissingBlock errors.  In
                 // those cases we shouldn’t reject the block instead passing
                 // it along.  Eventually, it’ll get saved as an orphan.
-                Ok(())
+                Ok(false)
             }
         }
     }
@@ -1102,11 +1207,13 @@ impl Client {
     /// Start the processing of a block. Note that this function will return before
     /// the full processing is finished because applying chunks is done asynchronously
     /// in the rayon thread pool.
+    /// `peer_id`: the peer which sent the block, unless it was produced or requested by the node.
     /// `apply_chunks_done_callback`: a callback that will be called when applying chunks is finished.
     pub fn start_process_block(
         &mut self,
         block: MaybeValidated<Block>,
         provenance: Provenance,
+        peer_id: Option<PeerId>,
         apply_chunks_done_callback: DoneApplyChunkCallback,
     ) -> Result<(), near_chain::Error> {
         let mut block_processing_artifacts = BlockProcessingArtifact::default();
@@ -1120,6 +1227,7 @@ impl Client {
                 &me,
                 block,
                 provenance,
+                peer_id,
                 &mut block_processing_artifacts,
                 apply_chunks_done_callback,
             )
@@ -1278,7 +1386,9 @@ impl Client {
         let chunk_header = partial_chunk.cloned_header();
         persist_chunk(partial_chunk, shard_chunk, self.chain.mut_store())
             .expect("Could not persist chunk");
-        self.chain.blocks_delay_tracker.mark_chunk_completed(&chunk_header, Clock::utc());
+        self.chain
+            .blocks_delay_tracker
+            .mark_chunk_completed(&chunk_header, self.clock.now_utc_chrono());
         // We're marking chunk as accepted.
         self.chain.blocks_with_missing_chunks.accept_chunk(&chunk_header.chunk_hash());
         // If this was the last chunk that was missing for a block, it will be processed now.
@@ -1306,7 +1416,10 @@ impl Client {
         self.prev_block_to_chunk_headers_ready_for_inclusion
             .get_mut(prev_block_hash)
             .unwrap()
-            .insert(chunk_header.shard_id(), (chunk_header, chrono::Utc::now(), chunk_producer));
+            .insert(
+                chunk_header.shard_id(),
+                (chunk_header, self.clock.now_utc_chrono(), chunk_producer),
+            );
     }
 
     pub fn sync_block_headers(
@@ -1333,7 +1446,7 @@ impl Client {
                 self.chain.get_block_header(&last_final_hash)?.height()
             };
             self.doomslug.set_tip(
-                Clock::instant(),
+                self.clock.now_std(),
                 tip.last_block_hash,
                 tip.height,
                 last_final_height,
@@ -1354,7 +1467,7 @@ impl Client {
         } else {
             self.chain.get_block_header(&last_final_hash)?.height()
         };
-        self.doomslug.set_tip(Clock::instant(), tip.last_block_hash, height, last_final_height);
+        self.doomslug.set_tip(self.clock.now_std(), tip.last_block_hash, height, last_final_height);
 
         Ok(())
     }
@@ -1465,6 +1578,11 @@ impl Client {
             if let Err(err) = self.send_network_chain_info() {
                 error!(target:"client","Failed to update network chain info: {err}");
             }
+
+            if provenance != Provenance::SYNC && !self.sync_status.is_syncing() {
+                self.dry_run_production(&block);
+                self.snapshot_at_epoch_boundary(&block);
+            }
         }
 
         if let Some(validator_signer) = self.validator_signer.clone() {
@@ -1615,9 +1733,10 @@ impl Client {
         blocks_missing_chunks: Vec<BlockMissingChunks>,
         orphans_missing_chunks: Vec<OrphanMissingChunks>,
     ) {
-        let now = Clock::utc();
+        let now = self.clock.now_utc_chrono();
         for BlockMissingChunks { prev_hash, missing_chunks } in blocks_missing_chunks {
             for chunk in &missing_chunks {
+                log_chunk_missing(chunk);
                 self.chain.blocks_delay_tracker.mark_chunk_requested(chunk, now);
             }
             self.shards_mgr.request_chunks(
@@ -1634,6 +1753,7 @@ impl Client {
             orphans_missing_chunks
         {
             for chunk in &missing_chunks {
+                log_chunk_missing(chunk);
                 self.chain.blocks_delay_tracker.mark_chunk_requested(chunk, now);
             }
             self.shards_mgr.request_chunks_for_orphan(
@@ -1848,7 +1968,37 @@ impl Client {
                     return;
                 }
             };
-        self.doomslug.on_approval_message(Clock::instant(), approval, &block_producer_stakes);
+        self.doomslug.on_approval_message(self.clock.now_std(), approval, &block_producer_stakes);
+    }
+
+    /// Chunk producers of `shard_id` in `epoch_id` to forward a transaction to.
+    ///
+    /// The producers of the upcoming heights are taken in order, skipping the ones which the
+    /// network doesn't know a route to, until TX_FORWARDING_TARGETS of them are found.
+    /// If the network doesn't know a route to any of them (for example, the network info
+    /// hasn't been received yet), the producers at fixed horizons are used.
+    fn tx_forwarding_targets(
+        &self,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+    ) -> Result<Vec<AccountId>, Error> {
+        let producers = (2..=TX_ROUTING_HEIGHT_HORIZON * 2)
+            .map(|horizon| {
+                self.chain.find_chunk_producer_for_forwarding(epoch_id, shard_id, horizon)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let targets =
+            select_tx_forwarding_targets(producers, &self.known_producers, TX_FORWARDING_TARGETS);
+        if !targets.is_empty() {
+            return Ok(targets);
+        }
+        (2..=TX_ROUTING_HEIGHT_HORIZON)
+            .chain(std::iter::once(TX_ROUTING_HEIGHT_HORIZON * 2))
+            .map(|horizon| {
+                self.chain.find_chunk_producer_for_forwarding(epoch_id, shard_id, horizon)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Into::into)
     }
 
     /// Forwards given transaction to upcoming validators.
@@ -1858,24 +2008,13 @@ impl Client {
         let head = self.chain.head()?;
         let maybe_next_epoch_id = self.get_next_epoch_id_if_at_boundary(&head)?;
 
-        let mut validators = HashSet::new();
-        for horizon in
-            (2..=TX_ROUTING_HEIGHT_HORIZON).chain(vec![TX_ROUTING_HEIGHT_HORIZON * 2].into_iter())
-        {
-            let validator =
-                self.chain.find_chunk_producer_for_forwarding(epoch_id, shard_id, horizon)?;
-            validators.insert(validator);
-            if let Some(next_epoch_id) = &maybe_next_epoch_id {
-                let next_shard_id = self
-                    .runtime_adapter
-                    .account_id_to_shard_id(&tx.transaction.signer_id, next_epoch_id)?;
-                let validator = self.chain.find_chunk_producer_for_forwarding(
-                    next_epoch_id,
-                    next_shard_id,
-                    horizon,
-                )?;
-                validators.insert(validator);
-            }
+        let mut validators: HashSet<AccountId> =
+            self.tx_forwarding_targets(epoch_id, shard_id)?.into_iter().collect();
+        if let Some(next_epoch_id) = &maybe_next_epoch_id {
+            let next_shard_id = self
+                .runtime_adapter
+                .account_id_to_shard_id(&tx.transaction.signer_id, next_epoch_id)?;
+            validators.extend(self.tx_forwarding_targets(next_epoch_id, next_shard_id)?);
         }
 
         if let Some(account_id) = self.validator_signer.as_ref().map(|bp| bp.validator_id()) {
@@ -2131,12 +2270,19 @@ impl Client {
                     HashMap::new()
                 }
             };
+            let clock = self.clock.clone();
             let state_sync_timeout = self.config.state_sync_timeout;
+            let state_handover_addr = self.config.state_handover_addr.clone();
             let epoch_id = self.chain.get_block(&sync_hash)?.header().epoch_id().clone();
             let (state_sync, new_shard_sync, blocks_catch_up_state) =
                 self.catchup_state_syncs.entry(sync_hash).or_insert_with(|| {
                     (
-                        StateSync::new(network_adapter1, state_sync_timeout),
+                        StateSync::new(
+                            clock,
+                            network_adapter1,
+                            state_sync_timeout,
+                            state_handover_addr,
+                        ),
                         new_shard_sync,
                         BlocksCatchUpState::new(sync_hash, epoch_id),
                     )
@@ -2234,7 +2380,11 @@ impl Client {
         // A RPC node should do regular garbage collection.
         if !self.config.archive {
             let tries = self.runtime_adapter.get_tries();
-            return self.chain.clear_data(tries, &self.config.gc);
+            self.chain.clear_data(tries, &self.config.gc)?;
+            if self.config.minimal_chunk_producer {
+                self.clear_untracked_shards_state()?;
+            }
+            return Ok(());
         }
 
         // An archival node with split storage should perform garbage collection
@@ -2250,7 +2400,30 @@ impl Client {
 
         // An archival node with legacy storage or in the midst of migration to split
         // storage should do the legacy clear_archive_data.
-        self.chain.clear_archive_data(self.config.gc.gc_blocks_limit)
+        self.chain.clear_archive_data(&self.config.gc, &self.config.archival_policy)
+    }
+
+    /// Deletes the state of the shards the node stopped tracking, once per
+    /// epoch after the first block of the epoch is final so that no fork of
+    /// the previous epoch needs it anymore.  Waits while state is being
+    /// synced, since the shards to sync are chosen by a different block.
+    fn clear_untracked_shards_state(&mut self) -> Result<(), near_chain::Error> {
+        if self.sync_status.is_syncing() || !self.catchup_state_syncs.is_empty() {
+            return Ok(());
+        }
+        let final_head = self.chain.final_head()?;
+        if self.untracked_state_cleared_epoch.as_ref() == Some(&final_head.epoch_id)
+            || self.chain.head()?.epoch_id != final_head.epoch_id
+        {
+            return Ok(());
+        }
+        let me = self.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+        let cleared = self.chain.clear_untracked_shards_state(&me)?;
+        if !cleared.is_empty() {
+            info!(target: "client", epoch_id = ?final_head.epoch_id, shards = ?cleared, "Deleted the state of untracked shards");
+        }
+        self.untracked_state_cleared_epoch = Some(final_head.epoch_id);
+        Ok(())
     }
 }
 
@@ -2383,8 +2556,12 @@ impl Client {
         };
         let tier1_accounts = self.get_tier1_accounts(&tip)?;
         let block = self.chain.get_block(&tip.last_block_hash)?;
+        // Archival nodes hold all the blocks, even if the hot storage is garbage collected.
+        let earliest_height =
+            if self.config.archive { self.chain.genesis().height() } else { self.chain.tail()? };
         self.network_adapter.do_send(
-            SetChainInfo(ChainInfo { block, tracked_shards, tier1_accounts }).with_span_context(),
+            SetChainInfo(ChainInfo { block, earliest_height, tracked_shards, tier1_accounts })
+                .with_span_context(),
         );
         Ok(())
     }
@@ -2411,3 +2588,33 @@ impl Client {
         Ok(ret)
     }
 }
+
+fn log_chunk_missing(chunk: &ShardChunkHeader) {
+    tracing::info!(
+        target: near_o11y::events::TARGET,
+        event = near_o11y::events::CHUNK_MISSING,
+        shard_id = chunk.shard_id(),
+        chunk_hash = %chunk.chunk_hash().0,
+        height_created = chunk.height_created(),
+        prev_block_hash = %chunk.prev_block_hash(),
+    );
+}
+
+/// Selects up to `max` distinct accounts out of `producers` (in order),
+/// which are present in `known`.
+pub(crate) fn select_tx_forwarding_targets(
+    producers: impl IntoIterator<Item = AccountId>,
+    known: &HashSet<AccountId>,
+    max: usize,
+) -> Vec<AccountId> {
+    let mut targets = vec![];
+    for p in producers {
+        if targets.len() >= max {
+            break;
+        }
+        if known.contains(&p) && !targets.contains(&p) {
+            targets.push(p);
+        }
+    }
+    targets
+}
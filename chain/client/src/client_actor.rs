@@ -6,19 +6,29 @@
 //! https://github.com/near/nearcore/issues/7899
 
 use crate::adapter::{
-    BlockApproval, BlockHeadersResponse, BlockResponse, ProcessTxRequest, ProcessTxResponse,
-    RecvChallenge, RecvPartialEncodedChunk, RecvPartialEncodedChunkForward,
-    RecvPartialEncodedChunkRequest, RecvPartialEncodedChunkResponse, SetNetworkInfo, StateResponse,
+    BlockApproval, BlockHeadersResponse, BlockResponse, DataGarbageCollected, ProcessTxRequest,
+    ProcessTxResponse, RecvChallenge, RecvPartialEncodedChunk, RecvPartialEncodedChunkForward,
+    RecvPartialEncodedChunkRequest, RecvPartialEncodedChunkResponse, SetNetworkInfo,
+    StateDeltaResponse, StateResponse,
 };
+use crate::alerts::Alerter;
 use crate::client::{Client, EPOCH_START_INFO_BLOCKS};
 use crate::config_updater::ConfigUpdater;
 use crate::debug::new_network_info_view;
+use crate::gas_price_estimate::{update_gas_price_window, GasPriceWindow};
 use crate::info::{display_sync_status, InfoHelper};
 use crate::metrics::PARTIAL_ENCODED_CHUNK_RESPONSE_DELAY;
 use crate::sync::state::{StateSync, StateSyncResult};
+use crate::sync_actor::{
+    start_sync_actor, RunStateSyncStep, SetSyncStatus, StartSync, SyncActor, SyncBlockHeaders,
+    SyncNetworkInfo,
+};
+use crate::view_client::BlockTimestampIndex;
 use crate::{metrics, StatusResponse};
 use actix::dev::SendError;
-use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message};
+use actix::{
+    Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message, MessageResult, ResponseFuture,
+};
 use actix_rt::ArbiterHandle;
 use borsh::BorshSerialize;
 use chrono::DateTime;
@@ -26,8 +36,6 @@ use near_chain::chain::{
     do_apply_chunks, ApplyStatePartsRequest, ApplyStatePartsResponse, BlockCatchUpRequest,
     BlockCatchUpResponse, StateSplitRequest, StateSplitResponse,
 };
-use near_chain::test_utils::format_hash;
-#[cfg(feature = "test_features")]
 use near_chain::ChainStoreAccess;
 use near_chain::{
     byzantine_assert, near_chain_primitives, Block, BlockHeader, BlockProcessingArtifact,
@@ -37,40 +45,44 @@ use near_chain_configs::ClientConfig;
 use near_chunks::client::ShardsManagerResponse;
 use near_chunks::logic::cares_about_shard_this_or_next_epoch;
 use near_client_primitives::types::{
-    Error, GetClientConfig, GetClientConfigError, GetNetworkInfo, NetworkInfoResponse, Status,
-    StatusError, StatusSyncInfo, SyncStatus,
+    Error, GetClientConfig, GetClientConfigError, GetHealth, GetNetworkInfo, HealthCheckResult,
+    HealthReport, NetworkInfoResponse, Status, StatusError, StatusSyncInfo, SubscribeHead,
+    SyncStatus,
 };
+use near_network::time;
 #[cfg(feature = "test_features")]
 use near_network::types::NetworkAdversarialMessage;
 use near_network::types::ReasonForBan;
 use near_network::types::{
-    NetworkInfo, NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest,
+    GarbageCollectedData, NetworkInfo, NetworkRequests, PeerManagerAdapter,
+    PeerManagerMessageRequest, StateDeltaResponseInfo,
 };
 use near_o11y::{handler_debug_span, OpenTelemetrySpanExt, WithSpanContext, WithSpanContextExt};
 use near_performance_metrics;
 use near_performance_metrics_macros::perf;
+use near_primitives::block::Tip;
 use near_primitives::block_header::ApprovalType;
 use near_primitives::epoch_manager::RngSeed;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::StatePartKey;
-use near_primitives::time::{Clock, Utc};
+use near_primitives::time::Utc;
 use near_primitives::types::BlockHeight;
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::PROTOCOL_VERSION;
 use near_primitives::views::{DetailedDebugStatus, ValidatorInfo};
-use near_store::DBCol;
+use near_store::{DBCol, HEALTH_CHECK_KEY};
 use near_telemetry::TelemetryActor;
 use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, oneshot, watch};
 use tracing::{debug, error, info, trace, warn};
 
 /// Multiplier on `max_block_time` to wait until deciding that chain stalled.
@@ -87,7 +99,7 @@ pub struct ClientActor {
     my_address: Addr<ClientActor>,
     pub(crate) client: Client,
     network_adapter: Arc<dyn PeerManagerAdapter>,
-    network_info: NetworkInfo,
+    pub(crate) network_info: NetworkInfo,
     /// Identity that represents this Client at the network level.
     /// It is used as part of the messages that identify this client.
     node_id: PeerId,
@@ -95,6 +107,8 @@ pub struct ClientActor {
     last_validator_announce_time: Option<Instant>,
     /// Info helper.
     info_helper: InfoHelper,
+    /// Sends alerts about problems with the node, if configured.
+    alerter: Option<Alerter>,
 
     /// Last time handle_block_production method was called
     block_production_next_attempt: DateTime<Utc>,
@@ -104,13 +118,20 @@ pub struct ClientActor {
 
     block_production_started: bool,
     doomslug_timer_next_attempt: DateTime<Utc>,
-    sync_timer_next_attempt: DateTime<Utc>,
     chunk_request_retry_next_attempt: DateTime<Utc>,
     sync_started: bool,
+    /// Whether flat storage creation, which runs in the background after
+    /// start, has finished.
+    flat_storage_creation_done: bool,
+    /// Low priority messages waiting for the mailbox to be empty.
+    deferred_messages: VecDeque<DeferredMessage>,
     state_parts_task_scheduler: Box<dyn Fn(ApplyStatePartsRequest)>,
     block_catch_up_scheduler: Box<dyn Fn(BlockCatchUpRequest)>,
     state_split_scheduler: Box<dyn Fn(StateSplitRequest)>,
     state_parts_client_arbiter: Arbiter,
+    /// Runs header and block sync and asks for steps of state sync.
+    sync_actor: Addr<SyncActor>,
+    sync_arbiter: Arbiter,
 
     #[cfg(feature = "sandbox")]
     fastforward_delta: near_primitives::types::BlockHeightDelta,
@@ -121,13 +142,22 @@ pub struct ClientActor {
 
     /// Manages updating the config.
     config_updater: Option<ConfigUpdater>,
+
+    /// Index of final blocks by height shared with the view client.
+    block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+
+    /// Gas prices of the latest blocks shared with the view client.
+    gas_price_window: Arc<Mutex<GasPriceWindow>>,
+
+    /// Head of the chain, for the subscribers of head updates.
+    head_sender: watch::Sender<Tip>,
 }
 
 /// Blocks the program until given genesis time arrives.
-fn wait_until_genesis(genesis_time: &DateTime<Utc>) {
+fn wait_until_genesis(clock: &time::Clock, genesis_time: &DateTime<Utc>) {
     loop {
         // Get chrono::Duration::num_seconds() by deducting genesis_time from now.
-        let duration = genesis_time.signed_duration_since(Clock::utc());
+        let duration = genesis_time.signed_duration_since(clock.now_utc_chrono());
         let chrono_seconds = duration.num_seconds();
         // Check if number of seconds in chrono::Duration larger than zero.
         if chrono_seconds <= 0 {
@@ -145,6 +175,7 @@ fn wait_until_genesis(genesis_time: &DateTime<Utc>) {
 
 impl ClientActor {
     pub fn new(
+        clock: time::Clock,
         address: Addr<ClientActor>,
         config: ClientConfig,
         chain_genesis: ChainGenesis,
@@ -159,6 +190,8 @@ impl ClientActor {
         shutdown_signal: Option<broadcast::Sender<()>>,
         adv: crate::adversarial::Controls,
         config_updater: Option<ConfigUpdater>,
+        block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+        gas_price_window: Arc<Mutex<GasPriceWindow>>,
     ) -> Result<Self, Error> {
         let state_parts_arbiter = Arbiter::new();
         let self_addr = ctx.address();
@@ -170,23 +203,45 @@ impl ClientActor {
                 SyncJobsActor { client_addr: self_addr_clone }
             },
         );
-        wait_until_genesis(&chain_genesis.time);
+        wait_until_genesis(&clock, &chain_genesis.time);
         if let Some(vs) = &validator_signer {
             info!(target: "client", "Starting validator node: {}", vs.validator_id());
         }
         let info_helper = InfoHelper::new(Some(telemetry_actor), &config, validator_signer.clone());
+        let alerter = config.alerts.clone().map(Alerter::new);
+        let now = clock.now_utc_chrono();
         let client = Client::new(
-            config,
-            chain_genesis,
-            runtime_adapter,
+            clock.clone(),
+            config.clone(),
+            chain_genesis.clone(),
+            runtime_adapter.clone(),
             network_adapter.clone(),
             Arc::new(self_addr.clone()),
             validator_signer,
             enable_doomslug,
             rng_seed,
         )?;
+        let head = client.chain.head()?;
+        if let Err(err) =
+            update_gas_price_window(&gas_price_window, &client.chain, head.last_block_hash)
+        {
+            warn!(target: "client", ?err, "Failed to fill the gas price window");
+        }
+        let (head_sender, _) = watch::channel(head);
+        // Started last, so that the arbiter isn't left running if creating
+        // the client fails, and the sync actor finds the genesis in the store.
+        let sync_arbiter = Arbiter::new();
+        let sync_actor = start_sync_actor(
+            clock,
+            &sync_arbiter.handle(),
+            self_addr,
+            config,
+            chain_genesis,
+            runtime_adapter,
+            network_adapter.clone(),
+            adv.clone(),
+        );
 
-        let now = Utc::now();
         Ok(ClientActor {
             adv,
             my_address: address,
@@ -207,13 +262,15 @@ impl ClientActor {
             },
             last_validator_announce_time: None,
             info_helper,
+            alerter,
             block_production_next_attempt: now,
             log_summary_timer_next_attempt: now,
             block_production_started: false,
             doomslug_timer_next_attempt: now,
-            sync_timer_next_attempt: now,
             chunk_request_retry_next_attempt: now,
             sync_started: false,
+            flat_storage_creation_done: false,
+            deferred_messages: VecDeque::new(),
             state_parts_task_scheduler: create_sync_job_scheduler::<ApplyStatePartsRequest>(
                 sync_jobs_actor_addr.clone(),
             ),
@@ -224,15 +281,127 @@ impl ClientActor {
                 sync_jobs_actor_addr,
             ),
             state_parts_client_arbiter: state_parts_arbiter,
+            sync_actor,
+            sync_arbiter,
 
             #[cfg(feature = "sandbox")]
             fastforward_delta: 0,
             shutdown_signal,
             config_updater,
+            block_timestamp_index,
+            gas_price_window,
+            head_sender,
         })
     }
 }
 
+/// Priority with which the client actor processes a message.
+///
+/// High and normal priority messages are processed as they come out of the
+/// mailbox, in the order they arrived; the two are told apart only in the
+/// queue delay metric.  Low priority messages are deferred until the messages
+/// queued in the mailbox are processed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MessagePriority {
+    /// Approvals, blocks and chunk parts, which consensus waits for.
+    High,
+    /// Messages not listed elsewhere.
+    Normal,
+    /// Forwarded transactions and sync responses, so that a burst of them
+    /// doesn't delay the messages above.
+    Low,
+}
+
+impl MessagePriority {
+    /// Returns the priority of the message of given type, as passed to
+    /// `ClientActor::wrap`.
+    fn of(msg_type: &str) -> Self {
+        match msg_type {
+            "BlockApproval"
+            | "BlockResponse"
+            | "RecvPartialEncodedChunk"
+            | "RectPartialEncodedChunkForward"
+            | "RecvPartialEncodedChunkResponse" => Self::High,
+            "SyncBlockHeaders" | "StateResponse" | "StateDeltaResponse" => Self::Low,
+            _ => Self::Normal,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Normal => "normal",
+            Self::Low => "low",
+        }
+    }
+
+    /// Records the time a message created at given time waited before being
+    /// processed.
+    fn observe_queue_delay(self, created: Instant) {
+        metrics::CLIENT_MESSAGE_QUEUE_DELAY
+            .with_label_values(&[self.as_str()])
+            .observe(created.elapsed().as_secs_f64());
+    }
+}
+
+/// Low priority message deferred by the client actor, with the sender of the
+/// response if the message has one.
+enum DeferredMessage {
+    ForwardedTx(WithSpanContext<ProcessTxRequest>, oneshot::Sender<ProcessTxResponse>),
+    BlockHeaders(WithSpanContext<SyncBlockHeaders>, oneshot::Sender<Result<(), ReasonForBan>>),
+    StateResponse(WithSpanContext<StateResponse>),
+    StateDeltaResponse(WithSpanContext<StateDeltaResponse>),
+}
+
+/// Processes the oldest deferred message.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+struct ProcessDeferredMessage;
+
+impl ClientActor {
+    /// Defers a low priority message.  The actor polls messages sent to itself
+    /// with `notify` only after it has taken from the mailbox everything queued
+    /// there, and each processed deferred message notifies about the next one.
+    /// Deferred messages are thus processed in the order they arrived, one
+    /// after each pass over the mailbox, rather than once the mailbox stays
+    /// empty: a steady stream of other messages delays them but doesn't starve
+    /// them.
+    fn defer(&mut self, msg: DeferredMessage, ctx: &mut Context<Self>) {
+        if self.deferred_messages.is_empty() {
+            ctx.notify(ProcessDeferredMessage);
+        }
+        self.deferred_messages.push_back(msg);
+        metrics::CLIENT_DEFERRED_MESSAGES.set(self.deferred_messages.len() as i64);
+    }
+}
+
+impl Handler<ProcessDeferredMessage> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, _: ProcessDeferredMessage, ctx: &mut Context<Self>) {
+        let msg = match self.deferred_messages.pop_front() {
+            Some(msg) => msg,
+            None => return,
+        };
+        metrics::CLIENT_DEFERRED_MESSAGES.set(self.deferred_messages.len() as i64);
+        if !self.deferred_messages.is_empty() {
+            ctx.notify(ProcessDeferredMessage);
+        }
+        // The requester may have given up waiting, so sending the response
+        // can fail.
+        match msg {
+            DeferredMessage::ForwardedTx(msg, sender) => {
+                let _ = sender.send(self.process_tx_request(msg, ctx, MessagePriority::Low));
+            }
+            DeferredMessage::BlockHeaders(msg, sender) => {
+                let _ = sender.send(self.process_sync_block_headers(msg, ctx));
+            }
+            DeferredMessage::StateResponse(msg) => self.process_state_response(msg, ctx),
+            DeferredMessage::StateDeltaResponse(msg) => self.process_state_delta_response(msg, ctx),
+        }
+    }
+}
+
 fn create_sync_job_scheduler<M>(address: Addr<SyncJobsActor>) -> Box<dyn Fn(M)>
 where
     M: Message + Send + 'static,
@@ -257,6 +426,10 @@ impl Actor for ClientActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        if self.client.config.offline {
+            // Only serve requests, without touching the chain or the storage.
+            return;
+        }
         self.start_flat_storage_creation(ctx);
 
         // Start syncing job.
@@ -292,6 +465,18 @@ impl ClientActor {
         msg_type: &str,
         f: impl FnOnce(&mut Self, Req) -> Res,
     ) -> Res {
+        self.wrap_with_priority(msg, ctx, msg_type, MessagePriority::of(msg_type), f)
+    }
+
+    fn wrap_with_priority<Req: std::fmt::Debug + actix::Message, Res>(
+        &mut self,
+        msg: WithSpanContext<Req>,
+        ctx: &mut Context<Self>,
+        msg_type: &str,
+        priority: MessagePriority,
+        f: impl FnOnce(&mut Self, Req) -> Res,
+    ) -> Res {
+        priority.observe_queue_delay(msg.created);
         let (_span, msg) = handler_debug_span!(target: "client", msg, msg_type);
         self.check_triggers(ctx);
         let _d =
@@ -356,6 +541,7 @@ impl Handler<WithSpanContext<NetworkAdversarialMessage>> for ClientActor {
                     let _ = this.client.start_process_block(
                         block.into(),
                         Provenance::PRODUCED,
+                        None,
                         this.get_apply_chunks_done_callback(),
                     );
                     blocks_produced += 1;
@@ -411,14 +597,33 @@ impl Handler<WithSpanContext<NetworkAdversarialMessage>> for ClientActor {
 }
 
 impl Handler<WithSpanContext<ProcessTxRequest>> for ClientActor {
-    type Result = ProcessTxResponse;
+    type Result = ResponseFuture<ProcessTxResponse>;
 
     fn handle(
         &mut self,
         msg: WithSpanContext<ProcessTxRequest>,
         ctx: &mut Context<Self>,
     ) -> Self::Result {
-        self.wrap(msg, ctx, "ProcessTxRequest", |this: &mut Self, msg| {
+        if msg.msg.is_forwarded {
+            let (sender, receiver) = oneshot::channel();
+            self.defer(DeferredMessage::ForwardedTx(msg, sender), ctx);
+            return Box::pin(
+                async move { receiver.await.unwrap_or(ProcessTxResponse::NoResponse) },
+            );
+        }
+        let response = self.process_tx_request(msg, ctx, MessagePriority::Normal);
+        Box::pin(futures::future::ready(response))
+    }
+}
+
+impl ClientActor {
+    fn process_tx_request(
+        &mut self,
+        msg: WithSpanContext<ProcessTxRequest>,
+        ctx: &mut Context<Self>,
+        priority: MessagePriority,
+    ) -> ProcessTxResponse {
+        self.wrap_with_priority(msg, ctx, "ProcessTxRequest", priority, |this: &mut Self, msg| {
             let ProcessTxRequest { transaction, is_forwarded, check_only } = msg;
             this.client.process_tx(transaction, is_forwarded, check_only)
         })
@@ -479,17 +684,47 @@ impl Handler<WithSpanContext<BlockResponse>> for ClientActor {
     }
 }
 
+/// Headers are checked by the sync actor first, which sends them back as
+/// `SyncBlockHeaders` unless they answer its search for the fork point.
 impl Handler<WithSpanContext<BlockHeadersResponse>> for ClientActor {
-    type Result = Result<(), ReasonForBan>;
+    type Result = ResponseFuture<Result<(), ReasonForBan>>;
 
     fn handle(
         &mut self,
         msg: WithSpanContext<BlockHeadersResponse>,
         ctx: &mut Context<Self>,
     ) -> Self::Result {
-        self.wrap(msg, ctx, "BlockHeadersResponse", |this, msg| {
-            let BlockHeadersResponse(headers, peer_id) = msg;
-            if this.receive_headers(headers, peer_id) {
+        let sync_actor = self.sync_actor.clone();
+        self.wrap(msg, ctx, "BlockHeadersResponse", |_, msg| -> Self::Result {
+            let request = sync_actor.send(msg.with_span_context());
+            Box::pin(async move { request.await.unwrap_or(Ok(())) })
+        })
+    }
+}
+
+impl Handler<WithSpanContext<SyncBlockHeaders>> for ClientActor {
+    type Result = ResponseFuture<Result<(), ReasonForBan>>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SyncBlockHeaders>,
+        ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let (sender, receiver) = oneshot::channel();
+        self.defer(DeferredMessage::BlockHeaders(msg, sender), ctx);
+        Box::pin(async move { receiver.await.unwrap_or(Ok(())) })
+    }
+}
+
+impl ClientActor {
+    fn process_sync_block_headers(
+        &mut self,
+        msg: WithSpanContext<SyncBlockHeaders>,
+        ctx: &mut Context<Self>,
+    ) -> Result<(), ReasonForBan> {
+        self.wrap(msg, ctx, "SyncBlockHeaders", |this, msg| {
+            let SyncBlockHeaders(headers) = msg;
+            if this.receive_headers(headers) {
                 Ok(())
             } else {
                 warn!(target: "client", "Banning node for sending invalid block headers");
@@ -517,6 +752,16 @@ impl Handler<WithSpanContext<StateResponse>> for ClientActor {
     type Result = ();
 
     fn handle(&mut self, msg: WithSpanContext<StateResponse>, ctx: &mut Context<Self>) {
+        self.defer(DeferredMessage::StateResponse(msg), ctx);
+    }
+}
+
+impl ClientActor {
+    fn process_state_response(
+        &mut self,
+        msg: WithSpanContext<StateResponse>,
+        ctx: &mut Context<Self>,
+    ) {
         self.wrap(msg,ctx,"StateResponse",|this,msg| {
             let StateResponse(state_response_info) = msg;
             let shard_id = state_response_info.shard_id();
@@ -557,6 +802,105 @@ impl Handler<WithSpanContext<StateResponse>> for ClientActor {
     }
 }
 
+/// StateDeltaResponse is used during catchup instead of state parts when the
+/// node already has the state at the end of an earlier epoch.
+impl Handler<WithSpanContext<StateDeltaResponse>> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<StateDeltaResponse>, ctx: &mut Context<Self>) {
+        self.defer(DeferredMessage::StateDeltaResponse(msg), ctx);
+    }
+}
+
+impl ClientActor {
+    fn process_state_delta_response(
+        &mut self,
+        msg: WithSpanContext<StateDeltaResponse>,
+        ctx: &mut Context<Self>,
+    ) {
+        self.wrap(msg, ctx, "StateDeltaResponse", |this, msg| {
+            let StateDeltaResponse(info) = msg;
+            let StateDeltaResponseInfo { shard_id, sync_hash: hash, delta } = *info;
+            trace!(target: "sync", shard_id, %hash, size = ?delta.as_ref().map(|delta| delta.size()), "Received state delta response");
+
+            if let SyncStatus::StateSync(sync_hash, shards_to_download) =
+                &mut this.client.sync_status
+            {
+                if hash == *sync_hash {
+                    if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
+                        this.client.state_sync.update_download_on_state_delta_response(shard_download, hash, shard_id, delta, &mut this.client.chain);
+                        return;
+                    }
+                }
+            }
+
+            if let Some((state_sync, shards_to_download, _)) =
+                this.client.catchup_state_syncs.get_mut(&hash)
+            {
+                if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
+                    state_sync.update_download_on_state_delta_response(shard_download, hash, shard_id, delta, &mut this.client.chain);
+                    return;
+                }
+            }
+
+            error!(target: "sync", "State sync received delta for hash {} that we're not expecting, potential malicious peer or a very delayed response.", hash);
+        })
+    }
+}
+
+/// DataGarbageCollected is the response of a peer to a request for a block or
+/// for state, which it has garbage collected.  The data is requested from
+/// another peer right away, without waiting for the request to time out.
+impl Handler<WithSpanContext<DataGarbageCollected>> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<DataGarbageCollected>, ctx: &mut Context<Self>) {
+        self.wrap(msg, ctx, "DataGarbageCollected", |this, msg| {
+            debug!(target: "sync", data = ?msg.data, peer_id = %msg.peer_id, "Peer has garbage collected the requested data");
+            let (shard_id, hash, part_id) = match msg.data {
+                // Blocks are requested by block sync, which runs in the sync actor.
+                GarbageCollectedData::Block(_) => {
+                    this.sync_actor.do_send(msg.with_span_context());
+                    return;
+                }
+                GarbageCollectedData::StateHeader { shard_id, sync_hash } => {
+                    (shard_id, sync_hash, None)
+                }
+                GarbageCollectedData::StatePart { shard_id, sync_hash, part_id } => {
+                    (shard_id, sync_hash, Some(part_id))
+                }
+            };
+
+            // State may be requested from validators by account id.
+            let sender_account = this
+                .network_info
+                .known_producers
+                .iter()
+                .find(|producer| producer.peer_id == msg.peer_id)
+                .map(|producer| &producer.account_id);
+
+            if let SyncStatus::StateSync(sync_hash, shards_to_download) =
+                &mut this.client.sync_status
+            {
+                if hash == *sync_hash {
+                    if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
+                        this.client.state_sync.update_download_on_garbage_collected(shard_download, hash, shard_id, part_id, &msg.peer_id, sender_account);
+                        return;
+                    }
+                }
+            }
+
+            if let Some((state_sync, shards_to_download, _)) =
+                this.client.catchup_state_syncs.get_mut(&hash)
+            {
+                if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
+                    state_sync.update_download_on_garbage_collected(shard_download, hash, shard_id, part_id, &msg.peer_id, sender_account);
+                }
+            }
+        })
+    }
+}
+
 impl Handler<WithSpanContext<RecvPartialEncodedChunkRequest>> for ClientActor {
     type Result = ();
 
@@ -646,7 +990,67 @@ impl Handler<WithSpanContext<SetNetworkInfo>> for ClientActor {
     fn handle(&mut self, msg: WithSpanContext<SetNetworkInfo>, ctx: &mut Context<Self>) {
         self.wrap(msg, ctx, "SetNetworkInfo", |this, msg| {
             let SetNetworkInfo(network_info) = msg;
+            this.client.known_producers =
+                network_info.known_producers.iter().map(|p| p.account_id.clone()).collect();
             this.network_info = network_info;
+            // Only consider peers whose latest block is not invalid blocks
+            let highest_height_peers = this.network_info.highest_height_peers.clone();
+            let eligible_peers: Vec<_> = highest_height_peers
+                .iter()
+                .filter(|p| !this.client.chain.is_block_invalid(&p.highest_block_hash))
+                .cloned()
+                .collect();
+            metrics::PEERS_WITH_INVALID_HASH
+                .set(highest_height_peers.len() as i64 - eligible_peers.len() as i64);
+            this.sync_actor.do_send(
+                SyncNetworkInfo { highest_height_peers, eligible_peers }.with_span_context(),
+            );
+        })
+    }
+}
+
+impl Handler<WithSpanContext<SetSyncStatus>> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<SetSyncStatus>, ctx: &mut Context<Self>) {
+        self.wrap(msg, ctx, "SetSyncStatus", |this, msg| {
+            let SetSyncStatus(sync_status) = msg;
+            let currently_syncing = this.client.sync_status.is_syncing();
+            this.client.sync_status = sync_status;
+            if currently_syncing && !this.client.sync_status.is_syncing() {
+                debug!(
+                    target: "client",
+                    "{:?} transitions to no sync",
+                    this.client.validator_signer.as_ref().map(|vs| vs.validator_id()),
+                );
+                // Initial transition out of "syncing" state.
+                // Announce this client's account id if their epoch is coming up.
+                match this.client.chain.head() {
+                    Ok(head) => this.check_send_announce_account(head.prev_block_hash),
+                    Err(err) => error!(target: "sync", "Sync: Unexpected error: {}", err),
+                }
+            }
+        })
+    }
+}
+
+impl Handler<WithSpanContext<RunStateSyncStep>> for ClientActor {
+    type Result = MessageResult<WithSpanContext<RunStateSyncStep>>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<RunStateSyncStep>,
+        ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        self.wrap(msg, ctx, "RunStateSyncStep", |this, msg| {
+            let RunStateSyncStep(sync_status) = msg;
+            // Progress of state sync is tracked in the status of the client,
+            // the sync actor only knows when it started.
+            if !matches!(this.client.sync_status, SyncStatus::StateSync(..)) {
+                this.client.sync_status = sync_status;
+            }
+            this.run_state_sync_step();
+            MessageResult(this.client.sync_status.clone())
         })
     }
 }
@@ -662,9 +1066,11 @@ impl Handler<WithSpanContext<near_client_primitives::types::SandboxMessage>> for
     ) -> near_client_primitives::types::SandboxResponse {
         let (_span, msg) = handler_debug_span!(target: "client", msg);
         match msg {
-            near_client_primitives::types::SandboxMessage::SandboxPatchState(state) => {
+            near_client_primitives::types::SandboxMessage::SandboxPatchState(records, balances) => {
                 self.client.chain.patch_state(
-                    near_primitives::sandbox::state_patch::SandboxStatePatch::new(state),
+                    near_primitives::sandbox::state_patch::SandboxStatePatch::with_balances(
+                        records, balances,
+                    ),
                 );
                 near_client_primitives::types::SandboxResponse::SandboxNoResponse
             }
@@ -691,6 +1097,100 @@ impl Handler<WithSpanContext<near_client_primitives::types::SandboxMessage>> for
     }
 }
 
+impl Handler<WithSpanContext<GetHealth>> for ClientActor {
+    type Result = Result<HealthReport, StatusError>;
+
+    #[perf]
+    fn handle(&mut self, msg: WithSpanContext<GetHealth>, ctx: &mut Context<Self>) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _d = delay_detector::DelayDetector::new(|| "client health".into());
+        self.check_triggers(ctx);
+
+        let config = msg.config;
+        let head = self.client.chain.head()?;
+        let head_header = self.client.chain.get_block_header(&head.last_block_hash)?;
+        let mut checks = vec![];
+
+        checks.push(match self.no_new_blocks_for(&head_header) {
+            Some(elapsed) => HealthCheckResult {
+                name: "new_blocks",
+                healthy: false,
+                details: format!("No new blocks for {:?}", elapsed),
+            },
+            None => HealthCheckResult {
+                name: "new_blocks",
+                healthy: true,
+                details: format!("Head is at height {}", head.height),
+            },
+        });
+        checks.push(HealthCheckResult {
+            name: "not_syncing",
+            healthy: !self.client.sync_status.is_syncing(),
+            details: self.client.sync_status.as_variant_name().to_string(),
+        });
+        if let Some(max_blocks_behind) = config.max_blocks_behind {
+            let highest_height = self
+                .network_info
+                .highest_height_peers
+                .iter()
+                .map(|peer| peer.highest_block_height)
+                .max()
+                .unwrap_or(head.height);
+            let blocks_behind = highest_height.saturating_sub(head.height);
+            checks.push(HealthCheckResult {
+                name: "blocks_behind",
+                healthy: blocks_behind <= max_blocks_behind,
+                details: format!(
+                    "{} blocks behind the highest height {} reported by peers, at most {} allowed",
+                    blocks_behind, highest_height, max_blocks_behind
+                ),
+            });
+        }
+        if let Some(min_peers) = config.min_peers {
+            let num_peers = self.network_info.num_connected_peers;
+            checks.push(HealthCheckResult {
+                name: "peers",
+                healthy: num_peers >= min_peers,
+                details: format!("{} peers connected, at least {} required", num_peers, min_peers),
+            });
+        }
+        if config.check_db_writable {
+            let mut store_update = self.client.chain.store().store().store_update();
+            store_update.set(
+                DBCol::BlockMisc,
+                HEALTH_CHECK_KEY,
+                &self.client.clock.now_utc_chrono().timestamp().to_le_bytes(),
+            );
+            checks.push(match store_update.commit() {
+                Ok(()) => HealthCheckResult {
+                    name: "db_writable",
+                    healthy: true,
+                    details: "Database accepts writes".to_string(),
+                },
+                Err(err) => HealthCheckResult {
+                    name: "db_writable",
+                    healthy: false,
+                    details: format!("Failed to write to the database: {}", err),
+                },
+            });
+        }
+        if config.check_no_migration {
+            checks.push(HealthCheckResult {
+                name: "no_migration",
+                healthy: self.flat_storage_creation_done,
+                details: if self.flat_storage_creation_done {
+                    "No migration in progress".to_string()
+                } else {
+                    "Flat storage creation in progress".to_string()
+                },
+            });
+        }
+
+        let healthy = checks.iter().all(|check| check.healthy);
+        Ok(HealthReport { healthy, checks })
+    }
+}
+
 impl Handler<WithSpanContext<Status>> for ClientActor {
     type Result = Result<StatusResponse, StatusError>;
 
@@ -705,18 +1205,8 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
         let latest_block_time = head_header.raw_timestamp();
         let latest_state_root = *head_header.prev_state_root();
         if msg.is_health_check {
-            let now = Utc::now();
-            let block_timestamp = from_timestamp(latest_block_time);
-            if now > block_timestamp {
-                let elapsed = (now - block_timestamp).to_std().unwrap();
-                if elapsed
-                    > Duration::from_millis(
-                        self.client.config.max_block_production_delay.as_millis() as u64
-                            * STATUS_WAIT_TIME_MULTIPLIER,
-                    )
-                {
-                    return Err(StatusError::NoNewBlocks { elapsed });
-                }
+            if let Some(elapsed) = self.no_new_blocks_for(&head_header) {
+                return Err(StatusError::NoNewBlocks { elapsed });
             }
 
             if self.client.sync_status.is_syncing() {
@@ -781,7 +1271,8 @@ impl Handler<WithSpanContext<Status>> for ClientActor {
         } else {
             None
         };
-        let uptime_sec = Clock::utc().timestamp() - self.info_helper.boot_time_seconds;
+        let uptime_sec =
+            self.client.clock.now_utc_chrono().timestamp() - self.info_helper.boot_time_seconds;
         Ok(StatusResponse {
             version: self.client.config.version.clone(),
             protocol_version,
@@ -899,7 +1390,7 @@ impl ClientActor {
             Some(signer) => signer,
         };
 
-        let now = Clock::instant();
+        let now = self.client.clock.now_std();
         // Check that we haven't announced it too recently
         if let Some(last_validator_announce_time) = self.last_validator_announce_time {
             // Don't make announcement if have passed less than half of the time in which other peers
@@ -988,7 +1479,9 @@ impl ClientActor {
         let delta_time = self.client.sandbox_delta_time();
         let new_latest_known = near_chain::types::LatestKnown {
             height: block_height + delta_height,
-            seen: near_primitives::utils::to_timestamp(Clock::utc() + delta_time),
+            seen: near_primitives::utils::to_timestamp(
+                self.client.clock.now_utc_chrono() + delta_time,
+            ),
         };
 
         Ok(Some(new_latest_known))
@@ -1095,7 +1588,7 @@ impl ClientActor {
                         == self.client.runtime_adapter.num_shards(&epoch_id).unwrap();
 
                 if self.client.doomslug.ready_to_produce_block(
-                    Clock::instant(),
+                    self.client.clock.now_std(),
                     height,
                     have_all_chunks,
                     log_block_production_info,
@@ -1157,23 +1650,10 @@ impl ClientActor {
         self.try_process_unfinished_blocks();
 
         let mut delay = Duration::from_secs(1);
-        let now = Utc::now();
+        let now = self.client.clock.now_utc_chrono();
 
         let timer = metrics::CHECK_TRIGGERS_TIME.start_timer();
         if self.sync_started {
-            self.sync_timer_next_attempt = self.run_timer(
-                self.sync_wait_period(),
-                self.sync_timer_next_attempt,
-                ctx,
-                |act, _| act.run_sync_step(),
-                "sync",
-            );
-
-            delay = std::cmp::min(
-                delay,
-                self.sync_timer_next_attempt.signed_duration_since(now).to_std().unwrap_or(delay),
-            );
-
             self.doomslug_timer_next_attempt = self.run_timer(
                 self.client.config.doosmslug_step_period,
                 self.doomslug_timer_next_attempt,
@@ -1215,7 +1695,10 @@ impl ClientActor {
             self.client.config.log_summary_period,
             self.log_summary_timer_next_attempt,
             ctx,
-            |act, _ctx| act.log_summary(),
+            |act, _ctx| {
+                act.log_summary();
+                act.check_alerts();
+            },
             "log_summary",
         );
         delay = core::cmp::min(
@@ -1274,7 +1757,7 @@ impl ClientActor {
     fn try_doomslug_timer(&mut self, _: &mut Context<ClientActor>) {
         let _span = tracing::debug_span!(target: "client", "try_doomslug_timer").entered();
         let _ = self.client.check_and_update_doomslug_tip();
-        let approvals = self.client.doomslug.process_timer(Clock::instant());
+        let approvals = self.client.doomslug.process_timer(self.client.clock.now_std());
 
         // Important to save the largest approval target height before sending approvals, so
         // that if the node crashes in the meantime, we cannot get slashed on recovery
@@ -1306,6 +1789,13 @@ impl ClientActor {
     fn produce_block(&mut self, next_height: BlockHeight) -> Result<(), Error> {
         let _span = tracing::debug_span!(target: "client", "produce_block", next_height).entered();
         if let Some(block) = self.client.produce_block(next_height)? {
+            tracing::info!(
+                target: near_o11y::events::TARGET,
+                event = near_o11y::events::BLOCK_PRODUCED,
+                height = block.header().height(),
+                hash = %block.hash(),
+                num_chunks = block.header().chunk_mask().iter().filter(|&&m| m).count(),
+            );
             // If we produced the block, send it out before we apply the block.
             self.network_adapter.do_send(
                 PeerManagerMessageRequest::NetworkRequests(NetworkRequests::Block {
@@ -1318,6 +1808,7 @@ impl ClientActor {
             let res = self.client.start_process_block(
                 block,
                 Provenance::PRODUCED,
+                None,
                 self.get_apply_chunks_done_callback(),
             );
             if let Err(e) = &res {
@@ -1352,11 +1843,17 @@ impl ClientActor {
 
             let last_final_hash = block.header().last_final_block();
             let last_final_ds_hash = block.header().last_ds_final_block();
-            let last_final_block_height = self
-                .client
-                .chain
-                .get_block(&last_final_hash)
-                .map_or(0, |block| block.header().height());
+            let last_final_block_height = match self.client.chain.get_block(&last_final_hash) {
+                Ok(block) => {
+                    self.block_timestamp_index.lock().unwrap().add_final_block(
+                        block.header().height(),
+                        *last_final_hash,
+                        block.header().raw_timestamp(),
+                    );
+                    block.header().height()
+                }
+                Err(_) => 0,
+            };
             let last_final_ds_block_height = self
                 .client
                 .chain
@@ -1387,12 +1884,25 @@ impl ClientActor {
                 chunks_in_block as u64,
                 block.header().gas_price(),
                 block.header().total_supply(),
+                block.header().height(),
                 last_final_block_height,
                 last_final_ds_block_height,
                 epoch_height,
             );
             self.check_send_announce_account(*last_final_hash);
         }
+        if let Ok(head) = self.client.chain.head() {
+            if *self.head_sender.borrow() != head {
+                if let Err(err) = update_gas_price_window(
+                    &self.gas_price_window,
+                    &self.client.chain,
+                    head.last_block_hash,
+                ) {
+                    warn!(target: "client", ?err, "Failed to update the gas price window");
+                }
+                self.head_sender.send_replace(head);
+            }
+        }
     }
 
     /// Returns the callback function that will be passed to various functions that may trigger
@@ -1405,11 +1915,7 @@ impl ClientActor {
         })
     }
 
-    fn receive_headers(&mut self, headers: Vec<BlockHeader>, peer_id: PeerId) -> bool {
-        info!(target: "client", "Received {} block headers from {}", headers.len(), peer_id);
-        if headers.len() == 0 {
-            return true;
-        }
+    fn receive_headers(&mut self, headers: Vec<BlockHeader>) -> bool {
         info!(target: "client", "Received block headers from height {} to {}", headers.first().unwrap().height(), headers.last().unwrap().height());
         match self.client.sync_block_headers(headers) {
             Ok(_) => true,
@@ -1425,63 +1931,27 @@ impl ClientActor {
         }
     }
 
-    /// Check whether need to (continue) sync.
-    /// Also return higher height with known peers at that height.
-    fn syncing_info(&self) -> Result<(bool, u64), near_chain::Error> {
-        let head = self.client.chain.head()?;
-        let mut is_syncing = self.client.sync_status.is_syncing();
-
-        // Only consider peers whose latest block is not invalid blocks
-        let eligible_peers: Vec<_> = self
-            .network_info
-            .highest_height_peers
-            .iter()
-            .filter(|p| !self.client.chain.is_block_invalid(&p.highest_block_hash))
-            .collect();
-        metrics::PEERS_WITH_INVALID_HASH
-            .set(self.network_info.highest_height_peers.len() as i64 - eligible_peers.len() as i64);
-        let peer_info = if let Some(peer_info) = eligible_peers.choose(&mut thread_rng()) {
-            peer_info
-        } else {
-            if !self.client.config.skip_sync_wait {
-                warn!(target: "client", "Sync: no peers available, disabling sync");
-            }
-            return Ok((false, 0));
-        };
-
-        if is_syncing {
-            if peer_info.highest_block_height <= head.height {
-                info!(target: "client", "Sync: synced at {} [{}], {}, highest height peer: {}",
-                      head.height, format_hash(head.last_block_hash),
-                      peer_info.peer_info.id, peer_info.highest_block_height,
-                );
-                is_syncing = false;
-            }
-        } else {
-            if peer_info.highest_block_height
-                > head.height + self.client.config.sync_height_threshold
-            {
-                info!(
-                    target: "client",
-                    "Sync: height: {}, peer id/height: {}/{}, enabling sync",
-                    head.height,
-                    peer_info.peer_info.id,
-                    peer_info.highest_block_height,
-                );
-                is_syncing = true;
-            }
+    /// Returns how long ago the head block was produced if it's longer than
+    /// the node is expected to go without receiving new blocks.
+    fn no_new_blocks_for(&self, head_header: &BlockHeader) -> Option<Duration> {
+        let now = self.client.clock.now_utc_chrono();
+        let block_timestamp = from_timestamp(head_header.raw_timestamp());
+        if now <= block_timestamp {
+            return None;
         }
-        Ok((is_syncing, peer_info.highest_block_height))
-    }
-
-    fn needs_syncing(&self, needs_syncing: bool) -> bool {
-        !self.adv.disable_header_sync() && needs_syncing
+        let elapsed = (now - block_timestamp).to_std().unwrap();
+        let max_elapsed = Duration::from_millis(
+            self.client.config.max_block_production_delay.as_millis() as u64
+                * STATUS_WAIT_TIME_MULTIPLIER,
+        );
+        (elapsed > max_elapsed).then_some(elapsed)
     }
 
     fn start_flat_storage_creation(&mut self, ctx: &mut Context<ClientActor>) {
         match self.client.run_flat_storage_creation_step() {
             Ok(false) => {}
             Ok(true) => {
+                self.flat_storage_creation_done = true;
                 return;
             }
             Err(err) => {
@@ -1514,8 +1984,7 @@ impl ClientActor {
             return;
         }
         self.sync_started = true;
-
-        // Sync loop will be started by check_triggers.
+        self.sync_actor.do_send(StartSync.with_span_context());
     }
 
     /// Select the block hash we are using to sync state. It will sync with the state before applying the
@@ -1586,7 +2055,7 @@ impl ClientActor {
     where
         F: FnOnce(&mut Self, &mut <Self as Actor>::Context) + 'static,
     {
-        let now = Utc::now();
+        let now = self.client.clock.now_utc_chrono();
         if now < next_attempt {
             return next_attempt;
         }
@@ -1599,26 +2068,11 @@ impl ClientActor {
         now.checked_add_signed(chrono::Duration::from_std(delay).unwrap()).unwrap()
     }
 
-    fn sync_wait_period(&self) -> Duration {
-        if let Ok((needs_syncing, _)) = self.syncing_info() {
-            if !self.needs_syncing(needs_syncing) {
-                // If we don't need syncing - retry the sync call rarely.
-                self.client.config.sync_check_period
-            } else {
-                // If we need syncing - retry the sync call often.
-                self.client.config.sync_step_period
-            }
-        } else {
-            self.client.config.sync_step_period
-        }
-    }
-
-    /// Main syncing job responsible for syncing client with other peers.
-    /// Runs itself iff it was not ran as reaction for message with results of
-    /// finishing state part job
-    fn run_sync_step(&mut self) {
-        let _span = tracing::debug_span!(target: "client", "sync").entered();
-        let _d = delay_detector::DelayDetector::new(|| "client sync".into());
+    /// Runs a step of state sync at the request of the sync actor.  State
+    /// sync runs in the client actor because it writes to the chain.
+    fn run_state_sync_step(&mut self) {
+        let _span = tracing::debug_span!(target: "client", "state_sync").entered();
+        let _d = delay_detector::DelayDetector::new(|| "client state sync".into());
 
         macro_rules! unwrap_and_report (($obj: expr) => (match $obj {
             Ok(v) => v,
@@ -1628,136 +2082,79 @@ impl ClientActor {
             }
         }));
 
-        let currently_syncing = self.client.sync_status.is_syncing();
-        let (needs_syncing, highest_height) = unwrap_and_report!(self.syncing_info());
+        let (sync_hash, mut new_shard_sync, just_enter_state_sync) = match &self.client.sync_status
+        {
+            SyncStatus::StateSync(sync_hash, shard_sync) => (*sync_hash, shard_sync.clone(), false),
+            _ => {
+                let sync_hash = unwrap_and_report!(self.find_sync_hash());
+                (sync_hash, HashMap::default(), true)
+            }
+        };
 
-        if !self.needs_syncing(needs_syncing) {
-            if currently_syncing {
-                debug!(
-                    target: "client",
-                    "{:?} transitions to no sync",
-                    self.client.validator_signer.as_ref().map(|vs| vs.validator_id()),
-                );
-                self.client.sync_status = SyncStatus::NoSync;
+        let me = self.client.validator_signer.as_ref().map(|x| x.validator_id().clone());
+        let block_header = unwrap_and_report!(self.client.chain.get_block_header(&sync_hash));
+        let prev_hash = *block_header.prev_hash();
+        let epoch_id = self.client.chain.get_block_header(&sync_hash).unwrap().epoch_id().clone();
+        let shards_to_sync = (0..self.client.runtime_adapter.num_shards(&epoch_id).unwrap())
+            .filter(|x| {
+                cares_about_shard_this_or_next_epoch(
+                    me.as_ref(),
+                    &prev_hash,
+                    *x,
+                    true,
+                    self.client.runtime_adapter.as_ref(),
+                )
+            })
+            .collect();
 
-                // Initial transition out of "syncing" state.
-                // Announce this client's account id if their epoch is coming up.
-                let head = unwrap_and_report!(self.client.chain.head());
-                self.check_send_announce_account(head.prev_block_hash);
-            }
-        } else {
-            // Run each step of syncing separately.
-            unwrap_and_report!(self.client.header_sync.run(
-                &mut self.client.sync_status,
-                &mut self.client.chain,
-                highest_height,
-                &self.network_info.highest_height_peers
-            ));
-            // Only body / state sync if header height is close to the latest.
-            let header_head = unwrap_and_report!(self.client.chain.header_head());
-
-            // Sync state if already running sync state or if block sync is too far.
-            let sync_state = match self.client.sync_status {
-                SyncStatus::StateSync(_, _) => true,
-                _ if header_head.height
-                    >= highest_height
-                        .saturating_sub(self.client.config.block_header_fetch_horizon) =>
-                {
-                    unwrap_and_report!(self.client.block_sync.run(
-                        &mut self.client.sync_status,
-                        &self.client.chain,
-                        highest_height,
-                        &self.network_info.highest_height_peers
-                    ))
-                }
-                _ => false,
-            };
-            if sync_state {
-                let (sync_hash, mut new_shard_sync, just_enter_state_sync) =
-                    match &self.client.sync_status {
-                        SyncStatus::StateSync(sync_hash, shard_sync) => {
-                            (*sync_hash, shard_sync.clone(), false)
-                        }
-                        _ => {
-                            let sync_hash = unwrap_and_report!(self.find_sync_hash());
-                            (sync_hash, HashMap::default(), true)
-                        }
-                    };
-
-                let me = self.client.validator_signer.as_ref().map(|x| x.validator_id().clone());
-                let block_header =
-                    unwrap_and_report!(self.client.chain.get_block_header(&sync_hash));
-                let prev_hash = *block_header.prev_hash();
-                let epoch_id =
-                    self.client.chain.get_block_header(&sync_hash).unwrap().epoch_id().clone();
-                let shards_to_sync =
-                    (0..self.client.runtime_adapter.num_shards(&epoch_id).unwrap())
-                        .filter(|x| {
-                            cares_about_shard_this_or_next_epoch(
-                                me.as_ref(),
-                                &prev_hash,
-                                *x,
-                                true,
-                                self.client.runtime_adapter.as_ref(),
-                            )
-                        })
-                        .collect();
-
-                if !self.client.config.archive && just_enter_state_sync {
-                    unwrap_and_report!(self.client.chain.reset_data_pre_state_sync(sync_hash));
-                }
+        if !self.client.config.archive && just_enter_state_sync {
+            unwrap_and_report!(self.client.chain.reset_data_pre_state_sync(sync_hash));
+        }
 
-                match unwrap_and_report!(self.client.state_sync.run(
-                    &me,
-                    sync_hash,
-                    &mut new_shard_sync,
-                    &mut self.client.chain,
-                    &self.client.runtime_adapter,
-                    &self.network_info.highest_height_peers,
-                    shards_to_sync,
-                    &self.state_parts_task_scheduler,
-                    &self.state_split_scheduler,
-                )) {
-                    StateSyncResult::Unchanged => (),
-                    StateSyncResult::Changed(fetch_block) => {
-                        self.client.sync_status = SyncStatus::StateSync(sync_hash, new_shard_sync);
-                        if fetch_block {
-                            if let Some(peer_info) =
-                                self.network_info.highest_height_peers.choose(&mut thread_rng())
-                            {
-                                let id = peer_info.peer_info.id.clone();
-
-                                if let Ok(header) = self.client.chain.get_block_header(&sync_hash) {
-                                    for hash in
-                                        vec![*header.prev_hash(), *header.hash()].into_iter()
-                                    {
-                                        self.client.request_block(hash, id.clone());
-                                    }
-                                }
+        match unwrap_and_report!(self.client.state_sync.run(
+            &me,
+            sync_hash,
+            &mut new_shard_sync,
+            &mut self.client.chain,
+            &self.client.runtime_adapter,
+            &self.network_info.highest_height_peers,
+            shards_to_sync,
+            &self.state_parts_task_scheduler,
+            &self.state_split_scheduler,
+        )) {
+            StateSyncResult::Unchanged => (),
+            StateSyncResult::Changed(fetch_block) => {
+                self.client.sync_status = SyncStatus::StateSync(sync_hash, new_shard_sync);
+                if fetch_block {
+                    if let Some(peer_info) =
+                        self.network_info.highest_height_peers.choose(&mut thread_rng())
+                    {
+                        let id = peer_info.peer_info.id.clone();
+
+                        if let Ok(header) = self.client.chain.get_block_header(&sync_hash) {
+                            for hash in vec![*header.prev_hash(), *header.hash()].into_iter() {
+                                self.client.request_block(hash, id.clone());
                             }
                         }
                     }
-                    StateSyncResult::Completed => {
-                        info!(target: "sync", "State sync: all shards are done");
+                }
+            }
+            StateSyncResult::Completed => {
+                info!(target: "sync", "State sync: all shards are done");
 
-                        let mut block_processing_artifacts = BlockProcessingArtifact::default();
+                let mut block_processing_artifacts = BlockProcessingArtifact::default();
 
-                        unwrap_and_report!(self.client.chain.reset_heads_post_state_sync(
-                            &me,
-                            sync_hash,
-                            &mut block_processing_artifacts,
-                            self.get_apply_chunks_done_callback(),
-                        ));
+                unwrap_and_report!(self.client.chain.reset_heads_post_state_sync(
+                    &me,
+                    sync_hash,
+                    &mut block_processing_artifacts,
+                    self.get_apply_chunks_done_callback(),
+                ));
 
-                        self.client.process_block_processing_artifact(block_processing_artifacts);
+                self.client.process_block_processing_artifact(block_processing_artifacts);
 
-                        self.client.sync_status = SyncStatus::BodySync {
-                            start_height: 0,
-                            current_height: 0,
-                            highest_height: 0,
-                        };
-                    }
-                }
+                self.client.sync_status =
+                    SyncStatus::BodySync { start_height: 0, current_height: 0, highest_height: 0 };
             }
         }
     }
@@ -1773,12 +2170,21 @@ impl ClientActor {
             &self.config_updater,
         )
     }
+
+    /// Sends alerts about problems with the node, if configured.
+    fn check_alerts(&mut self) {
+        if let Some(alerter) = &mut self.alerter {
+            let _span = tracing::debug_span!(target: "client", "check_alerts").entered();
+            alerter.check(&self.client, &self.network_info);
+        }
+    }
 }
 
 impl Drop for ClientActor {
     fn drop(&mut self) {
         let _span = tracing::debug_span!(target: "client", "drop").entered();
         self.state_parts_client_arbiter.stop();
+        self.sync_arbiter.stop();
     }
 }
 
@@ -1948,6 +2354,7 @@ impl Handler<WithSpanContext<ShardsManagerResponse>> for ClientActor {
         msg: WithSpanContext<ShardsManagerResponse>,
         _: &mut Self::Context,
     ) -> Self::Result {
+        MessagePriority::High.observe_queue_delay(msg.created);
         let (_span, msg) = handler_debug_span!(target: "client", msg);
         match msg {
             ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk } => {
@@ -1985,6 +2392,19 @@ impl Handler<WithSpanContext<GetClientConfig>> for ClientActor {
     }
 }
 
+impl Handler<WithSpanContext<SubscribeHead>> for ClientActor {
+    type Result = MessageResult<WithSpanContext<SubscribeHead>>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SubscribeHead>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, _msg) = handler_debug_span!(target: "client", msg);
+        MessageResult(self.head_sender.subscribe())
+    }
+}
+
 /// Returns random seed sampled from the current thread
 pub fn random_seed_from_thread() -> RngSeed {
     let mut rng_seed: RngSeed = [0; 32];
@@ -1994,6 +2414,7 @@ pub fn random_seed_from_thread() -> RngSeed {
 
 /// Starts client in a separate Arbiter (thread).
 pub fn start_client(
+    clock: time::Clock,
     client_config: ClientConfig,
     chain_genesis: ChainGenesis,
     runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
@@ -2004,11 +2425,14 @@ pub fn start_client(
     sender: Option<broadcast::Sender<()>>,
     adv: crate::adversarial::Controls,
     config_updater: Option<ConfigUpdater>,
+    block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+    gas_price_window: Arc<Mutex<GasPriceWindow>>,
 ) -> (Addr<ClientActor>, ArbiterHandle) {
     let client_arbiter = Arbiter::new();
     let client_arbiter_handle = client_arbiter.handle();
     let client_addr = ClientActor::start_in_arbiter(&client_arbiter_handle, move |ctx| {
         ClientActor::new(
+            clock,
             ctx.address(),
             client_config,
             chain_genesis,
@@ -2023,6 +2447,8 @@ pub fn start_client(
             sender,
             adv,
             config_updater,
+            block_timestamp_index,
+            gas_price_window,
         )
         .unwrap()
     });
@@ -0,0 +1,37 @@
+//! Snapshots of the database of the running node, created on request through
+//! the `EXPERIMENTAL_db_snapshot` admin RPC method (see
+//! `neard database snapshot`).
+use near_client_primitives::types::CreateDbSnapshotError;
+use near_store::db_snapshot::{create_snapshot, SnapshotManifest};
+use near_store::Store;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Creates snapshots of the hot database.
+///
+/// Flushing the database and creating the checkpoint may take a while, so
+/// snapshots are created on the blocking thread pool rather than by the view
+/// client, whose threads serve the RPC queries.
+#[derive(Clone)]
+pub struct DbSnapshotter {
+    store: Store,
+    chain_id: String,
+}
+
+impl DbSnapshotter {
+    pub fn new(store: Store, chain_id: String) -> Self {
+        Self { store, chain_id }
+    }
+
+    /// Creates a snapshot in `output`, which must not exist.
+    pub async fn create(&self, output: PathBuf) -> Result<SnapshotManifest, CreateDbSnapshotError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let manifest = create_snapshot(&this.store, &output, &this.chain_id)?;
+            info!(target: "client", output = %output.display(), head_height = manifest.head_height, "Created database snapshot");
+            Ok(manifest)
+        })
+        .await
+        .map_err(|err| CreateDbSnapshotError::IOError(err.to_string()))?
+    }
+}
@@ -7,8 +7,10 @@ use itertools::Itertools;
 use near_chain::crypto_hash_timer::CryptoHashTimer;
 use near_chain::{near_chain_primitives, Chain, ChainStoreAccess, RuntimeWithEpochManagerAdapter};
 use near_client_primitives::debug::{
-    ApprovalAtHeightStatus, BlockProduction, ChunkCollection, DebugBlockStatusData, DebugStatus,
-    DebugStatusResponse, MissedHeightInfo, ProductionAtHeight, ValidatorStatus,
+    ApprovalAtHeightStatus, BlockFinalityView, BlockProduction, ChunkCollection,
+    DebugBlockStatusData, DebugStatus, DebugStatusResponse, FinalityView, MemoryUsageEntry,
+    MemoryUsageView, MissedHeightInfo, ProducerPropagationView, ProductionAtHeight, ReorgEventView,
+    ValidatorStatus,
 };
 use near_client_primitives::types::Error;
 use near_client_primitives::{
@@ -19,6 +21,7 @@ use near_o11y::{handler_debug_span, log_assert, OpenTelemetrySpanExt, WithSpanCo
 use near_performance_metrics_macros::perf;
 use near_primitives::syncing::get_num_state_parts;
 use near_primitives::types::{AccountId, BlockHeight, ShardId, ValidatorInfoIdentifier};
+use near_primitives::utils::from_timestamp;
 use near_primitives::{
     hash::CryptoHash,
     syncing::{ShardStateSyncResponseHeader, StateHeaderKey},
@@ -28,6 +31,7 @@ use near_primitives::{
 use near_store::DBCol;
 use std::cmp::{max, min};
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use near_client_primitives::debug::{DebugBlockStatus, DebugChunkStatus};
 use near_network::types::{ConnectedPeerInfo, NetworkInfo, PeerType};
@@ -143,6 +147,61 @@ impl BlockProductionTracker {
     }
 }
 
+/// Delays between the production of blocks and their arrival at the node,
+/// aggregated by producer.
+pub struct BlockPropagationTracker(lru::LruCache<AccountId, ProducerPropagationStats>);
+
+#[derive(Default)]
+struct ProducerPropagationStats {
+    num_blocks: u64,
+    total_delay_ms: u64,
+    max_delay_ms: u64,
+    last_delay_ms: u64,
+    last_height: BlockHeight,
+}
+
+impl BlockPropagationTracker {
+    pub(crate) fn new() -> Self {
+        Self(lru::LruCache::new(PRODUCTION_TIMES_CACHE_SIZE))
+    }
+
+    /// Records that a block of `producer` arrived `delay` after the timestamp
+    /// in its header.
+    pub(crate) fn record(&mut self, producer: &AccountId, height: BlockHeight, delay: Duration) {
+        crate::metrics::BLOCK_PROPAGATION_DELAY
+            .with_label_values(&[producer.as_str()])
+            .observe(delay.as_secs_f64());
+        let delay_ms = delay.as_millis() as u64;
+        if !self.0.contains(producer) {
+            self.0.put(producer.clone(), ProducerPropagationStats::default());
+        }
+        let stats = self.0.get_mut(producer).unwrap();
+        stats.num_blocks += 1;
+        stats.total_delay_ms += delay_ms;
+        stats.max_delay_ms = max(stats.max_delay_ms, delay_ms);
+        stats.last_delay_ms = delay_ms;
+        stats.last_height = height;
+    }
+
+    /// Returns the statistics of all producers, slowest on average first.
+    pub(crate) fn slowest_producers(&self) -> Vec<ProducerPropagationView> {
+        let mut producers: Vec<_> = self
+            .0
+            .iter()
+            .map(|(account_id, stats)| ProducerPropagationView {
+                account_id: account_id.clone(),
+                num_blocks: stats.num_blocks,
+                avg_delay_ms: stats.total_delay_ms / stats.num_blocks,
+                max_delay_ms: stats.max_delay_ms,
+                last_delay_ms: stats.last_delay_ms,
+                last_height: stats.last_height,
+            })
+            .collect();
+        producers.sort_by(|a, b| b.avg_delay_ms.cmp(&a.avg_delay_ms));
+        producers
+    }
+}
+
 impl Handler<WithSpanContext<DebugStatus>> for ClientActor {
     type Result = Result<DebugStatusResponse, StatusError>;
 
@@ -178,11 +237,106 @@ impl Handler<WithSpanContext<DebugStatus>> for ClientActor {
             DebugStatus::ChainProcessingStatus => Ok(DebugStatusResponse::ChainProcessingStatus(
                 self.client.chain.get_chain_processing_info(),
             )),
+            DebugStatus::MemoryUsage => {
+                Ok(DebugStatusResponse::MemoryUsage(self.get_memory_usage_view()))
+            }
+            DebugStatus::Reorgs => Ok(DebugStatusResponse::Reorgs(self.get_reorgs_view()?)),
+            DebugStatus::Finality => Ok(DebugStatusResponse::Finality(self.get_finality_view()?)),
+            DebugStatus::BlockPropagation => Ok(DebugStatusResponse::BlockPropagation(
+                self.client.block_propagation_info.slowest_producers(),
+            )),
         }
     }
 }
 
 impl ClientActor {
+    // Sizes are approximate: they count the payloads held by each subsystem,
+    // not the overhead of the data structures holding them.
+    fn get_memory_usage_view(&self) -> MemoryUsageView {
+        let mut subsystems = vec![];
+        let tries = self.client.runtime_adapter.get_tries();
+        for (prefix, is_view) in [("trie_cache", false), ("trie_view_cache", true)] {
+            for (shard_uid, entries, bytes) in tries.get_cache_sizes(is_view) {
+                subsystems.push(MemoryUsageEntry {
+                    subsystem: format!("{}.s{}.v{}", prefix, shard_uid.shard_id, shard_uid.version),
+                    entries: entries as u64,
+                    bytes,
+                });
+            }
+        }
+        subsystems.push(MemoryUsageEntry {
+            subsystem: "orphan_pool".to_string(),
+            entries: self.client.chain.orphans_len() as u64,
+            bytes: self.client.chain.orphans_approximate_size(),
+        });
+        for (shard_id, entries, bytes) in self.client.sharded_tx_pool.get_pool_sizes() {
+            subsystems.push(MemoryUsageEntry {
+                subsystem: format!("tx_pool.s{}", shard_id),
+                entries: entries as u64,
+                bytes,
+            });
+        }
+        let (entries, bytes) = self.client.shards_mgr.get_chunk_cache_size();
+        subsystems.push(MemoryUsageEntry {
+            subsystem: "chunk_cache".to_string(),
+            entries: entries as u64,
+            bytes,
+        });
+        let peers =
+            self.network_info.connected_peers.iter().chain(&self.network_info.tier1_connections);
+        let (entries, bytes) = peers.fold((0, 0), |(entries, bytes), peer| {
+            (entries + peer.messages_to_send, bytes + peer.bytes_to_send)
+        });
+        subsystems.push(MemoryUsageEntry {
+            subsystem: "peer_send_queues".to_string(),
+            entries,
+            bytes,
+        });
+        MemoryUsageView { subsystems }
+    }
+
+    fn get_reorgs_view(&self) -> Result<Vec<ReorgEventView>, near_chain_primitives::Error> {
+        let reorg_log = self.client.chain.store().get_reorg_log()?;
+        Ok(reorg_log
+            .into_iter()
+            .rev()
+            .map(|event| ReorgEventView {
+                old_head_hash: event.old_head_hash,
+                old_head_height: event.old_head_height,
+                old_head_timestamp: event.old_head_timestamp,
+                new_head_hash: event.new_head_hash,
+                new_head_height: event.new_head_height,
+                new_head_timestamp: event.new_head_timestamp,
+                common_ancestor_height: event.common_ancestor_height,
+                depth: event.depth,
+                seen: from_timestamp(event.seen),
+            })
+            .collect())
+    }
+
+    fn get_finality_view(&self) -> Result<FinalityView, near_chain_primitives::Error> {
+        let chain = &self.client.chain;
+        // The genesis block has no final blocks, these default to height 0.
+        let height_of = |hash: &CryptoHash| chain.get_block_header(hash).map_or(0, |h| h.height());
+        let mut blocks = vec![];
+        let mut header = chain.head_header()?;
+        for _ in 0..DEBUG_BLOCKS_TO_FETCH {
+            blocks.push(BlockFinalityView {
+                block_hash: *header.hash(),
+                block_height: header.height(),
+                doomslug_final_height: height_of(header.last_ds_final_block()),
+                final_height: height_of(header.last_final_block()),
+            });
+            match chain.get_previous_header(&header) {
+                Ok(prev_header) => header = prev_header,
+                Err(_) => break,
+            }
+        }
+        let (head_height, doomslug_final_height, final_height) =
+            (blocks[0].block_height, blocks[0].doomslug_final_height, blocks[0].final_height);
+        Ok(FinalityView { head_height, doomslug_final_height, final_height, blocks })
+    }
+
     // Gets a list of block producers and chunk-only producers for a given epoch.
     fn get_producers_for_epoch(
         &self,
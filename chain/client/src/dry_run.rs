@@ -0,0 +1,146 @@
+//! Dry-run block and chunk production.
+//!
+//! Prospective validators want to know whether their hardware keeps up with
+//! block and chunk production before they stake.  When
+//! `ClientConfig::dry_run_validator` is set, the node shadows the production
+//! schedule of that account: every time the account is due to produce a block
+//! or a chunk at the height following the new head, the node performs all the
+//! production steps (chunk assembly, encoding into parts, block assembly,
+//! signing) using a throwaway key and reports how long it took.  Nothing
+//! produced this way is persisted or broadcast.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use near_chain::Chain;
+use near_chunks::logic::decode_encoded_chunk;
+use near_crypto::KeyType;
+use near_primitives::block::Block;
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+use tracing::{debug, info, warn};
+
+use crate::metrics;
+use crate::Client;
+use near_client_primitives::types::Error;
+
+pub(crate) struct DryRunProducer {
+    /// Throwaway signer for the shadowed account.  Its key is never the one
+    /// registered on chain, so whatever it signs is invalid for the network.
+    signer: Arc<dyn ValidatorSigner>,
+}
+
+impl DryRunProducer {
+    pub(crate) fn new(account_id: AccountId) -> Self {
+        info!(target: "client", account_id = %account_id, "Dry-run production enabled; blocks and chunks will be produced with a throwaway key and discarded");
+        Self {
+            signer: Arc::new(InMemoryValidatorSigner::from_random(account_id, KeyType::ED25519)),
+        }
+    }
+}
+
+impl Client {
+    /// Runs dry-run production of the chunks and block at the height following `block`, if the
+    /// shadowed account is due to produce them.  Called when `block` becomes the new head.
+    pub(crate) fn dry_run_production(&mut self, block: &Block) {
+        let signer = match &self.dry_run_producer {
+            Some(producer) => producer.signer.clone(),
+            None => return,
+        };
+        if let Err(err) = self.dry_run_production_impl(block, &*signer) {
+            warn!(target: "client", account_id = %signer.validator_id(), ?err, "Dry-run production failed");
+        }
+    }
+
+    fn dry_run_production_impl(
+        &mut self,
+        block: &Block,
+        signer: &dyn ValidatorSigner,
+    ) -> Result<(), Error> {
+        let prev_hash = *block.hash();
+        let next_height = block.header().height() + 1;
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&prev_hash)?;
+
+        for shard_id in 0..self.runtime_adapter.num_shards(&epoch_id)? {
+            let chunk_producer =
+                self.runtime_adapter.get_chunk_producer(&epoch_id, next_height, shard_id)?;
+            if &chunk_producer != signer.validator_id() {
+                continue;
+            }
+            let elapsed = match self.dry_run_produce_chunk(block, shard_id, signer) {
+                Ok(elapsed) => elapsed,
+                Err(err) => {
+                    // Keep going: a missing chunk must not prevent measuring the block.
+                    warn!(target: "client", height = next_height, shard_id, ?err, "Dry-run chunk production failed");
+                    continue;
+                }
+            };
+            metrics::DRY_RUN_CHUNK_PRODUCTION_TIME
+                .with_label_values(&[&shard_id.to_string()])
+                .observe(elapsed.as_secs_f64());
+            info!(
+                target: "client",
+                account_id = %signer.validator_id(),
+                height = next_height,
+                shard_id,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "Dry run: would have produced chunk");
+        }
+
+        let block_producer = self.runtime_adapter.get_block_producer(&epoch_id, next_height)?;
+        if &block_producer == signer.validator_id() {
+            let started = Instant::now();
+            let new_chunks = self.get_chunk_headers_ready_for_inclusion(&epoch_id, &prev_hash);
+            let num_new_chunks = new_chunks.len();
+            // Approvals are only ever sent to the real block producer, so the
+            // block is assembled without any.
+            let num_approvers =
+                self.runtime_adapter.get_epoch_block_approvers_ordered(&prev_hash)?.len();
+            let approvals = vec![None; num_approvers];
+            let produced =
+                self.assemble_block(block, next_height, new_chunks, approvals, signer)?;
+            let elapsed = started.elapsed();
+            metrics::DRY_RUN_BLOCK_PRODUCTION_TIME.observe(elapsed.as_secs_f64());
+            info!(
+                target: "client",
+                account_id = %signer.validator_id(),
+                height = next_height,
+                num_new_chunks,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "Dry run: would have produced block");
+            debug!(target: "client", hash = %produced.hash(), "Discarding dry-run block");
+        }
+        Ok(())
+    }
+
+    /// Assembles, encodes and splits into parts the chunk of `shard_id` on
+    /// top of `prev_block`, returning the time it took.
+    fn dry_run_produce_chunk(
+        &mut self,
+        prev_block: &Block,
+        shard_id: ShardId,
+        signer: &dyn ValidatorSigner,
+    ) -> Result<Duration, Error> {
+        let started = Instant::now();
+        let prev_hash = *prev_block.hash();
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&prev_hash)?;
+        let last_header =
+            Chain::get_prev_chunk_header(&*self.runtime_adapter, prev_block, shard_id)?;
+        let (encoded_chunk, merkle_paths, _receipts) = self.assemble_chunk(
+            prev_hash,
+            &epoch_id,
+            last_header,
+            prev_block.header().height() + 1,
+            shard_id,
+            signer,
+        )?;
+        // This is what a chunk producer does before distributing the parts;
+        // the result is discarded instead of being persisted and sent.
+        decode_encoded_chunk(
+            &encoded_chunk,
+            merkle_paths,
+            Some(signer.validator_id()),
+            self.runtime_adapter.as_ref(),
+        )?;
+        Ok(started.elapsed())
+    }
+}
@@ -0,0 +1,198 @@
+//! Snapshots of the database at epoch boundaries.
+//!
+//! When `ClientConfig::epoch_snapshot` is set, every time the head enters a
+//! new epoch the node creates a RocksDB checkpoint of its database in
+//! `<path>/checkpoint-<height>`, where `height` is the height of the first
+//! block of the epoch, optionally flushing the database before.  Checkpoints
+//! hard-link the immutable SST files so they are cheap to create.  The
+//! configured command is run with the path to the new checkpoint, e.g. to
+//! upload it somewhere, then only the latest `num_to_keep` checkpoints are
+//! kept.  If the command fails, no checkpoint is deleted.
+//!
+//! All of this happens on a separate thread so that it doesn't delay block
+//! processing.  An epoch boundary reached while the previous snapshot is still
+//! in progress is skipped.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use near_chain::ChainStoreAccess;
+use near_chain_configs::EpochSnapshotConfig;
+use near_primitives::block::Block;
+use near_primitives::types::BlockHeight;
+use near_store::Store;
+use tracing::{info, warn};
+
+use crate::Client;
+
+const CHECKPOINT_PREFIX: &str = "checkpoint-";
+
+pub(crate) struct EpochSnapshotter {
+    config: Arc<EpochSnapshotConfig>,
+    in_progress: Arc<AtomicBool>,
+}
+
+impl EpochSnapshotter {
+    pub(crate) fn new(config: EpochSnapshotConfig) -> Self {
+        info!(target: "client", path = %config.path.display(), "Database snapshots at epoch boundaries enabled");
+        Self { config: Arc::new(config), in_progress: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Starts a snapshot of the database for the epoch starting at given
+    /// height, unless the previous one is still in progress.
+    pub(crate) fn start(&self, store: Store, height: BlockHeight) {
+        if self.in_progress.swap(true, Ordering::AcqRel) {
+            warn!(target: "client", height, "Previous epoch snapshot still in progress, skipping");
+            return;
+        }
+        let config = self.config.clone();
+        let in_progress = self.in_progress.clone();
+        let result =
+            std::thread::Builder::new().name("epoch_snapshot".to_string()).spawn(move || {
+                if let Err(err) = snapshot(&config, &store, height) {
+                    warn!(target: "client", height, ?err, "Failed to snapshot the database");
+                }
+                in_progress.store(false, Ordering::Release);
+            });
+        if let Err(err) = result {
+            warn!(target: "client", ?err, "Failed to start the epoch snapshot thread");
+            self.in_progress.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl Client {
+    /// Starts a snapshot of the database if `block`, the new head, is the
+    /// first block of an epoch.
+    pub(crate) fn snapshot_at_epoch_boundary(&self, block: &Block) {
+        let snapshotter = match &self.epoch_snapshotter {
+            Some(snapshotter) => snapshotter,
+            None => return,
+        };
+        match self.runtime_adapter.is_next_block_epoch_start(block.header().prev_hash()) {
+            Ok(true) => {
+                snapshotter.start(self.chain.store().store().clone(), block.header().height())
+            }
+            Ok(false) => {}
+            Err(err) => warn!(target: "client", ?err, "Failed to check for an epoch boundary"),
+        }
+    }
+}
+
+fn snapshot(config: &EpochSnapshotConfig, store: &Store, height: BlockHeight) -> io::Result<()> {
+    let started = std::time::Instant::now();
+    std::fs::create_dir_all(&config.path)?;
+    let path = config.path.join(format!("{}{}", CHECKPOINT_PREFIX, height));
+    if config.flush {
+        store.flush()?;
+    }
+    store.create_checkpoint(&path)?;
+    info!(target: "client", height, path = %path.display(), took = ?started.elapsed(), "Created epoch snapshot");
+
+    if let Some((program, args)) = config.command.as_ref().and_then(|c| c.split_first()) {
+        let status = Command::new(program).args(args).arg(&path).status()?;
+        if !status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} exited with {}", program, status),
+            ));
+        }
+    }
+
+    for old in prune_checkpoints(&config.path, config.num_to_keep)? {
+        match std::fs::remove_dir_all(&old) {
+            Ok(()) => info!(target: "client", path = %old.display(), "Deleted old epoch snapshot"),
+            Err(err) => {
+                warn!(target: "client", path = %old.display(), ?err, "Failed to delete old epoch snapshot")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the checkpoints in the directory older than the latest
+/// `num_to_keep` ones.
+fn prune_checkpoints(dir: &Path, num_to_keep: usize) -> io::Result<Vec<PathBuf>> {
+    let mut checkpoints = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let height = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix(CHECKPOINT_PREFIX))
+            .and_then(|height| height.parse::<BlockHeight>().ok());
+        if let Some(height) = height {
+            checkpoints.push((height, entry.path()));
+        }
+    }
+    checkpoints.sort();
+    let num_to_prune = checkpoints.len().saturating_sub(num_to_keep);
+    Ok(checkpoints.into_iter().take(num_to_prune).map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["checkpoint-20", "checkpoint-100", "checkpoint-3", "unrelated"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        assert_eq!(
+            prune_checkpoints(dir.path(), 2).unwrap(),
+            vec![dir.path().join("checkpoint-3")]
+        );
+        assert_eq!(
+            prune_checkpoints(dir.path(), 1).unwrap(),
+            vec![dir.path().join("checkpoint-3"), dir.path().join("checkpoint-20")]
+        );
+        assert_eq!(prune_checkpoints(dir.path(), 5).unwrap(), Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    fn test_snapshot_runs_command_before_pruning() {
+        let home = tempfile::tempdir().unwrap();
+        let store = near_store::NodeStorage::opener(
+            home.path(),
+            &near_store::StoreConfig::test_config(),
+            None,
+        )
+        .open()
+        .unwrap()
+        .get_hot_store();
+        let dir = home.path().join("epoch_snapshots");
+        let log = home.path().join("uploaded");
+        // The command gets the path to the checkpoint as its last argument,
+        // which `sh -c` passes to the script as `$0`.
+        let upload = format!("test -d \"$0\" && echo \"$0\" >> {}", log.display());
+        let mut config = EpochSnapshotConfig {
+            path: dir.clone(),
+            num_to_keep: 1,
+            flush: false,
+            command: Some(vec!["sh".to_string(), "-c".to_string(), upload]),
+        };
+
+        snapshot(&config, &store, 10).unwrap();
+        snapshot(&config, &store, 20).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            format!(
+                "{}\n{}\n",
+                dir.join("checkpoint-10").display(),
+                dir.join("checkpoint-20").display()
+            )
+        );
+        assert!(!dir.join("checkpoint-10").exists());
+        assert!(dir.join("checkpoint-20").exists());
+
+        // A failed upload keeps the previous checkpoint.
+        config.command = Some(vec!["false".to_string()]);
+        assert!(snapshot(&config, &store, 30).is_err());
+        assert!(dir.join("checkpoint-20").exists());
+        assert!(dir.join("checkpoint-30").exists());
+    }
+}
@@ -0,0 +1,280 @@
+//! Estimation of the gas price to attach to transactions.
+//!
+//! The gas prices and the utilization of the chunks of the last
+//! `GAS_PRICE_WINDOW_SIZE` blocks of the canonical chain are kept in a
+//! [`GasPriceWindow`], which the client shares with all instances of the view
+//! client.  Whenever the head changes the client adds the new blocks to the
+//! window and drops the blocks which fell out of it, so that only the new
+//! blocks are read from the store and estimates don't read the store at
+//! all.  The estimate for a percentile is the greater of the
+//! percentile of the gas prices in the window and of the gas price projected
+//! `GAS_PRICE_ESTIMATE_HORIZON` blocks ahead, assuming the utilization of the
+//! newer half of the window persists.
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use near_chain::{Chain, ChainStoreAccess};
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{Balance, BlockHeight, BlockHeightDelta, Gas};
+use num_rational::Rational32;
+
+/// Number of blocks whose gas prices are kept in `GasPriceWindow`.
+pub(crate) const GAS_PRICE_WINDOW_SIZE: usize = 200;
+
+/// Number of blocks within which a transaction is expected to be included.
+pub(crate) const GAS_PRICE_ESTIMATE_HORIZON: BlockHeightDelta = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GasPriceSample {
+    pub height: BlockHeight,
+    pub hash: CryptoHash,
+    pub gas_price: Balance,
+    /// Gas used by the new chunks of the block.
+    pub gas_used: Gas,
+    /// Gas limit of the new chunks of the block.
+    pub gas_limit: Gas,
+}
+
+/// Gas prices of the most recent blocks, oldest first.
+pub struct GasPriceWindow {
+    size: usize,
+    samples: VecDeque<GasPriceSample>,
+}
+
+impl GasPriceWindow {
+    pub fn new() -> Self {
+        Self::with_size(GAS_PRICE_WINDOW_SIZE)
+    }
+
+    fn with_size(size: usize) -> Self {
+        Self { size, samples: VecDeque::with_capacity(size) }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub(crate) fn last(&self) -> Option<&GasPriceSample> {
+        self.samples.back()
+    }
+
+    /// Adds the samples of blocks following a block in the window, or of a
+    /// new chain if none does, oldest first.  Samples of the blocks of the
+    /// window they replace are dropped.
+    pub(crate) fn extend(&mut self, samples: Vec<GasPriceSample>) {
+        let first_height = match samples.first() {
+            Some(sample) => sample.height,
+            None => return,
+        };
+        while self.samples.back().map_or(false, |sample| sample.height >= first_height) {
+            self.samples.pop_back();
+        }
+        self.samples.extend(samples);
+        while self.samples.len() > self.size {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the gas price below which are `percentile` percent of the
+    /// gas prices in the window, using the nearest-rank method.
+    pub(crate) fn percentile(&self, percentile: u8) -> Option<Balance> {
+        let mut gas_prices: Vec<Balance> =
+            self.samples.iter().map(|sample| sample.gas_price).collect();
+        gas_prices.sort_unstable();
+        let rank = (percentile as usize * gas_prices.len() + 99) / 100;
+        gas_prices.get(rank.saturating_sub(1)).copied()
+    }
+
+    /// Returns the gas used and the gas limit of the chunks in the newer
+    /// half of the window and in the older half.
+    fn gas_by_half(&self) -> ((Gas, Gas), (Gas, Gas)) {
+        let sum = |samples: &mut dyn Iterator<Item = &GasPriceSample>| {
+            samples.fold((0, 0), |(used, limit), sample| {
+                (used + sample.gas_used, limit + sample.gas_limit)
+            })
+        };
+        let older = self.samples.len() / 2;
+        (sum(&mut self.samples.iter().skip(older)), sum(&mut self.samples.iter().take(older)))
+    }
+
+    /// Returns the ratio of the gas used to the gas limit of the chunks in
+    /// the window.
+    pub(crate) fn utilization(&self) -> f64 {
+        let ((newer_used, newer_limit), (older_used, older_limit)) = self.gas_by_half();
+        ratio(newer_used + older_used, newer_limit + older_limit)
+    }
+
+    /// Returns the utilization of the newer half of the window minus the
+    /// utilization of the older half.
+    pub(crate) fn utilization_trend(&self) -> f64 {
+        let ((newer_used, newer_limit), (older_used, older_limit)) = self.gas_by_half();
+        ratio(newer_used, newer_limit) - ratio(older_used, older_limit)
+    }
+
+    /// Returns the gas price `GAS_PRICE_ESTIMATE_HORIZON` blocks after the
+    /// last block in the window, assuming every block has the utilization of
+    /// the newer half of the window.
+    pub(crate) fn projected_gas_price(
+        &self,
+        min_gas_price: Balance,
+        max_gas_price: Balance,
+        gas_price_adjustment_rate: Rational32,
+    ) -> Option<Balance> {
+        let mut gas_price = self.last()?.gas_price;
+        let ((gas_used, gas_limit), _) = self.gas_by_half();
+        for _ in 0..GAS_PRICE_ESTIMATE_HORIZON {
+            gas_price = Block::compute_new_gas_price(
+                gas_price,
+                gas_used,
+                gas_limit,
+                gas_price_adjustment_rate,
+                min_gas_price,
+                max_gas_price,
+            );
+        }
+        Some(gas_price)
+    }
+}
+
+/// Adds the blocks of the canonical chain up to `head_hash` which aren't in
+/// the window yet, going back at most the size of the window or until a block
+/// which isn't stored, e.g. one before the state sync.  The blocks are read
+/// without holding the lock so that estimates aren't blocked by the store.
+pub(crate) fn update_gas_price_window(
+    window: &Mutex<GasPriceWindow>,
+    chain: &Chain,
+    head_hash: CryptoHash,
+) -> Result<(), near_chain::Error> {
+    let (size, known): (usize, HashSet<CryptoHash>) = {
+        let window = window.lock().unwrap();
+        (window.size, window.samples.iter().map(|sample| sample.hash).collect())
+    };
+    let mut block_hash = head_hash;
+    let mut samples = vec![];
+    while samples.len() < size && !known.contains(&block_hash) {
+        let block = match chain.store().get_block(&block_hash) {
+            Ok(block) => block,
+            Err(near_chain::Error::DBNotFoundErr(_)) => break,
+            Err(err) => return Err(err),
+        };
+        let header = block.header();
+        samples.push(GasPriceSample {
+            height: header.height(),
+            hash: block_hash,
+            gas_price: header.gas_price(),
+            gas_used: Block::compute_gas_used(block.chunks().iter(), header.height()),
+            gas_limit: Block::compute_gas_limit(block.chunks().iter(), header.height()),
+        });
+        if header.prev_hash() == &CryptoHash::default() {
+            break;
+        }
+        block_hash = *header.prev_hash();
+    }
+    samples.reverse();
+    window.lock().unwrap().extend(samples);
+    Ok(())
+}
+
+fn ratio(numerator: Gas, denominator: Gas) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestEnv;
+    use near_chain::ChainGenesis;
+    use near_primitives::hash::hash;
+
+    fn sample(height: BlockHeight, gas_price: Balance, gas_used: Gas) -> GasPriceSample {
+        GasPriceSample {
+            height,
+            hash: hash(&height.to_le_bytes()),
+            gas_price,
+            gas_used,
+            gas_limit: 100,
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut window = GasPriceWindow::with_size(3);
+        window.extend(vec![sample(1, 10, 0), sample(2, 20, 0)]);
+        assert_eq!(window.len(), 2);
+        window.extend(vec![sample(3, 30, 0), sample(4, 40, 0)]);
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.samples.front(), Some(&sample(2, 20, 0)));
+        assert_eq!(window.percentile(0), Some(20));
+
+        // A fork replacing the block at height 4.
+        window.extend(vec![sample(4, 45, 0), sample(5, 50, 0)]);
+        assert_eq!(
+            window.samples.iter().map(|s| s.gas_price).collect::<Vec<_>>(),
+            vec![30, 45, 50]
+        );
+        assert_eq!(window.last().map(|s| s.height), Some(5));
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut window = GasPriceWindow::with_size(10);
+        assert_eq!(window.percentile(50), None);
+        window.extend(
+            (1..=10).map(|height| sample(height, 110 - height as Balance * 10, 0)).collect(),
+        );
+        assert_eq!(window.percentile(0), Some(10));
+        assert_eq!(window.percentile(50), Some(50));
+        assert_eq!(window.percentile(90), Some(90));
+        assert_eq!(window.percentile(100), Some(100));
+    }
+
+    #[test]
+    fn test_utilization() {
+        let mut window = GasPriceWindow::with_size(4);
+        let gas_price = 1_000_000;
+        window.extend(
+            [20, 40, 60, 80]
+                .into_iter()
+                .enumerate()
+                .map(|(i, gas_used)| sample(i as BlockHeight + 1, gas_price, gas_used))
+                .collect(),
+        );
+        assert!((window.utilization() - 0.5).abs() < 1e-9);
+        assert!((window.utilization_trend() - 0.4).abs() < 1e-9);
+
+        // The newer half is 70% full, so the gas price grows by 0.2% per block.
+        let rate = Rational32::new(1, 100);
+        let projected = window.projected_gas_price(1, 2 * gas_price, rate).unwrap();
+        assert!(projected > gas_price * 1009 / 1000 && projected < gas_price * 1011 / 1000);
+        assert_eq!(window.projected_gas_price(1, gas_price + 1, rate), Some(gas_price + 1));
+    }
+
+    #[test]
+    fn test_update_gas_price_window() {
+        let mut env = TestEnv::builder(ChainGenesis::test()).build();
+        for height in 1..=5 {
+            env.produce_block(0, height);
+        }
+        let window = Mutex::new(GasPriceWindow::with_size(3));
+        let chain = &env.clients[0].chain;
+        let head = chain.head().unwrap();
+        update_gas_price_window(&window, chain, head.last_block_hash).unwrap();
+        let heights = |window: &Mutex<GasPriceWindow>| {
+            window.lock().unwrap().samples.iter().map(|s| s.height).collect::<Vec<_>>()
+        };
+        assert_eq!(heights(&window), vec![3, 4, 5]);
+
+        // Only the new block is added when the head moves on.
+        env.produce_block(0, 6);
+        let chain = &env.clients[0].chain;
+        let head = chain.head().unwrap();
+        update_gas_price_window(&window, chain, head.last_block_hash).unwrap();
+        assert_eq!(heights(&window), vec![4, 5, 6]);
+        assert_eq!(window.lock().unwrap().last().unwrap().hash, head.last_block_hash);
+    }
+}
@@ -2,9 +2,10 @@ use crate::config_updater::ConfigUpdater;
 use crate::{metrics, rocksdb_metrics, SyncStatus};
 use actix::Addr;
 use itertools::Itertools;
-use near_chain_configs::{ClientConfig, LogSummaryStyle};
+use near_chain_configs::{ClientConfig, LogSummaryStyle, ProtocolVersionRange};
 use near_network::types::NetworkInfo;
 use near_primitives::block::Tip;
+use near_primitives::epoch_manager::EpochConfig;
 use near_primitives::network::PeerId;
 use near_primitives::telemetry::{
     TelemetryAgentInfo, TelemetryChainInfo, TelemetryInfo, TelemetrySystemInfo,
@@ -16,18 +17,19 @@ use near_primitives::types::{
 };
 use near_primitives::unwrap_or_return;
 use near_primitives::validator_signer::ValidatorSigner;
-use near_primitives::version::Version;
+use near_primitives::version::{ProtocolVersion, Version, PROTOCOL_VERSION};
 use near_primitives::views::{
     CatchupStatusView, ChunkProcessingStatus, CurrentEpochValidatorInfo, EpochValidatorInfo,
-    ValidatorKickoutView,
+    ProtocolVersionVotingView, ValidatorKickoutView,
 };
 use near_store::db::StoreStatistics;
 use near_telemetry::{telemetry, TelemetryActor};
 use std::cmp::min;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::sync::Arc;
 use sysinfo::{get_current_pid, set_open_files_limit, Pid, ProcessExt, System, SystemExt};
-use tracing::info;
+use tracing::{error, info, warn};
 
 const TERAGAS: f64 = 1_000_000_000_000_f64;
 
@@ -103,6 +105,7 @@ impl InfoHelper {
         num_chunks: u64,
         gas_price: Balance,
         total_supply: Balance,
+        block_height: BlockHeight,
         last_final_block_height: BlockHeight,
         last_final_ds_block_height: BlockHeight,
         epoch_height: EpochHeight,
@@ -117,6 +120,12 @@ impl InfoHelper {
         metrics::TOTAL_SUPPLY.set(total_supply as f64);
         metrics::FINAL_BLOCK_HEIGHT.set(last_final_block_height as i64);
         metrics::FINAL_DOOMSLUG_BLOCK_HEIGHT.set(last_final_ds_block_height as i64);
+        metrics::FINALITY_LAG
+            .with_label_values(&["bft"])
+            .set(block_height.saturating_sub(last_final_block_height) as i64);
+        metrics::FINALITY_LAG
+            .with_label_values(&["doomslug"])
+            .set(block_height.saturating_sub(last_final_ds_block_height) as i64);
         metrics::EPOCH_HEIGHT.set(epoch_height as i64);
     }
 
@@ -172,6 +181,31 @@ impl InfoHelper {
                 .map(get_validator_epoch_stats)
                 .unwrap_or_default()
         };
+        if !is_syncing {
+            // Same as get_validator_info above, this is expensive while syncing.
+            if let Ok(voting) =
+                client.runtime_adapter.get_protocol_version_voting(&head.last_block_hash)
+            {
+                export_protocol_version_voting(&voting);
+                check_protocol_version_support(
+                    &voting,
+                    client.config.expected_protocol_version_range.as_ref(),
+                );
+            }
+        }
+        if let Some(signer) = &client.validator_signer {
+            if let Ok(epoch_config) = client.runtime_adapter.get_epoch_config(&head.epoch_id) {
+                for stats in
+                    validator_epoch_stats.iter().filter(|s| &s.account_id == signer.validator_id())
+                {
+                    check_kickout_margins(
+                        stats,
+                        &epoch_config,
+                        client.config.kickout_warning_margin,
+                    );
+                }
+            }
+        }
         let statistics = if client.config.enable_statistics_export {
             client.chain.store().get_store_statistics()
         } else {
@@ -640,6 +674,170 @@ impl ValidatorProductionStats {
     }
 }
 
+/// Minimum number of expected blocks or chunks before the share of produced
+/// ones is warned about, so that a slot missed early in the epoch doesn't
+/// raise a warning.
+const KICKOUT_WARNING_MIN_EXPECTED: NumBlocks = 10;
+
+/// Share of blocks or chunks produced by a validator compared to the kickout
+/// threshold.
+pub(crate) struct KickoutMargin {
+    pub kind: &'static str,
+    pub produced: NumBlocks,
+    pub expected: NumBlocks,
+    pub threshold: u8,
+    /// Percentage points by which the produced share is above the threshold,
+    /// negative if it's below.
+    pub margin: i64,
+}
+
+impl KickoutMargin {
+    /// Whether the validator is close enough to the threshold to be warned.
+    pub fn is_at_risk(&self, warning_margin: u8) -> bool {
+        self.expected >= KICKOUT_WARNING_MIN_EXPECTED && self.margin < warning_margin as i64
+    }
+}
+
+impl std::fmt::Display for KickoutMargin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Produced {} of {} expected {}s in the current epoch, {} percentage points above the kickout threshold of {}%",
+            self.produced, self.expected, self.kind, self.margin, self.threshold
+        )
+    }
+}
+
+/// Returns the margins of a validator for blocks and chunks, skipping the
+/// kinds it wasn't expected to produce.
+pub(crate) fn kickout_margins(
+    stats: &ValidatorProductionStats,
+    block_threshold: u8,
+    chunk_threshold: u8,
+) -> Vec<KickoutMargin> {
+    [
+        ("block", stats.num_produced_blocks, stats.num_expected_blocks, block_threshold),
+        ("chunk", stats.num_produced_chunks, stats.num_expected_chunks, chunk_threshold),
+    ]
+    .into_iter()
+    .filter(|(_, _, expected, _)| *expected > 0)
+    .map(|(kind, produced, expected, threshold)| KickoutMargin {
+        kind,
+        produced,
+        expected,
+        threshold,
+        margin: (produced * 100 / expected) as i64 - threshold as i64,
+    })
+    .collect()
+}
+
+/// Exports the kickout margins of the validator of the node and warns when
+/// it's at risk of being kicked out.
+fn check_kickout_margins(
+    stats: &ValidatorProductionStats,
+    epoch_config: &EpochConfig,
+    warning_margin: u8,
+) {
+    let margins = kickout_margins(
+        stats,
+        epoch_config.block_producer_kickout_threshold,
+        epoch_config.chunk_producer_kickout_threshold,
+    );
+    for margin in margins {
+        metrics::VALIDATOR_KICKOUT_MARGIN.with_label_values(&[margin.kind]).set(margin.margin);
+        if margin.is_at_risk(warning_margin) {
+            warn!(target: "stats", account_id = %stats.account_id, "{}", margin);
+        }
+    }
+}
+
+/// Exports the shares of stake voting for each protocol version.
+fn export_protocol_version_voting(voting: &ProtocolVersionVotingView) {
+    let total_stake = voting.total_stake.max(1) as f64;
+    let mut stakes = BTreeMap::new();
+    for vote in &voting.votes {
+        if let Some(version) = vote.protocol_version {
+            *stakes.entry(version).or_insert(0) += vote.stake;
+        }
+    }
+    // Versions nobody votes for anymore shouldn't keep their last value.
+    metrics::PROTOCOL_VERSION_VOTES.reset();
+    for (version, stake) in stakes {
+        metrics::PROTOCOL_VERSION_VOTES
+            .with_label_values(&[&version.to_string()])
+            .set(stake as f64 / total_stake);
+    }
+    metrics::PROTOCOL_VERSION_VOTE_THRESHOLD.set(voting.threshold_stake as f64 / total_stake);
+    metrics::PROTOCOL_VERSION_PROJECTED.set(voting.projected_protocol_version as i64);
+}
+
+/// Protocol version of the network not supported by the binary or outside of
+/// `ClientConfig::expected_protocol_version_range`, from the most to the least
+/// urgent.
+#[derive(Debug, PartialEq)]
+enum UnsupportedProtocolVersion {
+    /// The network already runs it.
+    Current(ProtocolVersion),
+    /// The network switches to it in the next epoch.
+    Next(ProtocolVersion),
+    /// Enough stake votes for it that the network switches to it in two
+    /// epochs unless the votes change.
+    Projected(ProtocolVersion),
+}
+
+fn unsupported_protocol_version(
+    voting: &ProtocolVersionVotingView,
+    range: Option<&ProtocolVersionRange>,
+) -> Option<UnsupportedProtocolVersion> {
+    let supported = |version: ProtocolVersion| {
+        version <= PROTOCOL_VERSION && range.map_or(true, |range| range.contains(version))
+    };
+    if !supported(voting.protocol_version) {
+        Some(UnsupportedProtocolVersion::Current(voting.protocol_version))
+    } else if !supported(voting.next_protocol_version) {
+        Some(UnsupportedProtocolVersion::Next(voting.next_protocol_version))
+    } else if !supported(voting.projected_protocol_version) {
+        Some(UnsupportedProtocolVersion::Projected(voting.projected_protocol_version))
+    } else {
+        None
+    }
+}
+
+/// Warns, more loudly the closer the switch is, when the network is about to
+/// run a protocol version the node doesn't support or doesn't expect.
+fn check_protocol_version_support(
+    voting: &ProtocolVersionVotingView,
+    range: Option<&ProtocolVersionRange>,
+) {
+    let upgrade_height = voting.estimated_upgrade_height;
+    match unsupported_protocol_version(voting, range) {
+        None => {}
+        Some(UnsupportedProtocolVersion::Current(version)) => error!(
+            target: "stats",
+            version,
+            binary_version = PROTOCOL_VERSION,
+            ?range,
+            "The network runs an unexpected protocol version. Please check that the node runs the right release."
+        ),
+        Some(UnsupportedProtocolVersion::Next(version)) => error!(
+            target: "stats",
+            version,
+            binary_version = PROTOCOL_VERSION,
+            ?range,
+            ?upgrade_height,
+            "The network switches to an unsupported protocol version in the next epoch. Please update nearcore before the upgrade height."
+        ),
+        Some(UnsupportedProtocolVersion::Projected(version)) => warn!(
+            target: "stats",
+            version,
+            binary_version = PROTOCOL_VERSION,
+            ?range,
+            ?upgrade_height,
+            "Validators vote for an unsupported protocol version. Please prepare to update nearcore."
+        ),
+    }
+}
+
 /// Converts EpochValidatorInfo into a vector of ValidatorProductionStats.
 fn get_validator_epoch_stats(
     current_validator_epoch_info: EpochValidatorInfo,
@@ -663,7 +861,6 @@ mod tests {
     use near_chain::types::ChainConfig;
     use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode};
     use near_network::test_utils::peer_id_from_seed;
-    use near_primitives::version::PROTOCOL_VERSION;
     use num_rational::Ratio;
 
     #[test]
@@ -686,6 +883,67 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unsupported_protocol_version() {
+        let voting = |protocol_version, next_protocol_version, projected_protocol_version| {
+            ProtocolVersionVotingView {
+                epoch_id: Default::default(),
+                epoch_start_height: 1,
+                protocol_version,
+                next_protocol_version,
+                projected_protocol_version,
+                votes: vec![],
+                total_stake: 0,
+                threshold_stake: 0,
+                estimated_upgrade_height: None,
+            }
+        };
+        let v = PROTOCOL_VERSION;
+        assert_eq!(unsupported_protocol_version(&voting(v, v, v), None), None);
+        assert_eq!(
+            unsupported_protocol_version(&voting(v, v, v + 1), None),
+            Some(UnsupportedProtocolVersion::Projected(v + 1))
+        );
+        assert_eq!(
+            unsupported_protocol_version(&voting(v, v + 1, v + 1), None),
+            Some(UnsupportedProtocolVersion::Next(v + 1))
+        );
+
+        let range = ProtocolVersionRange { min: v - 1, max: v - 1 };
+        assert_eq!(unsupported_protocol_version(&voting(v - 1, v - 1, v - 1), Some(&range)), None);
+        assert_eq!(
+            unsupported_protocol_version(&voting(v - 1, v - 1, v), Some(&range)),
+            Some(UnsupportedProtocolVersion::Projected(v))
+        );
+        assert_eq!(
+            unsupported_protocol_version(&voting(v, v, v), Some(&range)),
+            Some(UnsupportedProtocolVersion::Current(v))
+        );
+    }
+
+    #[test]
+    fn test_kickout_margins() {
+        let stats = ValidatorProductionStats {
+            account_id: "test".parse().unwrap(),
+            num_produced_blocks: 17,
+            num_expected_blocks: 20,
+            num_produced_chunks: 0,
+            num_expected_chunks: 0,
+        };
+        let margins = kickout_margins(&stats, 80, 60);
+        assert_eq!(margins.len(), 1);
+        assert_eq!(margins[0].kind, "block");
+        assert_eq!(margins[0].margin, 5);
+        assert!(margins[0].is_at_risk(10));
+        assert!(!margins[0].is_at_risk(5));
+
+        let stats =
+            ValidatorProductionStats { num_expected_blocks: 5, num_produced_blocks: 0, ..stats };
+        let margins = kickout_margins(&stats, 80, 60);
+        assert_eq!(margins[0].margin, -80);
+        assert!(!margins[0].is_at_risk(10));
+    }
+
     #[test]
     fn telemetry_info() {
         let config = ClientConfig::test(false, 1230, 2340, 50, false, true, true);
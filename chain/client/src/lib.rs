@@ -1,11 +1,15 @@
 pub use near_client_primitives::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk,
-    GetClientConfig, GetExecutionOutcome, GetExecutionOutcomeResponse,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
-    GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetSplitStorageInfo, GetStateChanges,
+    EpochLightClientBlock, Error, GetAccountOutcomes, GetBlock, GetBlockByTimestamp, GetBlockProof,
+    GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk, GetClientConfig,
+    GetEpochLightClientBlock, GetEpochProtocolConfig, GetExecutionOutcome,
+    GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceEstimate,
+    GetHealth, GetMaintenanceWindows, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig,
+    GetProtocolVersionVoting, GetReceipt, GetSplitStorageInfo, GetStateChanges,
     GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered, Query,
-    QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered,
+    GetValidatorProjection, GetValidatorSchedule, HealthCheckConfig, HealthReport, Query,
+    QueryError, SimulateTransaction, Status, StatusResponse, SubscribeHead, SyncStatus, TxStatus,
+    TxStatusError, TxStatusExtended,
 };
 
 pub use near_client_primitives::debug::DebugStatus;
@@ -16,18 +20,32 @@ pub use crate::adapter::{
 pub use crate::client::Client;
 pub use crate::client_actor::{start_client, ClientActor};
 pub use crate::config_updater::ConfigUpdater;
-pub use crate::view_client::{start_view_client, ViewClientActor};
+pub use crate::db_snapshot::DbSnapshotter;
+pub use crate::gas_price_estimate::GasPriceWindow;
+pub use crate::view_client::{
+    start_view_call_pool, start_view_client, BlockTimestampIndex, SyncServingBudget, ViewCallPool,
+    ViewClientActor,
+};
 
 pub mod adapter;
 pub mod adversarial;
+mod alerts;
 mod client;
 mod client_actor;
 mod config_updater;
+mod db_snapshot;
 pub mod debug;
+mod dry_run;
+mod epoch_snapshot;
+mod gas_price_estimate;
 mod info;
+pub mod loopback_network;
+pub mod message_log;
 mod metrics;
 mod rocksdb_metrics;
+pub mod simulation;
 pub mod sync;
+mod sync_actor;
 pub mod test_utils;
 #[cfg(test)]
 mod tests;
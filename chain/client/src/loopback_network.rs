@@ -0,0 +1,421 @@
+//! Network for integration tests which routes the messages of in-process
+//! nodes directly between their actors, without binding any ports.
+//!
+//! Every node is registered in a [`LoopbackNetwork`] under its account name
+//! and sends its messages through the [`LoopbackPeerManager`] returned by
+//! [`LoopbackNetwork::peer_manager`], which it uses as its
+//! `PeerManagerAdapter`.  The messages go through the same
+//! [`SimulatedNetwork`] as in a [`Simulation`](crate::simulation::Simulation),
+//! with the nodes indexed in the order they were added: tests can delay or
+//! drop the messages on any link, cap its bandwidth and partition the nodes
+//! while they are running.
+//!
+//! Only the messages needed to produce and sync blocks and chunks are routed:
+//! blocks, approvals, chunk parts, forwarded transactions, block and header
+//! requests and account announcements.  The rest are dropped.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use actix::Addr;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::time::Instant;
+use tracing::debug;
+
+use near_network::client::DataResponse;
+use near_network::types::{
+    BlockInfo, ConnectedPeerInfo, FullPeerInfo, MsgRecipient, NetworkInfo, NetworkRequests,
+    NetworkResponses, PeerChainInfo, PeerInfo, PeerManagerMessageRequest,
+    PeerManagerMessageResponse, PeerType, SetChainInfo,
+};
+use near_o11y::{WithSpanContext, WithSpanContextExt};
+use near_primitives::block::GenesisId;
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::time::Clock;
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::version::PROTOCOL_VERSION;
+
+use crate::adapter::{
+    AnnounceAccountRequest, BlockApproval, BlockHeadersRequest, BlockHeadersResponse, BlockRequest,
+    BlockResponse, DataGarbageCollected, ProcessTxRequest, RecvPartialEncodedChunk,
+    RecvPartialEncodedChunkForward, RecvPartialEncodedChunkRequest,
+    RecvPartialEncodedChunkResponse, SetNetworkInfo,
+};
+use crate::simulation::{borsh_size as size, SimulatedNetwork};
+use crate::{ClientActor, ViewClientActor};
+
+/// Node of a `LoopbackNetwork`.
+#[derive(Clone)]
+pub struct LoopbackNode {
+    pub account_id: AccountId,
+    pub peer_info: PeerInfo,
+    pub client: Addr<ClientActor>,
+    pub view_client: Addr<ViewClientActor>,
+}
+
+#[derive(Default)]
+struct Nodes {
+    nodes: Vec<LoopbackNode>,
+    /// Heights of the heads of the nodes, as of their last `SetChainInfo`.
+    heights: HashMap<AccountId, BlockHeight>,
+}
+
+impl Nodes {
+    fn index(&self, account_id: &AccountId) -> Option<usize> {
+        self.nodes.iter().position(|node| &node.account_id == account_id)
+    }
+}
+
+/// Switch routing the messages between the nodes registered in it.
+#[derive(Clone)]
+pub struct LoopbackNetwork {
+    nodes: Arc<Mutex<Nodes>>,
+    network: Arc<Mutex<SimulatedNetwork>>,
+}
+
+impl LoopbackNetwork {
+    /// Creates a network without nodes.  `seed` initialises the random
+    /// number generator deciding which messages are dropped.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            nodes: Arc::new(Mutex::new(Nodes::default())),
+            network: Arc::new(Mutex::new(SimulatedNetwork::new(seed))),
+        }
+    }
+
+    /// Returns the adapter through which the node named `account_id` sends
+    /// its messages.  Messages to and from the node are dropped until it's
+    /// added with `add_node`.
+    pub fn peer_manager(&self, account_id: AccountId) -> Arc<LoopbackPeerManager> {
+        Arc::new(LoopbackPeerManager { network: self.clone(), account_id })
+    }
+
+    /// Adds the node at the next index of the network, or replaces the node
+    /// with the same account keeping its index.
+    pub fn add_node(&self, node: LoopbackNode) {
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.index(&node.account_id) {
+            Some(index) => nodes.nodes[index] = node,
+            None => nodes.nodes.push(node),
+        }
+    }
+
+    /// Conditions of the network, to change them while the nodes are running.
+    pub fn network(&self) -> MutexGuard<'_, SimulatedNetwork> {
+        self.network.lock().unwrap()
+    }
+
+    /// Sends a message of `size` bytes from `from` to `to` over their link,
+    /// calling `deliver` with the receiving node when it gets there.
+    fn send(
+        &self,
+        from: &AccountId,
+        to: &AccountId,
+        size: u64,
+        deliver: impl FnOnce(&LoopbackNode) + Send + 'static,
+    ) {
+        let route = {
+            let nodes = self.nodes.lock().unwrap();
+            nodes
+                .index(from)
+                .zip(nodes.index(to))
+                .map(|(from, to)| (from, to, nodes.nodes[to].clone()))
+        };
+        let (from, to, node) = match route {
+            Some(route) => route,
+            None => return,
+        };
+        let now = Instant::now();
+        let arrival = match self.network().delivery_time(now.into_std(), from, to, || size) {
+            Some(arrival) => Instant::from_std(arrival),
+            None => return,
+        };
+        if arrival <= now {
+            deliver(&node);
+        } else {
+            actix::spawn(async move {
+                tokio::time::sleep_until(arrival).await;
+                deliver(&node);
+            });
+        }
+    }
+
+    /// Sends `f(node)` to every other node.
+    fn broadcast(
+        &self,
+        from: &AccountId,
+        size: u64,
+        f: impl Fn(&LoopbackNode) + Clone + Send + 'static,
+    ) {
+        let others: Vec<AccountId> = {
+            let nodes = self.nodes.lock().unwrap();
+            nodes.nodes.iter().map(|node| node.account_id.clone()).filter(|id| id != from).collect()
+        };
+        for to in others {
+            self.send(from, &to, size, f.clone());
+        }
+    }
+
+    fn find(&self, f: impl Fn(&LoopbackNode) -> bool) -> Option<LoopbackNode> {
+        self.nodes.lock().unwrap().nodes.iter().find(|node| f(node)).cloned()
+    }
+
+    fn route(&self, from: &AccountId, request: NetworkRequests) {
+        let sender = match self.find(|node| &node.account_id == from) {
+            Some(node) => node,
+            None => return,
+        };
+        let peer_id = sender.peer_info.id.clone();
+        match request {
+            NetworkRequests::Block { block } => {
+                self.broadcast(from, size(&block), move |node| {
+                    node.client.do_send(
+                        BlockResponse {
+                            block: block.clone(),
+                            peer_id: peer_id.clone(),
+                            was_requested: false,
+                        }
+                        .with_span_context(),
+                    );
+                });
+            }
+            NetworkRequests::Approval { approval_message } => {
+                let bytes = size(&approval_message);
+                let approval = approval_message.approval;
+                self.send(from, &approval_message.target, bytes, move |node| {
+                    node.client.do_send(BlockApproval(approval, peer_id).with_span_context());
+                });
+            }
+            NetworkRequests::ForwardTx(account_id, transaction) => {
+                self.send(from, &account_id, size(&transaction), move |node| {
+                    node.client.do_send(
+                        ProcessTxRequest { transaction, is_forwarded: true, check_only: false }
+                            .with_span_context(),
+                    );
+                });
+            }
+            NetworkRequests::PartialEncodedChunkRequest { target, request, .. } => {
+                let account_id = match target.account_id {
+                    Some(account_id) => account_id,
+                    None => {
+                        debug!(target: "network", ?request, "No target for the chunk request");
+                        return;
+                    }
+                };
+                let route_back = route_back(from);
+                self.send(from, &account_id, size(&request), move |node| {
+                    node.client.do_send(
+                        RecvPartialEncodedChunkRequest(request, route_back).with_span_context(),
+                    );
+                });
+            }
+            NetworkRequests::PartialEncodedChunkResponse { route_back: back, response } => {
+                let to = match self.find(|node| route_back(&node.account_id) == back) {
+                    Some(node) => node.account_id,
+                    None => return,
+                };
+                self.send(from, &to, size(&response), move |node| {
+                    node.client.do_send(
+                        RecvPartialEncodedChunkResponse(response, Clock::instant())
+                            .with_span_context(),
+                    );
+                });
+            }
+            NetworkRequests::PartialEncodedChunkMessage { account_id, partial_encoded_chunk } => {
+                let partial_encoded_chunk = partial_encoded_chunk.into();
+                self.send(from, &account_id, size(&partial_encoded_chunk), move |node| {
+                    node.client.do_send(
+                        RecvPartialEncodedChunk(partial_encoded_chunk).with_span_context(),
+                    );
+                });
+            }
+            NetworkRequests::PartialEncodedChunkForward { account_id, forward } => {
+                self.send(from, &account_id, size(&forward), move |node| {
+                    node.client
+                        .do_send(RecvPartialEncodedChunkForward(forward).with_span_context());
+                });
+            }
+            NetworkRequests::BlockRequest { hash, peer_id: target } => {
+                let to = match self.find(|node| node.peer_info.id == target) {
+                    Some(node) => node.account_id,
+                    None => return,
+                };
+                let (network, requester, responder) = (self.clone(), from.clone(), to.clone());
+                self.send(from, &to, size(&hash), move |node| {
+                    let request = node.view_client.send(BlockRequest(hash).with_span_context());
+                    actix::spawn(async move {
+                        let response = match request.await {
+                            Ok(Some(response)) => response,
+                            _ => return,
+                        };
+                        let bytes = match &response {
+                            DataResponse::Data(block) => size(block),
+                            DataResponse::GarbageCollected(_) => 0,
+                        };
+                        network.send(&responder, &requester, bytes, move |node| match response {
+                            DataResponse::Data(block) => node.client.do_send(
+                                BlockResponse {
+                                    block: *block,
+                                    peer_id: target,
+                                    was_requested: true,
+                                }
+                                .with_span_context(),
+                            ),
+                            DataResponse::GarbageCollected(data) => node.client.do_send(
+                                DataGarbageCollected { data, peer_id: target }.with_span_context(),
+                            ),
+                        });
+                    });
+                });
+            }
+            NetworkRequests::BlockHeadersRequest { hashes, peer_id: target } => {
+                let to = match self.find(|node| node.peer_info.id == target) {
+                    Some(node) => node.account_id,
+                    None => return,
+                };
+                let (network, requester, responder) = (self.clone(), from.clone(), to.clone());
+                self.send(from, &to, size(&hashes), move |node| {
+                    let request =
+                        node.view_client.send(BlockHeadersRequest(hashes).with_span_context());
+                    actix::spawn(async move {
+                        let headers = match request.await {
+                            Ok(Some(headers)) => headers,
+                            _ => return,
+                        };
+                        network.send(&responder, &requester, size(&headers), move |node| {
+                            node.client
+                                .do_send(BlockHeadersResponse(headers, target).with_span_context());
+                        });
+                    });
+                });
+            }
+            NetworkRequests::AnnounceAccount(announce_account) => {
+                // The node learns about its own account as well.
+                sender.view_client.do_send(
+                    AnnounceAccountRequest(vec![(announce_account.clone(), None)])
+                        .with_span_context(),
+                );
+                self.broadcast(from, size(&announce_account), move |node| {
+                    node.view_client.do_send(
+                        AnnounceAccountRequest(vec![(announce_account.clone(), None)])
+                            .with_span_context(),
+                    );
+                });
+            }
+            request => {
+                debug!(target: "network", ?request, "Dropping a request which isn't routed");
+            }
+        }
+    }
+
+    /// Tells the node about the nodes it's connected to, as the peer manager
+    /// does periodically.
+    fn set_chain_info(&self, from: &AccountId, height: BlockHeight) {
+        let (node, peers) = {
+            let mut nodes = self.nodes.lock().unwrap();
+            nodes.heights.insert(from.clone(), height);
+            let index = match nodes.index(from) {
+                Some(index) => index,
+                None => return,
+            };
+            let network = self.network();
+            let peers: Vec<ConnectedPeerInfo> = nodes
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(peer, _)| *peer != index && network.is_connected(index, *peer))
+                .map(|(_, peer)| {
+                    connected_peer_info(
+                        peer.peer_info.clone(),
+                        nodes.heights.get(&peer.account_id).copied().unwrap_or_default(),
+                    )
+                })
+                .collect();
+            (nodes.nodes[index].clone(), peers)
+        };
+        let highest_height_peers =
+            peers.iter().filter_map(|peer| peer.full_peer_info.clone().into()).collect();
+        let info = NetworkInfo {
+            num_connected_peers: peers.len(),
+            peer_max_count: peers.len() as u32,
+            connected_peers: peers,
+            tier1_connections: vec![],
+            highest_height_peers,
+            sent_bytes_per_sec: 0,
+            received_bytes_per_sec: 0,
+            known_producers: vec![],
+            tier1_accounts_keys: vec![],
+            tier1_accounts_data: vec![],
+        };
+        node.client.do_send(SetNetworkInfo(info).with_span_context());
+    }
+}
+
+/// `PeerManagerAdapter` of a node of a `LoopbackNetwork`.
+pub struct LoopbackPeerManager {
+    network: LoopbackNetwork,
+    account_id: AccountId,
+}
+
+impl MsgRecipient<WithSpanContext<PeerManagerMessageRequest>> for LoopbackPeerManager {
+    fn send(
+        &self,
+        msg: WithSpanContext<PeerManagerMessageRequest>,
+    ) -> BoxFuture<'static, Result<PeerManagerMessageResponse, actix::MailboxError>> {
+        self.do_send(msg);
+        futures::future::ok(PeerManagerMessageResponse::NetworkResponses(
+            NetworkResponses::NoResponse,
+        ))
+        .boxed()
+    }
+
+    fn do_send(&self, msg: WithSpanContext<PeerManagerMessageRequest>) {
+        if let PeerManagerMessageRequest::NetworkRequests(request) = msg.msg {
+            self.network.route(&self.account_id, request);
+        }
+    }
+}
+
+impl MsgRecipient<WithSpanContext<SetChainInfo>> for LoopbackPeerManager {
+    fn send(
+        &self,
+        msg: WithSpanContext<SetChainInfo>,
+    ) -> BoxFuture<'static, Result<(), actix::MailboxError>> {
+        self.do_send(msg);
+        futures::future::ok(()).boxed()
+    }
+
+    fn do_send(&self, msg: WithSpanContext<SetChainInfo>) {
+        self.network.set_chain_info(&self.account_id, msg.msg.0.block.header().height());
+    }
+}
+
+/// Address of the node for routing the responses to its chunk requests.
+fn route_back(account_id: &AccountId) -> CryptoHash {
+    hash(account_id.as_ref().as_bytes())
+}
+
+fn connected_peer_info(peer_info: PeerInfo, height: BlockHeight) -> ConnectedPeerInfo {
+    ConnectedPeerInfo {
+        full_peer_info: FullPeerInfo {
+            peer_info,
+            chain_info: PeerChainInfo {
+                genesis_id: GenesisId::default(),
+                last_block: Some(BlockInfo { height, hash: CryptoHash::default() }),
+                tracked_shards: vec![],
+                archival: true,
+                earliest_height: None,
+                protocol_version: PROTOCOL_VERSION,
+            },
+        },
+        received_bytes_per_sec: 0,
+        sent_bytes_per_sec: 0,
+        last_time_peer_requested: near_network::time::Instant::now(),
+        last_time_received_message: near_network::time::Instant::now(),
+        connection_established_time: near_network::time::Instant::now(),
+        peer_type: PeerType::Outbound,
+        nonce: 1,
+        messages_to_send: 0,
+        bytes_to_send: 0,
+    }
+}
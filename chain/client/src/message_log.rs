@@ -0,0 +1,492 @@
+//! Log of the inbound network messages affecting the client, for replaying
+//! them deterministically.
+//!
+//! When `ClientConfig::message_log` is set, the network adapter records every
+//! block, approval, chunk part and transaction it passes on to the client
+//! actor together with the time it was received.  `neard replay` feeds the
+//! messages of a log, in order and with the clock set to the times they were
+//! received, into a fresh client started from genesis, which makes consensus
+//! bugs observed on a live node reproducible under a debugger.
+//!
+//! A log starts with [`MAGIC`] and the little-endian format version, followed
+//! by the entries, each a little-endian u32 length and a borsh-serialised
+//! [`MessageLogEntry`].  Entries are written by a background thread which
+//! flushes the file whenever it runs out of entries to write, so a crash may
+//! only lose the last few messages.  At most [`QUEUE_SIZE`] entries wait for
+//! the writer; if the disk can't keep up, further messages are dropped and
+//! counted in `near_message_log_dropped_total` rather than buffered without
+//! bound, and the log may no longer replay faithfully.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use actix::MailboxError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::TimeZone;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use near_chain::types::RuntimeWithEpochManagerAdapter;
+use near_chain::ChainGenesis;
+use near_chain_configs::{ClientConfig, Genesis};
+use near_chunks::client::ShardsManagerResponse;
+use near_client_primitives::types::{Error, SyncStatus};
+use near_network::time;
+use near_network::types::{
+    MsgRecipient, OfflineNetwork, PartialEncodedChunkForwardMsg, PartialEncodedChunkResponseMsg,
+};
+use near_o11y::WithSpanContext;
+use near_primitives::block::{Approval, ApprovalType, Block};
+use near_primitives::network::PeerId;
+use near_primitives::sharding::PartialEncodedChunk;
+use near_primitives::time::{Clock, Instant, MockClockGuard, Utc};
+use near_primitives::transaction::SignedTransaction;
+use tracing::{debug, error, info, warn};
+
+use crate::{metrics, Client};
+
+/// Bytes every message log starts with.
+pub const MAGIC: &[u8; 8] = b"NEARMLOG";
+/// Version of the format of the entries.
+const VERSION: u32 = 1;
+/// Maximum number of entries waiting to be written.
+pub const QUEUE_SIZE: usize = 10_000;
+
+/// Inbound network message recorded in the log.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub enum RecordedMessage {
+    Block { block: Block, peer_id: PeerId, was_requested: bool },
+    BlockApproval { approval: Approval, peer_id: PeerId },
+    PartialEncodedChunk(PartialEncodedChunk),
+    PartialEncodedChunkForward(PartialEncodedChunkForwardMsg),
+    PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg),
+    Transaction { transaction: SignedTransaction, is_forwarded: bool },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct MessageLogEntry {
+    /// Time the message was received, in nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub message: RecordedMessage,
+}
+
+/// Appends the messages passed to [`Self::record`] to a message log.
+pub struct MessageRecorder {
+    sender: Mutex<Option<SyncSender<MessageLogEntry>>>,
+    writer: Option<JoinHandle<()>>,
+    /// Number of messages dropped because the queue was full.
+    dropped: AtomicU64,
+}
+
+impl MessageRecorder {
+    /// Creates a new log in directory `dir`, named after the current time so
+    /// that every run of the node starts its own log.
+    pub fn create(dir: &Path) -> io::Result<(Self, PathBuf)> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("messages-{}.log", Clock::utc().format("%Y%m%dT%H%M%S")));
+        let mut file = BufWriter::new(File::options().write(true).create_new(true).open(&path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        file.flush()?;
+
+        let (sender, receiver) = sync_channel(QUEUE_SIZE);
+        let log_path = path.clone();
+        let writer = std::thread::Builder::new().name("message_log".to_string()).spawn(move || {
+            if let Err(err) = write_entries(receiver, file) {
+                error!(target: "client", path = %log_path.display(), ?err, "Failed to write to the message log, no longer recording messages");
+            }
+        })?;
+        let recorder = Self {
+            sender: Mutex::new(Some(sender)),
+            writer: Some(writer),
+            dropped: AtomicU64::new(0),
+        };
+        Ok((recorder, path))
+    }
+
+    /// Records a message received now, or drops it if the writer is
+    /// [`QUEUE_SIZE`] entries behind.
+    pub fn record(&self, message: RecordedMessage) {
+        let timestamp = Clock::utc().timestamp_nanos() as u64;
+        if let Some(sender) = &*self.sender.lock().unwrap() {
+            match sender.try_send(MessageLogEntry { timestamp, message }) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                        warn!(target: "client", "Message log writer fell behind, dropping messages; the log may not replay faithfully");
+                    }
+                    metrics::MESSAGE_LOG_DROPPED.inc();
+                }
+                // The writer gave up, which it logged.
+                Err(TrySendError::Disconnected(_)) => {}
+            }
+        }
+    }
+
+    /// Number of messages which weren't recorded because the writer fell
+    /// behind.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MessageRecorder {
+    /// Waits for the pending entries to be written.
+    fn drop(&mut self) {
+        self.sender.lock().unwrap().take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn write_entries(receiver: Receiver<MessageLogEntry>, mut file: BufWriter<File>) -> io::Result<()> {
+    while let Ok(entry) = receiver.recv() {
+        write_entry(&mut file, &entry)?;
+        for entry in receiver.try_iter() {
+            write_entry(&mut file, &entry)?;
+        }
+        file.flush()?;
+    }
+    Ok(())
+}
+
+fn write_entry(file: &mut impl Write, entry: &MessageLogEntry) -> io::Result<()> {
+    let bytes = entry.try_to_vec()?;
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too large"))?;
+    file.write_all(&len.to_le_bytes())?;
+    file.write_all(&bytes)
+}
+
+/// Iterates over the entries of a message log.
+pub struct MessageLogReader {
+    file: BufReader<File>,
+}
+
+impl MessageLogReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} isn't a message log", path.display()),
+            ));
+        }
+        let version = u32::from_le_bytes(header[8..].try_into().unwrap());
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported message log version {}, expected {}", version, VERSION),
+            ));
+        }
+        Ok(Self { file })
+    }
+
+    fn read_entry(&mut self) -> io::Result<MessageLogEntry> {
+        let mut len = [0u8; 4];
+        self.file.read_exact(&mut len)?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.file.read_exact(&mut bytes)?;
+        MessageLogEntry::try_from_slice(&bytes)
+    }
+}
+
+impl Iterator for MessageLogReader {
+    type Item = io::Result<MessageLogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.file.fill_buf() {
+            Ok([]) => None,
+            Ok(_) => Some(self.read_entry()),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Responses of the shards manager of a replaying client, which are processed
+/// after every replayed message.
+#[derive(Default)]
+struct ShardsManagerResponses(Mutex<VecDeque<ShardsManagerResponse>>);
+
+impl ShardsManagerResponses {
+    fn pop(&self) -> Option<ShardsManagerResponse> {
+        self.0.lock().unwrap().pop_front()
+    }
+}
+
+impl MsgRecipient<WithSpanContext<ShardsManagerResponse>> for ShardsManagerResponses {
+    fn send(
+        &self,
+        msg: WithSpanContext<ShardsManagerResponse>,
+    ) -> BoxFuture<'static, Result<(), MailboxError>> {
+        self.do_send(msg);
+        futures::future::ok(()).boxed()
+    }
+
+    fn do_send(&self, msg: WithSpanContext<ShardsManagerResponse>) {
+        self.0.lock().unwrap().push_back(msg.msg);
+    }
+}
+
+/// Feeds the entries of a message log into a client which isn't connected to
+/// the network and doesn't validate.
+///
+/// Before every message the clock of the client is set to the time the
+/// message was received.  So is the clock of the current thread, which the
+/// code not using the clock of the client yet reads, so the replay must be
+/// driven from the thread which created it.  Requests the client sends to peers are
+/// dropped, so the log needs to contain every message the client depends on.
+pub struct MessageReplay {
+    client: Client,
+    client_adapter: Arc<ShardsManagerResponses>,
+    fake_clock: time::FakeClock,
+    clock: MockClockGuard,
+    /// Timestamp of the first replayed entry and the instant it was mapped to.
+    start: Option<(u64, Instant)>,
+    now: Instant,
+}
+
+impl MessageReplay {
+    /// Creates a client with given config from genesis.  The store of
+    /// `runtime_adapter` should be empty.
+    ///
+    /// Panics if the clock of the current thread is already mocked.
+    pub fn new(
+        config: ClientConfig,
+        genesis: &Genesis,
+        runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+    ) -> Result<Self, Error> {
+        Self::with_chain_genesis(config, ChainGenesis::new(genesis), runtime_adapter, true)
+    }
+
+    /// Same as [`Self::new`], for a chain whose blocks may be produced
+    /// without approvals if `enable_doomslug` isn't set.
+    pub fn with_chain_genesis(
+        config: ClientConfig,
+        chain_genesis: ChainGenesis,
+        runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+        enable_doomslug: bool,
+    ) -> Result<Self, Error> {
+        let fake_clock = time::FakeClock::new(
+            time::Utc::from_unix_timestamp_nanos(chain_genesis.time.timestamp_nanos() as i128)
+                .unwrap(),
+        );
+        let now = fake_clock.clock().now_std();
+        let clock = MockClockGuard::default();
+        clock.set_instant(now);
+        clock.set_utc(chain_genesis.time);
+        let client_adapter = Arc::new(ShardsManagerResponses::default());
+        let mut client = Client::new(
+            fake_clock.clock(),
+            config,
+            chain_genesis,
+            runtime_adapter,
+            Arc::new(OfflineNetwork),
+            client_adapter.clone(),
+            None,
+            enable_doomslug,
+            // Fixed seed so that replays of the same log are identical.
+            [0; 32],
+        )?;
+        client.sync_status = SyncStatus::NoSync;
+        Ok(Self { client, client_adapter, fake_clock, clock, start: None, now })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Replays all entries of the log at `path`.  Returns the number of
+    /// replayed entries.
+    pub fn replay_log(&mut self, path: &Path) -> io::Result<u64> {
+        let mut count = 0;
+        for entry in MessageLogReader::open(path)? {
+            self.replay(entry?);
+            count += 1;
+            if count % 10000 == 0 {
+                let head = self.client.chain.head().map(|tip| tip.height).unwrap_or_default();
+                info!(target: "replay", count, head, "Replaying messages");
+            }
+        }
+        Ok(count)
+    }
+
+    /// Delivers the message of an entry to the client and processes
+    /// everything it triggered, i.e. completed chunks and applied blocks.
+    pub fn replay(&mut self, entry: MessageLogEntry) {
+        self.set_time(entry.timestamp);
+        let client = &mut self.client;
+        match entry.message {
+            RecordedMessage::Block { block, peer_id, was_requested } => {
+                let hash = *block.hash();
+                if let Err(err) =
+                    client.receive_block_impl(block, peer_id, was_requested, Arc::new(|_| {}))
+                {
+                    debug!(target: "replay", %hash, ?err, "Failed to process block");
+                }
+            }
+            RecordedMessage::BlockApproval { approval, peer_id } => {
+                client.collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id));
+            }
+            RecordedMessage::PartialEncodedChunk(chunk) => {
+                if let Err(err) = client.shards_mgr.process_partial_encoded_chunk(chunk.into()) {
+                    debug!(target: "replay", ?err, "Failed to process chunk part");
+                }
+            }
+            RecordedMessage::PartialEncodedChunkForward(forward) => {
+                if let Err(err) = client.shards_mgr.process_partial_encoded_chunk_forward(forward) {
+                    debug!(target: "replay", ?err, "Failed to process forwarded chunk parts");
+                }
+            }
+            RecordedMessage::PartialEncodedChunkResponse(response) => {
+                if let Err(err) = client.shards_mgr.process_partial_encoded_chunk_response(response)
+                {
+                    debug!(target: "replay", ?err, "Failed to process chunk response");
+                }
+            }
+            RecordedMessage::Transaction { transaction, is_forwarded } => {
+                client.process_tx(transaction, is_forwarded, false);
+            }
+        }
+        self.process_shards_manager_responses_and_finish_blocks();
+    }
+
+    /// Sets the clocks to the time of an entry.  The instant is derived
+    /// from the offset to the first entry and never goes backwards, entries
+    /// recorded concurrently may be slightly out of order.
+    fn set_time(&mut self, timestamp: u64) {
+        let (first, start) = *self.start.get_or_insert((timestamp, self.now));
+        self.now =
+            std::cmp::max(self.now, start + Duration::from_nanos(timestamp.saturating_sub(first)));
+        self.clock.set_instant(self.now);
+        self.clock.set_utc(Utc.timestamp_nanos(timestamp as i64));
+        self.fake_clock.advance_until(time::Instant::from(self.now));
+        self.fake_clock.set_utc(time::Utc::from_unix_timestamp_nanos(timestamp as i128).unwrap());
+    }
+
+    fn process_shards_manager_responses_and_finish_blocks(&mut self) {
+        loop {
+            while let Some(response) = self.client_adapter.pop() {
+                match response {
+                    ShardsManagerResponse::ChunkCompleted { partial_chunk, shard_chunk } => {
+                        self.client.on_chunk_completed(
+                            partial_chunk,
+                            shard_chunk,
+                            Arc::new(|_| {}),
+                        );
+                    }
+                    ShardsManagerResponse::InvalidChunk(encoded_chunk) => {
+                        self.client.on_invalid_chunk(encoded_chunk);
+                    }
+                    ShardsManagerResponse::ChunkHeaderReadyForInclusion {
+                        chunk_header,
+                        chunk_producer,
+                    } => {
+                        self.client
+                            .on_chunk_header_ready_for_inclusion(chunk_header, chunk_producer);
+                    }
+                }
+            }
+            if self.client.finish_blocks_in_processing().is_empty() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestEnv;
+    use near_chain::test_utils::{KeyValueRuntime, ValidatorSchedule};
+    use near_chain::Provenance;
+    use near_crypto::{InMemorySigner, KeyType, PublicKey};
+    use near_primitives::hash::CryptoHash;
+    use near_store::test_utils::create_test_store;
+
+    fn transaction(nonce: u64) -> RecordedMessage {
+        let signer = InMemorySigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let transaction = SignedTransaction::send_money(
+            nonce,
+            "test".parse().unwrap(),
+            "other".parse().unwrap(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        RecordedMessage::Transaction { transaction, is_forwarded: nonce % 2 == 0 }
+    }
+
+    #[test]
+    fn test_message_log_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (recorder, path) = MessageRecorder::create(tmp.path()).unwrap();
+        for nonce in 0..10 {
+            recorder.record(transaction(nonce));
+        }
+        drop(recorder);
+
+        let entries =
+            MessageLogReader::open(&path).unwrap().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(entries.len(), 10);
+        for (nonce, entry) in entries.iter().enumerate() {
+            let expected = transaction(nonce as u64).try_to_vec().unwrap();
+            assert_eq!(entry.message.try_to_vec().unwrap(), expected);
+        }
+        assert!(entries.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        // A truncated entry at the end, e.g. after a crash, is an error.
+        let len = std::fs::metadata(&path).unwrap().len();
+        File::options().write(true).open(&path).unwrap().set_len(len - 1).unwrap();
+        let entries = MessageLogReader::open(&path).unwrap().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 10);
+        assert!(entries[9].is_err());
+
+        std::fs::write(&path, b"garbage garbage").unwrap();
+        assert!(MessageLogReader::open(&path).is_err());
+    }
+
+    /// Blocks recorded while a node produces them bring a client replaying
+    /// the log to the same head.
+    #[test]
+    fn test_record_and_replay() {
+        let mut env = TestEnv::builder(ChainGenesis::test()).build();
+        let tmp = tempfile::tempdir().unwrap();
+        let (recorder, path) = MessageRecorder::create(tmp.path()).unwrap();
+        let peer_id = PeerId::new(PublicKey::empty(KeyType::ED25519));
+        for height in 1..=5 {
+            let block = env.clients[0].produce_block(height).unwrap().unwrap();
+            recorder.record(RecordedMessage::Block {
+                block: block.clone(),
+                peer_id: peer_id.clone(),
+                was_requested: false,
+            });
+            env.process_block(0, block, Provenance::PRODUCED);
+        }
+        assert_eq!(recorder.dropped(), 0);
+        drop(recorder);
+
+        let chain_genesis = env.chain_genesis.clone();
+        let vs = ValidatorSchedule::new().block_producers_per_epoch(vec![env.validators.clone()]);
+        let runtime = Arc::new(KeyValueRuntime::new_with_validators(
+            create_test_store(),
+            vs,
+            chain_genesis.epoch_length,
+        ));
+        let mut config = ClientConfig::test(true, 10, 20, 1, false, true, true);
+        config.epoch_length = chain_genesis.epoch_length;
+        let mut replay =
+            MessageReplay::with_chain_genesis(config, chain_genesis, runtime, false).unwrap();
+        assert_eq!(replay.replay_log(&path).unwrap(), 5);
+        assert_eq!(replay.client().chain.head().unwrap(), env.clients[0].chain.head().unwrap());
+    }
+}
@@ -1,8 +1,8 @@
 use near_o11y::metrics::{
-    exponential_buckets, try_create_counter, try_create_gauge, try_create_histogram,
-    try_create_histogram_vec, try_create_int_counter, try_create_int_counter_vec,
-    try_create_int_gauge, try_create_int_gauge_vec, Counter, Gauge, Histogram, HistogramVec,
-    IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    exponential_buckets, try_create_counter, try_create_gauge, try_create_gauge_vec,
+    try_create_histogram, try_create_histogram_vec, try_create_int_counter,
+    try_create_int_counter_vec, try_create_int_gauge, try_create_int_gauge_vec, Counter, Gauge,
+    GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
 use once_cell::sync::Lazy;
 
@@ -126,6 +126,15 @@ pub(crate) static VALIDATORS_BLOCKS_EXPECTED: Lazy<IntGaugeVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static VALIDATOR_KICKOUT_MARGIN: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_validator_kickout_margin",
+        "Percentage points by which the share of blocks or chunks produced by the validator of this node in the current epoch is above the kickout threshold",
+        &["kind"],
+    )
+    .unwrap()
+});
+
 pub(crate) static SYNC_STATUS: Lazy<IntGauge> =
     Lazy::new(|| try_create_int_gauge("near_sync_status", "Node sync status").unwrap());
 
@@ -142,6 +151,31 @@ pub(crate) static PROTOCOL_UPGRADE_BLOCK_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static PROTOCOL_VERSION_VOTES: Lazy<GaugeVec> = Lazy::new(|| {
+    try_create_gauge_vec(
+        "near_protocol_version_votes",
+        "Share of the stake of the block producers of the current epoch voting for a protocol version",
+        &["protocol_version"],
+    )
+    .unwrap()
+});
+
+pub(crate) static PROTOCOL_VERSION_VOTE_THRESHOLD: Lazy<Gauge> = Lazy::new(|| {
+    try_create_gauge(
+        "near_protocol_version_vote_threshold",
+        "Share of the stake of the block producers a protocol version needs more votes than to be adopted",
+    )
+    .unwrap()
+});
+
+pub(crate) static PROTOCOL_VERSION_PROJECTED: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_protocol_version_projected",
+        "Protocol version the epoch after next would have if the current epoch ended now",
+    )
+    .unwrap()
+});
+
 pub(crate) static PEERS_WITH_INVALID_HASH: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_peers_with_invalid_hash", "Number of peers that are on invalid hash")
         .unwrap()
@@ -191,6 +225,34 @@ pub(crate) static CLIENT_MESSAGES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static CLIENT_MESSAGE_QUEUE_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_client_message_queue_delay",
+        "Time messages waited before client actor processed them, by priority",
+        &["priority"],
+        Some(exponential_buckets(0.0001, 1.6, 20).unwrap()),
+    )
+    .unwrap()
+});
+
+pub(crate) static CLIENT_DEFERRED_MESSAGES: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_client_deferred_messages",
+        "Number of low priority messages deferred by client actor",
+    )
+    .unwrap()
+});
+
+pub(crate) static SYNC_STEP_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_sync_step_time",
+        "Time taken by a step of sync in the sync actor, by sync status",
+        &["status"],
+        Some(exponential_buckets(0.0001, 1.6, 20).unwrap()),
+    )
+    .unwrap()
+});
+
 pub(crate) static CLIENT_MESSAGES_PROCESSING_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     try_create_histogram_vec(
         "near_client_messages_processing_time",
@@ -257,6 +319,25 @@ pub(crate) static FINAL_DOOMSLUG_BLOCK_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static BLOCK_PROPAGATION_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_block_propagation_delay",
+        "Time between the timestamp of a block and its first arrival at the node, by block producer",
+        &["producer"],
+        Some(exponential_buckets(0.01, 1.5, 16).unwrap()),
+    )
+    .unwrap()
+});
+
+pub(crate) static FINALITY_LAG: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_finality_lag_blocks",
+        "Difference between the height of the last processed block and the height of its last final block, by kind of finality",
+        &["finality"],
+    )
+    .unwrap()
+});
+
 static NODE_DB_VERSION: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge("near_node_db_version", "DB version used by the node").unwrap()
 });
@@ -324,6 +405,58 @@ pub static VIEW_CLIENT_MESSAGE_TIME: Lazy<near_o11y::metrics::HistogramVec> = La
     .unwrap()
 });
 
+pub(crate) static VIEW_CALLS_PENDING: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_view_calls_pending",
+        "Number of contract function calls waiting for or being executed by the view call pool",
+    )
+    .unwrap()
+});
+
+pub(crate) static STATE_REQUESTS_THROTTLED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_client_state_requests_throttled_total",
+        "Number of state sync requests of peers dropped by the view client, by the limit reached",
+        &["limit"],
+    )
+    .unwrap()
+});
+
+pub(crate) static STATE_REQUESTS_IN_PROGRESS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_view_client_state_requests_in_progress",
+        "Number of state sync requests of peers being served by the view client threads",
+    )
+    .unwrap()
+});
+
+pub(crate) static SYNC_SERVED_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_client_sync_served_bytes_total",
+        "Size of the responses to sync requests of peers sent by the view client, by kind of data",
+        &["kind"],
+    )
+    .unwrap()
+});
+
+pub(crate) static SYNC_REQUESTS_THROTTLED_BY_BANDWIDTH: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_client_sync_requests_throttled_by_bandwidth_total",
+        "Number of sync requests of peers dropped by the view client because a bandwidth budget was exhausted, by kind of data and budget",
+        &["kind", "budget"],
+    )
+    .unwrap()
+});
+
+pub(crate) static VIEW_CALLS_FAILED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_view_calls_failed_total",
+        "Number of contract function calls rejected because the view call pool was full or which timed out",
+        &["reason"],
+    )
+    .unwrap()
+});
+
 pub static PRODUCE_AND_DISTRIBUTE_CHUNK_TIME: Lazy<near_o11y::metrics::HistogramVec> =
     Lazy::new(|| {
         try_create_histogram_vec(
@@ -334,6 +467,42 @@ pub static PRODUCE_AND_DISTRIBUTE_CHUNK_TIME: Lazy<near_o11y::metrics::Histogram
         )
         .unwrap()
     });
+pub(crate) static DRY_RUN_BLOCK_PRODUCTION_TIME: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram(
+        "near_dry_run_block_production_time",
+        "Time it would have taken to produce a block in dry-run production mode",
+    )
+    .unwrap()
+});
+
+pub(crate) static DRY_RUN_CHUNK_PRODUCTION_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_dry_run_chunk_production_time",
+        "Time it would have taken to produce and encode a chunk in dry-run production mode",
+        &["shard_id"],
+        Some(exponential_buckets(0.001, 2.0, 16).unwrap()),
+    )
+    .unwrap()
+});
+
+pub(crate) static NETWORK_MESSAGES_QUEUE_LENGTH: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_network_messages_queue_length",
+        "Number of messages received from the network and not yet handled, by actor",
+        &["actor"],
+    )
+    .unwrap()
+});
+
+pub(crate) static NETWORK_MESSAGES_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_network_messages_dropped_total",
+        "Number of messages received from the network and dropped because the queue of the actor was full",
+        &["actor", "message"],
+    )
+    .unwrap()
+});
+
 /// Exports neard, protocol and database versions via Prometheus metrics.
 ///
 /// Sets metrics which export node’s max supported protocol version, used
@@ -353,3 +522,11 @@ pub(crate) fn export_version(neard_version: &near_primitives::version::Version)
         ])
         .inc();
 }
+
+pub(crate) static MESSAGE_LOG_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_message_log_dropped_total",
+        "Number of inbound messages not recorded in the message log because the writer fell behind",
+    )
+    .unwrap()
+});
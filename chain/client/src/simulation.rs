@@ -0,0 +1,650 @@
+//! Deterministic simulation of several clients reaching consensus.
+//!
+//! [`Simulation`] drives the clients of a [`TestEnv`] the way `ClientActor`
+//! would — doomslug timer, block production, handling of blocks, approvals
+//! and chunk parts — but from a single thread, using a virtual clock and a
+//! simulated network.  Time only moves when the simulation advances it and
+//! every message sent over the network is delivered after a configurable
+//! latency, unless the link is partitioned or the message is randomly
+//! dropped.  The same [`SimulatedNetwork`] connects the actors of a
+//! [`LoopbackNetwork`](crate::loopback_network::LoopbackNetwork), for tests
+//! which run the nodes with real timers instead.  With the same seed and the same sequence of calls, runs are
+//! reproducible, which makes it possible to test doomslug and fork choice
+//! edge cases that are hard to hit with real timers.
+//!
+//! The clients read the virtual time from a [`time::FakeClock`].  Code which
+//! doesn't use the clock of the client yet reads it through
+//! [`MockClockGuard`], which is thread-local, so the simulation must be
+//! created and driven on the same thread.  Code running on other threads
+//! (e.g. applying chunks) as well as code calling `Instant::now` directly
+//! still observes real time; it is only used for metrics and doesn't affect
+//! consensus.
+//!
+//! ```rust, ignore
+//! let builder = TestEnv::builder(ChainGenesis::test())
+//!     .clients_count(4)
+//!     .validator_seats(4)
+//!     .enable_doomslug(true);
+//! let mut sim = Simulation::new(builder, 42);
+//! sim.network().partition(&[&[0, 1], &[2, 3]]);
+//! sim.run_for(Duration::from_secs(1));
+//! sim.network().heal();
+//! assert!(sim.run_until(|env| env.clients[0].chain.head().unwrap().height >= 10, Duration::from_secs(5)));
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use borsh::BorshSerialize;
+use chrono::DateTime;
+
+use near_chain::Provenance;
+use near_crypto::{KeyType, PublicKey};
+use near_network::time;
+use near_network::types::{
+    NetworkRequests, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
+    PeerManagerMessageRequest,
+};
+use near_primitives::block::{Approval, ApprovalType, Block};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::network::PeerId;
+use near_primitives::sharding::{PartialEncodedChunk, PartialEncodedChunkWithArcReceipts};
+use near_primitives::time::{Instant, MockClockGuard, Utc};
+use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::utils::MaybeValidated;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tracing::{debug, warn};
+
+use crate::test_utils::{TestEnv, TestEnvBuilder};
+use near_client_primitives::types::Error;
+
+/// Default time the simulation advances by on every step.
+const DEFAULT_TICK: Duration = Duration::from_millis(1);
+/// Default one-way latency of every link.
+const DEFAULT_LATENCY: Duration = Duration::from_millis(2);
+
+/// Conditions of the link from one node to another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkConditions {
+    /// Time it takes a message to get to the other node once it's sent.
+    pub delay: Duration,
+    /// Probability of a message getting dropped, from 0 to 1.
+    pub drop_probability: f64,
+    /// Number of bytes per second the link can send, if capped.  Messages
+    /// are sized by their borsh encoding and queue up behind each other.
+    pub bandwidth: Option<u64>,
+}
+
+impl Default for LinkConditions {
+    fn default() -> Self {
+        Self { delay: DEFAULT_LATENCY, drop_probability: 0.0, bandwidth: None }
+    }
+}
+
+/// Number of messages sent over a [`SimulatedNetwork`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub delivered: u64,
+    /// Messages dropped on lossy links or between partitions.
+    pub dropped: u64,
+}
+
+/// Conditions of the network connecting the simulated nodes.  Nodes are
+/// identified by their index: in [`TestEnv::clients`] for a [`Simulation`]
+/// and in the order they were added for a
+/// [`LoopbackNetwork`](crate::loopback_network::LoopbackNetwork).
+pub struct SimulatedNetwork {
+    default_conditions: LinkConditions,
+    links: HashMap<(usize, usize), LinkConditions>,
+    /// Time until which each link is busy sending the messages queued on it.
+    busy_until: HashMap<(usize, usize), Instant>,
+    /// Partition each node belongs to.  Nodes in different partitions cannot
+    /// talk to each other.  Empty when the network is not partitioned.
+    partition_of: HashMap<usize, usize>,
+    rng: StdRng,
+    stats: NetworkStats,
+}
+
+impl SimulatedNetwork {
+    /// Creates a network without partitions, whose links have the default
+    /// conditions.  `seed` initialises the random number generator deciding
+    /// which messages are dropped.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            default_conditions: LinkConditions::default(),
+            links: HashMap::new(),
+            busy_until: HashMap::new(),
+            partition_of: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+            stats: NetworkStats::default(),
+        }
+    }
+
+    /// Sets the conditions of the links without conditions of their own.
+    pub fn set_default_conditions(&mut self, conditions: LinkConditions) {
+        self.default_conditions = conditions;
+    }
+
+    /// Sets the conditions of the link from `from` to `to`, but not of the
+    /// link back.
+    pub fn set_link_conditions(&mut self, from: usize, to: usize, conditions: LinkConditions) {
+        self.links.insert((from, to), conditions);
+    }
+
+    /// Sets latency of all links which don't have one set explicitly.
+    pub fn set_default_latency(&mut self, latency: Duration) {
+        self.default_conditions.delay = latency;
+    }
+
+    /// Sets latency of messages sent from node `from` to node `to`.
+    pub fn set_latency(&mut self, from: usize, to: usize, latency: Duration) {
+        let default_conditions = &self.default_conditions;
+        self.links.entry((from, to)).or_insert_with(|| default_conditions.clone()).delay = latency;
+    }
+
+    /// Sets probability with which every message is dropped.
+    pub fn set_drop_probability(&mut self, probability: f64) {
+        assert!((0.0..=1.0).contains(&probability), "invalid probability {}", probability);
+        self.default_conditions.drop_probability = probability;
+        for conditions in self.links.values_mut() {
+            conditions.drop_probability = probability;
+        }
+    }
+
+    /// Splits the network into given groups of nodes.  Messages between nodes
+    /// of different groups are dropped, so are messages from and to nodes not
+    /// listed in any group.  Messages already in flight are not affected.
+    pub fn partition(&mut self, groups: &[&[usize]]) {
+        self.partition_of = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group, nodes)| nodes.iter().map(move |node| (*node, group)))
+            .collect();
+    }
+
+    /// Removes the partition set with [`Self::partition`].
+    pub fn heal(&mut self) {
+        self.partition_of.clear();
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.stats.clone()
+    }
+
+    /// Whether node `from` can reach node `to` through the partition.
+    pub(crate) fn is_connected(&self, from: usize, to: usize) -> bool {
+        self.partition_of.is_empty()
+            || (self.partition_of.contains_key(&from)
+                && self.partition_of.get(&from) == self.partition_of.get(&to))
+    }
+
+    /// Returns when a message sent from `from` to `to` at `now` is delivered
+    /// or `None` if it is lost.  `size` is called for the size of the message
+    /// if the link has a bandwidth cap.
+    pub(crate) fn delivery_time(
+        &mut self,
+        now: Instant,
+        from: usize,
+        to: usize,
+        size: impl FnOnce() -> u64,
+    ) -> Option<Instant> {
+        if !self.is_connected(from, to) {
+            self.stats.dropped += 1;
+            return None;
+        }
+        let conditions = self.links.get(&(from, to)).unwrap_or(&self.default_conditions).clone();
+        if conditions.drop_probability > 0.0
+            && self.rng.gen_bool(conditions.drop_probability.min(1.0))
+        {
+            self.stats.dropped += 1;
+            return None;
+        }
+        let size = if conditions.bandwidth.is_some() { size() } else { 0 };
+        let (arrival, busy_until) =
+            schedule(now, self.busy_until.get(&(from, to)).copied(), size, &conditions);
+        self.busy_until.insert((from, to), busy_until);
+        self.stats.delivered += 1;
+        Some(arrival)
+    }
+}
+
+/// Returns when a message of `size` bytes sent at `now` gets to the other end
+/// of a link busy sending earlier messages until `busy_until`, and until when
+/// the link is busy sending it.
+fn schedule(
+    now: Instant,
+    busy_until: Option<Instant>,
+    size: u64,
+    conditions: &LinkConditions,
+) -> (Instant, Instant) {
+    let start = busy_until.map_or(now, |busy_until| busy_until.max(now));
+    let busy_until = match conditions.bandwidth {
+        Some(bandwidth) => start + Duration::from_secs_f64(size as f64 / bandwidth.max(1) as f64),
+        None => start,
+    };
+    (busy_until + conditions.delay, busy_until)
+}
+
+/// Size of the borsh encoding of `value`, for the bandwidth caps of the links.
+pub(crate) fn borsh_size(value: &impl BorshSerialize) -> u64 {
+    value.try_to_vec().map_or(0, |bytes| bytes.len() as u64)
+}
+
+/// Messages exchanged between the simulated nodes.
+#[derive(Debug)]
+enum Message {
+    Block { block: Block, was_requested: bool },
+    BlockRequest { hash: CryptoHash },
+    Approval(Approval),
+    PartialEncodedChunk(PartialEncodedChunkWithArcReceipts),
+    PartialEncodedChunkRequest { request: PartialEncodedChunkRequestMsg, route_back: CryptoHash },
+    PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg),
+}
+
+impl Message {
+    fn size(&self) -> u64 {
+        match self {
+            Message::Block { block, .. } => borsh_size(block),
+            Message::BlockRequest { hash } => borsh_size(hash),
+            Message::Approval(approval) => borsh_size(approval),
+            Message::PartialEncodedChunk(partial_encoded_chunk) => {
+                borsh_size(&PartialEncodedChunk::from(partial_encoded_chunk.clone()))
+            }
+            Message::PartialEncodedChunkRequest { request, .. } => borsh_size(request),
+            Message::PartialEncodedChunkResponse(response) => borsh_size(response),
+        }
+    }
+}
+
+struct Envelope {
+    from: usize,
+    to: usize,
+    message: Message,
+}
+
+pub struct Simulation {
+    pub env: TestEnv,
+    network: SimulatedNetwork,
+    fake_clock: time::FakeClock,
+    clock: MockClockGuard,
+    start: Instant,
+    start_utc: DateTime<Utc>,
+    now: Instant,
+    tick: Duration,
+    peer_ids: Vec<PeerId>,
+    /// Messages in flight, keyed by delivery time and sequence number so that
+    /// messages due at the same time are delivered in the order they were sent.
+    in_flight: BTreeMap<(Instant, u64), Envelope>,
+    next_seq: u64,
+}
+
+impl Simulation {
+    /// Creates a simulation of the clients built by `builder`, which read the
+    /// time from the virtual clock of the simulation.  `seed` initialises the
+    /// random number generator deciding which messages are dropped.
+    ///
+    /// Panics if the clock of the current thread is already mocked.
+    pub fn new(builder: TestEnvBuilder, seed: u64) -> Self {
+        let fake_clock = time::FakeClock::new(time::Utc::now_utc());
+        let env = builder.clock(fake_clock.clock()).build();
+        let start = fake_clock.clock().now_std();
+        let start_utc = fake_clock.clock().now_utc_chrono();
+        let clock = MockClockGuard::default();
+        clock.set_instant(start);
+        clock.set_utc(start_utc);
+        let peer_ids = (0..env.clients.len())
+            .map(|idx| {
+                let seed = env.get_client_id(idx).to_string();
+                PeerId::new(PublicKey::from_seed(KeyType::ED25519, &seed))
+            })
+            .collect();
+        Self {
+            env,
+            network: SimulatedNetwork::new(seed),
+            fake_clock,
+            clock,
+            start,
+            start_utc,
+            now: start,
+            tick: DEFAULT_TICK,
+            peer_ids,
+            in_flight: BTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Sets the time the simulation advances by on every step.
+    pub fn set_tick(&mut self, tick: Duration) {
+        assert!(!tick.is_zero());
+        self.tick = tick;
+    }
+
+    pub fn network(&mut self) -> &mut SimulatedNetwork {
+        &mut self.network
+    }
+
+    /// Returns virtual time elapsed since the simulation started.
+    pub fn elapsed(&self) -> Duration {
+        self.now - self.start
+    }
+
+    /// Runs the simulation for `duration` of virtual time.
+    pub fn run_for(&mut self, duration: Duration) {
+        let deadline = self.now + duration;
+        while self.now < deadline {
+            self.step();
+        }
+    }
+
+    /// Runs the simulation until `condition` holds, for at most `timeout` of
+    /// virtual time.  Returns whether the condition was met.
+    pub fn run_until(
+        &mut self,
+        mut condition: impl FnMut(&mut TestEnv) -> bool,
+        timeout: Duration,
+    ) -> bool {
+        let deadline = self.now + timeout;
+        while self.now < deadline {
+            if condition(&mut self.env) {
+                return true;
+            }
+            self.step();
+        }
+        condition(&mut self.env)
+    }
+
+    /// Performs a single step of the simulation: lets every node act at the
+    /// current time, delivers all messages due and advances the clock.
+    pub fn step(&mut self) {
+        for idx in 0..self.env.clients.len() {
+            self.env.process_shards_manager_responses_and_finish_processing_blocks(idx);
+            let client = &mut self.env.clients[idx];
+            if let Ok(header_head) = client.chain.header_head() {
+                client.shards_mgr.resend_chunk_requests(&header_head);
+            }
+            if let Err(err) = self.process_timer(idx) {
+                warn!(target: "simulation", idx, ?err, "Doomslug timer failed");
+            }
+            if let Err(err) = self.produce_blocks(idx) {
+                warn!(target: "simulation", idx, ?err, "Block production failed");
+            }
+            self.send_outgoing(idx);
+        }
+        while let Some(entry) = self.in_flight.first_entry() {
+            if entry.key().0 > self.now {
+                break;
+            }
+            let envelope = entry.remove();
+            let to = envelope.to;
+            self.deliver(envelope);
+            self.send_outgoing(to);
+        }
+        self.set_now(self.now + self.tick);
+    }
+
+    fn set_now(&mut self, now: Instant) {
+        self.fake_clock.advance((now - self.now).try_into().unwrap());
+        self.now = now;
+        self.clock.set_instant(now);
+        self.clock.set_utc(self.start_utc + chrono::Duration::from_std(now - self.start).unwrap());
+    }
+
+    /// Same as `ClientActor::try_doomslug_timer`.
+    fn process_timer(&mut self, idx: usize) -> Result<(), Error> {
+        let client = &mut self.env.clients[idx];
+        let _ = client.check_and_update_doomslug_tip();
+        let approvals = client.doomslug.process_timer(self.now);
+
+        let mut chain_store_update = client.chain.mut_store().store_update();
+        chain_store_update.save_largest_target_height(client.doomslug.get_largest_target_height());
+        chain_store_update.commit()?;
+
+        let head = client.chain.head()?;
+        if client.is_validator(&head.epoch_id, &head.last_block_hash)
+            || client.is_validator(&head.next_epoch_id, &head.last_block_hash)
+        {
+            let tip = client.doomslug.get_tip().0;
+            for approval in approvals {
+                client.send_approval(&tip, approval)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as `ClientActor::handle_block_production`, except that produced
+    /// blocks are broadcast through the simulated network.
+    fn produce_blocks(&mut self, idx: usize) -> Result<(), Error> {
+        let client = &mut self.env.clients[idx];
+        let me = match &client.validator_signer {
+            Some(signer) => signer.validator_id().clone(),
+            None => return Ok(()),
+        };
+        let _ = client.check_and_update_doomslug_tip();
+        let head = client.chain.head()?;
+        let latest_known = client.chain.store().get_latest_known()?;
+        let epoch_id =
+            client.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash)?;
+
+        let mut produced = vec![];
+        for height in
+            latest_known.height + 1..=client.doomslug.get_largest_height_crossing_threshold()
+        {
+            if client.runtime_adapter.get_block_producer(&epoch_id, height)? != me {
+                continue;
+            }
+            let num_chunks =
+                client.num_chunk_headers_ready_for_inclusion(&epoch_id, &head.last_block_hash);
+            let have_all_chunks = head.height == 0
+                || num_chunks as u64 == client.runtime_adapter.num_shards(&epoch_id)?;
+            if !client.doomslug.ready_to_produce_block(self.now, height, have_all_chunks, false) {
+                continue;
+            }
+            if let Some(block) = client.produce_block(height)? {
+                debug!(target: "simulation", idx, height, hash = %block.hash(), "Produced block");
+                produced.push(block.clone());
+                if let Err(err) = client.start_process_block(
+                    MaybeValidated::from_validated(block),
+                    Provenance::PRODUCED,
+                    None,
+                    Arc::new(|_| {}),
+                ) {
+                    if !matches!(err, near_chain::Error::ChunksMissing(_)) {
+                        return Err(err.into());
+                    }
+                }
+                client.finish_blocks_in_processing();
+            }
+        }
+        for block in produced {
+            self.broadcast(idx, || Message::Block { block: block.clone(), was_requested: false });
+        }
+        Ok(())
+    }
+
+    fn deliver(&mut self, envelope: Envelope) {
+        let Envelope { from, to, message } = envelope;
+        let peer_id = self.peer_ids[from].clone();
+        let client = &mut self.env.clients[to];
+        match message {
+            Message::Block { block, was_requested } => {
+                let _ = client.receive_block_impl(block, peer_id, was_requested, Arc::new(|_| {}));
+                self.env.process_shards_manager_responses_and_finish_processing_blocks(to);
+            }
+            Message::BlockRequest { hash } => {
+                if let Ok(block) = client.chain.get_block(&hash) {
+                    self.send(to, from, Message::Block { block, was_requested: true });
+                }
+            }
+            Message::Approval(approval) => {
+                client.collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id));
+            }
+            Message::PartialEncodedChunk(partial_encoded_chunk) => {
+                if let Err(err) = client.shards_mgr.process_partial_encoded_chunk(
+                    PartialEncodedChunk::from(partial_encoded_chunk).into(),
+                ) {
+                    debug!(target: "simulation", to, ?err, "Failed to process chunk part");
+                }
+                self.env.process_shards_manager_responses_and_finish_processing_blocks(to);
+            }
+            Message::PartialEncodedChunkRequest { request, route_back } => {
+                client.shards_mgr.process_partial_encoded_chunk_request(request, route_back);
+            }
+            Message::PartialEncodedChunkResponse(response) => {
+                if let Err(err) = client.shards_mgr.process_partial_encoded_chunk_response(response)
+                {
+                    debug!(target: "simulation", to, ?err, "Failed to process chunk response");
+                }
+                self.env.process_shards_manager_responses_and_finish_processing_blocks(to);
+            }
+        }
+    }
+
+    /// Routes the requests the client of node `idx` sent to its network
+    /// adapter through the simulated network.
+    fn send_outgoing(&mut self, idx: usize) {
+        while let Some(request) = self.env.network_adapters[idx].pop() {
+            let request = match request {
+                PeerManagerMessageRequest::NetworkRequests(request) => request,
+                _ => continue,
+            };
+            match request {
+                NetworkRequests::Block { block } => {
+                    self.broadcast(idx, || Message::Block {
+                        block: block.clone(),
+                        was_requested: false,
+                    });
+                }
+                NetworkRequests::Approval { approval_message } => {
+                    if let Some(to) = self.node_of_account(&approval_message.target) {
+                        self.send(idx, to, Message::Approval(approval_message.approval));
+                    }
+                }
+                NetworkRequests::BlockRequest { hash, peer_id } => {
+                    if let Some(to) = self.peer_ids.iter().position(|id| id == &peer_id) {
+                        self.send(idx, to, Message::BlockRequest { hash });
+                    }
+                }
+                NetworkRequests::PartialEncodedChunkMessage {
+                    account_id,
+                    partial_encoded_chunk,
+                } => {
+                    if let Some(to) = self.node_of_account(&account_id) {
+                        self.send(idx, to, Message::PartialEncodedChunk(partial_encoded_chunk));
+                    }
+                }
+                NetworkRequests::PartialEncodedChunkRequest { target, request, .. } => {
+                    let to =
+                        target.account_id.and_then(|account_id| self.node_of_account(&account_id));
+                    if let Some(to) = to {
+                        let route_back = route_back(idx);
+                        self.send(
+                            idx,
+                            to,
+                            Message::PartialEncodedChunkRequest { request, route_back },
+                        );
+                    }
+                }
+                NetworkRequests::PartialEncodedChunkResponse { route_back: hash, response } => {
+                    let to = (0..self.env.clients.len()).find(|&node| route_back(node) == hash);
+                    if let Some(to) = to {
+                        self.send(idx, to, Message::PartialEncodedChunkResponse(response));
+                    }
+                }
+                request => {
+                    debug!(target: "simulation", idx, ?request, "Ignoring network request");
+                }
+            }
+        }
+    }
+
+    fn broadcast(&mut self, from: usize, message: impl Fn() -> Message) {
+        for to in 0..self.env.clients.len() {
+            if to != from {
+                self.send(from, to, message());
+            }
+        }
+    }
+
+    fn send(&mut self, from: usize, to: usize, message: Message) {
+        match self.network.delivery_time(self.now, from, to, || message.size()) {
+            Some(arrival) => {
+                let seq = self.next_seq;
+                self.next_seq += 1;
+                self.in_flight.insert((arrival, seq), Envelope { from, to, message });
+            }
+            None => {
+                debug!(target: "simulation", from, to, ?message, "Message lost");
+            }
+        }
+    }
+
+    fn node_of_account(&self, account_id: &AccountId) -> Option<usize> {
+        (0..self.env.clients.len()).find(|&idx| self.env.get_client_id(idx) == account_id)
+    }
+
+    /// Returns head heights of all nodes.
+    pub fn head_heights(&self) -> Vec<BlockHeight> {
+        self.env.clients.iter().map(|client| client.chain.head().unwrap().height).collect()
+    }
+}
+
+/// Route back hash identifying node `idx` as the sender of a chunk request.
+fn route_back(idx: usize) -> CryptoHash {
+    hash(&(idx as u64).to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let now = Instant::now();
+        let conditions = LinkConditions { delay: Duration::ZERO, ..Default::default() };
+        assert_eq!(schedule(now, None, 1000, &conditions), (now, now));
+
+        let conditions = LinkConditions {
+            delay: Duration::from_millis(50),
+            drop_probability: 0.0,
+            bandwidth: Some(10_000),
+        };
+        let (arrival, busy_until) = schedule(now, None, 1000, &conditions);
+        assert_eq!(busy_until, now + Duration::from_millis(100));
+        assert_eq!(arrival, now + Duration::from_millis(150));
+        // The second message waits for the first one to be sent.
+        let (arrival, busy_until) = schedule(now, Some(busy_until), 1000, &conditions);
+        assert_eq!(busy_until, now + Duration::from_millis(200));
+        assert_eq!(arrival, now + Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_partition() {
+        let mut network = SimulatedNetwork::new(0);
+        assert!(network.is_connected(0, 2));
+        network.partition(&[&[0], &[1]]);
+        assert!(!network.is_connected(0, 1));
+        // Nodes in none of the groups are cut off from every node.
+        assert!(!network.is_connected(0, 2));
+        assert!(!network.is_connected(2, 1));
+        assert!(!network.is_connected(2, 3));
+        network.partition(&[&[0, 1]]);
+        assert!(network.is_connected(0, 1));
+        assert!(!network.is_connected(1, 2));
+        network.heal();
+        assert!(network.is_connected(1, 2));
+    }
+
+    /// Networks with the same seed drop the same messages.
+    #[test]
+    fn test_drops_are_seeded() {
+        let dropped = |seed| {
+            let mut network = SimulatedNetwork::new(seed);
+            network.set_drop_probability(0.5);
+            let now = Instant::now();
+            (0..100).map(|_| network.delivery_time(now, 0, 1, || 0).is_none()).collect::<Vec<_>>()
+        };
+        assert_eq!(dropped(42), dropped(42));
+        assert_ne!(dropped(42), dropped(43));
+    }
+}
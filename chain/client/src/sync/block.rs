@@ -1,17 +1,20 @@
 use near_chain::{check_known, ChainStoreAccess};
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use chrono::{DateTime, Duration};
 use rand::seq::IteratorRandom;
 
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
 use near_chain::Chain;
 use near_network::types::{HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter};
 use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
 
-use near_primitives::time::{Clock, Utc};
+use near_network::time;
+use near_primitives::time::Utc;
 
 use near_primitives::types::{BlockHeight, BlockHeightDelta};
 
@@ -31,25 +34,62 @@ pub struct BlockSyncRequest {
     head: CryptoHash,
     // when the block was requested
     when: DateTime<Utc>,
+    // peer each block was requested from
+    peers: HashMap<CryptoHash, PeerId>,
 }
 
 /// Helper to track block syncing.
 pub struct BlockSync {
+    clock: time::Clock,
     network_adapter: Arc<dyn PeerManagerAdapter>,
     last_request: Option<BlockSyncRequest>,
     /// How far to fetch blocks vs fetch state.
     block_fetch_horizon: BlockHeightDelta,
     /// Whether to enforce block sync
     archive: bool,
+    /// Whether to switch to state sync when more than `block_fetch_horizon`
+    /// blocks behind.
+    state_sync_fallback: bool,
+    /// Whether we already logged that state sync is skipped because
+    /// `state_sync_fallback` is disabled.
+    fallback_disabled_logged: bool,
+    /// Peers which responded that they garbage collected a requested block,
+    /// per block.  The block isn't requested from them again.
+    garbage_collected: HashMap<CryptoHash, HashSet<PeerId>>,
 }
 
 impl BlockSync {
     pub fn new(
+        clock: time::Clock,
         network_adapter: Arc<dyn PeerManagerAdapter>,
         block_fetch_horizon: BlockHeightDelta,
         archive: bool,
+        state_sync_fallback: bool,
     ) -> Self {
-        BlockSync { network_adapter, last_request: None, block_fetch_horizon, archive }
+        BlockSync {
+            clock,
+            network_adapter,
+            last_request: None,
+            block_fetch_horizon,
+            archive,
+            state_sync_fallback,
+            fallback_disabled_logged: false,
+            garbage_collected: HashMap::new(),
+        }
+    }
+
+    /// Records that `peer_id` has garbage collected the block, and makes the
+    /// next run request the block from another peer.  Ignored unless the
+    /// block was last requested from `peer_id`.
+    pub fn block_garbage_collected(&mut self, hash: CryptoHash, peer_id: PeerId) {
+        let requested = self.last_request.as_ref().and_then(|request| request.peers.get(&hash));
+        if requested != Some(&peer_id) {
+            debug!(target: "sync", %hash, %peer_id, "Block sync: ignoring garbage collected block which wasn't requested from the peer");
+            return;
+        }
+        debug!(target: "sync", %hash, %peer_id, "Block sync: peer has garbage collected the block");
+        self.garbage_collected.entry(hash).or_default().insert(peer_id);
+        self.last_request = None;
     }
 
     /// Runs check if block sync is needed, if it's needed and it's too far - sync state is started instead (returning true).
@@ -80,7 +120,7 @@ impl BlockSync {
     }
 
     /// Check if state download is required
-    fn check_state_needed(&self, chain: &Chain) -> Result<bool, near_chain::Error> {
+    fn check_state_needed(&mut self, chain: &Chain) -> Result<bool, near_chain::Error> {
         let head = chain.head()?;
         let header_head = chain.header_head()?;
 
@@ -95,7 +135,26 @@ impl BlockSync {
             if head.height < header_head.height.saturating_sub(self.block_fetch_horizon)
                 && !self.archive
             {
+                let blocks_behind = header_head.height - head.height;
+                if !self.state_sync_fallback {
+                    if !self.fallback_disabled_logged {
+                        info!(
+                            target: "sync",
+                            blocks_behind,
+                            block_fetch_horizon = self.block_fetch_horizon,
+                            "Applying all the missing blocks because consensus.state_sync_fallback is disabled");
+                        self.fallback_disabled_logged = true;
+                    }
+                    return Ok(false);
+                }
                 // Epochs are different and we are too far from horizon, State Sync is needed
+                info!(
+                    target: "sync",
+                    head_height = head.height,
+                    header_head_height = header_head.height,
+                    blocks_behind,
+                    block_fetch_horizon = self.block_fetch_horizon,
+                    "Too far behind to apply the blocks, switching to state sync. Set consensus.state_sync_fallback to false to apply all the blocks instead");
                 return Ok(true);
             }
         }
@@ -117,8 +176,11 @@ impl BlockSync {
         let chain_head = chain.head()?;
         // update last request now because we want to update it whether or not the rest of the logic
         // succeeds
-        self.last_request =
-            Some(BlockSyncRequest { head: chain_head.last_block_hash, when: Clock::utc() });
+        self.last_request = Some(BlockSyncRequest {
+            head: chain_head.last_block_hash,
+            when: self.clock.now_utc_chrono(),
+            peers: HashMap::new(),
+        });
 
         // reference_hash is the last block on the canonical chain that is in store (processed)
         let reference_hash = {
@@ -185,20 +247,28 @@ impl BlockSync {
 
         let gc_stop_height = chain.runtime_adapter.get_gc_stop_height(&header_head.last_block_hash);
 
+        self.garbage_collected.retain(|hash, _| requests.iter().any(|(_, h)| h == hash));
         for request in requests {
             let (height, hash) = request;
             let request_from_archival = self.archive && height < gc_stop_height;
-            let peer = if request_from_archival {
-                let archival_peer_iter = highest_height_peers.iter().filter(|p| p.archival);
-                archival_peer_iter.choose(&mut rand::thread_rng())
-            } else {
-                let peer_iter = highest_height_peers.iter();
-                peer_iter.choose(&mut rand::thread_rng())
-            };
+            let garbage_collected = self.garbage_collected.get(&hash);
+            // Peers which advertise the range of heights they hold are asked only for the
+            // blocks within it. Of the other ones, only archival peers are asked for the
+            // blocks which are likely to be garbage collected.
+            let peer = highest_height_peers
+                .iter()
+                .filter(|p| {
+                    garbage_collected.map_or(true, |peers| !peers.contains(&p.peer_info.id))
+                })
+                .filter(|p| p.holds_height(height).unwrap_or(p.archival || !request_from_archival))
+                .choose(&mut rand::thread_rng());
 
             if let Some(peer) = peer {
                 debug!(target: "sync", "Block sync: {}/{} requesting block {} at height {} from {} (out of {} peers)",
                        chain_head.height, header_head.height, hash, height, peer.peer_info.id, highest_height_peers.len());
+                if let Some(last_request) = &mut self.last_request {
+                    last_request.peers.insert(hash, peer.peer_info.id.clone());
+                }
                 self.network_adapter.do_send(
                     PeerManagerMessageRequest::NetworkRequests(NetworkRequests::BlockRequest {
                         hash,
@@ -222,7 +292,8 @@ impl BlockSync {
         match &self.last_request {
             None => Ok(true),
             Some(request) => Ok(chain.head()?.last_block_hash != request.head
-                || Clock::utc() - request.when > Duration::seconds(BLOCK_REQUEST_TIMEOUT)),
+                || self.clock.now_utc_chrono() - request.when
+                    > Duration::seconds(BLOCK_REQUEST_TIMEOUT)),
         }
     }
 }
@@ -239,6 +310,7 @@ mod test {
 
     use near_primitives::network::PeerId;
     use near_primitives::utils::MaybeValidated;
+    use near_primitives::version::PROTOCOL_VERSION;
 
     use super::*;
     use crate::test_utils::TestEnv;
@@ -284,6 +356,8 @@ mod test {
                 highest_block_hash: Default::default(),
                 tracked_shards: vec![],
                 archival: false,
+                earliest_height: None,
+                protocol_version: PROTOCOL_VERSION,
             })
             .collect()
     }
@@ -293,7 +367,13 @@ mod test {
         let mut capture = TracingCapture::enable();
         let network_adapter = Arc::new(MockPeerManagerAdapter::default());
         let block_fetch_horizon = 10;
-        let mut block_sync = BlockSync::new(network_adapter.clone(), block_fetch_horizon, false);
+        let mut block_sync = BlockSync::new(
+            time::Clock::real(),
+            network_adapter.clone(),
+            block_fetch_horizon,
+            false,
+            true,
+        );
         let mut chain_genesis = ChainGenesis::test();
         chain_genesis.epoch_length = 100;
         let mut env = TestEnv::builder(chain_genesis).clients_count(2).build();
@@ -368,11 +448,85 @@ mod test {
         assert!(requested_block_hashes.is_empty(), "{:?}", requested_block_hashes);
     }
 
+    #[test]
+    fn test_block_sync_garbage_collected() {
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut block_sync =
+            BlockSync::new(time::Clock::real(), network_adapter.clone(), 10, false, true);
+        let mut env = TestEnv::builder(ChainGenesis::test()).clients_count(2).build();
+        let mut blocks = vec![];
+        for i in 1..3 {
+            let block = env.clients[0].produce_block(i).unwrap().unwrap();
+            blocks.push(block.clone());
+            env.process_block(0, block, Provenance::PRODUCED);
+        }
+        let block_headers = blocks.iter().map(|b| b.header().clone()).collect::<Vec<_>>();
+        let mut challenges = vec![];
+        env.clients[1].chain.sync_block_headers(block_headers, &mut challenges).unwrap();
+        let mut peer_infos = create_highest_height_peer_infos(2);
+        for peer in peer_infos.iter_mut() {
+            peer.peer_info.id = PeerId::random();
+        }
+
+        // The response of a peer the block wasn't requested from is ignored.
+        let hash = *blocks[0].hash();
+        block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap();
+        let requested_from = |network_adapter: &MockPeerManagerAdapter| {
+            let mut requested_from = None;
+            for request in network_adapter.requests.write().unwrap().drain(..) {
+                match request {
+                    PeerManagerMessageRequest::NetworkRequests(NetworkRequests::BlockRequest {
+                        hash: requested,
+                        peer_id,
+                    }) if requested == hash => requested_from = Some(peer_id),
+                    _ => {}
+                }
+            }
+            requested_from.unwrap()
+        };
+        let peer_id = requested_from(&network_adapter);
+        let (gc_peer, other_peer) = if peer_id == peer_infos[0].peer_info.id {
+            (&peer_infos[0], &peer_infos[1])
+        } else {
+            (&peer_infos[1], &peer_infos[0])
+        };
+        block_sync.block_garbage_collected(hash, other_peer.peer_info.id.clone());
+        assert!(block_sync.last_request.is_some());
+
+        // The block isn't requested again from the peer which garbage collected it.
+        block_sync.block_garbage_collected(hash, gc_peer.peer_info.id.clone());
+        assert!(block_sync.last_request.is_none());
+        for _ in 0..10 {
+            block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap();
+            for request in network_adapter.requests.write().unwrap().drain(..) {
+                match request {
+                    PeerManagerMessageRequest::NetworkRequests(NetworkRequests::BlockRequest {
+                        hash: requested,
+                        peer_id,
+                    }) if requested == hash => assert_eq!(peer_id, other_peer.peer_info.id),
+                    _ => {}
+                }
+            }
+        }
+
+        // Nor from any other peer, once all of them have garbage collected it.
+        block_sync.block_garbage_collected(hash, other_peer.peer_info.id.clone());
+        block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap();
+        let requested_block_hashes = collect_hashes_from_network_adapter(&network_adapter);
+        assert_eq!(requested_block_hashes, HashSet::from([*blocks[1].hash()]));
+    }
+
     #[test]
     fn test_block_sync_archival() {
         let network_adapter = Arc::new(MockPeerManagerAdapter::default());
         let block_fetch_horizon = 10;
-        let mut block_sync = BlockSync::new(network_adapter.clone(), block_fetch_horizon, true);
+        let mut block_sync = BlockSync::new(
+            time::Clock::real(),
+            network_adapter.clone(),
+            block_fetch_horizon,
+            true,
+            true,
+        );
         let mut chain_genesis = ChainGenesis::test();
         chain_genesis.epoch_length = 5;
         let mut env = TestEnv::builder(chain_genesis).clients_count(2).build();
@@ -405,4 +559,86 @@ mod test {
             blocks.iter().take(MAX_BLOCK_REQUESTS).map(|b| *b.hash()).collect::<HashSet<_>>()
         );
     }
+
+    #[test]
+    fn test_block_sync_advertised_heights() {
+        let block_fetch_horizon = 10;
+        let mut chain_genesis = ChainGenesis::test();
+        chain_genesis.epoch_length = 5;
+        let mut env = TestEnv::builder(chain_genesis).clients_count(2).build();
+        let mut blocks = vec![];
+        for i in 1..41 {
+            let block = env.clients[0].produce_block(i).unwrap().unwrap();
+            blocks.push(block.clone());
+            env.process_block(0, block, Provenance::PRODUCED);
+        }
+        let block_headers = blocks.iter().map(|b| b.header().clone()).collect::<Vec<_>>();
+        let mut challenges = vec![];
+        env.clients[1].chain.sync_block_headers(block_headers, &mut challenges).unwrap();
+        assert!(challenges.is_empty());
+
+        // Non-archival peers which advertise that they hold the old blocks are asked for them.
+        let mut peer_infos = create_highest_height_peer_infos(2);
+        for peer in peer_infos.iter_mut() {
+            peer.highest_block_height = 40;
+            peer.earliest_height = Some(0);
+        }
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut block_sync = BlockSync::new(
+            time::Clock::real(),
+            network_adapter.clone(),
+            block_fetch_horizon,
+            true,
+            true,
+        );
+        assert!(!block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap());
+        assert_eq!(
+            collect_hashes_from_network_adapter(&network_adapter),
+            blocks.iter().take(MAX_BLOCK_REQUESTS).map(|b| *b.hash()).collect::<HashSet<_>>()
+        );
+
+        // Peers which advertise that they don't hold the blocks are not, even if archival.
+        for peer in peer_infos.iter_mut() {
+            peer.archival = true;
+            peer.earliest_height = Some(40);
+        }
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut block_sync = BlockSync::new(
+            time::Clock::real(),
+            network_adapter.clone(),
+            block_fetch_horizon,
+            true,
+            true,
+        );
+        assert!(!block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap());
+        assert_eq!(collect_hashes_from_network_adapter(&network_adapter), HashSet::new());
+    }
+
+    #[test]
+    fn test_block_sync_state_sync_fallback() {
+        let mut chain_genesis = ChainGenesis::test();
+        chain_genesis.epoch_length = 5;
+        let mut env = TestEnv::builder(chain_genesis).clients_count(2).build();
+        let mut block_headers = vec![];
+        for i in 1..41 {
+            let block = env.clients[0].produce_block(i).unwrap().unwrap();
+            block_headers.push(block.header().clone());
+            env.process_block(0, block, Provenance::PRODUCED);
+        }
+        let mut challenges = vec![];
+        env.clients[1].chain.sync_block_headers(block_headers, &mut challenges).unwrap();
+        assert!(challenges.is_empty());
+        let peer_infos = create_highest_height_peer_infos(2);
+
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut block_sync =
+            BlockSync::new(time::Clock::real(), network_adapter.clone(), 10, false, true);
+        assert!(block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap());
+
+        // With the fallback disabled the node keeps requesting blocks.
+        let mut block_sync =
+            BlockSync::new(time::Clock::real(), network_adapter.clone(), 10, false, false);
+        assert!(!block_sync.block_sync(&env.clients[1].chain, &peer_infos).unwrap());
+        assert_eq!(collect_hashes_from_network_adapter(&network_adapter).len(), MAX_BLOCK_REQUESTS);
+    }
 }
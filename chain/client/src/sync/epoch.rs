@@ -10,7 +10,8 @@ use near_network::types::PeerManagerAdapter;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 
-use near_primitives::time::{Clock, Utc};
+use near_network::time;
+use near_primitives::time::Utc;
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::EpochId;
 
@@ -58,6 +59,7 @@ pub struct EpochSync {
 
 impl EpochSync {
     pub fn new(
+        clock: &time::Clock,
         network_adapter: Arc<dyn PeerManagerAdapter>,
         genesis_epoch_id: EpochId,
         genesis_next_epoch_id: EpochId,
@@ -73,7 +75,7 @@ impl EpochSync {
             next_epoch_id: genesis_next_epoch_id,
             next_block_producers: first_epoch_block_producers,
             requested_epoch_id: genesis_epoch_id,
-            last_request_time: Clock::utc(),
+            last_request_time: clock.now_utc_chrono(),
             last_request_peer_id: None,
             request_timeout: Duration::from_std(request_timeout).unwrap(),
             peer_timeout: Duration::from_std(peer_timeout).unwrap(),
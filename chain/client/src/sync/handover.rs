@@ -0,0 +1,250 @@
+//! Streaming of state parts from the node designated to hand over a shard, see
+//! `ClientConfig::state_handover_addr`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use near_network::time;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::ShardId;
+use tracing::{debug, warn};
+
+/// Parts larger than this are treated as a broken stream.
+const MAX_PART_SIZE: u64 = 64 * 1024 * 1024;
+/// Number of received parts kept in memory before reading from the
+/// connection pauses until they are stored.
+const MAX_BUFFERED_PARTS: usize = 64;
+/// How long to wait before resuming a transfer which failed for the first
+/// time.  The delay doubles with every consecutive failure.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound of the delay before resuming a transfer.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Number of consecutive attempts which fail without receiving any part after
+/// which the handover is abandoned.
+const MAX_FAILED_ATTEMPTS: u32 = 8;
+
+#[derive(Default)]
+struct Transfer {
+    /// Parts received and not yet taken, with their ids.
+    parts: Vec<(u64, Vec<u8>)>,
+    /// Whether any part was received over the connection.
+    received_parts: bool,
+    /// Whether the connection was closed, successfully or not.
+    finished: bool,
+    /// Whether the connection was closed because of an error.
+    failed: bool,
+}
+
+/// Transfer of the parts of one shard, resumed after failures.
+#[derive(Default)]
+struct ShardTransfer {
+    /// Connection in progress, if any.
+    current: Option<Arc<Mutex<Transfer>>>,
+    /// Number of consecutive attempts which failed without receiving a part.
+    failed_attempts: u32,
+}
+
+/// Streams the state parts of shards from the debug RPC server of another
+/// node over a single connection per shard.  When the connection breaks, the
+/// transfer resumes from the first part which is still missing, after
+/// a delay growing with the number of consecutive failures.
+pub(crate) struct StateHandover {
+    clock: time::Clock,
+    addr: String,
+    timeout: Duration,
+    transfers: HashMap<(ShardId, CryptoHash), ShardTransfer>,
+}
+
+impl StateHandover {
+    pub fn new(clock: time::Clock, addr: String, timeout: Duration) -> Self {
+        Self { clock, addr, timeout, transfers: HashMap::new() }
+    }
+
+    /// Returns the parts of the shard received since the last call.  If no
+    /// transfer is in progress, starts one from `first_missing_part`.  Fails
+    /// once `MAX_FAILED_ATTEMPTS` consecutive attempts failed without
+    /// receiving any part, in which case the parts should be requested from
+    /// peers instead.
+    pub fn take_parts(
+        &mut self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        first_missing_part: u64,
+    ) -> Result<Vec<(u64, Vec<u8>)>, String> {
+        let shard_transfer = self.transfers.entry((shard_id, sync_hash)).or_default();
+        if shard_transfer.failed_attempts >= MAX_FAILED_ATTEMPTS {
+            return Err(format!(
+                "{} consecutive attempts to stream the parts of shard {} failed",
+                shard_transfer.failed_attempts, shard_id
+            ));
+        }
+        let failed_attempts = shard_transfer.failed_attempts;
+        let transfer = shard_transfer.current.get_or_insert_with(|| {
+            start_transfer(
+                &self.clock,
+                &self.addr,
+                self.timeout,
+                shard_id,
+                sync_hash,
+                first_missing_part,
+                retry_delay(failed_attempts),
+            )
+        });
+        let mut transfer = transfer.lock().unwrap();
+        let parts = std::mem::take(&mut transfer.parts);
+        if transfer.finished {
+            if transfer.failed && !transfer.received_parts {
+                shard_transfer.failed_attempts += 1;
+            } else {
+                shard_transfer.failed_attempts = 0;
+            }
+            drop(transfer);
+            shard_transfer.current = None;
+        }
+        Ok(parts)
+    }
+}
+
+/// Delay before resuming a transfer after `failed_attempts` consecutive
+/// failures.
+fn retry_delay(failed_attempts: u32) -> Duration {
+    INITIAL_RETRY_DELAY.saturating_mul(1 << failed_attempts.min(16)).min(MAX_RETRY_DELAY)
+}
+
+/// Streams the parts starting from `from_part` in a new task.  If the
+/// transfer fails, the task waits for `retry_delay` before marking it as
+/// finished, so that the next attempt doesn't start earlier.
+fn start_transfer(
+    clock: &time::Clock,
+    addr: &str,
+    timeout: Duration,
+    shard_id: ShardId,
+    sync_hash: CryptoHash,
+    from_part: u64,
+    retry_delay: Duration,
+) -> Arc<Mutex<Transfer>> {
+    let url = format!(
+        "{}/debug/api/state_snapshot/{}/{}?from_part={}",
+        addr.trim_end_matches('/'),
+        shard_id,
+        sync_hash,
+        from_part
+    );
+    debug!(target: "sync", %url, "Starting state handover");
+    let transfer = Arc::new(Mutex::new(Transfer::default()));
+    let task_transfer = transfer.clone();
+    let clock = clock.clone();
+    near_performance_metrics::actix::spawn("StateHandover", async move {
+        let result = stream_parts(&clock, &url, timeout, &task_transfer).await;
+        if let Err(err) = &result {
+            warn!(target: "sync", %url, %err, ?retry_delay, "State handover interrupted");
+            clock.sleep(retry_delay.try_into().unwrap()).await;
+        }
+        let mut transfer = task_transfer.lock().unwrap();
+        transfer.finished = true;
+        transfer.failed = result.is_err();
+    });
+    transfer
+}
+
+async fn stream_parts(
+    clock: &time::Clock,
+    url: &str,
+    timeout: Duration,
+    transfer: &Mutex<Transfer>,
+) -> Result<(), String> {
+    let mut response = awc::Client::builder()
+        .timeout(timeout)
+        .finish()
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.status().is_success() {
+        let body = response.body().await.unwrap_or_default();
+        return Err(format!("{}: {}", response.status(), String::from_utf8_lossy(&body)));
+    }
+    let mut buffer = Vec::new();
+    loop {
+        while transfer.lock().unwrap().parts.len() >= MAX_BUFFERED_PARTS {
+            clock.sleep(time::Duration::milliseconds(100)).await;
+        }
+        let chunk = tokio::select! {
+            chunk = response.next() => chunk,
+            _ = clock.sleep(timeout.try_into().unwrap()) => return Err("timed out".to_string()),
+        };
+        let chunk = match chunk {
+            None => break,
+            Some(chunk) => chunk.map_err(|err| err.to_string())?,
+        };
+        buffer.extend_from_slice(&chunk);
+        while let Some(part) = take_part(&mut buffer)? {
+            let mut transfer = transfer.lock().unwrap();
+            transfer.parts.push(part);
+            transfer.received_parts = true;
+        }
+    }
+    if !buffer.is_empty() {
+        return Err("stream ended in the middle of a part".to_string());
+    }
+    Ok(())
+}
+
+/// Removes the first part from the buffer if it was received completely.  A
+/// part is sent as its id and its length, both as little-endian u64, followed
+/// by its data.
+fn take_part(buffer: &mut Vec<u8>) -> Result<Option<(u64, Vec<u8>)>, String> {
+    if buffer.len() < 16 {
+        return Ok(None);
+    }
+    let part_id = u64::from_le_bytes(buffer[..8].try_into().unwrap());
+    let len = u64::from_le_bytes(buffer[8..16].try_into().unwrap());
+    if len > MAX_PART_SIZE {
+        return Err(format!("part {} is too large: {} bytes", part_id, len));
+    }
+    let end = 16 + len as usize;
+    if buffer.len() < end {
+        return Ok(None);
+    }
+    let data = buffer[16..end].to_vec();
+    buffer.drain(..end);
+    Ok(Some((part_id, data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_delay, take_part, MAX_RETRY_DELAY};
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0), Duration::from_secs(1));
+        assert_eq!(retry_delay(1), Duration::from_secs(2));
+        assert_eq!(retry_delay(4), Duration::from_secs(16));
+        assert_eq!(retry_delay(5), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_take_part() {
+        let mut buffer = Vec::new();
+        for (part_id, data) in [(3u64, b"abc".to_vec()), (4, vec![])] {
+            buffer.extend_from_slice(&part_id.to_le_bytes());
+            buffer.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            buffer.extend_from_slice(&data);
+        }
+        let mut partial = buffer[..20].to_vec();
+        assert_eq!(take_part(&mut partial), Ok(None));
+        assert_eq!(partial.len(), 20);
+
+        assert_eq!(take_part(&mut buffer), Ok(Some((3, b"abc".to_vec()))));
+        assert_eq!(take_part(&mut buffer), Ok(Some((4, vec![]))));
+        assert_eq!(take_part(&mut buffer), Ok(None));
+        assert!(buffer.is_empty());
+
+        let mut buffer = [0u64.to_le_bytes(), u64::MAX.to_le_bytes()].concat();
+        assert!(take_part(&mut buffer).is_err());
+    }
+}
@@ -3,15 +3,18 @@ use std::sync::Arc;
 use std::time::Duration as TimeDuration;
 
 use chrono::{DateTime, Duration};
+use lru::LruCache;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
 use near_chain::{Chain, ChainStoreAccess};
+use near_network::time;
 use near_network::types::{HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter};
-use near_primitives::block::Tip;
+use near_primitives::block::{BlockHeader, Tip};
 use near_primitives::hash::CryptoHash;
-use near_primitives::time::{Clock, Utc};
+use near_primitives::network::PeerId;
+use near_primitives::time::Utc;
 use near_primitives::types::BlockHeight;
 use near_primitives::utils::to_timestamp;
 
@@ -27,13 +30,30 @@ pub const MAX_BLOCK_HEADER_HASHES: usize = 20;
 
 pub const NS_PER_SECOND: u128 = 1_000_000_000;
 
+/// Number of searches for the fork point with a peer which may end without finding it, before
+/// we stop searching with that peer.
+const MAX_FORK_POINT_SEARCHES_PER_PEER: usize = 3;
+
+/// Number of peers for which the searches for the fork point are counted.
+const FORK_POINT_SEARCH_PEERS: usize = 100;
+
+/// Request for the headers of a peer at given heights, sent to find the fork point with it.
+struct ForkPointRequest {
+    peer_id: PeerId,
+    heights: Vec<BlockHeight>,
+}
+
 /// Helper to keep track of sync headers.
 /// Handles major re-orgs by finding closest header that matches and re-downloading headers from that point.
 pub struct HeaderSync {
+    clock: time::Clock,
     network_adapter: Arc<dyn PeerManagerAdapter>,
     prev_header_sync: (DateTime<Utc>, BlockHeight, BlockHeight, BlockHeight),
     syncing_peer: Option<HighestHeightPeerInfo>,
     stalling_ts: Option<DateTime<Utc>>,
+    fork_point_request: Option<ForkPointRequest>,
+    /// Number of searches for the fork point started with a peer since the last one which found it.
+    fork_point_searches: LruCache<PeerId, usize>,
 
     initial_timeout: Duration,
     progress_timeout: Duration,
@@ -43,6 +63,7 @@ pub struct HeaderSync {
 
 impl HeaderSync {
     pub fn new(
+        clock: time::Clock,
         network_adapter: Arc<dyn PeerManagerAdapter>,
         initial_timeout: TimeDuration,
         progress_timeout: TimeDuration,
@@ -50,10 +71,13 @@ impl HeaderSync {
         expected_height_per_second: u64,
     ) -> Self {
         HeaderSync {
+            prev_header_sync: (clock.now_utc_chrono(), 0, 0, 0),
+            clock,
             network_adapter,
-            prev_header_sync: (Clock::utc(), 0, 0, 0),
             syncing_peer: None,
             stalling_ts: None,
+            fork_point_request: None,
+            fork_point_searches: LruCache::new(FORK_POINT_SEARCH_PEERS),
             initial_timeout: Duration::from_std(initial_timeout).unwrap(),
             progress_timeout: Duration::from_std(progress_timeout).unwrap(),
             stall_ban_timeout: Duration::from_std(stall_ban_timeout).unwrap(),
@@ -125,7 +149,7 @@ impl HeaderSync {
         header_head: &Tip,
         highest_height: BlockHeight,
     ) -> bool {
-        let now = Clock::utc();
+        let now = self.clock.now_utc_chrono();
         let (timeout, old_expected_height, prev_height, prev_highest_height) =
             self.prev_header_sync;
 
@@ -234,6 +258,113 @@ impl HeaderSync {
         None
     }
 
+    /// Handles the headers sent by `peer_id` if they are part of a search for the fork point with
+    /// it, returning whether they were.
+    ///
+    /// The search starts when the peer we sync from sends no headers for our locator, so none of
+    /// its hashes is on the chain of the peer.  We ask the peer for the headers of its chain at
+    /// exponentially spaced heights, and resume header sync from the highest one we know.  If the
+    /// next requested height is too far above it to be covered by a single response, we search
+    /// again between the two.  A peer which keeps sending no headers for our locator nor for the
+    /// fork point heights is searched with at most `MAX_FORK_POINT_SEARCHES_PER_PEER` times, after
+    /// which its empty responses are processed as usual.
+    pub fn receive_fork_point_headers(
+        &mut self,
+        chain: &Chain,
+        headers: &[BlockHeader],
+        peer_id: &PeerId,
+    ) -> Result<bool, near_chain::Error> {
+        if let Some(request) = &self.fork_point_request {
+            if &request.peer_id == peer_id
+                && headers.iter().all(|header| request.heights.contains(&header.height()))
+            {
+                let request = self.fork_point_request.take().unwrap();
+                self.continue_fork_point_search(chain, request, headers);
+                return Ok(true);
+            }
+        }
+        if !headers.is_empty() {
+            return Ok(false);
+        }
+        match &self.syncing_peer {
+            Some(peer) if &peer.peer_info.id == peer_id => {
+                let searches = self.fork_point_searches.get(peer_id).copied().unwrap_or(0);
+                if searches >= MAX_FORK_POINT_SEARCHES_PER_PEER {
+                    debug!(target: "sync", "Sync: {} has none of the locator blocks, but the fork point search with it failed {} times", peer_id, searches);
+                    return Ok(false);
+                }
+                self.fork_point_searches.put(peer_id.clone(), searches + 1);
+                let header_head = chain.header_head()?;
+                debug!(target: "sync", "Sync: {} has none of the locator blocks, looking for the fork point", peer_id);
+                self.request_fork_point(
+                    peer_id.clone(),
+                    chain.genesis().height(),
+                    header_head.height,
+                );
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn continue_fork_point_search(
+        &mut self,
+        chain: &Chain,
+        request: ForkPointRequest,
+        headers: &[BlockHeader],
+    ) {
+        let known = headers
+            .iter()
+            .filter(|header| chain.get_block_header(header.hash()).is_ok())
+            .max_by_key(|header| header.height());
+        let known = match known {
+            Some(header) => header,
+            None => {
+                warn!(target: "sync", "Sync: {} sent none of our blocks at heights {:?}", request.peer_id, request.heights);
+                return;
+            }
+        };
+        let fork_height = known.height();
+        match request.heights.iter().filter(|height| **height > fork_height).min() {
+            Some(next_height) if next_height - fork_height > MAX_BLOCK_HEADERS => {
+                self.request_fork_point(request.peer_id, fork_height, next_height - 1)
+            }
+            _ => {
+                info!(target: "sync", "Sync: found the fork point with {} at height {}", request.peer_id, fork_height);
+                self.fork_point_searches.pop(&request.peer_id);
+                self.network_adapter.do_send(
+                    PeerManagerMessageRequest::NetworkRequests(
+                        NetworkRequests::BlockHeadersRequest {
+                            hashes: vec![*known.hash()],
+                            peer_id: request.peer_id,
+                        },
+                    )
+                    .with_span_context(),
+                );
+            }
+        }
+    }
+
+    fn request_fork_point(
+        &mut self,
+        peer_id: PeerId,
+        lowest_height: BlockHeight,
+        highest_height: BlockHeight,
+    ) {
+        let heights = get_fork_point_heights(lowest_height, highest_height);
+        debug!(target: "sync", "Sync: asking {} for headers at heights {:?}", peer_id, heights);
+        self.network_adapter.do_send(
+            PeerManagerMessageRequest::NetworkRequests(
+                NetworkRequests::BlockHeadersAtHeightsRequest {
+                    heights: heights.clone(),
+                    peer_id: peer_id.clone(),
+                },
+            )
+            .with_span_context(),
+        );
+        self.fork_point_request = Some(ForkPointRequest { peer_id, heights });
+    }
+
     // The remote side will return MAX_BLOCK_HEADERS headers, starting from the first hash in
     // the returned "locator" list that is on their canonical chain.
     //
@@ -282,6 +413,25 @@ fn get_locator_ordinals(lowest_ordinal: u64, highest_ordinal: u64) -> Vec<u64> {
     ordinals
 }
 
+/// Step back from highest to lowest height in powers of 2 steps, limited by
+/// MAX_BLOCK_HEADER_HASHES steps in total.  Unlike the locator, the steps are not limited, so
+/// that the whole range is covered.
+fn get_fork_point_heights(lowest_height: BlockHeight, highest_height: BlockHeight) -> Vec<u64> {
+    let mut current = highest_height;
+    let mut heights = vec![];
+    let mut step = 1;
+    while current > lowest_height && heights.len() < MAX_BLOCK_HEADER_HASHES - 1 {
+        heights.push(current);
+        if current <= lowest_height + step {
+            break;
+        }
+        current -= step;
+        step *= 2;
+    }
+    heights.push(lowest_height);
+    heights
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
@@ -296,6 +446,7 @@ mod test {
     use near_primitives::block::{Approval, Block, GenesisId};
     use near_primitives::network::PeerId;
     use near_primitives::test_utils::TestBlockBuilder;
+    use near_primitives::time::Clock;
 
     use super::*;
     use near_network::types::{BlockInfo, FullPeerInfo, PeerInfo};
@@ -343,11 +494,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_get_fork_point_heights() {
+        assert_eq!(get_fork_point_heights(0, 0), vec![0]);
+        assert_eq!(get_fork_point_heights(0, 1), vec![1, 0]);
+        assert_eq!(get_fork_point_heights(0, 6), vec![6, 5, 3, 0]);
+        assert_eq!(get_fork_point_heights(100, 200), vec![200, 199, 197, 193, 185, 169, 137, 100]);
+        let heights = get_fork_point_heights(0, 10_000_000);
+        assert_eq!(heights.len(), MAX_BLOCK_HEADER_HASHES);
+        assert_eq!(heights[..4], [10_000_000, 9_999_999, 9_999_997, 9_999_993]);
+        assert_eq!(heights.last(), Some(&0));
+    }
+
     /// Starts two chains that fork of genesis and checks that they can sync headers to the longest.
     #[test]
     fn test_sync_headers_fork() {
         let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
         let mut header_sync = HeaderSync::new(
+            time::Clock::real(),
             mock_adapter.clone(),
             TimeDuration::from_secs(10),
             TimeDuration::from_secs(2),
@@ -398,6 +562,8 @@ mod test {
                 },
                 tracked_shards: vec![],
                 archival: false,
+                earliest_height: None,
+                protocol_version: PROTOCOL_VERSION,
                 last_block: Some(BlockInfo {
                     height: chain2.head().unwrap().height,
                     hash: chain2.head().unwrap().last_block_hash,
@@ -430,10 +596,142 @@ mod test {
         );
     }
 
+    /// Checks that when the peer has none of the locator blocks, header sync looks for the fork
+    /// point by asking for the headers at exponentially spaced heights.
+    #[test]
+    fn test_sync_headers_fork_point() {
+        let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut header_sync = HeaderSync::new(
+            time::Clock::real(),
+            mock_adapter.clone(),
+            TimeDuration::from_secs(10),
+            TimeDuration::from_secs(2),
+            TimeDuration::from_secs(120),
+            1_000_000_000,
+        );
+        // The chains have blocks at heights 2, 4, 6 and 3, 6, 9, 12 respectively.
+        let mut chains = vec![];
+        for (step, num_blocks) in [(2, 3), (3, 4)] {
+            let (mut chain, _, signer) = setup();
+            for _ in 0..num_blocks {
+                let prev = chain.get_block(&chain.head().unwrap().last_block_hash).unwrap();
+                let block = TestBlockBuilder::new(&prev, signer.clone())
+                    .height(prev.header().height() + step)
+                    .build();
+                process_block_sync(
+                    &mut chain,
+                    &None,
+                    block.into(),
+                    Provenance::PRODUCED,
+                    &mut BlockProcessingArtifact::default(),
+                )
+                .unwrap();
+            }
+            chains.push(chain);
+        }
+        let chain2 = chains.pop().unwrap();
+        let mut chain = chains.pop().unwrap();
+        let peer = HighestHeightPeerInfo {
+            peer_info: PeerInfo::random(),
+            genesis_id: Default::default(),
+            highest_block_height: chain2.head().unwrap().height,
+            highest_block_hash: chain2.head().unwrap().last_block_hash,
+            tracked_shards: vec![],
+            archival: false,
+            earliest_height: None,
+            protocol_version: PROTOCOL_VERSION,
+        };
+        let peer_id = peer.peer_info.id.clone();
+        let mut sync_status = SyncStatus::NoSync;
+        let head = chain.head().unwrap();
+        header_sync.run(&mut sync_status, &mut chain, head.height, &[peer]).unwrap();
+        assert!(matches!(
+            mock_adapter.pop().unwrap().as_network_requests(),
+            NetworkRequests::BlockHeadersRequest { .. }
+        ));
+
+        // Headers from other peers are processed as usual.
+        let other_peer_id = PeerInfo::random().id;
+        assert!(!header_sync.receive_fork_point_headers(&chain, &[], &other_peer_id).unwrap());
+        assert!(mock_adapter.pop().is_none());
+
+        assert!(header_sync.receive_fork_point_headers(&chain, &[], &peer_id).unwrap());
+        let heights = vec![6, 5, 3, 0];
+        assert_eq!(
+            mock_adapter.pop().unwrap().as_network_requests(),
+            NetworkRequests::BlockHeadersAtHeightsRequest {
+                heights: heights.clone(),
+                peer_id: peer_id.clone()
+            }
+        );
+        let headers = chain2.retrieve_headers_at_heights(&heights, 20).unwrap();
+        assert_eq!(headers.iter().map(|header| header.height()).collect::<Vec<_>>(), vec![6, 3, 0]);
+        assert!(header_sync.receive_fork_point_headers(&chain, &headers, &peer_id).unwrap());
+        assert_eq!(
+            mock_adapter.pop().unwrap().as_network_requests(),
+            NetworkRequests::BlockHeadersRequest {
+                hashes: vec![*chain.genesis().hash()],
+                peer_id: peer_id.clone()
+            }
+        );
+        // The search is over, the response to the last request is processed as usual.
+        let headers = chain2.retrieve_headers(vec![*chain.genesis().hash()], 512, None).unwrap();
+        assert!(!header_sync.receive_fork_point_headers(&chain, &headers, &peer_id).unwrap());
+    }
+
+    /// Check that a peer which keeps sending no headers is searched for the fork point with a
+    /// bounded number of times.
+    #[test]
+    fn test_sync_headers_fork_point_retries() {
+        let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut header_sync = HeaderSync::new(
+            time::Clock::real(),
+            mock_adapter.clone(),
+            TimeDuration::from_secs(10),
+            TimeDuration::from_secs(2),
+            TimeDuration::from_secs(120),
+            1_000_000_000,
+        );
+        let (mut chain, _, _) = setup();
+        let peer = HighestHeightPeerInfo {
+            peer_info: PeerInfo::random(),
+            genesis_id: Default::default(),
+            highest_block_height: 10,
+            highest_block_hash: CryptoHash::default(),
+            tracked_shards: vec![],
+            archival: false,
+            earliest_height: None,
+            protocol_version: PROTOCOL_VERSION,
+        };
+        let peer_id = peer.peer_info.id.clone();
+        let mut sync_status = SyncStatus::NoSync;
+        let head = chain.head().unwrap();
+        header_sync.run(&mut sync_status, &mut chain, head.height, &[peer]).unwrap();
+        assert!(matches!(
+            mock_adapter.pop().unwrap().as_network_requests(),
+            NetworkRequests::BlockHeadersRequest { .. }
+        ));
+
+        for _ in 0..MAX_FORK_POINT_SEARCHES_PER_PEER {
+            // No headers for the locator start a search, and no headers at the requested heights
+            // end it without finding the fork point.
+            assert!(header_sync.receive_fork_point_headers(&chain, &[], &peer_id).unwrap());
+            assert!(matches!(
+                mock_adapter.pop().unwrap().as_network_requests(),
+                NetworkRequests::BlockHeadersAtHeightsRequest { .. }
+            ));
+            assert!(header_sync.receive_fork_point_headers(&chain, &[], &peer_id).unwrap());
+            assert!(mock_adapter.pop().is_none());
+        }
+        assert!(!header_sync.receive_fork_point_headers(&chain, &[], &peer_id).unwrap());
+        assert!(mock_adapter.pop().is_none());
+    }
+
     #[test]
     fn test_sync_headers_fork_from_final_block() {
         let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
         let mut header_sync = HeaderSync::new(
+            time::Clock::real(),
             mock_adapter.clone(),
             TimeDuration::from_secs(10),
             TimeDuration::from_secs(2),
@@ -498,6 +796,8 @@ mod test {
                 },
                 tracked_shards: vec![],
                 archival: false,
+                earliest_height: None,
+                protocol_version: PROTOCOL_VERSION,
                 last_block: Some(BlockInfo {
                     height: chain2.head().unwrap().height,
                     hash: chain2.head().unwrap().last_block_hash,
@@ -544,6 +844,7 @@ mod test {
 
         // Setup header_sync with expectation of 25 headers/second
         let mut header_sync = HeaderSync::new(
+            time::Clock::real(),
             network_adapter.clone(),
             TimeDuration::from_secs(1),
             TimeDuration::from_secs(1),
@@ -563,6 +864,8 @@ mod test {
                 highest_block_hash: Default::default(),
                 tracked_shards: vec![],
                 archival: false,
+                earliest_height: None,
+                protocol_version: PROTOCOL_VERSION,
             });
             header_sync.syncing_peer.as_mut().unwrap().highest_block_height = highest_height;
         };
@@ -638,6 +941,7 @@ mod test {
     fn test_sync_from_very_behind() {
         let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
         let mut header_sync = HeaderSync::new(
+            time::Clock::real(),
             mock_adapter.clone(),
             TimeDuration::from_secs(10),
             TimeDuration::from_secs(2),
@@ -724,6 +1028,8 @@ mod test {
                 },
                 tracked_shards: vec![],
                 archival: false,
+                earliest_height: None,
+                protocol_version: PROTOCOL_VERSION,
                 last_block: Some(BlockInfo {
                     height: chain2.head().unwrap().height,
                     hash: chain2.head().unwrap().last_block_hash,
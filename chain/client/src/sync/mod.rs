@@ -1,4 +1,5 @@
 pub mod block;
 pub mod epoch;
+mod handover;
 pub mod header;
 pub mod state;
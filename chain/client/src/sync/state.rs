@@ -36,12 +36,15 @@ use rand::{thread_rng, Rng};
 use tracing::{debug, error, info, warn};
 
 use near_chain::{Chain, RuntimeWithEpochManagerAdapter};
+use near_network::time;
 use near_network::types::{
     HighestHeightPeerInfo, NetworkRequests, NetworkResponses, PeerManagerAdapter,
 };
+use near_primitives::checked_feature;
 use near_primitives::hash::CryptoHash;
-use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponse};
-use near_primitives::time::{Clock, Utc};
+use near_primitives::network::PeerId;
+use near_primitives::syncing::{get_num_state_parts, ShardStateDelta, ShardStateSyncResponse};
+use near_primitives::time::Utc;
 use near_primitives::types::{AccountId, ShardId, StateRoot};
 
 use near_chain::chain::{ApplyStatePartsRequest, StateSplitRequest};
@@ -53,6 +56,8 @@ use near_network::types::PeerManagerMessageRequest;
 use near_o11y::WithSpanContextExt;
 use near_primitives::shard_layout::ShardUId;
 
+use super::handover::StateHandover;
+
 /// Maximum number of state parts to request per peer on each round when node is trying to download the state.
 pub const MAX_STATE_PART_REQUEST: u64 = 16;
 /// Number of state parts already requested stored as pending.
@@ -76,11 +81,11 @@ struct PendingRequestStatus {
 }
 
 impl PendingRequestStatus {
-    fn new(timeout: Duration) -> Self {
-        Self { missing_parts: 1, wait_until: Clock::utc().add(timeout) }
+    fn new(now: DateTime<Utc>, timeout: Duration) -> Self {
+        Self { missing_parts: 1, wait_until: now.add(timeout) }
     }
-    fn expired(&self) -> bool {
-        Clock::utc() > self.wait_until
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        now > self.wait_until
     }
 }
 
@@ -99,6 +104,7 @@ fn make_account_or_peer_id_or_hash(
 
 /// Helper to track state sync.
 pub struct StateSync {
+    clock: time::Clock,
     network_adapter: Arc<dyn PeerManagerAdapter>,
 
     last_time_block_requested: Option<DateTime<Utc>>,
@@ -116,11 +122,22 @@ pub struct StateSync {
 
     /// Maps shard_id to result of splitting state for resharding
     split_state_roots: HashMap<ShardId, Result<HashMap<ShardUId, StateRoot>, Error>>,
+
+    /// If set, state parts are streamed from the node designated to hand the
+    /// shards over instead of being requested from peers.
+    handover: Option<StateHandover>,
 }
 
 impl StateSync {
-    pub fn new(network_adapter: Arc<dyn PeerManagerAdapter>, timeout: TimeDuration) -> Self {
+    pub fn new(
+        clock: time::Clock,
+        network_adapter: Arc<dyn PeerManagerAdapter>,
+        timeout: TimeDuration,
+        handover_addr: Option<String>,
+    ) -> Self {
+        let handover = handover_addr.map(|addr| StateHandover::new(clock.clone(), addr, timeout));
         StateSync {
+            clock,
             network_adapter,
             last_time_block_requested: None,
             last_part_id_requested: Default::default(),
@@ -128,6 +145,7 @@ impl StateSync {
             timeout: Duration::from_std(timeout).unwrap(),
             state_parts_apply_results: HashMap::new(),
             split_state_roots: HashMap::new(),
+            handover,
         }
     }
 
@@ -206,25 +224,28 @@ impl StateSync {
                 ShardSyncStatus::StateDownloadHeader => {
                     // StateDownloadHeader is the first step. We want to fetch the basic information about the state (its size, hash etc).
                     if shard_sync_download.downloads[0].done {
-                        let shard_state_header = chain.get_state_header(shard_id, sync_hash)?;
-                        let state_num_parts =
-                            get_num_state_parts(shard_state_header.state_root_node().memory_usage);
-                        // If the header was downloaded succesfully - move to phase 2 (downloading parts).
-                        // Create the vector with entry for each part.
-                        *shard_sync_download = ShardSyncDownload {
-                            downloads: vec![
-                                DownloadStatus {
-                                    start_time: now,
-                                    prev_update_time: now,
-                                    run_me: Arc::new(AtomicBool::new(true)),
-                                    error: false,
-                                    done: false,
-                                    state_requests_count: 0,
-                                    last_target: None,
-                                };
-                                state_num_parts as usize
-                            ],
-                            status: ShardSyncStatus::StateDownloadParts,
+                        // If the header was downloaded succesfully - move to phase 2 (downloading
+                        // the delta if we have the state of an earlier epoch, otherwise parts).
+                        let base_hash = if highest_height_peers
+                            .iter()
+                            .any(|peer| can_serve_state_delta(peer, shard_id))
+                        {
+                            chain.get_state_delta_base(shard_id, sync_hash).unwrap_or_else(|err| {
+                                debug!(target: "sync", shard_id, %sync_hash, ?err, "Cannot sync state delta");
+                                None
+                            })
+                        } else {
+                            None
+                        };
+                        *shard_sync_download = match base_hash {
+                            Some(base_hash) => {
+                                debug!(target: "sync", shard_id, %sync_hash, %base_hash, "State sync downloading delta");
+                                ShardSyncDownload {
+                                    downloads: vec![new_download_status(now)],
+                                    status: ShardSyncStatus::StateDownloadDelta(base_hash),
+                                }
+                            }
+                            None => parts_download(chain, shard_id, sync_hash, now)?,
                         };
                         need_shard = true;
                     } else {
@@ -242,24 +263,81 @@ impl StateSync {
                         }
                     }
                 }
+                ShardSyncStatus::StateDownloadDelta(_) => {
+                    // Step 2 - download the trie nodes added since the state we have.
+                    if shard_sync_download.downloads[0].done {
+                        // The delta was applied when it was received.
+                        match chain.set_state_finalize(shard_id, sync_hash, Ok(())) {
+                            Ok(()) => {
+                                *shard_sync_download = ShardSyncDownload {
+                                    downloads: vec![],
+                                    status: ShardSyncStatus::StateDownloadComplete,
+                                }
+                            }
+                            Err(e) => {
+                                error!(target: "sync", "State sync finalizing delta error, shard = {}, hash = {}: {:?}", shard_id, sync_hash, e);
+                                *shard_sync_download =
+                                    parts_download(chain, shard_id, sync_hash, now)?;
+                                need_shard = true;
+                            }
+                        }
+                    } else if shard_sync_download.downloads[0].error
+                        || now - shard_sync_download.downloads[0].prev_update_time > self.timeout
+                    {
+                        // Peers may not have the trie changes anymore, don't insist.
+                        warn!(target: "sync", "State sync failed to download delta, shard = {}, hash = {}, downloading parts instead", shard_id, sync_hash);
+                        *shard_sync_download = parts_download(chain, shard_id, sync_hash, now)?;
+                        need_shard = true;
+                    } else if shard_sync_download.downloads[0].run_me.load(Ordering::SeqCst) {
+                        need_shard = true;
+                    }
+                }
                 ShardSyncStatus::StateDownloadParts => {
                     // Step 2 - download all the parts (each part is usually around 1MB).
                     let mut parts_done = true;
-                    for part_download in shard_sync_download.downloads.iter_mut() {
-                        if !part_download.done {
+                    if let Some(handover) = self.handover.as_mut() {
+                        // The parts are streamed over a single connection,
+                        // which resumes from the first missing part if it breaks.
+                        let downloads = &mut shard_sync_download.downloads;
+                        if let Some(first_missing_part) =
+                            downloads.iter().position(|download| !download.done)
+                        {
                             parts_done = false;
-                            let prev = part_download.prev_update_time;
-                            let error = part_download.error;
-                            let part_timeout = now - prev > self.timeout;
-                            // Retry parts that failed.
-                            if part_timeout || error {
-                                download_timeout |= part_timeout;
-                                part_download.run_me.store(true, Ordering::SeqCst);
-                                part_download.error = false;
-                                part_download.prev_update_time = now;
+                            match handover.take_parts(
+                                shard_id,
+                                sync_hash,
+                                first_missing_part as u64,
+                            ) {
+                                Ok(parts) => {
+                                    for (part_id, data) in parts {
+                                        set_state_part(
+                                            chain, downloads, shard_id, sync_hash, part_id, &data,
+                                        );
+                                    }
+                                }
+                                Err(err) => {
+                                    warn!(target: "sync", %err, "State handover failed, requesting state parts from peers");
+                                    self.handover = None;
+                                }
                             }
-                            if part_download.run_me.load(Ordering::SeqCst) {
-                                need_shard = true;
+                        }
+                    } else {
+                        for part_download in shard_sync_download.downloads.iter_mut() {
+                            if !part_download.done {
+                                parts_done = false;
+                                let prev = part_download.prev_update_time;
+                                let error = part_download.error;
+                                let part_timeout = now - prev > self.timeout;
+                                // Retry parts that failed.
+                                if part_timeout || error {
+                                    download_timeout |= part_timeout;
+                                    part_download.run_me.store(true, Ordering::SeqCst);
+                                    part_download.error = false;
+                                    part_download.prev_update_time = now;
+                                }
+                                if part_download.run_me.load(Ordering::SeqCst) {
+                                    need_shard = true;
+                                }
                             }
                         }
                     }
@@ -478,13 +556,14 @@ impl StateSync {
         // FIXME: something is wrong - the index should have a shard_id too.
         self.requested_target.put((part_id, sync_hash), target.clone());
 
+        let now = self.clock.now_utc_chrono();
         let timeout = self.timeout;
         self.last_part_id_requested
             .entry((target, shard_id))
             .and_modify(|pending_request| {
                 pending_request.missing_parts += 1;
             })
-            .or_insert_with(|| PendingRequestStatus::new(timeout));
+            .or_insert_with(|| PendingRequestStatus::new(now, timeout));
     }
 
     // Function called when our node receives the network response with a part.
@@ -522,9 +601,12 @@ impl StateSync {
         highest_height_peers: &[HighestHeightPeerInfo],
     ) -> Result<Vec<AccountOrPeerIdOrHash>, Error> {
         // Remove candidates from pending list if request expired due to timeout
-        self.last_part_id_requested.retain(|_, request| !request.expired());
+        let now = self.clock.now_utc_chrono();
+        self.last_part_id_requested.retain(|_, request| !request.expired(now));
 
-        let prev_block_hash = *chain.get_block_header(&sync_hash)?.prev_hash();
+        let sync_header = chain.get_block_header(&sync_hash)?;
+        let sync_height = sync_header.height();
+        let prev_block_hash = *sync_header.prev_hash();
         let epoch_hash = runtime_adapter.get_epoch_id_from_prev_block(&prev_block_hash)?;
 
         Ok(runtime_adapter
@@ -552,8 +634,10 @@ impl StateSync {
             .chain(highest_height_peers.iter().filter_map(|peer| {
                 // Select peers that are high enough (if they are syncing themselves, they might not have the data that we want)
                 //  and that are tracking the shard.
-                // TODO: possible optimization - simply select peers that have height greater than the epoch start that we're asking for.
-                if peer.tracked_shards.contains(&shard_id) {
+                // Skip the peers which advertise that they have already garbage collected the sync block.
+                if peer.tracked_shards.contains(&shard_id)
+                    && peer.holds_height(sync_height) != Some(false)
+                {
                     Some(AccountOrPeerIdOrHash::PeerId(peer.peer_info.id.clone()))
                 } else {
                     None
@@ -623,6 +707,61 @@ impl StateSync {
                         }),
                 );
             }
+            ShardSyncStatus::StateDownloadDelta(from_hash) => {
+                // Peers running an older protocol version can't parse the
+                // delta messages, neither as the target nor when routing
+                // them.  Only ask the peers we are connected to directly
+                // whose protocol version supports it.
+                let targets: Vec<_> = possible_targets
+                    .iter()
+                    .filter(|target| match target {
+                        AccountOrPeerIdOrHash::PeerId(peer_id) => {
+                            highest_height_peers.iter().any(|peer| {
+                                &peer.peer_info.id == peer_id
+                                    && can_serve_state_delta(peer, shard_id)
+                            })
+                        }
+                        _ => false,
+                    })
+                    .collect();
+                let target = match targets.choose(&mut thread_rng()) {
+                    Some(target) => (*target).clone(),
+                    None => {
+                        // Download parts instead.
+                        new_shard_sync_download.downloads[0].error = true;
+                        return Ok(new_shard_sync_download);
+                    }
+                };
+                new_shard_sync_download.downloads[0].run_me.store(false, Ordering::SeqCst);
+                new_shard_sync_download.downloads[0].state_requests_count += 1;
+                new_shard_sync_download.downloads[0].last_target =
+                    Some(make_account_or_peer_id_or_hash(target.clone()));
+                let run_me = new_shard_sync_download.downloads[0].run_me.clone();
+                near_performance_metrics::actix::spawn(
+                    std::any::type_name::<Self>(),
+                    self.network_adapter
+                        .send(
+                            PeerManagerMessageRequest::NetworkRequests(
+                                NetworkRequests::StateRequestDelta {
+                                    shard_id,
+                                    sync_hash,
+                                    from_hash,
+                                    target,
+                                },
+                            )
+                            .with_span_context(),
+                        )
+                        .then(move |result| {
+                            if let Ok(NetworkResponses::RouteNotFound) =
+                                result.map(|f| f.as_network_response())
+                            {
+                                // Send a StateRequestDelta on the next iteration
+                                run_me.store(true, Ordering::SeqCst);
+                            }
+                            future::ready(())
+                        }),
+                );
+            }
             ShardSyncStatus::StateDownloadParts => {
                 // We'll select all the 'highest' peers + validators as candidates (exluding those that gave us timeout in the past).
                 // And for each one of them, we'll ask for up to 16 (MAX_STATE_PART_REQUEST) parts.
@@ -703,7 +842,7 @@ impl StateSync {
         let _span = tracing::debug_span!(target: "sync", "run", sync = "StateSync").entered();
         debug!(target: "sync", %sync_hash, ?tracking_shards, "syncing state");
         let prev_hash = *chain.get_block_header(&sync_hash)?.prev_hash();
-        let now = Clock::utc();
+        let now = self.clock.now_utc_chrono();
 
         // FIXME: it checks if the block exists.. but I have no idea why..
         // seems that we don't really use this block in case of catchup - we use it only for state sync.
@@ -781,34 +920,168 @@ impl StateSync {
                 }
             }
             ShardSyncStatus::StateDownloadParts => {
-                if let Some(part) = state_response.take_part() {
-                    let num_parts = shard_sync_download.downloads.len() as u64;
-                    let (part_id, data) = part;
-                    if part_id >= num_parts {
-                        error!(target: "sync", "State sync received incorrect part_id # {:?} for hash {:?}, potential malicious peer", part_id, hash);
-                        return;
-                    }
-                    if !shard_sync_download.downloads[part_id as usize].done {
-                        match chain.set_state_part(
-                            shard_id,
-                            hash,
-                            PartId::new(part_id, num_parts),
-                            &data,
-                        ) {
-                            Ok(()) => {
-                                shard_sync_download.downloads[part_id as usize].done = true;
-                            }
-                            Err(err) => {
-                                error!(target: "sync", "State sync set_state_part error, shard = {}, part = {}, hash = {}: {:?}", shard_id, part_id, hash, err);
-                                shard_sync_download.downloads[part_id as usize].error = true;
-                            }
-                        }
-                    }
+                if let Some((part_id, data)) = state_response.take_part() {
+                    set_state_part(
+                        chain,
+                        &mut shard_sync_download.downloads,
+                        shard_id,
+                        hash,
+                        part_id,
+                        &data,
+                    );
                 }
             }
             _ => {}
         }
     }
+
+    /// Marks the download of the state header or part as failed, when the
+    /// peer it was requested from has garbage collected the state, so that it
+    /// is requested from another peer in the next step.  Responses from other
+    /// peers than the one the download was last requested from are ignored.
+    /// `sender_account` is the account of the validator with the `sender` peer
+    /// id, if known, for the downloads requested from a validator by account.
+    pub fn update_download_on_garbage_collected(
+        &mut self,
+        shard_sync_download: &mut ShardSyncDownload,
+        hash: CryptoHash,
+        shard_id: ShardId,
+        part_id: Option<u64>,
+        sender: &PeerId,
+        sender_account: Option<&AccountId>,
+    ) {
+        let index = match (&shard_sync_download.status, part_id) {
+            (ShardSyncStatus::StateDownloadHeader, None) => 0,
+            (ShardSyncStatus::StateDownloadParts, Some(part_id)) => part_id as usize,
+            _ => return,
+        };
+        let download = match shard_sync_download.downloads.get_mut(index) {
+            Some(download) => download,
+            None => return,
+        };
+        let from_target = match &download.last_target {
+            Some(near_client_primitives::types::AccountOrPeerIdOrHash::PeerId(peer_id)) => {
+                peer_id == sender
+            }
+            Some(near_client_primitives::types::AccountOrPeerIdOrHash::AccountId(account_id)) => {
+                sender_account == Some(account_id)
+            }
+            _ => false,
+        };
+        if !from_target {
+            debug!(target: "sync", shard_id, %hash, ?part_id, %sender, "Ignoring garbage collected state response from a peer it wasn't requested from");
+            return;
+        }
+        if let Some(part_id) = part_id {
+            self.received_requested_part(part_id, shard_id, hash);
+        }
+        if !download.done {
+            info!(target: "sync", shard_id, %hash, ?part_id, "State is garbage collected by the peer, should be re-requested");
+            download.error = true;
+        }
+    }
+
+    pub fn update_download_on_state_delta_response(
+        &mut self,
+        shard_sync_download: &mut ShardSyncDownload,
+        hash: CryptoHash,
+        shard_id: ShardId,
+        delta: Option<ShardStateDelta>,
+        chain: &mut Chain,
+    ) {
+        let base_hash = match shard_sync_download.status {
+            ShardSyncStatus::StateDownloadDelta(base_hash) => base_hash,
+            _ => return,
+        };
+        if shard_sync_download.downloads[0].done {
+            return;
+        }
+        let delta = match delta {
+            Some(delta) => delta,
+            None => {
+                info!(target: "sync", "state delta response is empty, shard = {}, hash = {}", shard_id, hash);
+                shard_sync_download.downloads[0].error = true;
+                return;
+            }
+        };
+        match chain.set_state_delta(shard_id, hash, base_hash, &delta) {
+            Ok(()) => {
+                shard_sync_download.downloads[0].done = true;
+            }
+            Err(err) => {
+                error!(target: "sync", "State sync set_state_delta error, shard = {}, hash = {}: {:?}", shard_id, hash, err);
+                shard_sync_download.downloads[0].error = true;
+            }
+        }
+    }
+}
+
+/// Whether a peer we are connected to directly can be asked for a state delta
+/// of given shard.
+fn can_serve_state_delta(peer: &HighestHeightPeerInfo, shard_id: ShardId) -> bool {
+    peer.tracked_shards.contains(&shard_id)
+        && checked_feature!(
+            "protocol_feature_state_sync_delta",
+            StateSyncDelta,
+            peer.protocol_version
+        )
+}
+
+/// Stores a downloaded state part and marks it as done.
+fn set_state_part(
+    chain: &mut Chain,
+    downloads: &mut [DownloadStatus],
+    shard_id: ShardId,
+    hash: CryptoHash,
+    part_id: u64,
+    data: &[u8],
+) {
+    let num_parts = downloads.len() as u64;
+    if part_id >= num_parts {
+        error!(target: "sync", "State sync received incorrect part_id # {:?} for hash {:?}, potential malicious peer", part_id, hash);
+        return;
+    }
+    let download = &mut downloads[part_id as usize];
+    if !download.done {
+        match chain.set_state_part(shard_id, hash, PartId::new(part_id, num_parts), data) {
+            Ok(()) => {
+                download.done = true;
+            }
+            Err(err) => {
+                error!(target: "sync", "State sync set_state_part error, shard = {}, part = {}, hash = {}: {:?}", shard_id, part_id, hash, err);
+                download.error = true;
+            }
+        }
+    }
+}
+
+fn new_download_status(now: DateTime<Utc>) -> DownloadStatus {
+    DownloadStatus {
+        start_time: now,
+        prev_update_time: now,
+        run_me: Arc::new(AtomicBool::new(true)),
+        error: false,
+        done: false,
+        state_requests_count: 0,
+        last_target: None,
+    }
+}
+
+/// Creates the download of all the state parts of a shard, whose header was
+/// already downloaded.
+fn parts_download(
+    chain: &Chain,
+    shard_id: ShardId,
+    sync_hash: CryptoHash,
+    now: DateTime<Utc>,
+) -> Result<ShardSyncDownload, near_chain::Error> {
+    let shard_state_header = chain.get_state_header(shard_id, sync_hash)?;
+    let state_num_parts = get_num_state_parts(shard_state_header.state_root_node().memory_usage);
+    // Create the vector with entry for each part.
+    Ok(ShardSyncDownload {
+        downloads: vec![new_download_status(now); state_num_parts as usize],
+        status: ShardSyncStatus::StateDownloadParts,
+    })
 }
 
 /// Create an abstract collection of elements to be shuffled.
@@ -896,7 +1169,12 @@ mod test {
     // Start a new state sync - and check that it asks for a header.
     fn test_ask_for_header() {
         let mock_peer_manager = Arc::new(MockPeerManagerAdapter::default());
-        let mut state_sync = StateSync::new(mock_peer_manager.clone(), TimeDuration::from_secs(1));
+        let mut state_sync = StateSync::new(
+            time::Clock::real(),
+            mock_peer_manager.clone(),
+            TimeDuration::from_secs(1),
+            None,
+        );
         let mut new_shard_sync = HashMap::new();
 
         let (mut chain, kv, signer) = test_utils::setup();
@@ -1005,4 +1283,74 @@ mod test {
             System::current().stop()
         });
     }
+
+    /// Only the peer a state header was requested from can make it re-requested
+    /// by responding that it garbage collected the state.
+    #[test]
+    fn test_garbage_collected_header_from_target_only() {
+        type Target = near_client_primitives::types::AccountOrPeerIdOrHash;
+        let mut state_sync = StateSync::new(
+            time::Clock::real(),
+            Arc::new(MockPeerManagerAdapter::default()),
+            TimeDuration::from_secs(1),
+            None,
+        );
+        let hash = CryptoHash::hash_bytes(b"sync");
+        let target = PeerId::random();
+        let other = PeerId::random();
+        let account: AccountId = "test".parse().unwrap();
+        let mut download = ShardSyncDownload::new(Utc::now());
+
+        download.downloads[0].last_target = Some(Target::PeerId(target.clone()));
+        state_sync.update_download_on_garbage_collected(
+            &mut download,
+            hash,
+            0,
+            None,
+            &other,
+            Some(&account),
+        );
+        assert!(!download.downloads[0].error);
+        // A part response doesn't fail the header download.
+        state_sync.update_download_on_garbage_collected(
+            &mut download,
+            hash,
+            0,
+            Some(0),
+            &target,
+            None,
+        );
+        assert!(!download.downloads[0].error);
+        state_sync.update_download_on_garbage_collected(
+            &mut download,
+            hash,
+            0,
+            None,
+            &target,
+            None,
+        );
+        assert!(download.downloads[0].error);
+
+        // Headers requested from a validator are matched by its account.
+        let mut download = ShardSyncDownload::new(Utc::now());
+        download.downloads[0].last_target = Some(Target::AccountId(account.clone()));
+        state_sync.update_download_on_garbage_collected(
+            &mut download,
+            hash,
+            0,
+            None,
+            &target,
+            None,
+        );
+        assert!(!download.downloads[0].error);
+        state_sync.update_download_on_garbage_collected(
+            &mut download,
+            hash,
+            0,
+            None,
+            &target,
+            Some(&account),
+        );
+        assert!(download.downloads[0].error);
+    }
 }
@@ -0,0 +1,551 @@
+//! Sync actor drives the sync of the node with its peers.
+//!
+//! It runs on its own arbiter with a read-only view of the chain, decides
+//! whether the node needs to sync and runs header and block sync, which
+//! request headers and blocks from peers.  The responses are processed by the
+//! client actor, which owns the chain.  State sync writes to the chain as it
+//! downloads the state, so its steps run in the client actor at the request
+//! of the sync actor, which answers with the resulting sync status.  The sync
+//! status is published to the client actor after every step.
+//!
+//! Running sync separately means a slow sync step doesn't delay block
+//! production, and the time spent in each phase of sync is visible in the
+//! `near_sync_step_time` metric.
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{
+    Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Recipient, ResponseFuture,
+};
+use actix_rt::ArbiterHandle;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use tracing::{debug, error, info, warn};
+
+use near_chain::test_utils::format_hash;
+use near_chain::{
+    BlockHeader, Chain, ChainGenesis, DoomslugThresholdMode, RuntimeWithEpochManagerAdapter,
+};
+use near_chain_configs::ClientConfig;
+use near_client_primitives::types::SyncStatus;
+use near_network::time;
+use near_network::types::{
+    GarbageCollectedData, HighestHeightPeerInfo, PeerManagerAdapter, ReasonForBan,
+};
+use near_o11y::{handler_debug_span, OpenTelemetrySpanExt, WithSpanContext, WithSpanContextExt};
+use near_primitives::network::PeerId;
+
+use crate::adapter::{BlockHeadersResponse, DataGarbageCollected};
+use crate::metrics;
+use crate::sync::block::BlockSync;
+use crate::sync::header::HeaderSync;
+use crate::ClientActor;
+
+/// Starts running sync steps.  Sent by the client actor once enough peers
+/// are connected.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct StartSync;
+
+/// Peers sync can request headers and blocks from.  Sent by the client actor
+/// whenever the network info changes.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct SyncNetworkInfo {
+    /// Peers with the highest heights.
+    pub highest_height_peers: Vec<HighestHeightPeerInfo>,
+    /// Those of `highest_height_peers` whose head isn't known to the client
+    /// to be invalid.
+    pub eligible_peers: Vec<HighestHeightPeerInfo>,
+}
+
+/// Sets the sync status of the client.  Sent by the sync actor.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct SetSyncStatus(pub SyncStatus);
+
+/// Runs a step of state sync in the client actor, which responds with its
+/// sync status after the step.  Carries the sync status of the sync actor,
+/// which the client adopts unless it's already in state sync.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "SyncStatus")]
+pub(crate) struct RunStateSyncStep(pub SyncStatus);
+
+/// Headers received during header sync, to be saved by the client actor.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "Result<(), ReasonForBan>")]
+pub(crate) struct SyncBlockHeaders(pub Vec<BlockHeader>);
+
+pub(crate) struct SyncActor {
+    client_addr: Addr<ClientActor>,
+    /// Runs the steps of state sync, that's the client actor.
+    state_sync_runner: Recipient<WithSpanContext<RunStateSyncStep>>,
+    /// Read-only view of the chain of the client.
+    chain: Chain,
+    config: ClientConfig,
+    adv: crate::adversarial::Controls,
+    header_sync: HeaderSync,
+    block_sync: BlockSync,
+    /// Sync status, the client actor has a copy of it.
+    sync_status: SyncStatus,
+    highest_height_peers: Vec<HighestHeightPeerInfo>,
+    eligible_peers: Vec<HighestHeightPeerInfo>,
+    /// Whether the client actor is running a step of state sync.
+    state_sync_step_in_progress: bool,
+}
+
+impl SyncActor {
+    fn new(
+        clock: time::Clock,
+        client_addr: Addr<ClientActor>,
+        state_sync_runner: Recipient<WithSpanContext<RunStateSyncStep>>,
+        config: ClientConfig,
+        chain_genesis: &ChainGenesis,
+        runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+        network_adapter: Arc<dyn PeerManagerAdapter>,
+        adv: crate::adversarial::Controls,
+    ) -> Result<Self, near_chain::Error> {
+        let chain = Chain::new_for_view_client(
+            runtime_adapter,
+            chain_genesis,
+            DoomslugThresholdMode::TwoThirds,
+            config.save_trie_changes,
+        )?;
+        let header_sync = HeaderSync::new(
+            clock.clone(),
+            network_adapter.clone(),
+            config.header_sync_initial_timeout,
+            config.header_sync_progress_timeout,
+            config.header_sync_stall_ban_timeout,
+            config.header_sync_expected_height_per_second,
+        );
+        let block_sync = BlockSync::new(
+            clock,
+            network_adapter,
+            config.block_fetch_horizon,
+            config.archive,
+            config.state_sync_fallback,
+        );
+        Ok(Self {
+            client_addr,
+            state_sync_runner,
+            chain,
+            config,
+            adv,
+            header_sync,
+            block_sync,
+            sync_status: SyncStatus::AwaitingPeers,
+            highest_height_peers: vec![],
+            eligible_peers: vec![],
+            state_sync_step_in_progress: false,
+        })
+    }
+
+    /// Check whether need to (continue) sync.
+    /// Also return higher height with known peers at that height.
+    fn syncing_info(&self) -> Result<(bool, u64), near_chain::Error> {
+        let head = self.chain.head()?;
+        let mut is_syncing = self.sync_status.is_syncing();
+
+        let peer_info = if let Some(peer_info) = self.eligible_peers.choose(&mut thread_rng()) {
+            peer_info
+        } else {
+            if !self.config.skip_sync_wait {
+                warn!(target: "client", "Sync: no peers available, disabling sync");
+            }
+            return Ok((false, 0));
+        };
+
+        if is_syncing {
+            if peer_info.highest_block_height <= head.height {
+                info!(target: "client", "Sync: synced at {} [{}], {}, highest height peer: {}",
+                      head.height, format_hash(head.last_block_hash),
+                      peer_info.peer_info.id, peer_info.highest_block_height,
+                );
+                is_syncing = false;
+            }
+        } else {
+            if peer_info.highest_block_height > head.height + self.config.sync_height_threshold {
+                info!(
+                    target: "client",
+                    "Sync: height: {}, peer id/height: {}/{}, enabling sync",
+                    head.height,
+                    peer_info.peer_info.id,
+                    peer_info.highest_block_height,
+                );
+                is_syncing = true;
+            }
+        }
+        Ok((is_syncing, peer_info.highest_block_height))
+    }
+
+    fn needs_syncing(&self, needs_syncing: bool) -> bool {
+        !self.adv.disable_header_sync() && needs_syncing
+    }
+
+    fn sync_wait_period(&self) -> Duration {
+        if let Ok((needs_syncing, _)) = self.syncing_info() {
+            if !self.needs_syncing(needs_syncing) {
+                // If we don't need syncing - retry the sync call rarely.
+                self.config.sync_check_period
+            } else {
+                // If we need syncing - retry the sync call often.
+                self.config.sync_step_period
+            }
+        } else {
+            self.config.sync_step_period
+        }
+    }
+
+    /// Runs a step of sync and schedules the next one.
+    fn sync(&mut self, ctx: &mut Context<Self>) {
+        if !self.state_sync_step_in_progress {
+            let timer = metrics::SYNC_STEP_TIME
+                .with_label_values(&[self.sync_status.as_variant_name()])
+                .start_timer();
+            self.run_sync_step(ctx);
+            timer.observe_duration();
+        }
+        near_performance_metrics::actix::run_later(
+            ctx,
+            self.sync_wait_period(),
+            move |act, ctx| {
+                act.sync(ctx);
+            },
+        );
+    }
+
+    /// Main syncing job responsible for syncing client with other peers.
+    fn run_sync_step(&mut self, ctx: &mut Context<Self>) {
+        let _span = tracing::debug_span!(target: "client", "sync").entered();
+        let _d = delay_detector::DelayDetector::new(|| "client sync".into());
+
+        macro_rules! unwrap_and_report (($obj: expr) => (match $obj {
+            Ok(v) => v,
+            Err(err) => {
+                error!(target: "sync", "Sync: Unexpected error: {}", err);
+                return;
+            }
+        }));
+
+        // The client may have changed the canonical chain since the last
+        // step, so don't read heights and next blocks from stale caches.
+        self.chain.mut_store().reset_canonical_chain_caches();
+
+        let currently_syncing = self.sync_status.is_syncing();
+        let (needs_syncing, highest_height) = unwrap_and_report!(self.syncing_info());
+
+        if !self.needs_syncing(needs_syncing) {
+            if currently_syncing {
+                self.set_sync_status(SyncStatus::NoSync);
+                self.client_addr.do_send(SetSyncStatus(SyncStatus::NoSync).with_span_context());
+            }
+            return;
+        }
+
+        // Run each step of syncing separately.
+        let mut sync_status = self.sync_status.clone();
+        unwrap_and_report!(self.header_sync.run(
+            &mut sync_status,
+            &self.chain,
+            highest_height,
+            &self.highest_height_peers
+        ));
+        // Only body / state sync if header height is close to the latest.
+        let header_head = unwrap_and_report!(self.chain.header_head());
+
+        // Sync state if already running sync state or if block sync is too far.
+        let sync_state = match sync_status {
+            SyncStatus::StateSync(_, _) => true,
+            _ if header_head.height
+                >= highest_height.saturating_sub(self.config.block_header_fetch_horizon) =>
+            {
+                unwrap_and_report!(self.block_sync.run(
+                    &mut sync_status,
+                    &self.chain,
+                    highest_height,
+                    &self.highest_height_peers
+                ))
+            }
+            _ => false,
+        };
+        if sync_state {
+            self.run_state_sync_step(sync_status, ctx);
+        } else {
+            self.set_sync_status(sync_status.clone());
+            self.client_addr.do_send(SetSyncStatus(sync_status).with_span_context());
+        }
+    }
+
+    /// Has the client actor run a step of state sync.  No other sync steps
+    /// run until the client actor is done with it, and the status is updated
+    /// once it is.  If the request fails, e.g. because the client actor
+    /// dropped it, the next sync step goes ahead as usual.
+    fn run_state_sync_step(&mut self, sync_status: SyncStatus, ctx: &mut Context<Self>) {
+        self.state_sync_step_in_progress = true;
+        let request =
+            self.state_sync_runner.send(RunStateSyncStep(sync_status).with_span_context());
+        ctx.spawn(actix::fut::wrap_future(request).map(|result, act: &mut Self, _| {
+            act.state_sync_step_in_progress = false;
+            match result {
+                Ok(sync_status) => act.set_sync_status(sync_status),
+                Err(err) => error!(target: "sync", "Sync: state sync step failed: {}", err),
+            }
+        }));
+    }
+
+    fn set_sync_status(&mut self, sync_status: SyncStatus) {
+        let sync_phase = self.sync_status.as_variant_name();
+        let new_sync_phase = sync_status.as_variant_name();
+        if new_sync_phase != sync_phase {
+            tracing::info!(
+                target: near_o11y::events::TARGET,
+                event = near_o11y::events::SYNC_PHASE_CHANGED,
+                from = %sync_phase,
+                to = %new_sync_phase,
+            );
+        }
+        self.sync_status = sync_status;
+    }
+
+    /// Checks whether the headers are a response to a request for the fork
+    /// point, returning false if they should be saved by the client.
+    fn receive_fork_point_headers(&mut self, headers: &[BlockHeader], peer_id: &PeerId) -> bool {
+        info!(target: "client", "Received {} block headers from {}", headers.len(), peer_id);
+        match self.header_sync.receive_fork_point_headers(&self.chain, headers, peer_id) {
+            Ok(consumed) => consumed || headers.is_empty(),
+            Err(err) => {
+                error!(target: "client", "Error looking for the fork point: {}", err);
+                true
+            }
+        }
+    }
+}
+
+impl Actor for SyncActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<WithSpanContext<StartSync>> for SyncActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<StartSync>, ctx: &mut Context<Self>) {
+        let (_span, _msg) = handler_debug_span!(target: "sync", msg);
+        debug!(target: "sync", "Starting sync");
+        self.sync(ctx);
+    }
+}
+
+impl Handler<WithSpanContext<SyncNetworkInfo>> for SyncActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<SyncNetworkInfo>, _: &mut Context<Self>) {
+        let (_span, msg) = handler_debug_span!(target: "sync", msg);
+        self.highest_height_peers = msg.highest_height_peers;
+        self.eligible_peers = msg.eligible_peers;
+    }
+}
+
+impl Handler<WithSpanContext<BlockHeadersResponse>> for SyncActor {
+    type Result = ResponseFuture<Result<(), ReasonForBan>>;
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<BlockHeadersResponse>,
+        _: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "sync", msg);
+        let BlockHeadersResponse(headers, peer_id) = msg;
+        if self.receive_fork_point_headers(&headers, &peer_id) {
+            return Box::pin(futures::future::ready(Ok(())));
+        }
+        let client_addr = self.client_addr.clone();
+        Box::pin(async move {
+            client_addr.send(SyncBlockHeaders(headers).with_span_context()).await.unwrap_or(Ok(()))
+        })
+    }
+}
+
+/// Forwarded by the client actor when a peer has garbage collected a block
+/// requested by block sync.
+impl Handler<WithSpanContext<DataGarbageCollected>> for SyncActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<DataGarbageCollected>, _: &mut Context<Self>) {
+        let (_span, msg) = handler_debug_span!(target: "sync", msg);
+        let DataGarbageCollected { data, peer_id } = msg;
+        if let GarbageCollectedData::Block(hash) = data {
+            self.block_sync.block_garbage_collected(hash, peer_id);
+        }
+    }
+}
+
+/// Starts the sync actor of the client actor with given address in given
+/// arbiter.
+pub(crate) fn start_sync_actor(
+    clock: time::Clock,
+    arbiter: &ArbiterHandle,
+    client_addr: Addr<ClientActor>,
+    config: ClientConfig,
+    chain_genesis: ChainGenesis,
+    runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+    network_adapter: Arc<dyn PeerManagerAdapter>,
+    adv: crate::adversarial::Controls,
+) -> Addr<SyncActor> {
+    SyncActor::start_in_arbiter(arbiter, move |_| {
+        SyncActor::new(
+            clock,
+            client_addr.clone(),
+            client_addr.recipient(),
+            config,
+            &chain_genesis,
+            runtime_adapter,
+            network_adapter,
+            adv,
+        )
+        .unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use actix::{Actor, Addr, Context, Handler, MessageResult, Recipient};
+    use near_actix_test_utils::run_actix;
+    use near_chain::test_utils::{KeyValueRuntime, ValidatorSchedule};
+    use near_chain::types::ChainConfig;
+    use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode};
+    use near_chain_configs::ClientConfig;
+    use near_client_primitives::types::SyncStatus;
+    use near_network::test_utils::MockPeerManagerAdapter;
+    use near_network::time;
+    use near_o11y::WithSpanContext;
+    use near_store::test_utils::create_test_store;
+
+    use super::{RunStateSyncStep, SyncActor};
+    use crate::ClientActor;
+
+    /// Runs a step of state sync in the sync actor.
+    #[derive(actix::Message)]
+    #[rtype(result = "()")]
+    struct StartStateSyncStep;
+
+    impl Handler<StartStateSyncStep> for SyncActor {
+        type Result = ();
+
+        fn handle(&mut self, _: StartStateSyncStep, ctx: &mut Context<Self>) {
+            self.run_state_sync_step(SyncStatus::AwaitingPeers, ctx);
+        }
+    }
+
+    /// Returns whether a step of state sync is in progress and the sync status.
+    #[derive(actix::Message)]
+    #[rtype(result = "(bool, String)")]
+    struct GetState;
+
+    impl Handler<GetState> for SyncActor {
+        type Result = MessageResult<GetState>;
+
+        fn handle(&mut self, _: GetState, _: &mut Context<Self>) -> Self::Result {
+            MessageResult((
+                self.state_sync_step_in_progress,
+                self.sync_status.as_variant_name().to_string(),
+            ))
+        }
+    }
+
+    /// Stands in for the client actor, answering state sync steps with
+    /// `NoSync`.
+    struct StateSyncRunner;
+
+    impl Actor for StateSyncRunner {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<WithSpanContext<RunStateSyncStep>> for StateSyncRunner {
+        type Result = MessageResult<WithSpanContext<RunStateSyncStep>>;
+
+        fn handle(
+            &mut self,
+            _: WithSpanContext<RunStateSyncStep>,
+            _: &mut Context<Self>,
+        ) -> Self::Result {
+            MessageResult(SyncStatus::NoSync)
+        }
+    }
+
+    fn start_sync_actor(
+        state_sync_runner: Recipient<WithSpanContext<RunStateSyncStep>>,
+    ) -> Addr<SyncActor> {
+        let vs =
+            ValidatorSchedule::new().block_producers_per_epoch(vec![vec!["test".parse().unwrap()]]);
+        let runtime = Arc::new(KeyValueRuntime::new_with_validators_and_no_gc(
+            create_test_store(),
+            vs,
+            5,
+            false,
+        ));
+        let chain_genesis = ChainGenesis::test();
+        Chain::new(
+            runtime.clone(),
+            &chain_genesis,
+            DoomslugThresholdMode::NoApprovals,
+            ChainConfig::test(),
+        )
+        .unwrap();
+        // Nothing is sent to the client actor in these tests.
+        let (client_sender, _) = actix::dev::channel::channel::<ClientActor>(16);
+        SyncActor::new(
+            time::Clock::real(),
+            Addr::new(client_sender),
+            state_sync_runner,
+            ClientConfig::test(true, 10, 20, 1, false, true, true),
+            &chain_genesis,
+            runtime,
+            Arc::new(MockPeerManagerAdapter::default()),
+            Default::default(),
+        )
+        .unwrap()
+        .start()
+    }
+
+    /// Waits until the sync actor isn't running a step of state sync anymore
+    /// and returns its sync status.
+    async fn wait_for_state_sync_step(sync_actor: &Addr<SyncActor>) -> String {
+        for _ in 0..100 {
+            let (in_progress, sync_status) = sync_actor.send(GetState).await.unwrap();
+            if !in_progress {
+                return sync_status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("State sync step still in progress");
+    }
+
+    #[test]
+    fn test_state_sync_step_done() {
+        run_actix(async {
+            let sync_actor = start_sync_actor(StateSyncRunner.start().recipient());
+            sync_actor.send(StartStateSyncStep).await.unwrap();
+            assert_eq!(wait_for_state_sync_step(&sync_actor).await, "NoSync");
+            actix::System::current().stop();
+        });
+    }
+
+    /// The sync actor doesn't stop syncing if the client actor drops a
+    /// request to run a step of state sync.
+    #[test]
+    fn test_state_sync_step_dropped() {
+        run_actix(async {
+            let (runner_sender, runner_receiver) =
+                actix::dev::channel::channel::<StateSyncRunner>(16);
+            drop(runner_receiver);
+            let sync_actor = start_sync_actor(Addr::new(runner_sender).recipient());
+            sync_actor.send(StartStateSyncStep).await.unwrap();
+            assert_eq!(wait_for_state_sync_step(&sync_actor).await, "AwaitingPeers");
+            actix::System::current().stop();
+        });
+    }
+}
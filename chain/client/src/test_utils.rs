@@ -14,8 +14,13 @@ use once_cell::sync::OnceCell;
 use rand::{thread_rng, Rng};
 use tracing::info;
 
-use crate::{start_view_client, Client, ClientActor, SyncStatus, ViewClientActor};
+use crate::loopback_network::{LoopbackNetwork, LoopbackNode};
+use crate::{
+    start_view_call_pool, start_view_client, BlockTimestampIndex, Client, ClientActor,
+    GasPriceWindow, SyncServingBudget, SyncStatus, ViewCallPool, ViewClientActor,
+};
 use near_chain::chain::{do_apply_chunks, BlockCatchUpRequest, StateSplitRequest};
+use near_chain::state_parts_cache::StatePartsCache;
 use near_chain::test_utils::{
     wait_for_all_blocks_in_processing, wait_for_block_in_processing, KeyValueRuntime,
     ValidatorSchedule,
@@ -30,7 +35,9 @@ use near_chunks::client::{ClientAdapterForShardsManager, ShardsManagerResponse};
 use near_chunks::test_utils::MockClientAdapterForShardsManager;
 use near_client_primitives::types::Error;
 use near_crypto::{InMemorySigner, KeyType, PublicKey};
+use near_network::client::DataResponse;
 use near_network::test_utils::MockPeerManagerAdapter;
+use near_network::time;
 use near_network::types::{
     AccountOrPeerIdOrHash, HighestHeightPeerInfo, PartialEncodedChunkRequestMsg,
     PartialEncodedChunkResponseMsg, PeerInfo, PeerType,
@@ -71,10 +78,11 @@ use near_store::Store;
 use near_telemetry::TelemetryActor;
 
 use crate::adapter::{
-    AnnounceAccountRequest, BlockApproval, BlockHeadersRequest, BlockHeadersResponse, BlockRequest,
-    BlockResponse, ProcessTxResponse, RecvPartialEncodedChunk, RecvPartialEncodedChunkForward,
-    RecvPartialEncodedChunkRequest, RecvPartialEncodedChunkResponse, SetNetworkInfo,
-    StateRequestHeader, StateRequestPart, StateResponse,
+    AnnounceAccountRequest, BlockApproval, BlockHeadersAtHeightsRequest, BlockHeadersRequest,
+    BlockHeadersResponse, BlockRequest, BlockResponse, DataGarbageCollected, ProcessTxResponse,
+    RecvPartialEncodedChunk, RecvPartialEncodedChunkForward, RecvPartialEncodedChunkRequest,
+    RecvPartialEncodedChunkResponse, SetNetworkInfo, StateRequestDelta, StateRequestHeader,
+    StateRequestPart, StateResponse,
 };
 
 pub struct PeerManagerMock {
@@ -139,7 +147,7 @@ impl Client {
         provenance: Provenance,
         should_produce_chunk: bool,
     ) -> Result<Vec<CryptoHash>, near_chain::Error> {
-        self.start_process_block(block, provenance, Arc::new(|_| {}))?;
+        self.start_process_block(block, provenance, None, Arc::new(|_| {}))?;
         wait_for_all_blocks_in_processing(&mut self.chain);
         let (accepted_blocks, errors) =
             self.postprocess_ready_blocks(Arc::new(|_| {}), should_produce_chunk);
@@ -220,13 +228,9 @@ pub fn setup(
     } else {
         DoomslugThresholdMode::NoApprovals
     };
-    let chain = Chain::new(
-        runtime.clone(),
-        &chain_genesis,
-        doomslug_threshold_mode,
-        ChainConfig { save_trie_changes: true, background_migration_threads: 1 },
-    )
-    .unwrap();
+    let chain =
+        Chain::new(runtime.clone(), &chain_genesis, doomslug_threshold_mode, ChainConfig::test())
+            .unwrap();
     let genesis_block = chain.get_block(&chain.genesis().hash().clone()).unwrap();
 
     let signer = Arc::new(create_test_signer(account_id.as_str()));
@@ -242,6 +246,8 @@ pub fn setup(
     );
 
     let adv = crate::adversarial::Controls::default();
+    let block_timestamp_index = Arc::new(Mutex::new(BlockTimestampIndex::new()));
+    let gas_price_window = Arc::new(Mutex::new(GasPriceWindow::new()));
 
     let view_client_addr = start_view_client(
         Some(signer.validator_id().clone()),
@@ -249,10 +255,15 @@ pub fn setup(
         runtime.clone(),
         network_adapter.clone(),
         config.clone(),
+        block_timestamp_index.clone(),
+        Arc::new(Mutex::new(SyncServingBudget::from_config(&config))),
+        gas_price_window.clone(),
+        Arc::new(StatePartsCache::new(config.state_parts_cache_size)),
         adv.clone(),
     );
 
     let client = ClientActor::new(
+        time::Clock::real(),
         ctx.address(),
         config,
         chain_genesis,
@@ -267,6 +278,8 @@ pub fn setup(
         None,
         adv,
         None,
+        block_timestamp_index,
+        gas_price_window,
     )
     .unwrap();
     (genesis_block, client, view_client_addr)
@@ -308,13 +321,8 @@ pub fn setup_only_view(
     } else {
         DoomslugThresholdMode::NoApprovals
     };
-    Chain::new(
-        runtime.clone(),
-        &chain_genesis,
-        doomslug_threshold_mode,
-        ChainConfig { save_trie_changes: true, background_migration_threads: 1 },
-    )
-    .unwrap();
+    Chain::new(runtime.clone(), &chain_genesis, doomslug_threshold_mode, ChainConfig::test())
+        .unwrap();
 
     let signer = Arc::new(create_test_signer(account_id.as_str()));
     TelemetryActor::default().start();
@@ -329,6 +337,8 @@ pub fn setup_only_view(
     );
 
     let adv = crate::adversarial::Controls::default();
+    let sync_serving_budget = Arc::new(Mutex::new(SyncServingBudget::from_config(&config)));
+    let state_parts_cache = Arc::new(StatePartsCache::new(config.state_parts_cache_size));
 
     start_view_client(
         Some(signer.validator_id().clone()),
@@ -336,10 +346,48 @@ pub fn setup_only_view(
         runtime,
         network_adapter.clone(),
         config,
+        Arc::new(Mutex::new(BlockTimestampIndex::new())),
+        sync_serving_budget,
+        Arc::new(Mutex::new(GasPriceWindow::new())),
+        state_parts_cache,
         adv,
     )
 }
 
+/// Sets up a view call pool of a single thread with given limits.
+pub fn setup_view_call_pool(max_pending: usize, timeout: Duration) -> ViewCallPool {
+    let store = create_test_store();
+    let vs =
+        ValidatorSchedule::new().block_producers_per_epoch(vec![vec!["test".parse().unwrap()]]);
+    let runtime = Arc::new(KeyValueRuntime::new_with_validators_and_no_gc(store, vs, 5, false));
+    let chain_genesis = ChainGenesis::test();
+    Chain::new(
+        runtime.clone(),
+        &chain_genesis,
+        DoomslugThresholdMode::NoApprovals,
+        ChainConfig::test(),
+    )
+    .unwrap();
+    let mut config = ClientConfig::test(true, 10, 20, 1, false, true, true);
+    config.view_call_threads = 1;
+    config.view_call_max_pending = max_pending;
+    config.view_call_timeout = timeout;
+    let sync_serving_budget = Arc::new(Mutex::new(SyncServingBudget::from_config(&config)));
+    let state_parts_cache = Arc::new(StatePartsCache::new(config.state_parts_cache_size));
+    start_view_call_pool(
+        None,
+        chain_genesis,
+        runtime,
+        Arc::new(MockPeerManagerAdapter::default()),
+        config,
+        Arc::new(Mutex::new(BlockTimestampIndex::new())),
+        sync_serving_budget,
+        Arc::new(Mutex::new(GasPriceWindow::new())),
+        state_parts_cache,
+        crate::adversarial::Controls::default(),
+    )
+}
+
 /// Sets up ClientActor and ViewClientActor with mock PeerManager.
 pub fn setup_mock(
     validators: Vec<AccountId>,
@@ -670,6 +718,8 @@ pub fn setup_mock_all_validators(
                                         }),
                                         tracked_shards: vec![],
                                         archival: true,
+                                        earliest_height: None,
+                                        protocol_version: PROTOCOL_VERSION,
                                     },
                                 },
                                 received_bytes_per_sec: 0,
@@ -679,6 +729,8 @@ pub fn setup_mock_all_validators(
                                 connection_established_time: near_network::time::Instant::now(),
                                 peer_type: PeerType::Outbound,
                                 nonce: 3,
+                                messages_to_send: 0,
+                                bytes_to_send: 0,
                             })
                             .collect();
                         let peers2 = peers
@@ -796,7 +848,7 @@ pub fn setup_mock_all_validators(
                                             .then(move |response| {
                                                 let response = response.unwrap();
                                                 match response {
-                                                    Some(block) => {
+                                                    Some(DataResponse::Data(block)) => {
                                                         me.do_send(
                                                             BlockResponse {
                                                                 block: *block,
@@ -806,6 +858,12 @@ pub fn setup_mock_all_validators(
                                                             .with_span_context(),
                                                         );
                                                     }
+                                                    Some(DataResponse::GarbageCollected(data)) => {
+                                                        me.do_send(
+                                                            DataGarbageCollected { data, peer_id }
+                                                                .with_span_context(),
+                                                        );
+                                                    }
                                                     None => {}
                                                 }
                                                 future::ready(())
@@ -843,6 +901,35 @@ pub fn setup_mock_all_validators(
                                 }
                             }
                         }
+                        NetworkRequests::BlockHeadersAtHeightsRequest { heights, peer_id } => {
+                            for (i, peer_info) in key_pairs.iter().enumerate() {
+                                let peer_id = peer_id.clone();
+                                if peer_info.id == peer_id {
+                                    let me = connectors1[my_ord].0.clone();
+                                    actix::spawn(
+                                        connectors1[i]
+                                            .1
+                                            .send(
+                                                BlockHeadersAtHeightsRequest(heights.clone())
+                                                    .with_span_context(),
+                                            )
+                                            .then(move |response| {
+                                                let response = response.unwrap();
+                                                match response {
+                                                    Some(headers) => {
+                                                        me.do_send(
+                                                            BlockHeadersResponse(headers, peer_id)
+                                                                .with_span_context(),
+                                                        );
+                                                    }
+                                                    None => {}
+                                                }
+                                                future::ready(())
+                                            }),
+                                    );
+                                }
+                            }
+                        }
                         NetworkRequests::StateRequestHeader {
                             shard_id,
                             sync_hash,
@@ -855,6 +942,7 @@ pub fn setup_mock_all_validators(
                             for (i, name) in validators_clone2.iter().enumerate() {
                                 if name == target_account_id {
                                     let me = connectors1[my_ord].0.clone();
+                                    let peer_id = key_pairs[i].id.clone();
                                     actix::spawn(
                                         connectors1[i]
                                             .1
@@ -862,15 +950,22 @@ pub fn setup_mock_all_validators(
                                                 StateRequestHeader {
                                                     shard_id: *shard_id,
                                                     sync_hash: *sync_hash,
+                                                    peer_id: my_key_pair.id.clone(),
                                                 }
                                                 .with_span_context(),
                                             )
                                             .then(move |response| {
                                                 let response = response.unwrap();
                                                 match response {
-                                                    Some(response) => {
+                                                    Some(DataResponse::Data(response)) => {
                                                         me.do_send(response.with_span_context());
                                                     }
+                                                    Some(DataResponse::GarbageCollected(data)) => {
+                                                        me.do_send(
+                                                            DataGarbageCollected { data, peer_id }
+                                                                .with_span_context(),
+                                                        );
+                                                    }
                                                     None => {}
                                                 }
                                                 future::ready(())
@@ -892,6 +987,7 @@ pub fn setup_mock_all_validators(
                             for (i, name) in validators_clone2.iter().enumerate() {
                                 if name == target_account_id {
                                     let me = connectors1[my_ord].0.clone();
+                                    let peer_id = key_pairs[i].id.clone();
                                     actix::spawn(
                                         connectors1[i]
                                             .1
@@ -900,6 +996,53 @@ pub fn setup_mock_all_validators(
                                                     shard_id: *shard_id,
                                                     sync_hash: *sync_hash,
                                                     part_id: *part_id,
+                                                    peer_id: my_key_pair.id.clone(),
+                                                }
+                                                .with_span_context(),
+                                            )
+                                            .then(move |response| {
+                                                let response = response.unwrap();
+                                                match response {
+                                                    Some(DataResponse::Data(response)) => {
+                                                        me.do_send(response.with_span_context());
+                                                    }
+                                                    Some(DataResponse::GarbageCollected(data)) => {
+                                                        me.do_send(
+                                                            DataGarbageCollected { data, peer_id }
+                                                                .with_span_context(),
+                                                        );
+                                                    }
+                                                    None => {}
+                                                }
+                                                future::ready(())
+                                            }),
+                                    );
+                                }
+                            }
+                        }
+                        NetworkRequests::StateRequestDelta {
+                            shard_id,
+                            sync_hash,
+                            from_hash,
+                            target: target_peer_id,
+                        } => {
+                            // Deltas are only requested from peers we are connected to directly.
+                            let target_peer_id = match target_peer_id {
+                                AccountOrPeerIdOrHash::PeerId(x) => x,
+                                _ => panic!(),
+                            };
+                            for (i, key_pair) in key_pairs.iter().enumerate() {
+                                if &key_pair.id == target_peer_id {
+                                    let me = connectors1[my_ord].0.clone();
+                                    actix::spawn(
+                                        connectors1[i]
+                                            .1
+                                            .send(
+                                                StateRequestDelta {
+                                                    shard_id: *shard_id,
+                                                    sync_hash: *sync_hash,
+                                                    from_hash: *from_hash,
+                                                    peer_id: my_key_pair.id.clone(),
                                                 }
                                                 .with_span_context(),
                                             )
@@ -1050,6 +1193,54 @@ pub fn setup_mock_all_validators(
     (value.clone().unwrap(), ret, block_stats)
 }
 
+/// Sets up ClientActors and ViewClientActors of all the validators talking to
+/// each other through a `LoopbackNetwork`, which tests can use to change the
+/// conditions of the network while the validators run.  The validators are
+/// indexed in the network in the order of `vs.all_validators()` and `seed`
+/// decides which messages the network drops.
+pub fn setup_loopback_validators(
+    vs: ValidatorSchedule,
+    epoch_length: BlockHeightDelta,
+    block_prod_time: u64,
+    seed: u64,
+) -> (LoopbackNetwork, Vec<(Addr<ClientActor>, Addr<ViewClientActor>)>) {
+    let network = LoopbackNetwork::new(seed);
+    let genesis_time = Clock::utc();
+    let mut connectors = vec![];
+    for account_id in vs.all_validators().cloned().collect::<Vec<_>>() {
+        let peer_manager = network.peer_manager(account_id.clone());
+        let mut view_client_addr_slot = None;
+        let client_addr = ClientActor::create(|ctx| {
+            let (_, client, view_client_addr) = setup(
+                vs.clone(),
+                epoch_length,
+                account_id.clone(),
+                true,
+                block_prod_time,
+                block_prod_time * 3,
+                false,
+                false,
+                false,
+                peer_manager,
+                10000,
+                genesis_time,
+                ctx,
+            );
+            view_client_addr_slot = Some(view_client_addr);
+            client
+        });
+        let view_client_addr = view_client_addr_slot.unwrap();
+        network.add_node(LoopbackNode {
+            account_id,
+            peer_info: PeerInfo::random(),
+            client: client_addr.clone(),
+            view_client: view_client_addr.clone(),
+        });
+        connectors.push((client_addr, view_client_addr));
+    }
+    (network, connectors)
+}
+
 /// Sets up ClientActor and ViewClientActor without network.
 pub fn setup_no_network(
     validators: Vec<AccountId>,
@@ -1086,6 +1277,7 @@ pub fn setup_no_network_with_validity_period_and_no_epoch_sync(
 }
 
 pub fn setup_client_with_runtime(
+    clock: time::Clock,
     num_validator_seats: NumSeats,
     account_id: Option<AccountId>,
     enable_doomslug: bool,
@@ -1103,6 +1295,7 @@ pub fn setup_client_with_runtime(
         ClientConfig::test(true, 10, 20, num_validator_seats, archive, save_trie_changes, true);
     config.epoch_length = chain_genesis.epoch_length;
     let mut client = Client::new(
+        clock,
         config,
         chain_genesis,
         runtime_adapter,
@@ -1118,6 +1311,7 @@ pub fn setup_client_with_runtime(
 }
 
 pub fn setup_client(
+    clock: time::Clock,
     store: Store,
     vs: ValidatorSchedule,
     account_id: Option<AccountId>,
@@ -1133,6 +1327,7 @@ pub fn setup_client(
     let runtime_adapter =
         Arc::new(KeyValueRuntime::new_with_validators(store, vs, chain_genesis.epoch_length));
     setup_client_with_runtime(
+        clock,
         num_validator_seats,
         account_id,
         enable_doomslug,
@@ -1161,6 +1356,8 @@ pub struct TestEnv {
     seeds: HashMap<AccountId, RngSeed>,
     archive: bool,
     save_trie_changes: bool,
+    enable_doomslug: bool,
+    clock: time::Clock,
 }
 
 /// A builder for the TestEnv structure.
@@ -1175,6 +1372,8 @@ pub struct TestEnvBuilder {
     seeds: HashMap<AccountId, RngSeed>,
     archive: bool,
     save_trie_changes: bool,
+    enable_doomslug: bool,
+    clock: time::Clock,
 }
 
 /// Builder for the [`TestEnv`] structure.
@@ -1193,6 +1392,8 @@ impl TestEnvBuilder {
             seeds,
             archive: false,
             save_trie_changes: true,
+            enable_doomslug: false,
+            clock: time::Clock::real(),
         }
     }
 
@@ -1268,6 +1469,21 @@ impl TestEnvBuilder {
         self
     }
 
+    /// Makes the clients require 2/3 of approvals to produce a block.  By
+    /// default blocks can be produced without any approvals, which is what
+    /// most tests calling [`TestEnv::produce_block`] directly want.
+    pub fn enable_doomslug(mut self, enable_doomslug: bool) -> Self {
+        self.enable_doomslug = enable_doomslug;
+        self
+    }
+
+    /// Sets the clock the clients read the time from.  By default it's the
+    /// real one.
+    pub fn clock(mut self, clock: time::Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Constructs new `TestEnv` structure.
     ///
     /// If no clients were configured (either through count or vector) one
@@ -1304,10 +1520,11 @@ impl TestEnvBuilder {
                     let vs = ValidatorSchedule::new()
                         .block_producers_per_epoch(vec![validators.clone()]);
                     setup_client(
+                        self.clock.clone(),
                         create_test_store(),
                         vs,
                         Some(account_id),
-                        false,
+                        self.enable_doomslug,
                         network_adapter.clone(),
                         client_adapter.clone(),
                         chain_genesis.clone(),
@@ -1330,9 +1547,10 @@ impl TestEnvBuilder {
                             None => TEST_SEED,
                         };
                         setup_client_with_runtime(
+                            self.clock.clone(),
                             u64::try_from(num_validators).unwrap(),
                             Some(account_id),
-                            false,
+                            self.enable_doomslug,
                             network_adapter.clone(),
                             client_adapter.clone(),
                             chain_genesis.clone(),
@@ -1362,6 +1580,8 @@ impl TestEnvBuilder {
             seeds,
             archive: self.archive,
             save_trie_changes: self.save_trie_changes,
+            enable_doomslug: self.enable_doomslug,
+            clock: self.clock,
         }
     }
 
@@ -1596,6 +1816,7 @@ impl TestEnv {
                 last_block.header().hash(),
                 last_block.header().epoch_id(),
                 &QueryRequest::ViewAccount { account_id },
+                None,
             )
             .unwrap();
         match response.kind {
@@ -1622,7 +1843,10 @@ impl TestEnv {
                     account_id,
                     prefix: vec![].into(),
                     include_proof: false,
+                    after_key: None,
+                    limit: None,
                 },
+                None,
             )
             .unwrap();
         match response.kind {
@@ -1651,9 +1875,10 @@ impl TestEnv {
         let num_validator_seats = vs.all_block_producers().count() as NumSeats;
         let runtime_adapter = self.clients[idx].runtime_adapter.clone();
         self.clients[idx] = setup_client_with_runtime(
+            self.clock.clone(),
             num_validator_seats,
             Some(self.get_client_id(idx).clone()),
-            false,
+            self.enable_doomslug,
             self.network_adapters[idx].clone(),
             self.client_adapters[idx].clone(),
             self.chain_genesis.clone(),
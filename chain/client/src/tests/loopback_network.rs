@@ -0,0 +1,65 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use actix::{Actor, System};
+use futures::{future, FutureExt};
+use near_actix_test_utils::run_actix;
+use near_chain::test_utils::ValidatorSchedule;
+use near_network::test_utils::WaitOrTimeoutActor;
+use near_o11y::testonly::init_integration_logger;
+use near_o11y::WithSpanContextExt;
+use near_primitives::types::BlockHeight;
+
+use crate::simulation::LinkConditions;
+use crate::test_utils::setup_loopback_validators;
+use crate::GetBlock;
+
+/// Validators produce blocks talking over a lossy loopback network with
+/// delays and capped bandwidth.
+#[test]
+fn test_loopback_network_produces_blocks() {
+    init_integration_logger();
+    const HEIGHT_GOAL: BlockHeight = 15;
+
+    run_actix(async move {
+        let vs = ValidatorSchedule::new().num_shards(2).block_producers_per_epoch(vec![vec![
+            "test1".parse().unwrap(),
+            "test2".parse().unwrap(),
+            "test3".parse().unwrap(),
+        ]]);
+        let (network, connectors) = setup_loopback_validators(vs, 5, 100, 42);
+        network.network().set_default_conditions(LinkConditions {
+            delay: Duration::from_millis(10),
+            drop_probability: 0.05,
+            bandwidth: Some(10_000_000),
+        });
+
+        let heights = Arc::new(RwLock::new(vec![0; connectors.len()]));
+        WaitOrTimeoutActor::new(
+            Box::new(move |_ctx| {
+                for (i, (_, view_client)) in connectors.iter().enumerate() {
+                    let heights = heights.clone();
+                    let network = network.clone();
+                    actix::spawn(view_client.send(GetBlock::latest().with_span_context()).then(
+                        move |res| {
+                            if let Ok(Ok(block)) = res {
+                                let mut heights = heights.write().unwrap();
+                                heights[i] = block.header.height;
+                                if heights.iter().all(|height| *height >= HEIGHT_GOAL) {
+                                    let stats = network.network().stats();
+                                    assert!(stats.delivered > 0);
+                                    assert!(stats.dropped > 0);
+                                    System::current().stop();
+                                }
+                            }
+                            future::ready(())
+                        },
+                    ));
+                }
+            }),
+            100,
+            60000,
+        )
+        .start();
+    });
+}
@@ -0,0 +1,44 @@
+use actix::System;
+use futures::FutureExt;
+use near_actix_test_utils::run_actix;
+use near_o11y::testonly::init_test_logger;
+use near_o11y::WithSpanContextExt;
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use std::sync::{Arc, Mutex};
+
+use crate::adapter::ProcessTxRequest;
+use crate::test_utils::setup_no_network;
+
+/// Forwarded transactions are deferred, so a transaction submitted after them
+/// is processed first, while the deferred ones keep the order they arrived in.
+#[test]
+fn test_low_priority_messages_processed_after_queued_ones() {
+    init_test_logger();
+    run_actix(async {
+        let (client, _) =
+            setup_no_network(vec!["test".parse().unwrap()], "other".parse().unwrap(), true, false);
+        let processed = Arc::new(Mutex::new(vec![]));
+        let requests = [("forwarded 1", true), ("forwarded 2", true), ("submitted", false)];
+        // The actor runs on this thread, so all the requests are in its
+        // mailbox before it processes any of them.  Responses are sent in the
+        // order the requests are processed.
+        for (label, is_forwarded) in requests {
+            let processed = processed.clone();
+            let request = ProcessTxRequest {
+                transaction: SignedTransaction::empty(CryptoHash::default()),
+                is_forwarded,
+                check_only: false,
+            };
+            actix::spawn(client.send(request.with_span_context()).map(move |res| {
+                res.unwrap();
+                let mut processed = processed.lock().unwrap();
+                processed.push(label);
+                if processed.len() == requests.len() {
+                    assert_eq!(*processed, ["submitted", "forwarded 1", "forwarded 2"]);
+                    System::current().stop();
+                }
+            }));
+        }
+    });
+}
@@ -4,6 +4,12 @@ mod chunks_management;
 mod consensus;
 mod cross_shard_tx;
 mod doomslug;
+mod loopback_network;
 mod maintenance_windows;
+mod message_priority;
 mod process_blocks;
 mod query_client;
+mod trace_propagation;
+mod simulation;
+mod tx_forwarding;
+mod validator_schedule;
@@ -1,6 +1,11 @@
+use crate::dry_run::DryRunProducer;
+use crate::metrics;
 use crate::test_utils::TestEnv;
 use near_chain::{test_utils, ChainGenesis, Provenance};
+use near_chain_configs::EmptyBlocksPolicy;
 use near_crypto::{KeyType, PublicKey};
+use near_network::time;
+use near_network::types::{NetworkRequests, PeerManagerMessageRequest};
 use near_primitives::network::PeerId;
 use near_primitives::test_utils::create_test_signer;
 use near_primitives::types::validator_stake::ValidatorStake;
@@ -37,3 +42,77 @@ fn test_not_process_height_twice() {
     // check that we didn't rebroadcast the second block
     assert!(env.network_adapters[0].pop().is_none());
 }
+
+/// Only blocks with a valid header count towards the propagation delays of
+/// their producer.
+#[test]
+fn test_block_propagation_recorded_after_validation() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).clients_count(2).build();
+    let block = env.clients[0].produce_block(1).unwrap().unwrap();
+    let mut forged_block = block.clone();
+    forged_block.mut_header().resign(&create_test_signer("other"));
+    let peer_id = PeerId::new(PublicKey::empty(KeyType::ED25519));
+    assert!(env.clients[1]
+        .receive_block_impl(forged_block, peer_id.clone(), false, Arc::new(|_| {}))
+        .is_err());
+    assert!(env.clients[1].block_propagation_info.slowest_producers().is_empty());
+
+    env.clients[1].receive_block_impl(block, peer_id, false, Arc::new(|_| {})).unwrap();
+    let producers = env.clients[1].block_propagation_info.slowest_producers();
+    assert_eq!(producers.len(), 1);
+    assert_eq!(producers[0].account_id.as_str(), "test0");
+    assert_eq!(producers[0].num_blocks, 1);
+}
+
+/// A node shadowing a validator in dry-run mode assembles that validator's blocks
+/// but never applies them to its own chain.
+#[test]
+fn test_dry_run_production() {
+    let mut env = TestEnv::builder(ChainGenesis::test()).clients_count(2).build();
+    env.clients[1].dry_run_producer = Some(DryRunProducer::new("test0".parse().unwrap()));
+    let dry_run_blocks_before = metrics::DRY_RUN_BLOCK_PRODUCTION_TIME.get_sample_count();
+    for height in 1..4 {
+        let block = env.clients[0].produce_block(height).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        env.process_block(1, block, Provenance::NONE);
+    }
+    assert!(metrics::DRY_RUN_BLOCK_PRODUCTION_TIME.get_sample_count() >= dry_run_blocks_before + 3);
+    assert_eq!(env.clients[1].chain.head().unwrap().height, 3);
+}
+
+/// The head is rebroadcast once it didn't change for the stall timeout,
+/// measured on the clock the client was built with.
+#[test]
+fn test_head_progress_stalled_on_fake_clock() {
+    let clock = time::FakeClock::default();
+    let mut env = TestEnv::builder(ChainGenesis::test()).clock(clock.clock()).build();
+    let stall_timeout = std::time::Duration::from_secs(10);
+    env.clients[0].check_head_progress_stalled(stall_timeout).unwrap();
+    assert!(env.network_adapters[0].pop().is_none());
+
+    clock.advance(time::Duration::seconds(11));
+    env.clients[0].check_head_progress_stalled(stall_timeout).unwrap();
+    assert!(matches!(
+        env.network_adapters[0].pop(),
+        Some(PeerManagerMessageRequest::NetworkRequests(NetworkRequests::Block { .. }))
+    ));
+    env.clients[0].check_head_progress_stalled(stall_timeout).unwrap();
+    assert!(env.network_adapters[0].pop().is_none());
+}
+
+/// With `EmptyBlocksPolicy::IfIdleLongerThan` a block without new chunks is
+/// only produced once the head is older than the given idle time.
+#[test]
+fn test_produce_empty_block_if_idle() {
+    let clock = time::FakeClock::new(time::Clock::real().now_utc());
+    let mut env = TestEnv::builder(ChainGenesis::test()).clock(clock.clock()).build();
+    env.clients[0]
+        .config
+        .produce_empty_blocks
+        .update(EmptyBlocksPolicy::IfIdleLongerThan(std::time::Duration::from_secs(10)));
+    assert!(env.clients[0].produce_block(1).unwrap().is_none());
+
+    clock.advance(time::Duration::seconds(11));
+    let block = env.clients[0].produce_block(1).unwrap().unwrap();
+    assert_eq!(block.header().height(), 1);
+}
@@ -1,13 +1,24 @@
 use actix::System;
 use futures::{future, FutureExt};
 use near_chain::test_utils::ValidatorSchedule;
+use near_primitives::hash::hash;
 use near_primitives::merkle::PartialMerkleTree;
+use near_primitives::network::PeerId;
 use near_primitives::test_utils::create_test_signer;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::adapter::{BlockResponse, ProcessTxRequest, ProcessTxResponse, StateRequestHeader};
-use crate::test_utils::{setup_mock_all_validators, setup_no_network, setup_only_view};
+use crate::adapter::{
+    BlockRequest, BlockResponse, ProcessTxRequest, ProcessTxResponse, StateRequestHeader,
+};
+use crate::test_utils::{
+    setup_mock_all_validators, setup_no_network, setup_only_view, setup_view_call_pool,
+};
+use crate::view_client::{
+    find_first_block_not_before, is_prev_block_closer, StateRequestThrottle, SyncServingBudget,
+    SyncServingKind,
+};
 use crate::{
     GetBlock, GetBlockWithMerkleTree, GetExecutionOutcomesForBlock, Query, QueryError, Status,
     TxStatus,
@@ -15,8 +26,9 @@ use crate::{
 use near_actix_test_utils::run_actix;
 use near_chain_configs::DEFAULT_GC_NUM_EPOCHS_TO_KEEP;
 use near_crypto::{InMemorySigner, KeyType};
+use near_network::client::DataResponse;
 use near_network::test_utils::MockPeerManagerAdapter;
-use near_network::types::PeerInfo;
+use near_network::types::{GarbageCollectedData, PeerInfo};
 use near_network::types::{
     NetworkRequests, NetworkResponses, PeerManagerMessageRequest, PeerManagerMessageResponse,
 };
@@ -226,11 +238,16 @@ fn test_state_request() {
                 .unwrap()
                 .header
                 .hash;
+            let peer_id = PeerId::random();
             for _ in 0..30 {
                 let res = view_client
                     .send(
-                        StateRequestHeader { shard_id: 0, sync_hash: block_hash }
-                            .with_span_context(),
+                        StateRequestHeader {
+                            shard_id: 0,
+                            sync_hash: block_hash,
+                            peer_id: peer_id.clone(),
+                        }
+                        .with_span_context(),
                     )
                     .await
                     .unwrap();
@@ -239,13 +256,27 @@ fn test_state_request() {
 
             // immediately query again, should be rejected
             let res = view_client
-                .send(StateRequestHeader { shard_id: 0, sync_hash: block_hash }.with_span_context())
+                .send(
+                    StateRequestHeader {
+                        shard_id: 0,
+                        sync_hash: block_hash,
+                        peer_id: peer_id.clone(),
+                    }
+                    .with_span_context(),
+                )
                 .await
                 .unwrap();
             assert!(res.is_none());
             actix::clock::sleep(Duration::from_secs(40)).await;
             let res = view_client
-                .send(StateRequestHeader { shard_id: 0, sync_hash: block_hash }.with_span_context())
+                .send(
+                    StateRequestHeader {
+                        shard_id: 0,
+                        sync_hash: block_hash,
+                        peer_id: peer_id.clone(),
+                    }
+                    .with_span_context(),
+                )
                 .await
                 .unwrap();
             assert!(res.is_some());
@@ -255,6 +286,165 @@ fn test_state_request() {
     });
 }
 
+#[test]
+fn test_state_request_throttle() {
+    let mut throttle = StateRequestThrottle::new(Duration::from_secs(10), 5, 2, 3);
+    let now = std::time::Instant::now();
+    let peers: Vec<_> = (0..4).map(|_| PeerId::random()).collect();
+
+    assert_eq!(throttle.start(now, Some(&peers[0])), Ok(()));
+    assert_eq!(throttle.start(now, Some(&peers[0])), Ok(()));
+    assert_eq!(throttle.start(now, Some(&peers[0])), Err("peer"));
+    assert_eq!(throttle.start(now, Some(&peers[1])), Ok(()));
+    assert_eq!(throttle.start(now, Some(&peers[1])), Err("in_progress"));
+    throttle.finish();
+    throttle.finish();
+    throttle.finish();
+    assert_eq!(throttle.start(now, Some(&peers[1])), Ok(()));
+    assert_eq!(throttle.start(now, Some(&peers[2])), Ok(()));
+    assert_eq!(throttle.start(now, Some(&peers[3])), Err("total"));
+    throttle.finish();
+    throttle.finish();
+
+    // Requests older than the period no longer count.
+    let later = now + Duration::from_secs(11);
+    assert_eq!(throttle.start(later, Some(&peers[0])), Ok(()));
+    assert_eq!(throttle.start(later, Some(&peers[0])), Ok(()));
+    assert_eq!(throttle.start(later, Some(&peers[3])), Ok(()));
+    throttle.finish();
+    throttle.finish();
+    throttle.finish();
+
+    // Requests not made by a peer count only towards the total limits.
+    let later = later + Duration::from_secs(11);
+    assert_eq!(throttle.start(later, Some(&peers[0])), Ok(()));
+    assert_eq!(throttle.start(later, Some(&peers[0])), Ok(()));
+    assert_eq!(throttle.start(later, None), Ok(()));
+    assert_eq!(throttle.start(later, None), Err("in_progress"));
+    throttle.finish();
+    assert_eq!(throttle.start(later, None), Ok(()));
+    throttle.finish();
+    assert_eq!(throttle.start(later, None), Ok(()));
+    throttle.finish();
+    assert_eq!(throttle.start(later, None), Err("total"));
+}
+
+#[test]
+fn test_sync_serving_budget() {
+    let mut budget = SyncServingBudget::new(Duration::from_secs(2), Some(1000), Some(300));
+    let now = std::time::Instant::now();
+
+    assert_eq!(budget.check(now, SyncServingKind::State), Ok(()));
+    budget.record(now, SyncServingKind::State, 700);
+    assert_eq!(budget.check(now, SyncServingKind::State), Err("state"));
+    assert_eq!(budget.check(now, SyncServingKind::Block), Ok(()));
+    budget.record(now, SyncServingKind::Block, 1500);
+    assert_eq!(budget.check(now, SyncServingKind::Headers), Err("total"));
+
+    // Responses older than the period no longer count.
+    let later = now + Duration::from_secs(3);
+    assert_eq!(budget.check(later, SyncServingKind::State), Ok(()));
+    assert_eq!(budget.check(later, SyncServingKind::Headers), Ok(()));
+    assert!(budget.is_limited());
+    assert!(SyncServingBudget::new(Duration::from_secs(2), None, Some(300)).is_limited());
+
+    let mut unlimited = SyncServingBudget::new(Duration::from_secs(2), None, None);
+    assert!(!unlimited.is_limited());
+    unlimited.record(now, SyncServingKind::State, u32::MAX as u64);
+    assert_eq!(unlimited.check(now, SyncServingKind::State), Ok(()));
+}
+
+fn call_function() -> Query {
+    Query::new(
+        BlockReference::latest(),
+        QueryRequest::CallFunction {
+            account_id: "test".parse().unwrap(),
+            method_name: "method".to_string(),
+            args: vec![].into(),
+        },
+    )
+}
+
+/// Calls beyond `view_call_max_pending` are rejected without being queued.
+#[test]
+fn test_view_call_pool_max_pending() {
+    init_test_logger();
+    run_actix(async {
+        let pool = setup_view_call_pool(1, Duration::from_secs(10));
+        let (first, second) =
+            future::join(pool.query(call_function()), pool.query(call_function())).await;
+        assert!(matches!(first.unwrap().kind, QueryResponseKind::CallResult(_)));
+        assert!(matches!(second, Err(QueryError::InternalError { .. })), "{second:?}");
+        // Once the first call is done, there's room for another one.
+        assert_eq!(pool.pending(), 0);
+        assert!(pool.query(call_function()).await.is_ok());
+        System::current().stop();
+    });
+}
+
+/// Calls which don't finish within `view_call_timeout` or before the deadline
+/// of the query are given up, and they stop counting towards the bound.
+#[test]
+fn test_view_call_pool_timeout() {
+    init_test_logger();
+    run_actix(async {
+        let pool = setup_view_call_pool(1, Duration::ZERO);
+        let result = pool.query(call_function()).await;
+        assert!(matches!(result, Err(QueryError::InternalError { .. })), "{result:?}");
+
+        let pool = setup_view_call_pool(1, Duration::from_secs(10));
+        let deadline = std::time::Instant::now();
+        let result = pool.query(call_function().with_deadline(Some(deadline))).await;
+        assert!(matches!(result, Err(QueryError::TimeoutError)), "{result:?}");
+
+        // The dropped call frees its slot once the pool gets to it.
+        let started = std::time::Instant::now();
+        while pool.pending() > 0 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(pool.query(call_function()).await.is_ok());
+        System::current().stop();
+    });
+}
+
+#[test]
+fn test_find_first_block_not_before() {
+    // Blocks at heights 3, 4, 7 and 8 with timestamps 10 times their height.
+    let heights: [u64; 4] = [3, 4, 7, 8];
+    let block_at_or_after = |height: u64| {
+        Ok::<_, ()>(
+            heights.iter().find(|h| **h >= height).map(|h| (*h, hash(&h.to_le_bytes()), *h * 10)),
+        )
+    };
+    let find = |timestamp| {
+        find_first_block_not_before(3, 8, timestamp, block_at_or_after)
+            .unwrap()
+            .map(|(height, _, _)| height)
+    };
+    assert_eq!(find(0), Some(3));
+    assert_eq!(find(30), Some(3));
+    assert_eq!(find(31), Some(4));
+    assert_eq!(find(45), Some(7));
+    assert_eq!(find(70), Some(7));
+    assert_eq!(find(80), Some(8));
+    assert_eq!(find(81), None);
+}
+
+#[test]
+fn test_is_prev_block_closer() {
+    assert!(is_prev_block_closer(15, 10, 20));
+    assert!(is_prev_block_closer(14, 10, 20));
+    assert!(!is_prev_block_closer(16, 10, 20));
+    assert!(is_prev_block_closer(10, 10, 20));
+    assert!(!is_prev_block_closer(20, 10, 20));
+    // A timestamp below the tail, the first block found is the tail and the
+    // previous block is after the timestamp as well.
+    assert!(is_prev_block_closer(5, 10, 20));
+    assert!(is_prev_block_closer(0, u64::MAX - 1, u64::MAX));
+    assert!(is_prev_block_closer(u64::MAX, 0, 0));
+}
+
 #[test]
 /// When querying data which was garbage collected on a node it returns
 /// `QueryError::GarbageCollectedBlock`.
@@ -380,3 +570,113 @@ fn test_garbage_collection() {
         near_network::test_utils::wait_or_panic(block_prod_time * target_height * 2 + 2000);
     })
 }
+
+/// Requests for blocks and state which the node has garbage collected are
+/// answered with `DataResponse::GarbageCollected`, while an archival node
+/// still serves the blocks.
+#[test]
+fn test_sync_requests_for_garbage_collected_data() {
+    init_test_logger();
+    run_actix(async {
+        let block_prod_time = 100;
+        let epoch_length = 5;
+        let target_height = epoch_length * (DEFAULT_GC_NUM_EPOCHS_TO_KEEP + 1);
+        let vs = ValidatorSchedule::new().num_shards(2).block_producers_per_epoch(vec![vec![
+            "test1".parse().unwrap(),
+            "test2".parse().unwrap(),
+        ]]);
+        let started = Arc::new(AtomicBool::new(false));
+
+        setup_mock_all_validators(
+            vs,
+            vec![PeerInfo::random(), PeerInfo::random()],
+            true,
+            block_prod_time,
+            false,
+            false,
+            epoch_length,
+            true,
+            vec![false, true], // first validator non-archival, second archival
+            vec![true, true],
+            true,
+            Box::new(
+                move |conns,
+                      _,
+                      msg: &PeerManagerMessageRequest|
+                      -> (PeerManagerMessageResponse, bool) {
+                    if let NetworkRequests::Block { block } = msg.as_network_requests_ref() {
+                        if block.header().height() > target_height
+                            && !started.swap(true, Ordering::SeqCst)
+                        {
+                            let view_client_non_archival = conns[0].1.clone();
+                            let view_client_archival = conns[1].1.clone();
+                            actix::spawn(async move {
+                                let hash_at = |height| {
+                                    let view_client = view_client_archival.clone();
+                                    async move {
+                                        view_client
+                                            .send(
+                                                GetBlock(BlockReference::BlockId(BlockId::Height(
+                                                    height,
+                                                )))
+                                                .with_span_context(),
+                                            )
+                                            .await
+                                            .unwrap()
+                                            .unwrap()
+                                            .header
+                                            .hash
+                                    }
+                                };
+                                let hash = hash_at(1).await;
+                                let response = view_client_non_archival
+                                    .send(BlockRequest(hash).with_span_context())
+                                    .await
+                                    .unwrap();
+                                assert_eq!(
+                                    response,
+                                    Some(DataResponse::GarbageCollected(
+                                        GarbageCollectedData::Block(hash)
+                                    ))
+                                );
+                                let response = view_client_archival
+                                    .send(BlockRequest(hash).with_span_context())
+                                    .await
+                                    .unwrap();
+                                assert!(matches!(response, Some(DataResponse::Data(_))));
+
+                                // The state for a sync hash is the state as of its previous block.
+                                let sync_hash = hash_at(2).await;
+                                let response = view_client_non_archival
+                                    .send(
+                                        StateRequestHeader {
+                                            shard_id: 0,
+                                            sync_hash,
+                                            peer_id: PeerId::random(),
+                                        }
+                                        .with_span_context(),
+                                    )
+                                    .await
+                                    .unwrap();
+                                match response {
+                                    Some(DataResponse::GarbageCollected(data)) => assert_eq!(
+                                        data,
+                                        GarbageCollectedData::StateHeader {
+                                            shard_id: 0,
+                                            sync_hash
+                                        }
+                                    ),
+                                    _ => panic!("Expected the state to be garbage collected"),
+                                }
+                                System::current().stop();
+                            });
+                        }
+                    }
+                    (NetworkResponses::NoResponse.into(), true)
+                },
+            ),
+        );
+
+        near_network::test_utils::wait_or_panic(block_prod_time * target_height * 2 + 2000);
+    })
+}
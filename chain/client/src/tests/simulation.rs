@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use crate::simulation::Simulation;
+use crate::test_utils::TestEnv;
+use near_chain::ChainGenesis;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+
+fn new_simulation(num_nodes: usize, seed: u64) -> Simulation {
+    let builder = TestEnv::builder(ChainGenesis::test())
+        .clients_count(num_nodes)
+        .validator_seats(num_nodes)
+        .enable_doomslug(true);
+    Simulation::new(builder, seed)
+}
+
+/// Returns heights of the blocks on the canonical chain of node `idx`.
+fn canonical_heights(sim: &Simulation, idx: usize) -> Vec<BlockHeight> {
+    let chain = &sim.env.clients[idx].chain;
+    let mut hash = chain.head().unwrap().last_block_hash;
+    let mut heights = vec![];
+    while hash != CryptoHash::default() {
+        let header = chain.get_block_header(&hash).unwrap();
+        heights.push(header.height());
+        hash = *header.prev_hash();
+    }
+    heights
+}
+
+/// Checks that all nodes have a final block past genesis and agree on the
+/// lowest of them.
+fn assert_final_block_agreed(sim: &Simulation) {
+    let final_height = sim
+        .env
+        .clients
+        .iter()
+        .map(|client| {
+            let head = client.chain.head().unwrap();
+            let header = client.chain.get_block_header(&head.last_block_hash).unwrap();
+            client.chain.get_block_header(header.last_final_block()).unwrap().height()
+        })
+        .min()
+        .unwrap();
+    assert!(final_height > 0);
+    let final_hash = sim.env.clients[0].chain.get_block_hash_by_height(final_height).unwrap();
+    for client in sim.env.clients.iter() {
+        assert_eq!(client.chain.get_block_hash_by_height(final_height).unwrap(), final_hash);
+    }
+}
+
+#[test]
+fn test_simulation_reaches_finality() {
+    init_test_logger();
+    let mut sim = new_simulation(4, 0);
+    assert!(sim.run_until(
+        |env| env.clients.iter().all(|client| client.chain.head().unwrap().height >= 10),
+        Duration::from_secs(5),
+    ));
+    assert_final_block_agreed(&sim);
+}
+
+/// With four validators split in halves neither side has 2/3 of stake, so
+/// the chain must stall until the partition heals.
+#[test]
+fn test_simulation_partition_stalls_and_heals() {
+    init_test_logger();
+    let mut sim = new_simulation(4, 0);
+    assert!(sim.run_until(
+        |env| env.clients.iter().all(|client| client.chain.head().unwrap().height >= 3),
+        Duration::from_secs(5),
+    ));
+
+    sim.network().partition(&[&[0, 1], &[2, 3]]);
+    // Let the messages sent before the partition settle.
+    sim.run_for(Duration::from_secs(1));
+    let stalled = sim.head_heights().into_iter().max().unwrap();
+    sim.run_for(Duration::from_secs(1));
+    assert_eq!(sim.head_heights().into_iter().max().unwrap(), stalled);
+
+    sim.network().heal();
+    assert!(sim.run_until(
+        |env| env.clients.iter().all(|client| client.chain.head().unwrap().height > stalled + 5),
+        Duration::from_secs(10),
+    ));
+    assert_final_block_agreed(&sim);
+}
+
+#[test]
+fn test_simulation_is_deterministic() {
+    init_test_logger();
+    let run = || {
+        let mut sim = new_simulation(4, 42);
+        sim.network().set_drop_probability(0.1);
+        sim.network().set_latency(0, 1, Duration::from_millis(15));
+        sim.run_for(Duration::from_secs(1));
+        (sim.head_heights(), canonical_heights(&sim, 0))
+    };
+    let first = run();
+    assert!(first.1.len() > 1);
+    assert_eq!(first, run());
+}
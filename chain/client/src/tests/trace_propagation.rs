@@ -0,0 +1,125 @@
+use crate::adapter::BlockResponse;
+use crate::test_utils::setup_no_network;
+use crate::GetBlockWithMerkleTree;
+use actix::System;
+use near_actix_test_utils::run_actix;
+use near_network::types::PeerInfo;
+use near_o11y::WithSpanContextExt;
+use near_primitives::block::{Block, BlockHeader};
+use near_primitives::merkle::PartialMerkleTree;
+use near_primitives::test_utils::create_test_signer;
+use near_primitives::types::EpochId;
+use near_primitives::utils::to_timestamp;
+use near_primitives::version::PROTOCOL_VERSION;
+use num_rational::Ratio;
+use opentelemetry::sdk::trace::TracerProvider;
+use opentelemetry::trace::{TraceContextExt, TraceId, TracerProvider as _};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::span;
+use tracing_opentelemetry::{OpenTelemetrySpanExt, OtelData};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Records the name and the OpenTelemetry trace of every span exited.
+#[derive(Clone, Default)]
+struct TraceRecorder(Arc<Mutex<Vec<(&'static str, TraceId)>>>);
+
+impl TraceRecorder {
+    fn spans_in_trace(&self, trace_id: TraceId, name: &str) -> usize {
+        self.0.lock().unwrap().iter().filter(|(n, t)| *n == name && *t == trace_id).count()
+    }
+}
+
+impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> Layer<S> for TraceRecorder {
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let extensions = span.extensions();
+        let data = match extensions.get::<OtelData>() {
+            Some(data) => data,
+            None => return,
+        };
+        // Same as the OpenTelemetry layer: the parent's trace if there is one.
+        let trace_id = if data.parent_cx.has_active_span() {
+            data.parent_cx.span().span_context().trace_id()
+        } else {
+            data.builder.trace_id.unwrap()
+        };
+        self.0.lock().unwrap().push((span.name(), trace_id));
+    }
+}
+
+/// A block received from the network is processed by the client actor, with
+/// its chunks applied on another thread, in the trace in which it was received.
+#[test]
+fn test_block_processing_continues_network_trace() {
+    let recorder = TraceRecorder::default();
+    let provider = TracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+        .with(recorder.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    run_actix(async move {
+        let (client, view_client) =
+            setup_no_network(vec!["test".parse().unwrap()], "other".parse().unwrap(), true, false);
+        actix::spawn(async move {
+            let signer = create_test_signer("test");
+            let (block, block_merkle_tree) = view_client
+                .send(GetBlockWithMerkleTree::latest().with_span_context())
+                .await
+                .unwrap()
+                .unwrap();
+            let mut block_merkle_tree = PartialMerkleTree::clone(&block_merkle_tree);
+            let header: BlockHeader = block.header.clone().into();
+            block_merkle_tree.insert(*header.hash());
+            let mut next_block = Block::produce(
+                PROTOCOL_VERSION,
+                PROTOCOL_VERSION,
+                &header,
+                block.header.height + 1,
+                header.block_ordinal() + 1,
+                block.chunks.into_iter().map(|c| c.into()).collect(),
+                EpochId(block.header.next_epoch_id),
+                EpochId(block.header.hash),
+                None,
+                vec![],
+                Ratio::from_integer(0),
+                0,
+                100,
+                None,
+                vec![],
+                vec![],
+                &signer,
+                block.header.next_bp_hash,
+                block_merkle_tree.root(),
+                None,
+            );
+            next_block.mut_header().get_mut().inner_lite.timestamp =
+                to_timestamp(next_block.header().timestamp() + chrono::Duration::seconds(1));
+            next_block.mut_header().resign(&signer);
+
+            // What the peer actor does when it receives a block.
+            let span = tracing::debug_span!("receive_block");
+            let trace_id = span.context().span().span_context().trace_id();
+            let msg = span.in_scope(|| {
+                BlockResponse {
+                    block: next_block,
+                    peer_id: PeerInfo::random().id,
+                    was_requested: false,
+                }
+                .with_span_context()
+            });
+            client.send(msg).await.unwrap();
+            while recorder.spans_in_trace(trace_id, "postprocess_block") == 0 {
+                actix::clock::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(recorder.spans_in_trace(trace_id, "do_apply_chunks"), 1);
+            // Both the handler of the block and the one of the message sent
+            // once the chunks are applied.
+            assert!(recorder.spans_in_trace(trace_id, "handle") >= 2);
+            System::current().stop();
+        });
+        near_network::test_utils::wait_or_panic(5000);
+    });
+}
@@ -0,0 +1,20 @@
+use crate::client::select_tx_forwarding_targets;
+use near_primitives::types::AccountId;
+use std::collections::HashSet;
+
+fn accounts(names: &[&str]) -> Vec<AccountId> {
+    names.iter().map(|n| n.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_select_tx_forwarding_targets() {
+    let producers = accounts(&["a", "b", "a", "c", "d", "e"]);
+    let known: HashSet<_> = accounts(&["a", "c", "d", "e"]).into_iter().collect();
+    // Unknown producers are skipped, repeated ones are taken once, in the order of heights.
+    assert_eq!(
+        select_tx_forwarding_targets(producers.clone(), &known, 3),
+        accounts(&["a", "c", "d"])
+    );
+    assert_eq!(select_tx_forwarding_targets(producers.clone(), &known, 1), accounts(&["a"]));
+    assert!(select_tx_forwarding_targets(producers, &HashSet::new(), 3).is_empty());
+}
@@ -0,0 +1,63 @@
+use crate::test_utils::setup_no_network;
+use actix::System;
+use futures::{future, FutureExt};
+use near_actix_test_utils::run_actix;
+use near_client_primitives::types::GetValidatorSchedule;
+use near_primitives::views::ChunkProductionSlotView;
+
+use near_o11y::testonly::init_test_logger;
+use near_o11y::WithSpanContextExt;
+
+/// get the production schedule of a validator from view client
+#[test]
+fn test_get_validator_schedule() {
+    init_test_logger();
+    run_actix(async {
+        let (_, view_client) =
+            setup_no_network(vec!["test".parse().unwrap()], "other".parse().unwrap(), true, true);
+        let actor = view_client.send(
+            GetValidatorSchedule { account_id: Some("test".parse().unwrap()) }.with_span_context(),
+        );
+
+        // `test` produces the blocks and the chunks at even heights, see
+        // `test_get_maintenance_windows_for_validator`.
+        let actor = actor.then(|res| {
+            let schedule = res.unwrap().unwrap();
+            assert_eq!(schedule.current_epoch.epoch_start_height, 0);
+            assert_eq!(schedule.current_epoch.block_production, vec![2, 4, 6, 8]);
+            assert_eq!(
+                schedule.current_epoch.chunk_production,
+                [2, 4, 6, 8]
+                    .into_iter()
+                    .map(|height| ChunkProductionSlotView { height, shard_id: 0 })
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(schedule.next_epoch.epoch_start_height, 10);
+            System::current().stop();
+            future::ready(())
+        });
+        actix::spawn(actor);
+    });
+}
+
+#[test]
+fn test_get_validator_schedule_for_not_validator() {
+    init_test_logger();
+    run_actix(async {
+        let (_, view_client) =
+            setup_no_network(vec!["test".parse().unwrap()], "other".parse().unwrap(), true, true);
+        let actor = view_client.send(
+            GetValidatorSchedule { account_id: Some("alice".parse().unwrap()) }.with_span_context(),
+        );
+        let actor = actor.then(|res| {
+            let schedule = res.unwrap().unwrap();
+            assert!(schedule.current_epoch.block_production.is_empty());
+            assert!(schedule.current_epoch.chunk_production.is_empty());
+            assert!(schedule.next_epoch.block_production.is_empty());
+            assert!(schedule.next_epoch.chunk_production.is_empty());
+            System::current().stop();
+            future::ready(())
+        });
+        actix::spawn(actor);
+    });
+}
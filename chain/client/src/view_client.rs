@@ -2,38 +2,49 @@
 //! Useful for querying from RPC.
 
 use actix::{Actor, Addr, Handler, SyncArbiter, SyncContext};
-use near_chain::types::Tip;
+use borsh::BorshSerialize;
+use near_chain::types::{SimulationLimits, Tip};
 use near_primitives::receipt::Receipt;
 use near_primitives::time::Clock;
 use near_store::{DBCol, COLD_HEAD_KEY, FINAL_HEAD_KEY, HEAD_KEY};
 use std::cmp::Ordering;
 use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
+use std::ops::Range;
+use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 use tracing::{debug, error, info, trace, warn};
 
+use near_chain::state_parts_cache::StatePartsCache;
 use near_chain::{
     get_epoch_block_producers_view, Chain, ChainGenesis, ChainStoreAccess, DoomslugThresholdMode,
     RuntimeWithEpochManagerAdapter,
 };
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
 use near_client_primitives::types::{
-    Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofError, GetBlockProofResponse,
-    GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome, GetExecutionOutcomeError,
-    GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError, GetMaintenanceWindows,
-    GetMaintenanceWindowsError, GetNextLightClientBlockError, GetProtocolConfig,
-    GetProtocolConfigError, GetReceipt, GetReceiptError, GetSplitStorageInfo,
-    GetSplitStorageInfoError, GetSplitStorageInfoResult, GetStateChangesError,
+    EpochLightClientBlock, Error, GetAccountOutcomes, GetAccountOutcomesError, GetBlock,
+    GetBlockByTimestamp, GetBlockError, GetBlockProof, GetBlockProofError, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunkError, GetEpochLightClientBlock, GetEpochProtocolConfig,
+    GetExecutionOutcome, GetExecutionOutcomeError, GetExecutionOutcomesForBlock, GetGasPrice,
+    GetGasPriceError, GetGasPriceEstimate, GetMaintenanceWindows, GetMaintenanceWindowsError,
+    GetNextLightClientBlockError, GetProtocolConfig, GetProtocolConfigError,
+    GetProtocolVersionVoting, GetProtocolVersionVotingError, GetReceipt, GetReceiptError,
+    GetSplitStorageInfo, GetSplitStorageInfoError, GetSplitStorageInfoResult, GetStateChangesError,
     GetStateChangesWithCauseInBlock, GetStateChangesWithCauseInBlockForTrackedShards,
-    GetValidatorInfoError, Query, QueryError, TxStatus, TxStatusError,
+    GetStateSnapshotPart, GetStateSnapshotPartError, GetValidatorInfoError, GetValidatorProjection,
+    GetValidatorProjectionError, GetValidatorSchedule, GetValidatorScheduleError, Query,
+    QueryError, SimulateTransaction, SimulateTransactionError, StateSnapshotPart, TxStatus,
+    TxStatusError, TxStatusExtended,
 };
+use near_network::client::DataResponse;
 #[cfg(feature = "test_features")]
 use near_network::types::NetworkAdversarialMessage;
 use near_network::types::{
-    NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest, ReasonForBan,
-    StateResponseInfo, StateResponseInfoV1, StateResponseInfoV2,
+    GarbageCollectedData, NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest,
+    ReasonForBan, StateDeltaResponseInfo, StateResponseInfo, StateResponseInfoV1,
+    StateResponseInfoV2,
 };
 use near_o11y::{handler_debug_span, OpenTelemetrySpanExt, WithSpanContext, WithSpanContextExt};
 use near_performance_metrics_macros::perf;
@@ -41,28 +52,34 @@ use near_primitives::block::{Block, BlockHeader};
 use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, PartialMerkleTree};
-use near_primitives::network::AnnounceAccount;
+use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::ShardChunk;
 use near_primitives::syncing::{
-    ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
-    ShardStateSyncResponseV2,
+    get_num_state_parts, ShardStateSyncResponse, ShardStateSyncResponseHeader,
+    ShardStateSyncResponseV1, ShardStateSyncResponseV2,
 };
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockId, BlockReference, EpochReference, Finality, MaybeBlockId,
-    ShardId, SyncCheckpoint, TransactionOrReceiptId, ValidatorInfoIdentifier,
+    AccountId, BlockHeight, BlockId, BlockReference, BlockTimestampMatch, EpochId, EpochReference,
+    Finality, Gas, MaybeBlockId, ShardId, StateChanges, StateChangesExt, StateChangesRequest,
+    SyncCheckpoint, TransactionOrReceiptId, ValidatorInfoIdentifier,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockView,
-    MaintenanceWindowsView, QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView,
-    StateChangesView,
+    AccountOutcomeIdsView, BlockView, ChunkProductionSlotView, ChunkView, EpochValidatorInfo,
+    ExecutionOutcomeProofView, ExecutionOutcomeWithIdView, FinalExecutionOutcomeView,
+    FinalExecutionOutcomeViewEnum, FinalExecutionOutcomeWithProofsView, GasPriceEstimateView,
+    GasPricePercentileView, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
+    MaintenanceWindowsView, ProtocolVersionVotingView, QueryRequest, QueryResponse, ReceiptView,
+    StateChangesKindsView, StateChangesView, TransactionSimulationView, ValidatorEpochScheduleView,
+    ValidatorProjectionView, ValidatorScheduleView,
 };
 
 use crate::adapter::{
-    AnnounceAccountRequest, BlockHeadersRequest, BlockRequest, StateRequestHeader,
-    StateRequestPart, StateResponse, TxStatusRequest, TxStatusResponse,
+    AnnounceAccountRequest, BlockHeadersAtHeightsRequest, BlockHeadersRequest, BlockRequest,
+    StateDeltaResponse, StateRequestDelta, StateRequestHeader, StateRequestPart, StateResponse,
+    TxStatusRequest, TxStatusResponse,
 };
+use crate::gas_price_estimate::GasPriceWindow;
 use crate::{
     metrics, sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
     GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
@@ -75,6 +92,9 @@ const REQUEST_WAIT_TIME: u64 = 1000;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
+/// Number of heights kept in `BlockTimestampIndex`.
+const BLOCK_TIMESTAMP_INDEX_SIZE: usize = 10_000;
+
 /// Request and response manager across all instances of ViewClientActor.
 pub struct ViewClientRequestManager {
     /// Transaction query that needs to be forwarded to other shards
@@ -89,6 +109,277 @@ pub struct ViewClientRequestManager {
     pub receipt_outcome_requests: lru::LruCache<CryptoHash, Instant>,
 }
 
+/// Limits on the state sync requests of peers served across all instances of
+/// ViewClientActor, see `ClientConfig::state_request_limit`.
+pub struct StateRequestThrottle {
+    period: Duration,
+    limit: usize,
+    limit_per_peer: usize,
+    max_in_progress: usize,
+    /// Requests served in the last `period`, oldest first, with the peers
+    /// which made them.
+    served: VecDeque<(Instant, Option<PeerId>)>,
+    /// Number of requests in `served` by peer.
+    served_per_peer: HashMap<PeerId, usize>,
+    in_progress: usize,
+}
+
+impl StateRequestThrottle {
+    pub fn new(
+        period: Duration,
+        limit: usize,
+        limit_per_peer: usize,
+        max_in_progress: usize,
+    ) -> Self {
+        Self {
+            period,
+            limit,
+            limit_per_peer,
+            max_in_progress,
+            served: VecDeque::new(),
+            served_per_peer: HashMap::new(),
+            in_progress: 0,
+        }
+    }
+
+    pub fn from_config(config: &ClientConfig) -> Self {
+        Self::new(
+            config.view_client_throttle_period,
+            config.state_request_limit,
+            config.state_request_limit_per_peer,
+            config.state_request_max_in_progress,
+        )
+    }
+
+    /// Starts serving a request of `peer_id` received at `now`, unless it
+    /// would exceed a limit, whose name is returned then.  Requests which
+    /// don't come from a peer, such as state handover ones, are passed with
+    /// no `peer_id` and count only towards the total limits.  Every
+    /// successful call must be followed by a call to `finish`.
+    pub(crate) fn start(
+        &mut self,
+        now: Instant,
+        peer_id: Option<&PeerId>,
+    ) -> Result<(), &'static str> {
+        // Assume that time is linear. While in different threads there might be some small differences,
+        // it should not matter in practice.
+        while let Some((time, _)) = self.served.front() {
+            if now.saturating_duration_since(*time) <= self.period {
+                break;
+            }
+            let (_, peer_id) = self.served.pop_front().unwrap();
+            let peer_id = match peer_id {
+                Some(peer_id) => peer_id,
+                None => continue,
+            };
+            if let std::collections::hash_map::Entry::Occupied(mut entry) =
+                self.served_per_peer.entry(peer_id)
+            {
+                *entry.get_mut() -= 1;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+        if self.served.len() >= self.limit {
+            return Err("total");
+        }
+        if peer_id.map_or(false, |peer_id| {
+            self.served_per_peer.get(peer_id).map_or(false, |&count| count >= self.limit_per_peer)
+        }) {
+            return Err("peer");
+        }
+        if self.in_progress >= self.max_in_progress {
+            return Err("in_progress");
+        }
+        self.served.push_back((now, peer_id.cloned()));
+        if let Some(peer_id) = peer_id {
+            *self.served_per_peer.entry(peer_id.clone()).or_default() += 1;
+        }
+        self.in_progress += 1;
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.in_progress -= 1;
+    }
+}
+
+/// Kind of sync data served to peers, see `SyncServingBudget`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SyncServingKind {
+    Block,
+    Headers,
+    State,
+}
+
+impl SyncServingKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Headers => "headers",
+            Self::State => "state",
+        }
+    }
+}
+
+/// Limits on the bytes sent in responses to sync requests of peers across all
+/// instances of ViewClientActor, see `ClientConfig::sync_serving_bandwidth_limit`.
+/// A single budget is shared by the view client and the view call pool.
+pub struct SyncServingBudget {
+    period: Duration,
+    /// Maximum bytes of all the responses per `period`.
+    total_budget: Option<u64>,
+    /// Maximum bytes of the state sync responses per `period`.
+    state_budget: Option<u64>,
+    /// Responses sent in the last `period`, oldest first.
+    served: VecDeque<(Instant, SyncServingKind, u64)>,
+    total_bytes: u64,
+    state_bytes: u64,
+}
+
+impl SyncServingBudget {
+    /// Limits are in bytes per second.
+    pub fn new(period: Duration, total_limit: Option<u64>, state_limit: Option<u64>) -> Self {
+        let budget = |limit: u64| (limit as f64 * period.as_secs_f64()) as u64;
+        Self {
+            period,
+            total_budget: total_limit.map(budget),
+            state_budget: state_limit.map(budget),
+            served: VecDeque::new(),
+            total_bytes: 0,
+            state_bytes: 0,
+        }
+    }
+
+    pub fn from_config(config: &ClientConfig) -> Self {
+        Self::new(
+            config.view_client_throttle_period,
+            config.sync_serving_bandwidth_limit,
+            config.state_serving_bandwidth_limit,
+        )
+    }
+
+    /// Checks whether a request of `kind` received at `now` can be served,
+    /// otherwise returns the name of the exhausted budget.  Responses may
+    /// overshoot the budget, since their size is known only once built.
+    pub(crate) fn check(
+        &mut self,
+        now: Instant,
+        kind: SyncServingKind,
+    ) -> Result<(), &'static str> {
+        while let Some((time, _, _)) = self.served.front() {
+            if now.saturating_duration_since(*time) <= self.period {
+                break;
+            }
+            let (_, kind, bytes) = self.served.pop_front().unwrap();
+            self.total_bytes -= bytes;
+            if kind == SyncServingKind::State {
+                self.state_bytes -= bytes;
+            }
+        }
+        if self.total_budget.map_or(false, |budget| self.total_bytes >= budget) {
+            return Err("total");
+        }
+        if kind == SyncServingKind::State
+            && self.state_budget.map_or(false, |budget| self.state_bytes >= budget)
+        {
+            return Err("state");
+        }
+        Ok(())
+    }
+
+    /// Whether any of the budgets is set.  Responses don't need to be
+    /// recorded if not.
+    pub(crate) fn is_limited(&self) -> bool {
+        self.total_budget.is_some() || self.state_budget.is_some()
+    }
+
+    /// Records a response of `bytes` to a request of `kind` sent at `now`.
+    pub(crate) fn record(&mut self, now: Instant, kind: SyncServingKind, bytes: u64) {
+        self.served.push_back((now, kind, bytes));
+        self.total_bytes += bytes;
+        if kind == SyncServingKind::State {
+            self.state_bytes += bytes;
+        }
+    }
+}
+
+/// State sync request being served, see `ViewClientActor::check_state_sync_request`.
+struct StateRequestInProgress(Arc<Mutex<StateRequestThrottle>>);
+
+impl Drop for StateRequestInProgress {
+    fn drop(&mut self) {
+        self.0.lock().expect(POISONED_LOCK_ERR).finish();
+        metrics::STATE_REQUESTS_IN_PROGRESS.dec();
+    }
+}
+
+/// Block hashes and timestamps of final blocks by height, shared between the
+/// ClientActor and all instances of ViewClientActor.  The client adds blocks
+/// as they become final, so recent heights are always indexed.  Heights
+/// visited by a search which aren't indexed yet, e.g. ones finalized before
+/// the node started, are read from the chain and added by the view client.
+/// Heights without a block are indexed as `None`.
+pub struct BlockTimestampIndex {
+    blocks: lru::LruCache<BlockHeight, Option<(CryptoHash, u64)>>,
+}
+
+impl BlockTimestampIndex {
+    pub fn new() -> Self {
+        Self { blocks: lru::LruCache::new(BLOCK_TIMESTAMP_INDEX_SIZE) }
+    }
+
+    /// Adds a block which became final.
+    pub(crate) fn add_final_block(
+        &mut self,
+        height: BlockHeight,
+        hash: CryptoHash,
+        timestamp: u64,
+    ) {
+        self.blocks.put(height, Some((hash, timestamp)));
+    }
+}
+
+/// Returns the first block, as (height, hash, timestamp), with a timestamp not
+/// before `timestamp` at a height in `[lo, hi]`.  `block_at_or_after(height)`
+/// returns the first block at or after `height`, timestamps of the blocks are
+/// expected to grow with their height.
+pub(crate) fn find_first_block_not_before<E>(
+    mut lo: BlockHeight,
+    mut hi: BlockHeight,
+    timestamp: u64,
+    mut block_at_or_after: impl FnMut(BlockHeight) -> Result<Option<(BlockHeight, CryptoHash, u64)>, E>,
+) -> Result<Option<(BlockHeight, CryptoHash, u64)>, E> {
+    let mut result = None;
+    while lo <= hi {
+        let mid = lo + (hi - lo) / 2;
+        match block_at_or_after(mid)? {
+            Some(block) if block.2 >= timestamp => {
+                result = Some(block);
+                if mid == 0 {
+                    break;
+                }
+                hi = mid - 1;
+            }
+            Some((height, _, _)) => lo = height + 1,
+            None => break,
+        }
+    }
+    Ok(result)
+}
+
+/// Whether the block at `prev_timestamp` is at least as close to `timestamp`
+/// as the following block at `block_timestamp`.  Timestamps come from block
+/// headers, so don't assume that `timestamp` lies between them.
+pub(crate) fn is_prev_block_closer(
+    timestamp: u64,
+    prev_timestamp: u64,
+    block_timestamp: u64,
+) -> bool {
+    timestamp.abs_diff(prev_timestamp) <= block_timestamp.abs_diff(timestamp)
+}
+
 /// View client provides currently committed (to the storage) view of the current chain and state.
 pub struct ViewClientActor {
     pub adv: crate::adversarial::Controls,
@@ -100,7 +391,10 @@ pub struct ViewClientActor {
     network_adapter: Arc<dyn PeerManagerAdapter>,
     pub config: ClientConfig,
     request_manager: Arc<RwLock<ViewClientRequestManager>>,
-    state_request_cache: Arc<Mutex<VecDeque<Instant>>>,
+    state_request_throttle: Arc<Mutex<StateRequestThrottle>>,
+    sync_serving_budget: Arc<Mutex<SyncServingBudget>>,
+    block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+    gas_price_window: Arc<Mutex<GasPriceWindow>>,
 }
 
 impl ViewClientRequestManager {
@@ -116,9 +410,6 @@ impl ViewClientRequestManager {
 }
 
 impl ViewClientActor {
-    /// Maximum number of state requests allowed per `view_client_throttle_period`.
-    const MAX_NUM_STATE_REQUESTS: usize = 30;
-
     pub fn new(
         validator_account_id: Option<AccountId>,
         chain_genesis: &ChainGenesis,
@@ -126,15 +417,21 @@ impl ViewClientActor {
         network_adapter: Arc<dyn PeerManagerAdapter>,
         config: ClientConfig,
         request_manager: Arc<RwLock<ViewClientRequestManager>>,
+        state_request_throttle: Arc<Mutex<StateRequestThrottle>>,
+        sync_serving_budget: Arc<Mutex<SyncServingBudget>>,
+        block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+        gas_price_window: Arc<Mutex<GasPriceWindow>>,
+        state_parts_cache: Arc<StatePartsCache>,
         adv: crate::adversarial::Controls,
     ) -> Result<Self, Error> {
         // TODO: should we create shared ChainStore that is passed to both Client and ViewClient?
-        let chain = Chain::new_for_view_client(
+        let mut chain = Chain::new_for_view_client(
             runtime_adapter.clone(),
             chain_genesis,
             DoomslugThresholdMode::TwoThirds,
             config.save_trie_changes,
         )?;
+        chain.set_state_parts_cache(state_parts_cache);
         Ok(ViewClientActor {
             adv,
             validator_account_id,
@@ -143,7 +440,10 @@ impl ViewClientActor {
             network_adapter,
             config,
             request_manager,
-            state_request_cache: Arc::new(Mutex::new(VecDeque::default())),
+            state_request_throttle,
+            sync_serving_budget,
+            block_timestamp_index,
+            gas_price_window,
         })
     }
 
@@ -301,7 +601,154 @@ impl ViewClientActor {
         Ok(windows)
     }
 
+    fn get_validator_schedule(
+        &self,
+        account_id: Option<AccountId>,
+    ) -> Result<ValidatorScheduleView, GetValidatorScheduleError> {
+        let account_id = account_id
+            .or_else(|| self.validator_account_id.clone())
+            .ok_or(GetValidatorScheduleError::NoAccountId)?;
+        let head = self.chain.head()?;
+        let epoch_start_height =
+            self.runtime_adapter.get_epoch_start_height(&head.last_block_hash)?;
+        let next_epoch_start_height = epoch_start_height
+            + self.runtime_adapter.get_epoch_config(&head.epoch_id)?.epoch_length;
+        let next_epoch_end_height = next_epoch_start_height
+            + self.runtime_adapter.get_epoch_config(&head.next_epoch_id)?.epoch_length;
+        let current_epoch = self.get_epoch_schedule(
+            &account_id,
+            &head.epoch_id,
+            epoch_start_height,
+            head.height + 1..next_epoch_start_height,
+        )?;
+        let next_epoch = self.get_epoch_schedule(
+            &account_id,
+            &head.next_epoch_id,
+            next_epoch_start_height,
+            next_epoch_start_height..next_epoch_end_height,
+        )?;
+        Ok(ValidatorScheduleView { account_id, current_epoch, next_epoch })
+    }
+
+    fn get_validator_projection(
+        &self,
+        account_id: Option<AccountId>,
+    ) -> Result<ValidatorProjectionView, GetValidatorProjectionError> {
+        let account_id = account_id
+            .or_else(|| self.validator_account_id.clone())
+            .ok_or(GetValidatorProjectionError::NoAccountId)?;
+        let head = self.chain.head()?;
+        Ok(self.runtime_adapter.get_validator_projection(&head.last_block_hash, &account_id)?)
+    }
+
+    fn get_protocol_version_voting(
+        &self,
+    ) -> Result<ProtocolVersionVotingView, GetProtocolVersionVotingError> {
+        let head = self.chain.head()?;
+        Ok(self.runtime_adapter.get_protocol_version_voting(&head.last_block_hash)?)
+    }
+
+    /// Returns the block and chunk production slots of `account_id` at the
+    /// given heights of an epoch.
+    fn get_epoch_schedule(
+        &self,
+        account_id: &AccountId,
+        epoch_id: &EpochId,
+        epoch_start_height: BlockHeight,
+        heights: Range<BlockHeight>,
+    ) -> Result<ValidatorEpochScheduleView, near_chain::Error> {
+        let epoch_info = self.runtime_adapter.get_epoch_info(epoch_id)?;
+        let num_shards = self.runtime_adapter.num_shards(epoch_id)?;
+        let mut block_production = vec![];
+        let mut chunk_production = vec![];
+        if let Some(&validator_id) = epoch_info.get_validator_id(account_id) {
+            for height in heights {
+                if epoch_info.sample_block_producer(height) == validator_id {
+                    block_production.push(height);
+                }
+                for shard_id in 0..num_shards {
+                    if epoch_info.sample_chunk_producer(height, shard_id) == validator_id {
+                        chunk_production.push(ChunkProductionSlotView { height, shard_id });
+                    }
+                }
+            }
+        }
+        Ok(ValidatorEpochScheduleView {
+            epoch_id: epoch_id.0,
+            epoch_start_height,
+            block_production,
+            chunk_production,
+        })
+    }
+
+    fn simulate_transaction(
+        &self,
+        msg: SimulateTransaction,
+    ) -> Result<TransactionSimulationView, SimulateTransactionError> {
+        let header = match self.get_block_header_by_reference(&msg.block_reference)? {
+            None => return Err(SimulateTransactionError::NotSyncedYet),
+            Some(header) => header,
+        };
+        let epoch_id = header.epoch_id();
+        let mut state_roots = HashMap::new();
+        for shard_id in 0..self.runtime_adapter.num_shards(epoch_id)? {
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, epoch_id)?;
+            match self.chain.get_chunk_extra(header.hash(), &shard_uid) {
+                Ok(chunk_extra) => {
+                    state_roots.insert(shard_id, *chunk_extra.state_root());
+                }
+                // The node doesn't track the shard.
+                Err(near_chain::near_chain_primitives::Error::DBNotFoundErr(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let signer_shard_id =
+            self.runtime_adapter.account_id_to_shard_id(&msg.transaction.signer_id, epoch_id)?;
+        if !state_roots.contains_key(&signer_shard_id) {
+            return Err(SimulateTransactionError::UnavailableShard {
+                requested_shard_id: signer_shard_id,
+            });
+        }
+
+        // The simulation stops once the deadline of the request or the view
+        // call timeout passes, whichever comes first.
+        let timeout_deadline = Instant::now() + self.config.view_call_timeout;
+        let limits = SimulationLimits {
+            deadline: Some(msg.deadline.map_or(timeout_deadline, |d| d.min(timeout_deadline))),
+            ..Default::default()
+        };
+        let result = self
+            .runtime_adapter
+            .simulate_transaction(&header, &state_roots, msg.transaction, msg.signature, &limits)?
+            .map_err(|error| SimulateTransactionError::InvalidTransaction { error })?;
+        let mut outcomes = result.outcomes.into_iter().map(|outcome| ExecutionOutcomeWithIdView {
+            proof: vec![],
+            block_hash: CryptoHash::default(),
+            id: outcome.id,
+            outcome: outcome.outcome.into(),
+        });
+        let transaction_outcome =
+            outcomes.next().ok_or_else(|| SimulateTransactionError::Unreachable {
+                error_message: "simulated transaction has no outcome".to_string(),
+            })?;
+        let receipts_outcome: Vec<_> = outcomes.collect();
+        let gas_burnt = transaction_outcome.outcome.gas_burnt
+            + receipts_outcome.iter().map(|outcome| outcome.outcome.gas_burnt).sum::<Gas>();
+        let state_changes = StateChanges::from_changes(result.state_changes.into_iter().map(Ok))
+            .map_err(|err| SimulateTransactionError::IOError { error_message: err.to_string() })?;
+        Ok(TransactionSimulationView {
+            transaction_outcome,
+            receipts_outcome,
+            gas_burnt,
+            state_changes: state_changes.into_iter().map(Into::into).collect(),
+            pending_receipts: result.pending_receipts.into_iter().map(Into::into).collect(),
+        })
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<QueryResponse, QueryError> {
+        if msg.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Err(QueryError::TimeoutError);
+        }
         let header = self.get_block_header_by_reference(&msg.block_reference);
         let header = match header {
             Ok(Some(header)) => Ok(header),
@@ -366,6 +813,7 @@ impl ViewClientActor {
             header.hash(),
             header.epoch_id(),
             &msg.request,
+            msg.deadline,
         ) {
             Ok(query_response) => Ok(query_response),
             Err(query_error) => Err(match query_error {
@@ -411,6 +859,9 @@ impl ViewClientActor {
                     block_height,
                     block_hash,
                 },
+                near_chain::near_chain_primitives::error::QueryError::DeadlineExceeded {
+                    ..
+                } => QueryError::TimeoutError,
             }),
         }
     }
@@ -488,6 +939,127 @@ impl ViewClientActor {
         }
     }
 
+    /// Outcome of a transaction or receipt with the proof of its inclusion in
+    /// the first block with a new chunk of its shard after the outcome.
+    fn get_execution_outcome_proof(
+        &self,
+        id: TransactionOrReceiptId,
+    ) -> Result<GetExecutionOutcomeResponse, GetExecutionOutcomeError> {
+        let (id, account_id) = match id {
+            TransactionOrReceiptId::Transaction { transaction_hash, sender_id } => {
+                (transaction_hash, sender_id)
+            }
+            TransactionOrReceiptId::Receipt { receipt_id, receiver_id } => {
+                (receipt_id, receiver_id)
+            }
+        };
+        match self.chain.get_execution_outcome(&id) {
+            Ok(outcome) => {
+                let mut outcome_proof = outcome;
+                let epoch_id =
+                    self.chain.get_block(&outcome_proof.block_hash)?.header().epoch_id().clone();
+                let target_shard_id =
+                    self.runtime_adapter.account_id_to_shard_id(&account_id, &epoch_id)?;
+                let res = self.chain.get_next_block_hash_with_new_chunk(
+                    &outcome_proof.block_hash,
+                    target_shard_id,
+                )?;
+                if let Some((h, target_shard_id)) = res {
+                    outcome_proof.block_hash = h;
+                    // Here we assume the number of shards is small so this reconstruction
+                    // should be fast
+                    let outcome_roots = self
+                        .chain
+                        .get_block(&h)?
+                        .chunks()
+                        .iter()
+                        .map(|header| header.outcome_root())
+                        .collect::<Vec<_>>();
+                    if target_shard_id >= (outcome_roots.len() as u64) {
+                        return Err(GetExecutionOutcomeError::InconsistentState {
+                            number_or_shards: outcome_roots.len(),
+                            execution_outcome_shard_id: target_shard_id,
+                        });
+                    }
+                    Ok(GetExecutionOutcomeResponse {
+                        outcome_proof: outcome_proof.into(),
+                        outcome_root_proof: merklize(&outcome_roots).1[target_shard_id as usize]
+                            .clone(),
+                    })
+                } else {
+                    Err(GetExecutionOutcomeError::NotConfirmed { transaction_or_receipt_id: id })
+                }
+            }
+            Err(near_chain::Error::DBNotFoundErr(_)) => {
+                let head = self.chain.head()?;
+                let target_shard_id =
+                    self.runtime_adapter.account_id_to_shard_id(&account_id, &head.epoch_id)?;
+                if self.runtime_adapter.cares_about_shard(
+                    self.validator_account_id.as_ref(),
+                    &head.last_block_hash,
+                    target_shard_id,
+                    true,
+                ) {
+                    Err(GetExecutionOutcomeError::UnknownTransactionOrReceipt {
+                        transaction_or_receipt_id: id,
+                    })
+                } else {
+                    Err(GetExecutionOutcomeError::UnavailableShard {
+                        transaction_or_receipt_id: id,
+                        shard_id: target_shard_id,
+                    })
+                }
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn get_block_proof(
+        &self,
+        block_hash: &CryptoHash,
+        head_block_hash: &CryptoHash,
+    ) -> Result<GetBlockProofResponse, GetBlockProofError> {
+        let block_header = self.chain.get_block_header(block_hash)?;
+        let head_block_header = self.chain.get_block_header(head_block_hash)?;
+        self.chain.check_blocks_final_and_canonical(&[&block_header, &head_block_header])?;
+        let block_header_lite = block_header.into();
+        let proof = self.chain.get_block_proof(block_hash, head_block_hash)?;
+        Ok(GetBlockProofResponse { block_header_lite, proof })
+    }
+
+    /// Proof of the outcome of a transaction or receipt against
+    /// `light_client_head`, `None` if it's not available (yet).
+    fn get_light_client_outcome_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: &CryptoHash,
+    ) -> Result<Option<ExecutionOutcomeProofView>, TxStatusError> {
+        let outcome = match self.get_execution_outcome_proof(id) {
+            Ok(outcome) => outcome,
+            // The outcome isn't in a block with a new chunk yet, or it isn't
+            // stored on this node.
+            Err(GetExecutionOutcomeError::NotConfirmed { .. })
+            | Err(GetExecutionOutcomeError::UnknownTransactionOrReceipt { .. })
+            | Err(GetExecutionOutcomeError::UnavailableShard { .. })
+            | Err(GetExecutionOutcomeError::UnknownBlock { .. }) => return Ok(None),
+            Err(err) => return Err(TxStatusError::InternalError(err.to_string())),
+        };
+        let block = match self.get_block_proof(&outcome.outcome_proof.block_hash, light_client_head)
+        {
+            Ok(block) => block,
+            // The block is unknown, not final yet or ahead of `light_client_head`.
+            Err(GetBlockProofError::UnknownBlock { .. })
+            | Err(GetBlockProofError::InternalError { .. }) => return Ok(None),
+            Err(err) => return Err(TxStatusError::InternalError(err.to_string())),
+        };
+        Ok(Some(ExecutionOutcomeProofView {
+            outcome_proof: outcome.outcome_proof,
+            outcome_root_proof: outcome.outcome_root_proof,
+            block_header_lite: block.block_header_lite,
+            block_proof: block.proof,
+        }))
+    }
+
     fn retrieve_headers(
         &mut self,
         hashes: Vec<CryptoHash>,
@@ -495,6 +1067,14 @@ impl ViewClientActor {
         self.chain.retrieve_headers(hashes, sync::header::MAX_BLOCK_HEADERS, None)
     }
 
+    fn retrieve_headers_at_heights(
+        &mut self,
+        heights: Vec<BlockHeight>,
+    ) -> Result<Vec<BlockHeader>, near_chain::Error> {
+        self.chain
+            .retrieve_headers_at_heights(&heights, sync::header::MAX_BLOCK_HEADER_HASHES as u64)
+    }
+
     fn check_signature_account_announce(
         &self,
         announce_account: &AnnounceAccount,
@@ -513,20 +1093,164 @@ impl ViewClientActor {
             .map_err(|e| e.into())
     }
 
-    fn check_state_sync_request(&self) -> bool {
-        let mut cache = self.state_request_cache.lock().expect(POISONED_LOCK_ERR);
-        let now = Clock::instant();
-        let cutoff = now - self.config.view_client_throttle_period;
-        // Assume that time is linear. While in different threads there might be some small differences,
-        // it should not matter in practice.
-        while !cache.is_empty() && *cache.front().unwrap() < cutoff {
-            cache.pop_front();
+    /// Returns `None` if a state request of `peer_id`, or a state handover
+    /// request if `None`, should be dropped because of a limit.  Otherwise the
+    /// request counts as in progress until the returned value is dropped.
+    fn check_state_sync_request(&self, peer_id: Option<&PeerId>) -> Option<StateRequestInProgress> {
+        let result = self
+            .state_request_throttle
+            .lock()
+            .expect(POISONED_LOCK_ERR)
+            .start(Clock::instant(), peer_id);
+        match result {
+            Ok(()) => {
+                metrics::STATE_REQUESTS_IN_PROGRESS.inc();
+                Some(StateRequestInProgress(self.state_request_throttle.clone()))
+            }
+            Err(limit) => {
+                debug!(target: "sync", ?peer_id, limit, "Dropping state request");
+                metrics::STATE_REQUESTS_THROTTLED.with_label_values(&[limit]).inc();
+                None
+            }
+        }
+    }
+
+    /// Returns the first block on the canonical chain at a height in
+    /// `[height, head_height]`, as (height, hash, timestamp).
+    fn get_block_at_or_after_height(
+        &self,
+        height: BlockHeight,
+        head_height: BlockHeight,
+        final_height: BlockHeight,
+    ) -> Result<Option<(BlockHeight, CryptoHash, u64)>, near_chain::Error> {
+        for height in height..=head_height {
+            let cached = self
+                .block_timestamp_index
+                .lock()
+                .expect(POISONED_LOCK_ERR)
+                .blocks
+                .get(&height)
+                .copied();
+            let block = match cached {
+                Some(block) => block,
+                None => {
+                    let block = match self.chain.get_block_hash_by_height(height) {
+                        Ok(hash) => {
+                            Some((hash, self.chain.get_block_header(&hash)?.raw_timestamp()))
+                        }
+                        Err(near_chain::Error::DBNotFoundErr(_)) => None,
+                        Err(err) => return Err(err),
+                    };
+                    // Blocks above the final head may still get replaced by a fork.
+                    if height <= final_height {
+                        self.block_timestamp_index
+                            .lock()
+                            .expect(POISONED_LOCK_ERR)
+                            .blocks
+                            .put(height, block);
+                    }
+                    block
+                }
+            };
+            if let Some((hash, timestamp)) = block {
+                return Ok(Some((height, hash, timestamp)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the hash of the block on the canonical chain matching
+    /// `timestamp`.  Blocks which have been garbage collected aren't considered.
+    fn find_block_by_timestamp(
+        &self,
+        timestamp: u64,
+        timestamp_match: BlockTimestampMatch,
+    ) -> Result<CryptoHash, GetBlockError> {
+        let head = self.chain.head()?;
+        let final_height = self.chain.final_head()?.height;
+        let tail = self.chain.tail()?;
+        let block = find_first_block_not_before(tail, head.height, timestamp, |height| {
+            self.get_block_at_or_after_height(height, head.height, final_height)
+        })?;
+        match (timestamp_match, block) {
+            (BlockTimestampMatch::AtOrAfter, Some((_, hash, _))) => Ok(hash),
+            (BlockTimestampMatch::AtOrAfter, None) => Err(GetBlockError::UnknownBlock {
+                error_message: format!("No block at or after timestamp {}", timestamp),
+            }),
+            (BlockTimestampMatch::Closest, None) => Ok(head.last_block_hash),
+            (BlockTimestampMatch::Closest, Some((_, hash, block_timestamp))) => {
+                // The previous block is the last one before `timestamp`.  If
+                // the block is at the tail, the previous one is garbage
+                // collected, only its header may be left.
+                let prev_hash = *self.chain.get_block_header(&hash)?.prev_hash();
+                match self.chain.get_block_header(&prev_hash) {
+                    Ok(prev_header)
+                        if prev_header.height() >= tail
+                            && is_prev_block_closer(
+                                timestamp,
+                                prev_header.raw_timestamp(),
+                                block_timestamp,
+                            ) =>
+                    {
+                        Ok(prev_hash)
+                    }
+                    _ => Ok(hash),
+                }
+            }
+        }
+    }
+
+    /// Returns false if a sync request of `kind` should be dropped because the
+    /// bandwidth budget for serving peers is exhausted.
+    fn check_sync_serving_budget(&self, kind: SyncServingKind) -> bool {
+        let result =
+            self.sync_serving_budget.lock().expect(POISONED_LOCK_ERR).check(Clock::instant(), kind);
+        match result {
+            Ok(()) => true,
+            Err(budget) => {
+                debug!(target: "sync", kind = kind.as_str(), budget, "Dropping sync request, bandwidth budget exhausted");
+                metrics::SYNC_REQUESTS_THROTTLED_BY_BANDWIDTH
+                    .with_label_values(&[kind.as_str(), budget])
+                    .inc();
+                false
+            }
         }
-        if cache.len() >= Self::MAX_NUM_STATE_REQUESTS {
-            return false;
+    }
+
+    /// Accounts a response of `bytes` to a sync request of `kind` against the
+    /// bandwidth budget for serving peers.
+    fn record_sync_served(&self, kind: SyncServingKind, bytes: u64) {
+        let mut budget = self.sync_serving_budget.lock().expect(POISONED_LOCK_ERR);
+        if budget.is_limited() {
+            budget.record(Clock::instant(), kind, bytes);
+        }
+        metrics::SYNC_SERVED_BYTES.with_label_values(&[kind.as_str()]).inc_by(bytes);
+    }
+
+    /// Same as `record_sync_served`, for a response whose size is known only
+    /// once it's serialized.
+    fn record_sync_served_response(&self, kind: SyncServingKind, response: &impl BorshSerialize) {
+        let bytes = response.try_to_vec().map_or(0, |v| v.len() as u64);
+        self.record_sync_served(kind, bytes);
+    }
+
+    /// Whether the block is known, but has already been garbage collected.
+    /// Headers are never garbage collected.
+    fn is_garbage_collected(&self, hash: &CryptoHash) -> bool {
+        match (self.chain.get_block_header(hash), self.chain.tail()) {
+            (Ok(header), Ok(tail)) => header.height() < tail,
+            _ => false,
+        }
+    }
+
+    /// Whether the state needed to respond to state requests for `sync_hash`,
+    /// which is the state as of the previous block, has been garbage
+    /// collected.
+    fn is_state_garbage_collected(&self, sync_hash: &CryptoHash) -> bool {
+        match self.chain.get_block_header(sync_hash) {
+            Ok(header) => self.is_garbage_collected(header.prev_hash()),
+            Err(_) => false,
         }
-        cache.push_back(now);
-        true
     }
 }
 
@@ -545,6 +1269,61 @@ impl Handler<WithSpanContext<Query>> for ViewClientActor {
     }
 }
 
+/// Contract function call of a `query` request, sent to the view call pool.
+#[derive(actix::Message)]
+#[rtype(result = "Result<QueryResponse, QueryError>")]
+struct ViewCall {
+    query: Query,
+    _pending: PendingViewCall,
+}
+
+/// Counts a view call as pending until it's handled, or dropped because it
+/// timed out before being handled.
+struct PendingViewCall(Arc<AtomicUsize>);
+
+impl Drop for PendingViewCall {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, atomic::Ordering::Relaxed);
+        metrics::VIEW_CALLS_PENDING.dec();
+    }
+}
+
+impl Handler<WithSpanContext<ViewCall>> for ViewClientActor {
+    type Result = Result<QueryResponse, QueryError>;
+
+    #[perf]
+    fn handle(&mut self, msg: WithSpanContext<ViewCall>, _: &mut Self::Context) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer =
+            metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["ViewCall"]).start_timer();
+        self.handle_query(msg.query)
+    }
+}
+
+/// Transaction simulation sent to the view call pool.
+#[derive(actix::Message)]
+#[rtype(result = "Result<TransactionSimulationView, SimulateTransactionError>")]
+struct SimulationCall {
+    simulation: SimulateTransaction,
+    _pending: PendingViewCall,
+}
+
+impl Handler<WithSpanContext<SimulationCall>> for ViewClientActor {
+    type Result = Result<TransactionSimulationView, SimulateTransactionError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SimulationCall>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer =
+            metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["SimulationCall"]).start_timer();
+        self.simulate_transaction(msg.simulation)
+    }
+}
+
 /// Handles retrieving block from the chain.
 impl Handler<WithSpanContext<GetBlock>> for ViewClientActor {
     type Result = Result<BlockView, GetBlockError>;
@@ -565,6 +1344,27 @@ impl Handler<WithSpanContext<GetBlock>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetBlockByTimestamp>> for ViewClientActor {
+    type Result = Result<BlockView, GetBlockError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetBlockByTimestamp>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetBlockByTimestamp"])
+            .start_timer();
+        let block_hash = self.find_block_by_timestamp(msg.timestamp, msg.timestamp_match)?;
+        self.handle(
+            GetBlock(BlockReference::BlockId(BlockId::Hash(block_hash))).with_span_context(),
+            ctx,
+        )
+    }
+}
+
 impl Handler<WithSpanContext<GetBlockWithMerkleTree>> for ViewClientActor {
     type Result = Result<(BlockView, Arc<PartialMerkleTree>), GetBlockError>;
 
@@ -655,6 +1455,64 @@ impl Handler<WithSpanContext<TxStatus>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<TxStatusExtended>> for ViewClientActor {
+    type Result = Result<Option<FinalExecutionOutcomeWithProofsView>, TxStatusError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<TxStatusExtended>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["TxStatusExtended"])
+            .start_timer();
+        let TxStatusExtended { tx_hash, signer_account_id, light_client_head } = msg;
+        let final_outcome = match self.get_tx_status(tx_hash, signer_account_id.clone(), true)? {
+            Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcomeWithReceipt(outcome)) => {
+                outcome
+            }
+            // The outcome was received from another node, the receipts are looked up locally.
+            Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome)) => {
+                self.chain.get_final_transaction_result_with_receipt(outcome)?
+            }
+            None => return Ok(None),
+        };
+        let light_client_head = match light_client_head {
+            Some(light_client_head) => light_client_head,
+            None => self.chain.final_head()?.last_block_hash,
+        };
+        let transaction_outcome_proof = self.get_light_client_outcome_proof(
+            TransactionOrReceiptId::Transaction {
+                transaction_hash: tx_hash,
+                sender_id: signer_account_id,
+            },
+            &light_client_head,
+        )?;
+        let receipts_outcome_proofs = final_outcome
+            .final_outcome
+            .receipts_outcome
+            .iter()
+            .map(|outcome| {
+                self.get_light_client_outcome_proof(
+                    TransactionOrReceiptId::Receipt {
+                        receipt_id: outcome.id,
+                        receiver_id: outcome.outcome.executor_id.clone(),
+                    },
+                    &light_client_head,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Some(FinalExecutionOutcomeWithProofsView {
+            final_outcome,
+            light_client_head,
+            transaction_outcome_proof,
+            receipts_outcome_proofs,
+        }))
+    }
+}
+
 impl Handler<WithSpanContext<GetValidatorInfo>> for ViewClientActor {
     type Result = Result<EpochValidatorInfo, GetValidatorInfoError>;
 
@@ -766,13 +1624,20 @@ impl Handler<WithSpanContext<GetStateChanges>> for ViewClientActor {
         let (_span, msg) = handler_debug_span!(target: "client", msg);
         let _timer =
             metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["GetStateChanges"]).start_timer();
-        Ok(self
-            .chain
-            .store()
-            .get_state_changes(&msg.block_hash, &msg.state_changes_request.into())?
-            .into_iter()
-            .map(Into::into)
-            .collect())
+        let request: StateChangesRequest = msg.state_changes_request.into();
+        let requests = match msg.deadline {
+            None => vec![request],
+            Some(_) => request.split_by_account(),
+        };
+        let mut changes = vec![];
+        for request in requests {
+            if msg.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Err(GetStateChangesError::TimeoutError);
+            }
+            let store = self.chain.store();
+            changes.extend(store.get_state_changes(&msg.block_hash, &request)?.into_iter());
+        }
+        Ok(changes.into_iter().map(Into::into).collect())
     }
 }
 
@@ -873,112 +1738,86 @@ impl Handler<WithSpanContext<GetNextLightClientBlock>> for ViewClientActor {
             let ret = Chain::create_light_client_block(
                 &head_header,
                 &*self.runtime_adapter,
-                self.chain.store(),
-            )?;
-
-            if ret.inner_lite.height <= last_height {
-                Ok(None)
-            } else {
-                Ok(Some(Arc::new(ret)))
-            }
-        } else {
-            match self.chain.store().get_epoch_light_client_block(&last_next_epoch_id.0) {
-                Ok(light_block) => Ok(Some(light_block)),
-                Err(e) => {
-                    if let near_chain::Error::DBNotFoundErr(_) = e {
-                        Ok(None)
-                    } else {
-                        Err(e.into())
-                    }
-                }
-            }
-        }
-    }
-}
-
-impl Handler<WithSpanContext<GetExecutionOutcome>> for ViewClientActor {
-    type Result = Result<GetExecutionOutcomeResponse, GetExecutionOutcomeError>;
-
-    #[perf]
-    fn handle(
-        &mut self,
-        msg: WithSpanContext<GetExecutionOutcome>,
-        _: &mut Self::Context,
-    ) -> Self::Result {
-        let (_span, msg) = handler_debug_span!(target: "client", msg);
-        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
-            .with_label_values(&["GetExecutionOutcome"])
-            .start_timer();
-        let (id, account_id) = match msg.id {
-            TransactionOrReceiptId::Transaction { transaction_hash, sender_id } => {
-                (transaction_hash, sender_id)
-            }
-            TransactionOrReceiptId::Receipt { receipt_id, receiver_id } => {
-                (receipt_id, receiver_id)
-            }
-        };
-        match self.chain.get_execution_outcome(&id) {
-            Ok(outcome) => {
-                let mut outcome_proof = outcome;
-                let epoch_id =
-                    self.chain.get_block(&outcome_proof.block_hash)?.header().epoch_id().clone();
-                let target_shard_id =
-                    self.runtime_adapter.account_id_to_shard_id(&account_id, &epoch_id)?;
-                let res = self.chain.get_next_block_hash_with_new_chunk(
-                    &outcome_proof.block_hash,
-                    target_shard_id,
-                )?;
-                if let Some((h, target_shard_id)) = res {
-                    outcome_proof.block_hash = h;
-                    // Here we assume the number of shards is small so this reconstruction
-                    // should be fast
-                    let outcome_roots = self
-                        .chain
-                        .get_block(&h)?
-                        .chunks()
-                        .iter()
-                        .map(|header| header.outcome_root())
-                        .collect::<Vec<_>>();
-                    if target_shard_id >= (outcome_roots.len() as u64) {
-                        return Err(GetExecutionOutcomeError::InconsistentState {
-                            number_or_shards: outcome_roots.len(),
-                            execution_outcome_shard_id: target_shard_id,
-                        });
-                    }
-                    Ok(GetExecutionOutcomeResponse {
-                        outcome_proof: outcome_proof.into(),
-                        outcome_root_proof: merklize(&outcome_roots).1[target_shard_id as usize]
-                            .clone(),
-                    })
-                } else {
-                    Err(GetExecutionOutcomeError::NotConfirmed { transaction_or_receipt_id: id })
-                }
-            }
-            Err(near_chain::Error::DBNotFoundErr(_)) => {
-                let head = self.chain.head()?;
-                let target_shard_id =
-                    self.runtime_adapter.account_id_to_shard_id(&account_id, &head.epoch_id)?;
-                if self.runtime_adapter.cares_about_shard(
-                    self.validator_account_id.as_ref(),
-                    &head.last_block_hash,
-                    target_shard_id,
-                    true,
-                ) {
-                    Err(GetExecutionOutcomeError::UnknownTransactionOrReceipt {
-                        transaction_or_receipt_id: id,
-                    })
-                } else {
-                    Err(GetExecutionOutcomeError::UnavailableShard {
-                        transaction_or_receipt_id: id,
-                        shard_id: target_shard_id,
-                    })
+                self.chain.store(),
+            )?;
+
+            if ret.inner_lite.height <= last_height {
+                Ok(None)
+            } else {
+                Ok(Some(Arc::new(ret)))
+            }
+        } else {
+            match self.chain.store().get_epoch_light_client_block(&last_next_epoch_id.0) {
+                Ok(light_block) => Ok(Some(light_block)),
+                Err(e) => {
+                    if let near_chain::Error::DBNotFoundErr(_) = e {
+                        Ok(None)
+                    } else {
+                        Err(e.into())
+                    }
                 }
             }
-            Err(err) => Err(err.into()),
         }
     }
 }
 
+impl Handler<WithSpanContext<GetEpochLightClientBlock>> for ViewClientActor {
+    type Result = Result<Option<EpochLightClientBlock>, GetNextLightClientBlockError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetEpochLightClientBlock>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetEpochLightClientBlock"])
+            .start_timer();
+        let light_block = match self.chain.store().get_epoch_light_client_block(&msg.epoch_id.0) {
+            Ok(light_block) => light_block,
+            Err(near_chain::Error::DBNotFoundErr(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // The final block is normally in the requested epoch, otherwise the
+        // blocks after it lead there.
+        let mut header = self.chain.get_block_header(
+            &LightClientBlockLiteView {
+                prev_block_hash: light_block.prev_block_hash,
+                inner_rest_hash: light_block.inner_rest_hash,
+                inner_lite: light_block.inner_lite.clone(),
+            }
+            .hash(),
+        )?;
+        while header.epoch_id() != &msg.epoch_id {
+            header = self
+                .chain
+                .get_block_header(&self.chain.store().get_next_block_hash(header.hash())?)?;
+        }
+        Ok(Some(EpochLightClientBlock {
+            light_block,
+            next_epoch_id: header.next_epoch_id().clone(),
+        }))
+    }
+}
+
+impl Handler<WithSpanContext<GetExecutionOutcome>> for ViewClientActor {
+    type Result = Result<GetExecutionOutcomeResponse, GetExecutionOutcomeError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetExecutionOutcome>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetExecutionOutcome"])
+            .start_timer();
+        self.get_execution_outcome_proof(msg.id)
+    }
+}
+
 /// Extract the list of execution outcomes that were produced in a given block
 /// (including those created for local receipts).
 impl Handler<WithSpanContext<GetExecutionOutcomesForBlock>> for ViewClientActor {
@@ -1020,6 +1859,36 @@ impl Handler<WithSpanContext<GetReceipt>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetAccountOutcomes>> for ViewClientActor {
+    type Result = Result<Vec<AccountOutcomeIdsView>, GetAccountOutcomesError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetAccountOutcomes>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetAccountOutcomes"])
+            .start_timer();
+        if !self.config.save_account_outcome_ids {
+            return Err(GetAccountOutcomesError::IndexDisabled);
+        }
+        Ok(self
+            .chain
+            .store()
+            .get_account_outcome_ids(&msg.account_id, msg.from_height, msg.limit)?
+            .into_iter()
+            .map(|(block_height, block_hash, outcome_ids)| AccountOutcomeIdsView {
+                block_height,
+                block_hash,
+                outcome_ids,
+            })
+            .collect())
+    }
+}
+
 impl Handler<WithSpanContext<GetBlockProof>> for ViewClientActor {
     type Result = Result<GetBlockProofResponse, GetBlockProofError>;
 
@@ -1032,12 +1901,7 @@ impl Handler<WithSpanContext<GetBlockProof>> for ViewClientActor {
         let (_span, msg) = handler_debug_span!(target: "client", msg);
         let _timer =
             metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["GetBlockProof"]).start_timer();
-        let block_header = self.chain.get_block_header(&msg.block_hash)?;
-        let head_block_header = self.chain.get_block_header(&msg.head_block_hash)?;
-        self.chain.check_blocks_final_and_canonical(&[&block_header, &head_block_header])?;
-        let block_header_lite = block_header.into();
-        let proof = self.chain.get_block_proof(&msg.block_hash, &msg.head_block_hash)?;
-        Ok(GetBlockProofResponse { block_header_lite, proof })
+        self.get_block_proof(&msg.block_hash, &msg.head_block_hash)
     }
 }
 
@@ -1065,6 +1929,51 @@ impl Handler<WithSpanContext<GetProtocolConfig>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetEpochProtocolConfig>> for ViewClientActor {
+    type Result = Result<(EpochId, ProtocolConfigView), GetProtocolConfigError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetEpochProtocolConfig>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetEpochProtocolConfig"])
+            .start_timer();
+        let epoch_id = match msg.0 {
+            EpochReference::EpochId(epoch_id) => epoch_id,
+            EpochReference::BlockId(BlockId::Hash(hash)) => {
+                self.chain.get_block_header(&hash)?.epoch_id().clone()
+            }
+            EpochReference::BlockId(BlockId::Height(height)) => {
+                self.chain.get_block_header_by_height(height)?.epoch_id().clone()
+            }
+            EpochReference::Latest => self.chain.head()?.epoch_id,
+        };
+        let config = self.runtime_adapter.get_protocol_config(&epoch_id)?;
+        Ok((epoch_id, config.into()))
+    }
+}
+
+impl Handler<WithSpanContext<SimulateTransaction>> for ViewClientActor {
+    type Result = Result<TransactionSimulationView, SimulateTransactionError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SimulateTransaction>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["SimulateTransaction"])
+            .start_timer();
+        self.simulate_transaction(msg)
+    }
+}
+
 #[cfg(feature = "test_features")]
 impl Handler<WithSpanContext<NetworkAdversarialMessage>> for ViewClientActor {
     type Result = Option<u64>;
@@ -1147,7 +2056,7 @@ impl Handler<WithSpanContext<TxStatusResponse>> for ViewClientActor {
 }
 
 impl Handler<WithSpanContext<BlockRequest>> for ViewClientActor {
-    type Result = Option<Box<Block>>;
+    type Result = Option<DataResponse<Box<Block>>>;
 
     #[perf]
     fn handle(
@@ -1159,8 +2068,15 @@ impl Handler<WithSpanContext<BlockRequest>> for ViewClientActor {
         let _timer =
             metrics::VIEW_CLIENT_MESSAGE_TIME.with_label_values(&["BlockRequest"]).start_timer();
         let BlockRequest(hash) = msg;
+        if !self.check_sync_serving_budget(SyncServingKind::Block) {
+            return None;
+        }
         if let Ok(block) = self.chain.get_block(&hash) {
-            Some(Box::new(block))
+            self.record_sync_served_response(SyncServingKind::Block, &block);
+            Some(DataResponse::Data(Box::new(block)))
+        } else if self.is_garbage_collected(&hash) {
+            debug!(target: "sync", %hash, "Requested block is garbage collected");
+            Some(DataResponse::GarbageCollected(GarbageCollectedData::Block(hash)))
         } else {
             None
         }
@@ -1182,9 +2098,12 @@ impl Handler<WithSpanContext<BlockHeadersRequest>> for ViewClientActor {
             .start_timer();
         let BlockHeadersRequest(hashes) = msg;
 
-        if self.adv.disable_header_sync() {
+        if self.adv.disable_header_sync()
+            || !self.check_sync_serving_budget(SyncServingKind::Headers)
+        {
             None
         } else if let Ok(headers) = self.retrieve_headers(hashes) {
+            self.record_sync_served_response(SyncServingKind::Headers, &headers);
             Some(headers)
         } else {
             None
@@ -1192,8 +2111,35 @@ impl Handler<WithSpanContext<BlockHeadersRequest>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<BlockHeadersAtHeightsRequest>> for ViewClientActor {
+    type Result = Option<Vec<BlockHeader>>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<BlockHeadersAtHeightsRequest>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["BlockHeadersAtHeightsRequest"])
+            .start_timer();
+        let BlockHeadersAtHeightsRequest(heights) = msg;
+
+        if self.adv.disable_header_sync()
+            || !self.check_sync_serving_budget(SyncServingKind::Headers)
+        {
+            None
+        } else {
+            let headers = self.retrieve_headers_at_heights(heights).ok()?;
+            self.record_sync_served_response(SyncServingKind::Headers, &headers);
+            Some(headers)
+        }
+    }
+}
+
 impl Handler<WithSpanContext<StateRequestHeader>> for ViewClientActor {
-    type Result = Option<StateResponse>;
+    type Result = Option<DataResponse<StateResponse>>;
 
     #[perf]
     fn handle(
@@ -1205,10 +2151,18 @@ impl Handler<WithSpanContext<StateRequestHeader>> for ViewClientActor {
         let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
             .with_label_values(&["StateRequestHeader"])
             .start_timer();
-        let StateRequestHeader { shard_id, sync_hash } = msg;
-        if !self.check_state_sync_request() {
+        let StateRequestHeader { shard_id, sync_hash, peer_id } = msg;
+        if !self.check_sync_serving_budget(SyncServingKind::State) {
             return None;
         }
+        let _in_progress = self.check_state_sync_request(Some(&peer_id))?;
+        if self.is_state_garbage_collected(&sync_hash) {
+            debug!(target: "sync", %sync_hash, "Requested state header is garbage collected");
+            return Some(DataResponse::GarbageCollected(GarbageCollectedData::StateHeader {
+                shard_id,
+                sync_hash,
+            }));
+        }
         let state_response = match self.chain.check_sync_hash_validity(&sync_hash) {
             Ok(true) => {
                 let header = match self.chain.get_state_response_header(shard_id, sync_hash) {
@@ -1267,7 +2221,8 @@ impl Handler<WithSpanContext<StateRequestHeader>> for ViewClientActor {
                     sync_hash,
                     state_response,
                 });
-                Some(StateResponse(Box::new(info)))
+                self.record_sync_served_response(SyncServingKind::State, &info);
+                Some(DataResponse::Data(StateResponse(Box::new(info))))
             }
             state_response @ ShardStateSyncResponse::V2(_) => {
                 let info = StateResponseInfo::V2(StateResponseInfoV2 {
@@ -1275,14 +2230,15 @@ impl Handler<WithSpanContext<StateRequestHeader>> for ViewClientActor {
                     sync_hash,
                     state_response,
                 });
-                Some(StateResponse(Box::new(info)))
+                self.record_sync_served_response(SyncServingKind::State, &info);
+                Some(DataResponse::Data(StateResponse(Box::new(info))))
             }
         }
     }
 }
 
 impl Handler<WithSpanContext<StateRequestPart>> for ViewClientActor {
-    type Result = Option<StateResponse>;
+    type Result = Option<DataResponse<StateResponse>>;
 
     #[perf]
     fn handle(
@@ -1294,10 +2250,19 @@ impl Handler<WithSpanContext<StateRequestPart>> for ViewClientActor {
         let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
             .with_label_values(&["StateRequestPart"])
             .start_timer();
-        let StateRequestPart { shard_id, sync_hash, part_id } = msg;
-        if !self.check_state_sync_request() {
+        let StateRequestPart { shard_id, sync_hash, part_id, peer_id } = msg;
+        if !self.check_sync_serving_budget(SyncServingKind::State) {
             return None;
         }
+        let _in_progress = self.check_state_sync_request(Some(&peer_id))?;
+        if self.is_state_garbage_collected(&sync_hash) {
+            debug!(target: "sync", %sync_hash, part_id, "Requested state part is garbage collected");
+            return Some(DataResponse::GarbageCollected(GarbageCollectedData::StatePart {
+                shard_id,
+                sync_hash,
+                part_id,
+            }));
+        }
         trace!(target: "sync", "Computing state request part {} {} {}", shard_id, sync_hash, part_id);
         let state_response = match self.chain.check_sync_hash_validity(&sync_hash) {
             Ok(true) => {
@@ -1331,7 +2296,49 @@ impl Handler<WithSpanContext<StateRequestPart>> for ViewClientActor {
         };
         let info =
             StateResponseInfo::V1(StateResponseInfoV1 { shard_id, sync_hash, state_response });
-        Some(StateResponse(Box::new(info)))
+        self.record_sync_served_response(SyncServingKind::State, &info);
+        Some(DataResponse::Data(StateResponse(Box::new(info))))
+    }
+}
+
+impl Handler<WithSpanContext<StateRequestDelta>> for ViewClientActor {
+    type Result = Option<StateDeltaResponse>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<StateRequestDelta>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["StateRequestDelta"])
+            .start_timer();
+        let StateRequestDelta { shard_id, sync_hash, from_hash, peer_id } = msg;
+        if !self.check_sync_serving_budget(SyncServingKind::State) {
+            return None;
+        }
+        let _in_progress = self.check_state_sync_request(Some(&peer_id))?;
+        let delta = match self.chain.check_sync_hash_validity(&sync_hash) {
+            Ok(true) => match self.chain.get_state_response_delta(shard_id, sync_hash, from_hash) {
+                Ok(delta) => Some(delta),
+                Err(e) => {
+                    info!(target: "sync", "Cannot build state delta (get_state_response_delta): {}", e);
+                    None
+                }
+            },
+            Ok(false) => {
+                warn!(target: "sync", "sync_hash {:?} didn't pass validation, possible malicious behavior", sync_hash);
+                return None;
+            }
+            Err(e) => {
+                info!(target: "sync", "Failed to verify sync_hash {:?} validity, {:?}", sync_hash, e);
+                None
+            }
+        };
+        let info = StateDeltaResponseInfo { shard_id, sync_hash, delta };
+        self.record_sync_served_response(SyncServingKind::State, &info);
+        Some(StateDeltaResponse(Box::new(info)))
     }
 }
 
@@ -1411,6 +2418,57 @@ impl Handler<WithSpanContext<GetGasPrice>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetGasPriceEstimate>> for ViewClientActor {
+    type Result = Result<GasPriceEstimateView, GetGasPriceError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetGasPriceEstimate>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetGasPriceEstimate"])
+            .start_timer();
+        let head = self.chain.head()?;
+        let window = self.gas_price_window.lock().expect(POISONED_LOCK_ERR);
+        let last = window.last().cloned().ok_or_else(|| GetGasPriceError::Unreachable {
+            error_message: "no blocks in the gas price window".to_string(),
+        })?;
+        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&head.epoch_id)?;
+        let economics_config = &self.chain.block_economics_config;
+        let projected_gas_price = window
+            .projected_gas_price(
+                economics_config.min_gas_price(protocol_version),
+                economics_config.max_gas_price(protocol_version),
+                economics_config.gas_price_adjustment_rate(protocol_version),
+            )
+            .unwrap_or(last.gas_price);
+        let estimates = msg
+            .percentiles
+            .into_iter()
+            .map(|percentile| GasPricePercentileView {
+                percentile,
+                gas_price: std::cmp::max(
+                    window.percentile(percentile).unwrap_or(last.gas_price),
+                    projected_gas_price,
+                ),
+            })
+            .collect();
+        Ok(GasPriceEstimateView {
+            block_hash: last.hash,
+            block_height: last.height,
+            gas_price: last.gas_price,
+            num_blocks: window.len() as u64,
+            utilization: window.utilization(),
+            utilization_trend: window.utilization_trend(),
+            projected_gas_price,
+            estimates,
+        })
+    }
+}
+
 impl Handler<WithSpanContext<GetMaintenanceWindows>> for ViewClientActor {
     type Result = Result<MaintenanceWindowsView, GetMaintenanceWindowsError>;
 
@@ -1425,6 +2483,57 @@ impl Handler<WithSpanContext<GetMaintenanceWindows>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<GetValidatorSchedule>> for ViewClientActor {
+    type Result = Result<ValidatorScheduleView, GetValidatorScheduleError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetValidatorSchedule>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetValidatorSchedule"])
+            .start_timer();
+        self.get_validator_schedule(msg.account_id)
+    }
+}
+
+impl Handler<WithSpanContext<GetValidatorProjection>> for ViewClientActor {
+    type Result = Result<ValidatorProjectionView, GetValidatorProjectionError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetValidatorProjection>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetValidatorProjection"])
+            .start_timer();
+        self.get_validator_projection(msg.account_id)
+    }
+}
+
+impl Handler<WithSpanContext<GetProtocolVersionVoting>> for ViewClientActor {
+    type Result = Result<ProtocolVersionVotingView, GetProtocolVersionVotingError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetProtocolVersionVoting>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, _msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetProtocolVersionVoting"])
+            .start_timer();
+        self.get_protocol_version_voting()
+    }
+}
+
 impl Handler<WithSpanContext<GetSplitStorageInfo>> for ViewClientActor {
     type Result = Result<GetSplitStorageInfoResult, GetSplitStorageInfoError>;
 
@@ -1449,16 +2558,206 @@ impl Handler<WithSpanContext<GetSplitStorageInfo>> for ViewClientActor {
     }
 }
 
-/// Starts the View Client in a new arbiter (thread).
+impl Handler<WithSpanContext<GetStateSnapshotPart>> for ViewClientActor {
+    type Result = Result<StateSnapshotPart, GetStateSnapshotPartError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<GetStateSnapshotPart>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["GetStateSnapshotPart"])
+            .start_timer();
+        let GetStateSnapshotPart { shard_id, sync_hash, part_id } = msg;
+        if !self.check_sync_serving_budget(SyncServingKind::State) {
+            return Err(GetStateSnapshotPartError::Throttled);
+        }
+        let _in_progress =
+            self.check_state_sync_request(None).ok_or(GetStateSnapshotPartError::Throttled)?;
+        match self.chain.check_sync_hash_validity(&sync_hash) {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(GetStateSnapshotPartError::InvalidRequest(format!(
+                    "{} is not the first block of an epoch",
+                    sync_hash
+                )))
+            }
+            Err(near_chain::Error::DBNotFoundErr(_)) => {
+                return Err(GetStateSnapshotPartError::UnknownBlock(sync_hash))
+            }
+            Err(err) => return Err(err.into()),
+        }
+        let header = self.chain.get_state_response_header(shard_id, sync_hash)?;
+        let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+        let data = self.chain.get_state_response_part(shard_id, part_id, sync_hash)?;
+        self.record_sync_served(SyncServingKind::State, data.len() as u64);
+        Ok(StateSnapshotPart { num_parts, data })
+    }
+}
+
+/// Pool of view client threads dedicated to contract function calls of
+/// `query` requests, so that heavy calls can't starve other queries.
+#[derive(Clone)]
+pub struct ViewCallPool {
+    addr: Addr<ViewClientActor>,
+    pending: Arc<AtomicUsize>,
+    max_pending: usize,
+    timeout: Duration,
+}
+
+/// Reasons for which the view call pool fails to execute a call.
+#[derive(thiserror::Error, Debug)]
+enum ViewCallPoolError {
+    #[error("{0} view calls are already pending")]
+    QueueFull(usize),
+    #[error("View call didn't finish before the deadline of the request")]
+    DeadlineExceeded,
+    #[error("View call timed out after {0:?}")]
+    TimedOut(Duration),
+    #[error("{0}")]
+    Mailbox(actix::MailboxError),
+}
+
+impl ViewCallPool {
+    /// Sends the call made by `make_call` to the pool.  Fails if
+    /// `view_call_max_pending` calls are already pending, or if the call
+    /// doesn't finish within `view_call_timeout` or before `deadline`.
+    ///
+    /// `make_call` gets the instant the call must stop at, which the call
+    /// passes on to the runtime so that it's aborted rather than running on
+    /// after we've given up waiting for it.  Calls which time out while still
+    /// queued are dropped without being executed.
+    async fn execute<M>(
+        &self,
+        deadline: Option<Instant>,
+        make_call: impl FnOnce(PendingViewCall, Instant) -> M,
+    ) -> Result<M::Result, ViewCallPoolError>
+    where
+        M: actix::Message + Send + 'static,
+        M::Result: Send,
+        ViewClientActor: Handler<WithSpanContext<M>>,
+    {
+        if self.pending.fetch_add(1, atomic::Ordering::Relaxed) >= self.max_pending {
+            self.pending.fetch_sub(1, atomic::Ordering::Relaxed);
+            metrics::VIEW_CALLS_FAILED.with_label_values(&["queue_full"]).inc();
+            return Err(ViewCallPoolError::QueueFull(self.max_pending));
+        }
+        metrics::VIEW_CALLS_PENDING.inc();
+        let timeout = match deadline {
+            Some(deadline) => self.timeout.min(deadline.saturating_duration_since(Instant::now())),
+            None => self.timeout,
+        };
+        let call = make_call(PendingViewCall(self.pending.clone()), Instant::now() + timeout);
+        match self.addr.send(call.with_span_context()).timeout(timeout).await {
+            Ok(result) => Ok(result),
+            Err(actix::MailboxError::Timeout) => {
+                metrics::VIEW_CALLS_FAILED.with_label_values(&["timeout"]).inc();
+                if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                    return Err(ViewCallPoolError::DeadlineExceeded);
+                }
+                Err(ViewCallPoolError::TimedOut(self.timeout))
+            }
+            Err(err) => Err(ViewCallPoolError::Mailbox(err)),
+        }
+    }
+
+    /// Executes the query in the pool.  Fails if `view_call_max_pending`
+    /// calls are already pending, or if the call doesn't finish within
+    /// `view_call_timeout` or before the deadline of the query.
+    pub async fn query(&self, query: Query) -> Result<QueryResponse, QueryError> {
+        let deadline = query.deadline;
+        let call = |pending, call_deadline| ViewCall {
+            query: query.with_deadline(Some(call_deadline)),
+            _pending: pending,
+        };
+        match self.execute(deadline, call).await {
+            Ok(result) => result,
+            Err(ViewCallPoolError::DeadlineExceeded) => Err(QueryError::TimeoutError),
+            Err(err) => Err(QueryError::InternalError { error_message: err.to_string() }),
+        }
+    }
+
+    /// Simulates the transaction in the pool, with the same limits as
+    /// [`Self::query`].
+    pub async fn simulate_transaction(
+        &self,
+        simulation: SimulateTransaction,
+    ) -> Result<TransactionSimulationView, SimulateTransactionError> {
+        let deadline = simulation.deadline;
+        let call = |pending, call_deadline| SimulationCall {
+            simulation: SimulateTransaction { deadline: Some(call_deadline), ..simulation },
+            _pending: pending,
+        };
+        match self.execute(deadline, call).await {
+            Ok(result) => result,
+            Err(err) => {
+                Err(SimulateTransactionError::InternalError { error_message: err.to_string() })
+            }
+        }
+    }
+
+    /// Number of calls waiting for or being executed by the pool.
+    #[cfg(test)]
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.load(atomic::Ordering::Relaxed)
+    }
+}
+
+/// Starts the pool of `view_call_threads` view client threads executing
+/// contract function calls.
+pub fn start_view_call_pool(
+    validator_account_id: Option<AccountId>,
+    chain_genesis: ChainGenesis,
+    runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+    network_adapter: Arc<dyn PeerManagerAdapter>,
+    config: ClientConfig,
+    block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+    sync_serving_budget: Arc<Mutex<SyncServingBudget>>,
+    gas_price_window: Arc<Mutex<GasPriceWindow>>,
+    state_parts_cache: Arc<StatePartsCache>,
+    adv: crate::adversarial::Controls,
+) -> ViewCallPool {
+    let max_pending = config.view_call_max_pending;
+    let timeout = config.view_call_timeout;
+    let request_manager = Arc::new(RwLock::new(ViewClientRequestManager::new()));
+    let state_request_throttle = Arc::new(Mutex::new(StateRequestThrottle::from_config(&config)));
+    let addr = SyncArbiter::start(config.view_call_threads, move || {
+        ViewClientActor::new(
+            validator_account_id.clone(),
+            &chain_genesis,
+            runtime_adapter.clone(),
+            network_adapter.clone(),
+            config.clone(),
+            request_manager.clone(),
+            state_request_throttle.clone(),
+            sync_serving_budget.clone(),
+            block_timestamp_index.clone(),
+            gas_price_window.clone(),
+            state_parts_cache.clone(),
+            adv.clone(),
+        )
+        .unwrap()
+    });
+    ViewCallPool { addr, pending: Arc::new(AtomicUsize::new(0)), max_pending, timeout }
+}
+
 pub fn start_view_client(
     validator_account_id: Option<AccountId>,
     chain_genesis: ChainGenesis,
     runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
     network_adapter: Arc<dyn PeerManagerAdapter>,
     config: ClientConfig,
+    block_timestamp_index: Arc<Mutex<BlockTimestampIndex>>,
+    sync_serving_budget: Arc<Mutex<SyncServingBudget>>,
+    gas_price_window: Arc<Mutex<GasPriceWindow>>,
+    state_parts_cache: Arc<StatePartsCache>,
     adv: crate::adversarial::Controls,
 ) -> Addr<ViewClientActor> {
     let request_manager = Arc::new(RwLock::new(ViewClientRequestManager::new()));
+    let state_request_throttle = Arc::new(Mutex::new(StateRequestThrottle::from_config(&config)));
     SyncArbiter::start(config.view_client_threads, move || {
         // ViewClientActor::start_in_arbiter(&Arbiter::current(), move |_ctx| {
         let validator_account_id1 = validator_account_id.clone();
@@ -1473,6 +2772,11 @@ pub fn start_view_client(
             network_adapter1,
             config1,
             request_manager1,
+            state_request_throttle.clone(),
+            sync_serving_budget.clone(),
+            block_timestamp_index.clone(),
+            gas_price_window.clone(),
+            state_parts_cache.clone(),
             adv.clone(),
         )
         .unwrap()
@@ -16,7 +16,9 @@ use near_primitives::types::{
     ValidatorInfoIdentifier,
 };
 use near_primitives::version::ProtocolVersion;
-use near_primitives::views::EpochValidatorInfo;
+use near_primitives::views::{
+    EpochValidatorInfo, ProtocolVersionVotingView, ValidatorProjectionView,
+};
 use near_store::ShardUId;
 use std::cmp::Ordering;
 use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard};
@@ -189,6 +191,25 @@ pub trait EpochManagerAdapter: Send + Sync {
         epoch_id: ValidatorInfoIdentifier,
     ) -> Result<EpochValidatorInfo, Error>;
 
+    /// WARNING: this call may be expensive.
+    ///
+    /// Projected seat, stake and reward of a validator if the epoch of the
+    /// given block ended at it.  Intended for diagnostic use in rpc.
+    fn get_validator_projection(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProjectionView, Error>;
+
+    /// WARNING: this call may be expensive.
+    ///
+    /// Protocol versions voted for by the block producers of the epoch of the
+    /// given block.  Intended for diagnostic use in logging & rpc.
+    fn get_protocol_version_voting(
+        &self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolVersionVotingView, Error>;
+
     /// Amount of tokens minted in given epoch.
     fn get_epoch_minted_amount(&self, epoch_id: &EpochId) -> Result<Balance, Error>;
 
@@ -620,6 +641,27 @@ impl<T: HasEpochMangerHandle + Send + Sync> EpochManagerAdapter for T {
         epoch_manager.get_validator_info(epoch_id).map_err(|e| e.into())
     }
 
+    /// WARNING: this function calls EpochManager::get_epoch_info_aggregator_upto_last
+    /// underneath which can be very expensive.
+    fn get_validator_projection(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProjectionView, Error> {
+        let epoch_manager = self.read();
+        Ok(epoch_manager.get_validator_projection(last_block_hash, account_id)?)
+    }
+
+    /// WARNING: this function calls EpochManager::get_epoch_info_aggregator_upto_last
+    /// underneath which can be very expensive.
+    fn get_protocol_version_voting(
+        &self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolVersionVotingView, Error> {
+        let epoch_manager = self.read();
+        Ok(epoch_manager.get_protocol_version_voting(last_block_hash)?)
+    }
+
     fn get_epoch_minted_amount(&self, epoch_id: &EpochId) -> Result<Balance, Error> {
         let epoch_manager = self.read();
         Ok(epoch_manager.get_epoch_info(epoch_id)?.minted_amount())
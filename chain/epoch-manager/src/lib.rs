@@ -19,7 +19,8 @@ use near_primitives::types::{
 };
 use near_primitives::version::{ProtocolVersion, UPGRADABILITY_FIX_PROTOCOL_VERSION};
 use near_primitives::views::{
-    CurrentEpochValidatorInfo, EpochValidatorInfo, NextEpochValidatorInfo, ValidatorKickoutView,
+    CurrentEpochValidatorInfo, EpochValidatorInfo, NextEpochValidatorInfo, ProtocolVersionVoteView,
+    ProtocolVersionVotingView, ValidatorKickoutView, ValidatorProjectionView,
 };
 use near_store::{DBCol, Store, StoreUpdate};
 use num_rational::Rational64;
@@ -106,6 +107,29 @@ impl EpochInfoProvider for EpochManagerHandle {
     }
 }
 
+/// Stake of the block producers of an epoch voting for each protocol version
+/// in the headers of their blocks.
+struct VersionVoting {
+    stakes: HashMap<ProtocolVersion, Balance>,
+    total_stake: Balance,
+    /// A version wins the vote with more stake than this.
+    threshold_stake: Balance,
+    /// Version of the epoch after next if no version wins the vote.
+    protocol_version: ProtocolVersion,
+}
+
+impl VersionVoting {
+    /// Protocol version of the epoch after next if the epoch ended now.
+    fn next_version(&self) -> ProtocolVersion {
+        // Note: non-deterministic iteration is fine here, there can be only one
+        // version with large enough stake.
+        match self.stakes.iter().max_by_key(|&(_version, stake)| stake) {
+            Some((&version, &stake)) if stake > self.threshold_stake => version,
+            _ => self.protocol_version,
+        }
+    }
+}
+
 /// Tracks epoch information across different forks, such as validators.
 /// Note: that even after garbage collection, the data about genesis epoch should be in the store.
 pub struct EpochManager {
@@ -503,8 +527,44 @@ impl EpochManager {
         (validator_kickout, validator_block_chunk_stats)
     }
 
+    /// Sums up the stake of block producers voting for each protocol version.
+    /// Implements https://github.com/nearprotocol/NEPs/pull/64/files#diff-45f773511fe4321b446c3c4226324873R76
+    fn version_voting(
+        &self,
+        epoch_info: &EpochInfo,
+        next_epoch_info: &EpochInfo,
+        version_tracker: &HashMap<ValidatorId, ProtocolVersion>,
+    ) -> VersionVoting {
+        let mut stakes = HashMap::new();
+        for (&validator_id, &version) in version_tracker {
+            let stake = epoch_info.validator_stake(validator_id);
+            *stakes.entry(version).or_insert(0) += stake;
+        }
+        let total_stake: u128 = epoch_info
+            .block_producers_settlement()
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>()
+            .iter()
+            .map(|&id| epoch_info.validator_stake(id))
+            .sum();
+
+        let protocol_version =
+            if epoch_info.protocol_version() >= UPGRADABILITY_FIX_PROTOCOL_VERSION {
+                next_epoch_info.protocol_version()
+            } else {
+                epoch_info.protocol_version()
+            };
+
+        let config = self.config.for_protocol_version(protocol_version);
+        let threshold_stake = (total_stake
+            * *config.protocol_upgrade_stake_threshold.numer() as u128)
+            / *config.protocol_upgrade_stake_threshold.denom() as u128;
+        VersionVoting { stakes, total_stake, threshold_stake, protocol_version }
+    }
+
     fn collect_blocks_info(
-        &mut self,
+        &self,
         last_block_info: &BlockInfo,
         last_block_hash: &CryptoHash,
     ) -> Result<EpochSummary, EpochError> {
@@ -523,47 +583,8 @@ impl EpochManager {
         let mut proposals = vec![];
         let mut validator_kickout = HashMap::new();
 
-        // Next protocol version calculation.
-        // Implements https://github.com/nearprotocol/NEPs/pull/64/files#diff-45f773511fe4321b446c3c4226324873R76
-        let mut versions = HashMap::new();
-        for (validator_id, version) in version_tracker {
-            let stake = epoch_info.validator_stake(validator_id);
-            *versions.entry(version).or_insert(0) += stake;
-        }
-        let total_block_producer_stake: u128 = epoch_info
-            .block_producers_settlement()
-            .iter()
-            .copied()
-            .collect::<HashSet<_>>()
-            .iter()
-            .map(|&id| epoch_info.validator_stake(id))
-            .sum();
-
-        let protocol_version =
-            if epoch_info.protocol_version() >= UPGRADABILITY_FIX_PROTOCOL_VERSION {
-                next_epoch_info.protocol_version()
-            } else {
-                epoch_info.protocol_version()
-            };
-
-        let config = self.config.for_protocol_version(protocol_version);
-        // Note: non-deterministic iteration is fine here, there can be only one
-        // version with large enough stake.
-        let next_version = if let Some((version, stake)) =
-            versions.into_iter().max_by_key(|&(_version, stake)| stake)
-        {
-            if stake
-                > (total_block_producer_stake
-                    * *config.protocol_upgrade_stake_threshold.numer() as u128)
-                    / *config.protocol_upgrade_stake_threshold.denom() as u128
-            {
-                version
-            } else {
-                protocol_version
-            }
-        } else {
-            protocol_version
-        };
+        let next_version =
+            self.version_voting(&epoch_info, &next_epoch_info, &version_tracker).next_version();
 
         // Gather slashed validators and add them to kick out first.
         let slashed_validators = last_block_info.slashed();
@@ -1342,6 +1363,94 @@ impl EpochManager {
         })
     }
 
+    /// Projects the standing of a validator as if the epoch of the given block
+    /// ended now: whether its current proposal or stake gets a seat in the
+    /// epoch which the current epoch selects validators for (the one after
+    /// next), the stake it would have there and the reward it would get for
+    /// the current epoch given its uptime so far.
+    ///
+    /// WARNING: this call is as expensive as `get_validator_info`.
+    pub fn get_validator_projection(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProjectionView, EpochError> {
+        let block_info = self.get_block_info(last_block_hash)?;
+        let epoch_summary = self.collect_blocks_info(&block_info, last_block_hash)?;
+        let epoch_info = self.get_epoch_info(block_info.epoch_id())?;
+        let epoch_protocol_version = epoch_info.protocol_version();
+        let validator_stake =
+            epoch_info.validators_iter().map(|r| r.account_and_stake()).collect::<HashMap<_, _>>();
+        let next_epoch_id = self.get_next_epoch_id_from_info(&block_info)?;
+        let next_epoch_info = self.get_epoch_info(&next_epoch_id)?;
+
+        let EpochSummary {
+            all_proposals,
+            validator_kickout,
+            validator_block_chunk_stats,
+            next_version,
+            ..
+        } = epoch_summary;
+        let kickout_reason = validator_kickout.get(account_id).cloned();
+
+        // Rewards are proportional to the duration of the epoch, so the
+        // duration so far is extrapolated to the full epoch length.
+        let first_block_info = self.get_block_info(block_info.epoch_first_block())?;
+        let last_block_in_last_epoch = self.get_block_info(first_block_info.prev_hash())?;
+        let epoch_length =
+            self.config.for_protocol_version(epoch_protocol_version).epoch_length as u128;
+        let elapsed_heights =
+            block_info.height().saturating_sub(last_block_in_last_epoch.height()).max(1) as u128;
+        let elapsed_duration = block_info
+            .timestamp_nanosec()
+            .saturating_sub(*last_block_in_last_epoch.timestamp_nanosec())
+            as u128;
+        let epoch_duration =
+            u64::try_from(elapsed_duration * epoch_length / elapsed_heights).unwrap_or(u64::MAX);
+        let (validator_reward, minted_amount) = self.reward_calculator.calculate_reward(
+            validator_block_chunk_stats,
+            &validator_stake,
+            *block_info.total_supply(),
+            epoch_protocol_version,
+            self.genesis_protocol_version,
+            epoch_duration,
+        );
+        let estimated_reward = validator_reward.get(account_id).copied().unwrap_or(0);
+
+        // The seed only affects the assignment of seats to shards and
+        // heights, not which validators get them.
+        let next_next_epoch_config = self.config.for_protocol_version(next_version);
+        let next_next_epoch_info = match proposals_to_epoch_info(
+            &next_next_epoch_config,
+            [0; 32],
+            &next_epoch_info,
+            all_proposals,
+            validator_kickout,
+            validator_reward,
+            minted_amount,
+            next_version,
+            epoch_protocol_version,
+        ) {
+            Ok(next_next_epoch_info) => Arc::new(next_next_epoch_info),
+            Err(EpochError::ThresholdError { .. })
+            | Err(EpochError::NotEnoughValidators { .. }) => next_epoch_info,
+            Err(err) => return Err(err),
+        };
+        let projected_stake = next_next_epoch_info
+            .get_validator_by_account(account_id)
+            .map(|validator| validator.stake());
+
+        Ok(ValidatorProjectionView {
+            account_id: account_id.clone(),
+            epoch_id: block_info.epoch_id().0,
+            epoch_start_height: first_block_info.height(),
+            projected_stake,
+            seat_price: next_next_epoch_info.seat_price(),
+            kickout_reason,
+            estimated_reward,
+        })
+    }
+
     /// Compare two epoch ids based on their start height. This works because finality gadget
     /// guarantees that we cannot have two different epochs on two forks
     pub fn compare_epoch_id(
@@ -1751,4 +1860,69 @@ impl EpochManager {
             Ok(None)
         }
     }
+
+    /// Returns the protocol versions the block producers of the epoch of the
+    /// given block voted for in their latest blocks, and the version the epoch
+    /// after next would have if the epoch ended at the block.
+    ///
+    /// WARNING: this call is as expensive as `get_validator_info`.
+    pub fn get_protocol_version_voting(
+        &self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolVersionVotingView, EpochError> {
+        let block_info = self.get_block_info(last_block_hash)?;
+        let epoch_info = self.get_epoch_info(block_info.epoch_id())?;
+        let next_epoch_id = self.get_next_epoch_id_from_info(&block_info)?;
+        let next_epoch_info = self.get_epoch_info(&next_epoch_id)?;
+        let version_tracker =
+            self.get_epoch_info_aggregator_upto_last(last_block_hash)?.version_tracker;
+        let voting = self.version_voting(&epoch_info, &next_epoch_info, &version_tracker);
+        let projected_protocol_version = voting.next_version();
+
+        let mut votes = epoch_info
+            .block_producers_settlement()
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|validator_id| ProtocolVersionVoteView {
+                account_id: epoch_info.validator_account_id(validator_id).clone(),
+                stake: epoch_info.validator_stake(validator_id),
+                protocol_version: version_tracker.get(&validator_id).copied(),
+            })
+            .collect::<Vec<_>>();
+        votes.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+
+        // A newer version takes effect either in the next epoch or, if it
+        // wins the current vote, in the one after it.
+        let epoch_start_height = self.get_block_info(block_info.epoch_first_block())?.height();
+        let next_epoch_start_height = epoch_start_height
+            + self.config.for_protocol_version(epoch_info.protocol_version()).epoch_length;
+        let estimated_upgrade_height =
+            if next_epoch_info.protocol_version() > epoch_info.protocol_version() {
+                Some(next_epoch_start_height)
+            } else if projected_protocol_version > next_epoch_info.protocol_version() {
+                Some(
+                    next_epoch_start_height
+                        + self
+                            .config
+                            .for_protocol_version(next_epoch_info.protocol_version())
+                            .epoch_length,
+                )
+            } else {
+                None
+            };
+
+        Ok(ProtocolVersionVotingView {
+            epoch_id: block_info.epoch_id().0,
+            epoch_start_height,
+            protocol_version: epoch_info.protocol_version(),
+            next_protocol_version: next_epoch_info.protocol_version(),
+            projected_protocol_version,
+            votes,
+            total_stake: voting.total_stake,
+            threshold_stake: voting.threshold_stake,
+            estimated_upgrade_height,
+        })
+    }
 }
@@ -754,6 +754,58 @@ fn test_validator_reward_one_validator() {
     assert_eq!(epoch_info.minted_amount(), inflation);
 }
 
+#[test]
+fn test_validator_projection() {
+    let amount_staked = 1_000_000;
+    let validators =
+        vec![("test1".parse().unwrap(), amount_staked), ("test2".parse().unwrap(), amount_staked)];
+    let epoch_length = 10;
+    let reward_calculator = RewardCalculator {
+        max_inflation_rate: Ratio::new(5, 100),
+        num_blocks_per_year: 50,
+        epoch_length,
+        protocol_reward_rate: Ratio::new(1, 10),
+        protocol_treasury_account: "near".parse().unwrap(),
+        online_min_threshold: Ratio::new(90, 100),
+        online_max_threshold: Ratio::new(99, 100),
+        num_seconds_per_year: 50,
+    };
+    let mut epoch_manager =
+        setup_epoch_manager(validators, epoch_length, 1, 2, 0, 90, 60, 0, reward_calculator);
+    let h = hash_range(5);
+    record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+    record_block(
+        &mut epoch_manager,
+        h[0],
+        h[1],
+        1,
+        vec![stake("test2".parse().unwrap(), 0), stake("test3".parse().unwrap(), amount_staked)],
+    );
+    for i in 2..5 {
+        record_block(&mut epoch_manager, h[i - 1], h[i], i as u64, vec![]);
+    }
+
+    let projection =
+        epoch_manager.get_validator_projection(&h[4], &"test1".parse().unwrap()).unwrap();
+    assert_eq!(projection.epoch_id, CryptoHash::default());
+    assert_eq!(projection.epoch_start_height, 1);
+    assert_eq!(projection.kickout_reason, None);
+    assert_eq!(projection.projected_stake, Some(amount_staked + projection.estimated_reward));
+    assert!(projection.seat_price > 0);
+
+    let projection =
+        epoch_manager.get_validator_projection(&h[4], &"test2".parse().unwrap()).unwrap();
+    assert_eq!(projection.kickout_reason, Some(ValidatorKickoutReason::Unstaked));
+    assert_eq!(projection.projected_stake, None);
+    assert_eq!(projection.estimated_reward, 0);
+
+    let projection =
+        epoch_manager.get_validator_projection(&h[4], &"test3".parse().unwrap()).unwrap();
+    assert_eq!(projection.kickout_reason, None);
+    assert_eq!(projection.projected_stake, Some(amount_staked));
+    assert_eq!(projection.estimated_reward, 0);
+}
+
 #[test]
 fn test_validator_reward_weight_by_stake() {
     let stake_amount1 = 1_000_000;
@@ -2125,6 +2177,38 @@ fn test_protocol_version_switch() {
     );
 }
 
+#[test]
+fn test_protocol_version_voting() {
+    let store = create_test_store();
+    let config = epoch_config(10, 1, 1, 0, 90, 60, 0);
+    let amount_staked = 1_000_000;
+    let validators = vec![stake("test1".parse().unwrap(), amount_staked)];
+    let mut epoch_manager =
+        EpochManager::new(store, config, 0, default_reward_calculator(), validators).unwrap();
+    let h = hash_range(5);
+    record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+    let mut block_info1 = block_info(h[1], 1, 1, h[0], h[0], h[0], vec![], DEFAULT_TOTAL_SUPPLY);
+    set_block_info_protocol_version(&mut block_info1, 0);
+    epoch_manager.record_block_info(block_info1, [0; 32]).unwrap();
+    for i in 2..5 {
+        record_block(&mut epoch_manager, h[i - 1], h[i], i as u64, vec![]);
+    }
+
+    let voting = epoch_manager.get_protocol_version_voting(&h[4]).unwrap();
+    assert_eq!(voting.epoch_id, CryptoHash::default());
+    assert_eq!(voting.epoch_start_height, 1);
+    assert_eq!(voting.protocol_version, 0);
+    assert_eq!(voting.next_protocol_version, 0);
+    assert_eq!(voting.projected_protocol_version, PROTOCOL_VERSION);
+    assert_eq!(voting.votes.len(), 1);
+    assert_eq!(voting.votes[0].account_id, "test1".parse::<AccountId>().unwrap());
+    assert_eq!(voting.votes[0].stake, amount_staked);
+    assert_eq!(voting.votes[0].protocol_version, Some(PROTOCOL_VERSION));
+    assert_eq!(voting.total_stake, amount_staked);
+    assert_eq!(voting.threshold_stake, amount_staked * 80 / 100);
+    assert_eq!(voting.estimated_upgrade_height, Some(21));
+}
+
 #[test]
 fn test_protocol_version_switch_with_shard_layout_change() {
     let store = create_test_store();
@@ -0,0 +1,5 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/node.proto");
+    tonic_build::configure().build_client(true).compile(&["proto/node.proto"], &["proto/"])?;
+    Ok(())
+}
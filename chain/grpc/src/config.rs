@@ -0,0 +1,18 @@
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GrpcConfig {
+    /// Address to listen on.  The server doesn't terminate TLS, so it only
+    /// listens on the loopback interface by default.
+    pub addr: String,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self { addr: "127.0.0.1:3050".to_owned() }
+    }
+}
+
+impl GrpcConfig {
+    pub fn new(addr: &str) -> Self {
+        Self { addr: addr.to_owned() }
+    }
+}
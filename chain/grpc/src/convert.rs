@@ -0,0 +1,344 @@
+//! Conversions between the protobuf messages and the near-primitives types.
+use near_client_primitives::types::GetChunk;
+use near_primitives::borsh::BorshSerialize;
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{AccountId, BlockId, BlockReference, Finality};
+use near_primitives::views::{
+    AccessKeyList, AccessKeyPermissionView, AccessKeyView, BlockHeaderView, BlockView,
+    ChunkHeaderView, ChunkView, FinalExecutionOutcomeView, FinalExecutionStatus, QueryRequest,
+    QueryResponse, QueryResponseKind,
+};
+use std::num::NonZeroU32;
+use tonic::Status;
+
+use crate::proto;
+
+/// Number of values listed by a `view_state` query which doesn't set a limit.
+const DEFAULT_VIEW_STATE_LIMIT: u32 = 1000;
+
+fn parse_hash(bytes: &[u8], field: &str) -> Result<CryptoHash, Status> {
+    CryptoHash::try_from(bytes)
+        .map_err(|_| Status::invalid_argument(format!("{} is not a 32 bytes hash", field)))
+}
+
+fn parse_account_id(account_id: &str) -> Result<AccountId, Status> {
+    account_id.parse().map_err(|err| {
+        Status::invalid_argument(format!("Invalid account id {}: {}", account_id, err))
+    })
+}
+
+fn to_borsh(value: &impl BorshSerialize) -> Result<Vec<u8>, Status> {
+    value.try_to_vec().map_err(|err| Status::internal(err.to_string()))
+}
+
+pub(crate) fn block_reference(
+    reference: Option<proto::BlockReference>,
+) -> Result<BlockReference, Status> {
+    use proto::block_reference::Reference;
+    Ok(match reference.and_then(|reference| reference.reference) {
+        None => BlockReference::Finality(Finality::Final),
+        Some(Reference::Height(height)) => BlockReference::BlockId(BlockId::Height(height)),
+        Some(Reference::Hash(hash)) => {
+            BlockReference::BlockId(BlockId::Hash(parse_hash(&hash, "block hash")?))
+        }
+        Some(Reference::Finality(finality)) => {
+            BlockReference::Finality(match proto::Finality::from_i32(finality) {
+                Some(proto::Finality::Final) => Finality::Final,
+                Some(proto::Finality::NearFinal) => Finality::DoomSlug,
+                Some(proto::Finality::Optimistic) => Finality::None,
+                None => {
+                    return Err(Status::invalid_argument(format!("Unknown finality {}", finality)))
+                }
+            })
+        }
+    })
+}
+
+pub(crate) fn get_chunk(request: proto::GetChunkRequest) -> Result<GetChunk, Status> {
+    use proto::get_chunk_request::{in_block::Block, Chunk};
+    match request.chunk {
+        Some(Chunk::ChunkHash(hash)) => {
+            Ok(GetChunk::ChunkHash(ChunkHash(parse_hash(&hash, "chunk hash")?)))
+        }
+        Some(Chunk::InBlock(in_block)) => match in_block.block {
+            Some(Block::Height(height)) => Ok(GetChunk::Height(height, in_block.shard_id)),
+            Some(Block::Hash(hash)) => {
+                Ok(GetChunk::BlockHash(parse_hash(&hash, "block hash")?, in_block.shard_id))
+            }
+            None => Err(Status::invalid_argument("Block of the chunk is missing")),
+        },
+        None => Err(Status::invalid_argument("Chunk is missing")),
+    }
+}
+
+pub(crate) fn tx_status(
+    request: &proto::GetTxStatusRequest,
+) -> Result<(CryptoHash, AccountId), Status> {
+    Ok((
+        parse_hash(&request.tx_hash, "transaction hash")?,
+        parse_account_id(&request.signer_account_id)?,
+    ))
+}
+
+pub(crate) fn query_request(
+    request: proto::QueryRequest,
+) -> Result<(BlockReference, QueryRequest), Status> {
+    use proto::query_request::Request;
+    let block_reference = block_reference(request.block)?;
+    let request = match request.request {
+        Some(Request::ViewAccount(request)) => {
+            QueryRequest::ViewAccount { account_id: parse_account_id(&request.account_id)? }
+        }
+        Some(Request::ViewCode(request)) => {
+            QueryRequest::ViewCode { account_id: parse_account_id(&request.account_id)? }
+        }
+        Some(Request::ViewState(request)) => QueryRequest::ViewState {
+            account_id: parse_account_id(&request.account_id)?,
+            prefix: request.prefix.into(),
+            include_proof: request.include_proof,
+            after_key: match request.after_key {
+                after_key if after_key.is_empty() => None,
+                after_key => Some(after_key),
+            },
+            limit: Some(
+                NonZeroU32::new(request.limit)
+                    .unwrap_or(NonZeroU32::new(DEFAULT_VIEW_STATE_LIMIT).unwrap()),
+            ),
+        },
+        Some(Request::ViewAccessKey(request)) => QueryRequest::ViewAccessKey {
+            account_id: parse_account_id(&request.account_id)?,
+            public_key: request.public_key.parse().map_err(|err| {
+                Status::invalid_argument(format!(
+                    "Invalid public key {}: {}",
+                    request.public_key, err
+                ))
+            })?,
+        },
+        Some(Request::ViewAccessKeyList(request)) => QueryRequest::ViewAccessKeyList {
+            account_id: parse_account_id(&request.account_id)?,
+            after_public_key: match request.after_public_key.as_str() {
+                "" => None,
+                public_key => Some(public_key.parse().map_err(|err| {
+                    Status::invalid_argument(format!("Invalid public key {}: {}", public_key, err))
+                })?),
+            },
+            limit: NonZeroU32::new(request.limit),
+        },
+        Some(Request::CallFunction(request)) => QueryRequest::CallFunction {
+            account_id: parse_account_id(&request.account_id)?,
+            method_name: request.method_name,
+            args: request.args.into(),
+        },
+        None => return Err(Status::invalid_argument("Query request is missing")),
+    };
+    Ok((block_reference, request))
+}
+
+fn block_header(header: &BlockHeaderView) -> proto::BlockHeader {
+    proto::BlockHeader {
+        height: header.height,
+        hash: header.hash.as_bytes().to_vec(),
+        prev_hash: header.prev_hash.as_bytes().to_vec(),
+        epoch_id: header.epoch_id.as_bytes().to_vec(),
+        next_epoch_id: header.next_epoch_id.as_bytes().to_vec(),
+        timestamp_nanosec: header.timestamp_nanosec,
+        prev_state_root: header.prev_state_root.as_bytes().to_vec(),
+        last_final_block: header.last_final_block.as_bytes().to_vec(),
+        last_ds_final_block: header.last_ds_final_block.as_bytes().to_vec(),
+        chunks_included: header.chunks_included,
+        gas_price: header.gas_price.to_string(),
+        total_supply: header.total_supply.to_string(),
+        latest_protocol_version: header.latest_protocol_version,
+    }
+}
+
+fn chunk_header(header: &ChunkHeaderView) -> proto::ChunkHeader {
+    proto::ChunkHeader {
+        chunk_hash: header.chunk_hash.as_bytes().to_vec(),
+        prev_block_hash: header.prev_block_hash.as_bytes().to_vec(),
+        shard_id: header.shard_id,
+        height_created: header.height_created,
+        height_included: header.height_included,
+        prev_state_root: header.prev_state_root.as_bytes().to_vec(),
+        outcome_root: header.outcome_root.as_bytes().to_vec(),
+        tx_root: header.tx_root.as_bytes().to_vec(),
+        gas_used: header.gas_used,
+        gas_limit: header.gas_limit,
+        balance_burnt: header.balance_burnt.to_string(),
+    }
+}
+
+pub(crate) fn block(block: &BlockView) -> Result<proto::GetBlockResponse, Status> {
+    Ok(proto::GetBlockResponse {
+        author: block.author.to_string(),
+        header: Some(block_header(&block.header)),
+        chunks: block.chunks.iter().map(chunk_header).collect(),
+        view_borsh: to_borsh(block)?,
+    })
+}
+
+pub(crate) fn chunk(chunk: &ChunkView) -> Result<proto::GetChunkResponse, Status> {
+    Ok(proto::GetChunkResponse {
+        author: chunk.author.to_string(),
+        header: Some(chunk_header(&chunk.header)),
+        transaction_hashes: chunk
+            .transactions
+            .iter()
+            .map(|tx| tx.hash.as_bytes().to_vec())
+            .collect(),
+        receipt_ids: chunk
+            .receipts
+            .iter()
+            .map(|receipt| receipt.receipt_id.as_bytes().to_vec())
+            .collect(),
+        view_borsh: to_borsh(chunk)?,
+    })
+}
+
+pub(crate) fn tx_outcome(
+    outcome: &FinalExecutionOutcomeView,
+) -> Result<proto::GetTxStatusResponse, Status> {
+    use proto::get_tx_status_response::Status as TxStatus;
+    let status = match &outcome.status {
+        FinalExecutionStatus::NotStarted => TxStatus::NotStarted(proto::Empty {}),
+        FinalExecutionStatus::Started => TxStatus::Started(proto::Empty {}),
+        FinalExecutionStatus::Failure(err) => TxStatus::Failure(
+            serde_json::to_string(err).map_err(|err| Status::internal(err.to_string()))?,
+        ),
+        FinalExecutionStatus::SuccessValue(value) => TxStatus::SuccessValue(value.clone()),
+    };
+    Ok(proto::GetTxStatusResponse {
+        status: Some(status),
+        receipt_ids: outcome
+            .receipts_outcome
+            .iter()
+            .map(|outcome| outcome.id.as_bytes().to_vec())
+            .collect(),
+        outcome_borsh: to_borsh(outcome)?,
+    })
+}
+
+fn access_key(access_key: &AccessKeyView) -> proto::AccessKey {
+    use proto::access_key::{FunctionCall, Permission};
+    let permission = match &access_key.permission {
+        AccessKeyPermissionView::FullAccess => Permission::FullAccess(proto::Empty {}),
+        AccessKeyPermissionView::FunctionCall { allowance, receiver_id, method_names } => {
+            Permission::FunctionCall(FunctionCall {
+                allowance: allowance.map(|allowance| allowance.to_string()).unwrap_or_default(),
+                receiver_id: receiver_id.clone(),
+                method_names: method_names.clone(),
+            })
+        }
+    };
+    proto::AccessKey { nonce: access_key.nonce, permission: Some(permission) }
+}
+
+fn access_key_list(list: &AccessKeyList) -> proto::AccessKeyList {
+    proto::AccessKeyList {
+        keys: list
+            .keys
+            .iter()
+            .map(|key| proto::AccessKeyInfo {
+                public_key: key.public_key.to_string(),
+                access_key: Some(access_key(&key.access_key)),
+            })
+            .collect(),
+        next_public_key: list
+            .next_public_key
+            .as_ref()
+            .map_or_else(String::new, |public_key| public_key.to_string()),
+    }
+}
+
+pub(crate) fn query_response(response: QueryResponse) -> proto::QueryResponse {
+    use proto::query_response::Kind;
+    let kind = match response.kind {
+        QueryResponseKind::ViewAccount(account) => Kind::Account(proto::Account {
+            amount: account.amount.to_string(),
+            locked: account.locked.to_string(),
+            code_hash: account.code_hash.as_bytes().to_vec(),
+            storage_usage: account.storage_usage,
+        }),
+        QueryResponseKind::ViewCode(code) => {
+            Kind::Code(proto::ContractCode { code: code.code, hash: code.hash.as_bytes().to_vec() })
+        }
+        QueryResponseKind::ViewState(state) => Kind::State(proto::ViewStateResult {
+            values: state
+                .values
+                .into_iter()
+                .map(|item| proto::StateItem { key: item.key, value: item.value })
+                .collect(),
+            proof: state.proof.iter().map(|node| node.to_vec()).collect(),
+            next_key: state.next_key.unwrap_or_default(),
+        }),
+        QueryResponseKind::CallResult(result) => {
+            Kind::CallResult(proto::CallResult { result: result.result, logs: result.logs })
+        }
+        QueryResponseKind::AccessKey(key) => Kind::AccessKey(access_key(&key)),
+        QueryResponseKind::AccessKeyList(list) => Kind::AccessKeyList(access_key_list(&list)),
+    };
+    proto::QueryResponse {
+        block_height: response.block_height,
+        block_hash: response.block_hash.as_bytes().to_vec(),
+        kind: Some(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_reference() {
+        assert_eq!(block_reference(None).unwrap(), BlockReference::Finality(Finality::Final));
+        let reference = |reference| Some(proto::BlockReference { reference: Some(reference) });
+        use proto::block_reference::Reference;
+        assert_eq!(
+            block_reference(reference(Reference::Height(7))).unwrap(),
+            BlockReference::BlockId(BlockId::Height(7))
+        );
+        assert_eq!(
+            block_reference(reference(Reference::Finality(proto::Finality::Optimistic as i32)))
+                .unwrap(),
+            BlockReference::Finality(Finality::None)
+        );
+        let hash = CryptoHash::hash_bytes(b"block");
+        assert_eq!(
+            block_reference(reference(Reference::Hash(hash.as_bytes().to_vec()))).unwrap(),
+            BlockReference::BlockId(BlockId::Hash(hash))
+        );
+        let err = block_reference(reference(Reference::Hash(vec![1, 2, 3]))).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_query_request() {
+        let request = proto::QueryRequest {
+            block: None,
+            request: Some(proto::query_request::Request::CallFunction(
+                proto::query_request::CallFunction {
+                    account_id: "test.near".to_string(),
+                    method_name: "get".to_string(),
+                    args: vec![1, 2],
+                },
+            )),
+        };
+        let (_, request) = query_request(request).unwrap();
+        assert_eq!(
+            request,
+            QueryRequest::CallFunction {
+                account_id: "test.near".parse().unwrap(),
+                method_name: "get".to_string(),
+                args: vec![1, 2].into(),
+            }
+        );
+        let request = proto::QueryRequest {
+            block: None,
+            request: Some(proto::query_request::Request::ViewAccount(
+                proto::query_request::ViewAccount { account_id: "Invalid!".to_string() },
+            )),
+        };
+        assert_eq!(query_request(request).unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
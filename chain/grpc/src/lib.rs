@@ -0,0 +1,275 @@
+#![doc = include_str!("../README.md")]
+
+use actix::Addr;
+use near_client::ViewClientActor;
+use near_client_primitives::types::{
+    GetBlock, GetBlockError, GetChunkError, Query, QueryError, TxStatus, TxStatusError,
+};
+use near_jsonrpc::{AuthError, Authenticator, RpcAuthConfig};
+use near_o11y::{WithSpanContext, WithSpanContextExt};
+use tonic::{Request, Response, Status};
+
+pub use config::GrpcConfig;
+
+mod config;
+mod convert;
+
+pub mod proto {
+    tonic::include_proto!("near.grpc.v1");
+}
+
+/// Serves the `Node` service by sending the requests to the view client
+/// actors.
+struct NodeService {
+    view_client_addr: Addr<ViewClientActor>,
+}
+
+impl NodeService {
+    async fn view_client_send<M, T, E>(&self, msg: M) -> Result<Result<T, E>, Status>
+    where
+        M: actix::Message<Result = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+        ViewClientActor: actix::Handler<WithSpanContext<M>>,
+    {
+        self.view_client_addr
+            .send(msg.with_span_context())
+            .await
+            .map_err(|err| Status::unavailable(format!("View client is not available: {}", err)))
+    }
+}
+
+fn block_error(err: GetBlockError) -> Status {
+    match err {
+        GetBlockError::UnknownBlock { .. } => Status::not_found(err.to_string()),
+        GetBlockError::NotSyncedYet => Status::unavailable(err.to_string()),
+        GetBlockError::IOError { .. } | GetBlockError::Unreachable { .. } => {
+            Status::internal(err.to_string())
+        }
+    }
+}
+
+fn chunk_error(err: GetChunkError) -> Status {
+    match err {
+        GetChunkError::UnknownBlock { .. } | GetChunkError::UnknownChunk { .. } => {
+            Status::not_found(err.to_string())
+        }
+        GetChunkError::InvalidShardId { .. } => Status::invalid_argument(err.to_string()),
+        GetChunkError::IOError { .. } | GetChunkError::Unreachable { .. } => {
+            Status::internal(err.to_string())
+        }
+    }
+}
+
+fn tx_status_error(err: TxStatusError) -> Status {
+    match err {
+        TxStatusError::MissingTransaction(hash) => {
+            Status::not_found(format!("Transaction {} doesn't exist", hash))
+        }
+        TxStatusError::TimeoutError => Status::deadline_exceeded("Timeout"),
+        TxStatusError::ChainError(err) => Status::internal(err.to_string()),
+        TxStatusError::InternalError(err) => Status::internal(err),
+    }
+}
+
+fn query_error(err: QueryError) -> Status {
+    match err {
+        QueryError::UnknownAccount { .. }
+        | QueryError::NoContractCode { .. }
+        | QueryError::UnknownAccessKey { .. }
+        | QueryError::GarbageCollectedBlock { .. }
+        | QueryError::UnknownBlock { .. } => Status::not_found(err.to_string()),
+        QueryError::InvalidAccount { .. } | QueryError::ContractExecutionError { .. } => {
+            Status::invalid_argument(err.to_string())
+        }
+        QueryError::TooLargeContractState { .. } => Status::failed_precondition(err.to_string()),
+        QueryError::NoSyncedBlocks | QueryError::UnavailableShard { .. } => {
+            Status::unavailable(err.to_string())
+        }
+        QueryError::TimeoutError => Status::deadline_exceeded(err.to_string()),
+        QueryError::InternalError { .. } | QueryError::Unreachable { .. } => {
+            Status::internal(err.to_string())
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl proto::node_server::Node for NodeService {
+    async fn get_block(
+        &self,
+        request: Request<proto::GetBlockRequest>,
+    ) -> Result<Response<proto::GetBlockResponse>, Status> {
+        let block_reference = convert::block_reference(request.into_inner().block)?;
+        let block = self.view_client_send(GetBlock(block_reference)).await?.map_err(block_error)?;
+        Ok(Response::new(convert::block(&block)?))
+    }
+
+    async fn get_chunk(
+        &self,
+        request: Request<proto::GetChunkRequest>,
+    ) -> Result<Response<proto::GetChunkResponse>, Status> {
+        let get_chunk = convert::get_chunk(request.into_inner())?;
+        let chunk = self.view_client_send(get_chunk).await?.map_err(chunk_error)?;
+        Ok(Response::new(convert::chunk(&chunk)?))
+    }
+
+    async fn get_tx_status(
+        &self,
+        request: Request<proto::GetTxStatusRequest>,
+    ) -> Result<Response<proto::GetTxStatusResponse>, Status> {
+        let (tx_hash, signer_account_id) = convert::tx_status(request.get_ref())?;
+        let outcome = self
+            .view_client_send(TxStatus { tx_hash, signer_account_id, fetch_receipt: false })
+            .await?
+            .map_err(tx_status_error)?
+            // The view client asked the nodes tracking the shard of the
+            // transaction, the client may retry once they answered.
+            .ok_or_else(|| Status::unavailable("Transaction status is not known yet"))?;
+        Ok(Response::new(convert::tx_outcome(&outcome.into_outcome())?))
+    }
+
+    async fn query(
+        &self,
+        request: Request<proto::QueryRequest>,
+    ) -> Result<Response<proto::QueryResponse>, Status> {
+        let (block_reference, request) = convert::query_request(request.into_inner())?;
+        let response = self
+            .view_client_send(Query::new(block_reference, request))
+            .await?
+            .map_err(query_error)?;
+        Ok(Response::new(convert::query_response(response)))
+    }
+}
+
+/// Checks a request against the access control of the RPC server.  Unlike
+/// for HTTP there are no public paths, every method requires a token if any
+/// is configured.
+fn check_access(
+    authenticator: &Authenticator,
+    request: Request<()>,
+) -> Result<Request<()>, Status> {
+    let authorization =
+        request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+    match authenticator.check("", request.remote_addr().map(|addr| addr.ip()), authorization) {
+        Ok(()) => Ok(request),
+        Err(err @ AuthError::IpNotAllowed) => Err(Status::permission_denied(err.to_string())),
+        Err(err @ AuthError::InvalidToken) => Err(Status::unauthenticated(err.to_string())),
+    }
+}
+
+/// Starts the gRPC server on the current Tokio runtime.  It runs until the
+/// runtime is shut down.  Requests are subject to the same bearer tokens and
+/// IP allowlist as the RPC server, given in `auth`.
+pub fn start_grpc(
+    config: GrpcConfig,
+    auth: &RpcAuthConfig,
+    view_client_addr: Addr<ViewClientActor>,
+) -> anyhow::Result<()> {
+    let addr = config
+        .addr
+        .parse()
+        .map_err(|err| anyhow::anyhow!("Invalid gRPC address {}: {}", config.addr, err))?;
+    let authenticator = Authenticator::new(auth)?;
+    let service = proto::node_server::NodeServer::with_interceptor(
+        NodeService { view_client_addr },
+        move |request| check_access(&authenticator, request),
+    );
+    tracing::info!(target: "grpc", addr = %addr, "Starting gRPC server");
+    tokio::spawn(async move {
+        if let Err(err) = tonic::transport::Server::builder().add_service(service).serve(addr).await
+        {
+            tracing::error!(target: "grpc", ?err, "gRPC server failed");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_actix_test_utils::run_actix;
+    use near_client::test_utils::setup_no_network;
+    use std::time::Duration;
+
+    fn request(authorization: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(authorization) = authorization {
+            request.metadata_mut().insert("authorization", authorization.parse().unwrap());
+        }
+        request
+    }
+
+    #[test]
+    fn test_check_access() {
+        let open = Authenticator::new(&RpcAuthConfig::default()).unwrap();
+        assert!(check_access(&open, request(None)).is_ok());
+
+        let auth = Authenticator::new(&RpcAuthConfig {
+            bearer_tokens: vec!["secret".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(check_access(&auth, request(Some("Bearer secret"))).is_ok());
+        let status = check_access(&auth, request(Some("Bearer wrong"))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert!(check_access(&auth, request(None)).is_err());
+
+        // The peer address of a request which didn't come over TCP is unknown.
+        let allowlist = Authenticator::new(&RpcAuthConfig {
+            allowed_ips: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let status = check_access(&allowlist, request(None)).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_default_addr_is_loopback() {
+        let addr: std::net::SocketAddr = GrpcConfig::default().addr.parse().unwrap();
+        assert!(addr.ip().is_loopback());
+    }
+
+    /// Requests to the server need the bearer token configured for the RPC.
+    #[test]
+    fn test_service_requires_token() {
+        run_actix(async {
+            let (_client, view_client) = setup_no_network(
+                vec!["test".parse().unwrap()],
+                "test".parse().unwrap(),
+                true,
+                false,
+            );
+            let port =
+                std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            let auth =
+                RpcAuthConfig { bearer_tokens: vec!["secret".to_string()], ..Default::default() };
+            start_grpc(GrpcConfig::new(&format!("127.0.0.1:{}", port)), &auth, view_client)
+                .unwrap();
+
+            let url = format!("http://127.0.0.1:{}", port);
+            let mut client = loop {
+                match proto::node_client::NodeClient::connect(url.clone()).await {
+                    Ok(client) => break client,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let get_block = |authorization: Option<&str>| {
+                let mut request = request(None).map(|()| proto::GetBlockRequest {
+                    block: Some(proto::BlockReference {
+                        reference: Some(proto::block_reference::Reference::Height(0)),
+                    }),
+                });
+                if let Some(authorization) = authorization {
+                    request.metadata_mut().insert("authorization", authorization.parse().unwrap());
+                }
+                request
+            };
+            let status = client.get_block(get_block(None)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+            let response = client.get_block(get_block(Some("Bearer secret"))).await.unwrap();
+            assert_eq!(response.into_inner().header.unwrap().height, 0);
+            actix::System::current().stop();
+        });
+    }
+}
@@ -14,8 +14,11 @@ pub use near_indexer_primitives::{
     StreamerMessage,
 };
 
+mod nats_sink;
 mod streamer;
 
+pub use nats_sink::{NatsSink, NatsSinkConfig};
+
 pub const INDEXER: &str = "indexer";
 
 /// Config wrapper to simplify signature and usage of `nearcore::init_configs`
@@ -109,26 +112,44 @@ impl Indexer {
             nearcore::config::load_config(&indexer_config.home_dir, genesis_validation_mode)
                 .unwrap_or_else(|e| panic!("Error loading config: {:#}", e));
 
-        assert!(
-            !&near_config.client_config.tracked_shards.is_empty(),
-            "Indexer should track at least one shard. \n\
-            Tip: You may want to update {} with `\"tracked_shards\": [0]`
-            ",
-            indexer_config.home_dir.join("config.json").display()
-        );
+        assert_tracks_shards(&indexer_config, &near_config);
         let nearcore::NearNode { client, view_client, .. } =
             nearcore::start_with_config(&indexer_config.home_dir, near_config.clone())
                 .with_context(|| "start_with_config")?;
         Ok(Self { view_client, client, near_config, indexer_config })
     }
 
+    /// Attach Indexer to a node which is already running in this process
+    /// (e.g. started with `nearcore::start_with_config`) instead of starting
+    /// a new one.  `near_config` must be the config the node was started with.
+    pub fn from_node(
+        indexer_config: IndexerConfig,
+        near_config: nearcore::NearConfig,
+        node: &nearcore::NearNode,
+    ) -> Self {
+        assert_tracks_shards(&indexer_config, &near_config);
+        Self {
+            view_client: node.view_client.clone(),
+            client: node.client.clone(),
+            near_config,
+            indexer_config,
+        }
+    }
+
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
     pub fn streamer(&self) -> mpsc::Receiver<StreamerMessage> {
+        self.streamer_with_sync_mode(self.indexer_config.sync_mode.clone())
+    }
+
+    pub(crate) fn streamer_with_sync_mode(
+        &self,
+        sync_mode: SyncModeEnum,
+    ) -> mpsc::Receiver<StreamerMessage> {
         let (sender, receiver) = mpsc::channel(100);
         actix::spawn(streamer::start(
             self.view_client.clone(),
             self.client.clone(),
-            self.indexer_config.clone(),
+            IndexerConfig { sync_mode, ..self.indexer_config.clone() },
             self.near_config.config.store.clone(),
             sender,
         ));
@@ -148,6 +169,16 @@ impl Indexer {
     }
 }
 
+fn assert_tracks_shards(indexer_config: &IndexerConfig, near_config: &nearcore::NearConfig) {
+    assert!(
+        !&near_config.client_config.tracked_shards.is_empty(),
+        "Indexer should track at least one shard. \n\
+        Tip: You may want to update {} with `\"tracked_shards\": [0]`
+        ",
+        indexer_config.home_dir.join("config.json").display()
+    );
+}
+
 /// Function that initializes configs for the node which
 /// accepts `InitConfigWrapper` and calls original `init_configs` from `neard`
 pub fn indexer_init_configs(
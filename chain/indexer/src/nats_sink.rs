@@ -0,0 +1,291 @@
+//! Publishing of `StreamerMessage`s to a [NATS](https://nats.io) server.
+//!
+//! Every block is published as a single message on the configured subject.
+//! The payload is the JSON serialization of the `StreamerMessage`, which
+//! includes the block, its chunks, the execution outcomes with receipts and
+//! the state changes of every tracked shard.  The message carries a
+//! `Nats-Msg-Id` header set to the block hash, so JetStream can deduplicate
+//! messages which are published again after a restart.
+//!
+//! Messages are published to JetStream with the `async-nats` client, so the
+//! stream capturing the subject acknowledges every message once it has
+//! persisted it.  Delivery is at-least-once: the height of a block is stored
+//! as the cursor in the sink's database only after its message has been
+//! acknowledged, and streaming resumes from the next height.
+//!
+//! There is no Kafka sink: Kafka users can move the messages over with a
+//! NATS-to-Kafka bridge.
+use std::time::Duration;
+
+use async_nats::jetstream;
+use rocksdb::DB;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use near_indexer_primitives::StreamerMessage;
+use near_primitives::types::BlockHeight;
+
+use crate::{Indexer, SyncModeEnum, INDEXER};
+
+const CURSOR_KEY: &[u8] = b"last_published_block_height";
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct NatsSinkConfig {
+    /// Address of the NATS server, e.g. `127.0.0.1:4222`.
+    pub address: String,
+    /// Subject to publish the messages on, e.g. `near.blocks`.  Wildcards
+    /// aren't allowed.
+    pub subject: String,
+}
+
+pub struct NatsSink {
+    config: NatsSinkConfig,
+    db: DB,
+    jetstream: Option<jetstream::Context>,
+}
+
+impl NatsSink {
+    /// Opens the sink, keeping its cursor in the data directory of the
+    /// indexer's node.
+    pub fn open(indexer: &Indexer, config: NatsSinkConfig) -> anyhow::Result<Self> {
+        validate_subject(&config.subject)?;
+        let db_path = near_store::NodeStorage::opener(
+            &indexer.indexer_config.home_dir,
+            &indexer.near_config.config.store,
+            None,
+        )
+        .path()
+        .join("indexer-nats-sink");
+        let db = DB::open_default(db_path)?;
+        Ok(Self { config, db, jetstream: None })
+    }
+
+    /// Height of the last block acknowledged by the server, if any.
+    pub fn cursor(&self) -> anyhow::Result<Option<BlockHeight>> {
+        Ok(match self.db.get(CURSOR_KEY)? {
+            Some(value) => Some(String::from_utf8(value)?.parse()?),
+            None => None,
+        })
+    }
+
+    /// Starts streaming from the block after the cursor, or according to the
+    /// indexer's sync mode if nothing has been published yet, and publishing
+    /// the messages in the background.
+    pub fn start(self, indexer: &Indexer) -> anyhow::Result<()> {
+        let sync_mode = match self.cursor()? {
+            Some(height) => SyncModeEnum::BlockHeight(height + 1),
+            None => indexer.indexer_config.sync_mode.clone(),
+        };
+        let stream = indexer.streamer_with_sync_mode(sync_mode);
+        actix::spawn(async move {
+            if let Err(err) = self.run(stream).await {
+                error!(target: INDEXER, "NATS sink stopped: {:#}", err);
+            }
+        });
+        Ok(())
+    }
+
+    /// Publishes the messages of the stream until it's closed.
+    async fn run(mut self, mut stream: mpsc::Receiver<StreamerMessage>) -> anyhow::Result<()> {
+        while let Some(streamer_message) = stream.recv().await {
+            let height = streamer_message.block.header.height;
+            let msg_id = streamer_message.block.header.hash.to_string();
+            let payload = serde_json::to_vec(&streamer_message)?;
+            self.publish_block(height, &msg_id, &payload).await?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the message of a block, retrying until it's acknowledged,
+    /// and only then moves the cursor to the block.
+    async fn publish_block(
+        &mut self,
+        height: BlockHeight,
+        msg_id: &str,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        loop {
+            let result = tokio::time::timeout(ACK_TIMEOUT, self.publish(msg_id, payload))
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out waiting for acknowledgement")));
+            match result {
+                Ok(()) => break,
+                Err(err) => {
+                    warn!(
+                        target: INDEXER,
+                        "Failed to publish block #{} to NATS: {:#}", height, err
+                    );
+                    self.jetstream = None;
+                    tokio::time::sleep(RECONNECT_INTERVAL).await;
+                }
+            }
+        }
+        self.db.put(CURSOR_KEY, height.to_string())?;
+        Ok(())
+    }
+
+    async fn publish(&mut self, msg_id: &str, payload: &[u8]) -> anyhow::Result<()> {
+        if self.jetstream.is_none() {
+            info!(target: INDEXER, "Connecting to NATS server at {}", self.config.address);
+            let client = async_nats::ConnectOptions::new()
+                .name("near-indexer")
+                .connect(&self.config.address)
+                .await?;
+            self.jetstream = Some(jetstream::new(client));
+        }
+        let jetstream = self.jetstream.as_ref().unwrap();
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert("Nats-Msg-Id", msg_id);
+        let ack = jetstream
+            .publish_with_headers(self.config.subject.clone(), headers, payload.to_vec().into())
+            .await
+            .map_err(|err| anyhow::anyhow!(err))?;
+        ack.await.map_err(|err| anyhow::anyhow!(err))?;
+        Ok(())
+    }
+}
+
+/// Checks that messages can be published on `subject`: it must consist of
+/// non-empty tokens separated by dots, without whitespace or wildcards.
+fn validate_subject(subject: &str) -> anyhow::Result<()> {
+    let is_valid_token = |token: &str| {
+        !token.is_empty() && !token.contains(|c: char| c.is_whitespace() || c == '*' || c == '>')
+    };
+    anyhow::ensure!(
+        subject.split('.').all(is_valid_token),
+        "Invalid NATS subject to publish on: {:?}",
+        subject
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::{validate_subject, NatsSink, NatsSinkConfig};
+
+    const OK_ACK: &str = r#"{"stream":"blocks","seq":1}"#;
+    const ERROR_ACK: &str =
+        r#"{"error":{"code":503,"err_code":10077,"description":"storage full"}}"#;
+
+    /// Starts a server which talks NATS just enough to answer the sink's
+    /// publishes with given acknowledgements, one per publish, and returns
+    /// its address and the ids of the messages published so far.  `None`
+    /// stands for no acknowledgement at all.
+    async fn start_server(acks: Vec<Option<&'static str>>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let published = Arc::new(Mutex::new(vec![]));
+        let published_clone = published.clone();
+        let acks = Arc::new(Mutex::new(acks.into_iter()));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve_connection(stream, acks.clone(), published_clone.clone()));
+            }
+        });
+        (address, published)
+    }
+
+    async fn serve_connection(
+        stream: TcpStream,
+        acks: Arc<Mutex<std::vec::IntoIter<Option<&'static str>>>>,
+        published: Arc<Mutex<Vec<String>>>,
+    ) {
+        let mut stream = BufReader::new(stream);
+        stream.get_mut().write_all(b"INFO {\"headers\":true}\r\n").await.unwrap();
+        // Subscription ids by subject.
+        let mut sids = HashMap::new();
+        let mut line = String::new();
+        while matches!(stream.read_line(&mut line).await, Ok(len) if len > 0) {
+            let args = line.split_whitespace().collect::<Vec<_>>();
+            match args[0] {
+                "PING" => stream.get_mut().write_all(b"PONG\r\n").await.unwrap(),
+                "SUB" => {
+                    sids.insert(args[1].to_string(), args[args.len() - 1].to_string());
+                }
+                "HPUB" => {
+                    let reply = args[2].to_string();
+                    let headers_len: usize = args[3].parse().unwrap();
+                    let total_len: usize = args[4].parse().unwrap();
+                    let mut message = vec![0; total_len + 2];
+                    stream.read_exact(&mut message).await.unwrap();
+                    let headers = std::str::from_utf8(&message[..headers_len]).unwrap();
+                    let msg_id = headers
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Nats-Msg-Id: "))
+                        .unwrap();
+                    published.lock().unwrap().push(msg_id.to_string());
+                    let ack = acks.lock().unwrap().next().unwrap();
+                    if let Some(ack) = ack {
+                        let msg =
+                            format!("MSG {} {} {}\r\n{}\r\n", reply, sids[&reply], ack.len(), ack);
+                        stream.get_mut().write_all(msg.as_bytes()).await.unwrap();
+                    }
+                }
+                _ => {}
+            }
+            line.clear();
+        }
+    }
+
+    fn open_sink(address: String, dir: &tempfile::TempDir) -> NatsSink {
+        let config = NatsSinkConfig { address, subject: "near.blocks".to_string() };
+        let db = rocksdb::DB::open_default(dir.path()).unwrap();
+        NatsSink { config, db, jetstream: None }
+    }
+
+    #[tokio::test]
+    async fn test_cursor_advanced_after_ack() {
+        let (address, published) = start_server(vec![Some(OK_ACK)]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = open_sink(address, &dir);
+        sink.publish_block(7, "hash7", b"{}").await.unwrap();
+        assert_eq!(sink.cursor().unwrap(), Some(7));
+        assert_eq!(*published.lock().unwrap(), vec!["hash7"]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_retried_after_error_ack() {
+        let (address, published) = start_server(vec![Some(ERROR_ACK), Some(OK_ACK)]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = open_sink(address, &dir);
+        sink.publish_block(7, "hash7", b"{}").await.unwrap();
+        assert_eq!(sink.cursor().unwrap(), Some(7));
+        assert_eq!(*published.lock().unwrap(), vec!["hash7"; 2]);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_not_advanced_without_ack() {
+        let (address, published) = start_server(vec![None]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = open_sink(address, &dir);
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            sink.publish_block(7, "hash7", b"{}"),
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(sink.cursor().unwrap(), None);
+        assert_eq!(*published.lock().unwrap(), vec!["hash7"]);
+    }
+
+    #[test]
+    fn test_validate_subject() {
+        for subject in ["near", "near.blocks", "near.mainnet-blocks_v1"] {
+            validate_subject(subject).unwrap();
+        }
+        for subject in
+            ["", ".", "near.", ".near", "near..blocks", "near.*", "near.>", "near blocks"]
+        {
+            assert!(validate_subject(subject).is_err(), "{:?}", subject);
+        }
+    }
+}
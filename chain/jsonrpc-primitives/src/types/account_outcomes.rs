@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of blocks returned by a single request.
+pub const MAX_ACCOUNT_OUTCOMES_LIMIT: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcAccountOutcomesRequest {
+    pub account_id: near_primitives::types::AccountId,
+    /// Lowest height of the blocks to return outcomes from.
+    #[serde(default)]
+    pub from_height: near_primitives::types::BlockHeight,
+    /// Maximum number of blocks to return, capped at `MAX_ACCOUNT_OUTCOMES_LIMIT`.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcAccountOutcomesResponse {
+    pub outcomes: Vec<near_primitives::views::AccountOutcomeIdsView>,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcAccountOutcomesError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+    #[error("Indexing of outcomes by account is disabled on this node")]
+    IndexDisabled,
+}
+
+impl From<RpcAccountOutcomesError> for crate::errors::RpcError {
+    fn from(error: RpcAccountOutcomesError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcAccountOutcomesError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -1,3 +1,4 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -25,6 +26,15 @@ pub struct RpcBlockRequest {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct RpcBlockByTimestampRequest {
+    /// Unix timestamp in nanoseconds.
+    #[serde(with = "near_primitives::serialize::dec_format")]
+    pub timestamp_nanosec: u64,
+    #[serde(default)]
+    pub timestamp_match: near_primitives::types::BlockTimestampMatch,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 pub struct RpcBlockResponse {
     #[serde(flatten)]
     pub block_view: near_primitives::views::BlockView,
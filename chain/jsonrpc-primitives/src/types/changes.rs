@@ -1,3 +1,4 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -6,7 +7,7 @@ pub struct RpcStateChangesInBlockRequest {
     pub block_reference: near_primitives::types::BlockReference,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 pub struct RpcStateChangesInBlockResponse {
     pub block_hash: near_primitives::hash::CryptoHash,
     pub changes: near_primitives::views::StateChangesView,
@@ -20,7 +21,7 @@ pub struct RpcStateChangesInBlockByTypeRequest {
     pub state_changes_request: near_primitives::views::StateChangesRequestView,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 pub struct RpcStateChangesInBlockByTypeResponse {
     pub block_hash: near_primitives::hash::CryptoHash,
     pub changes: near_primitives::views::StateChangesKindsView,
@@ -38,6 +39,8 @@ pub enum RpcStateChangesError {
     NotSyncedYet,
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
     InternalError { error_message: String },
+    #[error("The deadline of the request passed before the changes were read")]
+    TimeoutError,
 }
 
 impl From<RpcStateChangesError> for crate::errors::RpcError {
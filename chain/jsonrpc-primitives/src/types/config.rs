@@ -13,6 +13,19 @@ pub struct RpcProtocolConfigResponse {
     pub config_view: near_chain_configs::ProtocolConfigView,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcEpochProtocolConfigRequest {
+    #[serde(flatten)]
+    pub epoch_reference: near_primitives::types::EpochReference,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcEpochProtocolConfigResponse {
+    pub epoch_id: near_primitives::types::EpochId,
+    #[serde(flatten)]
+    pub config_view: near_chain_configs::ProtocolConfigView,
+}
+
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 #[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RpcProtocolConfigError {
@@ -21,6 +34,11 @@ pub enum RpcProtocolConfigError {
         #[serde(skip_serializing)]
         error_message: String,
     },
+    #[error("Epoch has never been observed: {error_message}")]
+    UnknownEpoch {
+        #[serde(skip_serializing)]
+        error_message: String,
+    },
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
     InternalError { error_message: String },
 }
@@ -31,6 +49,9 @@ impl From<RpcProtocolConfigError> for crate::errors::RpcError {
             RpcProtocolConfigError::UnknownBlock { error_message } => {
                 Some(Value::String(format!("Block Not Found: {}", error_message)))
             }
+            RpcProtocolConfigError::UnknownEpoch { error_message } => {
+                Some(Value::String(format!("Epoch Not Found: {}", error_message)))
+            }
             RpcProtocolConfigError::InternalError { .. } => Some(Value::String(error.to_string())),
         };
 
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcDbSnapshotRequest {
+    /// Name of the snapshot directory, created in the `rpc.db_snapshot_dir`
+    /// directory of the node.  Must not exist.
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcDbSnapshotResponse {
+    /// Path of the snapshot on the host of the node.
+    pub path: std::path::PathBuf,
+    pub chain_id: String,
+    pub head_height: near_primitives::types::BlockHeight,
+    pub db_version: u32,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcDbSnapshotError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+    #[error("Database snapshots are disabled on this node, see rpc.db_snapshot_dir")]
+    SnapshotsDisabled,
+    #[error("Snapshot name {name} is not a plain directory name")]
+    InvalidName { name: String },
+}
+
+impl From<RpcDbSnapshotError> for crate::errors::RpcError {
+    fn from(error: RpcDbSnapshotError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcDbSnapshotError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
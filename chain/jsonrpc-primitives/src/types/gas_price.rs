@@ -13,6 +13,24 @@ pub struct RpcGasPriceResponse {
     pub gas_price_view: near_primitives::views::GasPriceView,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcGasPriceEstimateRequest {
+    /// Percentiles of the gas prices of recent blocks to estimate the gas
+    /// price for, in percents.
+    #[serde(default = "default_percentiles")]
+    pub percentiles: Vec<u8>,
+}
+
+fn default_percentiles() -> Vec<u8> {
+    vec![50, 90]
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcGasPriceEstimateResponse {
+    #[serde(flatten)]
+    pub gas_price_estimate: near_primitives::views::GasPriceEstimateView,
+}
+
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 #[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RpcGasPriceError {
@@ -1,16 +1,22 @@
+pub mod account_outcomes;
 pub mod blocks;
 pub mod changes;
 pub mod chunks;
 pub mod client_config;
 pub mod config;
+pub mod db_snapshot;
 pub mod gas_price;
 pub mod light_client;
 pub mod maintenance;
 pub mod network_info;
+pub mod protocol_version_voting;
 pub mod query;
 pub mod receipts;
 pub mod sandbox;
+pub mod simulation;
 pub mod split_storage;
 pub mod status;
 pub mod transactions;
 pub mod validator;
+pub mod validator_projection;
+pub mod validator_schedule;
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcProtocolVersionVotingRequest {}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcProtocolVersionVotingResponse {
+    #[serde(flatten)]
+    pub voting: near_primitives::views::ProtocolVersionVotingView,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcProtocolVersionVotingError {
+    #[error("Node is not synced yet")]
+    NotSyncedYet,
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcProtocolVersionVotingError> for crate::errors::RpcError {
+    fn from(error: RpcProtocolVersionVotingError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcProtocolVersionVotingError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -1,10 +1,16 @@
+use near_primitives::sandbox::SandboxBalancePatch;
 use near_primitives::state_record::StateRecord;
 use near_primitives::types::BlockHeightDelta;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct RpcSandboxPatchStateRequest {
+    #[serde(default)]
     pub records: Vec<StateRecord>,
+    /// Balances of existing accounts to set after applying `records`, all
+    /// in the same block.
+    #[serde(default)]
+    pub balances: Vec<SandboxBalancePatch>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -1,8 +1,9 @@
 use near_client_primitives::debug::{
-    DebugBlockStatusData, EpochInfoView, TrackedShardsView, ValidatorStatus,
+    DebugBlockStatusData, EpochInfoView, FinalityView, MemoryUsageView, ProducerPropagationView,
+    ReorgEventView, TrackedShardsView, ValidatorStatus,
 };
 use near_primitives::views::{
-    CatchupStatusView, ChainProcessingInfo, NetworkGraphView, PeerStoreView,
+    CatchupStatusView, ChainProcessingInfo, NetworkBandwidthView, NetworkGraphView, PeerStoreView,
     RequestedStatePartsView, SyncStatusView,
 };
 use serde::{Deserialize, Serialize};
@@ -29,6 +30,16 @@ pub enum DebugStatusResponse {
     // The state parts already requested.
     RequestedStateParts(Vec<RequestedStatePartsView>),
     NetworkGraph(NetworkGraphView),
+    // Traffic sent and received, by network message type.
+    NetworkBandwidth(NetworkBandwidthView),
+    // Approximate memory held by the major subsystems.
+    MemoryUsage(MemoryUsageView),
+    // The most recent reorgs of the head, newest first.
+    Reorgs(Vec<ReorgEventView>),
+    // Lag of doomslug and BFT finality behind the head.
+    Finality(FinalityView),
+    // Propagation delays of blocks by producer, slowest first.
+    BlockPropagation(Vec<ProducerPropagationView>),
 }
 
 #[cfg(feature = "debug_types")]
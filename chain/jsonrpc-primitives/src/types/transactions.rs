@@ -11,6 +11,19 @@ pub struct RpcTransactionStatusCommonRequest {
     pub transaction_info: TransactionInfo,
 }
 
+#[derive(Debug, Clone)]
+pub struct RpcRelayDelegateActionRequest {
+    pub signed_delegate_action: near_primitives::transaction::SignedDelegateAction,
+}
+
+#[derive(Debug)]
+pub struct RpcTransactionStatusExtendedRequest {
+    pub transaction_info: TransactionInfo,
+    /// Block against which the light client proofs are built, the last final
+    /// block if not set.
+    pub light_client_head: Option<near_primitives::hash::CryptoHash>,
+}
+
 #[derive(Clone, Debug)]
 pub enum TransactionInfo {
     Transaction(near_primitives::transaction::SignedTransaction),
@@ -38,6 +51,10 @@ pub enum RpcTransactionError {
     InternalError { debug_info: String },
     #[error("Timeout")]
     TimeoutError,
+    #[error("Delegate action is invalid: {error_message}")]
+    InvalidDelegateAction { error_message: String },
+    #[error("The node does not relay delegate actions")]
+    RelayerNotConfigured,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -46,6 +63,12 @@ pub struct RpcTransactionResponse {
     pub final_execution_outcome: near_primitives::views::FinalExecutionOutcomeViewEnum,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcTransactionStatusExtendedResponse {
+    #[serde(flatten)]
+    pub final_execution_outcome: near_primitives::views::FinalExecutionOutcomeWithProofsView,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcBroadcastTxSyncResponse {
     pub transaction_hash: near_primitives::hash::CryptoHash,
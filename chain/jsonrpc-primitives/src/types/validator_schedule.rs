@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcValidatorScheduleRequest {
+    /// Validator to return the schedule of, the validator of the node if not
+    /// given.
+    #[serde(default)]
+    pub account_id: Option<near_primitives::types::AccountId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcValidatorScheduleResponse {
+    #[serde(flatten)]
+    pub schedule: near_primitives::views::ValidatorScheduleView,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcValidatorScheduleError {
+    #[error("Account id must be given, the node isn't a validator")]
+    NoAccountId,
+    #[error("Node is not synced yet")]
+    NotSyncedYet,
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcValidatorScheduleError> for crate::errors::RpcError {
+    fn from(error: RpcValidatorScheduleError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcValidatorScheduleError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -251,6 +251,22 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_receipt", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_tx_status_extended(
+        &self,
+        hash: CryptoHash,
+        account_id: AccountId,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::transactions::RpcTransactionStatusExtendedResponse,
+    > {
+        call_method(
+            &self.client,
+            &self.server_addr,
+            "EXPERIMENTAL_tx_status_extended",
+            (hash, account_id),
+        )
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_protocol_config(
         &self,
@@ -259,6 +275,21 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_protocol_config", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_protocol_version_voting(
+        &self,
+        request: near_jsonrpc_primitives::types::protocol_version_voting::RpcProtocolVersionVotingRequest,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::protocol_version_voting::RpcProtocolVersionVotingResponse,
+    > {
+        call_method(
+            &self.client,
+            &self.server_addr,
+            "EXPERIMENTAL_protocol_version_voting",
+            request,
+        )
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_split_storage_info(
         &self,
@@ -267,6 +298,25 @@ impl JsonRpcClient {
     {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_split_storage_info", request)
     }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_validator_schedule(
+        &self,
+        request: near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleResponse>
+    {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_validator_schedule", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_validator_projection(
+        &self,
+        request: near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionRequest,
+    ) -> RpcRequest<
+        near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionResponse,
+    > {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_validator_projection", request)
+    }
 }
 
 fn create_client() -> Client {
@@ -16,6 +16,9 @@ pub static TEST_GENESIS_CONFIG: Lazy<GenesisConfig> =
 pub enum NodeType {
     Validator,
     NonValidator,
+    /// The only validator, so it produces every block and the blocks become
+    /// final.
+    SoleValidator,
 }
 
 pub fn start_all(node_type: NodeType) -> (Addr<ViewClientActor>, tcp::ListenerAddr) {
@@ -27,27 +30,65 @@ pub fn start_all_with_validity_period_and_no_epoch_sync(
     transaction_validity_period: NumBlocks,
     enable_doomslug: bool,
 ) -> (Addr<ViewClientActor>, tcp::ListenerAddr) {
+    let addr = tcp::ListenerAddr::reserve_for_test();
+    let view_client_addr = start_all_with_config(
+        node_type,
+        transaction_validity_period,
+        enable_doomslug,
+        RpcConfig::new(addr),
+    );
+    (view_client_addr, addr)
+}
+
+/// Starts the node with a separate debug listener, returning the address of
+/// the public listener and of the debug one.
+pub fn start_all_with_debug_listener(
+    node_type: NodeType,
+) -> (Addr<ViewClientActor>, tcp::ListenerAddr, String) {
+    let addr = tcp::ListenerAddr::reserve_for_test();
+    // The debug listener is bound by address, so pick a free port for it.
+    let debug_addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .to_string();
+    let config = RpcConfig { debug_addr: Some(debug_addr.clone()), ..RpcConfig::new(addr) };
+    let view_client_addr = start_all_with_config(node_type, 100, false, config);
+    (view_client_addr, addr, debug_addr)
+}
+
+fn start_all_with_config(
+    node_type: NodeType,
+    transaction_validity_period: NumBlocks,
+    enable_doomslug: bool,
+    config: RpcConfig,
+) -> Addr<ViewClientActor> {
+    let validators = match node_type {
+        NodeType::SoleValidator => vec!["test1".parse().unwrap()],
+        _ => vec!["test1".parse().unwrap(), "test2".parse().unwrap()],
+    };
     let (client_addr, view_client_addr) = setup_no_network_with_validity_period_and_no_epoch_sync(
-        vec!["test1".parse().unwrap(), "test2".parse().unwrap()],
-        if let NodeType::Validator = node_type {
-            "test1".parse().unwrap()
-        } else {
+        validators,
+        if let NodeType::NonValidator = node_type {
             "other".parse().unwrap()
+        } else {
+            "test1".parse().unwrap()
         },
         true,
         transaction_validity_period,
         enable_doomslug,
     );
 
-    let addr = tcp::ListenerAddr::reserve_for_test();
     start_http(
-        RpcConfig::new(addr),
+        config,
         TEST_GENESIS_CONFIG.clone(),
         client_addr,
         view_client_addr.clone(),
         None,
-    );
-    (view_client_addr, addr)
+        None,
+        None,
+    )
+    .unwrap();
+    view_client_addr
 }
 
 #[macro_export]
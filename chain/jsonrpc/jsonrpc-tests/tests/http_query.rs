@@ -1,9 +1,14 @@
 use actix::System;
-use futures::{future, FutureExt};
+use borsh::BorshDeserialize;
+use futures::{future, FutureExt, StreamExt};
 
 use near_actix_test_utils::run_actix;
 use near_jsonrpc::client::new_http_client;
+use near_jsonrpc_primitives::message::{from_slice, Message};
+use near_jsonrpc_primitives::types::blocks::RpcBlockResponse;
 use near_o11y::testonly::init_test_logger;
+use near_primitives::serialize::from_base64;
+use near_primitives::views::LightClientBlockView;
 
 use near_jsonrpc_tests as test_utils;
 
@@ -26,3 +31,151 @@ fn test_status() {
         }));
     });
 }
+
+/// Retrieve a block encoded in borsh, raw and in a JSON RPC response.
+#[test]
+fn test_block_borsh() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "block",
+                "id": "dontcare",
+                "params": {"block_id": 0},
+            });
+            let mut response =
+                client.post(format!("http://{}/borsh", addr)).send_json(&request).await.unwrap();
+            assert_eq!(response.headers().get("Content-Type").unwrap(), "application/borsh");
+            let block = RpcBlockResponse::try_from_slice(&response.body().await.unwrap()).unwrap();
+            assert_eq!(block.block_view.header.height, 0);
+
+            let mut response = client
+                .post(format!("http://{}", addr))
+                .insert_header(("Accept", "application/borsh+base64"))
+                .send_json(&request)
+                .await
+                .unwrap();
+            let result = match from_slice(&response.body().await.unwrap()).unwrap() {
+                Message::Response(response) => response.result.unwrap(),
+                message => panic!("unexpected message {:?}", message),
+            };
+            let bytes = from_base64(result.as_str().unwrap()).unwrap();
+            let block2 = RpcBlockResponse::try_from_slice(&bytes).unwrap();
+            assert_eq!(block2.block_view.header.hash, block.block_view.header.hash);
+
+            // Methods not returning chain data don't support borsh.
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "status",
+                "id": "dontcare",
+                "params": [],
+            });
+            let mut response =
+                client.post(format!("http://{}/borsh", addr)).send_json(&request).await.unwrap();
+            match from_slice(&response.body().await.unwrap()).unwrap() {
+                Message::Response(response) => {
+                    assert_eq!(response.result.unwrap_err().code, -32_601)
+                }
+                message => panic!("unexpected message {:?}", message),
+            }
+            System::current().stop();
+        });
+    });
+}
+
+/// Streaming light client blocks from an unknown block fails before the
+/// stream starts.
+#[test]
+fn test_light_client_blocks_unknown_block() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            let response = client
+                .get(format!(
+                    "http://{}/light_client_blocks?from_block_hash={}",
+                    addr,
+                    near_primitives::hash::CryptoHash::hash_bytes(b"unknown")
+                ))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), awc::http::StatusCode::NOT_FOUND);
+            System::current().stop();
+        });
+    });
+}
+
+/// Light client blocks are streamed over a WebSocket.
+#[test]
+fn test_light_client_blocks_websocket() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::SoleValidator);
+
+        actix::spawn(async move {
+            let (response, mut connection) = awc::Client::new()
+                .ws(format!("ws://{}/light_client_blocks", addr))
+                .connect()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), awc::http::StatusCode::SWITCHING_PROTOCOLS);
+            // The block of the genesis epoch is pushed once the head enters
+            // the next epoch.
+            let frame = connection.next().await.unwrap().unwrap();
+            let light_block: LightClientBlockView = match frame {
+                awc::ws::Frame::Text(text) => serde_json::from_slice(&text).unwrap(),
+                frame => panic!("expected a light client block, got {:?}", frame),
+            };
+            assert!(light_block.inner_lite.height > 0);
+            System::current().stop();
+        });
+    });
+}
+
+/// Admin methods are served only on the debug listener, never on the public
+/// address, whether or not a debug listener is configured.
+#[test]
+fn test_admin_methods_only_on_debug_listener() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr, debug_addr) =
+            test_utils::start_all_with_debug_listener(test_utils::NodeType::NonValidator);
+        let (_view_client_addr, addr_without_debug) =
+            test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            for addr in [addr, addr_without_debug] {
+                let err = test_utils::call_method::<serde_json::Value>(
+                    &client,
+                    &format!("http://{}", addr),
+                    "client_config",
+                    serde_json::Value::Null,
+                )
+                .await
+                .unwrap_err();
+                assert_eq!(err.code, -32_601, "{:?}", err);
+            }
+            test_utils::call_method::<serde_json::Value>(
+                &client,
+                &format!("http://{}", debug_addr),
+                "client_config",
+                serde_json::Value::Null,
+            )
+            .await
+            .unwrap();
+            System::current().stop();
+        });
+    });
+}
@@ -222,7 +222,11 @@ fn test_query_access_keys() {
         let query_response = client
             .query(near_jsonrpc_primitives::types::query::RpcQueryRequest {
                 block_reference: BlockReference::latest(),
-                request: QueryRequest::ViewAccessKeyList { account_id: "test".parse().unwrap() },
+                request: QueryRequest::ViewAccessKeyList {
+                    account_id: "test".parse().unwrap(),
+                    after_public_key: None,
+                    limit: None,
+                },
             })
             .await
             .unwrap();
@@ -299,6 +303,8 @@ fn test_query_state() {
                     account_id: "test".parse().unwrap(),
                     prefix: vec![].into(),
                     include_proof: false,
+                    after_key: None,
+                    limit: None,
                 },
             })
             .await
@@ -582,3 +588,37 @@ fn test_get_chunk_with_object_in_params() {
         assert_eq!(chunk.header.chunk_hash, same_chunk.header.chunk_hash);
     });
 }
+
+#[test]
+fn test_batch() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let request = |id: usize, method: &str| json!({"jsonrpc": "2.0", "id": id, "method": method, "params": json!([])});
+        let batch = json!([request(1, "status"), request(2, "no_such_method"), json!(1)]);
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .insert_header(("Content-Type", "application/json"))
+            .send_json(&batch)
+            .await
+            .unwrap();
+        let response: serde_json::Value = response.json().await.unwrap();
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert_eq!(responses[0]["result"]["chain_id"], json!("unittest"));
+        assert_eq!(responses[1]["id"], json!(2));
+        assert_eq!(responses[1]["error"]["code"], json!(-32_601));
+        assert!(responses[2]["error"] != json!(null));
+
+        let batch = serde_json::Value::Array((0..101).map(|id| request(id, "status")).collect());
+        let response = &mut client
+            .client
+            .post(&client.server_addr)
+            .insert_header(("Content-Type", "application/json"))
+            .send_json(&batch)
+            .await
+            .unwrap();
+        let response: serde_json::Value = response.json().await.unwrap();
+        assert!(response["error"] != json!(null), "{}", response);
+    });
+}
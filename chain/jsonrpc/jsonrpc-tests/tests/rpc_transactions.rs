@@ -97,6 +97,54 @@ fn test_send_tx_commit() {
     });
 }
 
+/// Test that the extended transaction status proves the outcomes to a light
+/// client once the block they are included in is final.
+#[test]
+fn test_tx_status_extended() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let block_hash = client.block(BlockReference::latest()).await.unwrap().header.hash;
+        let signer = InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let tx = SignedTransaction::send_money(
+            1,
+            "test1".parse().unwrap(),
+            "test2".parse().unwrap(),
+            &signer,
+            100,
+            block_hash,
+        );
+        let bytes = tx.try_to_vec().unwrap();
+        client.broadcast_tx_commit(to_base64(&bytes)).await.unwrap();
+        loop {
+            let result = client
+                .EXPERIMENTAL_tx_status_extended(tx.get_hash(), "test1".parse().unwrap())
+                .await
+                .unwrap()
+                .final_execution_outcome;
+            let outcome = &result.final_outcome;
+            assert_eq!(outcome.final_outcome.status, FinalExecutionStatus::SuccessValue(vec![]));
+            assert_eq!(outcome.final_outcome.transaction_outcome.id, tx.get_hash());
+            assert_eq!(
+                outcome.final_outcome.receipts_outcome.len(),
+                result.receipts_outcome_proofs.len()
+            );
+            let proofs = std::iter::once(&result.transaction_outcome_proof)
+                .chain(result.receipts_outcome_proofs.iter())
+                .map(Option::as_ref)
+                .collect::<Option<Vec<_>>>();
+            if let Some(proofs) = proofs {
+                let outcomes = std::iter::once(&outcome.final_outcome.transaction_outcome)
+                    .chain(outcome.final_outcome.receipts_outcome.iter());
+                for (proof, outcome) in proofs.into_iter().zip(outcomes) {
+                    assert_eq!(proof.outcome_proof.id, outcome.id);
+                }
+                break;
+            }
+            // The outcomes can't be proven until their blocks are final.
+            actix::clock::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    });
+}
+
 /// Test that expired transaction should be rejected
 #[test]
 fn test_expired_tx() {
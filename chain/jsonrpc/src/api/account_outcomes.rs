@@ -0,0 +1,39 @@
+use serde_json::Value;
+
+use near_client_primitives::types::GetAccountOutcomesError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::account_outcomes::{
+    RpcAccountOutcomesError, RpcAccountOutcomesRequest,
+};
+
+use super::{parse_params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcAccountOutcomesRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcAccountOutcomesError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetAccountOutcomesError> for RpcAccountOutcomesError {
+    fn rpc_from(error: GetAccountOutcomesError) -> Self {
+        match error {
+            GetAccountOutcomesError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetAccountOutcomesError::IndexDisabled => Self::IndexDisabled,
+            GetAccountOutcomesError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcAccountOutcomesError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
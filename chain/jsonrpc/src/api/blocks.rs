@@ -2,7 +2,9 @@ use serde_json::Value;
 
 use near_client_primitives::types::GetBlockError;
 use near_jsonrpc_primitives::errors::RpcParseError;
-use near_jsonrpc_primitives::types::blocks::{RpcBlockError, RpcBlockRequest};
+use near_jsonrpc_primitives::types::blocks::{
+    RpcBlockByTimestampRequest, RpcBlockError, RpcBlockRequest,
+};
 use near_primitives::types::{BlockId, BlockReference};
 
 use super::{parse_params, RpcFrom, RpcRequest};
@@ -18,6 +20,12 @@ impl RpcRequest for RpcBlockRequest {
     }
 }
 
+impl RpcRequest for RpcBlockByTimestampRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
 impl RpcFrom<actix::MailboxError> for RpcBlockError {
     fn rpc_from(error: actix::MailboxError) -> Self {
         Self::InternalError { error_message: error.to_string() }
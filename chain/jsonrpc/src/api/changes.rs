@@ -53,6 +53,7 @@ impl RpcFrom<GetStateChangesError> for RpcStateChangesError {
                 Self::UnknownBlock { error_message }
             }
             GetStateChangesError::NotSyncedYet => Self::NotSyncedYet,
+            GetStateChangesError::TimeoutError => Self::TimeoutError,
             GetStateChangesError::Unreachable { ref error_message } => {
                 tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
                 crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
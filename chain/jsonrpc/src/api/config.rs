@@ -2,8 +2,10 @@ use serde_json::Value;
 
 use near_client_primitives::types::GetProtocolConfigError;
 use near_jsonrpc_primitives::errors::RpcParseError;
-use near_jsonrpc_primitives::types::config::{RpcProtocolConfigError, RpcProtocolConfigRequest};
-use near_primitives::types::BlockReference;
+use near_jsonrpc_primitives::types::config::{
+    RpcEpochProtocolConfigRequest, RpcProtocolConfigError, RpcProtocolConfigRequest,
+};
+use near_primitives::types::{BlockReference, EpochReference};
 
 use super::{parse_params, RpcFrom, RpcRequest};
 
@@ -13,6 +15,12 @@ impl RpcRequest for RpcProtocolConfigRequest {
     }
 }
 
+impl RpcRequest for RpcEpochProtocolConfigRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<EpochReference>(value).map(|epoch_reference| Self { epoch_reference })
+    }
+}
+
 impl RpcFrom<actix::MailboxError> for RpcProtocolConfigError {
     fn rpc_from(error: actix::MailboxError) -> Self {
         Self::InternalError { error_message: error.to_string() }
@@ -25,6 +33,9 @@ impl RpcFrom<GetProtocolConfigError> for RpcProtocolConfigError {
             GetProtocolConfigError::UnknownBlock(error_message) => {
                 Self::UnknownBlock { error_message }
             }
+            GetProtocolConfigError::UnknownEpoch(error_message) => {
+                Self::UnknownEpoch { error_message }
+            }
             GetProtocolConfigError::IOError(error_message) => Self::InternalError { error_message },
             GetProtocolConfigError::Unreachable(ref error_message) => {
                 tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
@@ -0,0 +1,21 @@
+use serde_json::Value;
+
+use near_client_primitives::types::CreateDbSnapshotError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::db_snapshot::{RpcDbSnapshotError, RpcDbSnapshotRequest};
+
+use super::{parse_params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcDbSnapshotRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcFrom<CreateDbSnapshotError> for RpcDbSnapshotError {
+    fn rpc_from(error: CreateDbSnapshotError) -> Self {
+        match error {
+            CreateDbSnapshotError::IOError(error_message) => Self::InternalError { error_message },
+        }
+    }
+}
@@ -2,7 +2,9 @@ use serde_json::Value;
 
 use near_client_primitives::types::GetGasPriceError;
 use near_jsonrpc_primitives::errors::RpcParseError;
-use near_jsonrpc_primitives::types::gas_price::{RpcGasPriceError, RpcGasPriceRequest};
+use near_jsonrpc_primitives::types::gas_price::{
+    RpcGasPriceError, RpcGasPriceEstimateRequest, RpcGasPriceRequest,
+};
 use near_primitives::types::MaybeBlockId;
 
 use super::{parse_params, RpcFrom, RpcRequest};
@@ -13,6 +15,17 @@ impl RpcRequest for RpcGasPriceRequest {
     }
 }
 
+impl RpcRequest for RpcGasPriceEstimateRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        // All parameters are optional.
+        let request = parse_params::<Self>(Some(value.unwrap_or_else(|| serde_json::json!({}))))?;
+        if let Some(percentile) = request.percentiles.iter().find(|p| **p > 100) {
+            return Err(RpcParseError(format!("Percentile {} is greater than 100", percentile)));
+        }
+        Ok(request)
+    }
+}
+
 impl RpcFrom<actix::MailboxError> for RpcGasPriceError {
     fn rpc_from(error: actix::MailboxError) -> Self {
         Self::InternalError { error_message: error.to_string() }
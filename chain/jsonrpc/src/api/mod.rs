@@ -5,22 +5,28 @@ use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::errors::{RpcError, ServerError};
 use near_primitives::borsh::BorshDeserialize;
 
+mod account_outcomes;
 mod blocks;
 mod changes;
 mod chunks;
 mod client_config;
 mod config;
+mod db_snapshot;
 mod gas_price;
 mod light_client;
 mod maintenance;
 mod network_info;
+mod protocol_version_voting;
 mod query;
 mod receipts;
 mod sandbox;
+mod simulation;
 mod split_storage;
 mod status;
 mod transactions;
 mod validator;
+mod validator_projection;
+mod validator_schedule;
 
 pub(crate) trait RpcRequest: Sized {
     fn parse(value: Option<Value>) -> Result<Self, RpcParseError>;
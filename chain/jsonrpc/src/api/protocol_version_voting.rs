@@ -0,0 +1,39 @@
+use serde_json::Value;
+
+use near_client_primitives::types::GetProtocolVersionVotingError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::protocol_version_voting::{
+    RpcProtocolVersionVotingError, RpcProtocolVersionVotingRequest,
+};
+
+use super::{parse_params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcProtocolVersionVotingRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcProtocolVersionVotingError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetProtocolVersionVotingError> for RpcProtocolVersionVotingError {
+    fn rpc_from(error: GetProtocolVersionVotingError) -> Self {
+        match error {
+            GetProtocolVersionVotingError::NotSyncedYet => Self::NotSyncedYet,
+            GetProtocolVersionVotingError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetProtocolVersionVotingError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcProtocolVersionVotingError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
@@ -55,7 +55,11 @@ impl RpcRequest for RpcQueryRequest {
             let request = match query_command {
                 "account" => QueryRequest::ViewAccount { account_id },
                 "access_key" => match maybe_extra_arg {
-                    None => QueryRequest::ViewAccessKeyList { account_id },
+                    None => QueryRequest::ViewAccessKeyList {
+                        account_id,
+                        after_public_key: None,
+                        limit: None,
+                    },
                     Some(pk) => QueryRequest::ViewAccessKey {
                         account_id,
                         public_key: pk
@@ -68,6 +72,8 @@ impl RpcRequest for RpcQueryRequest {
                     account_id,
                     prefix: parse_data()?.into(),
                     include_proof: false,
+                    after_key: None,
+                    limit: None,
                 },
                 "call" => match maybe_extra_arg {
                     Some(method_name) => QueryRequest::CallFunction {
@@ -90,7 +96,10 @@ impl RpcRequest for RpcQueryRequest {
 
 impl RpcFrom<actix::MailboxError> for RpcQueryError {
     fn rpc_from(error: actix::MailboxError) -> Self {
-        Self::InternalError { error_message: error.to_string() }
+        match error {
+            actix::MailboxError::Timeout => Self::TimeoutError,
+            _ => Self::InternalError { error_message: error.to_string() },
+        }
     }
 }
 
@@ -131,6 +140,7 @@ impl RpcFrom<QueryError> for RpcQueryError {
             QueryError::TooLargeContractState { contract_account_id, block_height, block_hash } => {
                 Self::TooLargeContractState { contract_account_id, block_height, block_hash }
             }
+            QueryError::TimeoutError => Self::TimeoutError,
         }
     }
 }
@@ -5,12 +5,30 @@ use near_jsonrpc_primitives::types::sandbox::{
     RpcSandboxFastForwardError, RpcSandboxFastForwardRequest, RpcSandboxPatchStateError,
     RpcSandboxPatchStateRequest,
 };
+use near_primitives::state_record::StateRecord;
 
 use super::{parse_params, RpcFrom, RpcRequest};
 
 impl RpcRequest for RpcSandboxPatchStateRequest {
     fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
-        parse_params::<Self>(value)
+        let request = parse_params::<Self>(value)?;
+        // The runtime can only patch these kinds of records, reject the
+        // rest before they get to it.
+        for record in &request.records {
+            match record {
+                StateRecord::Account { .. }
+                | StateRecord::AccessKey { .. }
+                | StateRecord::Contract { .. }
+                | StateRecord::Data { .. } => {}
+                _ => {
+                    return Err(RpcParseError(format!(
+                        "patch_state can only patch Account, AccessKey, Contract and Data records, got {}",
+                        record
+                    )))
+                }
+            }
+        }
+        Ok(request)
     }
 }
 
@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use near_client_primitives::types::SimulateTransactionError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::simulation::{
+    RpcSimulateTransactionError, RpcSimulateTransactionRequest,
+};
+use near_primitives::borsh::BorshDeserialize;
+use near_primitives::transaction::{SignedTransaction, Transaction};
+use near_primitives::types::BlockReference;
+
+use super::{parse_params, RpcFrom, RpcRequest};
+
+/// Parameters of the request, with either a signed or an unsigned
+/// transaction, serialized with borsh and encoded in base64.
+#[derive(Deserialize)]
+struct SimulateTransactionParams {
+    #[serde(flatten)]
+    block_reference: BlockReference,
+    signed_transaction: Option<String>,
+    transaction: Option<String>,
+}
+
+fn decode<T: BorshDeserialize>(encoded: &str) -> Result<T, RpcParseError> {
+    let bytes = near_primitives::serialize::from_base64(encoded)
+        .map_err(|err| RpcParseError(err.to_string()))?;
+    T::try_from_slice(&bytes)
+        .map_err(|err| RpcParseError(format!("Failed to decode transaction: {}", err)))
+}
+
+impl RpcRequest for RpcSimulateTransactionRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        let params = parse_params::<SimulateTransactionParams>(value)?;
+        let (transaction, signature) = match (params.signed_transaction, params.transaction) {
+            (Some(encoded), None) => {
+                let signed_transaction = decode::<SignedTransaction>(&encoded)?;
+                (signed_transaction.transaction, Some(signed_transaction.signature))
+            }
+            (None, Some(encoded)) => (decode::<Transaction>(&encoded)?, None),
+            _ => {
+                return Err(RpcParseError(
+                    "Exactly one of signed_transaction and transaction has to be set".to_string(),
+                ))
+            }
+        };
+        Ok(Self { block_reference: params.block_reference, transaction, signature })
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcSimulateTransactionError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<SimulateTransactionError> for RpcSimulateTransactionError {
+    fn rpc_from(error: SimulateTransactionError) -> Self {
+        match error {
+            SimulateTransactionError::IOError { error_message } => {
+                Self::InternalError { error_message }
+            }
+            SimulateTransactionError::UnknownBlock { error_message } => {
+                Self::UnknownBlock { error_message }
+            }
+            SimulateTransactionError::NotSyncedYet => Self::NotSyncedYet,
+            SimulateTransactionError::UnavailableShard { requested_shard_id } => {
+                Self::UnavailableShard { requested_shard_id }
+            }
+            SimulateTransactionError::InvalidTransaction { error } => {
+                Self::InvalidTransaction { context: error }
+            }
+            SimulateTransactionError::InternalError { error_message } => {
+                Self::InternalError { error_message }
+            }
+            SimulateTransactionError::Unreachable { ref error_message } => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcSimulateTransactionError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
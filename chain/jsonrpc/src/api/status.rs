@@ -49,6 +49,18 @@ impl RpcFrom<near_client_primitives::debug::DebugStatusResponse>
                     x,
                 )
             }
+            near_client_primitives::debug::DebugStatusResponse::MemoryUsage(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::MemoryUsage(x)
+            }
+            near_client_primitives::debug::DebugStatusResponse::Reorgs(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::Reorgs(x)
+            }
+            near_client_primitives::debug::DebugStatusResponse::Finality(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::Finality(x)
+            }
+            near_client_primitives::debug::DebugStatusResponse::BlockPropagation(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::BlockPropagation(x)
+            }
         }
     }
 }
@@ -64,6 +76,9 @@ impl RpcFrom<near_network::debug::DebugStatus>
             near_network::debug::DebugStatus::Graph(x) => {
                 near_jsonrpc_primitives::types::status::DebugStatusResponse::NetworkGraph(x)
             }
+            near_network::debug::DebugStatus::Bandwidth(x) => {
+                near_jsonrpc_primitives::types::status::DebugStatusResponse::NetworkBandwidth(x)
+            }
         }
     }
 }
@@ -1,12 +1,15 @@
+use near_primitives::borsh::BorshDeserialize;
 use serde_json::Value;
 
 use near_client_primitives::types::TxStatusError;
 use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::types::transactions::{
-    RpcBroadcastTransactionRequest, RpcTransactionError, RpcTransactionResponse,
-    RpcTransactionStatusCommonRequest, TransactionInfo,
+    RpcBroadcastTransactionRequest, RpcRelayDelegateActionRequest, RpcTransactionError,
+    RpcTransactionResponse, RpcTransactionStatusCommonRequest, RpcTransactionStatusExtendedRequest,
+    TransactionInfo,
 };
 use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedDelegateAction;
 use near_primitives::types::AccountId;
 use near_primitives::views::FinalExecutionOutcomeViewEnum;
 
@@ -32,6 +35,33 @@ impl RpcRequest for RpcTransactionStatusCommonRequest {
     }
 }
 
+impl RpcRequest for RpcRelayDelegateActionRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        let (encoded,) = parse_params::<(String,)>(value)?;
+        let bytes = near_primitives::serialize::from_base64(&encoded)
+            .map_err(|err| RpcParseError(err.to_string()))?;
+        let signed_delegate_action = SignedDelegateAction::try_from_slice(&bytes)
+            .map_err(|err| RpcParseError(format!("Failed to decode delegate action: {}", err)))?;
+        Ok(Self { signed_delegate_action })
+    }
+}
+
+impl RpcRequest for RpcTransactionStatusExtendedRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        let (hash, account_id, light_client_head) = if let Ok((hash, account_id)) =
+            parse_params::<(CryptoHash, AccountId)>(value.clone())
+        {
+            (hash, account_id, None)
+        } else {
+            let (hash, account_id, light_client_head) =
+                parse_params::<(CryptoHash, AccountId, CryptoHash)>(value)?;
+            (hash, account_id, Some(light_client_head))
+        };
+        let transaction_info = TransactionInfo::TransactionId { hash, account_id };
+        Ok(Self { transaction_info, light_client_head })
+    }
+}
+
 impl RpcFrom<actix::MailboxError> for RpcTransactionError {
     fn rpc_from(error: actix::MailboxError) -> Self {
         Self::InternalError { debug_info: error.to_string() }
@@ -0,0 +1,40 @@
+use serde_json::Value;
+
+use near_client_primitives::types::GetValidatorProjectionError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::validator_projection::{
+    RpcValidatorProjectionError, RpcValidatorProjectionRequest,
+};
+
+use super::{parse_params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcValidatorProjectionRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcValidatorProjectionError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetValidatorProjectionError> for RpcValidatorProjectionError {
+    fn rpc_from(error: GetValidatorProjectionError) -> Self {
+        match error {
+            GetValidatorProjectionError::NoAccountId => Self::NoAccountId,
+            GetValidatorProjectionError::NotSyncedYet => Self::NotSyncedYet,
+            GetValidatorProjectionError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetValidatorProjectionError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcValidatorProjectionError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use serde_json::Value;
+
+use near_client_primitives::types::GetValidatorScheduleError;
+use near_jsonrpc_primitives::errors::RpcParseError;
+use near_jsonrpc_primitives::types::validator_schedule::{
+    RpcValidatorScheduleError, RpcValidatorScheduleRequest,
+};
+
+use super::{parse_params, RpcFrom, RpcRequest};
+
+impl RpcRequest for RpcValidatorScheduleRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcValidatorScheduleError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetValidatorScheduleError> for RpcValidatorScheduleError {
+    fn rpc_from(error: GetValidatorScheduleError) -> Self {
+        match error {
+            GetValidatorScheduleError::NoAccountId => Self::NoAccountId,
+            GetValidatorScheduleError::NotSyncedYet => Self::NotSyncedYet,
+            GetValidatorScheduleError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetValidatorScheduleError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcValidatorScheduleError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
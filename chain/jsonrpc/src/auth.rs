@@ -0,0 +1,184 @@
+//! TLS and access control of the RPC, Prometheus and gRPC servers.
+use actix_web::dev::ServiceRequest;
+use actix_web::error::{ErrorForbidden, ErrorUnauthorized};
+use actix_web::http::header;
+use anyhow::Context;
+use ipnet::IpNet;
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use subtle::ConstantTimeEq;
+
+/// Certificate and private key the servers terminate TLS with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RpcTlsConfig {
+    /// PEM file with the certificate chain, starting with the server's
+    /// certificate.
+    pub cert_file: PathBuf,
+    /// PEM file with the private key of the certificate.
+    pub key_file: PathBuf,
+}
+
+impl RpcTlsConfig {
+    pub(crate) fn acceptor(&self) -> anyhow::Result<SslAcceptorBuilder> {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+        builder
+            .set_private_key_file(&self.key_file, SslFiletype::PEM)
+            .with_context(|| format!("Failed loading TLS key from {}", self.key_file.display()))?;
+        builder.set_certificate_chain_file(&self.cert_file).with_context(|| {
+            format!("Failed loading TLS certificate from {}", self.cert_file.display())
+        })?;
+        builder.check_private_key().with_context(|| {
+            format!(
+                "TLS key {} doesn't match certificate {}",
+                self.key_file.display(),
+                self.cert_file.display()
+            )
+        })?;
+        Ok(builder)
+    }
+}
+
+/// Access control of the servers.  Everything is allowed by default.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RpcAuthConfig {
+    /// If not empty, requests must carry one of the tokens in an
+    /// `Authorization: Bearer <token>` header.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bearer_tokens: Vec<String>,
+    /// If not empty, only requests from these IP addresses or networks in
+    /// CIDR notation are served.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_ips: Vec<String>,
+    /// Paths served without a token, e.g. `/status` and `/health` for load
+    /// balancer checks.  The IP allowlist still applies to them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub public_paths: Vec<String>,
+}
+
+/// Reason a request was rejected by [`Authenticator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("IP address not allowed")]
+    IpNotAllowed,
+    #[error("Missing or invalid bearer token")]
+    InvalidToken,
+}
+
+/// Checks requests against `RpcAuthConfig`.
+#[derive(Clone)]
+pub struct Authenticator {
+    bearer_tokens: Vec<String>,
+    allowed_networks: Vec<IpNet>,
+    public_paths: Vec<String>,
+}
+
+impl Authenticator {
+    pub fn new(config: &RpcAuthConfig) -> anyhow::Result<Self> {
+        let allowed_networks = config
+            .allowed_ips
+            .iter()
+            .map(|ip| {
+                ip.parse::<IpNet>().or_else(|_| ip.parse::<IpAddr>().map(IpNet::from)).map_err(
+                    |_| anyhow::anyhow!("Invalid IP address or network {} in allowed_ips", ip),
+                )
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            bearer_tokens: config.bearer_tokens.clone(),
+            allowed_networks,
+            public_paths: config.public_paths.clone(),
+        })
+    }
+
+    pub(crate) fn check_request(&self, req: &ServiceRequest) -> Result<(), actix_web::Error> {
+        let authorization =
+            req.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        self.check(req.path(), req.peer_addr().map(|addr| addr.ip()), authorization).map_err(
+            |err| match err {
+                AuthError::IpNotAllowed => ErrorForbidden(err),
+                AuthError::InvalidToken => ErrorUnauthorized(err),
+            },
+        )
+    }
+
+    /// Checks a request for `path` from `peer_ip` with the value of its
+    /// `Authorization` header.
+    pub fn check(
+        &self,
+        path: &str,
+        peer_ip: Option<IpAddr>,
+        authorization: Option<&str>,
+    ) -> Result<(), AuthError> {
+        if !self.allowed_networks.is_empty() {
+            let allowed = peer_ip.map_or(false, |ip| {
+                self.allowed_networks.iter().any(|network| network.contains(&ip))
+            });
+            if !allowed {
+                return Err(AuthError::IpNotAllowed);
+            }
+        }
+        if self.bearer_tokens.is_empty() || self.public_paths.iter().any(|public| public == path) {
+            return Ok(());
+        }
+        let token = authorization.and_then(|value| value.strip_prefix("Bearer "));
+        let authorized = token.map_or(false, |token| {
+            self.bearer_tokens
+                .iter()
+                .any(|expected| bool::from(expected.as_bytes().ct_eq(token.as_bytes())))
+        });
+        if authorized {
+            Ok(())
+        } else {
+            Err(AuthError::InvalidToken)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let open = Authenticator::new(&RpcAuthConfig::default()).unwrap();
+        assert!(open.check("/", None, None).is_ok());
+
+        let auth = Authenticator::new(&RpcAuthConfig {
+            bearer_tokens: vec!["secret".to_string()],
+            allowed_ips: vec!["10.0.0.0/8".to_string(), "192.168.1.1".to_string()],
+            public_paths: vec!["/status".to_string()],
+        })
+        .unwrap();
+        let inside: IpAddr = "10.1.2.3".parse().unwrap();
+        let host: IpAddr = "192.168.1.1".parse().unwrap();
+        let outside: IpAddr = "192.168.1.2".parse().unwrap();
+        assert!(auth.check("/", Some(inside), Some("Bearer secret")).is_ok());
+        assert!(auth.check("/", Some(host), Some("Bearer secret")).is_ok());
+        assert_eq!(
+            auth.check("/", Some(outside), Some("Bearer secret")),
+            Err(AuthError::IpNotAllowed)
+        );
+        assert!(auth.check("/", None, Some("Bearer secret")).is_err());
+        assert_eq!(
+            auth.check("/", Some(inside), Some("Bearer wrong")),
+            Err(AuthError::InvalidToken)
+        );
+        assert!(auth.check("/", Some(inside), None).is_err());
+        assert!(auth.check("/status", Some(inside), None).is_ok());
+        assert!(auth.check("/status", Some(outside), None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let auth =
+            RpcAuthConfig { allowed_ips: vec!["10.0.0.0/33".to_string()], ..Default::default() };
+        assert!(Authenticator::new(&auth).is_err());
+        let tls = RpcTlsConfig {
+            cert_file: "/nonexistent/cert.pem".into(),
+            key_file: "/nonexistent/key.pem".into(),
+        };
+        assert!(tls.acceptor().is_err());
+    }
+}
@@ -1,32 +1,45 @@
 #![doc = include_str!("../README.md")]
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use actix::{Addr, MailboxError};
 use actix_cors::Cors;
+use actix_web::dev::Service;
 use actix_web::http::header;
 use actix_web::HttpRequest;
 use actix_web::{get, http, middleware, web, App, Error as HttpError, HttpResponse, HttpServer};
+use actix_web_actors::ws;
+use anyhow::Context;
+use futures::future::{ready, Either};
 use futures::Future;
 use futures::FutureExt;
-use near_client_primitives::types::GetSplitStorageInfo;
+use futures::StreamExt;
+use near_client_primitives::types::{
+    GetBlockError, GetSplitStorageInfo, GetStateSnapshotPart, GetStateSnapshotPartError, QueryError,
+};
 
 use near_jsonrpc_primitives::types::split_storage::RpcSplitStorageInfoResponse;
 use near_network::PeerManagerActor;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use tokio::sync::Semaphore;
 use tokio::time::{sleep, timeout};
 use tracing::info;
 
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    ClientActor, DebugStatus, GetBlock, GetBlockProof, GetChunk, GetClientConfig,
-    GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
-    GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, ProcessTxRequest,
-    ProcessTxResponse, Query, Status, TxStatus, ViewClientActor,
+    ClientActor, DbSnapshotter, DebugStatus, GetAccountOutcomes, GetBlock, GetBlockByTimestamp,
+    GetBlockProof, GetChunk, GetClientConfig, GetEpochProtocolConfig, GetExecutionOutcome,
+    GetGasPrice, GetGasPriceEstimate, GetHealth, GetMaintenanceWindows, GetNetworkInfo,
+    GetNextLightClientBlock, GetProtocolConfig, GetProtocolVersionVoting, GetReceipt,
+    GetStateChanges, GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
+    GetValidatorProjection, GetValidatorSchedule, HealthCheckConfig, HealthReport,
+    ProcessTxRequest, ProcessTxResponse, Query, SimulateTransaction, Status, SubscribeHead,
+    TxStatus, TxStatusExtended, ViewCallPool, ViewClientActor,
 };
+use near_crypto::{InMemorySigner, PublicKey};
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_primitives::errors::RpcError;
 use near_jsonrpc_primitives::message::{Message, Request};
@@ -35,15 +48,26 @@ use near_network::tcp;
 use near_o11y::metrics::{prometheus, Encoder, TextEncoder};
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockHeight};
-use near_primitives::views::FinalExecutionOutcomeViewEnum;
+use near_primitives::types::{
+    AccountId, BlockHeight, BlockId, BlockReference, EpochId, Nonce, ShardId,
+};
+use near_primitives::views::{
+    FinalExecutionOutcomeViewEnum, QueryRequest, QueryResponse, QueryResponseKind,
+};
 
 mod api;
+mod auth;
+mod light_client_blocks;
 mod metrics;
+mod relayer;
 
 use api::RpcRequest;
 pub use api::{RpcFrom, RpcInto};
+pub use auth::{AuthError, Authenticator, RpcAuthConfig, RpcTlsConfig};
+use light_client_blocks::LightClientBlocksSession;
 use near_o11y::{WithSpanContext, WithSpanContextExt};
+use relayer::Relayer;
+pub use relayer::RelayerPolicy;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct RpcPollingConfig {
@@ -60,15 +84,52 @@ impl Default for RpcPollingConfig {
     }
 }
 
+fn default_max_batch_size() -> usize {
+    100
+}
+
+fn default_max_concurrent_requests_per_connection() -> usize {
+    10
+}
+
+fn default_max_light_client_block_streams() -> usize {
+    100
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RpcLimitsConfig {
     /// Maximum byte size of the json payload.
     pub json_payload_max_size: usize,
+    /// Maximum number of requests in a JSON RPC batch.  Larger batches are
+    /// rejected as a whole.
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    /// Maximum number of requests of a single connection processed at the
+    /// same time.  Since connections don't pipeline requests, this limits how
+    /// many requests of a batch are processed in parallel.
+    #[serde(default = "default_max_concurrent_requests_per_connection")]
+    pub max_concurrent_requests_per_connection: usize,
+    /// If set, requests to the view client which don't finish within this
+    /// time fail with a timeout error, and queries are aborted.  Clients can
+    /// ask for a shorter timeout with the `X-Request-Timeout-Ms` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<Duration>,
+    /// Maximum number of `/light_client_blocks` streams open at the same
+    /// time.  Further streams are refused until one of them is closed.
+    #[serde(default = "default_max_light_client_block_streams")]
+    pub max_light_client_block_streams: usize,
 }
 
 impl Default for RpcLimitsConfig {
     fn default() -> Self {
-        Self { json_payload_max_size: 10 * 1024 * 1024 }
+        Self {
+            json_payload_max_size: 10 * 1024 * 1024,
+            max_batch_size: default_max_batch_size(),
+            max_concurrent_requests_per_connection: default_max_concurrent_requests_per_connection(
+            ),
+            request_timeout: None,
+            max_light_client_block_streams: default_max_light_client_block_streams(),
+        }
     }
 }
 
@@ -76,11 +137,67 @@ fn default_enable_debug_rpc() -> bool {
     false
 }
 
+/// JSON RPC methods exposing internals of the node rather than chain data.
+/// They are only served by the separate debug listener (see
+/// [`RpcConfig::debug_addr`]), never on the public address.
+const ADMIN_METHODS: &[&str] = &[
+    "client_config",
+    "network_info",
+    "EXPERIMENTAL_db_snapshot",
+    "EXPERIMENTAL_maintenance_windows",
+    "EXPERIMENTAL_protocol_version_voting",
+    "EXPERIMENTAL_split_storage_info",
+    "EXPERIMENTAL_validator_projection",
+    "EXPERIMENTAL_validator_schedule",
+];
+
+fn is_admin_method(method: &str) -> bool {
+    ADMIN_METHODS.contains(&method) || method.starts_with("adv_")
+}
+
+/// Header with the timeout of the request in milliseconds.
+const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+
+tokio::task_local! {
+    /// Deadline of the HTTP request being processed, attached to the messages
+    /// sent to the view client.
+    static REQUEST_DEADLINE: Option<Instant>;
+}
+
+/// Returns the deadline of the request: the shorter of the timeout asked for
+/// in the header and `request_timeout`.
+fn request_deadline(request: &HttpRequest, limits: &RpcLimitsConfig) -> Option<Instant> {
+    let requested = request
+        .headers()
+        .get(REQUEST_TIMEOUT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_millis);
+    let timeout = match (requested, limits.request_timeout) {
+        (Some(requested), Some(limit)) => requested.min(limit),
+        (requested, limit) => requested.or(limit)?,
+    };
+    Some(Instant::now() + timeout)
+}
+
+fn current_deadline() -> Option<Instant> {
+    REQUEST_DEADLINE.try_with(|deadline| *deadline).ok().flatten()
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RpcConfig {
     pub addr: tcp::ListenerAddr,
     // If provided, will start an http server exporting only Prometheus metrics on that address.
     pub prometheus_addr: Option<String>,
+    /// If provided, the debug pages and APIs, `/network_info` and the admin
+    /// JSON RPC methods (such as `client_config`) are served by a separate
+    /// http server on that address rather than on `addr`, which then serves
+    /// only the public endpoints.  Not set by default, in which case the
+    /// debug pages and APIs are served on `addr` and the admin methods
+    /// aren't served at all.  The admin methods and the
+    /// `/debug/api/state_snapshot` endpoint are only served on this address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_addr: Option<String>,
     pub cors_allowed_origins: Vec<String>,
     pub polling_config: RpcPollingConfig,
     #[serde(default)]
@@ -93,6 +210,33 @@ pub struct RpcConfig {
     // be read from this directory, instead of the contents compiled into the binary. This allows
     // for quick iterative development.
     pub experimental_debug_pages_src_path: Option<String>,
+    /// Criteria checked by the `/health` endpoint in addition to the node
+    /// receiving new blocks and not syncing.
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+    /// If provided, both the RPC and the Prometheus servers serve HTTPS
+    /// instead of plain HTTP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<RpcTlsConfig>,
+    /// Bearer tokens and IP allowlist applied to the RPC and the Prometheus
+    /// servers, and to the gRPC server if it's enabled.
+    #[serde(default)]
+    pub auth: RpcAuthConfig,
+    /// If provided, the node relays delegate actions (meta transactions)
+    /// submitted with the `EXPERIMENTAL_relay_delegate_action` method, paying
+    /// for them with the account of this key file.  Meant for testing and
+    /// private chains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayer_key_file: Option<PathBuf>,
+    /// Delegate actions which the relayer pays for.  By default it relays
+    /// delegate actions to any account, as long as they attach no deposit.
+    #[serde(default)]
+    pub relayer_policy: RelayerPolicy,
+    /// If provided, the admin method `EXPERIMENTAL_db_snapshot` creates
+    /// snapshots of the database in this directory.  It can't create them
+    /// anywhere else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_snapshot_dir: Option<PathBuf>,
 }
 
 impl Default for RpcConfig {
@@ -100,21 +244,50 @@ impl Default for RpcConfig {
         RpcConfig {
             addr: tcp::ListenerAddr::new("0.0.0.0:3030".parse().unwrap()),
             prometheus_addr: None,
+            debug_addr: None,
             cors_allowed_origins: vec!["*".to_owned()],
             polling_config: Default::default(),
             limits_config: Default::default(),
             enable_debug_rpc: false,
             experimental_debug_pages_src_path: None,
+            health_check: Default::default(),
+            tls: None,
+            auth: Default::default(),
+            relayer_key_file: None,
+            relayer_policy: Default::default(),
+            db_snapshot_dir: None,
         }
     }
 }
 
 impl RpcConfig {
+    /// Returns config of a server serving everything but the admin methods,
+    /// including the debug pages, on `addr`.
     pub fn new(addr: tcp::ListenerAddr) -> Self {
-        RpcConfig { addr, ..Default::default() }
+        RpcConfig { addr, debug_addr: None, ..Default::default() }
+    }
+
+    /// Checks that the IP allowlist parses and that the TLS certificate and
+    /// key and the relayer key can be loaded, so that a bad config is
+    /// reported when it's read rather than when the servers start.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        Authenticator::new(&self.auth)?;
+        if let Some(tls) = &self.tls {
+            tls.acceptor()?;
+        }
+        if let Some(relayer_key_file) = &self.relayer_key_file {
+            load_relayer_key(relayer_key_file)?;
+        }
+        self.relayer_policy.validate()?;
+        Ok(())
     }
 }
 
+fn load_relayer_key(path: &Path) -> anyhow::Result<InMemorySigner> {
+    InMemorySigner::from_file(path)
+        .with_context(|| format!("Failed loading relayer key from {}", path.display()))
+}
+
 /// Serialises response of a query into JSON to be sent to the client.
 ///
 /// Returns an internal server error if the value fails to serialise.
@@ -122,6 +295,40 @@ fn serialize_response(value: impl serde::ser::Serialize) -> Result<Value, RpcErr
     serde_json::to_value(value).map_err(|err| RpcError::serialization_error(err.to_string()))
 }
 
+/// Processes a specific method call, returning the borsh-encoded result.
+///
+/// Like [`process_method_call`], but for clients requesting results in borsh.
+async fn process_method_call_borsh<R, V, E, F>(
+    request: Request,
+    callback: impl FnOnce(R) -> F,
+) -> Result<Vec<u8>, RpcError>
+where
+    R: RpcRequest,
+    V: near_primitives::borsh::BorshSerialize,
+    RpcError: std::convert::From<E>,
+    F: std::future::Future<Output = Result<V, E>>,
+{
+    callback(R::parse(request.params)?)
+        .await?
+        .try_to_vec()
+        .map_err(|err| RpcError::serialization_error(err.to_string()))
+}
+
+/// Media type of JSON RPC responses with base64 encoded borsh results.  The
+/// `/` endpoint returns them if the request accepts it.
+const BORSH_BASE64_MEDIA_TYPE: &str = "application/borsh+base64";
+/// Media type of the raw borsh results returned by the `/borsh` endpoint.
+const BORSH_MEDIA_TYPE: &str = "application/borsh";
+
+/// Encoding of the results of JSON RPC requests.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ResultEncoding {
+    Json,
+    /// Base64 encoded borsh, supported only by the methods in
+    /// [`JsonRpcHandler::process_borsh_request_internal`].
+    BorshBase64,
+}
+
 /// Processes a specific method call.
 ///
 /// The arguments for the method (which is implemented by the `callback`) will
@@ -222,36 +429,122 @@ fn process_query_response(
     }
 }
 
+#[derive(Clone)]
 struct JsonRpcHandler {
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
+    /// Executes contract function calls, if set.  Otherwise they are executed
+    /// by the view client like other queries.
+    view_call_pool: Option<ViewCallPool>,
     peer_manager_addr: Option<Addr<PeerManagerActor>>,
     polling_config: RpcPollingConfig,
+    limits_config: RpcLimitsConfig,
     genesis_config: GenesisConfig,
     enable_debug_rpc: bool,
     debug_pages_src_path: Option<PathBuf>,
+    health_check_config: HealthCheckConfig,
+    /// Whether the methods listed in [`ADMIN_METHODS`] are served.
+    enable_admin_methods: bool,
+    relayer: Option<Arc<Relayer>>,
+    /// Directory the snapshots are created in and the snapshotter, set if
+    /// `db_snapshot_dir` is configured.
+    db_snapshot: Option<(PathBuf, DbSnapshotter)>,
+    /// Permits for the open `/light_client_blocks` streams.
+    light_client_block_streams: Arc<Semaphore>,
 }
 
 impl JsonRpcHandler {
     pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
+        self.process_with_encoding(message, ResultEncoding::Json).await
+    }
+
+    async fn process_with_encoding(
+        &self,
+        message: Message,
+        encoding: ResultEncoding,
+    ) -> Result<Message, HttpError> {
         let id = message.id();
         match message {
             Message::Request(request) => {
-                Ok(Message::response(id, self.process_request(request).await))
+                Ok(Message::response(id, self.process_encoded_request(request, encoding).await))
             }
+            Message::Batch(messages) => Ok(self.process_batch(messages, encoding).await),
             _ => Ok(Message::error(RpcError::parse_error(
                 "JSON RPC Request format was expected".to_owned(),
             ))),
         }
     }
 
+    /// Processes the requests of a batch, at most
+    /// `max_concurrent_requests_per_connection` of them at a time, and returns
+    /// their responses in the same order.
+    async fn process_batch(&self, messages: Vec<Message>, encoding: ResultEncoding) -> Message {
+        let limits = &self.limits_config;
+        if messages.is_empty() {
+            return Message::error(RpcError::parse_error("Batch must not be empty".to_owned()));
+        }
+        if messages.len() > limits.max_batch_size {
+            return Message::error(RpcError::parse_error(format!(
+                "Batch of {} requests exceeds the limit of {}",
+                messages.len(),
+                limits.max_batch_size
+            )));
+        }
+        let responses: Vec<Message> = futures::stream::iter(messages)
+            .map(|message| async move {
+                match message {
+                    Message::Request(request) => {
+                        let id = request.id.clone();
+                        Message::response(id, self.process_encoded_request(request, encoding).await)
+                    }
+                    _ => Message::error(RpcError::parse_error(
+                        "JSON RPC Request format was expected".to_owned(),
+                    )),
+                }
+            })
+            .buffered(limits.max_concurrent_requests_per_connection.max(1))
+            .collect()
+            .await;
+        Message::Batch(responses)
+    }
+
+    async fn process_encoded_request(
+        &self,
+        request: Request,
+        encoding: ResultEncoding,
+    ) -> Result<Value, RpcError> {
+        match encoding {
+            ResultEncoding::Json => self.process_request(request).await,
+            ResultEncoding::BorshBase64 => self
+                .process_borsh_request(request)
+                .await
+                .map(|bytes| Value::String(near_primitives::serialize::to_base64(bytes))),
+        }
+    }
+
     // `process_request` increments affected metrics but the request processing is done by
     // `process_request_internal`.
     async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
+        self.with_metrics(request, |request| self.process_request_internal(request)).await
+    }
+
+    /// Like [`Self::process_request`], but returns the result encoded in borsh.
+    async fn process_borsh_request(&self, request: Request) -> Result<Vec<u8>, RpcError> {
+        self.with_metrics(request, |request| self.process_borsh_request_internal(request)).await
+    }
+
+    async fn with_metrics<T, F>(
+        &self,
+        request: Request,
+        process: impl FnOnce(Request) -> F,
+    ) -> Result<T, RpcError>
+    where
+        F: Future<Output = Result<T, RpcError>>,
+    {
         let timer = Instant::now();
 
         let request_method = request.method.clone();
-        let response = self.process_request_internal(request).await;
+        let response = process(request).await;
 
         let request_method = match &response {
             Err(err) if err.code == -32_601 => "UNSUPPORTED_METHOD",
@@ -274,6 +567,9 @@ impl JsonRpcHandler {
 
     /// Processes the request without updating any metrics.
     async fn process_request_internal(&self, request: Request) -> Result<Value, RpcError> {
+        if !self.enable_admin_methods && is_admin_method(&request.method) {
+            return Err(RpcError::method_not_found(request.method));
+        }
         let request = match self.process_adversarial_request_internal(request).await {
             Ok(response) => return response,
             Err(request) => request,
@@ -327,9 +623,15 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_changes" => {
                 process_method_call(request, |params| self.changes_in_block_by_type(params)).await
             }
+            "EXPERIMENTAL_account_outcomes" => {
+                process_method_call(request, |params| self.account_outcomes(params)).await
+            }
             "EXPERIMENTAL_changes_in_block" => {
                 process_method_call(request, |params| self.changes_in_block(params)).await
             }
+            "EXPERIMENTAL_db_snapshot" => {
+                process_method_call(request, |params| self.db_snapshot(params)).await
+            }
             "EXPERIMENTAL_check_tx" => {
                 process_method_call(request, |params| self.check_tx(params)).await
             }
@@ -339,6 +641,15 @@ impl JsonRpcHandler {
                 })
                 .await
             }
+            "EXPERIMENTAL_block_by_timestamp" => {
+                process_method_call(request, |params| self.block_by_timestamp(params)).await
+            }
+            "EXPERIMENTAL_epoch_protocol_config" => {
+                process_method_call(request, |params| self.epoch_protocol_config(params)).await
+            }
+            "EXPERIMENTAL_gas_price_estimate" => {
+                process_method_call(request, |params| self.gas_price_estimate(params)).await
+            }
             "EXPERIMENTAL_light_client_proof" => {
                 process_method_call(request, |params| {
                     self.light_client_execution_outcome_proof(params)
@@ -348,12 +659,24 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_protocol_config" => {
                 process_method_call(request, |params| self.protocol_config(params)).await
             }
+            "EXPERIMENTAL_protocol_version_voting" => {
+                process_method_call(request, |params| self.protocol_version_voting(params)).await
+            }
             "EXPERIMENTAL_receipt" => {
                 process_method_call(request, |params| self.receipt(params)).await
             }
+            "EXPERIMENTAL_relay_delegate_action" => {
+                process_method_call(request, |params| self.relay_delegate_action(params)).await
+            }
+            "EXPERIMENTAL_simulate_tx" => {
+                process_method_call(request, |params| self.simulate_tx(params)).await
+            }
             "EXPERIMENTAL_tx_status" => {
                 process_method_call(request, |params| self.tx_status_common(params, true)).await
             }
+            "EXPERIMENTAL_tx_status_extended" => {
+                process_method_call(request, |params| self.tx_status_extended(params)).await
+            }
             "EXPERIMENTAL_validators_ordered" => {
                 process_method_call(request, |params| self.validators_ordered(params)).await
             }
@@ -363,6 +686,12 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_split_storage_info" => {
                 process_method_call(request, |params| self.split_storage_info(params)).await
             }
+            "EXPERIMENTAL_validator_projection" => {
+                process_method_call(request, |params| self.validator_projection(params)).await
+            }
+            "EXPERIMENTAL_validator_schedule" => {
+                process_method_call(request, |params| self.validator_schedule(params)).await
+            }
             #[cfg(feature = "sandbox")]
             "sandbox_patch_state" => {
                 process_method_call(request, |params| self.sandbox_patch_state(params)).await
@@ -375,6 +704,31 @@ impl JsonRpcHandler {
         }
     }
 
+    /// Processes the request of a client asking for the result encoded in borsh.  Only the
+    /// methods returning chain data, which indexers fetch in bulk, support it.
+    async fn process_borsh_request_internal(&self, request: Request) -> Result<Vec<u8>, RpcError> {
+        match request.method.as_ref() {
+            "block" => process_method_call_borsh(request, |params| self.block(params)).await,
+            "EXPERIMENTAL_block_by_timestamp" => {
+                process_method_call_borsh(request, |params| self.block_by_timestamp(params)).await
+            }
+            "chunk" => process_method_call_borsh(request, |params| self.chunk(params)).await,
+            "query" => process_method_call_borsh(request, |params| self.query(params)).await,
+            "EXPERIMENTAL_changes" => {
+                process_method_call_borsh(request, |params| self.changes_in_block_by_type(params))
+                    .await
+            }
+            "EXPERIMENTAL_changes_in_block" => {
+                process_method_call_borsh(request, |params| self.changes_in_block(params)).await
+            }
+            _ => Err(RpcError::new(
+                -32_601,
+                format!("Method {} does not support borsh encoding", request.method),
+                None,
+            )),
+        }
+    }
+
     /// Handles adversarial requests if they are enabled.
     ///
     /// Adversarial requests are only enabled when `test_features` Cargo feature
@@ -429,11 +783,16 @@ impl JsonRpcHandler {
         E: RpcFrom<F>,
         E: RpcFrom<actix::MailboxError>,
     {
-        self.view_client_addr
-            .send(msg.with_span_context())
-            .await
-            .map_err(RpcFrom::rpc_from)?
-            .map_err(RpcFrom::rpc_from)
+        let request = self.view_client_addr.send(msg.with_span_context());
+        // Messages still queued when the deadline passes are dropped without
+        // being handled.
+        let response = match current_deadline() {
+            Some(deadline) => {
+                request.timeout(deadline.saturating_duration_since(Instant::now())).await
+            }
+            None => request.await,
+        };
+        response.map_err(RpcFrom::rpc_from)?.map_err(RpcFrom::rpc_from)
     }
 
     async fn peer_manager_send<M, T, E>(&self, msg: M) -> Result<T, E>
@@ -739,6 +1098,111 @@ impl JsonRpcHandler {
         }
     }
 
+    /// Validates a delegate action, wraps it in a transaction signed by the
+    /// relayer and submits it, waiting for the outcome like
+    /// `broadcast_tx_commit`.
+    async fn relay_delegate_action(
+        &self,
+        request_data: near_jsonrpc_primitives::types::transactions::RpcRelayDelegateActionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::transactions::RpcTransactionResponse,
+        near_jsonrpc_primitives::types::transactions::RpcTransactionError,
+    > {
+        let relayer = self.relayer.as_ref().ok_or(
+            near_jsonrpc_primitives::types::transactions::RpcTransactionError::RelayerNotConfigured,
+        )?;
+        let signed_delegate_action = request_data.signed_delegate_action;
+        relayer.policy().check(&signed_delegate_action).map_err(|error_message| {
+            near_jsonrpc_primitives::types::transactions::RpcTransactionError::InvalidDelegateAction {
+                error_message,
+            }
+        })?;
+        let delegate_action = &signed_delegate_action.delegate_action;
+        let (access_key_nonce, head_height, _) = self
+            .view_access_key_nonce(
+                delegate_action.sender_id.clone(),
+                delegate_action.public_key.clone(),
+            )
+            .await?
+            .ok_or_else(|| {
+                near_jsonrpc_primitives::types::transactions::RpcTransactionError::InvalidDelegateAction {
+                    error_message: format!(
+                        "Access key {} of {} does not exist",
+                        delegate_action.public_key, delegate_action.sender_id
+                    ),
+                }
+            })?;
+        relayer::validate_delegate_action(&signed_delegate_action, head_height, access_key_nonce)
+            .map_err(|error_message| {
+            near_jsonrpc_primitives::types::transactions::RpcTransactionError::InvalidDelegateAction {
+                error_message,
+            }
+        })?;
+        let signer = relayer.signer();
+        let (relayer_nonce, _, block_hash) = self
+            .view_access_key_nonce(signer.account_id.clone(), signer.public_key.clone())
+            .await?
+            .ok_or_else(|| {
+                near_jsonrpc_primitives::types::transactions::RpcTransactionError::InternalError {
+                    debug_info: format!("Relayer access key {} does not exist", signer.public_key),
+                }
+            })?;
+        let signed_transaction = relayer
+            .sign_transaction(signed_delegate_action, relayer.next_nonce(relayer_nonce), block_hash)
+            .map_err(|error_message| {
+                near_jsonrpc_primitives::types::transactions::RpcTransactionError::InvalidDelegateAction {
+                    error_message,
+                }
+            })?;
+        self.send_tx_commit(
+            near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest {
+                signed_transaction,
+            },
+        )
+        .await
+    }
+
+    /// Returns the nonce of an access key, if it exists, with the height and
+    /// hash of the latest block at which it was read.
+    async fn view_access_key_nonce(
+        &self,
+        account_id: AccountId,
+        public_key: PublicKey,
+    ) -> Result<
+        Option<(Nonce, BlockHeight, CryptoHash)>,
+        near_jsonrpc_primitives::types::transactions::RpcTransactionError,
+    > {
+        let query = Query::new(
+            BlockReference::latest(),
+            QueryRequest::ViewAccessKey { account_id, public_key },
+        );
+        let response = self
+            .view_client_addr
+            .send(query.with_span_context())
+            .await
+            .map_err(RpcFrom::rpc_from)?;
+        match response {
+            Ok(QueryResponse {
+                kind: QueryResponseKind::AccessKey(access_key),
+                block_height,
+                block_hash,
+            }) => Ok(Some((access_key.nonce, block_height, block_hash))),
+            Err(QueryError::UnknownAccount { .. } | QueryError::UnknownAccessKey { .. }) => {
+                Ok(None)
+            }
+            Ok(response) => Err(
+                near_jsonrpc_primitives::types::transactions::RpcTransactionError::InternalError {
+                    debug_info: format!("Unexpected query response {:?}", response.kind),
+                },
+            ),
+            Err(err) => Err(
+                near_jsonrpc_primitives::types::transactions::RpcTransactionError::InternalError {
+                    debug_info: err.to_string(),
+                },
+            ),
+        }
+    }
+
     async fn health(
         &self,
     ) -> Result<
@@ -749,6 +1213,14 @@ impl JsonRpcHandler {
         Ok(status.rpc_into())
     }
 
+    /// Runs the health check with the criteria configured for the `/health`
+    /// endpoint.
+    async fn health_report(
+        &self,
+    ) -> Result<HealthReport, near_jsonrpc_primitives::types::status::RpcStatusError> {
+        self.client_send(GetHealth { config: self.health_check_config.clone() }).await
+    }
+
     pub async fn status(
         &self,
     ) -> Result<
@@ -808,6 +1280,16 @@ impl JsonRpcHandler {
                     "/debug/api/requested_state_parts" => {
                         self.client_send(DebugStatus::RequestedStateParts).await?.rpc_into()
                     }
+                    "/debug/api/memory_usage" => {
+                        self.client_send(DebugStatus::MemoryUsage).await?.rpc_into()
+                    }
+                    "/debug/api/reorgs" => self.client_send(DebugStatus::Reorgs).await?.rpc_into(),
+                    "/debug/api/finality" => {
+                        self.client_send(DebugStatus::Finality).await?.rpc_into()
+                    }
+                    "/debug/api/block_propagation" => {
+                        self.client_send(DebugStatus::BlockPropagation).await?.rpc_into()
+                    }
                     "/debug/api/peer_store" => self
                         .peer_manager_send(near_network::debug::GetDebugStatus::PeerStore)
                         .await?
@@ -816,6 +1298,10 @@ impl JsonRpcHandler {
                         .peer_manager_send(near_network::debug::GetDebugStatus::Graph)
                         .await?
                         .rpc_into(),
+                    "/debug/api/network_bandwidth" => self
+                        .peer_manager_send(near_network::debug::GetDebugStatus::Bandwidth)
+                        .await?
+                        .rpc_into(),
                     _ => return Ok(None),
                 };
             return Ok(Some(near_jsonrpc_primitives::types::status::RpcDebugStatusResponse {
@@ -856,6 +1342,47 @@ impl JsonRpcHandler {
         Ok(RpcProtocolConfigResponse { config_view })
     }
 
+    async fn epoch_protocol_config(
+        &self,
+        request_data: near_jsonrpc_primitives::types::config::RpcEpochProtocolConfigRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::config::RpcEpochProtocolConfigResponse,
+        near_jsonrpc_primitives::types::config::RpcProtocolConfigError,
+    > {
+        let (epoch_id, config_view) =
+            self.view_client_send(GetEpochProtocolConfig(request_data.epoch_reference)).await?;
+        Ok(near_jsonrpc_primitives::types::config::RpcEpochProtocolConfigResponse {
+            epoch_id,
+            config_view,
+        })
+    }
+
+    async fn simulate_tx(
+        &self,
+        request_data: near_jsonrpc_primitives::types::simulation::RpcSimulateTransactionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::simulation::RpcSimulateTransactionResponse,
+        near_jsonrpc_primitives::types::simulation::RpcSimulateTransactionError,
+    > {
+        let simulation = SimulateTransaction {
+            block_reference: request_data.block_reference,
+            transaction: request_data.transaction,
+            signature: request_data.signature,
+            deadline: current_deadline(),
+        };
+        // Simulations execute contracts like function calls, so they go to
+        // the view call pool too.
+        let simulation = match &self.view_call_pool {
+            Some(view_call_pool) => {
+                view_call_pool.simulate_transaction(simulation).await.map_err(RpcFrom::rpc_from)?
+            }
+            None => self.view_client_send(simulation).await?,
+        };
+        Ok(near_jsonrpc_primitives::types::simulation::RpcSimulateTransactionResponse {
+            simulation,
+        })
+    }
+
     async fn query(
         &self,
         request_data: near_jsonrpc_primitives::types::query::RpcQueryRequest,
@@ -863,9 +1390,14 @@ impl JsonRpcHandler {
         near_jsonrpc_primitives::types::query::RpcQueryResponse,
         near_jsonrpc_primitives::types::query::RpcQueryError,
     > {
-        let query_response = self
-            .view_client_send(Query::new(request_data.block_reference, request_data.request))
-            .await?;
+        let query = Query::new(request_data.block_reference, request_data.request)
+            .with_deadline(current_deadline());
+        let query_response = match (&self.view_call_pool, &query.request) {
+            (Some(view_call_pool), QueryRequest::CallFunction { .. }) => {
+                view_call_pool.query(query).await.map_err(RpcFrom::rpc_from)?
+            }
+            _ => self.view_client_send(query).await?,
+        };
         Ok(query_response.rpc_into())
     }
 
@@ -881,6 +1413,45 @@ impl JsonRpcHandler {
         Ok(tx_status.rpc_into())
     }
 
+    async fn tx_status_extended(
+        &self,
+        request_data: near_jsonrpc_primitives::types::transactions::RpcTransactionStatusExtendedRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::transactions::RpcTransactionStatusExtendedResponse,
+        near_jsonrpc_primitives::types::transactions::RpcTransactionError,
+    > {
+        let (tx_hash, signer_account_id) = match &request_data.transaction_info {
+            near_jsonrpc_primitives::types::transactions::TransactionInfo::Transaction(tx) => {
+                (tx.get_hash(), tx.transaction.signer_id.clone())
+            }
+            near_jsonrpc_primitives::types::transactions::TransactionInfo::TransactionId {
+                hash,
+                account_id,
+            } => (*hash, account_id.clone()),
+        };
+        // Wait for the transaction to get executed first, same as tx status does.
+        self.tx_status_fetch(request_data.transaction_info, true).await?;
+        match self
+            .view_client_send(TxStatusExtended {
+                tx_hash,
+                signer_account_id,
+                light_client_head: request_data.light_client_head,
+            })
+            .await?
+        {
+            Some(final_execution_outcome) => Ok(
+                near_jsonrpc_primitives::types::transactions::RpcTransactionStatusExtendedResponse {
+                    final_execution_outcome,
+                },
+            ),
+            None => Err(
+                near_jsonrpc_primitives::types::transactions::RpcTransactionError::UnknownTransaction {
+                    requested_transaction_hash: tx_hash,
+                },
+            ),
+        }
+    }
+
     async fn block(
         &self,
         request_data: near_jsonrpc_primitives::types::blocks::RpcBlockRequest,
@@ -892,6 +1463,22 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::blocks::RpcBlockResponse { block_view })
     }
 
+    async fn block_by_timestamp(
+        &self,
+        request_data: near_jsonrpc_primitives::types::blocks::RpcBlockByTimestampRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::blocks::RpcBlockResponse,
+        near_jsonrpc_primitives::types::blocks::RpcBlockError,
+    > {
+        let block_view = self
+            .view_client_send(GetBlockByTimestamp {
+                timestamp: request_data.timestamp_nanosec,
+                timestamp_match: request_data.timestamp_match,
+            })
+            .await?;
+        Ok(near_jsonrpc_primitives::types::blocks::RpcBlockResponse { block_view })
+    }
+
     async fn chunk(
         &self,
         request_data: near_jsonrpc_primitives::types::chunks::RpcChunkRequest,
@@ -904,6 +1491,30 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::chunks::RpcChunkResponse { chunk_view })
     }
 
+    /// Returns the ids of transactions and receipts executed on an account,
+    /// grouped by block.  Only available if the node indexes them.
+    async fn account_outcomes(
+        &self,
+        request: near_jsonrpc_primitives::types::account_outcomes::RpcAccountOutcomesRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::account_outcomes::RpcAccountOutcomesResponse,
+        near_jsonrpc_primitives::types::account_outcomes::RpcAccountOutcomesError,
+    > {
+        let near_jsonrpc_primitives::types::account_outcomes::RpcAccountOutcomesRequest {
+            account_id,
+            from_height,
+            limit,
+        } = request;
+        let max_limit =
+            near_jsonrpc_primitives::types::account_outcomes::MAX_ACCOUNT_OUTCOMES_LIMIT;
+        let limit = limit.unwrap_or(max_limit).min(max_limit);
+        let outcomes =
+            self.view_client_send(GetAccountOutcomes { account_id, from_height, limit }).await?;
+        Ok(near_jsonrpc_primitives::types::account_outcomes::RpcAccountOutcomesResponse {
+            outcomes,
+        })
+    }
+
     async fn receipt(
         &self,
         request_data: near_jsonrpc_primitives::types::receipts::RpcReceiptRequest,
@@ -960,6 +1571,7 @@ impl JsonRpcHandler {
             .view_client_send(GetStateChanges {
                 block_hash,
                 state_changes_request: request.state_changes_request,
+                deadline: current_deadline(),
             })
             .await?;
 
@@ -1044,6 +1656,21 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::gas_price::RpcGasPriceResponse { gas_price_view })
     }
 
+    async fn gas_price_estimate(
+        &self,
+        request_data: near_jsonrpc_primitives::types::gas_price::RpcGasPriceEstimateRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::gas_price::RpcGasPriceEstimateResponse,
+        near_jsonrpc_primitives::types::gas_price::RpcGasPriceError,
+    > {
+        let gas_price_estimate = self
+            .view_client_send(GetGasPriceEstimate { percentiles: request_data.percentiles })
+            .await?;
+        Ok(near_jsonrpc_primitives::types::gas_price::RpcGasPriceEstimateResponse {
+            gas_price_estimate,
+        })
+    }
+
     async fn validators(
         &self,
         request_data: near_jsonrpc_primitives::types::validator::RpcValidatorRequest,
@@ -1073,6 +1700,23 @@ impl JsonRpcHandler {
         Ok(validators)
     }
 
+    /// Returns the protocol versions the block producers of the current epoch
+    /// voted for and the version projected to be adopted.
+    async fn protocol_version_voting(
+        &self,
+        _request: near_jsonrpc_primitives::types::protocol_version_voting::RpcProtocolVersionVotingRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::protocol_version_voting::RpcProtocolVersionVotingResponse,
+        near_jsonrpc_primitives::types::protocol_version_voting::RpcProtocolVersionVotingError,
+    > {
+        let voting = self.view_client_send(GetProtocolVersionVoting {}).await?;
+        Ok(
+            near_jsonrpc_primitives::types::protocol_version_voting::RpcProtocolVersionVotingResponse {
+                voting,
+            },
+        )
+    }
+
     /// If experimental_debug_pages_src_path config is set, reads the html file from that
     /// directory. Otherwise, returns None.
     fn read_html_file_override(&self, html_file: &'static str) -> Option<String> {
@@ -1101,6 +1745,44 @@ impl JsonRpcHandler {
         Ok(windows.iter().map(|r| (r.start, r.end)).collect())
     }
 
+    /// Returns the upcoming block and chunk production slots of the specified
+    /// account, or of the validator of the node, in the current and the next
+    /// epoch.
+    async fn validator_schedule(
+        &self,
+        request: near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleResponse,
+        near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleError,
+    > {
+        let near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleRequest {
+            account_id,
+        } = request;
+        let schedule = self.view_client_send(GetValidatorSchedule { account_id }).await?;
+        Ok(near_jsonrpc_primitives::types::validator_schedule::RpcValidatorScheduleResponse {
+            schedule,
+        })
+    }
+
+    /// Returns whether the specified account, or the validator of the node,
+    /// would get a seat with its current proposal or stake, along with its
+    /// stake and the reward it would get if the current epoch ended now.
+    async fn validator_projection(
+        &self,
+        request: near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionResponse,
+        near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionError,
+    > {
+        let near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionRequest {
+            account_id,
+        } = request;
+        let projection = self.view_client_send(GetValidatorProjection { account_id }).await?;
+        Ok(near_jsonrpc_primitives::types::validator_projection::RpcValidatorProjectionResponse {
+            projection,
+        })
+    }
+
     async fn client_config(
         &self,
     ) -> Result<
@@ -1111,6 +1793,38 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::client_config::RpcClientConfigResponse { client_config })
     }
 
+    /// Creates a snapshot of the database in a new directory of
+    /// `db_snapshot_dir`.
+    async fn db_snapshot(
+        &self,
+        request: near_jsonrpc_primitives::types::db_snapshot::RpcDbSnapshotRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::db_snapshot::RpcDbSnapshotResponse,
+        near_jsonrpc_primitives::types::db_snapshot::RpcDbSnapshotError,
+    > {
+        use near_jsonrpc_primitives::types::db_snapshot::{
+            RpcDbSnapshotError, RpcDbSnapshotResponse,
+        };
+        let (dir, snapshotter) =
+            self.db_snapshot.as_ref().ok_or(RpcDbSnapshotError::SnapshotsDisabled)?;
+        let name = request.name;
+        // Only a single normal component, so that the snapshot can't be
+        // created outside of the directory.
+        let mut components = std::path::Path::new(&name).components();
+        match (components.next(), components.next()) {
+            (Some(std::path::Component::Normal(_)), None) => {}
+            _ => return Err(RpcDbSnapshotError::InvalidName { name }),
+        }
+        let path = dir.join(&name);
+        let manifest = snapshotter.create(path.clone()).await.map_err(RpcFrom::rpc_from)?;
+        Ok(RpcDbSnapshotResponse {
+            path,
+            chain_id: manifest.chain_id,
+            head_height: manifest.head_height,
+            db_version: manifest.db_version,
+        })
+    }
+
     pub async fn split_storage_info(
         &self,
         _request_data: near_jsonrpc_primitives::types::split_storage::RpcSplitStorageInfoRequest,
@@ -1136,6 +1850,7 @@ impl JsonRpcHandler {
             .send(
                 near_client_primitives::types::SandboxMessage::SandboxPatchState(
                     patch_state_request.records,
+                    patch_state_request.balances,
                 )
                 .with_span_context(),
             )
@@ -1338,14 +2053,48 @@ impl JsonRpcHandler {
 }
 
 fn rpc_handler(
+    request: HttpRequest,
     message: web::Json<Message>,
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    let accepts_borsh = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.split(',').any(|t| t.trim() == BORSH_BASE64_MEDIA_TYPE));
+    let encoding = if accepts_borsh { ResultEncoding::BorshBase64 } else { ResultEncoding::Json };
+    let deadline = request_deadline(&request, &handler.limits_config);
     let response = async move {
-        let message = handler.process(message.0).await?;
+        let message = handler.process_with_encoding(message.0, encoding).await?;
         Ok(HttpResponse::Ok().json(&message))
     };
-    response.boxed()
+    REQUEST_DEADLINE.scope(deadline, response).boxed()
+}
+
+/// Handles a JSON RPC request returning the raw borsh-encoded result.  Errors
+/// are returned as JSON RPC responses, with a JSON content type.
+fn rpc_borsh_handler(
+    request: HttpRequest,
+    message: web::Json<Message>,
+    handler: web::Data<JsonRpcHandler>,
+) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    let deadline = request_deadline(&request, &handler.limits_config);
+    let response = async move {
+        let request = match message.0 {
+            Message::Request(request) => request,
+            _ => {
+                return Ok(HttpResponse::Ok().json(&Message::error(RpcError::parse_error(
+                    "JSON RPC Request format was expected".to_owned(),
+                ))))
+            }
+        };
+        let id = request.id.clone();
+        Ok(match handler.process_borsh_request(request).await {
+            Ok(bytes) => HttpResponse::Ok().content_type(BORSH_MEDIA_TYPE).body(bytes),
+            Err(err) => HttpResponse::Ok().json(&Message::response(id, Err(err))),
+        })
+    };
+    REQUEST_DEADLINE.scope(deadline, response).boxed()
 }
 
 fn status_handler(
@@ -1392,12 +2141,127 @@ async fn debug_block_status_handler(
     }
 }
 
+#[derive(Deserialize)]
+struct StateSnapshotQuery {
+    /// Part to start streaming from, to resume an interrupted transfer.
+    #[serde(default)]
+    from_part: u64,
+}
+
+/// Streams the state parts of a shard as of `sync_hash`, the first block of an
+/// epoch, starting from `from_part`.  Each part is sent as its id and its
+/// length, both as little-endian u64, followed by its data.  The total number
+/// of parts is sent in the `x-near-num-parts` header.  The parts count towards
+/// the limits on serving state sync requests of peers; the stream ends with an
+/// error when a limit is hit, and the requesting node resumes it later.
+async fn state_snapshot_handler(
+    path: web::Path<(ShardId, CryptoHash)>,
+    query: web::Query<StateSnapshotQuery>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let (shard_id, sync_hash) = path.into_inner();
+    let view_client_addr = handler.view_client_addr.clone();
+    let get_part = move |part_id: u64| {
+        let view_client_addr = view_client_addr.clone();
+        async move {
+            let part = view_client_addr
+                .send(GetStateSnapshotPart { shard_id, sync_hash, part_id }.with_span_context())
+                .await
+                .map_err(|err| GetStateSnapshotPartError::InternalError(err.to_string()))??;
+            let mut frame = Vec::with_capacity(16 + part.data.len());
+            frame.extend_from_slice(&part_id.to_le_bytes());
+            frame.extend_from_slice(&(part.data.len() as u64).to_le_bytes());
+            frame.extend_from_slice(&part.data);
+            Ok::<_, GetStateSnapshotPartError>((part.num_parts, web::Bytes::from(frame)))
+        }
+    };
+    // Fetch the first part before responding so that invalid requests get an
+    // error status instead of an empty stream.
+    let from_part = query.from_part;
+    let (num_parts, first) = match get_part(from_part).await {
+        Ok(part) => part,
+        Err(err @ GetStateSnapshotPartError::InvalidRequest(_)) => {
+            return Ok(HttpResponse::BadRequest().body(err.to_string()))
+        }
+        Err(err @ GetStateSnapshotPartError::UnknownBlock(_)) => {
+            return Ok(HttpResponse::NotFound().body(err.to_string()))
+        }
+        Err(err @ GetStateSnapshotPartError::Throttled) => {
+            return Ok(HttpResponse::ServiceUnavailable().body(err.to_string()))
+        }
+        Err(err) => return Ok(HttpResponse::InternalServerError().body(err.to_string())),
+    };
+    let rest = futures::stream::try_unfold(from_part + 1, move |part_id| {
+        let get_part = get_part.clone();
+        async move {
+            if part_id >= num_parts {
+                return Ok(None);
+            }
+            let (_, frame) = get_part(part_id)
+                .await
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+            Ok::<_, std::io::Error>(Some((frame, part_id + 1)))
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(("x-near-num-parts", num_parts.to_string()))
+        .streaming(futures::stream::once(ready(Ok(first))).chain(rest)))
+}
+
+#[derive(Deserialize)]
+struct LightClientBlocksQuery {
+    /// Block in the epoch to start streaming from.  The current epoch if not set.
+    from_block_hash: Option<CryptoHash>,
+}
+
+/// Streams the light client blocks of the last final blocks of the epochs,
+/// starting from the epoch of `from_block_hash`, over a WebSocket.  Blocks of
+/// the epochs which already ended are sent right away, then a block is sent
+/// whenever an epoch ends, so relayers don't need to poll
+/// `next_light_client_block`.
+async fn light_client_blocks_handler(
+    req: HttpRequest,
+    payload: web::Payload,
+    query: web::Query<LightClientBlocksQuery>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let permit = match handler.light_client_block_streams.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            return Ok(HttpResponse::ServiceUnavailable()
+                .body("Too many light client block streams, try again later"))
+        }
+    };
+    let block_reference = match query.from_block_hash {
+        Some(hash) => BlockReference::BlockId(BlockId::Hash(hash)),
+        None => BlockReference::latest(),
+    };
+    let epoch_id =
+        match handler.view_client_addr.send(GetBlock(block_reference).with_span_context()).await {
+            Ok(Ok(block)) => EpochId(block.header.epoch_id),
+            Ok(Err(err @ GetBlockError::UnknownBlock { .. })) => {
+                return Ok(HttpResponse::NotFound().body(err.to_string()))
+            }
+            Ok(Err(err)) => return Ok(HttpResponse::InternalServerError().body(err.to_string())),
+            Err(_) => return Ok(HttpResponse::ServiceUnavailable().finish()),
+        };
+    let head = match handler.client_addr.send(SubscribeHead {}.with_span_context()).await {
+        Ok(head) => head,
+        Err(_) => return Ok(HttpResponse::ServiceUnavailable().finish()),
+    };
+    let blocks =
+        light_client_blocks::light_client_blocks(handler.view_client_addr.clone(), head, epoch_id);
+    ws::start(LightClientBlocksSession::new(blocks, permit), &req, payload)
+}
+
 fn health_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
     let response = async move {
-        match handler.health().await {
-            Ok(value) => Ok(HttpResponse::Ok().json(&value)),
+        match handler.health_report().await {
+            Ok(report) if report.healthy => Ok(HttpResponse::Ok().json(&report)),
+            Ok(report) => Ok(HttpResponse::ServiceUnavailable().json(&report)),
             Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
         }
     };
@@ -1463,6 +2327,7 @@ fn get_cors(cors_allowed_origins: &[String]) -> Cors {
     cors.allowed_methods(vec!["GET", "POST"])
         .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
         .allowed_header(http::header::CONTENT_TYPE)
+        .allowed_header(REQUEST_TIMEOUT_HEADER)
         .max_age(3600)
 }
 
@@ -1499,6 +2364,7 @@ async fn display_debug_html(
         "chain_n_chunk_info" => Some(debug_page_string!("chain_n_chunk_info.html", handler)),
         "sync" => Some(debug_page_string!("sync.html", handler)),
         "validator" => Some(debug_page_string!("validator.html", handler)),
+        "status" => Some(debug_page_string!("status.html", handler)),
         _ => None,
     };
 
@@ -1510,81 +2376,155 @@ async fn display_debug_html(
     }
 }
 
+/// Registers the public endpoints: JSON RPC calls, `/status`, `/health`,
+/// `/metrics` and `/light_client_blocks`.
+fn configure_public_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/").route(web::post().to(rpc_handler)))
+        .service(web::resource("/borsh").route(web::post().to(rpc_borsh_handler)))
+        .service(
+            web::resource("/status")
+                .route(web::get().to(status_handler))
+                .route(web::head().to(status_handler)),
+        )
+        .service(
+            web::resource("/health")
+                .route(web::get().to(health_handler))
+                .route(web::head().to(health_handler)),
+        )
+        .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
+        .service(
+            web::resource("/light_client_blocks").route(web::get().to(light_client_blocks_handler)),
+        );
+}
+
+/// Registers the endpoints exposing internals of the node: network info, the
+/// debug APIs and pages and the client config.
+fn configure_debug_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/network_info").route(web::get().to(network_info_handler)))
+        .service(
+            web::resource("/tier1_network_info").route(web::get().to(tier1_network_info_handler)),
+        )
+        .service(web::resource("/debug/api/{api}").route(web::get().to(debug_handler)))
+        .service(
+            web::resource("/debug/api/block_status/{starting_height}")
+                .route(web::get().to(debug_block_status_handler)),
+        )
+        .service(web::resource("/debug/client_config").route(web::get().to(client_config_handler)))
+        .service(debug_html)
+        .service(display_debug_html);
+}
+
+/// Registers the endpoint streaming state parts for shard handover.  It's
+/// served only by the separate debug listener, never on the public address.
+fn configure_state_handover_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/debug/api/state_snapshot/{shard_id}/{sync_hash}")
+            .route(web::get().to(state_snapshot_handler)),
+    );
+}
+
 /// Starts HTTP server(s) listening for RPC requests.
 ///
 /// Starts an HTTP server which handles JSON RPC calls as well as states
 /// endpoints such as `/status`, `/health`, `/metrics` etc.  Depending on
 /// configuration may also start another HTTP server just for providing
-/// Prometheus metrics (i.e. covering the `/metrics` path).
+/// Prometheus metrics (i.e. covering the `/metrics` path) and one serving the
+/// debug pages and admin methods (see [`RpcConfig::debug_addr`]).
 ///
 /// Returns a vector of servers that have been started.  Each server is returned
 /// as a tuple containing a name of the server (e.g. `"JSON RPC"`) which can be
 /// used in diagnostic messages and a [`actix_web::dev::Server`] object which
-/// can be used to control the server (most notably stop it).
+/// can be used to control the server (most notably stop it).  Fails if
+/// a server can't be started, e.g. because its address is taken or the TLS
+/// certificate can't be loaded.
 pub fn start_http(
     config: RpcConfig,
     genesis_config: GenesisConfig,
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
+    view_call_pool: Option<ViewCallPool>,
+    db_snapshotter: Option<DbSnapshotter>,
     peer_manager_addr: Option<Addr<PeerManagerActor>>,
-) -> Vec<(&'static str, actix_web::dev::ServerHandle)> {
+) -> anyhow::Result<Vec<(&'static str, actix_web::dev::ServerHandle)>> {
     let RpcConfig {
         addr,
         prometheus_addr,
+        debug_addr,
         cors_allowed_origins,
         polling_config,
         limits_config,
         enable_debug_rpc,
         experimental_debug_pages_src_path: debug_pages_src_path,
+        health_check: health_check_config,
+        tls,
+        auth,
+        relayer_key_file,
+        relayer_policy,
+        db_snapshot_dir,
     } = config;
+    let relayer = match relayer_key_file {
+        Some(path) => {
+            let signer = load_relayer_key(&path)?;
+            info!(target: "network", "Relaying delegate actions as {}", signer.account_id);
+            Some(Arc::new(Relayer::new(signer, relayer_policy)))
+        }
+        None => None,
+    };
     let prometheus_addr = prometheus_addr.filter(|it| it != &addr.to_string());
-    let cors_allowed_origins_clone = cors_allowed_origins.clone();
+    let debug_addr = debug_addr.filter(|it| it != &addr.to_string());
+    let json_payload_max_size = limits_config.json_payload_max_size;
+    let light_client_block_streams =
+        Arc::new(Semaphore::new(limits_config.max_light_client_block_streams));
+    let handler = JsonRpcHandler {
+        client_addr,
+        view_client_addr,
+        view_call_pool,
+        peer_manager_addr,
+        polling_config,
+        limits_config,
+        genesis_config,
+        enable_debug_rpc,
+        debug_pages_src_path: debug_pages_src_path.map(Into::into),
+        health_check_config,
+        // Only the debug listener serves the admin methods.
+        enable_admin_methods: false,
+        relayer,
+        db_snapshot: db_snapshot_dir.zip(db_snapshotter),
+        light_client_block_streams,
+    };
+    let authenticator = Authenticator::new(&auth)?;
     info!(target:"network", "Starting http server at {}", addr);
     let mut servers = Vec::new();
-    let server = HttpServer::new(move || {
-        App::new()
-            .wrap(get_cors(&cors_allowed_origins))
-            .app_data(web::Data::new(JsonRpcHandler {
-                client_addr: client_addr.clone(),
-                view_client_addr: view_client_addr.clone(),
-                peer_manager_addr: peer_manager_addr.clone(),
-                polling_config,
-                genesis_config: genesis_config.clone(),
-                enable_debug_rpc,
-                debug_pages_src_path: debug_pages_src_path.clone().map(Into::into),
-            }))
-            .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
-            .wrap(middleware::Logger::default())
-            .service(web::resource("/").route(web::post().to(rpc_handler)))
-            .service(
-                web::resource("/status")
-                    .route(web::get().to(status_handler))
-                    .route(web::head().to(status_handler)),
-            )
-            .service(
-                web::resource("/health")
-                    .route(web::get().to(health_handler))
-                    .route(web::head().to(health_handler)),
-            )
-            .service(web::resource("/network_info").route(web::get().to(network_info_handler)))
-            .service(
-                web::resource("/tier1_network_info")
-                    .route(web::get().to(tier1_network_info_handler)),
-            )
-            .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
-            .service(web::resource("/debug/api/{api}").route(web::get().to(debug_handler)))
-            .service(
-                web::resource("/debug/api/block_status/{starting_height}")
-                    .route(web::get().to(debug_block_status_handler)),
-            )
-            .service(
-                web::resource("/debug/client_config").route(web::get().to(client_config_handler)),
-            )
-            .service(debug_html)
-            .service(display_debug_html)
-    })
-    .listen(addr.std_listener().unwrap())
-    .unwrap()
+    let server = HttpServer::new({
+        let handler = handler.clone();
+        let authenticator = authenticator.clone();
+        let cors_allowed_origins = cors_allowed_origins.clone();
+        // Without a separate debug listener, the main one serves the debug pages.
+        let serve_debug_routes = debug_addr.is_none();
+        move || {
+            let authenticator = authenticator.clone();
+            App::new()
+                .wrap_fn(move |req, srv| match authenticator.check_request(&req) {
+                    Ok(()) => Either::Left(srv.call(req)),
+                    Err(err) => Either::Right(ready(Err(err))),
+                })
+                .wrap(get_cors(&cors_allowed_origins))
+                .app_data(web::Data::new(handler.clone()))
+                .app_data(web::JsonConfig::default().limit(json_payload_max_size))
+                .wrap(middleware::Logger::default())
+                .configure(configure_public_routes)
+                .configure(|cfg| {
+                    if serve_debug_routes {
+                        configure_debug_routes(cfg)
+                    }
+                })
+        }
+    });
+    let server = match &tls {
+        Some(tls) => server.listen_openssl(addr.std_listener()?, tls.acceptor()?),
+        None => server.listen(addr.std_listener()?),
+    }
+    .with_context(|| format!("Failed to listen on {}", addr))?
     .workers(4)
     .shutdown_timeout(5)
     .disable_signals()
@@ -1594,18 +2534,65 @@ pub fn start_http(
 
     tokio::spawn(server);
 
+    if let Some(debug_addr) = debug_addr {
+        info!(target:"network", "Starting http debug server at {}", debug_addr);
+        // Serves the public endpoints as well, so that the debug pages and
+        // admin methods can be used on their own.
+        let handler = JsonRpcHandler { enable_admin_methods: true, ..handler };
+        let server = HttpServer::new({
+            let authenticator = authenticator.clone();
+            let cors_allowed_origins = cors_allowed_origins.clone();
+            move || {
+                let authenticator = authenticator.clone();
+                App::new()
+                    .wrap_fn(move |req, srv| match authenticator.check_request(&req) {
+                        Ok(()) => Either::Left(srv.call(req)),
+                        Err(err) => Either::Right(ready(Err(err))),
+                    })
+                    .wrap(get_cors(&cors_allowed_origins))
+                    .app_data(web::Data::new(handler.clone()))
+                    .app_data(web::JsonConfig::default().limit(json_payload_max_size))
+                    .wrap(middleware::Logger::default())
+                    .configure(configure_public_routes)
+                    .configure(configure_debug_routes)
+                    .configure(configure_state_handover_routes)
+            }
+        });
+        let server = match &tls {
+            Some(tls) => server.bind_openssl(&debug_addr, tls.acceptor()?),
+            None => server.bind(&debug_addr),
+        }
+        .with_context(|| format!("Failed to bind the debug RPC to {}", debug_addr))?
+        .workers(1)
+        .shutdown_timeout(5)
+        .disable_signals()
+        .run();
+
+        servers.push(("Debug RPC", server.handle()));
+
+        tokio::spawn(server);
+    }
+
     if let Some(prometheus_addr) = prometheus_addr {
         info!(target:"network", "Starting http monitoring server at {}", prometheus_addr);
         // Export only the /metrics service. It's a read-only service and can have very relaxed
         // access restrictions.
         let server = HttpServer::new(move || {
+            let authenticator = authenticator.clone();
             App::new()
-                .wrap(get_cors(&cors_allowed_origins_clone))
+                .wrap_fn(move |req, srv| match authenticator.check_request(&req) {
+                    Ok(()) => Either::Left(srv.call(req)),
+                    Err(err) => Either::Right(ready(Err(err))),
+                })
+                .wrap(get_cors(&cors_allowed_origins))
                 .wrap(middleware::Logger::default())
                 .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
-        })
-        .bind(prometheus_addr)
-        .unwrap()
+        });
+        let server = match &tls {
+            Some(tls) => server.bind_openssl(&prometheus_addr, tls.acceptor()?),
+            None => server.bind(&prometheus_addr),
+        }
+        .with_context(|| format!("Failed to bind Prometheus metrics to {}", prometheus_addr))?
         .workers(2)
         .shutdown_timeout(5)
         .disable_signals()
@@ -1616,5 +2603,5 @@ pub fn start_http(
         tokio::spawn(server);
     }
 
-    servers
+    Ok(servers)
 }
@@ -0,0 +1,128 @@
+//! WebSocket stream of the light client blocks of the epochs, served at
+//! `/light_client_blocks`.
+
+use std::sync::Arc;
+
+use actix::{Actor, ActorContext, Addr, AsyncContext, StreamHandler};
+use actix_web_actors::ws;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use near_client::{EpochLightClientBlock, GetEpochLightClientBlock, ViewClientActor};
+use near_o11y::WithSpanContextExt;
+use near_primitives::block::Tip;
+use near_primitives::types::{BlockHeight, EpochId};
+use near_primitives::views::LightClientBlockView;
+use tokio::sync::{watch, OwnedSemaphorePermit};
+
+type LightClientBlocks = BoxStream<'static, Result<Arc<LightClientBlockView>, String>>;
+
+/// Returns the light client blocks of the last final blocks of the epochs,
+/// starting from `epoch_id`.  The blocks of the epochs which already ended
+/// come right away, then a block comes whenever `head` enters a new epoch.
+///
+/// An epoch in which no block became final has the same light client block as
+/// the epoch before it, the block is returned only once.
+pub(crate) fn light_client_blocks(
+    view_client_addr: Addr<ViewClientActor>,
+    head: watch::Receiver<Tip>,
+    epoch_id: EpochId,
+) -> LightClientBlocks {
+    let state: (EpochId, watch::Receiver<Tip>, Option<BlockHeight>) = (epoch_id, head, None);
+    futures::stream::try_unfold(state, move |(mut epoch_id, mut head, last_height)| {
+        let view_client_addr = view_client_addr.clone();
+        async move {
+            loop {
+                let light_block = view_client_addr
+                    .send(
+                        GetEpochLightClientBlock { epoch_id: epoch_id.clone() }.with_span_context(),
+                    )
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .map_err(|err| err.to_string())?;
+                match light_block {
+                    Some(EpochLightClientBlock { light_block, next_epoch_id }) => {
+                        epoch_id = next_epoch_id;
+                        let height = light_block.inner_lite.height;
+                        if last_height.map_or(true, |last_height| last_height < height) {
+                            return Ok(Some((light_block, (epoch_id, head, Some(height)))));
+                        }
+                    }
+                    None => {
+                        // The light client block of an epoch is recorded when
+                        // the head enters the next epoch.
+                        loop {
+                            head.changed()
+                                .await
+                                .map_err(|_| "the node is shutting down".to_string())?;
+                            if head.borrow().epoch_id != epoch_id {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Sends the light client blocks to the WebSocket, one JSON text message per
+/// block.
+pub(crate) struct LightClientBlocksSession {
+    blocks: Option<LightClientBlocks>,
+    /// Counts the session against `max_light_client_block_streams` for as
+    /// long as it's open.
+    _permit: OwnedSemaphorePermit,
+}
+
+impl LightClientBlocksSession {
+    pub(crate) fn new(blocks: LightClientBlocks, permit: OwnedSemaphorePermit) -> Self {
+        Self { blocks: Some(blocks), _permit: permit }
+    }
+}
+
+impl Actor for LightClientBlocksSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(blocks) = self.blocks.take() {
+            ctx.add_stream(blocks);
+        }
+    }
+}
+
+impl StreamHandler<Result<Arc<LightClientBlockView>, String>> for LightClientBlocksSession {
+    fn handle(
+        &mut self,
+        light_block: Result<Arc<LightClientBlockView>, String>,
+        ctx: &mut Self::Context,
+    ) {
+        let text = light_block.and_then(|light_block| {
+            serde_json::to_string(&*light_block).map_err(|err| err.to_string())
+        });
+        match text {
+            Ok(text) => ctx.text(text),
+            Err(err) => {
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Error,
+                    description: Some(err),
+                }));
+                ctx.stop();
+            }
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for LightClientBlocksSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
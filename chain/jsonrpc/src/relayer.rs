@@ -0,0 +1,246 @@
+//! Relaying of delegate actions (meta transactions) submitted over RPC, see
+//! [`crate::RpcConfig::relayer_key_file`].
+
+use std::sync::Mutex;
+
+use near_crypto::{InMemorySigner, Signer};
+use near_primitives::account::AccessKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::dec_format;
+use near_primitives::transaction::{Action, SignedDelegateAction, SignedTransaction, Transaction};
+use near_primitives::types::{AccountId, Balance, BlockHeight, Nonce};
+use serde::{Deserialize, Serialize};
+
+/// Delegate actions which the relayer agrees to pay for, see
+/// [`crate::RpcConfig::relayer_policy`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelayerPolicy {
+    /// If provided, only delegate actions addressed to one of these accounts
+    /// are relayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_receivers: Option<Vec<AccountId>>,
+    /// Maximum total deposit attached to the actions of a delegate action,
+    /// which the relayer pays on top of the gas.  No deposits by default.
+    #[serde(default, with = "dec_format")]
+    pub max_deposit: Balance,
+}
+
+impl RelayerPolicy {
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        if self.allowed_receivers.as_ref().map_or(false, Vec::is_empty) {
+            anyhow::bail!(
+                "relayer_policy.allowed_receivers is empty, no delegate action would be relayed"
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that the relayer agrees to pay for the delegate action.
+    pub(crate) fn check(
+        &self,
+        signed_delegate_action: &SignedDelegateAction,
+    ) -> Result<(), String> {
+        let delegate_action = &signed_delegate_action.delegate_action;
+        if let Some(allowed_receivers) = &self.allowed_receivers {
+            if !allowed_receivers.contains(&delegate_action.receiver_id) {
+                return Err(format!(
+                    "Delegate actions to {} are not relayed",
+                    delegate_action.receiver_id
+                ));
+            }
+        }
+        let deposit = delegate_action.actions.iter().try_fold(0, |total: Balance, action| {
+            total.checked_add(action.0.get_deposit_balance())
+        });
+        if deposit.map_or(true, |deposit| deposit > self.max_deposit) {
+            return Err(format!(
+                "Deposit attached to the actions exceeds the limit of {}",
+                self.max_deposit
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Relayer {
+    signer: InMemorySigner,
+    policy: RelayerPolicy,
+    /// Nonce of the last transaction signed by the relayer, so that
+    /// concurrent relays don't reuse a nonce before the previous transaction
+    /// updates the access key.
+    last_nonce: Mutex<Nonce>,
+}
+
+impl Relayer {
+    pub(crate) fn new(signer: InMemorySigner, policy: RelayerPolicy) -> Self {
+        Self { signer, policy, last_nonce: Mutex::new(0) }
+    }
+
+    pub(crate) fn signer(&self) -> &InMemorySigner {
+        &self.signer
+    }
+
+    pub(crate) fn policy(&self) -> &RelayerPolicy {
+        &self.policy
+    }
+
+    /// Returns the nonce of the next transaction of the relayer given the
+    /// current nonce of its access key.
+    pub(crate) fn next_nonce(&self, access_key_nonce: Nonce) -> Nonce {
+        let mut last_nonce = self.last_nonce.lock().unwrap();
+        *last_nonce = std::cmp::max(*last_nonce, access_key_nonce) + 1;
+        *last_nonce
+    }
+
+    /// Wraps the delegate action in a transaction from the relayer to the
+    /// sender of the delegate action.
+    pub(crate) fn sign_transaction(
+        &self,
+        signed_delegate_action: SignedDelegateAction,
+        nonce: Nonce,
+        block_hash: CryptoHash,
+    ) -> Result<SignedTransaction, String> {
+        let receiver_id = signed_delegate_action.delegate_action.sender_id.clone();
+        let actions = delegate_actions(signed_delegate_action)?;
+        let transaction = Transaction {
+            signer_id: self.signer.account_id.clone(),
+            public_key: self.signer.public_key(),
+            nonce,
+            receiver_id,
+            block_hash,
+            actions,
+        };
+        let signature = self.signer.sign(transaction.get_hash_and_size().0.as_ref());
+        Ok(SignedTransaction::new(signature, transaction))
+    }
+}
+
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+fn delegate_actions(signed_delegate_action: SignedDelegateAction) -> Result<Vec<Action>, String> {
+    Ok(vec![signed_delegate_action.into()])
+}
+
+#[cfg(not(feature = "protocol_feature_nep366_delegate_action"))]
+fn delegate_actions(_: SignedDelegateAction) -> Result<Vec<Action>, String> {
+    Err("Delegate actions are not supported by this build".to_string())
+}
+
+/// Checks the signature, expiration and nonce of a delegate action the same
+/// way the runtime does when applying it, so that the relayer doesn't pay for
+/// delegate actions which are bound to fail.  `head_height` is the height of
+/// the block at which `access_key_nonce` of the key which signed the delegate
+/// action has been read.
+pub(crate) fn validate_delegate_action(
+    signed_delegate_action: &SignedDelegateAction,
+    head_height: BlockHeight,
+    access_key_nonce: Nonce,
+) -> Result<(), String> {
+    let delegate_action = &signed_delegate_action.delegate_action;
+    if !signed_delegate_action.verify() {
+        return Err("Invalid signature".to_string());
+    }
+    if head_height > delegate_action.max_block_height {
+        return Err(format!(
+            "Expired at height {}, the head is at height {}",
+            delegate_action.max_block_height, head_height
+        ));
+    }
+    if delegate_action.nonce <= access_key_nonce {
+        return Err(format!(
+            "Nonce {} has to be greater than the nonce of the access key {}",
+            delegate_action.nonce, access_key_nonce
+        ));
+    }
+    let upper_bound = head_height * AccessKey::ACCESS_KEY_NONCE_RANGE_MULTIPLIER;
+    if delegate_action.nonce >= upper_bound {
+        return Err(format!("Nonce {} has to be less than {}", delegate_action.nonce, upper_bound));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::KeyType;
+    use near_primitives::transaction::{DelegateAction, NonDelegateAction, TransferAction};
+
+    fn signed_delegate_action(nonce: Nonce, max_block_height: BlockHeight) -> SignedDelegateAction {
+        signed_delegate_action_with_actions(nonce, max_block_height, vec![])
+    }
+
+    fn signed_delegate_action_with_actions(
+        nonce: Nonce,
+        max_block_height: BlockHeight,
+        actions: Vec<Action>,
+    ) -> SignedDelegateAction {
+        let signer = InMemorySigner::from_seed("alice".parse().unwrap(), KeyType::ED25519, "alice");
+        let delegate_action = DelegateAction {
+            sender_id: "alice".parse().unwrap(),
+            receiver_id: "bob".parse().unwrap(),
+            actions: actions.into_iter().map(NonDelegateAction).collect(),
+            nonce,
+            max_block_height,
+            public_key: signer.public_key(),
+        };
+        let signature = signer.sign(delegate_action.get_hash().as_ref());
+        SignedDelegateAction { delegate_action, signature }
+    }
+
+    #[test]
+    fn test_validate_delegate_action() {
+        assert_eq!(validate_delegate_action(&signed_delegate_action(11, 100), 50, 10), Ok(()));
+        assert!(validate_delegate_action(&signed_delegate_action(11, 100), 101, 10).is_err());
+        assert!(validate_delegate_action(&signed_delegate_action(10, 100), 50, 10).is_err());
+        assert!(validate_delegate_action(&signed_delegate_action(50_000_000, 100), 50, 10).is_err());
+
+        let mut tampered = signed_delegate_action(11, 100);
+        tampered.delegate_action.receiver_id = "carol".parse().unwrap();
+        assert!(validate_delegate_action(&tampered, 50, 10).is_err());
+    }
+
+    #[test]
+    fn test_next_nonce() {
+        let signer = InMemorySigner::from_seed("relayer".parse().unwrap(), KeyType::ED25519, "r");
+        let relayer = Relayer::new(signer, RelayerPolicy::default());
+        assert_eq!(relayer.next_nonce(5), 6);
+        // The access key wasn't updated by the previous transaction yet.
+        assert_eq!(relayer.next_nonce(5), 7);
+        assert_eq!(relayer.next_nonce(20), 21);
+    }
+
+    #[test]
+    fn test_relayer_policy() {
+        let transfer = |deposit| {
+            signed_delegate_action_with_actions(
+                11,
+                100,
+                vec![
+                    Action::Transfer(TransferAction { deposit }),
+                    Action::Transfer(TransferAction { deposit }),
+                ],
+            )
+        };
+        let policy = RelayerPolicy::default();
+        assert_eq!(policy.check(&signed_delegate_action(11, 100)), Ok(()));
+        assert!(policy.check(&transfer(1)).is_err());
+
+        let policy = RelayerPolicy { allowed_receivers: None, max_deposit: 10 };
+        assert_eq!(policy.check(&transfer(5)), Ok(()));
+        assert!(policy.check(&transfer(6)).is_err());
+        assert!(policy.check(&transfer(Balance::MAX)).is_err());
+
+        let policy = RelayerPolicy {
+            allowed_receivers: Some(vec!["carol".parse().unwrap()]),
+            max_deposit: 0,
+        };
+        assert!(policy.check(&signed_delegate_action(11, 100)).is_err());
+        let policy = RelayerPolicy {
+            allowed_receivers: Some(vec!["bob".parse().unwrap(), "carol".parse().unwrap()]),
+            max_deposit: 0,
+        };
+        assert_eq!(policy.check(&signed_delegate_action(11, 100)), Ok(()));
+        assert!(RelayerPolicy { allowed_receivers: Some(vec![]), max_deposit: 0 }
+            .validate()
+            .is_err());
+    }
+}
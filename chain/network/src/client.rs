@@ -1,6 +1,6 @@
 use crate::network_protocol::{
-    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
-    StateResponseInfo,
+    GarbageCollectedData, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg, StateDeltaResponseInfo, StateResponseInfo,
 };
 use crate::types::{NetworkInfo, ReasonForBan};
 use near_primitives::block::{Approval, Block, BlockHeader};
@@ -9,9 +9,26 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::PartialEncodedChunk;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, EpochId, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
 
+/// Response of the client to a request for a block or for state.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DataResponse<T> {
+    Data(T),
+    /// The client has already garbage collected the requested data.
+    GarbageCollected(GarbageCollectedData),
+}
+
+impl<T> DataResponse<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> DataResponse<U> {
+        match self {
+            Self::Data(data) => DataResponse::Data(f(data)),
+            Self::GarbageCollected(data) => DataResponse::GarbageCollected(data),
+        }
+    }
+}
+
 /// A strongly typed asynchronous API for the Client logic.
 /// It abstracts away the fact that client is implemented using actix
 /// actors.
@@ -25,21 +42,38 @@ pub trait Client: Send + Sync + 'static {
 
     async fn tx_status_response(&self, tx_result: FinalExecutionOutcomeView);
 
+    /// `peer_id` is the author of the request, used to throttle peers.
     async fn state_request_header(
         &self,
         shard_id: ShardId,
         sync_hash: CryptoHash,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan>;
+        peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan>;
 
     async fn state_request_part(
         &self,
         shard_id: ShardId,
         sync_hash: CryptoHash,
         part_id: u64,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan>;
+        peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan>;
 
     async fn state_response(&self, info: StateResponseInfo);
 
+    /// `peer_id` has responded to a request for a block or for state that it
+    /// has garbage collected the data.
+    async fn data_garbage_collected(&self, data: GarbageCollectedData, peer_id: PeerId);
+
+    async fn state_request_delta(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        from_hash: CryptoHash,
+        peer_id: PeerId,
+    ) -> Result<Option<StateDeltaResponseInfo>, ReasonForBan>;
+
+    async fn state_delta_response(&self, info: StateDeltaResponseInfo);
+
     async fn block_approval(&self, approval: Approval, peer_id: PeerId);
 
     async fn transaction(&self, transaction: SignedTransaction, is_forwarded: bool);
@@ -50,20 +84,32 @@ pub trait Client: Send + Sync + 'static {
         msg_hash: CryptoHash,
     );
 
+    /// `author` is the peer which sent the response and `msg_len` the size of
+    /// the message which carried it over the network.
     async fn partial_encoded_chunk_response(
         &self,
         resp: PartialEncodedChunkResponseMsg,
         timestamp: time::Instant,
+        author: PeerId,
+        msg_len: usize,
     );
 
     async fn partial_encoded_chunk(&self, chunk: PartialEncodedChunk);
 
     async fn partial_encoded_chunk_forward(&self, msg: PartialEncodedChunkForwardMsg);
 
-    async fn block_request(&self, hash: CryptoHash) -> Option<Box<Block>>;
+    async fn block_request(&self, hash: CryptoHash) -> Option<DataResponse<Box<Block>>>;
 
     async fn block_headers_request(&self, hashes: Vec<CryptoHash>) -> Option<Vec<BlockHeader>>;
 
+    /// Clients which don't serve headers by height answer as if they had none of them.
+    async fn block_headers_at_heights_request(
+        &self,
+        _heights: Vec<BlockHeight>,
+    ) -> Option<Vec<BlockHeader>> {
+        None
+    }
+
     async fn block(&self, block: Block, peer_id: PeerId, was_requested: bool);
 
     async fn block_headers(
@@ -101,7 +147,8 @@ impl Client for Noop {
         &self,
         _shard_id: ShardId,
         _sync_hash: CryptoHash,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        _peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
         Ok(None)
     }
 
@@ -110,11 +157,27 @@ impl Client for Noop {
         _shard_id: ShardId,
         _sync_hash: CryptoHash,
         _part_id: u64,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        _peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
         Ok(None)
     }
 
     async fn state_response(&self, _info: StateResponseInfo) {}
+
+    async fn data_garbage_collected(&self, _data: GarbageCollectedData, _peer_id: PeerId) {}
+
+    async fn state_request_delta(
+        &self,
+        _shard_id: ShardId,
+        _sync_hash: CryptoHash,
+        _from_hash: CryptoHash,
+        _peer_id: PeerId,
+    ) -> Result<Option<StateDeltaResponseInfo>, ReasonForBan> {
+        Ok(None)
+    }
+
+    async fn state_delta_response(&self, _info: StateDeltaResponseInfo) {}
+
     async fn block_approval(&self, _approval: Approval, _peer_id: PeerId) {}
 
     async fn transaction(&self, _transaction: SignedTransaction, _is_forwarded: bool) {}
@@ -130,6 +193,8 @@ impl Client for Noop {
         &self,
         _resp: PartialEncodedChunkResponseMsg,
         _timestamp: time::Instant,
+        _author: PeerId,
+        _msg_len: usize,
     ) {
     }
 
@@ -137,7 +202,7 @@ impl Client for Noop {
 
     async fn partial_encoded_chunk_forward(&self, _msg: PartialEncodedChunkForwardMsg) {}
 
-    async fn block_request(&self, _hash: CryptoHash) -> Option<Box<Block>> {
+    async fn block_request(&self, _hash: CryptoHash) -> Option<DataResponse<Box<Block>>> {
         None
     }
 
@@ -145,6 +210,13 @@ impl Client for Noop {
         None
     }
 
+    async fn block_headers_at_heights_request(
+        &self,
+        _heights: Vec<BlockHeight>,
+    ) -> Option<Vec<BlockHeader>> {
+        None
+    }
+
     async fn block(&self, _block: Block, _peer_id: PeerId, _was_requested: bool) {}
 
     async fn block_headers(
@@ -1,5 +1,6 @@
 use crate::blacklist;
 use crate::concurrency::rate;
+use crate::network_protocol::Encoding;
 use crate::network_protocol::PeerAddr;
 use crate::network_protocol::PeerInfo;
 use crate::peer_manager::peer_manager_actor::Event;
@@ -7,9 +8,12 @@ use crate::peer_manager::peer_store;
 use crate::sink::Sink;
 use crate::tcp;
 use crate::time;
+use crate::trusted_peers::TrustedPeers;
 use crate::types::ROUTED_MESSAGE_TTL;
 use anyhow::Context;
 use near_crypto::{KeyType, SecretKey};
+use near_primitives::block::GenesisId;
+use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 use near_primitives::test_utils::create_test_signer;
 use near_primitives::types::AccountId;
@@ -88,6 +92,78 @@ pub struct Tier1 {
     pub enable_outbound: bool,
 }
 
+/// Compression of large messages sent to peers which support it.
+#[derive(Clone, Debug)]
+pub struct Compression {
+    /// Encoded messages smaller than that are sent uncompressed.
+    pub min_message_size: usize,
+    /// zstd compression level.
+    pub level: i32,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        // Level 3 (zstd default) compresses blocks and state parts well,
+        // while keeping the CPU cost well below the cost of deserialization.
+        Self { min_message_size: 16 * 1024, level: 3 }
+    }
+}
+
+/// Application-level liveness checks of established connections.
+/// See `peer::keepalive`.
+#[derive(Clone, Debug)]
+pub struct Keepalive {
+    /// Interval between the pings sent to peers which support them.
+    /// It is also the period of checking the timeout and the send backlog.
+    pub ping_interval: time::Duration,
+    /// Bounds of the pong timeout, which adapts to the round trip time
+    /// observed on the connection.
+    pub min_timeout: time::Duration,
+    pub max_timeout: time::Duration,
+    /// Connections with more bytes than that queued for sending for longer
+    /// than `send_backlog_timeout` are closed: the peer doesn't read from the
+    /// connection fast enough (or at all).  The default is the size of the
+    /// largest message, so that a single large message (e.g. a state part)
+    /// queued for a slow peer doesn't get the connection closed.
+    pub max_send_backlog_bytes: u64,
+    pub send_backlog_timeout: time::Duration,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            ping_interval: time::Duration::seconds(2),
+            min_timeout: time::Duration::seconds(3),
+            max_timeout: time::Duration::seconds(20),
+            max_send_backlog_bytes: crate::peer::stream::NETWORK_MESSAGE_MAX_SIZE_BYTES as u64,
+            send_backlog_timeout: time::Duration::minutes(1),
+        }
+    }
+}
+
+/// TCP keepalive options of the peer connections.
+#[derive(Clone, Debug)]
+pub struct TcpKeepalive {
+    /// Idle time after which the keepalive probes are sent.
+    pub time: time::Duration,
+    /// Interval between the keepalive probes.
+    pub interval: time::Duration,
+    /// Number of unanswered probes after which the connection is dropped.
+    pub retries: u32,
+}
+
+/// Foreign chain whose headers are synced, read-only, to measure header sync
+/// timing.  See `crate::config_json::ShadowChainConfig`.
+#[derive(Clone)]
+pub struct ShadowChain {
+    /// Genesis presented in the handshakes with the boot nodes of the chain,
+    /// instead of the genesis of the node.
+    pub genesis_id: GenesisId,
+    pub boot_nodes: Vec<PeerInfo>,
+    /// Hash of the header to start the header sync from.
+    pub start_hash: CryptoHash,
+}
+
 /// Validated configuration for the peer-to-peer manager.
 #[derive(Clone)]
 pub struct NetworkConfig {
@@ -97,6 +173,7 @@ pub struct NetworkConfig {
 
     pub peer_store: peer_store::Config,
     pub whitelist_nodes: Vec<PeerInfo>,
+    pub trusted_peers: TrustedPeers,
     pub handshake_timeout: time::Duration,
 
     /// Maximum time between refreshing the peer list.
@@ -150,6 +227,22 @@ pub struct NetworkConfig {
     pub routing_table_update_rate_limit: rate::Limit,
     /// Config of the TIER1 network.
     pub tier1: Option<Tier1>,
+    /// Foreign chain shadowed by the node, if any.
+    pub shadow_chain: Option<ShadowChain>,
+    /// Compression of large messages. If None, messages are never compressed
+    /// (compressed messages are still accepted).
+    pub compression: Option<Compression>,
+    /// Encodings supported by this node, most preferred first.
+    /// Each connection uses the first of them supported by the peer.
+    pub encodings: Vec<Encoding>,
+    /// Whether to multiplex the logical streams (consensus, transactions, sync) of
+    /// a connection, if the peer supports it. See `peer::stream::Kind`.
+    pub multiplexing: bool,
+    /// Application-level liveness checks. If None, dead connections are
+    /// detected only by TCP.
+    pub keepalive: Option<Keepalive>,
+    /// TCP keepalive options. If None, the OS defaults are used.
+    pub tcp_keepalive: Option<TcpKeepalive>,
 
     // Whether to ignore tombstones some time after startup.
     //
@@ -256,6 +349,12 @@ impl NetworkConfig {
                     .collect::<anyhow::Result<_>>()
                     .context("whitelist_nodes")?
             },
+            trusted_peers: cfg
+                .trusted_peers
+                .iter()
+                .map(|s| s.parse())
+                .collect::<Result<_, _>>()
+                .context("failed to parse trusted_peers")?,
             handshake_timeout: cfg.handshake_timeout.try_into()?,
             monitor_peers_max_period: cfg.monitor_peers_max_period.try_into()?,
             max_num_peers: cfg.max_num_peers,
@@ -284,6 +383,55 @@ impl NetworkConfig {
                 enable_outbound: cfg.experimental.tier1_enable_outbound,
             }),
             inbound_disabled: cfg.experimental.inbound_disabled,
+            encodings: cfg.experimental.encodings.clone(),
+            multiplexing: cfg.experimental.multiplexing,
+            keepalive: if cfg.experimental.keepalive_ping_interval.is_zero() {
+                None
+            } else {
+                Some(Keepalive {
+                    ping_interval: cfg.experimental.keepalive_ping_interval.try_into()?,
+                    min_timeout: cfg.experimental.keepalive_min_timeout.try_into()?,
+                    max_timeout: cfg.experimental.keepalive_max_timeout.try_into()?,
+                    max_send_backlog_bytes: cfg.experimental.max_send_backlog_bytes,
+                    send_backlog_timeout: cfg.experimental.send_backlog_timeout.try_into()?,
+                })
+            },
+            tcp_keepalive: if cfg.experimental.tcp_keepalive {
+                Some(TcpKeepalive {
+                    time: cfg.experimental.tcp_keepalive_time.try_into()?,
+                    interval: cfg.experimental.tcp_keepalive_interval.try_into()?,
+                    retries: cfg.experimental.tcp_keepalive_retries,
+                })
+            } else {
+                None
+            },
+            compression: if cfg.experimental.message_compression {
+                Some(Compression::default())
+            } else {
+                None
+            },
+            shadow_chain: match cfg.shadow_chain {
+                None => None,
+                Some(shadow) => Some(ShadowChain {
+                    genesis_id: GenesisId {
+                        chain_id: shadow.chain_id,
+                        hash: shadow.genesis_hash,
+                    },
+                    boot_nodes: shadow
+                        .boot_nodes
+                        .split(',')
+                        .map(|peer| match peer.parse::<PeerInfo>() {
+                            Ok(peer) if peer.addr.is_none() => anyhow::bail!(
+                                "shadow_chain.boot_nodes are required to specify both PeerId and IP:port"
+                            ),
+                            Ok(peer) => Ok(peer),
+                            Err(err) => Err(err.into()),
+                        })
+                        .collect::<anyhow::Result<_>>()
+                        .context("shadow_chain.boot_nodes")?,
+                    start_hash: shadow.start_hash.unwrap_or(shadow.genesis_hash),
+                }),
+            },
             skip_tombstones: if cfg.experimental.skip_sending_tombstones_seconds > 0 {
                 Some(time::Duration::seconds(cfg.experimental.skip_sending_tombstones_seconds))
             } else {
@@ -320,6 +468,7 @@ impl NetworkConfig {
                 connect_only_to_boot_nodes: false,
             },
             whitelist_nodes: vec![],
+            trusted_peers: TrustedPeers::default(),
             handshake_timeout: time::Duration::seconds(5),
             monitor_peers_max_period: time::Duration::seconds(100),
             max_num_peers: 40,
@@ -350,12 +499,33 @@ impl NetworkConfig {
                 enable_inbound: true,
                 enable_outbound: true,
             }),
+            shadow_chain: None,
+            compression: Some(Compression::default()),
+            encodings: vec![Encoding::Proto, Encoding::Borsh],
+            multiplexing: true,
+            // Disabled, so that moving the fake clock in tests doesn't
+            // time out the pings in flight.
+            keepalive: None,
+            tcp_keepalive: None,
             skip_tombstones: None,
             event_sink: Sink::null(),
         }
     }
 
     pub fn verify(self) -> anyhow::Result<VerifiedConfig> {
+        if self.encodings.is_empty() {
+            anyhow::bail!("encodings cannot be empty");
+        }
+        if let Some(k) = &self.keepalive {
+            if !(k.min_timeout <= k.max_timeout) {
+                anyhow::bail!(
+                    "keepalive min_timeout({}) > max_timeout({})",
+                    k.min_timeout,
+                    k.max_timeout
+                );
+            }
+        }
+
         if !(self.ideal_connections_lo <= self.ideal_connections_hi) {
             anyhow::bail!(
                 "Invalid ideal_connections values. lo({}) > hi({}).",
@@ -454,6 +624,28 @@ mod test {
         assert!(nc.verify().is_err());
     }
 
+    #[test]
+    fn test_shadow_chain_config() {
+        let node_key = near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, "123");
+        let peer = "ed25519:C6HLP37VJN1Wj2irxxZPsVsSya92Rnx12tqK3us5erKV";
+        let mut cfg = crate::config_json::Config::default();
+        cfg.shadow_chain = Some(crate::config_json::ShadowChainConfig {
+            chain_id: "testnet".to_string(),
+            genesis_hash: near_primitives::hash::hash(b"genesis"),
+            boot_nodes: format!("{peer}@127.0.0.1:24567"),
+            start_hash: None,
+        });
+        let nc = config::NetworkConfig::new(cfg.clone(), node_key.clone(), None, false).unwrap();
+        let shadow = nc.shadow_chain.unwrap();
+        assert_eq!(shadow.genesis_id.chain_id, "testnet");
+        assert_eq!(shadow.start_hash, near_primitives::hash::hash(b"genesis"));
+        assert_eq!(shadow.boot_nodes.len(), 1);
+
+        // Boot nodes of the shadowed chain can't be discovered, they need addresses.
+        cfg.shadow_chain.as_mut().unwrap().boot_nodes = peer.to_string();
+        assert!(config::NetworkConfig::new(cfg, node_key, None, false).is_err());
+    }
+
     // Check that MAX_PEER_ADDRS limit is consistent with the
     // network_protocol::MAX_ACCOUNT_DATA_SIZE_BYTES limit
     #[test]
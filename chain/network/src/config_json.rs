@@ -1,4 +1,5 @@
-use crate::network_protocol::PeerAddr;
+use crate::network_protocol::{Encoding, PeerAddr};
+use near_primitives::hash::CryptoHash;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -75,6 +76,15 @@ pub struct Config {
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@nearnode.com:24567
     #[serde(default)]
     pub whitelist_nodes: String,
+    /// List of trusted peers, each given either as a PeerId or as an IP address.
+    /// Inbound connections from trusted peers are always accepted, and trusted peers
+    /// are never disconnected to make room for other peers.
+    /// See `near_network::trusted_peers::TrustedPeers`.
+    /// Examples:
+    ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw
+    ///   31.192.22.209
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_peers: Vec<String>,
     /// Maximum number of active peers. Hard limit.
     #[serde(default = "default_max_num_peers")]
     pub max_num_peers: u32,
@@ -168,6 +178,30 @@ pub struct Config {
     // Field names in here can change/disappear at any moment without warning.
     #[serde(default)]
     pub experimental: ExperimentalConfig,
+    /// If set, the node additionally connects to the boot nodes of another
+    /// chain, read-only, and syncs its headers to measure header sync timing
+    /// against that chain's topology.  See `ShadowChainConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_chain: Option<ShadowChainConfig>,
+}
+
+/// A foreign chain shadowed by the node, e.g. testnet shadowed by a mainnet
+/// node to rehearse a deployment.  The node presents the genesis of that chain
+/// in the handshakes with its boot nodes and only requests headers from them,
+/// the headers are discarded after being counted.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShadowChainConfig {
+    /// Chain id of the shadowed chain.
+    pub chain_id: String,
+    /// Hash of the genesis block of the shadowed chain.
+    pub genesis_hash: CryptoHash,
+    /// Comma separated list of boot nodes of the shadowed chain, in the same
+    /// format as `boot_nodes`.  The addresses are required.
+    pub boot_nodes: String,
+    /// Hash of the header to start the header sync from.  Defaults to the
+    /// genesis hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_hash: Option<CryptoHash>,
 }
 
 fn default_tier1_enable_inbound() -> bool {
@@ -179,6 +213,54 @@ fn default_tier1_enable_outbound() -> bool {
     false
 }
 
+fn default_message_compression() -> bool {
+    true
+}
+
+fn default_encodings() -> Vec<Encoding> {
+    vec![Encoding::Proto, Encoding::Borsh]
+}
+
+fn default_multiplexing() -> bool {
+    true
+}
+
+fn default_keepalive_ping_interval() -> Duration {
+    Duration::from_secs(2)
+}
+
+fn default_keepalive_min_timeout() -> Duration {
+    Duration::from_secs(3)
+}
+
+fn default_keepalive_max_timeout() -> Duration {
+    Duration::from_secs(20)
+}
+
+fn default_max_send_backlog_bytes() -> u64 {
+    crate::peer::stream::NETWORK_MESSAGE_MAX_SIZE_BYTES as u64
+}
+
+fn default_send_backlog_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_tcp_keepalive() -> bool {
+    true
+}
+
+fn default_tcp_keepalive_time() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_tcp_keepalive_interval() -> Duration {
+    Duration::from_secs(3)
+}
+
+fn default_tcp_keepalive_retries() -> u32 {
+    3
+}
+
 fn default_tier1_connect_interval() -> Duration {
     Duration::from_secs(60)
 }
@@ -219,6 +301,44 @@ pub struct ExperimentalConfig {
     /// See `near_network::config::Tier1::new_connections_per_attempt`.
     #[serde(default = "default_tier1_new_connections_per_attempt")]
     pub tier1_new_connections_per_attempt: u64,
+
+    /// Whether to compress large messages sent to peers which support it.
+    /// See `near_network::config::Compression`.
+    #[serde(default = "default_message_compression")]
+    pub message_compression: bool,
+
+    /// See `near_network::config::NetworkConfig::encodings`.
+    /// Allows rolling out a new encoding gradually, and disabling it if needed.
+    #[serde(default = "default_encodings")]
+    pub encodings: Vec<Encoding>,
+
+    /// See `near_network::config::NetworkConfig::multiplexing`.
+    #[serde(default = "default_multiplexing")]
+    pub multiplexing: bool,
+
+    /// See `near_network::config::Keepalive`. Zero ping interval disables
+    /// the application-level liveness checks.
+    #[serde(default = "default_keepalive_ping_interval")]
+    pub keepalive_ping_interval: Duration,
+    #[serde(default = "default_keepalive_min_timeout")]
+    pub keepalive_min_timeout: Duration,
+    #[serde(default = "default_keepalive_max_timeout")]
+    pub keepalive_max_timeout: Duration,
+    #[serde(default = "default_max_send_backlog_bytes")]
+    pub max_send_backlog_bytes: u64,
+    #[serde(default = "default_send_backlog_timeout")]
+    pub send_backlog_timeout: Duration,
+
+    /// Whether to enable TCP keepalive on peer connections.
+    /// See `near_network::config::TcpKeepalive`.
+    #[serde(default = "default_tcp_keepalive")]
+    pub tcp_keepalive: bool,
+    #[serde(default = "default_tcp_keepalive_time")]
+    pub tcp_keepalive_time: Duration,
+    #[serde(default = "default_tcp_keepalive_interval")]
+    pub tcp_keepalive_interval: Duration,
+    #[serde(default = "default_tcp_keepalive_retries")]
+    pub tcp_keepalive_retries: u32,
 }
 
 impl Default for ExperimentalConfig {
@@ -231,6 +351,18 @@ impl Default for ExperimentalConfig {
             tier1_enable_outbound: default_tier1_enable_outbound(),
             tier1_connect_interval: default_tier1_connect_interval(),
             tier1_new_connections_per_attempt: default_tier1_new_connections_per_attempt(),
+            message_compression: default_message_compression(),
+            encodings: default_encodings(),
+            multiplexing: default_multiplexing(),
+            keepalive_ping_interval: default_keepalive_ping_interval(),
+            keepalive_min_timeout: default_keepalive_min_timeout(),
+            keepalive_max_timeout: default_keepalive_max_timeout(),
+            max_send_backlog_bytes: default_max_send_backlog_bytes(),
+            send_backlog_timeout: default_send_backlog_timeout(),
+            tcp_keepalive: default_tcp_keepalive(),
+            tcp_keepalive_time: default_tcp_keepalive_time(),
+            tcp_keepalive_interval: default_tcp_keepalive_interval(),
+            tcp_keepalive_retries: default_tcp_keepalive_retries(),
         }
     }
 }
@@ -241,6 +373,7 @@ impl Default for Config {
             addr: "0.0.0.0:24567".to_string(),
             boot_nodes: "".to_string(),
             whitelist_nodes: "".to_string(),
+            trusted_peers: vec![],
             max_num_peers: default_max_num_peers(),
             minimum_outbound_peers: default_minimum_outbound_connections(),
             ideal_connections_lo: default_ideal_connections_lo(),
@@ -260,6 +393,7 @@ impl Default for Config {
             allow_private_ip_in_public_addrs: false,
             trusted_stun_servers: vec![],
             experimental: Default::default(),
+            shadow_chain: None,
         }
     }
 }
@@ -1,16 +1,18 @@
 use ::actix::Message;
-use near_primitives::views::{NetworkGraphView, PeerStoreView};
+use near_primitives::views::{NetworkBandwidthView, NetworkGraphView, PeerStoreView};
 
 // Different debug requests that can be sent by HTML pages, via GET.
 pub enum GetDebugStatus {
     PeerStore,
     Graph,
+    Bandwidth,
 }
 
 #[derive(actix::MessageResponse, Debug)]
 pub enum DebugStatus {
     PeerStore(PeerStoreView),
     Graph(NetworkGraphView),
+    Bandwidth(NetworkBandwidthView),
 }
 
 impl Message for GetDebugStatus {
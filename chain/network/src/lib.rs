@@ -1,5 +1,7 @@
 pub use crate::peer_manager::peer_manager_actor::{Event, PeerManagerActor};
-pub use crate::peer_manager::peer_store::iter_peers_from_store;
+pub use crate::peer_manager::peer_store::{
+    export_peers_from_store, import_peers_to_store, iter_peers_from_store, ExportedPeer,
+};
 
 mod accounts_data;
 mod concurrency;
@@ -7,6 +9,7 @@ mod network_protocol;
 mod peer;
 mod peer_manager;
 mod private_actix;
+mod shadow_chain;
 mod stats;
 mod store;
 
@@ -20,7 +23,8 @@ pub mod raw;
 pub mod routing;
 pub mod tcp;
 pub mod test_utils;
-pub mod time;
+pub use near_primitives::clock as time;
+pub mod trusted_peers;
 pub mod types;
 
 #[cfg(test)]
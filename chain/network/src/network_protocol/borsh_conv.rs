@@ -15,6 +15,13 @@ impl From<&net::Handshake> for mem::Handshake {
             sender_chain_info: x.sender_chain_info.clone(),
             partial_edge_info: x.partial_edge_info.clone(),
             owned_account: None,
+            supports_compression: false,
+            // Handshake was received in borsh, so the peer doesn't
+            // necessarily understand any other encoding.
+            supported_encodings: vec![mem::Encoding::Borsh],
+            supports_multiplexing: false,
+            supports_keepalive: false,
+            sender_earliest_height: None,
         }
     }
 }
@@ -179,10 +186,25 @@ impl From<&mem::PeerMessage> for net::PeerMessage {
             mem::PeerMessage::BlockHeadersRequest(bhs) => {
                 net::PeerMessage::BlockHeadersRequest(bhs)
             }
+            // This message is not supported, we translate it to a request without hashes, which
+            // is answered with no headers.
+            mem::PeerMessage::BlockHeadersAtHeightsRequest(_) => {
+                net::PeerMessage::BlockHeadersRequest(vec![])
+            }
             mem::PeerMessage::BlockHeaders(bhs) => net::PeerMessage::BlockHeaders(bhs),
             mem::PeerMessage::BlockRequest(bh) => net::PeerMessage::BlockRequest(bh),
             mem::PeerMessage::Block(b) => net::PeerMessage::Block(b),
+            // This message is not supported, we translate it to an empty RoutingTableUpdate.
+            // The requester times out as before.
+            mem::PeerMessage::DataGarbageCollected(_) => {
+                net::PeerMessage::SyncRoutingTable(net::RoutingTableUpdate::default())
+            }
             mem::PeerMessage::Transaction(t) => net::PeerMessage::Transaction(t),
+            // These messages are not supported, we translate them to an empty RoutingTableUpdate.
+            // They are never sent to peers using Borsh, which don't set supports_keepalive.
+            mem::PeerMessage::KeepalivePing(_) | mem::PeerMessage::KeepalivePong(_) => {
+                net::PeerMessage::SyncRoutingTable(net::RoutingTableUpdate::default())
+            }
             mem::PeerMessage::Routed(r) => net::PeerMessage::Routed(Box::new(r.msg.clone())),
             mem::PeerMessage::Disconnect => net::PeerMessage::Disconnect,
             mem::PeerMessage::Challenge(c) => net::PeerMessage::Challenge(c),
@@ -22,6 +22,7 @@ pub use _proto::network as proto;
 use crate::network_protocol::proto_conv::trace_context::{
     extract_span_context, inject_trace_context,
 };
+use crate::stats::metrics;
 use crate::time;
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
 use near_crypto::PublicKey;
@@ -35,7 +36,7 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::{
     ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart, ReceiptProof, ShardChunkHeader,
 };
-use near_primitives::syncing::{ShardStateSyncResponse, ShardStateSyncResponseV1};
+use near_primitives::syncing::{ShardStateDelta, ShardStateSyncResponse, ShardStateSyncResponseV1};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::AccountId;
 use near_primitives::types::{BlockHeight, ShardId};
@@ -307,6 +308,17 @@ pub struct Handshake {
     pub(crate) partial_edge_info: PartialEdgeInfo,
     /// Account owned by the sender.
     pub(crate) owned_account: Option<SignedOwnedAccount>,
+    /// Whether the sender accepts compressed messages.
+    pub(crate) supports_compression: bool,
+    /// Encodings supported by the sender, most preferred first.
+    pub(crate) supported_encodings: Vec<Encoding>,
+    /// Whether the sender accepts chunks of multiplexed streams.
+    pub(crate) supports_multiplexing: bool,
+    /// Whether the sender responds to keepalive pings.
+    pub(crate) supports_keepalive: bool,
+    /// Lowest height of the blocks the sender holds.
+    /// None if the sender doesn't advertise it.
+    pub(crate) sender_earliest_height: Option<BlockHeight>,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, strum::IntoStaticStr)]
@@ -342,10 +354,16 @@ pub enum PeerMessage {
     PeersResponse(Vec<PeerInfo>),
 
     BlockHeadersRequest(Vec<CryptoHash>),
+    /// Asks for the headers of the canonical chain at the given heights.
+    /// Answered with `BlockHeaders`.
+    BlockHeadersAtHeightsRequest(Vec<BlockHeight>),
     BlockHeaders(Vec<BlockHeader>),
 
     BlockRequest(CryptoHash),
     Block(Block),
+    /// Sent instead of a `Block` in response to a `BlockRequest` for a block
+    /// which the peer has garbage collected.
+    DataGarbageCollected(GarbageCollectedData),
 
     Transaction(SignedTransaction),
     Routed(Box<RoutedMessageV2>),
@@ -353,6 +371,11 @@ pub enum PeerMessage {
     /// Gracefully disconnect from other peer.
     Disconnect,
     Challenge(Challenge),
+
+    /// Application-level liveness check, answered with a `KeepalivePong`
+    /// carrying the same nonce. See `peer::keepalive`.
+    KeepalivePing(u64),
+    KeepalivePong(u64),
 }
 
 impl fmt::Display for PeerMessage {
@@ -361,12 +384,35 @@ impl fmt::Display for PeerMessage {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, strum::IntoStaticStr)]
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Debug,
+    Hash,
+    strum::IntoStaticStr,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum Encoding {
     Borsh,
     Proto,
 }
 
+/// Encodings supported by nodes which don't announce them in the Handshake.
+pub(crate) const LEGACY_ENCODINGS: [Encoding; 2] = [Encoding::Proto, Encoding::Borsh];
+
+/// Picks the encoding of a connection: the first of `preferred` (ordered by preference),
+/// which is also in `supported`. Returns None if there is no common encoding.
+pub(crate) fn negotiate_encoding(
+    preferred: &[Encoding],
+    supported: &[Encoding],
+) -> Option<Encoding> {
+    preferred.iter().find(|e| supported.contains(e)).copied()
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParsePeerMessageError {
     #[error("BorshDecode")]
@@ -377,6 +423,87 @@ pub enum ParsePeerMessageError {
     ProtoDecode(#[source] protobuf::Error),
     #[error("ProtoConv")]
     ProtoConv(#[source] proto_conv::ParsePeerMessageError),
+    #[error("Decompress")]
+    Decompress(#[source] std::io::Error),
+    #[error("DecompressedTooLarge")]
+    DecompressedTooLarge,
+}
+
+/// Upper bound on the size of a decompressed message.
+/// Same as the upper bound on the size of a message on the wire.
+const MAX_DECOMPRESSED_MESSAGE_SIZE: usize = crate::peer::stream::NETWORK_MESSAGE_MAX_SIZE_BYTES;
+/// A compressed message may decompress to at most this many times its size,
+/// or to `MIN_DECOMPRESSED_MESSAGE_LIMIT` if that's more, so that a small
+/// message can't make the node allocate hundreds of MiB.
+const MAX_COMPRESSION_RATIO: usize = 16;
+const MIN_DECOMPRESSED_MESSAGE_LIMIT: usize = 4 * bytesize::MIB as usize;
+
+/// Returns the maximal size of the decompressed content of `compressed_len`
+/// bytes of compressed data.
+fn max_decompressed_size(compressed_len: usize) -> usize {
+    compressed_len
+        .saturating_mul(MAX_COMPRESSION_RATIO)
+        .clamp(MIN_DECOMPRESSED_MESSAGE_LIMIT, MAX_DECOMPRESSED_MESSAGE_SIZE)
+}
+
+/// Compresses a proto-encoded PeerMessage into a proto-encoded `CompressedPeerMessage`.
+/// Returns None if compression doesn't make the message smaller, or if it makes it so
+/// small that the receiver would reject it as decompressing to too much data
+/// (see `max_decompressed_size`); such messages have to be sent uncompressed.
+pub(crate) fn compress_proto(data: &[u8], level: i32) -> Option<Vec<u8>> {
+    let _timer =
+        metrics::PEER_MESSAGE_COMPRESSION_TIME.with_label_values(&["compress"]).start_timer();
+    let zstd = match zstd::bulk::compress(data, level) {
+        Ok(zstd) => zstd,
+        Err(err) => {
+            tracing::warn!(target: "network", ?err, "failed to compress a message");
+            return None;
+        }
+    };
+    if data.len() > max_decompressed_size(zstd.len()) {
+        return None;
+    }
+    let msg = proto::PeerMessage {
+        message_type: Some(proto::peer_message::Message_type::Compressed(
+            proto::CompressedPeerMessage { zstd, ..Default::default() },
+        )),
+        ..Default::default()
+    };
+    let compressed = msg.write_to_bytes().unwrap();
+    if compressed.len() >= data.len() {
+        return None;
+    }
+    metrics::PEER_MESSAGE_COMPRESSION_BYTES
+        .with_label_values(&["sent", "raw"])
+        .inc_by(data.len() as u64);
+    metrics::PEER_MESSAGE_COMPRESSION_BYTES
+        .with_label_values(&["sent", "compressed"])
+        .inc_by(compressed.len() as u64);
+    Some(compressed)
+}
+
+/// Decompresses the content of a `CompressedPeerMessage`.
+fn decompress(zstd: &[u8]) -> Result<Vec<u8>, ParsePeerMessageError> {
+    use std::io::Read as _;
+    let _timer =
+        metrics::PEER_MESSAGE_COMPRESSION_TIME.with_label_values(&["decompress"]).start_timer();
+    let max_size = max_decompressed_size(zstd.len());
+    let mut data = vec![];
+    zstd::stream::read::Decoder::new(zstd)
+        .map_err(ParsePeerMessageError::Decompress)?
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(ParsePeerMessageError::Decompress)?;
+    if data.len() > max_size {
+        return Err(ParsePeerMessageError::DecompressedTooLarge);
+    }
+    metrics::PEER_MESSAGE_COMPRESSION_BYTES
+        .with_label_values(&["received", "raw"])
+        .inc_by(data.len() as u64);
+    metrics::PEER_MESSAGE_COMPRESSION_BYTES
+        .with_label_values(&["received", "compressed"])
+        .inc_by(zstd.len() as u64);
+    Ok(data)
 }
 
 impl PeerMessage {
@@ -405,8 +532,14 @@ impl PeerMessage {
                 .try_into()
                 .map_err(ParsePeerMessageError::BorshConv)?,
             Encoding::Proto => {
-                let proto_msg: proto::PeerMessage = proto::PeerMessage::parse_from_bytes(data)
+                let mut proto_msg: proto::PeerMessage = proto::PeerMessage::parse_from_bytes(data)
                     .map_err(ParsePeerMessageError::ProtoDecode)?;
+                if let Some(proto::peer_message::Message_type::Compressed(c)) =
+                    &proto_msg.message_type
+                {
+                    proto_msg = proto::PeerMessage::parse_from_bytes(&decompress(&c.zstd)?)
+                        .map_err(ParsePeerMessageError::ProtoDecode)?;
+                }
                 if let Ok(extracted_span_context) = extract_span_context(&proto_msg.trace_context) {
                     span.clone().or_current().add_link(extracted_span_context);
                 }
@@ -466,6 +599,13 @@ pub enum RoutedMessageBody {
     VersionedPartialEncodedChunk(PartialEncodedChunk),
     VersionedStateResponse(StateResponseInfo),
     PartialEncodedChunkForward(PartialEncodedChunkForwardMsg),
+    /// Requests the state delta of a shard between the end of the epoch of
+    /// the block with the second hash and the sync hash.
+    StateRequestDelta(ShardId, CryptoHash, CryptoHash),
+    StateDeltaResponse(StateDeltaResponseInfo),
+    /// Sent instead of a `VersionedStateResponse` in response to a state
+    /// request for state which the peer has garbage collected.
+    DataGarbageCollected(GarbageCollectedData),
 }
 
 impl RoutedMessageBody {
@@ -539,6 +679,15 @@ impl fmt::Debug for RoutedMessageBody {
             ),
             RoutedMessageBody::Ping(_) => write!(f, "Ping"),
             RoutedMessageBody::Pong(_) => write!(f, "Pong"),
+            RoutedMessageBody::StateRequestDelta(shard_id, sync_hash, from_hash) => {
+                write!(f, "StateRequestDelta({}, {}, {})", shard_id, sync_hash, from_hash)
+            }
+            RoutedMessageBody::StateDeltaResponse(response) => {
+                write!(f, "StateDeltaResponse({}, {})", response.shard_id, response.sync_hash)
+            }
+            RoutedMessageBody::DataGarbageCollected(data) => {
+                write!(f, "DataGarbageCollected({:?})", data)
+            }
         }
     }
 }
@@ -623,6 +772,7 @@ impl RoutedMessage {
                 | RoutedMessageBody::TxStatusRequest(_, _)
                 | RoutedMessageBody::StateRequestHeader(_, _)
                 | RoutedMessageBody::StateRequestPart(_, _, _)
+                | RoutedMessageBody::StateRequestDelta(_, _, _)
                 | RoutedMessageBody::PartialEncodedChunkRequest(_)
                 | RoutedMessageBody::ReceiptOutcomeRequest(_)
         )
@@ -754,6 +904,25 @@ impl StateResponseInfo {
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Debug, borsh::BorshSerialize, borsh::BorshDeserialize)]
+pub struct StateDeltaResponseInfo {
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+    /// None if the peer couldn't build the delta, e.g. because it doesn't
+    /// have the trie changes anymore.
+    pub delta: Option<ShardStateDelta>,
+}
+
+/// Data which a peer was asked for, but which it has already garbage collected.
+/// The peer responds with it right away, so that the requester can ask another
+/// peer instead of waiting for the request to time out.
+#[derive(PartialEq, Eq, Clone, Debug, borsh::BorshSerialize, borsh::BorshDeserialize)]
+pub enum GarbageCollectedData {
+    Block(CryptoHash),
+    StateHeader { shard_id: ShardId, sync_hash: CryptoHash },
+    StatePart { shard_id: ShardId, sync_hash: CryptoHash, part_id: u64 },
+}
+
 #[derive(
     Debug,
     Clone,
@@ -2,7 +2,7 @@
 use super::*;
 
 use crate::network_protocol::proto;
-use crate::network_protocol::{Handshake, HandshakeFailureReason};
+use crate::network_protocol::{Encoding, Handshake, HandshakeFailureReason, LEGACY_ENCODINGS};
 use crate::network_protocol::{PeerChainInfoV2, PeerInfo};
 use near_primitives::block::GenesisId;
 use protobuf::MessageField as MF;
@@ -90,6 +90,21 @@ impl From<&Handshake> for proto::Handshake {
             sender_chain_info: MF::some((&x.sender_chain_info).into()),
             partial_edge_info: MF::some((&x.partial_edge_info).into()),
             owned_account: x.owned_account.as_ref().map(Into::into).into(),
+            supports_compression: x.supports_compression,
+            supported_encodings: x
+                .supported_encodings
+                .iter()
+                .map(|e| {
+                    match e {
+                        Encoding::Borsh => proto::Encoding::BORSH,
+                        Encoding::Proto => proto::Encoding::PROTO,
+                    }
+                    .into()
+                })
+                .collect(),
+            supports_multiplexing: x.supports_multiplexing,
+            supports_keepalive: x.supports_keepalive,
+            sender_earliest_height: x.sender_earliest_height,
             ..Self::default()
         }
     }
@@ -120,6 +135,23 @@ impl TryFrom<&proto::Handshake> for Handshake {
                 .map_err(Self::Error::PartialEdgeInfo)?,
             owned_account: try_from_optional(&p.owned_account)
                 .map_err(Self::Error::OwnedAccount)?,
+            supports_compression: p.supports_compression,
+            supported_encodings: if p.supported_encodings.is_empty() {
+                LEGACY_ENCODINGS.to_vec()
+            } else {
+                // Encodings unknown to us are skipped.
+                p.supported_encodings
+                    .iter()
+                    .filter_map(|e| match e.enum_value() {
+                        Ok(proto::Encoding::BORSH) => Some(Encoding::Borsh),
+                        Ok(proto::Encoding::PROTO) => Some(Encoding::Proto),
+                        _ => None,
+                    })
+                    .collect()
+            },
+            supports_multiplexing: p.supports_multiplexing,
+            supports_keepalive: p.supports_keepalive,
+            sender_earliest_height: p.sender_earliest_height,
         })
     }
 }
@@ -3,7 +3,9 @@ use super::*;
 
 use crate::network_protocol::proto;
 use crate::network_protocol::proto::peer_message::Message_type as ProtoMT;
-use crate::network_protocol::{PeerMessage, RoutingTableUpdate, SyncAccountsData};
+use crate::network_protocol::{
+    GarbageCollectedData, PeerMessage, RoutingTableUpdate, SyncAccountsData,
+};
 use crate::network_protocol::{RoutedMessage, RoutedMessageV2};
 use crate::time::error::ComponentRange;
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
@@ -120,6 +122,12 @@ impl From<&PeerMessage> for proto::PeerMessage {
                         ..Default::default()
                     })
                 }
+                PeerMessage::BlockHeadersAtHeightsRequest(heights) => {
+                    ProtoMT::BlockHeadersRequest(proto::BlockHeadersRequest {
+                        heights: heights.clone(),
+                        ..Default::default()
+                    })
+                }
                 PeerMessage::BlockHeaders(bhs) => {
                     ProtoMT::BlockHeadersResponse(proto::BlockHeadersResponse {
                         block_headers: bhs.iter().map(Into::into).collect(),
@@ -134,6 +142,12 @@ impl From<&PeerMessage> for proto::PeerMessage {
                     block: MF::some(b.into()),
                     ..Default::default()
                 }),
+                PeerMessage::DataGarbageCollected(data) => {
+                    ProtoMT::DataGarbageCollected(proto::DataGarbageCollected {
+                        borsh: data.try_to_vec().unwrap(),
+                        ..Default::default()
+                    })
+                }
                 PeerMessage::Transaction(t) => ProtoMT::Transaction(proto::SignedTransaction {
                     borsh: t.try_to_vec().unwrap(),
                     ..Default::default()
@@ -149,6 +163,14 @@ impl From<&PeerMessage> for proto::PeerMessage {
                     borsh: r.try_to_vec().unwrap(),
                     ..Default::default()
                 }),
+                PeerMessage::KeepalivePing(nonce) => ProtoMT::KeepalivePing(proto::KeepalivePing {
+                    nonce: *nonce,
+                    ..Default::default()
+                }),
+                PeerMessage::KeepalivePong(nonce) => ProtoMT::KeepalivePong(proto::KeepalivePong {
+                    nonce: *nonce,
+                    ..Default::default()
+                }),
             }),
             ..Default::default()
         }
@@ -158,6 +180,7 @@ impl From<&PeerMessage> for proto::PeerMessage {
 pub type ParseTransactionError = borsh::maybestd::io::Error;
 pub type ParseRoutedError = borsh::maybestd::io::Error;
 pub type ParseChallengeError = borsh::maybestd::io::Error;
+pub type ParseDataGarbageCollectedError = borsh::maybestd::io::Error;
 
 #[derive(thiserror::Error, Debug)]
 pub enum ParsePeerMessageError {
@@ -185,6 +208,8 @@ pub enum ParsePeerMessageError {
     BlockRequest(ParseRequiredError<ParseCryptoHashError>),
     #[error("block_response: {0}")]
     BlockResponse(ParseRequiredError<ParseBlockError>),
+    #[error("data_garbage_collected: {0}")]
+    DataGarbageCollected(ParseDataGarbageCollectedError),
     #[error("transaction: {0}")]
     Transaction(ParseTransactionError),
     #[error("routed: {0}")]
@@ -195,6 +220,8 @@ pub enum ParsePeerMessageError {
     RoutedCreatedAtTimestamp(ComponentRange),
     #[error("sync_accounts_data: {0}")]
     SyncAccountsData(ParseVecError<ParseSignedAccountDataError>),
+    #[error("unexpected compressed message")]
+    UnexpectedCompressed,
 }
 
 impl TryFrom<&proto::PeerMessage> for PeerMessage {
@@ -242,6 +269,9 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::PeersResponse(pr) => PeerMessage::PeersResponse(
                 try_from_slice(&pr.peers).map_err(Self::Error::PeersResponse)?,
             ),
+            ProtoMT::BlockHeadersRequest(bhr) if !bhr.heights.is_empty() => {
+                PeerMessage::BlockHeadersAtHeightsRequest(bhr.heights.clone())
+            }
             ProtoMT::BlockHeadersRequest(bhr) => PeerMessage::BlockHeadersRequest(
                 try_from_slice(&bhr.block_hashes).map_err(Self::Error::BlockHeadersRequest)?,
             ),
@@ -254,6 +284,10 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::BlockResponse(br) => PeerMessage::Block(
                 try_from_required(&br.block).map_err(Self::Error::BlockResponse)?,
             ),
+            ProtoMT::DataGarbageCollected(dgc) => PeerMessage::DataGarbageCollected(
+                GarbageCollectedData::try_from_slice(&dgc.borsh)
+                    .map_err(Self::Error::DataGarbageCollected)?,
+            ),
             ProtoMT::Transaction(t) => PeerMessage::Transaction(
                 SignedTransaction::try_from_slice(&t.borsh).map_err(Self::Error::Transaction)?,
             ),
@@ -271,6 +305,11 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::Challenge(c) => PeerMessage::Challenge(
                 Challenge::try_from_slice(&c.borsh).map_err(Self::Error::Challenge)?,
             ),
+            ProtoMT::KeepalivePing(p) => PeerMessage::KeepalivePing(p.nonce),
+            ProtoMT::KeepalivePong(p) => PeerMessage::KeepalivePong(p.nonce),
+            // Compressed messages are unwrapped in PeerMessage::deserialize,
+            // a compressed message within a compressed message is not allowed.
+            ProtoMT::Compressed(_) => return Err(Self::Error::UnexpectedCompressed),
         })
     }
 }
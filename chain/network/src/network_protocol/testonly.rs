@@ -286,6 +286,7 @@ impl Chain {
         ChainInfo {
             tracked_shards: Default::default(),
             block: self.blocks.last().unwrap().clone(),
+            earliest_height: self.blocks[0].header().height(),
             tier1_accounts: Arc::new(self.get_tier1_accounts()),
         }
     }
@@ -346,6 +347,13 @@ pub fn make_handshake<R: Rng>(rng: &mut R, chain: &Chain) -> Handshake {
         sender_chain_info: chain.get_peer_chain_info(),
         partial_edge_info: make_partial_edge(rng),
         owned_account: None,
+        supports_compression: false,
+        // Fields which are not representable in borsh are set to
+        // the values they get when decoded from borsh.
+        supported_encodings: vec![Encoding::Borsh],
+        supports_multiplexing: false,
+        supports_keepalive: false,
+        sender_earliest_height: None,
     }
 }
 
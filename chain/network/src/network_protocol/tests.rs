@@ -167,3 +167,143 @@ fn serialize_deserialize() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn serialize_block_headers_at_heights_request() {
+    let msg = PeerMessage::BlockHeadersAtHeightsRequest(vec![100, 99, 97, 0]);
+    let got = PeerMessage::deserialize(Encoding::Proto, &msg.serialize(Encoding::Proto)).unwrap();
+    assert_eq!(got, msg);
+    // Not supported by Borsh, sent as a request without hashes.
+    let got = PeerMessage::deserialize(Encoding::Borsh, &msg.serialize(Encoding::Borsh)).unwrap();
+    assert_eq!(got, PeerMessage::BlockHeadersRequest(vec![]));
+}
+
+#[test]
+fn serialize_data_garbage_collected() {
+    use borsh::{BorshDeserialize as _, BorshSerialize as _};
+
+    let msg = PeerMessage::DataGarbageCollected(GarbageCollectedData::Block(CryptoHash([7; 32])));
+    let got = PeerMessage::deserialize(Encoding::Proto, &msg.serialize(Encoding::Proto)).unwrap();
+    assert_eq!(got, msg);
+    // Not supported by Borsh, sent as an empty routing table update.
+    let got = PeerMessage::deserialize(Encoding::Borsh, &msg.serialize(Encoding::Borsh)).unwrap();
+    assert_eq!(got, PeerMessage::SyncRoutingTable(RoutingTableUpdate::default()));
+
+    let body = RoutedMessageBody::DataGarbageCollected(GarbageCollectedData::StatePart {
+        shard_id: 1,
+        sync_hash: CryptoHash([7; 32]),
+        part_id: 3,
+    });
+    assert_eq!(RoutedMessageBody::try_from_slice(&body.try_to_vec().unwrap()).unwrap(), body);
+}
+
+#[test]
+fn serialize_deserialize_compressed() {
+    // Sync requests and responses are highly repetitive.
+    let msg = PeerMessage::BlockHeadersRequest(vec![CryptoHash([7; 32]); 1000]);
+    let raw = msg.serialize(Encoding::Proto);
+    let compressed = compress_proto(&raw, 3).unwrap();
+    assert!(compressed.len() < raw.len());
+    assert_eq!(PeerMessage::deserialize(Encoding::Proto, &compressed).unwrap(), msg);
+    // Compressed messages are not parseable as borsh.
+    assert!(PeerMessage::deserialize(Encoding::Borsh, &compressed).is_err());
+    // Nested compression is not allowed.
+    let wrap = |zstd| proto::PeerMessage {
+        message_type: Some(proto::peer_message::Message_type::Compressed(
+            proto::CompressedPeerMessage { zstd, ..Default::default() },
+        )),
+        ..Default::default()
+    };
+    let nested = wrap(zstd::bulk::compress(&compressed, 3).unwrap()).write_to_bytes().unwrap();
+    assert!(PeerMessage::deserialize(Encoding::Proto, &nested).is_err());
+    // Garbage is rejected.
+    let garbage = wrap(vec![1, 2, 3, 4]).write_to_bytes().unwrap();
+    assert!(PeerMessage::deserialize(Encoding::Proto, &garbage).is_err());
+    // So are small messages decompressing to much more than they weigh.
+    let bomb = zstd::bulk::compress(&vec![0; 2 * MIN_DECOMPRESSED_MESSAGE_LIMIT], 3).unwrap();
+    assert!(bomb.len() * MAX_COMPRESSION_RATIO < 2 * MIN_DECOMPRESSED_MESSAGE_LIMIT);
+    assert!(matches!(
+        PeerMessage::deserialize(Encoding::Proto, &wrap(bomb).write_to_bytes().unwrap()),
+        Err(ParsePeerMessageError::DecompressedTooLarge)
+    ));
+}
+
+#[test]
+fn compress_proto_round_trip() {
+    // A message compressing better than the receiver accepts is sent uncompressed.
+    let msg = PeerMessage::BlockHeadersRequest(vec![CryptoHash([7; 32]); 500_000]);
+    let raw = msg.serialize(Encoding::Proto);
+    assert!(raw.len() > MIN_DECOMPRESSED_MESSAGE_LIMIT);
+    assert!(zstd::bulk::compress(&raw, 3).unwrap().len() * MAX_COMPRESSION_RATIO < raw.len());
+    assert_eq!(compress_proto(&raw, 3), None);
+    assert_eq!(PeerMessage::deserialize(Encoding::Proto, &raw).unwrap(), msg);
+
+    // Whatever gets compressed decompresses back to the message.
+    for n in [1, 100, 10_000, 100_000] {
+        let msg = PeerMessage::BlockHeadersRequest(vec![CryptoHash([7; 32]); n]);
+        let raw = msg.serialize(Encoding::Proto);
+        if let Some(compressed) = compress_proto(&raw, 3) {
+            assert_eq!(PeerMessage::deserialize(Encoding::Proto, &compressed).unwrap(), msg);
+        }
+    }
+}
+
+#[test]
+fn encodings_conformance() {
+    let mut rng = make_rng(2398472934);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 12);
+    let msgs = [
+        PeerMessage::Tier2Handshake(data::make_handshake(&mut rng, &chain)),
+        PeerMessage::SyncRoutingTable(data::make_routing_table(&mut rng)),
+        PeerMessage::PeersResponse((0..5).map(|_| data::make_peer_info(&mut rng)).collect()),
+        PeerMessage::BlockHeaders(chain.get_block_headers()),
+        PeerMessage::Block(chain.blocks[5].clone()),
+        PeerMessage::Transaction(data::make_signed_transaction(&mut rng)),
+        PeerMessage::Challenge(data::make_challenge(&mut rng)),
+    ];
+    // Messages representable in both encodings decode to identical structs,
+    // no matter which encoding was negotiated for the connection.
+    for m in &msgs {
+        let proto = PeerMessage::deserialize(Encoding::Proto, &m.serialize(Encoding::Proto))
+            .with_context(|| m.to_string())
+            .unwrap();
+        let borsh = PeerMessage::deserialize(Encoding::Borsh, &m.serialize(Encoding::Borsh))
+            .with_context(|| m.to_string())
+            .unwrap();
+        assert_eq!(proto, borsh, "{m}");
+    }
+}
+
+#[test]
+fn handshake_supported_encodings() {
+    let mut rng = make_rng(2398472934);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 12);
+    let mut h = data::make_handshake(&mut rng, &chain);
+    h.supported_encodings = vec![Encoding::Proto];
+    let m = PeerMessage::Tier2Handshake(h.clone());
+    assert_eq!(
+        m,
+        PeerMessage::deserialize(Encoding::Proto, &m.serialize(Encoding::Proto)).unwrap()
+    );
+
+    // Nodes which don't announce encodings support both proto and borsh.
+    let mut p = proto::Handshake::from(&h);
+    p.supported_encodings.clear();
+    assert_eq!(Handshake::try_from(&p).unwrap().supported_encodings, LEGACY_ENCODINGS.to_vec());
+    // Encodings unknown to us are skipped.
+    p.supported_encodings =
+        vec![protobuf::EnumOrUnknown::from_i32(1000), proto::Encoding::BORSH.into()];
+    assert_eq!(Handshake::try_from(&p).unwrap().supported_encodings, vec![Encoding::Borsh]);
+}
+
+#[test]
+fn negotiate_encodings() {
+    use Encoding::*;
+    assert_eq!(negotiate_encoding(&[Proto, Borsh], &[Borsh, Proto]), Some(Proto));
+    assert_eq!(negotiate_encoding(&[Borsh, Proto], &[Proto, Borsh]), Some(Borsh));
+    assert_eq!(negotiate_encoding(&[Proto, Borsh], &[Borsh]), Some(Borsh));
+    assert_eq!(negotiate_encoding(&[Proto], &[Borsh]), None);
+    assert_eq!(negotiate_encoding(&[], &[Borsh]), None);
+}
@@ -0,0 +1,87 @@
+//! Application-level liveness checks of a connection.
+//!
+//! TCP detects a dead peer only once the retransmission timeout expires (~15 minutes
+//! with the default Linux settings), and doesn't detect it at all on an idle connection.
+//! Connections over flaky routes are often left half-dead: the socket is open, but
+//! nothing gets through. To detect that in seconds, a PeerActor periodically sends a
+//! `KeepalivePing` and expects a `KeepalivePong` within a timeout adapted to the round
+//! trip time observed on the connection (RFC 6298 style estimation).
+use crate::config;
+use crate::time;
+
+/// Multiplier of the RTT variance in the timeout, as in RFC 6298.
+const RTTVAR_MULTIPLIER: i32 = 4;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// Nothing to do, the ping in flight hasn't timed out yet.
+    Wait,
+    /// Send a `KeepalivePing` with the given nonce.
+    SendPing(u64),
+    /// The ping in flight hasn't been answered in time, the connection is dead.
+    Timeout,
+}
+
+pub(crate) struct Keepalive {
+    cfg: config::Keepalive,
+    /// Smoothed RTT. None until the first pong is received.
+    srtt: Option<time::Duration>,
+    /// RTT variance.
+    rttvar: time::Duration,
+    /// Ping awaiting a pong: nonce and the time it was sent.
+    in_flight: Option<(u64, time::Instant)>,
+    next_nonce: u64,
+}
+
+impl Keepalive {
+    pub fn new(cfg: config::Keepalive) -> Self {
+        Self { cfg, srtt: None, rttvar: time::Duration::ZERO, in_flight: None, next_nonce: 0 }
+    }
+
+    /// Time within which a pong is expected.
+    /// Before any RTT has been observed, it is the max timeout.
+    pub fn timeout(&self) -> time::Duration {
+        match self.srtt {
+            None => self.cfg.max_timeout,
+            Some(srtt) => (srtt + self.rttvar * RTTVAR_MULTIPLIER)
+                .clamp(self.cfg.min_timeout, self.cfg.max_timeout),
+        }
+    }
+
+    /// Called every `ping_interval`.
+    pub fn tick(&mut self, now: time::Instant) -> Action {
+        if let Some((_, sent)) = self.in_flight {
+            if now - sent > self.timeout() {
+                return Action::Timeout;
+            }
+            return Action::Wait;
+        }
+        let nonce = self.next_nonce;
+        self.next_nonce += 1;
+        self.in_flight = Some((nonce, now));
+        Action::SendPing(nonce)
+    }
+
+    /// Called when a `KeepalivePong` is received.
+    /// Returns the RTT, unless the pong doesn't match the ping in flight.
+    pub fn pong(&mut self, now: time::Instant, nonce: u64) -> Option<time::Duration> {
+        match self.in_flight {
+            Some((n, sent)) if n == nonce => {
+                self.in_flight = None;
+                let rtt = now - sent;
+                match self.srtt {
+                    None => {
+                        self.srtt = Some(rtt);
+                        self.rttvar = rtt / 2;
+                    }
+                    Some(srtt) => {
+                        self.rttvar = self.rttvar * 3 / 4 + (srtt - rtt).abs() / 4;
+                        self.srtt = Some(srtt * 7 / 8 + rtt / 8);
+                    }
+                }
+                Some(rtt)
+            }
+            _ => None,
+        }
+    }
+}
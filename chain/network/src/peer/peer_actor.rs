@@ -1,11 +1,15 @@
 use crate::accounts_data;
+use crate::client::DataResponse;
 use crate::concurrency::atomic_cell::AtomicCell;
 use crate::concurrency::demux;
+use crate::config;
+use crate::network_protocol;
 use crate::network_protocol::{
     Edge, EdgeState, Encoding, OwnedAccount, ParsePeerMessageError, PartialEdgeInfo,
     PeerChainInfoV2, PeerIdOrHash, PeerInfo, RawRoutedMessage, RoutedMessageBody, RoutedMessageV2,
     RoutingTableUpdate, StateResponseInfo, SyncAccountsData,
 };
+use crate::peer::keepalive;
 use crate::peer::stream;
 use crate::peer::tracker::Tracker;
 use crate::peer_manager::connection;
@@ -104,6 +108,10 @@ pub(crate) enum ClosingReason {
     TooLargeClockSkew,
     #[error("owned_account.peer_id doesn't match handshake.sender_peer_id")]
     OwnedAccountMismatch,
+    #[error("keepalive ping not answered within {0}")]
+    KeepaliveTimeout(time::Duration),
+    #[error("messages queued for sending exceeded {0}B for too long")]
+    SendBacklogExceeded(u64),
     #[error("PeerActor stopped NOT via PeerActor::stop()")]
     Unknown,
 }
@@ -137,6 +145,15 @@ pub(crate) struct PeerActor {
     /// Whether the PeerActor should skip protobuf support detection and use
     /// a given encoding right away.
     force_encoding: Option<Encoding>,
+    /// Encoding negotiated in the handshake.
+    negotiated_encoding: Option<Encoding>,
+    /// Compression of large messages, set once both ends declared support
+    /// for it in the handshake.
+    compression: Option<config::Compression>,
+    /// Liveness checks, set once both ends declared support for them in the handshake.
+    keepalive: Option<keepalive::Keepalive>,
+    /// Since when the send backlog exceeds `config::Keepalive::max_send_backlog_bytes`.
+    send_backlog_since: Option<time::Instant>,
 
     /// Peer status.
     peer_status: PeerStatus,
@@ -166,6 +183,54 @@ struct HandshakeSpec {
 type HandshakeSignalSender = tokio::sync::oneshot::Sender<std::convert::Infallible>;
 pub type HandshakeSignal = tokio::sync::oneshot::Receiver<std::convert::Infallible>;
 
+/// Body of the response to a state request.
+fn state_response_body(response: DataResponse<StateResponseInfo>) -> RoutedMessageBody {
+    match response {
+        DataResponse::Data(info) => RoutedMessageBody::VersionedStateResponse(info),
+        DataResponse::GarbageCollected(data) => RoutedMessageBody::DataGarbageCollected(data),
+    }
+}
+
+/// Logical stream of the connection which the message is sent on.
+fn stream_kind(msg: &PeerMessage) -> stream::Kind {
+    match msg {
+        PeerMessage::Tier1Handshake(_)
+        | PeerMessage::Tier2Handshake(_)
+        | PeerMessage::HandshakeFailure(..)
+        | PeerMessage::LastEdge(_)
+        | PeerMessage::Disconnect
+        | PeerMessage::Block(_)
+        | PeerMessage::Challenge(_)
+        | PeerMessage::KeepalivePing(_)
+        | PeerMessage::KeepalivePong(_) => stream::Kind::Consensus,
+        PeerMessage::BlockHeadersRequest(_)
+        | PeerMessage::BlockHeadersAtHeightsRequest(_)
+        | PeerMessage::BlockHeaders(_)
+        | PeerMessage::BlockRequest(_)
+        | PeerMessage::DataGarbageCollected(_) => stream::Kind::Sync,
+        PeerMessage::Transaction(_) => stream::Kind::Tx,
+        PeerMessage::Routed(msg) => match &msg.msg.body {
+            RoutedMessageBody::BlockApproval(_)
+            | RoutedMessageBody::PartialEncodedChunkRequest(_)
+            | RoutedMessageBody::PartialEncodedChunkResponse(_)
+            | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+            | RoutedMessageBody::PartialEncodedChunkForward(_) => stream::Kind::Consensus,
+            RoutedMessageBody::ForwardTx(_)
+            | RoutedMessageBody::TxStatusRequest(..)
+            | RoutedMessageBody::TxStatusResponse(_) => stream::Kind::Tx,
+            RoutedMessageBody::StateRequestHeader(..)
+            | RoutedMessageBody::StateRequestPart(..)
+            | RoutedMessageBody::StateResponse(_)
+            | RoutedMessageBody::VersionedStateResponse(_)
+            | RoutedMessageBody::StateRequestDelta(..)
+            | RoutedMessageBody::StateDeltaResponse(_)
+            | RoutedMessageBody::DataGarbageCollected(_) => stream::Kind::Sync,
+            _ => stream::Kind::Other,
+        },
+        _ => stream::Kind::Other,
+    }
+}
+
 impl PeerActor {
     /// Spawns a PeerActor on a separate actix::Arbiter and awaits for the
     /// handshake to succeed/fail. The actual result is not returned because
@@ -212,13 +277,19 @@ impl PeerActor {
         network_state: Arc<NetworkState>,
     ) -> Result<(actix::Addr<Self>, HandshakeSignal), ClosingReason> {
         let connecting_status = match &stream.type_ {
-            tcp::StreamType::Inbound => ConnectingStatus::Inbound(
+            // Trusted peers are not subject to the limit of inflight inbound handshakes.
+            tcp::StreamType::Inbound
+                if network_state.config.trusted_peers.contains_ip(stream.peer_addr.ip()) =>
+            {
+                ConnectingStatus::Inbound(None)
+            }
+            tcp::StreamType::Inbound => ConnectingStatus::Inbound(Some(
                 network_state
                     .inbound_handshake_permits
                     .clone()
                     .try_acquire_owned()
                     .map_err(|_| ClosingReason::TooManyInbound)?,
-            ),
+            )),
             tcp::StreamType::Outbound { tier, peer_id } => ConnectingStatus::Outbound {
                 _permit: match tier {
                     tcp::Tier::T1 => network_state
@@ -248,6 +319,11 @@ impl PeerActor {
                 },
             },
         };
+        if let Some(cfg) = &network_state.config.tcp_keepalive {
+            if let Err(err) = stream.set_keepalive(cfg) {
+                tracing::warn!(target: "network", ?err, "Failed to enable TCP keepalive");
+            }
+        }
         // Override force_encoding for outbound Tier1 connections,
         // since Tier1Handshake is supported only with proto encoding.
         let force_encoding = match &stream.type_ {
@@ -289,6 +365,10 @@ impl PeerActor {
                     routed_message_cache: LruCache::new(ROUTED_MESSAGE_CACHE_SIZE),
                     protocol_buffers_supported: false,
                     force_encoding,
+                    negotiated_encoding: None,
+                    compression: None,
+                    keepalive: None,
+                    send_backlog_since: None,
                     peer_info: match &stream_type {
                         tcp::StreamType::Inbound => None,
                         tcp::StreamType::Outbound { peer_id, .. } => Some(PeerInfo {
@@ -306,6 +386,7 @@ impl PeerActor {
     }
 
     // Determines the encoding to use for communication with the peer.
+    // Once the Handshake has been processed, it is the negotiated encoding.
     // It can be None while Handshake with the peer has not been finished yet.
     // In case it is None, both encodings are attempted for parsing, and each message
     // is sent twice.
@@ -313,6 +394,12 @@ impl PeerActor {
         if self.force_encoding.is_some() {
             return self.force_encoding;
         }
+        if self.negotiated_encoding.is_some() {
+            return self.negotiated_encoding;
+        }
+        if let [enc] = &self.network_state.config.encodings[..] {
+            return Some(*enc);
+        }
         if self.protocol_buffers_supported {
             return Some(Encoding::Proto);
         }
@@ -373,11 +460,18 @@ impl PeerActor {
             _ => (),
         };
 
-        let bytes = msg.serialize(enc);
+        let mut bytes = msg.serialize(enc);
+        if let (Encoding::Proto, Some(c)) = (enc, &self.compression) {
+            if bytes.len() >= c.min_message_size {
+                if let Some(compressed) = network_protocol::compress_proto(&bytes, c.level) {
+                    bytes = compressed;
+                }
+            }
+        }
         self.tracker.lock().increment_sent(&self.clock, bytes.len() as u64);
         let bytes_len = bytes.len();
         tracing::trace!(target: "network", msg_len = bytes_len);
-        self.framed.send(stream::Frame(bytes));
+        self.framed.send(stream_kind(msg), stream::Frame(bytes));
         metrics::PEER_DATA_SENT_BYTES.inc_by(bytes_len as u64);
         metrics::PEER_MESSAGE_SENT_BY_TYPE_TOTAL.with_label_values(&[msg_type]).inc();
         metrics::PEER_MESSAGE_SENT_BY_TYPE_BYTES
@@ -386,11 +480,15 @@ impl PeerActor {
     }
 
     fn send_handshake(&self, spec: HandshakeSpec) {
-        let (height, tracked_shards) =
+        let (height, tracked_shards, earliest_height) =
             if let Some(chain_info) = self.network_state.chain_info.load().as_ref() {
-                (chain_info.block.header().height(), chain_info.tracked_shards.clone())
+                (
+                    chain_info.block.header().height(),
+                    chain_info.tracked_shards.clone(),
+                    Some(chain_info.earliest_height),
+                )
             } else {
-                (0, vec![])
+                (0, vec![], None)
             };
         let handshake = Handshake {
             protocol_version: spec.protocol_version,
@@ -414,6 +512,12 @@ impl PeerActor {
                 }
                 .sign(vc.signer.as_ref())
             }),
+            supports_compression: self.network_state.config.compression.is_some(),
+            supported_encodings: self.network_state.config.encodings.clone(),
+            supports_multiplexing: self.network_state.config.multiplexing,
+            // Pings are always answered, even if we don't send any.
+            supports_keepalive: true,
+            sender_earliest_height: earliest_height,
         };
         let msg = match spec.tier {
             tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
@@ -430,6 +534,57 @@ impl PeerActor {
         ctx.stop();
     }
 
+    fn schedule_liveness_check(&self, ctx: &mut actix::Context<PeerActor>, period: time::Duration) {
+        let clock = self.clock.clone();
+        ctx.spawn(wrap_future(async move { clock.sleep(period).await }).map(
+            move |(), act: &mut PeerActor, ctx| {
+                if act.check_liveness(ctx) {
+                    act.schedule_liveness_check(ctx, period);
+                }
+            },
+        ));
+    }
+
+    /// Closes the connection if the peer doesn't answer keepalive pings in time,
+    /// or doesn't read the messages we send for too long.
+    /// Returns false iff the connection has been closed.
+    fn check_liveness(&mut self, ctx: &mut actix::Context<PeerActor>) -> bool {
+        let cfg = match &self.network_state.config.keepalive {
+            Some(cfg) => cfg.clone(),
+            None => return false,
+        };
+        let now = self.clock.now();
+        if self.stats.bytes_to_send.load(Ordering::Acquire) > cfg.max_send_backlog_bytes {
+            let since = *self.send_backlog_since.get_or_insert(now);
+            if now - since > cfg.send_backlog_timeout {
+                tracing::info!(target: "network", peer = %self.peer_info, backlog_since = ?since, "Send backlog too large, closing connection");
+                metrics::PEER_CONNECTIONS_EVICTED.with_label_values(&["send_backlog"]).inc();
+                self.stop(ctx, ClosingReason::SendBacklogExceeded(cfg.max_send_backlog_bytes));
+                return false;
+            }
+        } else {
+            self.send_backlog_since = None;
+        }
+        let action = match &mut self.keepalive {
+            Some(keepalive) => keepalive.tick(now),
+            None => return true,
+        };
+        match action {
+            keepalive::Action::Wait => {}
+            keepalive::Action::SendPing(nonce) => {
+                self.send_message_or_log(&PeerMessage::KeepalivePing(nonce))
+            }
+            keepalive::Action::Timeout => {
+                let timeout = self.keepalive.as_ref().unwrap().timeout();
+                tracing::info!(target: "network", peer = %self.peer_info, %timeout, "Keepalive ping not answered, closing connection");
+                metrics::PEER_CONNECTIONS_EVICTED.with_label_values(&["keepalive_timeout"]).inc();
+                self.stop(ctx, ClosingReason::KeepaliveTimeout(timeout));
+                return false;
+            }
+        }
+        true
+    }
+
     /// `PeerId` of the current node.
     fn my_node_id(&self) -> &PeerId {
         &self.my_node_info.id
@@ -553,6 +708,31 @@ impl PeerActor {
             }
         }
 
+        if self.force_encoding.is_none() {
+            // Preferences of the inbound end of the connection take priority,
+            // so that both ends pick the same encoding.
+            let ours = &self.network_state.config.encodings;
+            let theirs = &handshake.supported_encodings;
+            let (preferred, supported) = match cs {
+                ConnectingStatus::Outbound { .. } => (theirs, ours),
+                ConnectingStatus::Inbound { .. } => (ours, theirs),
+            };
+            match network_protocol::negotiate_encoding(preferred, supported) {
+                Some(enc) => self.negotiated_encoding = Some(enc),
+                None => {
+                    tracing::debug!(target: "network", peer_id=?handshake.sender_peer_id, supported_encodings=?handshake.supported_encodings, "No common encoding. Disconnecting peer");
+                    self.stop(ctx, ClosingReason::HandshakeFailed);
+                    return;
+                }
+            }
+        }
+        if handshake.supports_compression {
+            self.compression = self.network_state.config.compression.clone();
+        }
+        if handshake.supports_multiplexing && self.network_state.config.multiplexing {
+            self.framed.enable_multiplexing();
+        }
+
         // Merge partial edges.
         let nonce = handshake.partial_edge_info.nonce;
         let partial_edge_info = match cs {
@@ -589,10 +769,17 @@ impl PeerActor {
             tier,
             addr: ctx.address(),
             peer_info: peer_info.clone(),
+            peer_addr: self.peer_addr,
             owned_account: handshake.owned_account.clone(),
             genesis_id: handshake.sender_chain_info.genesis_id.clone(),
             tracked_shards: handshake.sender_chain_info.tracked_shards.clone(),
             archival: handshake.sender_chain_info.archival,
+            // A peer can't hold blocks above its head.
+            advertised_earliest_height: handshake
+                .sender_earliest_height
+                .map(|height| height.min(handshake.sender_chain_info.height)),
+            handshake_height: handshake.sender_chain_info.height,
+            protocol_version: handshake.protocol_version,
             last_block: Default::default(),
             peer_type: self.peer_type,
             stats: self.stats.clone(),
@@ -649,6 +836,13 @@ impl PeerActor {
                     Ok(()) => {
                         act.peer_info = Some(peer_info).into();
                         act.peer_status = PeerStatus::Ready(conn.clone());
+                        if let Some(cfg) = act.network_state.config.keepalive.clone() {
+                            // Keepalive messages exist only in proto encoding.
+                            if handshake.supports_keepalive && act.encoding() == Some(Encoding::Proto) {
+                                act.keepalive = Some(keepalive::Keepalive::new(cfg.clone()));
+                            }
+                            act.schedule_liveness_check(ctx, cfg.ping_interval);
+                        }
                         // Respond to handshake if it's inbound and connection was consolidated.
                         if act.peer_type == PeerType::Inbound {
                             act.send_handshake(HandshakeSpec{
@@ -875,7 +1069,9 @@ impl PeerActor {
         clock: &time::Clock,
         network_state: &NetworkState,
         peer_id: PeerId,
+        author: PeerId,
         msg_hash: CryptoHash,
+        msg_len: usize,
         body: RoutedMessageBody,
     ) -> Result<Option<RoutedMessageBody>, ReasonForBan> {
         let _span = tracing::trace_span!(target: "network", "receive_routed_message").entered();
@@ -891,18 +1087,31 @@ impl PeerActor {
             }
             RoutedMessageBody::StateRequestHeader(shard_id, sync_hash) => network_state
                 .client
-                .state_request_header(shard_id, sync_hash)
+                .state_request_header(shard_id, sync_hash, author)
                 .await?
-                .map(RoutedMessageBody::VersionedStateResponse),
+                .map(state_response_body),
             RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => network_state
                 .client
-                .state_request_part(shard_id, sync_hash, part_id)
+                .state_request_part(shard_id, sync_hash, part_id, author)
                 .await?
-                .map(RoutedMessageBody::VersionedStateResponse),
+                .map(state_response_body),
             RoutedMessageBody::VersionedStateResponse(info) => {
                 network_state.client.state_response(info).await;
                 None
             }
+            RoutedMessageBody::StateRequestDelta(shard_id, sync_hash, from_hash) => network_state
+                .client
+                .state_request_delta(shard_id, sync_hash, from_hash, author)
+                .await?
+                .map(RoutedMessageBody::StateDeltaResponse),
+            RoutedMessageBody::StateDeltaResponse(info) => {
+                network_state.client.state_delta_response(info).await;
+                None
+            }
+            RoutedMessageBody::DataGarbageCollected(data) => {
+                network_state.client.data_garbage_collected(data, author).await;
+                None
+            }
             RoutedMessageBody::StateResponse(info) => {
                 network_state.client.state_response(StateResponseInfo::V1(info)).await;
                 None
@@ -920,7 +1129,10 @@ impl PeerActor {
                 None
             }
             RoutedMessageBody::PartialEncodedChunkResponse(response) => {
-                network_state.client.partial_encoded_chunk_response(response, clock.now()).await;
+                network_state
+                    .client
+                    .partial_encoded_chunk_response(response, clock.now(), author, msg_len)
+                    .await;
                 None
             }
             RoutedMessageBody::VersionedPartialEncodedChunk(chunk) => {
@@ -950,6 +1162,7 @@ impl PeerActor {
         ctx: &mut actix::Context<Self>,
         conn: &connection::Connection,
         msg: PeerMessage,
+        msg_len: usize,
     ) {
         let _span = tracing::trace_span!(target: "network", "receive_message").entered();
         // This is a fancy way to clone the message iff event_sink is non-null.
@@ -963,6 +1176,12 @@ impl PeerActor {
             PeerMessage::Block(block) => {
                 self.network_state.txns_since_last_block.store(0, Ordering::Release);
                 let hash = *block.hash();
+                self.network_state.seen.received(
+                    &self.clock,
+                    msg.msg_variant(),
+                    hash,
+                    conn.peer_info.id.clone(),
+                );
                 let height = block.header().height();
                 conn.last_block.rcu(|last_block| {
                     if last_block.is_none() || last_block.unwrap().height <= height {
@@ -975,6 +1194,45 @@ impl PeerActor {
                 tracker.push_received(hash);
                 tracker.has_request(&hash)
             }
+            PeerMessage::Challenge(challenge) => {
+                self.network_state.seen.received(
+                    &self.clock,
+                    msg.msg_variant(),
+                    // Challenge::hash is not a part of the encoding.
+                    CryptoHash::hash_borsh(challenge),
+                    conn.peer_info.id.clone(),
+                );
+                false
+            }
+            // Transactions and approvals are processed once: the duplicates,
+            // received from other peers or over both TIERs, are dropped, so
+            // that they are not forwarded again.
+            PeerMessage::Transaction(transaction) => {
+                if !self.network_state.seen.received(
+                    &self.clock,
+                    msg.msg_variant(),
+                    transaction.get_hash(),
+                    conn.peer_info.id.clone(),
+                ) {
+                    message_processed_event();
+                    return;
+                }
+                false
+            }
+            PeerMessage::Routed(routed) => {
+                if let RoutedMessageBody::BlockApproval(approval) = &routed.msg.body {
+                    if !self.network_state.seen.received(
+                        &self.clock,
+                        routed.body_variant(),
+                        CryptoHash::hash_borsh(approval),
+                        conn.peer_info.id.clone(),
+                    ) {
+                        message_processed_event();
+                        return;
+                    }
+                }
+                false
+            }
             _ => false,
         };
         let clock = self.clock.clone();
@@ -984,7 +1242,7 @@ impl PeerActor {
             Ok(match msg {
                 PeerMessage::Routed(msg) => {
                     let msg_hash = msg.hash();
-                    Self::receive_routed_message(&clock, &network_state, peer_id, msg_hash, msg.msg.body).await?.map(
+                    Self::receive_routed_message(&clock, &network_state, peer_id, msg.msg.author, msg_hash, msg_len, msg.msg.body).await?.map(
                         |body| {
                             PeerMessage::Routed(network_state.sign_message(
                                 &clock,
@@ -994,13 +1252,29 @@ impl PeerActor {
                     )
                 }
                 PeerMessage::BlockRequest(hash) => {
-                    network_state.client.block_request(hash).await.map(|b|PeerMessage::Block(*b))
+                    network_state.client.block_request(hash).await.map(|response| match response {
+                        DataResponse::Data(block) => PeerMessage::Block(*block),
+                        DataResponse::GarbageCollected(data) => PeerMessage::DataGarbageCollected(data),
+                    })
                 }
                 PeerMessage::BlockHeadersRequest(hashes) => {
                     network_state.client.block_headers_request(hashes).await.map(PeerMessage::BlockHeaders)
                 }
+                PeerMessage::BlockHeadersAtHeightsRequest(heights) => {
+                    network_state.client.block_headers_at_heights_request(heights).await.map(PeerMessage::BlockHeaders)
+                }
                 PeerMessage::Block(block) => {
-                    network_state.client.block(block, peer_id, was_requested).await;
+                    // Root of the trace of the processing of the block by this node.
+                    let span = tracing::debug_span!(
+                        target: "network",
+                        "receive_block",
+                        height = block.header().height(),
+                        hash = %block.hash());
+                    network_state.client.block(block, peer_id, was_requested).instrument(span).await;
+                    None
+                }
+                PeerMessage::DataGarbageCollected(data) => {
+                    network_state.client.data_garbage_collected(data, peer_id).await;
                     None
                 }
                 PeerMessage::Transaction(transaction) => {
@@ -1057,6 +1331,7 @@ impl PeerActor {
         ctx: &mut actix::Context<Self>,
         conn: Arc<connection::Connection>,
         peer_msg: PeerMessage,
+        msg_len: usize,
     ) {
         let _span = tracing::trace_span!(
             target: "network",
@@ -1072,6 +1347,15 @@ impl PeerActor {
                 // Received handshake after already have seen handshake from this peer.
                 tracing::debug!(target: "network", "Duplicate handshake from {}", self.peer_info);
             }
+            PeerMessage::KeepalivePing(nonce) => {
+                self.send_message_or_log(&PeerMessage::KeepalivePong(nonce));
+            }
+            PeerMessage::KeepalivePong(nonce) => {
+                let now = self.clock.now();
+                if let Some(rtt) = self.keepalive.as_mut().and_then(|k| k.pong(now, nonce)) {
+                    metrics::PEER_KEEPALIVE_RTT.observe(rtt.as_seconds_f64());
+                }
+            }
             PeerMessage::PeersRequest => {
                 let peers = self
                     .network_state
@@ -1276,7 +1560,12 @@ impl PeerActor {
                                 .event_sink
                                 .push(Event::MessageProcessed(conn.tier, PeerMessage::Routed(msg)));
                         }
-                        _ => self.receive_message(ctx, &conn, PeerMessage::Routed(msg.clone())),
+                        _ => self.receive_message(
+                            ctx,
+                            &conn,
+                            PeerMessage::Routed(msg.clone()),
+                            msg_len,
+                        ),
                     }
                 } else {
                     if msg.decrease_ttl() {
@@ -1290,7 +1579,7 @@ impl PeerActor {
                     }
                 }
             }
-            msg => self.receive_message(ctx, &conn, msg),
+            msg => self.receive_message(ctx, &conn, msg, msg_len),
         }
     }
 
@@ -1413,7 +1702,9 @@ impl actix::Handler<stream::Error> for PeerActor {
     type Result = ();
     fn handle(&mut self, err: stream::Error, ctx: &mut Self::Context) {
         let expected = match &err {
-            stream::Error::Recv(stream::RecvError::MessageTooLarge { .. }) => {
+            stream::Error::Recv(stream::RecvError::MessageTooLarge { .. })
+            | stream::Error::Recv(stream::RecvError::MalformedChunk)
+            | stream::Error::Recv(stream::RecvError::BufferOverflow { .. }) => {
                 self.stop(ctx, ClosingReason::Ban(ReasonForBan::Abusive));
                 true
             }
@@ -1518,7 +1809,7 @@ impl actix::Handler<stream::Frame> for PeerActor {
                     }
                 }
                 // Handle the message.
-                self.handle_msg_ready(ctx, conn.clone(), peer_msg);
+                self.handle_msg_ready(ctx, conn.clone(), peer_msg, msg.len());
             }
         }
     }
@@ -1562,8 +1853,12 @@ type InboundHandshakePermit = tokio::sync::OwnedSemaphorePermit;
 
 #[derive(Debug)]
 enum ConnectingStatus {
-    Inbound(InboundHandshakePermit),
-    Outbound { _permit: connection::OutboundHandshakePermit, handshake_spec: HandshakeSpec },
+    /// None for trusted peers, which are not subject to the limit of inflight handshakes.
+    Inbound(Option<InboundHandshakePermit>),
+    Outbound {
+        _permit: connection::OutboundHandshakePermit,
+        handshake_spec: HandshakeSpec,
+    },
 }
 
 /// State machine of the PeerActor.
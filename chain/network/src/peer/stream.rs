@@ -4,19 +4,96 @@ use crate::tcp;
 use actix::fut::future::wrap_future;
 use actix::AsyncContext as _;
 use bytesize::{GIB, MIB};
+use std::collections::VecDeque;
 use std::io;
 use std::net::SocketAddr;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::io::AsyncReadExt as _;
 use tokio::io::AsyncWriteExt as _;
 
 /// Maximum size of network message in encoded format.
 /// We encode length as `u32`, and therefore maximum size can't be larger than `u32::MAX`.
-const NETWORK_MESSAGE_MAX_SIZE_BYTES: usize = 512 * MIB as usize;
+pub(crate) const NETWORK_MESSAGE_MAX_SIZE_BYTES: usize = 512 * MIB as usize;
 /// Maximum capacity of write buffer in bytes.
 const MAX_WRITE_BUFFER_CAPACITY_BYTES: usize = GIB as usize;
 
+/// Bit of the frame length prefix marking a chunk of a multiplexed stream.
+/// Lengths of whole messages never have it set, because of NETWORK_MESSAGE_MAX_SIZE_BYTES.
+pub(crate) const MUX_CHUNK_BIT: u32 = 1 << 31;
+/// Flag of a chunk which is the last chunk of a message.
+pub(crate) const MUX_LAST_CHUNK_FLAG: u8 = 1;
+/// Flag of a chunk which carries no message data, but the number of bytes of the
+/// stream's messages which the receiver has delivered, as a little endian u32.
+pub(crate) const MUX_WINDOW_UPDATE_FLAG: u8 = 2;
+/// Size of the payload of a window update chunk.
+const MUX_WINDOW_UPDATE_BYTES: usize = 4;
+/// Size of the chunk header: stream kind and flags.
+const MUX_CHUNK_HEADER_BYTES: usize = 2;
+/// Maximum size of a chunk payload. Messages larger than that are split into chunks,
+/// which are interleaved with chunks of other streams, so that a large message
+/// cannot delay small messages of other streams by more than a few chunks.
+const MUX_CHUNK_MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+/// Flow control window of a stream: the sender starts sending a message of a stream
+/// only if the bytes of the stream's messages which the receiver hasn't delivered yet
+/// would stay within the window, or if there are none of them.
+pub(crate) const MUX_STREAM_WINDOW_BYTES: usize = 16 * MIB as usize;
+/// Messages larger than the window of their stream are started only if the bytes of
+/// all the messages which the receiver hasn't delivered yet stay within this limit.
+const MUX_MAX_IN_FLIGHT_BYTES: usize = NETWORK_MESSAGE_MAX_SIZE_BYTES;
+/// Upper bound on the bytes of messages of multiplexed streams which a receiver has
+/// to buffer for a sender respecting the windows: at most one message larger than
+/// the window per stream, all of which fit in MUX_MAX_IN_FLIGHT_BYTES, and at most
+/// a window worth of smaller messages per stream.
+const MUX_MAX_BUFFERED_BYTES: usize =
+    MUX_MAX_IN_FLIGHT_BYTES + Kind::ALL.len() * MUX_STREAM_WINDOW_BYTES;
+
+/// Logical stream of a connection.
+///
+/// Once multiplexing has been enabled (both peers have announced support for it in the
+/// handshake), messages are sent in chunks prefixed with the stream they belong to.
+/// Every stream has its own send queue with its own capacity, and the send loop
+/// sends one chunk of each non-empty stream in turn, in the order of `Kind::ALL`.
+/// Before multiplexing is enabled, messages are sent whole, in the order in which
+/// they were queued, regardless of their stream.
+///
+/// Streams have independent flow control: the receiver delivers the messages of
+/// each stream to the actor independently of the other streams, and reports the
+/// delivered bytes back to the sender with window update chunks. The sender
+/// doesn't start sending a message of a stream whose window
+/// (MUX_STREAM_WINDOW_BYTES) is used up by undelivered messages, so a stream whose
+/// messages are slow to process doesn't hold back the other streams, and the
+/// receiver buffers at most MUX_MAX_BUFFERED_BYTES for all the streams together.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Kind {
+    /// Approvals, blocks, chunks and connection management.
+    Consensus = 0,
+    /// Transactions.
+    Tx = 1,
+    /// Everything else.
+    Other = 2,
+    /// Headers, blocks and state requested by syncing nodes.
+    Sync = 3,
+}
+
+impl Kind {
+    /// All the streams in the order in which they are served.
+    pub const ALL: [Kind; 4] = [Kind::Consensus, Kind::Tx, Kind::Other, Kind::Sync];
+
+    fn from_u8(x: u8) -> Option<Kind> {
+        Self::ALL.into_iter().find(|k| *k as u8 == x)
+    }
+
+    /// Maximal number of bytes queued for sending on the stream.
+    /// Messages exceeding it are dropped, without affecting the other streams.
+    fn max_queued_bytes(self) -> usize {
+        match self {
+            Kind::Consensus | Kind::Tx => 128 * MIB as usize,
+            Kind::Other | Kind::Sync => 512 * MIB as usize,
+        }
+    }
+}
+
 type ReadHalf = tokio::io::ReadHalf<tokio::net::TcpStream>;
 type WriteHalf = tokio::io::WriteHalf<tokio::net::TcpStream>;
 
@@ -34,6 +111,29 @@ pub(crate) enum RecvError {
     IO(#[source] io::Error),
     #[error("message too large: got {got_bytes}B, want <={want_max_bytes}B")]
     MessageTooLarge { got_bytes: usize, want_max_bytes: usize },
+    #[error("malformed chunk of a multiplexed stream")]
+    MalformedChunk,
+    #[error("too many undelivered bytes of multiplexed streams: got {got_bytes}B, want <={want_max_bytes}B")]
+    BufferOverflow { got_bytes: usize, want_max_bytes: usize },
+}
+
+/// Bytes of the messages of each multiplexed stream which have been sent (at least
+/// partially), but whose delivery the peer hasn't reported yet.
+#[derive(Default)]
+struct InFlight {
+    bytes: [AtomicUsize; Kind::ALL.len()],
+    /// Notified whenever the peer reports a delivery.
+    released: tokio::sync::Notify,
+}
+
+impl InFlight {
+    /// Whether a message of `len` bytes of the `kind` stream may be started.
+    fn may_start(&self, kind: Kind, len: usize) -> bool {
+        let stream = self.bytes[kind as usize].load(Ordering::Acquire);
+        let total: usize = self.bytes.iter().map(|b| b.load(Ordering::Acquire)).sum();
+        (stream == 0 || stream + len <= MUX_STREAM_WINDOW_BYTES)
+            && (len <= MUX_STREAM_WINDOW_BYTES || total + len <= MUX_MAX_IN_FLIGHT_BYTES)
+    }
 }
 
 #[derive(actix::Message, PartialEq, Eq, Clone, Debug)]
@@ -57,8 +157,12 @@ pub(crate) enum Error {
 }
 
 pub(crate) struct FramedStream<Actor: actix::Actor> {
-    queue_send: tokio::sync::mpsc::UnboundedSender<Frame>,
+    queue_send: tokio::sync::mpsc::UnboundedSender<(Kind, Frame)>,
     stats: Arc<connection::Stats>,
+    /// Bytes queued for sending, per stream kind.
+    queued_bytes: Arc<[AtomicUsize; Kind::ALL.len()]>,
+    /// Whether the peer accepts chunks of multiplexed streams.
+    multiplexing: Arc<AtomicBool>,
     send_buf_size_metric: Arc<metrics::IntGaugeGuard>,
     addr: actix::Addr<Actor>,
 }
@@ -76,40 +180,100 @@ where
     ) -> Self {
         let (tcp_recv, tcp_send) = tokio::io::split(stream.stream);
         let (queue_send, queue_recv) = tokio::sync::mpsc::unbounded_channel();
+        // Window updates to be sent to the peer.
+        let (window_send, window_recv) = tokio::sync::mpsc::unbounded_channel();
+        let in_flight = Arc::new(InFlight::default());
         let send_buf_size_metric = Arc::new(metrics::MetricGuard::new(
             &*metrics::PEER_DATA_WRITE_BUFFER_SIZE,
             vec![stream.peer_addr.to_string()],
         ));
+        let queued_bytes: Arc<[AtomicUsize; Kind::ALL.len()]> = Arc::new(Default::default());
+        let multiplexing = Arc::new(AtomicBool::new(false));
         ctx.spawn(wrap_future({
             let addr = ctx.address();
             let stats = stats.clone();
+            let queued_bytes = queued_bytes.clone();
+            let multiplexing = multiplexing.clone();
+            let in_flight = in_flight.clone();
             let m = send_buf_size_metric.clone();
             async move {
-                if let Err(err) = Self::run_send_loop(tcp_send, queue_recv, stats, m).await {
+                if let Err(err) = Self::run_send_loop(
+                    tcp_send,
+                    queue_recv,
+                    window_recv,
+                    stats,
+                    queued_bytes,
+                    multiplexing,
+                    in_flight,
+                    m,
+                )
+                .await
+                {
                     addr.do_send(Error::Send(SendError::IO(err)));
                 }
             }
         }));
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let delivery_send = Kind::ALL.map(|kind| {
+            let (delivery_send, delivery_recv) = tokio::sync::mpsc::unbounded_channel();
+            ctx.spawn(wrap_future(Self::run_delivery_loop(
+                kind,
+                delivery_recv,
+                ctx.address(),
+                buffered.clone(),
+                window_send.clone(),
+            )));
+            delivery_send
+        });
         ctx.spawn(wrap_future({
             let addr = ctx.address();
             let stats = stats.clone();
             async move {
-                if let Err(err) =
-                    Self::run_recv_loop(stream.peer_addr, tcp_recv, addr.clone(), stats).await
+                if let Err(err) = Self::run_recv_loop(
+                    stream.peer_addr,
+                    tcp_recv,
+                    addr.clone(),
+                    delivery_send,
+                    buffered,
+                    in_flight,
+                    stats,
+                )
+                .await
                 {
                     addr.do_send(Error::Recv(err));
                 }
             }
         }));
-        Self { queue_send, stats, send_buf_size_metric, addr: ctx.address() }
+        Self {
+            queue_send,
+            stats,
+            queued_bytes,
+            multiplexing,
+            send_buf_size_metric,
+            addr: ctx.address(),
+        }
     }
 
-    /// Pushes `msg` to the send queue.
+    /// Makes the send loop split messages into chunks of multiplexed streams.
+    /// Should be called once the peer has declared support for multiplexing.
+    /// Receiving chunks is always supported.
+    pub fn enable_multiplexing(&self) {
+        self.multiplexing.store(true, Ordering::Release);
+    }
+
+    /// Pushes `msg` to the send queue of the `kind` stream.
     /// Silently drops message if the connection has been closed.
     /// If the message is too large, it will be silently dropped inside run_send_loop.
-    /// Emits a critical error to Actor if send queue is full.
-    pub fn send(&self, frame: Frame) {
+    /// Drops the message if the queue of the stream is full.
+    /// Emits a critical error to Actor if the whole send queue is full.
+    pub fn send(&self, kind: Kind, frame: Frame) {
         let msg = &frame.0;
+        let queued = &self.queued_bytes[kind as usize];
+        if queued.load(Ordering::Acquire) + msg.len() > kind.max_queued_bytes() {
+            metrics::MessageDropped::StreamCapacityExceeded.inc_unknown_msg();
+            return;
+        }
+        queued.fetch_add(msg.len(), Ordering::Acquire);
         let mut buf_size =
             self.stats.bytes_to_send.fetch_add(msg.len() as u64, Ordering::Acquire) as usize;
         buf_size += msg.len();
@@ -125,11 +289,14 @@ where
                 want_max_bytes: MAX_WRITE_BUFFER_CAPACITY_BYTES,
             }));
         }
-        let _ = self.queue_send.send(frame);
+        let _ = self.queue_send.send((kind, frame));
     }
 
     /// Event loop receiving and processing messages.
-    /// Loop waits for the message to be processed before reading the next message.
+    /// For whole messages, the loop waits for the message to be processed before reading
+    /// the next message. Messages of multiplexed streams are handed over to the delivery
+    /// loops of their streams instead, and the peer's flow control bounds the number of
+    /// bytes waiting there (see `Kind`).
     /// Note that if the message handler spawns an asynchronous subhandler and returns,
     /// then the loop will start reading the next message before the subhandler returns.
     /// Loop uses a fixed small buffer allocated by BufReader.
@@ -140,6 +307,9 @@ where
         peer_addr: SocketAddr,
         read: ReadHalf,
         addr: actix::Addr<Actor>,
+        delivery_send: [tokio::sync::mpsc::UnboundedSender<Vec<u8>>; Kind::ALL.len()],
+        buffered: Arc<AtomicUsize>,
+        in_flight: Arc<InFlight>,
         stats: Arc<connection::Stats>,
     ) -> Result<(), RecvError> {
         const READ_BUFFER_CAPACITY: usize = 8 * 1024;
@@ -151,41 +321,145 @@ where
             &metrics::PEER_DATA_READ_BUFFER_SIZE,
             vec![peer_addr.to_string()],
         );
+        // Partially received messages of multiplexed streams.
+        let mut partial: [Vec<u8>; Kind::ALL.len()] = Default::default();
         loop {
-            let n = read.read_u32_le().await.map_err(RecvError::IO)? as usize;
-            if n > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+            let n = read.read_u32_le().await.map_err(RecvError::IO)?;
+            if n & MUX_CHUNK_BIT == 0 {
+                let n = n as usize;
+                if n > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+                    return Err(RecvError::MessageTooLarge {
+                        got_bytes: n,
+                        want_max_bytes: NETWORK_MESSAGE_MAX_SIZE_BYTES,
+                    });
+                }
+                msg_size_metric.observe(n as f64);
+                buf_size_metric.set(n as i64);
+                let mut buf = vec![0; n];
+                let t = metrics::PEER_MSG_READ_LATENCY.start_timer();
+                read.read_exact(&mut buf[..]).await.map_err(RecvError::IO)?;
+                t.observe_duration();
+                buf_size_metric.set(0);
+                stats.received_messages.fetch_add(1, Ordering::Relaxed);
+                stats.received_bytes.fetch_add(n as u64, Ordering::Relaxed);
+                if let Err(_) = addr.send(Frame(buf)).await {
+                    // We got mailbox error, which means that Actor has stopped,
+                    // so we should just close the stream.
+                    return Ok(());
+                }
+                continue;
+            }
+            let n = (n & !MUX_CHUNK_BIT) as usize;
+            if n < MUX_CHUNK_HEADER_BYTES {
+                return Err(RecvError::MalformedChunk);
+            }
+            let kind = Kind::from_u8(read.read_u8().await.map_err(RecvError::IO)?)
+                .ok_or(RecvError::MalformedChunk)?;
+            let flags = read.read_u8().await.map_err(RecvError::IO)?;
+            let payload = n - MUX_CHUNK_HEADER_BYTES;
+            if flags & MUX_WINDOW_UPDATE_FLAG != 0 {
+                if flags != MUX_WINDOW_UPDATE_FLAG || payload != MUX_WINDOW_UPDATE_BYTES {
+                    return Err(RecvError::MalformedChunk);
+                }
+                let delivered = read.read_u32_le().await.map_err(RecvError::IO)? as usize;
+                let bytes = &in_flight.bytes[kind as usize];
+                if bytes
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |b| b.checked_sub(delivered))
+                    .is_err()
+                {
+                    return Err(RecvError::MalformedChunk);
+                }
+                in_flight.released.notify_one();
+                continue;
+            }
+            let buf = &mut partial[kind as usize];
+            if buf.len() + payload > NETWORK_MESSAGE_MAX_SIZE_BYTES {
                 return Err(RecvError::MessageTooLarge {
-                    got_bytes: n,
+                    got_bytes: buf.len() + payload,
                     want_max_bytes: NETWORK_MESSAGE_MAX_SIZE_BYTES,
                 });
             }
-            msg_size_metric.observe(n as f64);
-            buf_size_metric.set(n as i64);
-            let mut buf = vec![0; n];
+            let total = buffered.fetch_add(payload, Ordering::AcqRel) + payload;
+            if total > MUX_MAX_BUFFERED_BYTES {
+                return Err(RecvError::BufferOverflow {
+                    got_bytes: total,
+                    want_max_bytes: MUX_MAX_BUFFERED_BYTES,
+                });
+            }
+            let offset = buf.len();
+            buf.resize(offset + payload, 0);
             let t = metrics::PEER_MSG_READ_LATENCY.start_timer();
-            read.read_exact(&mut buf[..]).await.map_err(RecvError::IO)?;
+            read.read_exact(&mut buf[offset..]).await.map_err(RecvError::IO)?;
             t.observe_duration();
-            buf_size_metric.set(0);
+            if flags & MUX_LAST_CHUNK_FLAG == 0 {
+                continue;
+            }
+            let buf = std::mem::take(buf);
+            msg_size_metric.observe(buf.len() as f64);
             stats.received_messages.fetch_add(1, Ordering::Relaxed);
-            stats.received_bytes.fetch_add(n as u64, Ordering::Relaxed);
-            if let Err(_) = addr.send(Frame(buf)).await {
-                // We got mailbox error, which means that Actor has stopped,
-                // so we should just close the stream.
+            stats.received_bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+            if delivery_send[kind as usize].send(buf).is_err() {
+                // The delivery loop has stopped, which means that Actor has stopped.
                 return Ok(());
             }
         }
     }
+
+    /// Event loop delivering the messages of the `kind` multiplexed stream to Actor,
+    /// one at a time, and reporting the delivered bytes to the peer.
+    async fn run_delivery_loop(
+        kind: Kind,
+        mut delivery_recv: tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>,
+        addr: actix::Addr<Actor>,
+        buffered: Arc<AtomicUsize>,
+        window_send: tokio::sync::mpsc::UnboundedSender<(Kind, usize)>,
+    ) {
+        while let Some(buf) = delivery_recv.recv().await {
+            let n = buf.len();
+            if let Err(_) = addr.send(Frame(buf)).await {
+                return;
+            }
+            buffered.fetch_sub(n, Ordering::AcqRel);
+            let _ = window_send.send((kind, n));
+        }
+    }
+    /// Event loop sending the queued messages and the window updates of the streams
+    /// received from the peer.
     async fn run_send_loop(
         tcp_send: WriteHalf,
-        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<Frame>,
+        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<(Kind, Frame)>,
+        mut window_recv: tokio::sync::mpsc::UnboundedReceiver<(Kind, usize)>,
         stats: Arc<connection::Stats>,
+        queued_bytes: Arc<[AtomicUsize; Kind::ALL.len()]>,
+        multiplexing: Arc<AtomicBool>,
+        in_flight: Arc<InFlight>,
         buf_size_metric: Arc<metrics::IntGaugeGuard>,
     ) -> io::Result<()> {
         const WRITE_BUFFER_CAPACITY: usize = 8 * 1024;
         let mut writer = tokio::io::BufWriter::with_capacity(WRITE_BUFFER_CAPACITY, tcp_send);
-        while let Some(Frame(mut msg)) = queue_recv.recv().await {
-            // Try writing a batch of messages and flush once at the end.
+        // Messages waiting to be sent, together with the number of bytes already sent.
+        // Used only once multiplexing has been enabled.
+        let mut queues: [VecDeque<(Vec<u8>, usize)>; Kind::ALL.len()] = Default::default();
+        // A message received while waiting for something to send.
+        let mut pending = None;
+        loop {
+            while let Ok((kind, delivered)) = window_recv.try_recv() {
+                Self::write_window_update(&mut writer, kind, delivered).await?;
+            }
             loop {
+                let (kind, msg) = match pending.take() {
+                    Some(it) => it,
+                    None => match queue_recv.try_recv() {
+                        Ok((kind, Frame(msg))) => (kind, msg),
+                        Err(_) => break,
+                    },
+                };
+                if multiplexing.load(Ordering::Acquire) {
+                    queues[kind as usize].push_back((msg, 0));
+                    continue;
+                }
+                // Until multiplexing is enabled, write a batch of whole messages and
+                // flush once at the end.
                 // TODO(gprusak): sending a too large message should probably be treated as a bug,
                 // since dropping messages may lead to hard-to-debug high-level issues.
                 if msg.len() > NETWORK_MESSAGE_MAX_SIZE_BYTES {
@@ -194,22 +468,90 @@ where
                     writer.write_u32_le(msg.len() as u32).await?;
                     writer.write_all(&msg[..]).await?;
                 }
-                stats.messages_to_send.fetch_sub(1, Ordering::Release);
-                stats.bytes_to_send.fetch_sub(msg.len() as u64, Ordering::Release);
-                buf_size_metric.sub(msg.len() as i64);
-                msg = match queue_recv.try_recv() {
-                    Ok(Frame(it)) => it,
-                    Err(_) => break,
+                Self::on_sent(&stats, &queued_bytes, &buf_size_metric, kind, &msg);
+            }
+            // Send a chunk of the first message of each stream, unless it's a new
+            // message which the window of the stream has no room for.
+            let mut sent_chunk = false;
+            for kind in Kind::ALL {
+                let queue = &mut queues[kind as usize];
+                let (msg, sent) = match queue.front_mut() {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let done = if msg.len() > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+                    metrics::MessageDropped::InputTooLong.inc_unknown_msg();
+                    true
+                } else {
+                    if *sent == 0 {
+                        if !in_flight.may_start(kind, msg.len()) {
+                            continue;
+                        }
+                        in_flight.bytes[kind as usize].fetch_add(msg.len(), Ordering::AcqRel);
+                    }
+                    let end = std::cmp::min(msg.len(), *sent + MUX_CHUNK_MAX_PAYLOAD_BYTES);
+                    let last = end == msg.len();
+                    let n = (MUX_CHUNK_HEADER_BYTES + end - *sent) as u32;
+                    writer.write_u32_le(n | MUX_CHUNK_BIT).await?;
+                    writer.write_u8(kind as u8).await?;
+                    writer.write_u8(if last { MUX_LAST_CHUNK_FLAG } else { 0 }).await?;
+                    writer.write_all(&msg[*sent..end]).await?;
+                    *sent = end;
+                    sent_chunk = true;
+                    last
                 };
+                if done {
+                    let (msg, _) = queue.pop_front().unwrap();
+                    Self::on_sent(&stats, &queued_bytes, &buf_size_metric, kind, &msg);
+                }
+            }
+            // The writer flushes by itself whenever its buffer fills up, so chunks keep
+            // flowing while the queues are busy. Flush the rest once there is nothing
+            // more to send, and wait for a new message, a window update to send, or
+            // for the peer to make room in the windows.
+            if sent_chunk {
+                continue;
             }
-            // This is an unconditional flush, which means that even if new messages
-            // will be added to the queue in the meantime, we will wait for the buffer
-            // to be flushed before sending them. This is suboptimal in case messages are small
-            // and added to the queue at a rate similar to flush latency. To fix that
-            // we would need to put writer.flush() and queue_recv.recv() into a tokio::select
-            // and make sure that both are cancellation-safe.
             writer.flush().await?;
+            tokio::select! {
+                it = queue_recv.recv() => match it {
+                    Some((kind, Frame(msg))) => pending = Some((kind, msg)),
+                    None => return Ok(()),
+                },
+                Some((kind, delivered)) = window_recv.recv() => {
+                    Self::write_window_update(&mut writer, kind, delivered).await?;
+                }
+                () = in_flight.released.notified() => {}
+            }
         }
-        Ok(())
+    }
+
+    /// Writes a chunk reporting to the peer that `delivered` bytes of the messages of
+    /// the `kind` stream have been delivered.
+    async fn write_window_update(
+        writer: &mut tokio::io::BufWriter<WriteHalf>,
+        kind: Kind,
+        delivered: usize,
+    ) -> io::Result<()> {
+        let n = (MUX_CHUNK_HEADER_BYTES + MUX_WINDOW_UPDATE_BYTES) as u32;
+        writer.write_u32_le(n | MUX_CHUNK_BIT).await?;
+        writer.write_u8(kind as u8).await?;
+        writer.write_u8(MUX_WINDOW_UPDATE_FLAG).await?;
+        writer.write_u32_le(delivered as u32).await
+    }
+
+    /// Updates the send queue counters after `msg` of the `kind` stream has been
+    /// written (or dropped).
+    fn on_sent(
+        stats: &connection::Stats,
+        queued_bytes: &[AtomicUsize; Kind::ALL.len()],
+        buf_size_metric: &metrics::IntGaugeGuard,
+        kind: Kind,
+        msg: &[u8],
+    ) {
+        queued_bytes[kind as usize].fetch_sub(msg.len(), Ordering::Release);
+        stats.messages_to_send.fetch_sub(1, Ordering::Release);
+        stats.bytes_to_send.fetch_sub(msg.len() as u64, Ordering::Release);
+        buf_size_metric.sub(msg.len() as i64);
     }
 }
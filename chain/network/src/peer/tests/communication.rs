@@ -207,6 +207,11 @@ async fn test_handshake(outbound_encoding: Option<Encoding>, inbound_encoding: O
         sender_chain_info: outbound_cfg.chain.get_peer_chain_info(),
         partial_edge_info: outbound_cfg.partial_edge_info(&inbound.cfg.id(), 1),
         owned_account: None,
+        supports_compression: false,
+        supported_encodings: vec![Encoding::Proto, Encoding::Borsh],
+        supports_multiplexing: false,
+        supports_keepalive: false,
+        sender_earliest_height: None,
     };
     // We will also introduce chain_id mismatch, but ProtocolVersionMismatch is expected to take priority.
     handshake.sender_chain_info.genesis_id.chain_id = "unknown_chain".to_string();
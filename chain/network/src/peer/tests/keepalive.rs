@@ -0,0 +1,60 @@
+use crate::config;
+use crate::peer::keepalive::{Action, Keepalive};
+use crate::time;
+
+fn make_cfg() -> config::Keepalive {
+    config::Keepalive {
+        ping_interval: time::Duration::seconds(1),
+        min_timeout: time::Duration::seconds(2),
+        max_timeout: time::Duration::seconds(20),
+        max_send_backlog_bytes: 1000,
+        send_backlog_timeout: time::Duration::seconds(20),
+    }
+}
+
+#[test]
+fn ping_pong() {
+    let clock = time::FakeClock::default();
+    let mut k = Keepalive::new(make_cfg());
+    // No RTT observed yet.
+    assert_eq!(k.timeout(), time::Duration::seconds(20));
+
+    assert_eq!(k.tick(clock.now()), Action::SendPing(0));
+    clock.advance(time::Duration::milliseconds(100));
+    // Only a single ping is in flight at a time.
+    assert_eq!(k.tick(clock.now()), Action::Wait);
+    // Pongs with an unexpected nonce are ignored.
+    assert_eq!(k.pong(clock.now(), 7), None);
+    assert_eq!(k.pong(clock.now(), 0), Some(time::Duration::milliseconds(100)));
+    // A pong is accepted only once.
+    assert_eq!(k.pong(clock.now(), 0), None);
+    // Low RTT connection: timeout is bounded from below.
+    assert_eq!(k.timeout(), time::Duration::seconds(2));
+
+    // High RTT connection: timeout follows the RTT.
+    for _ in 0..20 {
+        let nonce = match k.tick(clock.now()) {
+            Action::SendPing(nonce) => nonce,
+            action => panic!("unexpected {action:?}"),
+        };
+        clock.advance(time::Duration::seconds(3));
+        k.pong(clock.now(), nonce).unwrap();
+    }
+    assert!(k.timeout() > time::Duration::seconds(3));
+    assert!(k.timeout() < time::Duration::seconds(5));
+}
+
+#[test]
+fn timeout() {
+    let clock = time::FakeClock::default();
+    let mut k = Keepalive::new(make_cfg());
+    assert_eq!(k.tick(clock.now()), Action::SendPing(0));
+    clock.advance(time::Duration::milliseconds(10));
+    k.pong(clock.now(), 0).unwrap();
+
+    assert_eq!(k.tick(clock.now()), Action::SendPing(1));
+    clock.advance(time::Duration::seconds(2));
+    assert_eq!(k.tick(clock.now()), Action::Wait);
+    clock.advance(time::Duration::seconds(1));
+    assert_eq!(k.tick(clock.now()), Action::Timeout);
+}
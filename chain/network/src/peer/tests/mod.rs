@@ -1,2 +1,3 @@
 mod communication;
+mod keepalive;
 mod stream;
@@ -7,6 +7,7 @@ use actix::Actor as _;
 use actix::ActorContext as _;
 use rand::Rng as _;
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
 use tokio::sync::mpsc;
 
 struct Actor {
@@ -25,7 +26,32 @@ struct SendFrame(stream::Frame);
 impl actix::Handler<SendFrame> for Actor {
     type Result = ();
     fn handle(&mut self, SendFrame(frame): SendFrame, _ctx: &mut Self::Context) {
-        self.stream.send(frame);
+        self.stream.send(stream::Kind::Other, frame);
+    }
+}
+
+/// Enqueues all the frames at once, before the send loop gets a chance to run.
+#[derive(actix::Message)]
+#[rtype("()")]
+struct SendFrames(Vec<(stream::Kind, stream::Frame)>);
+
+impl actix::Handler<SendFrames> for Actor {
+    type Result = ();
+    fn handle(&mut self, SendFrames(frames): SendFrames, _ctx: &mut Self::Context) {
+        for (kind, frame) in frames {
+            self.stream.send(kind, frame);
+        }
+    }
+}
+
+#[derive(actix::Message)]
+#[rtype("()")]
+struct EnableMultiplexing;
+
+impl actix::Handler<EnableMultiplexing> for Actor {
+    type Result = ();
+    fn handle(&mut self, _: EnableMultiplexing, _ctx: &mut Self::Context) {
+        self.stream.enable_multiplexing();
     }
 }
 
@@ -90,3 +116,150 @@ async fn send_recv() {
         }
     }
 }
+
+fn make_frame(rng: &mut impl rand::Rng, size: usize) -> stream::Frame {
+    let mut msg = vec![0; size];
+    rng.fill(&mut msg[..]);
+    stream::Frame(msg)
+}
+
+#[tokio::test]
+async fn multiplexing() {
+    let mut rng = make_rng(2093842);
+    let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(s1).await;
+    let mut a2 = Actor::spawn(s2).await;
+
+    // Before multiplexing is enabled, whole messages are sent in the order in which they
+    // were queued, regardless of their stream.
+    let before: Vec<_> = [stream::Kind::Sync, stream::Kind::Other, stream::Kind::Consensus]
+        .into_iter()
+        .map(|kind| (kind, make_frame(&mut rng, 100000)))
+        .collect();
+    a1.system.addr.send(SendFrames(before.clone())).await.unwrap();
+    for (_, want) in &before {
+        assert_eq!(&a2.queue_recv.recv().await.unwrap(), want);
+    }
+
+    a1.system.addr.send(EnableMultiplexing).await.unwrap();
+    // A small consensus message is not blocked by a large sync message queued before it.
+    let large = make_frame(&mut rng, 3 * 1024 * 1024);
+    let small = make_frame(&mut rng, 100);
+    let empty = stream::Frame(vec![]);
+    a1.system
+        .addr
+        .send(SendFrames(vec![
+            (stream::Kind::Sync, large.clone()),
+            (stream::Kind::Consensus, small.clone()),
+            (stream::Kind::Tx, empty.clone()),
+        ]))
+        .await
+        .unwrap();
+    // Streams are delivered independently, so the order of the small messages may vary.
+    let got = [a2.queue_recv.recv().await.unwrap(), a2.queue_recv.recv().await.unwrap()];
+    assert!(got == [small.clone(), empty.clone()] || got == [empty, small]);
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), large);
+
+    // Messages within a stream are delivered in order.
+    let msgs: Vec<_> = (0..10)
+        .map(|_| {
+            let size = rng.gen_range(0..200000);
+            make_frame(&mut rng, size)
+        })
+        .collect();
+    a1.system
+        .addr
+        .send(SendFrames(msgs.iter().map(|m| (stream::Kind::Sync, m.clone())).collect()))
+        .await
+        .unwrap();
+    for want in &msgs {
+        assert_eq!(&a2.queue_recv.recv().await.unwrap(), want);
+    }
+}
+
+/// Reads a chunk of a multiplexed stream: its stream kind, flags and payload.
+async fn read_chunk(s: &mut tokio::net::TcpStream) -> (u8, u8, Vec<u8>) {
+    let n = s.read_u32_le().await.unwrap();
+    assert!(n & stream::MUX_CHUNK_BIT != 0);
+    let kind = s.read_u8().await.unwrap();
+    let flags = s.read_u8().await.unwrap();
+    let mut payload = vec![0; (n & !stream::MUX_CHUNK_BIT) as usize - 2];
+    s.read_exact(&mut payload).await.unwrap();
+    (kind, flags, payload)
+}
+
+async fn write_chunk(s: &mut tokio::net::TcpStream, kind: stream::Kind, flags: u8, payload: &[u8]) {
+    s.write_u32_le((payload.len() + 2) as u32 | stream::MUX_CHUNK_BIT).await.unwrap();
+    s.write_u8(kind as u8).await.unwrap();
+    s.write_u8(flags).await.unwrap();
+    s.write_all(payload).await.unwrap();
+}
+
+#[tokio::test]
+async fn flow_control() {
+    let mut rng = make_rng(9837432);
+    let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let a1 = Actor::spawn(s1).await;
+    let mut peer = s2.stream;
+    a1.system.addr.send(EnableMultiplexing).await.unwrap();
+
+    // The peer doesn't deliver anything, so only the sync messages fitting in the
+    // window are sent, while the consensus stream is not affected.
+    let half_window = stream::MUX_STREAM_WINDOW_BYTES / 2;
+    let mut frames: Vec<_> =
+        (0..3).map(|_| (stream::Kind::Sync, make_frame(&mut rng, half_window))).collect();
+    frames.push((stream::Kind::Consensus, make_frame(&mut rng, 100)));
+    a1.system.addr.send(SendFrames(frames)).await.unwrap();
+    let mut completed = vec![];
+    while completed.len() < 3 {
+        let (kind, flags, _) = read_chunk(&mut peer).await;
+        if flags & stream::MUX_LAST_CHUNK_FLAG != 0 {
+            completed.push(kind);
+        }
+    }
+    completed.sort();
+    assert_eq!(
+        completed,
+        vec![stream::Kind::Consensus as u8, stream::Kind::Sync as u8, stream::Kind::Sync as u8]
+    );
+    let idle = tokio::time::timeout(std::time::Duration::from_millis(200), peer.read_u32_le());
+    assert!(idle.await.is_err());
+
+    // Once the peer reports a delivery, the last sync message is sent.
+    let delivered = (half_window as u32).to_le_bytes();
+    write_chunk(&mut peer, stream::Kind::Sync, stream::MUX_WINDOW_UPDATE_FLAG, &delivered).await;
+    let mut received = 0;
+    loop {
+        let (kind, flags, payload) = read_chunk(&mut peer).await;
+        assert_eq!(kind, stream::Kind::Sync as u8);
+        received += payload.len();
+        if flags & stream::MUX_LAST_CHUNK_FLAG != 0 {
+            break;
+        }
+    }
+    assert_eq!(received, half_window);
+}
+
+#[tokio::test]
+async fn window_updates() {
+    let mut rng = make_rng(4329872);
+    let (s1, s2) = tcp::Stream::loopback(data::make_peer_id(&mut rng), tcp::Tier::T2).await;
+    let mut peer = s1.stream;
+    let mut a2 = Actor::spawn(s2).await;
+
+    // Delivered messages of a stream are reported back to the sender.
+    let msg = make_frame(&mut rng, 100);
+    write_chunk(&mut peer, stream::Kind::Tx, 0, &msg.0[..40]).await;
+    write_chunk(&mut peer, stream::Kind::Tx, stream::MUX_LAST_CHUNK_FLAG, &msg.0[40..]).await;
+    assert_eq!(a2.queue_recv.recv().await.unwrap(), msg);
+    let (kind, flags, payload) = read_chunk(&mut peer).await;
+    assert_eq!(kind, stream::Kind::Tx as u8);
+    assert_eq!(flags, stream::MUX_WINDOW_UPDATE_FLAG);
+    assert_eq!(payload, 100u32.to_le_bytes());
+
+    // Reporting the delivery of more than was sent closes the connection.
+    let delivered = 1u32.to_le_bytes();
+    write_chunk(&mut peer, stream::Kind::Tx, stream::MUX_WINDOW_UPDATE_FLAG, &delivered).await;
+    let mut buf = vec![];
+    assert_eq!(peer.read_to_end(&mut buf).await.unwrap_or(0), 0);
+}
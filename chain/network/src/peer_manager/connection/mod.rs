@@ -17,10 +17,11 @@ use near_crypto::PublicKey;
 use near_o11y::WithSpanContextExt;
 use near_primitives::block::GenesisId;
 use near_primitives::network::PeerId;
-use near_primitives::types::ShardId;
+use near_primitives::types::{BlockHeight, ProtocolVersion, ShardId};
 use std::collections::{hash_map::Entry, HashMap};
 use std::fmt;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Weak};
 
@@ -38,6 +39,7 @@ impl tcp::Tier {
             PeerMessage::Tier2Handshake(_) => self == tcp::Tier::T2,
             PeerMessage::HandshakeFailure(_, _) => true,
             PeerMessage::LastEdge(_) => true,
+            PeerMessage::KeepalivePing(_) | PeerMessage::KeepalivePong(_) => true,
             PeerMessage::Routed(msg) => self.is_allowed_routed(&msg.body),
             _ => self == tcp::Tier::T2,
         }
@@ -78,6 +80,10 @@ pub(crate) struct Connection {
     pub addr: actix::Addr<PeerActor>,
 
     pub peer_info: PeerInfo,
+    /// Address of the peer's end of the TCP connection.  Unlike
+    /// `peer_info.addr`, which has the port the peer says it listens on, it
+    /// doesn't come from the peer.
+    pub peer_addr: SocketAddr,
     /// AccountKey ownership proof.
     pub owned_account: Option<SignedOwnedAccount>,
     /// Chain Id and hash of genesis block.
@@ -86,6 +92,13 @@ pub(crate) struct Connection {
     pub tracked_shards: Vec<ShardId>,
     /// Denote if a node is running in archival mode or not.
     pub archival: bool,
+    /// Lowest height of the blocks the peer held at the time of the handshake.
+    /// None if the peer doesn't advertise it.
+    pub advertised_earliest_height: Option<BlockHeight>,
+    /// Height of the peer's head at the time of the handshake.
+    pub handshake_height: BlockHeight,
+    /// Protocol version agreed on in the handshake.
+    pub protocol_version: ProtocolVersion,
     pub last_block: ArcSwap<Option<BlockInfo>>,
 
     /// Who started connection. Inbound (other) or Outbound (us).
@@ -116,6 +129,26 @@ impl fmt::Debug for Connection {
     }
 }
 
+/// Estimates the earliest height held by a peer which advertised
+/// `advertised_earliest_height` in a handshake at `handshake_height` and has
+/// since sent a block at `last_height`, see [`Connection::earliest_height`].
+///
+/// All the heights come from the peer, so the arithmetic saturates rather than
+/// trusting them to be sane.
+pub(crate) fn estimate_earliest_height(
+    advertised_earliest_height: Option<BlockHeight>,
+    archival: bool,
+    handshake_height: BlockHeight,
+    last_height: Option<BlockHeight>,
+) -> Option<BlockHeight> {
+    let earliest = advertised_earliest_height?;
+    if archival {
+        return Some(earliest);
+    }
+    let progress = last_height.map_or(0, |height| height.saturating_sub(handshake_height));
+    Some(earliest.saturating_add(progress))
+}
+
 impl Connection {
     pub fn full_peer_info(&self) -> FullPeerInfo {
         let chain_info = PeerChainInfo {
@@ -123,10 +156,25 @@ impl Connection {
             last_block: self.last_block.load().as_ref().clone(),
             tracked_shards: self.tracked_shards.clone(),
             archival: self.archival,
+            earliest_height: self.earliest_height(),
+            protocol_version: self.protocol_version,
         };
         FullPeerInfo { peer_info: self.peer_info.clone(), chain_info }
     }
 
+    /// Estimated lowest height of the blocks the peer currently holds.
+    /// The range is advertised only in the handshake, but a non-archival peer
+    /// garbage collects old blocks as its head advances, so its earliest height is
+    /// assumed to advance by the same amount.
+    pub fn earliest_height(&self) -> Option<BlockHeight> {
+        estimate_earliest_height(
+            self.advertised_earliest_height,
+            self.archival,
+            self.handshake_height,
+            self.last_block.load().as_ref().map(|last_block| last_block.height),
+        )
+    }
+
     pub fn stop(&self, ban_reason: Option<ReasonForBan>) {
         self.addr.do_send(peer_actor::Stop { ban_reason }.with_span_context());
     }
@@ -120,3 +120,20 @@ async fn duplicate_connections() {
     );
     drop(conn1);
 }
+
+#[test]
+fn earliest_height_estimate() {
+    use connection::estimate_earliest_height;
+
+    assert_eq!(None, estimate_earliest_height(None, false, 100, Some(150)));
+    assert_eq!(Some(10), estimate_earliest_height(Some(10), true, 100, Some(150)));
+    assert_eq!(Some(10), estimate_earliest_height(Some(10), false, 100, None));
+    assert_eq!(Some(60), estimate_earliest_height(Some(10), false, 100, Some(150)));
+    // Blocks below the handshake height don't move the estimate back.
+    assert_eq!(Some(10), estimate_earliest_height(Some(10), false, 100, Some(50)));
+
+    // Hostile values saturate instead of overflowing.
+    assert_eq!(Some(u64::MAX), estimate_earliest_height(Some(u64::MAX), false, 100, Some(101)));
+    assert_eq!(Some(u64::MAX), estimate_earliest_height(Some(u64::MAX), false, 0, Some(u64::MAX)));
+    assert_eq!(Some(u64::MAX - 1), estimate_earliest_height(Some(u64::MAX - 1), false, 5, Some(5)));
+}
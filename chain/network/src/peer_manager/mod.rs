@@ -2,6 +2,7 @@ pub(crate) mod connection;
 pub(crate) mod network_state;
 pub(crate) mod peer_manager_actor;
 pub(crate) mod peer_store;
+pub(crate) mod seen_cache;
 
 #[cfg(test)]
 pub(crate) mod testonly;
@@ -11,6 +11,7 @@ use crate::peer::peer_actor::{ClosingReason, ConnectionClosedEvent};
 use crate::peer_manager::connection;
 use crate::peer_manager::peer_manager_actor::Event;
 use crate::peer_manager::peer_store;
+use crate::peer_manager::seen_cache;
 use crate::private_actix::RegisterPeerError;
 use crate::routing::route_back_cache::RouteBackCache;
 use crate::stats::metrics;
@@ -107,6 +108,9 @@ pub(crate) struct NetworkState {
     /// It allows us to determine whether messages arrived faster over TIER1 or TIER2 network.
     pub recent_routed_messages: Mutex<lru::LruCache<CryptoHash, ()>>,
 
+    /// Gossiped messages recently received, with the peers which sent them.
+    pub seen: seen_cache::SeenCache,
+
     /// Hash of messages that requires routing back to respective previous hop.
     /// Currently unused, as TIER1 messages do not require a response.
     /// Also TIER1 connections are direct by design (except for proxies),
@@ -172,6 +176,7 @@ impl NetworkState {
             recent_routed_messages: Mutex::new(lru::LruCache::new(
                 RECENT_ROUTED_MESSAGES_CACHE_SIZE,
             )),
+            seen: seen_cache::SeenCache::new(),
             txns_since_last_block: AtomicUsize::new(0),
             whitelist_nodes,
             max_num_peers: AtomicU32::new(config.max_num_peers),
@@ -227,8 +232,8 @@ impl NetworkState {
             .any(|wn| wn.account_id.is_none() || wn.account_id == peer_info.account_id)
     }
 
-    /// predicate checking whether we should allow an inbound connection from peer_info.
-    fn is_inbound_allowed(&self, peer_info: &PeerInfo) -> bool {
+    /// predicate checking whether we should allow an inbound connection.
+    fn is_inbound_allowed(&self, conn: &connection::Connection) -> bool {
         // Check if we have spare inbound connections capacity.
         let tier2 = self.tier2.load();
         if tier2.ready.len() + tier2.outbound_handshakes.len()
@@ -237,9 +242,11 @@ impl NetworkState {
         {
             return true;
         }
-        // Whitelisted nodes are allowed to connect, even if the inbound connections limit has
-        // been reached.
-        if self.is_peer_whitelisted(peer_info) {
+        // Whitelisted and trusted nodes are allowed to connect, even if the inbound connections
+        // limit has been reached.
+        if self.is_peer_whitelisted(&conn.peer_info)
+            || self.config.trusted_peers.contains(&conn.peer_info.id, conn.peer_addr)
+        {
             return true;
         }
         false
@@ -295,7 +302,7 @@ impl NetworkState {
                 }
                 tcp::Tier::T2 => {
                     if conn.peer_type == PeerType::Inbound {
-                        if !this.is_inbound_allowed(&peer_info) {
+                        if !this.is_inbound_allowed(&conn) {
                             // TODO(1896): Gracefully drop inbound connection for other peer.
                             let tier2 = this.tier2.load();
                             tracing::debug!(target: "network",
@@ -384,6 +391,24 @@ impl NetworkState {
         }
     }
 
+    /// Broadcasts a gossiped message (identified by `hash`) to all TIER2 peers,
+    /// except the peers which are known to already have it: the peers which sent it
+    /// to us and the peers which advertised it as their latest block.
+    pub fn gossip(&self, clock: &time::Clock, hash: CryptoHash, msg: Arc<PeerMessage>) {
+        let msg_type = msg.msg_variant();
+        metrics::BROADCAST_MESSAGES.with_label_values(&[msg_type]).inc();
+        let have = self.seen.peers(clock, &hash);
+        for conn in self.tier2.load().ready.values() {
+            let advertised = matches!(conn.last_block.load().as_ref(), Some(b) if b.hash == hash);
+            if advertised || have.contains(&conn.peer_info.id) {
+                metrics::GOSSIP_BROADCAST_SENDS.with_label_values(&[msg_type, "suppressed"]).inc();
+                continue;
+            }
+            metrics::GOSSIP_BROADCAST_SENDS.with_label_values(&[msg_type, "sent"]).inc();
+            conn.send_message(msg.clone());
+        }
+    }
+
     pub fn send_ping(&self, clock: &time::Clock, tier: tcp::Tier, nonce: u64, target: PeerId) {
         let body = RoutedMessageBody::Ping(Ping { nonce, source: self.config.node_id() });
         let msg = RawRoutedMessage { target: PeerIdOrHash::PeerId(target), body };
@@ -9,7 +9,7 @@ use crate::peer::peer_actor::PeerActor;
 use crate::peer_manager::connection;
 use crate::peer_manager::network_state::{NetworkState, WhitelistNode};
 use crate::peer_manager::peer_store;
-use crate::stats::metrics;
+use crate::stats::{bandwidth, metrics};
 use crate::store;
 use crate::tcp;
 use crate::time;
@@ -24,6 +24,7 @@ use anyhow::Context as _;
 use near_o11y::{handler_debug_span, handler_trace_span, OpenTelemetrySpanExt, WithSpanContext};
 use near_performance_metrics_macros::perf;
 use near_primitives::block::GenesisId;
+use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::views::{EdgeView, KnownPeerStateView, NetworkGraphView, PeerStoreView};
 use rand::seq::IteratorRandom;
@@ -166,6 +167,18 @@ impl PeerManagerActor {
         genesis_id: GenesisId,
     ) -> anyhow::Result<actix::Addr<Self>> {
         let config = config.verify().context("config")?;
+        if let Some(shadow) = &config.shadow_chain {
+            anyhow::ensure!(
+                shadow.genesis_id != genesis_id,
+                "shadow_chain must be another chain than the one of the node"
+            );
+        }
+        let shadow = match config.shadow_chain.clone() {
+            Some(chain) => Some(
+                crate::shadow_chain::Shadow::new(&clock, &config, chain).context("shadow_chain")?,
+            ),
+            None => None,
+        };
         let store = store::Store::from(store);
         let peer_store =
             peer_store::PeerStore::new(&clock, config.peer_store.clone(), store.clone())
@@ -232,6 +245,9 @@ impl PeerManagerActor {
                         }
                     });
                 }
+                if let Some(shadow) = shadow {
+                    arbiter.spawn(shadow.run(clock.clone()));
+                }
                 if let Some(cfg) = state.config.tier1.clone() {
                     // Connect to TIER1 proxies and broadcast the list those connections periodically.
                     arbiter.spawn({
@@ -250,11 +266,10 @@ impl PeerManagerActor {
                     arbiter.spawn({
                         let clock = clock.clone();
                         let state = state.clone();
-                        let mut interval = tokio::time::interval(cfg.connect_interval.try_into().unwrap());
-                        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                        let mut interval = time::Interval::new(clock.now(), cfg.connect_interval);
                         async move {
                             loop {
-                                interval.tick().await;
+                                interval.tick(&clock).await;
                                 state.tier1_connect(&clock).await;
                             }
                         }
@@ -303,6 +318,11 @@ impl PeerManagerActor {
             total_bandwidth_used_by_all_peers,
             total_msg_received_count, "Bandwidth stats"
         );
+        tracing::debug!(
+            target: "bandwidth",
+            top_message_types = bandwidth::top_types_summary(),
+            "Bandwidth by message type since start"
+        );
 
         near_performance_metrics::actix::run_later(
             ctx,
@@ -388,12 +408,12 @@ impl PeerManagerActor {
             .collect()
     }
 
-    /// Check if the number of connections (excluding whitelisted ones) exceeds ideal_connections_hi.
+    /// Check if the number of connections (excluding whitelisted and trusted ones) exceeds ideal_connections_hi.
     /// If so, constructs a safe set of peers and selects one random peer outside of that set
     /// and sends signal to stop connection to it gracefully.
     ///
     /// Safe set contruction process:
-    /// 1. Add all whitelisted peers to the safe set.
+    /// 1. Add all whitelisted and trusted peers to the safe set.
     /// 2. If the number of outbound connections is less or equal than minimum_outbound_connections,
     ///    add all outbound connections to the safe set.
     /// 3. Find all peers who sent us a message within the last peer_recent_time_window,
@@ -413,8 +433,11 @@ impl PeerManagerActor {
         // Build safe set
         let mut safe_set = HashSet::new();
 
-        // Add whitelisted nodes to the safe set.
-        let whitelisted_peers = filter_peers(&|p| self.state.is_peer_whitelisted(&p.peer_info));
+        // Add whitelisted and trusted nodes to the safe set.
+        let whitelisted_peers = filter_peers(&|p| {
+            self.state.is_peer_whitelisted(&p.peer_info)
+                || self.state.config.trusted_peers.contains(&p.peer_info.id, p.peer_addr)
+        });
         safe_set.extend(whitelisted_peers);
 
         // If there is not enough non-whitelisted peers, return without disconnecting anyone.
@@ -597,6 +620,8 @@ impl PeerManagerActor {
                 Some(e) => e.nonce(),
                 None => 0,
             },
+            messages_to_send: cp.stats.messages_to_send.load(Ordering::Relaxed),
+            bytes_to_send: cp.stats.bytes_to_send.load(Ordering::Relaxed),
         };
         NetworkInfo {
             connected_peers: tier2.ready.values().map(connected_peer).collect(),
@@ -672,7 +697,7 @@ impl PeerManagerActor {
         metrics::REQUEST_COUNT_BY_TYPE_TOTAL.with_label_values(&[msg.as_ref()]).inc();
         match msg {
             NetworkRequests::Block { block } => {
-                self.state.tier2.broadcast_message(Arc::new(PeerMessage::Block(block)));
+                self.state.gossip(&self.clock, *block.hash(), Arc::new(PeerMessage::Block(block)));
                 NetworkResponses::NoResponse
             }
             NetworkRequests::Approval { approval_message } => {
@@ -702,6 +727,16 @@ impl PeerManagerActor {
                     NetworkResponses::RouteNotFound
                 }
             }
+            NetworkRequests::BlockHeadersAtHeightsRequest { heights, peer_id } => {
+                if self.state.tier2.send_message(
+                    peer_id,
+                    Arc::new(PeerMessage::BlockHeadersAtHeightsRequest(heights)),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
             NetworkRequests::StateRequestHeader { shard_id, sync_hash, target } => {
                 if self.send_message_to_account_or_peer_or_hash(
                     &target,
@@ -722,6 +757,16 @@ impl PeerManagerActor {
                     NetworkResponses::RouteNotFound
                 }
             }
+            NetworkRequests::StateRequestDelta { shard_id, sync_hash, from_hash, target } => {
+                if self.send_message_to_account_or_peer_or_hash(
+                    &target,
+                    RoutedMessageBody::StateRequestDelta(shard_id, sync_hash, from_hash),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
             NetworkRequests::StateResponse { route_back, response } => {
                 let body = RoutedMessageBody::VersionedStateResponse(response);
                 if self.state.send_message_to_peer(
@@ -771,7 +816,13 @@ impl PeerManagerActor {
                         let mut matching_peers = vec![];
                         for (peer_id, peer) in &self.state.tier2.load().ready {
                             let last_block = peer.last_block.load();
-                            if (peer.archival || !target.only_archival)
+                            // Old chunks are requested from the peers which advertise holding
+                            // them, or from the archival ones if the peer doesn't advertise it.
+                            let holds_old_chunks = match peer.earliest_height() {
+                                Some(earliest_height) => earliest_height <= target.min_height,
+                                None => peer.archival,
+                            };
+                            if (holds_old_chunks || !target.only_archival)
                                 && last_block.is_some()
                                 && last_block.as_ref().unwrap().height >= target.min_height
                                 && peer.tracked_shards.contains(&target.shard_id)
@@ -874,7 +925,11 @@ impl PeerManagerActor {
             }
             NetworkRequests::Challenge(challenge) => {
                 // TODO(illia): smarter routing?
-                self.state.tier2.broadcast_message(Arc::new(PeerMessage::Challenge(challenge)));
+                self.state.gossip(
+                    &self.clock,
+                    CryptoHash::hash_borsh(&challenge),
+                    Arc::new(PeerMessage::Challenge(challenge)),
+                );
                 NetworkResponses::NoResponse
             }
         }
@@ -1042,6 +1097,7 @@ impl actix::Handler<GetDebugStatus> for PeerManagerActor {
                     })
                     .collect(),
             }),
+            GetDebugStatus::Bandwidth => DebugStatus::Bandwidth(bandwidth::view()),
         }
     }
 }
@@ -484,6 +484,12 @@ impl PeerStore {
         ban_reason: ReasonForBan,
     ) -> anyhow::Result<()> {
         tracing::warn!(target: "network", "Banning peer {} for {:?}", peer_id, ban_reason);
+        tracing::warn!(
+            target: near_o11y::events::TARGET,
+            event = near_o11y::events::PEER_BANNED,
+            peer_id = %peer_id,
+            reason = ?ban_reason,
+        );
         let mut inner = self.0.lock();
         let mut store = inner.store.clone();
         if let Some(peer_state) = inner.peer_states.get_mut(peer_id) {
@@ -603,3 +609,85 @@ where
         f(x)
     }
 }
+
+/// Peer store entry, in the format used by `neard network peers export` and `import`
+/// to move the learned peers between machines.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportedPeer {
+    pub peer_id: PeerId,
+    pub addr: SocketAddr,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<near_primitives::types::AccountId>,
+    /// Whether the node has been connected to the peer, rather than just heard about it.
+    pub verified: bool,
+    /// Unix timestamp (in seconds) of when the peer was first seen.
+    pub first_seen: i64,
+    /// Unix timestamp (in seconds) of when the peer was last seen.
+    /// Imported peers which haven't been seen for `peer_expiration_duration` are removed
+    /// on the next start of the node.
+    pub last_seen: i64,
+}
+
+/// Lists the peers stored in the database, in a form which can be imported by another node.
+/// Peers without a known address are skipped, and so are the banned ones: the bans
+/// are local decisions of the node.
+pub fn export_peers_from_store(db: Arc<dyn Database>) -> anyhow::Result<Vec<ExportedPeer>> {
+    let store = crate::store::Store::from(db);
+    let mut peers = vec![];
+    for (peer_id, state) in store.list_peer_states()? {
+        let addr = match state.peer_info.addr {
+            Some(addr) => addr,
+            None => continue,
+        };
+        let verified = match state.status {
+            KnownPeerStatus::Banned(..) => continue,
+            KnownPeerStatus::Unknown => false,
+            KnownPeerStatus::NotConnected | KnownPeerStatus::Connected => true,
+        };
+        peers.push(ExportedPeer {
+            peer_id,
+            addr,
+            account_id: state.peer_info.account_id,
+            verified,
+            first_seen: state.first_seen.unix_timestamp(),
+            last_seen: state.last_seen.unix_timestamp(),
+        });
+    }
+    Ok(peers)
+}
+
+/// Adds the exported peers to the database. Peers which are already stored are left
+/// unchanged, since the local information about them is more accurate.
+/// Returns the number of peers added. The node must not be running.
+pub fn import_peers_to_store(
+    db: Arc<dyn Database>,
+    peers: Vec<ExportedPeer>,
+) -> anyhow::Result<usize> {
+    let mut store = crate::store::Store::from(db);
+    let known: std::collections::HashSet<_> =
+        store.list_peer_states()?.into_iter().map(|(peer_id, _)| peer_id).collect();
+    let mut added = 0;
+    for p in peers {
+        if known.contains(&p.peer_id) {
+            continue;
+        }
+        let state = KnownPeerState {
+            peer_info: PeerInfo {
+                id: p.peer_id.clone(),
+                addr: Some(p.addr),
+                account_id: p.account_id,
+            },
+            status: if p.verified {
+                KnownPeerStatus::NotConnected
+            } else {
+                KnownPeerStatus::Unknown
+            },
+            first_seen: time::Utc::from_unix_timestamp(p.first_seen)?,
+            last_seen: time::Utc::from_unix_timestamp(p.last_seen)?,
+            last_outbound_attempt: None,
+        };
+        store.set_peer_state(&p.peer_id, &state)?;
+        added += 1;
+    }
+    Ok(added)
+}
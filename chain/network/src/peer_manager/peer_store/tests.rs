@@ -608,3 +608,46 @@ fn test_delete_peers() {
     }
     assert_peers_in_store(&opener, &[]);
 }
+
+#[test]
+fn test_export_import() {
+    let clock = time::FakeClock::default();
+    let peer_infos: Vec<_> = (0..4).map(gen_peer_info).collect();
+    let src = near_store::db::TestDB::new();
+    {
+        let peer_store = PeerStore::new(
+            &clock.clock(),
+            make_config(&[], Default::default(), false),
+            store::Store::from(src.clone()),
+        )
+        .unwrap();
+        peer_store.add_indirect_peers(&clock.clock(), peer_infos[..3].iter().cloned()).unwrap();
+        peer_store.peer_connected(&clock.clock(), &peer_infos[0]).unwrap();
+        peer_store.peer_ban(&clock.clock(), &peer_infos[1].id, ReasonForBan::Abusive).unwrap();
+    }
+    let mut exported = export_peers_from_store(src).unwrap();
+    exported.sort_by_key(|p| p.addr);
+    // Banned peer is not exported.
+    assert_eq!(
+        vec![(peer_infos[0].id.clone(), true), (peer_infos[2].id.clone(), false)],
+        exported.iter().map(|p| (p.peer_id.clone(), p.verified)).collect::<Vec<_>>()
+    );
+
+    // Peers which are already known are not overwritten.
+    let dst = near_store::db::TestDB::new();
+    let mut dst_store = store::Store::from(dst.clone());
+    let known = KnownPeerState::new(peer_infos[3].clone(), clock.now_utc());
+    dst_store.set_peer_state(&peer_infos[3].id, &known).unwrap();
+    let mut conflicting = exported[0].clone();
+    conflicting.peer_id = peer_infos[3].id.clone();
+    exported.push(conflicting);
+    assert_eq!(2, import_peers_to_store(dst, exported).unwrap());
+
+    let peer_store =
+        PeerStore::new(&clock.clock(), make_config(&[], Default::default(), false), dst_store)
+            .unwrap();
+    let states = peer_store.load();
+    assert_eq!(KnownPeerStatus::NotConnected, states[&peer_infos[0].id].status);
+    assert_eq!(KnownPeerStatus::Unknown, states[&peer_infos[2].id].status);
+    assert_eq!(peer_infos[3], states[&peer_infos[3].id].peer_info);
+}
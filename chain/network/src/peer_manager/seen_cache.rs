@@ -0,0 +1,97 @@
+//! Cache of the recently received messages (blocks, challenges, transactions
+//! and approvals), keyed by the message hash.
+//!
+//! For each message it remembers the peers which we have received it from.
+//! Broadcasting a message skips those peers, so that a block is not echoed back
+//! to the peers which have just sent it to us.  Duplicate transactions and
+//! approvals are dropped on receipt, so that they are not forwarded again.
+//! The cache is bounded both by the number of entries and by the age of an entry.
+use crate::stats::metrics;
+use crate::time;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+
+/// Number of messages tracked by the cache.
+/// It should be large enough to cover all the blocks gossiped within SEEN_CACHE_TTL.
+pub(crate) const SEEN_CACHE_SIZE: usize = 10000;
+/// Entries older than that are ignored: a peer which had a message that long ago,
+/// doesn't necessarily still have it (for example, it could have restarted).
+pub(crate) const SEEN_CACHE_TTL: time::Duration = time::Duration::minutes(10);
+
+struct Entry {
+    first_seen: time::Instant,
+    peers: HashSet<PeerId>,
+}
+
+pub(crate) struct SeenCache(Mutex<lru::LruCache<CryptoHash, Entry>>);
+
+impl SeenCache {
+    pub fn new() -> Self {
+        Self(Mutex::new(lru::LruCache::new(SEEN_CACHE_SIZE)))
+    }
+
+    /// Records that the message `hash` (of type `msg_type`) has been received from `peer`.
+    /// Returns true iff the message hasn't been seen before.
+    pub fn received(
+        &self,
+        clock: &time::Clock,
+        msg_type: &'static str,
+        hash: CryptoHash,
+        peer: PeerId,
+    ) -> bool {
+        let now = clock.now();
+        let mut cache = self.0.lock();
+        let fresh = match cache.get(&hash) {
+            Some(e) => now - e.first_seen > SEEN_CACHE_TTL,
+            None => true,
+        };
+        if fresh {
+            cache.put(hash, Entry { first_seen: now, peers: HashSet::new() });
+        }
+        cache.get_mut(&hash).unwrap().peers.insert(peer);
+        metrics::GOSSIP_SEEN_MESSAGES
+            .with_label_values(&[msg_type, if fresh { "new" } else { "duplicate" }])
+            .inc();
+        fresh
+    }
+
+    /// Peers known to have the message `hash`.
+    pub fn peers(&self, clock: &time::Clock, hash: &CryptoHash) -> HashSet<PeerId> {
+        let now = clock.now();
+        match self.0.lock().get(hash) {
+            Some(e) if now - e.first_seen <= SEEN_CACHE_TTL => e.peers.clone(),
+            _ => HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::testonly::make_rng;
+
+    #[test]
+    fn received() {
+        let mut rng = make_rng(921853233);
+        let rng = &mut rng;
+        let clock = time::FakeClock::default();
+        let cache = SeenCache::new();
+        let hash = CryptoHash::hash_bytes(b"block");
+        let p: Vec<_> = (0..2).map(|_| data::make_peer_id(rng)).collect();
+
+        assert!(cache.peers(&clock.clock(), &hash).is_empty());
+        assert!(cache.received(&clock.clock(), "Block", hash, p[0].clone()));
+        assert!(!cache.received(&clock.clock(), "Block", hash, p[1].clone()));
+        assert!(!cache.received(&clock.clock(), "Block", hash, p[1].clone()));
+        assert_eq!(cache.peers(&clock.clock(), &hash), p.iter().cloned().collect());
+
+        // Expired entries are ignored and replaced.
+        clock.advance(SEEN_CACHE_TTL + time::Duration::seconds(1));
+        assert!(cache.peers(&clock.clock(), &hash).is_empty());
+        assert!(cache.received(&clock.clock(), "Block", hash, p[1].clone()));
+        assert_eq!(cache.peers(&clock.clock(), &hash), [p[1].clone()].into_iter().collect());
+    }
+}
@@ -103,6 +103,11 @@ async fn loop_connection() {
                 &pm.cfg.node_key,
             ),
             owned_account: None,
+            supports_compression: false,
+            supported_encodings: vec![Encoding::Proto],
+            supports_multiplexing: false,
+            supports_keepalive: false,
+            sender_earliest_height: None,
         }))
         .await;
     let reason = events
@@ -171,6 +176,11 @@ async fn owned_account_mismatch() {
                 }
                 .sign(vc.signer.as_ref()),
             ),
+            supports_compression: false,
+            supported_encodings: vec![Encoding::Proto],
+            supports_multiplexing: false,
+            supports_keepalive: false,
+            sender_earliest_height: None,
         }))
         .await;
     let reason = events
@@ -284,6 +294,11 @@ async fn invalid_edge() {
                     }
                     .sign(vc.signer.as_ref()),
                 ),
+                supports_compression: false,
+                supported_encodings: vec![Encoding::Proto],
+                supports_multiplexing: false,
+                supports_keepalive: false,
+                sender_earliest_height: None,
             };
             let handshake = match tier {
                 tcp::Tier::T1 => PeerMessage::Tier1Handshake(handshake),
@@ -310,3 +325,44 @@ async fn invalid_edge() {
         }
     }
 }
+
+/// Inbound connections of peers trusted by IP are accepted even when the
+/// connection limit has been reached.  The IP is the one of the TCP
+/// connection.
+#[tokio::test]
+async fn trusted_peer_over_connection_limit() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.max_num_peers = 0;
+    cfg.ideal_connections_lo = 0;
+    cfg.ideal_connections_hi = 0;
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg.clone(),
+        chain.clone(),
+    )
+    .await;
+    let conn = pm.start_inbound(chain.clone(), chain.make_config(rng)).await;
+    assert_eq!(
+        ClosingReason::RejectedByPeerManager(RegisterPeerError::ConnectionLimitExceeded),
+        conn.manager_fail_handshake(&clock.clock()).await
+    );
+
+    // Test connections come from the loopback address.
+    cfg.trusted_peers = ["::1".parse().unwrap()].into_iter().collect();
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    let conn = pm.start_inbound(chain.clone(), chain.make_config(rng)).await;
+    conn.handshake(&clock.clock()).await;
+}
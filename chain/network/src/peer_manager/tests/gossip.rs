@@ -0,0 +1,121 @@
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::PeerMessage;
+use crate::peer;
+use crate::peer_manager;
+use crate::peer_manager::testonly::Event;
+use crate::testonly::fake_client;
+use crate::testonly::make_rng;
+use crate::time;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::block::Block;
+use near_primitives::transaction::SignedTransaction;
+use std::sync::Arc;
+
+async fn recv_block(peer: &mut peer::testonly::PeerHandle) -> Block {
+    peer.events
+        .recv_until(|ev| match ev {
+            peer::testonly::Event::Client(fake_client::Event::Block(block)) => Some(block),
+            _ => None,
+        })
+        .await
+}
+
+async fn recv_transaction(pm: &mut peer_manager::testonly::ActorHandler) -> SignedTransaction {
+    pm.events
+        .recv_until(|ev| match ev {
+            Event::Client(fake_client::Event::Transaction(tx)) => Some(tx),
+            _ => None,
+        })
+        .await
+}
+
+// A gossiped block is not broadcast back to the peer which sent it.
+#[tokio::test]
+async fn gossip_skips_sender() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        chain.make_config(rng),
+        chain.clone(),
+    )
+    .await;
+    let mut sender = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .handshake(&clock.clock())
+        .await;
+    let mut other = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .handshake(&clock.clock())
+        .await;
+
+    tracing::info!(target:"test", "Send a block to the node and wait until it is processed.");
+    let block = chain.blocks[5].clone();
+    sender.send(PeerMessage::Block(block.clone())).await;
+    pm.events
+        .recv_until(|ev| match ev {
+            Event::Client(fake_client::Event::Block(got)) if got == block => Some(()),
+            _ => None,
+        })
+        .await;
+
+    tracing::info!(target:"test", "Broadcast it, followed by a block nobody has.");
+    let clock_ = clock.clock();
+    let blocks = [block.clone(), chain.blocks[6].clone()];
+    pm.with_state(move |s| async move {
+        for b in blocks {
+            s.gossip(&clock_, *b.hash(), Arc::new(PeerMessage::Block(b)));
+        }
+    })
+    .await;
+
+    tracing::info!(target:"test", "The other peer receives both, the sender only the new one.");
+    assert_eq!(block, recv_block(&mut other).await);
+    assert_eq!(chain.blocks[6], recv_block(&mut other).await);
+    assert_eq!(chain.blocks[6], recv_block(&mut sender).await);
+}
+
+// A transaction received again from another peer is dropped.
+#[tokio::test]
+async fn duplicate_transaction_is_dropped() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        chain.make_config(rng),
+        chain.clone(),
+    )
+    .await;
+    let a = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .handshake(&clock.clock())
+        .await;
+    let b = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .handshake(&clock.clock())
+        .await;
+
+    let tx = data::make_signed_transaction(rng);
+    let other_tx = data::make_signed_transaction(rng);
+    a.send(PeerMessage::Transaction(tx.clone())).await;
+    assert_eq!(tx, recv_transaction(&mut pm).await);
+    b.send(PeerMessage::Transaction(tx.clone())).await;
+    // Messages of a connection are processed in order, so the duplicate has
+    // been handled once the next transaction is.
+    b.send(PeerMessage::Transaction(other_tx.clone())).await;
+    assert_eq!(other_tx, recv_transaction(&mut pm).await);
+}
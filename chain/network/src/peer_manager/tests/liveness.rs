@@ -0,0 +1,179 @@
+use crate::config;
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::{Encoding, Handshake, PartialEdgeInfo, PeerMessage};
+use crate::peer::peer_actor::ClosingReason;
+use crate::peer_manager;
+use crate::peer_manager::peer_manager_actor::Event as PME;
+use crate::peer_manager::testonly::Event;
+use crate::tcp;
+use crate::testonly::make_rng;
+use crate::testonly::stream::Stream;
+use crate::time;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::{Action, DeployContractAction, SignedTransaction};
+use near_primitives::version::PROTOCOL_VERSION;
+use std::sync::Arc;
+
+fn make_keepalive_cfg() -> config::Keepalive {
+    config::Keepalive {
+        ping_interval: time::Duration::seconds(1),
+        min_timeout: time::Duration::seconds(2),
+        max_timeout: time::Duration::seconds(5),
+        max_send_backlog_bytes: bytesize::MIB,
+        send_backlog_timeout: time::Duration::seconds(10),
+    }
+}
+
+/// Connects to the peer manager and performs the handshake, without ever
+/// reading from the connection afterwards: neither the pings are answered,
+/// nor the messages sent by the peer manager are consumed.
+async fn connect_silent_peer(
+    pm: &peer_manager::testonly::ActorHandler,
+    chain: &data::Chain,
+    cfg: &config::NetworkConfig,
+    supports_keepalive: bool,
+) -> (Stream, tcp::StreamId) {
+    let mut events = pm.events.from_now();
+    let stream = tcp::Stream::connect(&pm.peer_info(), tcp::Tier::T2).await.unwrap();
+    let stream_id = stream.id();
+    let port = stream.local_addr.port();
+    let mut stream = Stream::new(Some(Encoding::Proto), stream);
+    stream
+        .write(&PeerMessage::Tier2Handshake(Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            oldest_supported_version: PROTOCOL_VERSION,
+            sender_peer_id: cfg.node_id(),
+            target_peer_id: pm.cfg.node_id(),
+            sender_listen_port: Some(port),
+            sender_chain_info: chain.get_peer_chain_info(),
+            partial_edge_info: PartialEdgeInfo::new(
+                &cfg.node_id(),
+                &pm.cfg.node_id(),
+                1,
+                &cfg.node_key,
+            ),
+            owned_account: None,
+            supports_compression: false,
+            supported_encodings: vec![Encoding::Proto],
+            supports_multiplexing: false,
+            supports_keepalive,
+            sender_earliest_height: None,
+        }))
+        .await;
+    events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::HandshakeCompleted(ev)) if ev.stream_id == stream_id => {
+                Some(())
+            }
+            Event::PeerManager(PME::ConnectionClosed(ev)) if ev.stream_id == stream_id => {
+                panic!("handshake aborted: {}", ev.reason)
+            }
+            _ => None,
+        })
+        .await;
+    (stream, stream_id)
+}
+
+/// Advances the clock until the connection is closed, returning the reason.
+/// The liveness checks sleep on the clock, so it is advanced in small steps,
+/// giving the peer actor the time to schedule the next check.
+async fn wait_for_close(
+    clock: &time::FakeClock,
+    pm: &peer_manager::testonly::ActorHandler,
+    stream_id: tcp::StreamId,
+) -> ClosingReason {
+    let mut events = pm.events.from_now();
+    let ticker = tokio::spawn({
+        let clock = clock.clone();
+        async move {
+            loop {
+                clock.advance(time::Duration::milliseconds(500));
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        }
+    });
+    let reason = events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::ConnectionClosed(ev)) if ev.stream_id == stream_id => {
+                Some(ev.reason)
+            }
+            _ => None,
+        })
+        .await;
+    ticker.abort();
+    reason
+}
+
+#[tokio::test]
+async fn keepalive_timeout() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.keepalive = Some(make_keepalive_cfg());
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    let (_stream, stream_id) =
+        connect_silent_peer(&pm, &chain, &chain.make_config(rng), true).await;
+    // No pong has been received, so the timeout is the maximal one.
+    assert_eq!(
+        ClosingReason::KeepaliveTimeout(time::Duration::seconds(5)),
+        wait_for_close(&clock, &pm, stream_id).await
+    );
+}
+
+#[tokio::test]
+async fn send_backlog_exceeded() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.keepalive = Some(make_keepalive_cfg());
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    // Without keepalive pings, only the send backlog can get the connection closed.
+    let peer_cfg = chain.make_config(rng);
+    let (_stream, stream_id) = connect_silent_peer(&pm, &chain, &peer_cfg, false).await;
+
+    // Queue far more than the socket buffers can absorb.
+    let signer = data::make_signer(rng);
+    let tx = SignedTransaction::from_actions(
+        1,
+        signer.account_id.clone(),
+        signer.account_id.clone(),
+        &signer,
+        vec![Action::DeployContract(DeployContractAction {
+            code: vec![7; bytesize::MIB as usize],
+        })],
+        CryptoHash::default(),
+    );
+    let peer_id = peer_cfg.node_id();
+    pm.with_state(move |s| async move {
+        let msg = Arc::new(PeerMessage::Transaction(tx));
+        for _ in 0..64 {
+            assert!(s.tier2.send_message(peer_id.clone(), msg.clone()));
+        }
+    })
+    .await;
+    assert_eq!(
+        ClosingReason::SendBacklogExceeded(bytesize::MIB),
+        wait_for_close(&clock, &pm, stream_id).await
+    );
+}
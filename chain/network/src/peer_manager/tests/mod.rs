@@ -1,5 +1,7 @@
 mod accounts_data;
 mod connection_pool;
+mod gossip;
+mod liveness;
 mod nonce;
 mod routing;
 mod tier1;
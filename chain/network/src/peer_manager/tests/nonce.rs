@@ -75,6 +75,11 @@ async fn test_nonces() {
             sender_chain_info: chain.get_peer_chain_info(),
             partial_edge_info: PartialEdgeInfo::new(&peer_id, &pm.cfg.node_id(), test.0, &peer_key),
             owned_account: None,
+            supports_compression: false,
+            supported_encodings: vec![Encoding::Proto],
+            supports_multiplexing: false,
+            supports_keepalive: false,
+            sender_earliest_height: None,
         });
         stream.write(&handshake).await;
         if test.1 {
@@ -29,6 +29,7 @@ pub struct Connection {
     stream: TcpStream,
     buf: BytesMut,
     recv_timeout: Duration,
+    peer_height: BlockHeight,
 }
 
 /// The types of messages it's possible to receive from a `Peer`. Any PeerMessage
@@ -81,6 +82,8 @@ pub enum ConnectError {
     HandshakeFailure(HandshakeFailureReason),
     #[error("received unexpected message before the handshake: {0:?}")]
     UnexpectedFirstMessage(PeerMessage),
+    #[error("peer is on another chain: {0:?}")]
+    GenesisMismatch(GenesisId),
 }
 
 impl Connection {
@@ -116,6 +119,7 @@ impl Connection {
             secret_key,
             my_peer_id,
             recv_timeout,
+            peer_height: 0,
         };
         peer.do_handshake(
             my_protocol_version.unwrap_or(PROTOCOL_VERSION),
@@ -156,6 +160,11 @@ impl Connection {
                 &self.secret_key,
             ),
             owned_account: None,
+            supports_compression: false,
+            supported_encodings: vec![Encoding::Proto],
+            supports_multiplexing: false,
+            supports_keepalive: false,
+            sender_earliest_height: None,
         });
 
         self.write_message(&handshake).await.map_err(ConnectError::IO)?;
@@ -165,9 +174,14 @@ impl Connection {
         let (message, timestamp) = self.recv_message().await.map_err(ConnectError::IO)?;
 
         match message {
-            // TODO: maybe check the handshake for sanity
-            PeerMessage::Tier2Handshake(_) => {
+            // TODO: maybe check the rest of the handshake for sanity
+            PeerMessage::Tier2Handshake(handshake) => {
                 tracing::info!(target: "network", "handshake latency: {}", timestamp - start);
+                let genesis_id = handshake.sender_chain_info.genesis_id;
+                if genesis_id.chain_id != chain_id || genesis_id.hash != genesis_hash {
+                    return Err(ConnectError::GenesisMismatch(genesis_id));
+                }
+                self.peer_height = handshake.sender_chain_info.height;
             }
             PeerMessage::HandshakeFailure(_peer_info, reason) => {
                 return Err(ConnectError::HandshakeFailure(reason))
@@ -247,6 +261,11 @@ impl Connection {
         })
     }
 
+    /// Height of the head of the peer as of the handshake.
+    pub fn peer_height(&self) -> BlockHeight {
+        self.peer_height
+    }
+
     /// Reads from the socket until we receive some message that we care to pass to the caller
     /// (that is, represented in `ReceivedMessage`).
     pub async fn recv(&mut self) -> io::Result<(ReceivedMessage, Instant)> {
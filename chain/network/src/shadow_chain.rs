@@ -0,0 +1,354 @@
+//! Shadow tracking of a foreign chain.
+//!
+//! To rehearse a deployment against the topology of another chain, e.g. to see
+//! how fast a mainnet node would sync headers from testnet-like peers, a node
+//! can be configured with `shadow_chain`.  It then connects to the boot nodes
+//! of that chain with a separate identity: a random peer id and the genesis of
+//! the shadowed chain in the handshake.  The connections go through the regular
+//! peer stack (`PeerActor`), but with a `NetworkState` of their own, backed by
+//! an in-memory store, so they are invisible to the peer manager of the node:
+//! they are never advertised, take no part in routing and the only messages
+//! sent over them on our initiative are headers requests.  Like header sync,
+//! the headers are requested from one peer at a time, moving to the next boot
+//! node when the current one fails.  The headers are counted and their height
+//! is tracked in the `near_shadow_chain_*` metrics, but they are neither
+//! validated nor stored.
+//!
+//! The handshake advertises the listening port of the node, because peers
+//! reject inbound connections without one.  The connections which the peers of
+//! the shadowed chain may open in return are rejected by the node, as their
+//! genesis doesn't match.
+use crate::client;
+use crate::config::{NetworkConfig, ShadowChain};
+use crate::network_protocol::{
+    GarbageCollectedData, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg, PeerInfo, PeerMessage, StateDeltaResponseInfo,
+    StateResponseInfo,
+};
+use crate::peer::peer_actor::PeerActor;
+use crate::peer_manager::network_state::NetworkState;
+use crate::peer_manager::peer_store;
+use crate::sink::Sink;
+use crate::stats::metrics;
+use crate::store;
+use crate::tcp;
+use crate::time;
+use crate::types::{NetworkInfo, ReasonForBan};
+use anyhow::Context as _;
+use near_crypto::{KeyType, SecretKey};
+use near_primitives::block::{Approval, Block, BlockHeader};
+use near_primitives::challenge::Challenge;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::sharding::PartialEncodedChunk;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{AccountId, EpochId, ShardId};
+use near_primitives::views::FinalExecutionOutcomeView;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Time after which a silent connection is considered broken.
+const RECV_TIMEOUT: time::Duration = time::Duration::seconds(30);
+/// Time to wait before requesting more headers once the peer has no more, and
+/// before retrying the boot nodes once all of them failed.
+const RETRY_INTERVAL: time::Duration = time::Duration::seconds(10);
+
+/// The node's view of the shadowed chain.
+pub(crate) struct Shadow {
+    chain: ShadowChain,
+    state: Arc<NetworkState>,
+    /// Headers received over the shadow connections, with their sender.
+    headers: tokio::sync::Mutex<mpsc::UnboundedReceiver<(PeerId, Vec<BlockHeader>)>>,
+}
+
+impl Shadow {
+    /// `config` is the config of the node: the shadow connections inherit its
+    /// limits and timeouts, but not its identity.
+    pub fn new(
+        clock: &time::Clock,
+        config: &NetworkConfig,
+        chain: ShadowChain,
+    ) -> anyhow::Result<Self> {
+        let mut config = config.clone();
+        config.node_key = SecretKey::from_random(KeyType::ED25519);
+        config.validator = None;
+        config.tier1 = None;
+        config.shadow_chain = None;
+        config.whitelist_nodes = vec![];
+        config.peer_store.boot_nodes = chain.boot_nodes.clone();
+        config.event_sink = Sink::null();
+        let config = config.verify().context("config")?;
+        // Nothing about the shadowed chain is persisted.
+        let store = store::Store::from(near_store::db::TestDB::new());
+        let peer_store =
+            peer_store::PeerStore::new(clock, config.peer_store.clone(), store.clone())
+                .context("PeerStore::new")?;
+        let (send, recv) = mpsc::unbounded_channel();
+        let state = Arc::new(NetworkState::new(
+            clock,
+            store,
+            peer_store,
+            config,
+            chain.genesis_id.clone(),
+            Arc::new(ShadowClient { headers: send }),
+            vec![],
+        ));
+        Ok(Self { chain, state, headers: tokio::sync::Mutex::new(recv) })
+    }
+
+    /// Syncs the headers of the shadowed chain forever.
+    pub async fn run(self, clock: time::Clock) {
+        tracing::info!(target: "network", chain_id = %self.chain.genesis_id.chain_id, boot_nodes = self.chain.boot_nodes.len(), "Shadowing header sync of a foreign chain");
+        let mut last_hash = self.chain.start_hash;
+        loop {
+            for peer in &self.chain.boot_nodes {
+                if let Err(err) = self.sync_headers(&clock, peer, &mut last_hash).await {
+                    tracing::info!(target: "network", peer = %peer, ?err, "Shadow chain header sync from peer interrupted");
+                }
+            }
+            clock.sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Connects to the peer and requests headers following `last_hash` from it
+    /// until the connection fails, advancing `last_hash` as they are received.
+    async fn sync_headers(
+        &self,
+        clock: &time::Clock,
+        peer: &PeerInfo,
+        last_hash: &mut CryptoHash,
+    ) -> anyhow::Result<()> {
+        let stream =
+            tcp::Stream::connect(peer, tcp::Tier::T2).await.context("tcp::Stream::connect()")?;
+        PeerActor::spawn_and_handshake(clock.clone(), stream, None, self.state.clone())
+            .await
+            .context("PeerActor::spawn()")?;
+        let conn = self
+            .state
+            .tier2
+            .load()
+            .ready
+            .get(&peer.id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("handshake failed"))?;
+        metrics::SHADOW_CHAIN_PEER_HEIGHT.set(conn.handshake_height as i64);
+        let result = self.request_headers(clock, &peer.id, last_hash).await;
+        conn.stop(None);
+        result
+    }
+
+    async fn request_headers(
+        &self,
+        clock: &time::Clock,
+        peer_id: &PeerId,
+        last_hash: &mut CryptoHash,
+    ) -> anyhow::Result<()> {
+        let mut recv = self.headers.lock().await;
+        loop {
+            let sent = clock.now();
+            let msg = Arc::new(PeerMessage::BlockHeadersRequest(vec![*last_hash]));
+            if !self.state.tier2.send_message(peer_id.clone(), msg) {
+                anyhow::bail!("connection closed");
+            }
+            let headers = loop {
+                tokio::select! {
+                    _ = clock.sleep(RECV_TIMEOUT) => anyhow::bail!("timeout"),
+                    msg = recv.recv() => match msg {
+                        Some((sender, headers)) if &sender == peer_id => break headers,
+                        // Late response over a previous connection.
+                        Some(_) => {}
+                        None => anyhow::bail!("network state dropped"),
+                    },
+                }
+            };
+            metrics::SHADOW_CHAIN_HEADERS_REQUEST_LATENCY
+                .observe((clock.now() - sent).as_seconds_f64());
+            match headers.iter().max_by_key(|header| header.height()) {
+                Some(last) => {
+                    metrics::SHADOW_CHAIN_HEADERS_RECEIVED.inc_by(headers.len() as u64);
+                    metrics::SHADOW_CHAIN_HEADER_HEAD_HEIGHT.set(last.height() as i64);
+                    *last_hash = *last.hash();
+                }
+                // Caught up with the peer.
+                None => clock.sleep(RETRY_INTERVAL).await,
+            }
+        }
+    }
+}
+
+/// Client of the shadow connections: it passes the received headers to the
+/// sync loop and ignores everything else, without serving any requests.
+struct ShadowClient {
+    headers: mpsc::UnboundedSender<(PeerId, Vec<BlockHeader>)>,
+}
+
+#[async_trait::async_trait]
+impl client::Client for ShadowClient {
+    async fn tx_status_request(
+        &self,
+        _account_id: AccountId,
+        _tx_hash: CryptoHash,
+    ) -> Option<Box<FinalExecutionOutcomeView>> {
+        None
+    }
+
+    async fn tx_status_response(&self, _tx_result: FinalExecutionOutcomeView) {}
+
+    async fn state_request_header(
+        &self,
+        _shard_id: ShardId,
+        _sync_hash: CryptoHash,
+        _peer_id: PeerId,
+    ) -> Result<Option<client::DataResponse<StateResponseInfo>>, ReasonForBan> {
+        Ok(None)
+    }
+
+    async fn state_request_part(
+        &self,
+        _shard_id: ShardId,
+        _sync_hash: CryptoHash,
+        _part_id: u64,
+        _peer_id: PeerId,
+    ) -> Result<Option<client::DataResponse<StateResponseInfo>>, ReasonForBan> {
+        Ok(None)
+    }
+
+    async fn state_response(&self, _info: StateResponseInfo) {}
+
+    async fn data_garbage_collected(&self, _data: GarbageCollectedData, _peer_id: PeerId) {}
+
+    async fn state_request_delta(
+        &self,
+        _shard_id: ShardId,
+        _sync_hash: CryptoHash,
+        _from_hash: CryptoHash,
+        _peer_id: PeerId,
+    ) -> Result<Option<StateDeltaResponseInfo>, ReasonForBan> {
+        Ok(None)
+    }
+
+    async fn state_delta_response(&self, _info: StateDeltaResponseInfo) {}
+
+    async fn block_approval(&self, _approval: Approval, _peer_id: PeerId) {}
+
+    async fn transaction(&self, _transaction: SignedTransaction, _is_forwarded: bool) {}
+
+    async fn partial_encoded_chunk_request(
+        &self,
+        _req: PartialEncodedChunkRequestMsg,
+        _msg_hash: CryptoHash,
+    ) {
+    }
+
+    async fn partial_encoded_chunk_response(
+        &self,
+        _resp: PartialEncodedChunkResponseMsg,
+        _timestamp: time::Instant,
+        _author: PeerId,
+        _msg_len: usize,
+    ) {
+    }
+
+    async fn partial_encoded_chunk(&self, _chunk: PartialEncodedChunk) {}
+
+    async fn partial_encoded_chunk_forward(&self, _msg: PartialEncodedChunkForwardMsg) {}
+
+    async fn block_request(&self, _hash: CryptoHash) -> Option<client::DataResponse<Box<Block>>> {
+        None
+    }
+
+    async fn block_headers_request(&self, _hashes: Vec<CryptoHash>) -> Option<Vec<BlockHeader>> {
+        None
+    }
+
+    async fn block(&self, _block: Block, _peer_id: PeerId, _was_requested: bool) {}
+
+    async fn block_headers(
+        &self,
+        headers: Vec<BlockHeader>,
+        peer_id: PeerId,
+    ) -> Result<(), ReasonForBan> {
+        // The receiver is gone only when the sync loop is.
+        let _ = self.headers.send((peer_id, headers));
+        Ok(())
+    }
+
+    async fn challenge(&self, _challenge: Challenge) {}
+
+    async fn network_info(&self, _info: NetworkInfo) {}
+
+    async fn announce_account(
+        &self,
+        _accounts: Vec<(AnnounceAccount, Option<EpochId>)>,
+    ) -> Result<Vec<AnnounceAccount>, ReasonForBan> {
+        Ok(vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::peer::testonly::{Event, PeerConfig, PeerHandle};
+    use crate::testonly::fake_client;
+    use crate::testonly::make_rng;
+    use near_o11y::testonly::init_test_logger;
+
+    #[test]
+    fn test_sync_headers() {
+        init_test_logger();
+        let mut rng = make_rng(921853233);
+        let mut clock = time::FakeClock::default();
+        let chain = Arc::new(data::Chain::make(&mut clock, &mut rng, 12));
+        let headers = chain.get_block_headers();
+
+        // PeerActor is spawned on its own arbiter, which requires an actix system.
+        actix::System::new().block_on(async {
+            let listener_addr = tcp::ListenerAddr::reserve_for_test();
+            let peer_cfg = PeerConfig {
+                chain: chain.clone(),
+                network: chain.make_config(&mut rng),
+                force_encoding: None,
+            };
+            let shadow_chain = ShadowChain {
+                genesis_id: chain.genesis_id.clone(),
+                boot_nodes: vec![PeerInfo {
+                    id: peer_cfg.id(),
+                    addr: Some(*listener_addr),
+                    account_id: None,
+                }],
+                start_hash: *headers[0].hash(),
+            };
+            let node_cfg = chain.make_config(&mut rng);
+            let shadow = Shadow::new(&clock.clock(), &node_cfg, shadow_chain).unwrap();
+            // The shadow identity is not the one of the node.
+            assert_ne!(shadow.state.config.node_id(), node_cfg.node_id());
+
+            let mut listener = listener_addr.listener().unwrap();
+            let sync = actix::spawn(shadow.run(clock.clock()));
+            let stream = listener.accept().await.unwrap();
+            let mut peer = PeerHandle::start_endpoint(clock.clock(), peer_cfg, stream).await;
+            peer.complete_handshake().await;
+
+            let headers_request = |hash: CryptoHash| {
+                move |ev| match ev {
+                    Event::Client(fake_client::Event::BlockHeadersRequest(hashes))
+                        if hashes == vec![hash] =>
+                    {
+                        Some(())
+                    }
+                    _ => None,
+                }
+            };
+            tracing::info!(target:"test", "The sync starts from start_hash.");
+            peer.events.recv_until(headers_request(*headers[0].hash())).await;
+            tracing::info!(target:"test", "The sync continues from the highest header received.");
+            peer.send(PeerMessage::BlockHeaders(headers[1..6].to_vec())).await;
+            peer.events.recv_until(headers_request(*headers[5].hash())).await;
+            peer.send(PeerMessage::BlockHeaders(headers[6..].iter().rev().cloned().collect()))
+                .await;
+            peer.events.recv_until(headers_request(*headers[11].hash())).await;
+            sync.abort();
+        });
+    }
+}
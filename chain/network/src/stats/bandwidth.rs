@@ -0,0 +1,139 @@
+//! Report of the network traffic broken down by message type.
+//!
+//! The report is read from the `near_peer_message_{sent,received}_by_type_*`
+//! Prometheus counters, which PeerActors update for every message sent or
+//! received (labelled with `PeerMessage::msg_variant()`, which resolves routed
+//! messages to their body kind), so it doesn't cost anything on the hot path
+//! and never diverges from the metrics.  The debug page and the periodic
+//! "bandwidth" log line are built from it.
+use crate::stats::metrics;
+use near_o11y::metrics::prometheus::core::Collector;
+use near_o11y::metrics::IntCounterVec;
+use near_primitives::views::{MessageTypeBandwidthView, NetworkBandwidthView};
+use std::collections::HashMap;
+
+/// Number of message types included in the periodic log line.
+const LOG_TOP_TYPES: usize = 5;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Counters {
+    pub sent_bytes: u64,
+    pub sent_messages: u64,
+    pub received_bytes: u64,
+    pub received_messages: u64,
+}
+
+impl Counters {
+    fn total_bytes(&self) -> u64 {
+        self.sent_bytes + self.received_bytes
+    }
+}
+
+/// Adds the values of `counter` by message type to `stats`, using `field` to
+/// pick the counter of `Counters` to add them to.
+fn collect_by_type(
+    stats: &mut HashMap<String, Counters>,
+    counter: &IntCounterVec,
+    field: fn(&mut Counters) -> &mut u64,
+) {
+    for family in counter.collect() {
+        for metric in family.get_metric() {
+            let msg_type = match metric.get_label().iter().find(|label| label.get_name() == "type")
+            {
+                Some(label) => label.get_value(),
+                None => continue,
+            };
+            *field(stats.entry(msg_type.to_string()).or_default()) +=
+                metric.get_counter().get_value() as u64;
+        }
+    }
+}
+
+/// Counters per message type since the node started,
+/// sorted by the total (sent + received) number of bytes, biggest first.
+pub(crate) fn snapshot() -> Vec<(String, Counters)> {
+    let mut stats = HashMap::new();
+    collect_by_type(&mut stats, &metrics::PEER_MESSAGE_SENT_BY_TYPE_BYTES, |c| &mut c.sent_bytes);
+    collect_by_type(&mut stats, &metrics::PEER_MESSAGE_SENT_BY_TYPE_TOTAL, |c| {
+        &mut c.sent_messages
+    });
+    collect_by_type(&mut stats, &metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES, |c| {
+        &mut c.received_bytes
+    });
+    collect_by_type(&mut stats, &metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_TOTAL, |c| {
+        &mut c.received_messages
+    });
+    let mut res: Vec<_> = stats.into_iter().collect();
+    res.sort_by(|a, b| b.1.total_bytes().cmp(&a.1.total_bytes()).then(a.0.cmp(&b.0)));
+    res
+}
+
+/// Short human readable summary of the heaviest message types, for logging.
+pub(crate) fn top_types_summary() -> String {
+    snapshot()
+        .iter()
+        .take(LOG_TOP_TYPES)
+        .map(|(t, c)| format!("{t}: sent={}B recv={}B", c.sent_bytes, c.received_bytes))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn view() -> NetworkBandwidthView {
+    NetworkBandwidthView {
+        message_types: snapshot()
+            .into_iter()
+            .map(|(t, c)| MessageTypeBandwidthView {
+                message_type: t,
+                sent_bytes: c.sent_bytes,
+                sent_messages: c.sent_messages,
+                received_bytes: c.received_bytes,
+                received_messages: c.received_messages,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_sent(msg_type: &str, bytes: u64) {
+        metrics::PEER_MESSAGE_SENT_BY_TYPE_TOTAL.with_label_values(&[msg_type]).inc();
+        metrics::PEER_MESSAGE_SENT_BY_TYPE_BYTES.with_label_values(&[msg_type]).inc_by(bytes);
+    }
+
+    fn record_received(msg_type: &str, bytes: u64) {
+        metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_TOTAL.with_label_values(&[msg_type]).inc();
+        metrics::PEER_MESSAGE_RECEIVED_BY_TYPE_BYTES.with_label_values(&[msg_type]).inc_by(bytes);
+    }
+
+    #[test]
+    fn snapshot_sorted_by_total_bytes() {
+        // The counters are global, so the test uses message types which no
+        // other test sends.
+        record_sent("TestBlock", 100);
+        record_received("TestBlock", 50);
+        record_received("TestPartialEncodedChunk", 1000);
+        record_sent("TestForwardTx", 10);
+        record_sent("TestForwardTx", 10);
+
+        let snapshot: Vec<_> =
+            snapshot().into_iter().filter(|(t, _)| t.starts_with("Test")).collect();
+        let types: Vec<_> = snapshot.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(types, vec!["TestPartialEncodedChunk", "TestBlock", "TestForwardTx"]);
+        assert_eq!(
+            snapshot[1].1,
+            Counters {
+                sent_bytes: 100,
+                sent_messages: 1,
+                received_bytes: 50,
+                received_messages: 1
+            }
+        );
+        assert_eq!(
+            snapshot[2].1,
+            Counters { sent_bytes: 20, sent_messages: 2, received_bytes: 0, received_messages: 0 }
+        );
+        assert!(view().message_types.iter().any(|t| t.message_type == "TestBlock"));
+    }
+}
@@ -144,6 +144,43 @@ pub(crate) static PEER_DATA_SENT_BYTES: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter("near_peer_data_sent_bytes", "Total data sent to peers").unwrap()
 });
 
+pub(crate) static PEER_MESSAGE_COMPRESSION_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_message_compression_bytes",
+        "Size of compressed messages sent/received before and after compression",
+        &["direction", "form"],
+    )
+    .unwrap()
+});
+
+pub(crate) static PEER_MESSAGE_COMPRESSION_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_peer_message_compression_time",
+        "Time spent on compressing and decompressing messages",
+        &["op"],
+        Some(exponential_buckets(0.00001, 2., 20).unwrap()),
+    )
+    .unwrap()
+});
+
+pub(crate) static PEER_KEEPALIVE_RTT: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_peer_keepalive_rtt",
+        "Round trip time of the keepalive pings sent to peers",
+        exponential_buckets(0.001, 1.5, 25).unwrap(),
+    )
+    .unwrap()
+});
+
+pub(crate) static PEER_CONNECTIONS_EVICTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_connections_evicted",
+        "Number of connections closed because they were detected to be dead or stuck",
+        &["reason"],
+    )
+    .unwrap()
+});
+
 pub(crate) static PEER_DATA_READ_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     try_create_int_gauge_vec(
         "near_peer_read_buffer_size",
@@ -324,6 +361,24 @@ pub(crate) static BROADCAST_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec("near_broadcast_msg", "Broadcasted messages", &["type"]).unwrap()
 });
 
+pub(crate) static GOSSIP_SEEN_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_gossip_seen_messages",
+        "Blocks, challenges, transactions and approvals received from peers, by whether they have been seen before",
+        &["type", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static GOSSIP_BROADCAST_SENDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_gossip_broadcast_sends",
+        "Copies of broadcasted messages sent to peers, or suppressed because the peer already has the message",
+        &["type", "result"],
+    )
+    .unwrap()
+});
+
 static NETWORK_ROUTED_MSG_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
     try_create_histogram_vec(
         "near_network_routed_msg_latency",
@@ -426,12 +481,43 @@ fn record_routed_msg_hops(msg: &RoutedMessageV2) {
     }
 }
 
+pub(crate) static SHADOW_CHAIN_PEER_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_shadow_chain_peer_height",
+        "Highest head height reported by the boot nodes of the shadowed chain",
+    )
+    .unwrap()
+});
+pub(crate) static SHADOW_CHAIN_HEADER_HEAD_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_shadow_chain_header_head_height",
+        "Height of the last header synced from the shadowed chain",
+    )
+    .unwrap()
+});
+pub(crate) static SHADOW_CHAIN_HEADERS_RECEIVED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_shadow_chain_headers_received_total",
+        "Number of headers received from the boot nodes of the shadowed chain",
+    )
+    .unwrap()
+});
+pub(crate) static SHADOW_CHAIN_HEADERS_REQUEST_LATENCY: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_shadow_chain_headers_request_latency_seconds",
+        "Time between sending a headers request to a boot node of the shadowed chain and receiving the headers",
+        exponential_buckets(0.001, 1.5, 25).unwrap(),
+    )
+    .unwrap()
+});
+
 #[derive(Clone, Copy, strum::AsRefStr)]
 pub(crate) enum MessageDropped {
     NoRouteFound,
     UnknownAccount,
     InputTooLong,
     MaxCapacityExceeded,
+    StreamCapacityExceeded,
     TransactionsPerBlockExceeded,
     Duplicate,
 }
@@ -1 +1,2 @@
+pub(crate) mod bandwidth;
 pub mod metrics;
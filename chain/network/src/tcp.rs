@@ -1,3 +1,4 @@
+use crate::config;
 use crate::network_protocol::PeerInfo;
 use anyhow::{anyhow, Context as _};
 use near_primitives::network::PeerId;
@@ -102,6 +103,16 @@ impl Stream {
         Ok(Stream::new(stream, StreamType::Outbound { peer_id: peer_info.id.clone(), tier })?)
     }
 
+    /// Enables TCP keepalive probes on the connection, so that the OS
+    /// closes it if the peer becomes unreachable.
+    pub(crate) fn set_keepalive(&self, cfg: &config::TcpKeepalive) -> std::io::Result<()> {
+        let keepalive = socket2::TcpKeepalive::new()
+            .with_time(cfg.time.try_into().unwrap())
+            .with_interval(cfg.interval.try_into().unwrap())
+            .with_retries(cfg.retries);
+        socket2::SockRef::from(&self.stream).set_tcp_keepalive(&keepalive)
+    }
+
     /// Establishes a loopback TCP connection to localhost with random ports.
     /// Returns a pair of streams: (outbound,inbound).
     #[cfg(test)]
@@ -1,7 +1,8 @@
 use crate::client;
+use crate::client::DataResponse;
 use crate::network_protocol::{
-    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
-    StateResponseInfo,
+    GarbageCollectedData, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg, StateDeltaResponseInfo, StateResponseInfo,
 };
 use crate::sink::Sink;
 use crate::types::{NetworkInfo, ReasonForBan, StateResponseInfoV2};
@@ -12,7 +13,7 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::{ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart};
 use near_primitives::syncing::{ShardStateSyncResponse, ShardStateSyncResponseV2};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, EpochId, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -22,8 +23,10 @@ pub enum Event {
     BlockHeaders(Vec<BlockHeader>),
     BlockApproval(Approval, PeerId),
     BlockHeadersRequest(Vec<CryptoHash>),
+    BlockHeadersAtHeightsRequest(Vec<BlockHeight>),
     BlockRequest(CryptoHash),
     Challenge(Challenge),
+    DataGarbageCollected(GarbageCollectedData, PeerId),
     Chunk(Vec<PartialEncodedChunkPart>),
     ChunkRequest(ChunkHash),
     Transaction(SignedTransaction),
@@ -49,7 +52,8 @@ impl client::Client for Fake {
         &self,
         _shard_id: ShardId,
         _sync_hash: CryptoHash,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        _peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
         unimplemented!();
     }
 
@@ -58,15 +62,16 @@ impl client::Client for Fake {
         shard_id: ShardId,
         sync_hash: CryptoHash,
         part_id: u64,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        _peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
         let part = Some((part_id, vec![]));
         let state_response =
             ShardStateSyncResponse::V2(ShardStateSyncResponseV2 { header: None, part });
-        let result = Some(StateResponseInfo::V2(StateResponseInfoV2 {
+        let result = Some(DataResponse::Data(StateResponseInfo::V2(StateResponseInfoV2 {
             shard_id,
             sync_hash,
             state_response,
-        }));
+        })));
         Ok(result)
     }
 
@@ -74,6 +79,24 @@ impl client::Client for Fake {
         unimplemented!();
     }
 
+    async fn data_garbage_collected(&self, data: GarbageCollectedData, peer_id: PeerId) {
+        self.event_sink.push(Event::DataGarbageCollected(data, peer_id));
+    }
+
+    async fn state_request_delta(
+        &self,
+        _shard_id: ShardId,
+        _sync_hash: CryptoHash,
+        _from_hash: CryptoHash,
+        _peer_id: PeerId,
+    ) -> Result<Option<StateDeltaResponseInfo>, ReasonForBan> {
+        unimplemented!();
+    }
+
+    async fn state_delta_response(&self, _info: StateDeltaResponseInfo) {
+        unimplemented!();
+    }
+
     async fn block_approval(&self, approval: Approval, peer_id: PeerId) {
         self.event_sink.push(Event::BlockApproval(approval, peer_id));
     }
@@ -94,6 +117,8 @@ impl client::Client for Fake {
         &self,
         resp: PartialEncodedChunkResponseMsg,
         _timestamp: time::Instant,
+        _author: PeerId,
+        _msg_len: usize,
     ) {
         self.event_sink.push(Event::Chunk(resp.parts));
     }
@@ -106,7 +131,7 @@ impl client::Client for Fake {
         unimplemented!();
     }
 
-    async fn block_request(&self, hash: CryptoHash) -> Option<Box<Block>> {
+    async fn block_request(&self, hash: CryptoHash) -> Option<DataResponse<Box<Block>>> {
         self.event_sink.push(Event::BlockRequest(hash));
         None
     }
@@ -116,6 +141,14 @@ impl client::Client for Fake {
         None
     }
 
+    async fn block_headers_at_heights_request(
+        &self,
+        heights: Vec<BlockHeight>,
+    ) -> Option<Vec<BlockHeader>> {
+        self.event_sink.push(Event::BlockHeadersAtHeightsRequest(heights));
+        None
+    }
+
     async fn block(&self, block: Block, _peer_id: PeerId, _was_requested: bool) {
         self.event_sink.push(Event::Block(block));
     }
@@ -0,0 +1,90 @@
+use near_primitives::network::PeerId;
+use std::collections::HashSet;
+use std::net;
+
+/// A trusted peer, identified either by its PeerId or by its IP address.
+/// As in `blacklist::Entry`, IPv4 addresses are mapped to IPv6.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub enum Entry {
+    PeerId(PeerId),
+    Ip(net::Ipv6Addr),
+}
+
+impl Entry {
+    pub fn from_ip(ip: net::IpAddr) -> Entry {
+        Entry::Ip(match ip {
+            net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            net::IpAddr::V6(ip) => ip,
+        })
+    }
+}
+
+impl std::str::FromStr for Entry {
+    type Err = near_crypto::ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<net::IpAddr>() {
+            Ok(ip) => Ok(Entry::from_ip(ip)),
+            Err(_) => Ok(Entry::PeerId(PeerId::new(s.parse()?))),
+        }
+    }
+}
+
+/// Peers whose inbound connections are always accepted (even if max_num_peers has been
+/// reached), which are exempt from the inbound handshake throttling and which are never
+/// disconnected to make room for other peers. Meant for the operator's own nodes and
+/// monitoring probes.
+///
+/// Handshake throttling is applied before the PeerId of the peer is known,
+/// so only the peers trusted by IP are exempt from it.
+#[derive(Debug, Default, Clone)]
+pub struct TrustedPeers(HashSet<Entry>);
+
+impl FromIterator<Entry> for TrustedPeers {
+    fn from_iter<I: IntoIterator<Item = Entry>>(i: I) -> Self {
+        Self(i.into_iter().collect())
+    }
+}
+
+impl TrustedPeers {
+    /// Returns whether connections from the given IP are trusted.
+    pub fn contains_ip(&self, ip: net::IpAddr) -> bool {
+        self.0.contains(&Entry::from_ip(ip))
+    }
+
+    /// Returns whether the peer connected from `peer_addr` is trusted, either
+    /// by PeerId or by IP.  `peer_addr` has to be the address of the TCP
+    /// connection, rather than the one the peer advertises.
+    pub fn contains(&self, peer_id: &PeerId, peer_addr: net::SocketAddr) -> bool {
+        self.0.contains(&Entry::PeerId(peer_id.clone())) || self.contains_ip(peer_addr.ip())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::testonly::make_rng;
+
+    #[test]
+    fn test_contains() {
+        let mut rng = make_rng(7812934);
+        let trusted_id = data::make_peer_id(&mut rng);
+        let other_id = data::make_peer_id(&mut rng);
+        let trusted: TrustedPeers = [trusted_id.to_string().as_str(), "192.0.2.1", "2001:db8::1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert!("foo".parse::<Entry>().is_err());
+        assert!("192.0.2.1:24567".parse::<Entry>().is_err());
+
+        let addr = |addr: &str| addr.parse().unwrap();
+        assert!(trusted.contains(&trusted_id, addr("192.0.2.2:1234")));
+        assert!(trusted.contains(&other_id, addr("192.0.2.1:1234")));
+        // IPv4 mapped to IPv6 is the same address.
+        assert!(trusted.contains(&other_id, addr("[::ffff:192.0.2.1]:1234")));
+        assert!(trusted.contains(&other_id, addr("[2001:db8::1]:1234")));
+        assert!(!trusted.contains(&other_id, addr("192.0.2.2:1234")));
+        assert!(trusted.contains_ip("192.0.2.1".parse().unwrap()));
+    }
+}
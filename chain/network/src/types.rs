@@ -16,7 +16,7 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::PartialEncodedChunkWithArcReceipts;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::BlockHeight;
-use near_primitives::types::{AccountId, ShardId};
+use near_primitives::types::{AccountId, ProtocolVersion, ShardId};
 use once_cell::sync::OnceCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
@@ -25,9 +25,9 @@ use std::sync::Arc;
 
 /// Exported types, which are part of network protocol.
 pub use crate::network_protocol::{
-    Edge, PartialEdgeInfo, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
-    PartialEncodedChunkResponseMsg, PeerChainInfoV2, PeerInfo, Ping, Pong, StateResponseInfo,
-    StateResponseInfoV1, StateResponseInfoV2,
+    Edge, GarbageCollectedData, PartialEdgeInfo, PartialEncodedChunkForwardMsg,
+    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, PeerChainInfoV2, PeerInfo, Ping,
+    Pong, StateDeltaResponseInfo, StateResponseInfo, StateResponseInfoV1, StateResponseInfoV2,
 };
 
 /// Number of hops a message is allowed to travel before being dropped.
@@ -135,6 +135,9 @@ pub struct ChainInfo {
     pub tracked_shards: Vec<ShardId>,
     // The lastest block on chain.
     pub block: Block,
+    // Height of the earliest block the node holds: the genesis height for archival
+    // nodes, the tail of the chain for the ones garbage collecting old blocks.
+    pub earliest_height: BlockHeight,
     // Public keys of accounts participating in the BFT consensus
     // (both accounts from current and next epoch are important, that's why
     // the map is indexed by (EpochId,AccountId) pair).
@@ -231,6 +234,8 @@ pub enum NetworkRequests {
     BlockRequest { hash: CryptoHash, peer_id: PeerId },
     /// Request given block headers.
     BlockHeadersRequest { hashes: Vec<CryptoHash>, peer_id: PeerId },
+    /// Request the block headers at given heights, to find the fork point with the peer.
+    BlockHeadersAtHeightsRequest { heights: Vec<BlockHeight>, peer_id: PeerId },
     /// Request state header for given shard at given state root.
     StateRequestHeader { shard_id: ShardId, sync_hash: CryptoHash, target: AccountOrPeerIdOrHash },
     /// Request state part for given shard at given state root.
@@ -240,6 +245,14 @@ pub enum NetworkRequests {
         part_id: u64,
         target: AccountOrPeerIdOrHash,
     },
+    /// Request the state delta of given shard since the end of the epoch of
+    /// `from_hash`.
+    StateRequestDelta {
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        from_hash: CryptoHash,
+        target: AccountOrPeerIdOrHash,
+    },
     /// Response to state request.
     StateResponse { route_back: CryptoHash, response: StateResponseInfo },
     /// Ban given peer.
@@ -293,6 +306,20 @@ pub struct HighestHeightPeerInfo {
     pub tracked_shards: Vec<ShardId>,
     /// Denote if a node is running in archival mode or not.
     pub archival: bool,
+    /// Lowest height of the blocks the peer holds.
+    /// None if the peer doesn't advertise it.
+    pub earliest_height: Option<BlockHeight>,
+    /// Protocol version agreed on for the connection with the peer.
+    pub protocol_version: ProtocolVersion,
+}
+
+impl HighestHeightPeerInfo {
+    /// Whether the peer holds the block at `height`, according to the range of heights
+    /// it has advertised. None if the peer doesn't advertise it.
+    pub fn holds_height(&self, height: BlockHeight) -> Option<bool> {
+        self.earliest_height
+            .map(|earliest| earliest <= height && height <= self.highest_block_height)
+    }
 }
 
 impl From<FullPeerInfo> for Option<HighestHeightPeerInfo> {
@@ -305,6 +332,8 @@ impl From<FullPeerInfo> for Option<HighestHeightPeerInfo> {
                 highest_block_hash: p.chain_info.last_block.unwrap().hash,
                 tracked_shards: p.chain_info.tracked_shards,
                 archival: p.chain_info.archival,
+                earliest_height: p.chain_info.earliest_height,
+                protocol_version: p.chain_info.protocol_version,
             })
         } else {
             None
@@ -331,6 +360,11 @@ pub struct PeerChainInfo {
     pub tracked_shards: Vec<ShardId>,
     /// Denote if a node is running in archival mode or not.
     pub archival: bool,
+    /// Lowest height of the blocks the peer holds.
+    /// None if the peer doesn't advertise it.
+    pub earliest_height: Option<BlockHeight>,
+    /// Protocol version agreed on for the connection with the peer.
+    pub protocol_version: ProtocolVersion,
 }
 
 // Information about the connected peer that is shared with the rest of the system.
@@ -351,6 +385,10 @@ pub struct ConnectedPeerInfo {
     pub peer_type: PeerType,
     /// Nonce used for the connection with the peer.
     pub nonce: u64,
+    /// Number of messages waiting to be sent to the peer.
+    pub messages_to_send: u64,
+    /// Total size in bytes of the messages waiting to be sent to the peer.
+    pub bytes_to_send: u64,
 }
 
 #[derive(Debug, Clone, actix::MessageResponse)]
@@ -446,6 +484,20 @@ impl<M: actix::Message, T: MsgRecipient<M>> MsgRecipient<M> for NetworkRecipient
     }
 }
 
+/// PeerManagerAdapter of a node started without networking.
+/// Messages are dropped and requests fail as if the PeerManagerActor was stopped.
+pub struct OfflineNetwork;
+
+impl<M: actix::Message + Send + 'static> MsgRecipient<M> for OfflineNetwork
+where
+    M::Result: Send,
+{
+    fn send(&self, _msg: M) -> BoxFuture<'static, Result<M::Result, actix::MailboxError>> {
+        futures::future::err(actix::MailboxError::Closed).boxed()
+    }
+    fn do_send(&self, _msg: M) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
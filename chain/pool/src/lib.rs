@@ -113,6 +113,12 @@ impl TransactionPool {
     pub fn len(&self) -> usize {
         self.unique_transactions.len()
     }
+
+    /// Total size in bytes of the transactions in the pool.  Transactions
+    /// taken out by a pool iterator which is still alive aren't counted.
+    pub fn transactions_size(&self) -> u64 {
+        self.transactions.values().flatten().map(|tx| tx.get_size()).sum()
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -64,6 +64,9 @@ impl From<near_client_primitives::types::GetStateChangesError> for ErrorKind {
             near_client_primitives::types::GetStateChangesError::Unreachable { error_message } => {
                 Self::InternalError(error_message)
             }
+            near_client_primitives::types::GetStateChangesError::TimeoutError => {
+                Self::Timeout(err.to_string())
+            }
         }
     }
 }
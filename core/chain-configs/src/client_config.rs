@@ -1,6 +1,7 @@
 //! Chain Client Configuration
 use std::cmp::max;
 use std::cmp::min;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
@@ -9,7 +10,7 @@ use crate::MutableConfigValue;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockHeightDelta, Gas, NumBlocks, NumSeats, ShardId,
 };
-use near_primitives::version::Version;
+use near_primitives::version::{ProtocolVersion, Version};
 
 pub const TEST_STATE_SYNC_TIMEOUT: u64 = 5;
 
@@ -21,6 +22,27 @@ pub enum LogSummaryStyle {
     Colored,
 }
 
+/// When a block producer produces blocks which don't include any new chunks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyBlocksPolicy {
+    /// Produce a block at every height.
+    Always,
+    /// Only produce blocks with new chunks, i.e. when there are transactions or receipts.  The
+    /// chain doesn't progress otherwise.
+    Never,
+    /// Only produce a block without new chunks once the head is older than this.  Private chains
+    /// use it to avoid storing empty blocks while still advancing the block timestamp for
+    /// contracts relying on it.
+    IfIdleLongerThan(Duration),
+}
+
+impl Default for EmptyBlocksPolicy {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
 /// Minimum number of epochs for which we keep store data
 pub const MIN_GC_NUM_EPOCHS_TO_KEEP: u64 = 3;
 
@@ -73,6 +95,133 @@ impl GCConfig {
     }
 }
 
+/// How long archival nodes keep a kind of data.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    /// Keep the data since genesis.
+    Forever,
+    /// Keep the data of this number of latest epochs.  Values lower than
+    /// `gc_num_epochs_to_keep` are raised to it.
+    Epochs(u64),
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention::Forever
+    }
+}
+
+/// Data kept by archival nodes, per kind of data.  Block headers are always
+/// kept since header sync needs them.  Ignored by non-archival nodes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ArchivalPolicy {
+    /// Blocks, without their chunks.
+    pub blocks: Retention,
+    /// Chunks with their transactions and receipts.
+    pub chunks: Retention,
+    /// State changes, returned by the `EXPERIMENTAL_changes` RPC methods.
+    pub state_changes: Retention,
+    /// Execution outcomes of transactions and receipts.
+    pub outcomes: Retention,
+}
+
+impl ArchivalPolicy {
+    /// Whether the node keeps all the data.
+    pub fn keeps_everything(&self) -> bool {
+        [self.blocks, self.chunks, self.state_changes, self.outcomes]
+            .iter()
+            .all(|retention| *retention == Retention::Forever)
+    }
+}
+
+/// Configuration of alerts sent when the node misses its block or chunk
+/// production slots, falls behind its peers or loses peers.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// URL to which the payload of every alert is sent in a POST request.
+    pub webhook_url: Option<String>,
+    /// Command, with its arguments, run for every alert with the payload
+    /// appended as the last argument.
+    pub command: Option<Vec<String>>,
+    /// Template of the payload.  The placeholders `{alert}`, `{message}`,
+    /// `{chain_id}`, `{account_id}` and `{height}` are replaced with the name
+    /// of the alert, its description, the chain id, the validator account of
+    /// the node and the height of the head, escaped to be embeddable in JSON
+    /// strings.
+    pub payload_template: String,
+    /// Alert when the head is more than this many blocks behind the highest
+    /// height reported by peers.
+    pub max_blocks_behind: Option<BlockHeightDelta>,
+    /// Alert when the head is more than this many blocks above its last BFT
+    /// final block, which usually means the network doesn't collect enough
+    /// approvals.
+    pub max_finality_lag: Option<BlockHeightDelta>,
+    /// Minimum time between two alerts of the same kind.
+    pub min_interval: Duration,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            command: None,
+            payload_template: concat!(
+                r#"{"alert":"{alert}","message":"{message}","chain_id":"{chain_id}","#,
+                r#""account_id":"{account_id}","height":{height}}"#
+            )
+            .to_string(),
+            max_blocks_behind: None,
+            max_finality_lag: None,
+            min_interval: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Configuration of the snapshots of the database taken at epoch boundaries,
+/// e.g. for backups.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct EpochSnapshotConfig {
+    /// Directory where the checkpoints are created, one subdirectory per
+    /// checkpoint named after the height of the first block of the epoch.
+    /// Relative paths are resolved against the home directory.  Must be on
+    /// the same filesystem as the database so that the files can be
+    /// hard-linked.
+    pub path: PathBuf,
+    /// Number of latest checkpoints kept, older ones are deleted once
+    /// `command` succeeds for the new checkpoint.
+    pub num_to_keep: usize,
+    /// Whether to flush the memtables of the database to disk before creating
+    /// the checkpoint.
+    pub flush: bool,
+    /// Command, with its arguments, run after every checkpoint with the path
+    /// to the checkpoint appended as the last argument, e.g. to upload it.
+    pub command: Option<Vec<String>>,
+}
+
+impl Default for EpochSnapshotConfig {
+    fn default() -> Self {
+        Self { path: "epoch_snapshots".into(), num_to_keep: 2, flush: true, command: None }
+    }
+}
+
+/// Range of protocol versions, both ends inclusive, the operator of a node
+/// expects the network to run.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProtocolVersionRange {
+    pub min: ProtocolVersion,
+    pub max: ProtocolVersion,
+}
+
+impl ProtocolVersionRange {
+    pub fn contains(&self, version: ProtocolVersion) -> bool {
+        self.min <= version && version <= self.max
+    }
+}
+
 /// ClientConfig where some fields can be updated at runtime.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClientConfig {
@@ -118,8 +267,8 @@ pub struct ClientConfig {
     pub log_summary_period: Duration,
     /// Enable coloring of the logs
     pub log_summary_style: LogSummaryStyle,
-    /// Produce empty blocks, use `false` for testing.
-    pub produce_empty_blocks: bool,
+    /// Whether to produce blocks without new chunks.  Can be changed while the node is running.
+    pub produce_empty_blocks: MutableConfigValue<EmptyBlocksPolicy>,
     /// Epoch length.
     pub epoch_length: BlockHeightDelta,
     /// Number of block producer seats
@@ -130,6 +279,9 @@ pub struct ClientConfig {
     pub ttl_account_id_router: Duration,
     /// Horizon at which instead of fetching block, fetch full state.
     pub block_fetch_horizon: BlockHeightDelta,
+    /// Whether to switch to state sync when more than `block_fetch_horizon`
+    /// blocks behind.  Archival nodes never do.
+    pub state_sync_fallback: bool,
     /// Horizon to step from the latest block when fetching state.
     pub state_fetch_horizon: NumBlocks,
     /// Time between check to perform catchup.
@@ -148,6 +300,8 @@ pub struct ClientConfig {
     pub tracked_shards: Vec<ShardId>,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Data archival nodes keep.  By default, everything.
+    pub archival_policy: ArchivalPolicy,
     /// save_trie_changes should be set to true iff
     /// - archive if false - non-archivale nodes need trie changes to perform garbage collection
     /// - archive is true, cold_store is configured and migration to split_storage is finished - node
@@ -155,22 +309,117 @@ pub struct ClientConfig {
     pub save_trie_changes: bool,
     /// Number of threads for ViewClientActor pool.
     pub view_client_threads: usize,
+    /// Number of threads executing contract function calls of `query` requests, separately from
+    /// the ViewClientActor pool so that heavy calls can't starve other queries.
+    pub view_call_threads: usize,
+    /// Maximum number of function calls waiting for or being executed by the view call pool.
+    /// Further calls are rejected until some of them finish.
+    pub view_call_max_pending: usize,
+    /// Time after which a function call still waiting for or being executed by the view call
+    /// pool is answered with an error.
+    pub view_call_timeout: Duration,
+    /// Maximum number of messages from the network queued for the client actor.  Once reached,
+    /// transactions, chunk part requests and network info updates are dropped; blocks, chunks
+    /// and approvals never are.
+    pub client_queue_capacity: usize,
+    /// Maximum number of messages from the network queued for the view client actors.  Once
+    /// reached, requests of peers are dropped, peers retry them with other nodes.
+    pub view_client_queue_capacity: usize,
     /// Run Epoch Sync on the start.
     pub epoch_sync_enabled: bool,
     /// Number of seconds between state requests for view client.
     pub view_client_throttle_period: Duration,
+    /// Maximum number of state sync requests served per `view_client_throttle_period` by all
+    /// view client threads together.
+    pub state_request_limit: usize,
+    /// Maximum number of state sync requests of a single peer served per
+    /// `view_client_throttle_period`, so that a few bootstrapping peers can't use up
+    /// `state_request_limit`.
+    pub state_request_limit_per_peer: usize,
+    /// Maximum number of state sync requests the view client threads serve at the same time.
+    /// Further requests are dropped, peers retry them with other nodes.
+    pub state_request_max_in_progress: usize,
+    /// Maximum bytes per second of responses to the sync requests of peers (blocks, headers and
+    /// state), averaged over `view_client_throttle_period`.  Requests are dropped once it's used
+    /// up, so that bootstrapping nodes can't consume the whole uplink of a validator.
+    pub sync_serving_bandwidth_limit: Option<u64>,
+    /// Maximum bytes per second of responses to the state sync requests of peers, counted
+    /// towards `sync_serving_bandwidth_limit` as well.
+    pub state_serving_bandwidth_limit: Option<u64>,
+    /// Maximum total size, in bytes, of the state parts generated for serving state sync which
+    /// are kept on disk for other requesters.  Least recently requested parts are deleted first.
+    pub state_parts_cache_size: u64,
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     pub trie_viewer_state_size_limit: Option<u64>,
     /// Max burnt gas per view method.  If present, overrides value stored in
     /// genesis file.  The value only affects the RPCs without influencing the
     /// protocol thus changing it per-node doesn’t affect the blockchain.
     pub max_gas_burnt_view: Option<Gas>,
+    /// If set, action receipts taking longer to execute than this multiple of the time implied
+    /// by the gas they burnt (1 Tgas per millisecond) are logged with details and counted in
+    /// the `near_slow_receipts_total` metric.  Such receipts point to undercharged operations.
+    pub slow_receipt_factor: Option<f64>,
+    /// If set, chunks applied at an older protocol version are applied again at this version,
+    /// enabling the protocol features up to it, and the results are compared with the regular
+    /// ones.  Only the regular results are used.
+    pub shadow_protocol_version: Option<ProtocolVersion>,
+    /// If set, blocks, approvals, chunk parts and transactions received from the network are
+    /// recorded in a new file in this directory on every start, to be replayed with
+    /// `neard replay`.
+    pub message_log: Option<PathBuf>,
     /// Re-export storage layer statistics as prometheus metrics.
     pub enable_statistics_export: bool,
     /// Number of threads to execute background migration work in client.
     pub client_background_migration_threads: usize,
     /// Duration to perform background flat storage creation step.
     pub flat_storage_creation_period: Duration,
+    /// If set, the node shadows block and chunk production of the given validator account:
+    /// whenever the account is due to produce, the node assembles and signs the block or chunk
+    /// with a throwaway key, reports how long it took and discards the result.
+    pub dry_run_validator: Option<AccountId>,
+    /// If set, the node runs without networking (`neard run --offline`), to inspect a copy of a
+    /// data directory: the storage isn't migrated, the client doesn't run its sync, catchup and
+    /// block production timers and the cold store loop, the outcomes by account index backfill,
+    /// telemetry and PeerManagerActor aren't started.  The database is still opened read-write,
+    /// and starting the chain may write to it, e.g. to reset the header head, so it should be a
+    /// copy rather than the data directory of a node that will be started again.
+    pub offline: bool,
+    /// If set, the node indexes the ids of execution outcomes by the account they were executed
+    /// on, so that they can be queried with the `EXPERIMENTAL_account_outcomes` RPC method.
+    /// Blocks saved before it got enabled are indexed in the background, and the index is
+    /// cleared when it gets disabled.
+    pub save_account_outcome_ids: bool,
+    /// If set, new chunks whose application takes longer than this are logged with their hash,
+    /// the contracts they invoked and the time it took.
+    pub slow_chunk_threshold: Option<Duration>,
+    /// Number of threads applying the chunks of different shards of a block in parallel. If not
+    /// set, chunks are applied in the global rayon thread pool, shared with other work.
+    pub apply_chunks_threads: Option<usize>,
+    /// Number of heights below the head for which all the received parts and receipts of
+    /// completed chunks are kept in the store, to answer requests from peers which fell behind.
+    /// Zero disables keeping them.
+    pub recent_partial_chunks_num_heights: BlockHeightDelta,
+    /// If set, the node sends alerts about problems with its operation.
+    pub alerts: Option<AlertsConfig>,
+    /// If set, the node creates a checkpoint of its database at every epoch boundary.
+    pub epoch_snapshot: Option<EpochSnapshotConfig>,
+    /// If set, state parts are streamed from the debug RPC server at this address, i.e. the
+    /// `rpc.debug_addr` of the other node (e.g. `http://10.0.0.1:3031`), which has to be set
+    /// there since the endpoint isn't served on `rpc.addr`, instead of being
+    /// requested from random peers.  Meant for handing over a shard between the RPC nodes of
+    /// one operator.  If the transfer keeps failing, the parts are requested from peers.
+    pub state_handover_addr: Option<String>,
+    /// If set, the node runs as a chunk-only producer which tracks only the shards it produces
+    /// chunks for in the current or the next epoch.  The state of other shards is deleted once
+    /// the node stops tracking them and is synced again if the node is assigned to them later.
+    pub minimal_chunk_producer: bool,
+    /// A warning is logged, and an alert raised, when the share of blocks or chunks produced by
+    /// the validator of the node in the current epoch is less than this many percentage points
+    /// above the kickout threshold.
+    pub kickout_warning_margin: u8,
+    /// If set, the node refuses to start when the protocol version of the binary is outside of
+    /// this range, and warns when the network is about to switch to a version outside of it.
+    pub expected_protocol_version_range: Option<ProtocolVersionRange>,
 }
 
 impl ClientConfig {
@@ -213,12 +462,16 @@ impl ClientConfig {
             header_sync_expected_height_per_second: 1,
             min_num_peers: 1,
             log_summary_period: Duration::from_secs(10),
-            produce_empty_blocks: true,
+            produce_empty_blocks: MutableConfigValue::new(
+                EmptyBlocksPolicy::Always,
+                "produce_empty_blocks",
+            ),
             epoch_length: 10,
             num_block_producer_seats,
             announce_account_horizon: 5,
             ttl_account_id_router: Duration::from_secs(60 * 60),
             block_fetch_horizon: 50,
+            state_sync_fallback: true,
             state_fetch_horizon: 5,
             catchup_step_period: Duration::from_millis(1),
             chunk_request_retry_period: min(
@@ -231,16 +484,43 @@ impl ClientConfig {
             tracked_accounts: vec![],
             tracked_shards: vec![],
             archive,
+            archival_policy: ArchivalPolicy::default(),
             save_trie_changes,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
+            view_call_threads: 2,
+            view_call_max_pending: 64,
+            view_call_timeout: Duration::from_secs(10),
+            client_queue_capacity: usize::MAX,
+            view_client_queue_capacity: usize::MAX,
             epoch_sync_enabled,
             view_client_throttle_period: Duration::from_secs(1),
+            state_request_limit: 30,
+            state_request_limit_per_peer: 30,
+            state_request_max_in_progress: usize::MAX,
+            sync_serving_bandwidth_limit: None,
+            state_serving_bandwidth_limit: None,
+            state_parts_cache_size: u64::MAX,
             trie_viewer_state_size_limit: None,
             max_gas_burnt_view: None,
+            slow_receipt_factor: None,
+            shadow_protocol_version: None,
+            message_log: None,
             enable_statistics_export: true,
             client_background_migration_threads: 1,
             flat_storage_creation_period: Duration::from_secs(1),
+            dry_run_validator: None,
+            offline: false,
+            save_account_outcome_ids: false,
+            slow_chunk_threshold: None,
+            apply_chunks_threads: None,
+            recent_partial_chunks_num_heights: 0,
+            alerts: None,
+            epoch_snapshot: None,
+            state_handover_addr: None,
+            minimal_chunk_producer: false,
+            kickout_warning_margin: 10,
+            expected_protocol_version_range: None,
         }
     }
 }
@@ -0,0 +1,264 @@
+//! Programmatic construction of genesis for custom chains.
+use std::collections::BTreeMap;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use near_crypto::PublicKey;
+use near_primitives::account::{AccessKey, Account};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::state_record::StateRecord;
+use near_primitives::types::{AccountId, AccountInfo, Balance, NumSeats};
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
+use num_rational::Rational32;
+
+use crate::genesis_config::{Genesis, GenesisConfig, GenesisRecords, GenesisValidationMode};
+use crate::genesis_validate::check_genesis;
+
+/// Builds a `Genesis` from accounts, contracts and validators.
+///
+/// The output depends only on the inputs: the genesis time defaults to the
+/// Unix epoch and the records are ordered by account, so building the same
+/// chain twice yields genesis files with the same [`Genesis::json_hash`].
+/// The config defaults match the genesis generated by `neard init` and can
+/// be changed with [`Self::configure`].
+pub struct GenesisBuilder {
+    config: GenesisConfig,
+    num_block_producer_seats: Option<NumSeats>,
+    accounts: BTreeMap<AccountId, AccountSpec>,
+}
+
+#[derive(Default)]
+struct AccountSpec {
+    amount: Balance,
+    locked: Balance,
+    access_keys: BTreeMap<PublicKey, AccessKey>,
+    code: Option<Vec<u8>>,
+    data: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl GenesisBuilder {
+    pub fn new(chain_id: impl Into<String>) -> Self {
+        let config = GenesisConfig {
+            protocol_version: PROTOCOL_VERSION,
+            genesis_time: DateTime::<Utc>::from(UNIX_EPOCH),
+            chain_id: chain_id.into(),
+            genesis_height: 0,
+            protocol_upgrade_stake_threshold: Rational32::new(4, 5),
+            protocol_upgrade_num_epochs: 2,
+            epoch_length: 500,
+            gas_limit: 1_000_000_000_000_000,
+            min_gas_price: 100_000_000,
+            gas_price_adjustment_rate: Rational32::new(1, 100),
+            block_producer_kickout_threshold: 90,
+            chunk_producer_kickout_threshold: 90,
+            online_max_threshold: Rational32::new(99, 100),
+            online_min_threshold: Rational32::new(90, 100),
+            transaction_validity_period: 100,
+            protocol_reward_rate: Rational32::new(1, 10),
+            max_inflation_rate: Rational32::new(1, 20),
+            num_blocks_per_year: 365 * 24 * 60 * 60,
+            fishermen_threshold: 10 * 10u128.pow(24),
+            protocol_treasury_account: "near".parse().unwrap(),
+            shard_layout: ShardLayout::v0_single_shard(),
+            ..Default::default()
+        };
+        Self { config, num_block_producer_seats: None, accounts: BTreeMap::new() }
+    }
+
+    pub fn genesis_time(mut self, genesis_time: DateTime<Utc>) -> Self {
+        self.config.genesis_time = genesis_time;
+        self
+    }
+
+    pub fn protocol_version(mut self, protocol_version: ProtocolVersion) -> Self {
+        self.config.protocol_version = protocol_version;
+        self
+    }
+
+    pub fn epoch_length(mut self, epoch_length: u64) -> Self {
+        self.config.epoch_length = epoch_length;
+        self
+    }
+
+    pub fn shard_layout(mut self, shard_layout: ShardLayout) -> Self {
+        self.config.shard_layout = shard_layout;
+        self
+    }
+
+    /// Number of block producer seats.  Defaults to the number of validators.
+    pub fn num_block_producer_seats(mut self, num_seats: NumSeats) -> Self {
+        self.num_block_producer_seats = Some(num_seats);
+        self
+    }
+
+    /// Changes any other config fields, such as gas limit and prices.  The
+    /// seats per shard, validators and total supply are overwritten by
+    /// [`Self::build`].
+    pub fn configure(mut self, f: impl FnOnce(&mut GenesisConfig)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    /// Adds `amount` to the liquid balance of the account, creating it if
+    /// needed.
+    pub fn add_account(mut self, account_id: AccountId, amount: Balance) -> Self {
+        self.accounts.entry(account_id).or_default().amount += amount;
+        self
+    }
+
+    /// Adds a full access key to the account.
+    pub fn add_key(self, account_id: AccountId, public_key: PublicKey) -> Self {
+        self.add_access_key(account_id, public_key, AccessKey::full_access())
+    }
+
+    pub fn add_access_key(
+        mut self,
+        account_id: AccountId,
+        public_key: PublicKey,
+        access_key: AccessKey,
+    ) -> Self {
+        self.accounts.entry(account_id).or_default().access_keys.insert(public_key, access_key);
+        self
+    }
+
+    /// Deploys the contract to the account, replacing the previous one.
+    pub fn add_contract(mut self, account_id: AccountId, code: Vec<u8>) -> Self {
+        self.accounts.entry(account_id).or_default().code = Some(code);
+        self
+    }
+
+    /// Sets a key of the contract state of the account.
+    pub fn add_data(mut self, account_id: AccountId, key: Vec<u8>, value: Vec<u8>) -> Self {
+        self.accounts.entry(account_id).or_default().data.insert(key, value);
+        self
+    }
+
+    /// Adds a validator staking `stake` with `public_key`.  The stake is added
+    /// to the locked balance of the account and the key as its full access
+    /// key.
+    pub fn add_validator(
+        mut self,
+        account_id: AccountId,
+        public_key: PublicKey,
+        stake: Balance,
+    ) -> Self {
+        self.config.validators.push(AccountInfo {
+            account_id: account_id.clone(),
+            public_key: public_key.clone(),
+            amount: stake,
+        });
+        self.accounts.entry(account_id.clone()).or_default().locked += stake;
+        self.add_key(account_id, public_key)
+    }
+
+    /// Returns the genesis, failing if it's not valid.
+    pub fn build(self) -> anyhow::Result<Genesis> {
+        let mut config = self.config;
+        if !self.accounts.contains_key(&config.protocol_treasury_account) {
+            bail!(
+                "Protocol treasury account {} is not in the genesis",
+                config.protocol_treasury_account
+            );
+        }
+        let num_seats =
+            self.num_block_producer_seats.unwrap_or(config.validators.len() as NumSeats);
+        let num_shards = config.shard_layout.num_shards();
+        config.num_block_producer_seats = num_seats;
+        config.num_block_producer_seats_per_shard = vec![num_seats; num_shards as usize];
+        config.avg_hidden_validator_seats_per_shard = vec![0; num_shards as usize];
+
+        let mut records = vec![];
+        let mut total_supply: Balance = 0;
+        for (account_id, spec) in self.accounts {
+            let code_hash = spec.code.as_deref().map_or(CryptoHash::default(), hash);
+            total_supply = total_supply
+                .checked_add(spec.amount)
+                .and_then(|supply| supply.checked_add(spec.locked))
+                .ok_or_else(|| anyhow!("Total supply overflows"))?;
+            records.push(StateRecord::Account {
+                account_id: account_id.clone(),
+                account: Account::new(spec.amount, spec.locked, code_hash, 0),
+            });
+            for (public_key, access_key) in spec.access_keys {
+                records.push(StateRecord::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key,
+                    access_key,
+                });
+            }
+            if let Some(code) = spec.code {
+                records.push(StateRecord::Contract { account_id: account_id.clone(), code });
+            }
+            for (data_key, value) in spec.data {
+                records.push(StateRecord::Data { account_id: account_id.clone(), data_key, value });
+            }
+        }
+        config.total_supply = total_supply;
+
+        let genesis = Genesis::new_validated(
+            config,
+            GenesisRecords(records),
+            GenesisValidationMode::UnsafeFast,
+        );
+        let report = check_genesis(&genesis)?;
+        if !report.errors.is_empty() {
+            bail!("Invalid genesis: {}", report.errors.join("; "));
+        }
+        Ok(genesis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, SecretKey};
+
+    fn build() -> Genesis {
+        let key = SecretKey::from_seed(KeyType::ED25519, "test").public_key();
+        GenesisBuilder::new("test-chain")
+            .add_account("near".parse().unwrap(), 1000)
+            .add_key("near".parse().unwrap(), key.clone())
+            .add_validator("validator".parse().unwrap(), key.clone(), 100)
+            .add_account("validator".parse().unwrap(), 50)
+            .add_contract("contract".parse().unwrap(), vec![0, 97, 115, 109])
+            .add_account("contract".parse().unwrap(), 10)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_build() {
+        let mut genesis = build();
+        assert_eq!(genesis.config.total_supply, 1160);
+        assert_eq!(genesis.config.num_block_producer_seats, 1);
+        let records = &genesis.force_read_records().0;
+        // Accounts are ordered by account id.
+        assert!(
+            matches!(&records[0], StateRecord::Account { account_id, .. } if account_id.as_ref() == "contract")
+        );
+        assert_eq!(records.len(), 6);
+    }
+
+    #[test]
+    fn test_build_is_deterministic() {
+        assert_eq!(build().json_hash(), build().json_hash());
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let key = SecretKey::from_seed(KeyType::ED25519, "test").public_key();
+        let result = GenesisBuilder::new("test-chain")
+            .add_account("near".parse().unwrap(), 1000)
+            .add_key("near".parse().unwrap(), key)
+            .build();
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("no validators in genesis"), "{}", err);
+
+        let result =
+            GenesisBuilder::new("test-chain").add_account("alice".parse().unwrap(), 1).build();
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("Protocol treasury account near"), "{}", err);
+    }
+}
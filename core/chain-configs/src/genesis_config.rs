@@ -5,7 +5,7 @@
 //! out the better place.
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::{fmt, io};
 
@@ -14,6 +14,7 @@ use chrono::{DateTime, Utc};
 use near_primitives::views::RuntimeConfigView;
 use num_rational::Rational32;
 use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self as ser, SerializeSeq};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Serializer;
 use sha2::digest::Digest;
@@ -21,6 +22,7 @@ use smart_default::SmartDefault;
 use tracing::warn;
 
 use crate::genesis_validate::validate_genesis;
+use crate::shard_layout_upgrade::{resolve_shard_layout_upgrades, ShardLayoutUpgrade};
 use near_primitives::epoch_manager::{AllEpochConfig, EpochConfig};
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::types::validator_stake::ValidatorStake;
@@ -160,6 +162,9 @@ pub struct GenesisConfig {
     #[serde(default = "default_shard_layout")]
     #[default(ShardLayout::v0_single_shard())]
     pub shard_layout: ShardLayout,
+    /// Shard splits scheduled at later protocol versions, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shard_layout_upgrades: Vec<ShardLayoutUpgrade>,
     #[serde(default = "default_num_chunk_only_producer_seats")]
     #[default(300)]
     pub num_chunk_only_producer_seats: NumSeats,
@@ -188,6 +193,18 @@ impl GenesisConfig {
     pub fn use_production_config(&self) -> bool {
         self.use_production_config || self.chain_id == "testnet" || self.chain_id == "mainnet"
     }
+
+    /// Returns the shard layouts of `shard_layout_upgrades` paired with the
+    /// protocol versions they start at.
+    pub fn resolved_shard_layout_upgrades(
+        &self,
+    ) -> Result<Vec<(ProtocolVersion, ShardLayout)>, String> {
+        resolve_shard_layout_upgrades(
+            self.protocol_version,
+            &self.shard_layout,
+            &self.shard_layout_upgrades,
+        )
+    }
 }
 
 impl From<&GenesisConfig> for EpochConfig {
@@ -221,9 +238,11 @@ impl From<&GenesisConfig> for EpochConfig {
 impl From<&GenesisConfig> for AllEpochConfig {
     fn from(genesis_config: &GenesisConfig) -> Self {
         let initial_epoch_config = EpochConfig::from(genesis_config);
-        let epoch_config =
-            Self::new(genesis_config.use_production_config(), initial_epoch_config.clone());
-        epoch_config
+        let shard_layout_upgrades = genesis_config
+            .resolved_shard_layout_upgrades()
+            .unwrap_or_else(|err| panic!("Invalid shard layout upgrades: {}", err));
+        Self::new(genesis_config.use_production_config(), initial_epoch_config)
+            .with_shard_layout_upgrades(shard_layout_upgrades)
     }
 }
 
@@ -243,7 +262,7 @@ pub struct GenesisRecords(pub Vec<StateRecord>);
 /// `Genesis` has an invariant that `total_supply` is equal to the supply seen in the records.
 /// However, we can't enfore that invariant. All fields are public, but the clients are expected to
 /// use the provided methods for instantiation, serialization and deserialization.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Genesis {
     #[serde(flatten)]
     pub config: GenesisConfig,
@@ -254,6 +273,10 @@ pub struct Genesis {
     /// so they should be processed in streaming fashion with for_each_record.
     #[serde(skip)]
     records_file: PathBuf,
+    /// Whether `records_file` is the genesis file itself rather than a
+    /// separate records file referenced by the node config.
+    #[serde(skip)]
+    records_in_genesis_file: bool,
 }
 
 impl GenesisConfig {
@@ -434,6 +457,46 @@ impl GenesisJsonHasher {
     }
 }
 
+/// Serialized form of `Genesis`.  Records which aren't loaded into memory are
+/// streamed from the genesis file they were read from.  Records kept in a
+/// separate records file are left out, as that file is referenced by the node
+/// config and inlining them would make the node read the records twice.
+#[derive(Serialize)]
+struct SerializedGenesis<'a> {
+    #[serde(flatten)]
+    config: &'a GenesisConfig,
+    records: SerializedRecords<'a>,
+}
+
+struct SerializedRecords<'a>(&'a Genesis);
+
+impl Serialize for SerializedRecords<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        if self.0.records.as_ref().is_empty() && !self.0.records_in_genesis_file {
+            return seq.end();
+        }
+        let mut result = Ok(());
+        self.0
+            .try_for_each_record(|record| {
+                if result.is_ok() {
+                    result = seq.serialize_element(record);
+                }
+            })
+            .map_err(<S::Error as ser::Error>::custom)?;
+        result?;
+        seq.end()
+    }
+}
+
+impl Serialize for Genesis {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedGenesis { config: &self.config, records: SerializedRecords(self) }
+            .serialize(serializer)
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum GenesisValidationMode {
     Full,
     UnsafeFast,
@@ -449,13 +512,20 @@ impl Genesis {
     }
 
     /// Reads Genesis from a single file.
+    ///
+    /// Only the config is loaded into memory, the records are streamed from
+    /// the file whenever they are iterated over.  This keeps memory usage
+    /// bounded even for genesis files of several gigabytes.
     pub fn from_file<P: AsRef<Path>>(path: P, genesis_validation: GenesisValidationMode) -> Self {
-        let reader = BufReader::new(File::open(path).expect("Could not open genesis config file."));
-        let genesis: Genesis =
-            serde_json::from_reader(reader).expect("Failed to deserialize the genesis records.");
-        // As serde skips the `records_file` field, we can assume that `Genesis` has `records` and
-        // doesn't have `records_file`.
-        Self::new_validated(genesis.config, genesis.records, genesis_validation)
+        // Deserializing the config skips over the records without storing them.
+        let config = GenesisConfig::from_file(&path).unwrap();
+        let genesis = Self {
+            config,
+            records: GenesisRecords(vec![]),
+            records_file: path.as_ref().to_path_buf(),
+            records_in_genesis_file: true,
+        };
+        genesis.validate(genesis_validation)
     }
 
     /// Reads Genesis from config and records files.
@@ -472,12 +542,13 @@ impl Genesis {
         Self::new_with_path_validated(config, records_path, genesis_validation)
     }
 
-    fn new_validated(
+    pub(crate) fn new_validated(
         config: GenesisConfig,
         records: GenesisRecords,
         genesis_validation: GenesisValidationMode,
     ) -> Self {
-        let genesis = Self { config, records, records_file: PathBuf::new() };
+        let genesis =
+            Self { config, records, records_file: PathBuf::new(), records_in_genesis_file: false };
         genesis.validate(genesis_validation)
     }
 
@@ -490,6 +561,7 @@ impl Genesis {
             config,
             records: GenesisRecords(vec![]),
             records_file: records_file.as_ref().to_path_buf(),
+            records_in_genesis_file: false,
         };
         genesis.validate(genesis_validation)
     }
@@ -506,12 +578,22 @@ impl Genesis {
         self
     }
     /// Writes Genesis to the file.
+    ///
+    /// Records which aren't loaded into memory are streamed from the genesis
+    /// file they were read from, while records kept in a separate records file
+    /// are not written.  The genesis is written to a temporary file first, so
+    /// it may be written over the file it's read from.
     pub fn to_file<P: AsRef<Path>>(&self, path: P) {
-        std::fs::write(
-            path,
-            serde_json::to_vec_pretty(self).expect("Error serializing the genesis config."),
-        )
-        .expect("Failed to create / write a genesis config file.");
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let file =
+            File::create(&tmp_path).expect("Failed to create / write a genesis config file.");
+        let mut writer = BufWriter::new(file);
+        self.serialize(&mut Serializer::pretty(&mut writer))
+            .expect("Error serializing the genesis config.");
+        writer.flush().expect("Failed to create / write a genesis config file.");
+        std::fs::rename(&tmp_path, path).expect("Failed to create / write a genesis config file.");
     }
 
     /// Hash of the json-serialized input.
@@ -537,17 +619,23 @@ impl Genesis {
 
     /// If records vector is empty processes records stream from records_file.
     /// May panic if records_file is removed or is in wrong format.
-    pub fn for_each_record(&self, mut callback: impl FnMut(&StateRecord)) {
-        if self.records.as_ref().is_empty() {
+    pub fn for_each_record(&self, callback: impl FnMut(&StateRecord)) {
+        self.try_for_each_record(callback).expect("error while streaming records");
+    }
+
+    /// Like [`Self::for_each_record`] but returns an error if the records
+    /// can't be read from records_file.
+    pub fn try_for_each_record(&self, mut callback: impl FnMut(&StateRecord)) -> io::Result<()> {
+        if self.records.as_ref().is_empty() && !self.records_file.as_os_str().is_empty() {
             let callback_move = |record: StateRecord| {
                 callback(&record);
             };
             self.stream_records_with_callback(callback_move)
-                .expect("error while streaming records");
         } else {
             for record in self.records.as_ref() {
                 callback(record);
             }
+            Ok(())
         }
     }
 
@@ -560,8 +648,11 @@ impl Genesis {
     /// them.  Otherwise, reads them from `records_file`, stores them in memory
     /// and then returns mutable reference to them.
     pub fn force_read_records(&mut self) -> &mut GenesisRecords {
-        if self.records.as_ref().is_empty() {
-            self.records = GenesisRecords::from_file(&self.records_file);
+        if self.records.as_ref().is_empty() && !self.records_file.as_os_str().is_empty() {
+            let mut records = vec![];
+            self.stream_records_with_callback(|record| records.push(record))
+                .expect("error while streaming records");
+            self.records = GenesisRecords(records);
         }
         &mut self.records
     }
@@ -710,6 +801,9 @@ pub fn get_initial_supply(records: &[StateRecord]) -> Balance {
 #[cfg(test)]
 mod test {
     use crate::genesis_config::RecordsProcessor;
+    use crate::{Genesis, GenesisConfig, GenesisRecords, GenesisValidationMode};
+    use near_primitives::account::Account;
+    use near_primitives::hash::CryptoHash;
     use near_primitives::state_record::StateRecord;
     use serde::Deserializer;
 
@@ -859,4 +953,69 @@ mod test {
         }"#;
         stream_records_from_json_str(genesis).expect("error reading records from genesis");
     }
+
+    fn test_genesis() -> Genesis {
+        let records = ["alice.near", "bob.near"]
+            .iter()
+            .map(|account_id| StateRecord::Account {
+                account_id: account_id.parse().unwrap(),
+                account: Account::new(100, 0, CryptoHash::default(), 100),
+            })
+            .collect();
+        let config = GenesisConfig { chain_id: "test".to_string(), ..Default::default() };
+        Genesis::new_validated(config, GenesisRecords(records), GenesisValidationMode::UnsafeFast)
+    }
+
+    fn records_json(genesis: &Genesis) -> serde_json::Value {
+        let mut records = vec![];
+        genesis.for_each_record(|record| records.push(serde_json::to_value(record).unwrap()));
+        serde_json::Value::Array(records)
+    }
+
+    #[test]
+    fn test_genesis_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let genesis = test_genesis();
+        let path = dir.path().join("genesis.json");
+        genesis.to_file(&path);
+
+        // Records streamed from the genesis file are written back inline,
+        // including when writing over the file they are read from.
+        let read = Genesis::from_file(&path, GenesisValidationMode::UnsafeFast);
+        assert!(read.records_len().is_err());
+        read.to_file(&path);
+        let read = Genesis::from_file(&path, GenesisValidationMode::UnsafeFast);
+        assert_eq!(
+            serde_json::to_value(&read.config).unwrap(),
+            serde_json::to_value(&genesis.config).unwrap()
+        );
+        assert_eq!(records_json(&read), records_json(&genesis));
+    }
+
+    #[test]
+    fn test_genesis_with_records_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let genesis = test_genesis();
+        let config_path = dir.path().join("genesis.json");
+        let records_path = dir.path().join("records.json");
+        genesis.config.to_file(&config_path);
+        GenesisRecords(genesis.records.0.clone()).to_file(&records_path);
+
+        // Records kept in a separate records file stay there and aren't
+        // duplicated into the genesis file.
+        let read =
+            Genesis::from_files(&config_path, &records_path, GenesisValidationMode::UnsafeFast);
+        read.to_file(&config_path);
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&config_path).unwrap()).unwrap();
+        assert_eq!(written["records"], serde_json::json!([]));
+
+        let read =
+            Genesis::from_files(&config_path, &records_path, GenesisValidationMode::UnsafeFast);
+        assert_eq!(
+            serde_json::to_value(&read.config).unwrap(),
+            serde_json::to_value(&genesis.config).unwrap()
+        );
+        assert_eq!(records_json(&read), records_json(&genesis));
+    }
 }
@@ -1,23 +1,60 @@
 use std::collections::{HashMap, HashSet};
+use std::io;
 
 use crate::genesis_config::{Genesis, GenesisConfig};
 use near_crypto::key_conversion::is_valid_staking_key;
 use near_primitives::state_record::StateRecord;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Balance};
 use num_rational::Rational32;
 
 /// Validate genesis config and records. Panics if genesis is ill-formed.
 pub fn validate_genesis(genesis: &Genesis) {
+    let report = check_genesis(genesis).expect("error while streaming records");
+    if !report.errors.is_empty() {
+        panic!("{}", report.errors.join("\n"));
+    }
+}
+
+/// Validates genesis config and records, collecting all the problems found
+/// rather than stopping at the first one.  Fails only if the records can't be
+/// read.
+pub fn check_genesis(genesis: &Genesis) -> io::Result<GenesisReport> {
     let mut genesis_validator = GenesisValidator::new(&genesis.config);
-    genesis.for_each_record(|record: &StateRecord| {
+    genesis.try_for_each_record(|record: &StateRecord| {
         genesis_validator.process_record(record);
-    });
-    genesis_validator.validate();
+    })?;
+    Ok(genesis_validator.validate())
+}
+
+/// Result of [`check_genesis`].
+#[derive(Debug, Default)]
+pub struct GenesisReport {
+    pub summary: GenesisSummary,
+    /// Descriptions of the problems found, empty if the genesis is valid.
+    pub errors: Vec<String>,
+}
+
+/// Statistics of the genesis records.
+#[derive(Debug, Default)]
+pub struct GenesisSummary {
+    pub num_records: u64,
+    pub num_accounts: u64,
+    pub num_access_keys: u64,
+    pub num_contracts: u64,
+    pub num_data_records: u64,
+    pub num_other_records: u64,
+    /// Sum of the amounts and locked amounts of the accounts.
+    pub total_balance: Balance,
+    /// Sum of the locked amounts of the accounts.
+    pub total_locked: Balance,
+    /// Sum of the sizes of the contracts, in bytes.
+    pub total_contract_size: u64,
 }
 
 struct GenesisValidator<'a> {
     genesis_config: &'a GenesisConfig,
-    total_supply: u128,
+    summary: GenesisSummary,
+    errors: Vec<String>,
     staked_accounts: HashMap<AccountId, u128>,
     account_ids: HashSet<AccountId>,
     access_key_account_ids: HashSet<AccountId>,
@@ -28,7 +65,8 @@ impl<'a> GenesisValidator<'a> {
     pub fn new(genesis_config: &'a GenesisConfig) -> Self {
         Self {
             genesis_config,
-            total_supply: 0,
+            summary: GenesisSummary::default(),
+            errors: vec![],
             staked_accounts: HashMap::new(),
             account_ids: HashSet::new(),
             access_key_account_ids: HashSet::new(),
@@ -37,98 +75,116 @@ impl<'a> GenesisValidator<'a> {
     }
 
     pub fn process_record(&mut self, record: &StateRecord) {
+        self.summary.num_records += 1;
         match record {
             StateRecord::Account { account_id, account } => {
-                if self.account_ids.contains(account_id) {
-                    panic!("Duplicate account id {} in genesis records", account_id);
+                self.summary.num_accounts += 1;
+                if !self.account_ids.insert(account_id.clone()) {
+                    self.errors
+                        .push(format!("Duplicate account id {} in genesis records", account_id));
                 }
-                self.total_supply += account.locked() + account.amount();
-                self.account_ids.insert(account_id.clone());
+                self.summary.total_balance += account.locked() + account.amount();
+                self.summary.total_locked += account.locked();
                 if account.locked() > 0 {
                     self.staked_accounts.insert(account_id.clone(), account.locked());
                 }
             }
             StateRecord::AccessKey { account_id, .. } => {
+                self.summary.num_access_keys += 1;
                 self.access_key_account_ids.insert(account_id.clone());
             }
-            StateRecord::Contract { account_id, .. } => {
-                if self.contract_account_ids.contains(account_id) {
-                    panic!("account {} has more than one contract deployed", account_id);
+            StateRecord::Contract { account_id, code } => {
+                self.summary.num_contracts += 1;
+                self.summary.total_contract_size += code.len() as u64;
+                if !self.contract_account_ids.insert(account_id.clone()) {
+                    self.errors.push(format!(
+                        "account {} has more than one contract deployed",
+                        account_id
+                    ));
                 }
-                self.contract_account_ids.insert(account_id.clone());
             }
-            _ => {}
+            StateRecord::Data { .. } => self.summary.num_data_records += 1,
+            _ => self.summary.num_other_records += 1,
         }
     }
 
-    pub fn validate(&self) {
-        let validators = self
-            .genesis_config
-            .validators
-            .clone()
-            .into_iter()
-            .map(|account_info| {
-                assert!(
-                    is_valid_staking_key(&account_info.public_key),
-                    "validator staking key is not valid"
-                );
-                (account_info.account_id, account_info.amount)
-            })
-            .collect::<HashMap<_, _>>();
-        assert_eq!(
-            validators.len(),
-            self.genesis_config.validators.len(),
-            "Duplicate account in validators"
+    pub fn validate(mut self) -> GenesisReport {
+        let config = self.genesis_config;
+        let mut validators = HashMap::new();
+        for account_info in &config.validators {
+            if !is_valid_staking_key(&account_info.public_key) {
+                self.errors.push(format!(
+                    "validator staking key is not valid: {} of {}",
+                    account_info.public_key, account_info.account_id
+                ));
+            }
+            validators.insert(account_info.account_id.clone(), account_info.amount);
+        }
+        let mut check = |condition: bool, error: String| {
+            if !condition {
+                self.errors.push(error);
+            }
+        };
+        check(
+            validators.len() == config.validators.len(),
+            "Duplicate account in validators".into(),
         );
-        assert!(!validators.is_empty(), "no validators in genesis");
-
-        assert_eq!(self.total_supply, self.genesis_config.total_supply, "wrong total supply");
-        assert_eq!(
-            validators, self.staked_accounts,
-            "validator accounts do not match staked accounts"
+        check(!validators.is_empty(), "no validators in genesis".into());
+        check(
+            self.summary.total_balance == config.total_supply,
+            format!(
+                "wrong total supply: {} in records, {} in config",
+                self.summary.total_balance, config.total_supply
+            ),
+        );
+        check(
+            validators == self.staked_accounts,
+            "validator accounts do not match staked accounts".into(),
         );
         for account_id in &self.access_key_account_ids {
-            assert!(
+            check(
                 self.account_ids.contains(account_id),
-                "access key account {} does not exist",
-                account_id
+                format!("access key account {} does not exist", account_id),
             );
         }
         for account_id in &self.contract_account_ids {
-            assert!(
+            check(
                 self.account_ids.contains(account_id),
-                "contract account {} does not exist",
-                account_id
+                format!("contract account {} does not exist", account_id),
             );
         }
-        assert!(
-            self.genesis_config.online_max_threshold > self.genesis_config.online_min_threshold,
-            "Online max threshold smaller than min threshold"
+        check(
+            config.online_max_threshold > config.online_min_threshold,
+            "Online max threshold smaller than min threshold".into(),
         );
-        assert!(
-            self.genesis_config.online_max_threshold <= Rational32::from_integer(1),
-            "Online max threshold must be less or equal than 1"
+        check(
+            config.online_max_threshold <= Rational32::from_integer(1),
+            "Online max threshold must be less or equal than 1".into(),
         );
-        assert!(
-            *self.genesis_config.online_max_threshold.numer() < 10_000_000,
-            "Numerator is too large, may lead to overflow."
+        check(
+            *config.online_max_threshold.numer() < 10_000_000,
+            "Numerator is too large, may lead to overflow.".into(),
         );
-        assert!(
-            *self.genesis_config.online_min_threshold.numer() < 10_000_000,
-            "Numerator is too large, may lead to overflow."
+        check(
+            *config.online_min_threshold.numer() < 10_000_000,
+            "Numerator is too large, may lead to overflow.".into(),
         );
-        assert!(
-            *self.genesis_config.online_max_threshold.denom() < 10_000_000,
-            "Denominator is too large, may lead to overflow."
+        check(
+            *config.online_max_threshold.denom() < 10_000_000,
+            "Denominator is too large, may lead to overflow.".into(),
         );
-        assert!(
-            *self.genesis_config.online_min_threshold.denom() < 10_000_000,
-            "Denominator is too large, may lead to overflow."
+        check(
+            *config.online_min_threshold.denom() < 10_000_000,
+            "Denominator is too large, may lead to overflow.".into(),
         );
-        assert!(
-            self.genesis_config.gas_price_adjustment_rate < Rational32::from_integer(1),
-            "Gas price adjustment rate must be less than 1"
+        check(
+            config.gas_price_adjustment_rate < Rational32::from_integer(1),
+            "Gas price adjustment rate must be less than 1".into(),
         );
+        if let Err(err) = config.resolved_shard_layout_upgrades() {
+            check(false, err);
+        }
+        GenesisReport { summary: self.summary, errors: self.errors }
     }
 }
 
@@ -248,4 +304,34 @@ mod test {
         ]);
         validate_genesis(&Genesis::new(config, records));
     }
+
+    #[test]
+    fn test_report_collects_all_errors() {
+        let mut config = GenesisConfig::default();
+        config.validators = vec![AccountInfo {
+            account_id: "test".parse().unwrap(),
+            public_key: VALID_ED25519_RISTRETTO_KEY.parse().unwrap(),
+            amount: 10,
+        }];
+        config.total_supply = 110;
+        let records = vec![
+            StateRecord::Account { account_id: "test".parse().unwrap(), account: create_account() },
+            StateRecord::Account { account_id: "test".parse().unwrap(), account: create_account() },
+            StateRecord::Contract { account_id: "test1".parse().unwrap(), code: vec![1, 2, 3] },
+        ];
+        let mut validator = GenesisValidator::new(&config);
+        for record in &records {
+            validator.process_record(record);
+        }
+        let report = validator.validate();
+        assert_eq!(report.summary.num_records, 3);
+        assert_eq!(report.summary.num_accounts, 2);
+        assert_eq!(report.summary.num_contracts, 1);
+        assert_eq!(report.summary.total_balance, 220);
+        assert_eq!(report.summary.total_contract_size, 3);
+        assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+        assert!(report.errors[0].starts_with("Duplicate account id test"));
+        assert!(report.errors[1].starts_with("wrong total supply"));
+        assert!(report.errors[2].starts_with("contract account test1 does not exist"));
+    }
 }
@@ -1,15 +1,20 @@
 mod client_config;
+mod genesis_builder;
 mod genesis_config;
 pub mod genesis_validate;
 mod metrics;
+mod shard_layout_upgrade;
 mod updateable_config;
 
 pub use client_config::{
-    ClientConfig, GCConfig, LogSummaryStyle, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
+    AlertsConfig, ArchivalPolicy, ClientConfig, EmptyBlocksPolicy, EpochSnapshotConfig, GCConfig,
+    LogSummaryStyle, ProtocolVersionRange, Retention, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
     MIN_GC_NUM_EPOCHS_TO_KEEP, TEST_STATE_SYNC_TIMEOUT,
 };
+pub use genesis_builder::GenesisBuilder;
 pub use genesis_config::{
     get_initial_supply, stream_records_from_file, Genesis, GenesisChangeConfig, GenesisConfig,
     GenesisRecords, GenesisValidationMode, ProtocolConfig, ProtocolConfigView,
 };
+pub use shard_layout_upgrade::ShardLayoutUpgrade;
 pub use updateable_config::{MutableConfigValue, UpdateableClientConfig};
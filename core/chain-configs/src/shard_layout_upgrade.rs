@@ -0,0 +1,172 @@
+//! Changes of the shard layout scheduled in the genesis config.
+use near_primitives::shard_layout::{ShardLayout, ShardVersion};
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::version::ProtocolVersion;
+use serde::{Deserialize, Serialize};
+
+/// Splits shards of the previous layout starting with the epoch that runs
+/// `protocol_version`.
+///
+/// The new layout keeps the fixed shards and the boundary accounts of the
+/// previous one, so every new shard lies within a single parent shard, whose
+/// state is split when the node reaches the last epoch of the old layout.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ShardLayoutUpgrade {
+    /// First protocol version using the new layout.
+    pub protocol_version: ProtocolVersion,
+    /// Boundary accounts of the new layout, sorted.  Must include the
+    /// boundary accounts of the previous layout.
+    pub boundary_accounts: Vec<AccountId>,
+    /// Version of the new layout, greater than the previous one.  It's part of
+    /// the ids of the shards in storage.
+    pub version: ShardVersion,
+}
+
+impl ShardLayoutUpgrade {
+    /// Returns the layout this upgrade switches to from `prev`.
+    pub fn shard_layout(&self, prev: &ShardLayout) -> Result<ShardLayout, String> {
+        if self.version <= prev.version() {
+            return Err(format!(
+                "shard layout version {} is not greater than the previous version {}",
+                self.version,
+                prev.version()
+            ));
+        }
+        if let ShardLayout::V0(_) = prev {
+            if prev.num_shards() != 1 {
+                return Err(format!(
+                    "a V0 shard layout with {} shards can't be split",
+                    prev.num_shards()
+                ));
+            }
+        }
+        if !self.boundary_accounts.windows(2).all(|w| w[0] < w[1]) {
+            return Err("boundary accounts are not sorted or contain duplicates".to_string());
+        }
+        let prev_boundaries = prev.boundary_accounts();
+        if let Some(missing) =
+            prev_boundaries.iter().find(|account| !self.boundary_accounts.contains(account))
+        {
+            return Err(format!("boundary account {} of the previous layout is missing", missing));
+        }
+        if self.boundary_accounts.len() == prev_boundaries.len() {
+            return Err("no shard is split".to_string());
+        }
+
+        let fixed_shards = prev.fixed_shards().to_vec();
+        let num_fixed = fixed_shards.len() as ShardId;
+        let mut split_map: Vec<Vec<ShardId>> = (0..prev.num_shards()).map(|_| Vec::new()).collect();
+        for shard_id in 0..num_fixed {
+            split_map[shard_id as usize].push(shard_id);
+        }
+        // The range of shard `num_fixed + i` starts at boundary `i - 1` and
+        // belongs to the parent range containing that boundary.
+        for i in 0..=self.boundary_accounts.len() {
+            let parent_range = match i {
+                0 => 0,
+                _ => {
+                    let start = &self.boundary_accounts[i - 1];
+                    prev_boundaries.iter().filter(|boundary| *boundary <= start).count()
+                }
+            };
+            split_map[num_fixed as usize + parent_range].push(num_fixed + i as ShardId);
+        }
+        Ok(ShardLayout::v1(
+            fixed_shards,
+            self.boundary_accounts.clone(),
+            Some(split_map),
+            self.version,
+        ))
+    }
+}
+
+/// Returns the layouts switched to by `upgrades`, paired with the protocol
+/// versions they start at.
+pub(crate) fn resolve_shard_layout_upgrades(
+    genesis_protocol_version: ProtocolVersion,
+    genesis_shard_layout: &ShardLayout,
+    upgrades: &[ShardLayoutUpgrade],
+) -> Result<Vec<(ProtocolVersion, ShardLayout)>, String> {
+    let mut prev_protocol_version = genesis_protocol_version;
+    let mut prev_layout = genesis_shard_layout.clone();
+    let mut layouts = Vec::with_capacity(upgrades.len());
+    for upgrade in upgrades {
+        if upgrade.protocol_version <= prev_protocol_version {
+            return Err(format!(
+                "shard layout upgrade at protocol version {} is not after protocol version {}",
+                upgrade.protocol_version, prev_protocol_version
+            ));
+        }
+        let layout = upgrade.shard_layout(&prev_layout).map_err(|err| {
+            format!(
+                "invalid shard layout upgrade at protocol version {}: {}",
+                upgrade.protocol_version, err
+            )
+        })?;
+        layouts.push((upgrade.protocol_version, layout.clone()));
+        prev_protocol_version = upgrade.protocol_version;
+        prev_layout = layout;
+    }
+    Ok(layouts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::shard_layout::account_id_to_shard_id;
+
+    fn accounts(names: &[&str]) -> Vec<AccountId> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    fn upgrade(protocol_version: ProtocolVersion, boundaries: &[&str]) -> ShardLayoutUpgrade {
+        ShardLayoutUpgrade {
+            protocol_version,
+            boundary_accounts: accounts(boundaries),
+            version: protocol_version as ShardVersion,
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let layouts = resolve_shard_layout_upgrades(
+            10,
+            &ShardLayout::v0_single_shard(),
+            &[upgrade(11, &["mm"]), upgrade(12, &["cc", "mm", "xx"])],
+        )
+        .unwrap();
+        assert_eq!(layouts.len(), 2);
+        assert_eq!(layouts[0].1.num_shards(), 2);
+        let layout = &layouts[1].1;
+        assert_eq!(layout.num_shards(), 4);
+        assert_eq!(layout.get_split_shard_ids(0), Some(vec![0, 1]));
+        assert_eq!(layout.get_split_shard_ids(1), Some(vec![2, 3]));
+        for (account, parent) in [("aa", 0), ("dd", 0), ("nn", 1), ("yy", 1)] {
+            let shard_id = account_id_to_shard_id(&account.parse().unwrap(), layout);
+            assert_eq!(layout.get_parent_shard_id(shard_id).unwrap(), parent, "{}", account);
+            let parent_id = account_id_to_shard_id(&account.parse().unwrap(), &layouts[0].1);
+            assert_eq!(parent_id, parent);
+        }
+    }
+
+    #[test]
+    fn test_resolve_invalid() {
+        let genesis = ShardLayout::v0_single_shard();
+        let check = |upgrades: &[ShardLayoutUpgrade], expected: &str| {
+            let err = resolve_shard_layout_upgrades(10, &genesis, upgrades).unwrap_err();
+            assert!(err.contains(expected), "{}", err);
+        };
+        check(&[upgrade(10, &["mm"])], "is not after protocol version 10");
+        check(&[upgrade(11, &["mm"]), upgrade(12, &["cc"])], "boundary account mm");
+        check(&[upgrade(11, &["mm", "cc"])], "not sorted");
+        check(&[upgrade(11, &["mm"]), upgrade(12, &["mm"])], "no shard is split");
+        let mut same_version = upgrade(12, &["cc", "mm"]);
+        same_version.version = 11;
+        check(&[upgrade(11, &["mm"]), same_version], "not greater than");
+        let err =
+            resolve_shard_layout_upgrades(10, &ShardLayout::v0(2, 0), &[upgrade(11, &["mm"])])
+                .unwrap_err();
+        assert!(err.contains("can't be split"), "{}", err);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::metrics;
+use crate::EmptyBlocksPolicy;
 use chrono::{DateTime, Utc};
 use near_primitives::time::Clock;
 use near_primitives::types::BlockHeight;
@@ -73,4 +74,6 @@ impl<T: Copy + PartialEq + Debug> MutableConfigValue<T> {
 pub struct UpdateableClientConfig {
     /// Graceful shutdown at expected block height.
     pub expected_shutdown: Option<BlockHeight>,
+    /// Whether to produce blocks without new chunks.
+    pub produce_empty_blocks: EmptyBlocksPolicy,
 }
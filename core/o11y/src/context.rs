@@ -8,11 +8,14 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 pub struct WithSpanContext<T: actix::Message> {
     pub msg: T,
     pub context: opentelemetry::Context,
+    /// When the message was created, to measure how long it waited in the
+    /// mailbox of the actor.
+    pub created: std::time::Instant,
 }
 
 impl<T: actix::Message> WithSpanContext<T> {
     pub fn new(msg: T) -> Self {
-        Self { msg, context: Span::current().context() }
+        Self { msg, context: Span::current().context(), created: std::time::Instant::now() }
     }
 }
 
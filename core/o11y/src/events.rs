@@ -0,0 +1,35 @@
+//! Node health events.
+//!
+//! The events are logged at `INFO` level (`WARN` for `peer_banned`) under the
+//! [`TARGET`] target, with an `event` field naming the event and the fields
+//! listed below.  Their names are stable, so that log pipelines can rely on
+//! them.  The target is not enabled by default; add `events=info` to the log
+//! filter (e.g. `RUST_LOG`) and run the node with `--log-json-targets events`
+//! to get the events as JSON without changing the format of the rest of the
+//! log.
+//!
+//! * `block_produced` — the node produced a block.
+//!   * `height` — height of the block,
+//!   * `hash` — hash of the block,
+//!   * `num_chunks` — number of new chunks included in the block.
+//! * `chunk_missing` — a block can't be processed until the node gets one of
+//!   its chunks, which is being requested from peers.
+//!   * `shard_id` — shard of the chunk,
+//!   * `chunk_hash` — hash of the chunk,
+//!   * `height_created` — height at which the chunk was produced,
+//!   * `prev_block_hash` — hash of the block the chunk was produced on top of.
+//! * `peer_banned` — the node banned a peer.
+//!   * `peer_id` — id of the peer,
+//!   * `reason` — reason for the ban.
+//! * `sync_phase_changed` — the node moved to another phase of syncing.
+//!   * `from` — previous phase,
+//!   * `to` — new phase, one of `AwaitingPeers`, `NoSync`, `EpochSync`,
+//!     `HeaderSync`, `StateSync`, `StateSyncDone` and `BodySync`.
+
+/// Target of the node health events.
+pub const TARGET: &str = "events";
+
+pub const BLOCK_PRODUCED: &str = "block_produced";
+pub const CHUNK_MISSING: &str = "chunk_missing";
+pub const PEER_BANNED: &str = "peer_banned";
+pub const SYNC_PHASE_CHANGED: &str = "sync_phase_changed";
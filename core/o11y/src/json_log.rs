@@ -0,0 +1,145 @@
+//! JSON formatting of log events.
+//!
+//! Every event selected for JSON output is written as a single line holding
+//! a JSON object with the following fields:
+//!
+//! * `timestamp_ms` — milliseconds since the Unix epoch,
+//! * `level` — `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`,
+//! * `target` — target of the event,
+//! * `spans` — names of the spans the event was emitted in, outermost first,
+//! * all the fields of the event, with the message under `message`.
+//!
+//! Node health events emitted under the [`crate::events::TARGET`] target
+//! additionally carry an `event` field naming the event; see
+//! [`crate::events`] for their schemas.
+use serde_json::{Map, Value};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{self, FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Format of the log output.
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable text.
+    Plain,
+    /// One JSON object per line.
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Plain
+    }
+}
+
+/// Event formatter writing events either in the default human-readable
+/// format or as JSON, depending on their target.
+pub struct EventFormat {
+    plain: format::Format,
+    /// Targets whose events are written as JSON.  `None` means all of them.
+    json_targets: Option<Vec<String>>,
+}
+
+impl EventFormat {
+    /// Formats events as JSON if `format` is `Json` or if their target is one
+    /// of `json_targets` or a module within one of them.
+    pub fn new(format: LogFormat, json_targets: Vec<String>) -> Self {
+        let json_targets = match format {
+            LogFormat::Plain => Some(json_targets),
+            LogFormat::Json => None,
+        };
+        Self { plain: format::Format::default(), json_targets }
+    }
+
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.plain = self.plain.with_ansi(ansi);
+        self
+    }
+
+    fn is_json(&self, target: &str) -> bool {
+        match &self.json_targets {
+            None => true,
+            Some(targets) => targets.iter().any(|json_target| {
+                target
+                    .strip_prefix(json_target.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+            }),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for EventFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        if !self.is_json(metadata.target()) {
+            return self.plain.format_event(ctx, writer, event);
+        }
+
+        let mut object = Map::new();
+        let timestamp_ms =
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis() as u64);
+        object.insert("timestamp_ms".to_string(), timestamp_ms.into());
+        object.insert("level".to_string(), metadata.level().as_str().into());
+        object.insert("target".to_string(), metadata.target().into());
+        let spans = ctx
+            .event_scope()
+            .map(|scope| scope.from_root().map(|span| Value::from(span.name())).collect())
+            .unwrap_or_default();
+        object.insert("spans".to_string(), Value::Array(spans));
+        event.record(&mut JsonVisitor(&mut object));
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_json() {
+        let format = EventFormat::new(LogFormat::Plain, vec!["events".to_string()]);
+        assert!(format.is_json("events"));
+        assert!(format.is_json("events::client"));
+        assert!(!format.is_json("eventsx"));
+        assert!(!format.is_json("client"));
+        assert!(!EventFormat::new(LogFormat::Plain, vec![]).is_json("events"));
+        assert!(EventFormat::new(LogFormat::Json, vec![]).is_json("client"));
+    }
+}
@@ -5,12 +5,14 @@ pub use {tracing, tracing_appender, tracing_subscriber};
 
 use clap::Parser;
 pub use context::*;
+use json_log::{EventFormat, LogFormat};
 use near_crypto::PublicKey;
 use near_primitives_core::types::AccountId;
 use once_cell::sync::OnceCell;
 use opentelemetry::sdk::trace::{self, IdGenerator, Sampler, Tracer};
 use opentelemetry::sdk::Resource;
 use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_semantic_conventions::resource::SERVICE_NAME;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -27,7 +29,9 @@ use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
 
 /// Custom tracing subscriber implementation that produces IO traces.
 pub mod context;
+pub mod events;
 mod io_tracer;
+pub mod json_log;
 pub mod log_config;
 pub mod macros;
 pub mod metrics;
@@ -56,7 +60,7 @@ static OTLP_LAYER_RELOAD_HANDLE: OnceCell<reload::Handle<LevelFilter, LogLayer<R
 
 type LogLayer<Inner> = Layered<
     Filtered<
-        fmt::Layer<Inner, fmt::format::DefaultFields, fmt::format::Format, NonBlocking>,
+        fmt::Layer<Inner, fmt::format::DefaultFields, EventFormat, NonBlocking>,
         reload::Layer<EnvFilter, Inner>,
         Inner,
     >,
@@ -64,11 +68,7 @@ type LogLayer<Inner> = Layered<
 >;
 
 type SimpleLogLayer<Inner, W> = Layered<
-    Filtered<
-        fmt::Layer<Inner, fmt::format::DefaultFields, fmt::format::Format, W>,
-        EnvFilter,
-        Inner,
-    >,
+    Filtered<fmt::Layer<Inner, fmt::format::DefaultFields, EventFormat, W>, EnvFilter, Inner>,
     Inner,
 >;
 
@@ -136,10 +136,26 @@ pub struct Options {
     #[clap(long, arg_enum, default_value = "off")]
     opentelemetry: OpenTelemetryLevel,
 
+    /// Address of the OTLP collector (e.g. Jaeger or Tempo) to export span data
+    /// to, such as `http://localhost:4317`.  Defaults to the standard OTLP gRPC
+    /// endpoint on localhost.
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+
     /// Whether the log needs to be colored.
     #[clap(long, arg_enum, default_value = "auto")]
     color: ColorOutput,
 
+    /// Format of the log output.  The JSON format writes one object per event,
+    /// see `near_o11y::json_log` for its fields.
+    #[clap(long, arg_enum, default_value = "plain")]
+    log_format: LogFormat,
+
+    /// Comma-separated list of targets whose events are written as JSON even
+    /// if the plain log format is used, e.g. `events` for node health events.
+    #[clap(long, use_value_delimiter = true)]
+    log_json_targets: Vec<String>,
+
     /// Enable logging of spans. For instance, this prints timestamps of entering and exiting a span,
     /// together with the span duration and used/idle CPU time.
     #[clap(long)]
@@ -202,13 +218,18 @@ fn add_simple_log_layer<S, W>(
     filter: EnvFilter,
     writer: W,
     ansi: bool,
+    event_format: EventFormat,
     subscriber: S,
 ) -> SimpleLogLayer<S, W>
 where
     S: tracing::Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
     W: for<'writer> fmt::MakeWriter<'writer> + 'static,
 {
-    let layer = fmt::layer().with_ansi(ansi).with_writer(writer).with_filter(filter);
+    let layer = fmt::layer()
+        .event_format(event_format.with_ansi(ansi))
+        .with_ansi(ansi)
+        .with_writer(writer)
+        .with_filter(filter);
 
     subscriber.with(layer)
 }
@@ -226,6 +247,7 @@ fn add_non_blocking_log_layer<S>(
     writer: NonBlocking,
     ansi: bool,
     with_span_events: bool,
+    event_format: EventFormat,
     subscriber: S,
 ) -> (LogLayer<S>, reload::Handle<EnvFilter, S>)
 where
@@ -234,8 +256,9 @@ where
     let (filter, handle) = reload::Layer::<EnvFilter, S>::new(filter);
 
     let layer = fmt::layer()
-        .with_ansi(ansi)
         .with_span_events(get_fmt_span(with_span_events))
+        .event_format(event_format.with_ansi(ansi))
+        .with_ansi(ansi)
         .with_writer(writer)
         .with_filter(filter);
 
@@ -248,6 +271,7 @@ where
 // register timers and channels and whatnot.
 async fn add_opentelemetry_layer<S>(
     opentelemetry_level: OpenTelemetryLevel,
+    otlp_endpoint: Option<String>,
     chain_id: String,
     node_public_key: PublicKey,
     account_id: Option<AccountId>,
@@ -273,9 +297,13 @@ where
     };
     resource.push(KeyValue::new(SERVICE_NAME, service_name));
 
+    let mut exporter = opentelemetry_otlp::new_exporter().tonic();
+    if let Some(endpoint) = otlp_endpoint {
+        exporter = exporter.with_endpoint(endpoint);
+    }
     let tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
-        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .with_exporter(exporter)
         .with_trace_config(
             trace::config()
                 .with_sampler(Sampler::AlwaysOn)
@@ -325,6 +353,10 @@ fn use_color_output(options: &Options) -> bool {
     }
 }
 
+fn event_format(options: &Options) -> EventFormat {
+    EventFormat::new(options.log_format, options.log_json_targets.clone())
+}
+
 fn use_color_auto() -> bool {
     std::env::var_os("NO_COLOR").is_none() && is_terminal()
 }
@@ -351,7 +383,13 @@ pub fn default_subscriber(
     };
 
     let subscriber = tracing_subscriber::registry();
-    let subscriber = add_simple_log_layer(env_filter, make_writer, color_output, subscriber);
+    let subscriber = add_simple_log_layer(
+        env_filter,
+        make_writer,
+        color_output,
+        event_format(options),
+        subscriber,
+    );
 
     DefaultSubscriberGuard {
         subscriber: Some(subscriber),
@@ -394,6 +432,7 @@ pub async fn default_subscriber_with_opentelemetry(
         writer,
         color_output,
         options.log_span_events,
+        event_format(options),
         subscriber,
     );
     LOG_LAYER_RELOAD_HANDLE
@@ -402,6 +441,7 @@ pub async fn default_subscriber_with_opentelemetry(
 
     let (subscriber, handle) = add_opentelemetry_layer(
         options.opentelemetry,
+        options.otlp_endpoint.clone(),
         chain_id,
         node_public_key,
         account_id,
@@ -59,8 +59,7 @@ enum ClockInner {
 ///
 /// This is a reimplementation of primitives/src/time.rs
 /// with a more systematic approach.
-/// TODO: add tests, put it is some reusable package and use
-/// throughout the nearcore codebase.
+/// TODO: add tests and use throughout the nearcore codebase.
 #[derive(Clone)]
 pub struct Clock(ClockInner);
 
@@ -86,6 +85,18 @@ impl Clock {
         }
     }
 
+    /// Same as [`Self::now`] but as `std::time::Instant`, for the code which
+    /// still uses the types of `near_primitives::time`.
+    pub fn now_std(&self) -> std::time::Instant {
+        self.now().into_inner()
+    }
+    /// Same as [`Self::now_utc`] but as `chrono::DateTime`, for the code which
+    /// still uses the types of `near_primitives::time`.
+    pub fn now_utc_chrono(&self) -> chrono::DateTime<chrono::Utc> {
+        use chrono::TimeZone;
+        chrono::Utc.timestamp_nanos(self.now_utc().unix_timestamp_nanos() as i64)
+    }
+
     /// Cancellable.
     pub async fn sleep_until(&self, t: Instant) {
         match &self.0 {
@@ -87,11 +87,24 @@ pub struct AllEpochConfig {
     use_production_config: bool,
     /// EpochConfig from genesis
     genesis_epoch_config: EpochConfig,
+    /// Shard layouts switched to at the given protocol versions, in order
+    shard_layout_upgrades: Vec<(ProtocolVersion, ShardLayout)>,
 }
 
 impl AllEpochConfig {
     pub fn new(use_production_config: bool, genesis_epoch_config: EpochConfig) -> Self {
-        Self { use_production_config, genesis_epoch_config }
+        Self { use_production_config, genesis_epoch_config, shard_layout_upgrades: vec![] }
+    }
+
+    /// Switches to each of the shard layouts starting with the epoch that
+    /// runs the protocol version paired with it.  The layouts must have been
+    /// built on top of each other, starting from the genesis layout.
+    pub fn with_shard_layout_upgrades(
+        mut self,
+        shard_layout_upgrades: Vec<(ProtocolVersion, ShardLayout)>,
+    ) -> Self {
+        self.shard_layout_upgrades = shard_layout_upgrades;
+        self
     }
 
     pub fn for_protocol_version(&self, protocol_version: ProtocolVersion) -> EpochConfig {
@@ -126,6 +139,18 @@ impl AllEpochConfig {
                 config.validator_max_kickout_stake_perc = 30;
             }
         }
+        if let Some((_, shard_layout)) = self
+            .shard_layout_upgrades
+            .iter()
+            .rev()
+            .find(|(upgrade_version, _)| *upgrade_version <= protocol_version)
+        {
+            config.shard_layout = shard_layout.clone();
+            let num_shards = shard_layout.num_shards() as usize;
+            config.num_block_producer_seats_per_shard =
+                vec![config.num_block_producer_seats; num_shards];
+            config.avg_hidden_validator_seats_per_shard = vec![0; num_shards];
+        }
         config
     }
 }
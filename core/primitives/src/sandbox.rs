@@ -1,6 +1,25 @@
+use crate::serialize::dec_format;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{AccountId, Balance};
+
+/// New balance of an existing account, set via sandbox-only state patching
+/// without having to provide the whole account.  Balances which are `None`
+/// are left as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SandboxBalancePatch {
+    pub account_id: AccountId,
+    #[serde(default, with = "dec_format")]
+    pub amount: Option<Balance>,
+    #[serde(default, with = "dec_format")]
+    pub locked: Option<Balance>,
+}
+
 #[cfg(feature = "sandbox")]
 pub mod state_patch {
-    use crate::state_record::StateRecord;
+    use super::SandboxBalancePatch;
+    use crate::state_record::{state_record_to_account_id, StateRecord};
+    use crate::types::AccountId;
 
     /// Changes to the state to be applied via sandbox-only state patching
     /// feature.
@@ -12,43 +31,72 @@ pub mod state_patch {
     #[derive(Default)]
     pub struct SandboxStatePatch {
         records: Vec<StateRecord>,
+        balances: Vec<SandboxBalancePatch>,
     }
 
     impl SandboxStatePatch {
         pub fn new(records: Vec<StateRecord>) -> SandboxStatePatch {
-            SandboxStatePatch { records }
+            SandboxStatePatch { records, balances: vec![] }
+        }
+
+        pub fn with_balances(
+            records: Vec<StateRecord>,
+            balances: Vec<SandboxBalancePatch>,
+        ) -> SandboxStatePatch {
+            SandboxStatePatch { records, balances }
         }
 
         pub fn is_empty(&self) -> bool {
-            self.records.is_empty()
+            self.records.is_empty() && self.balances.is_empty()
         }
 
         pub fn clear(&mut self) {
             self.records.clear();
+            self.balances.clear();
         }
 
         pub fn take(&mut self) -> SandboxStatePatch {
-            Self { records: core::mem::take(&mut self.records) }
+            Self {
+                records: core::mem::take(&mut self.records),
+                balances: core::mem::take(&mut self.balances),
+            }
+        }
+
+        /// Takes the changes of the accounts for which `predicate` holds,
+        /// e.g. the accounts of a single shard, keeping their order.
+        pub fn take_accounts(
+            &mut self,
+            predicate: impl Fn(&AccountId) -> bool,
+        ) -> SandboxStatePatch {
+            let (records, rest) = core::mem::take(&mut self.records)
+                .into_iter()
+                .partition(|record| predicate(state_record_to_account_id(record)));
+            self.records = rest;
+            let (balances, rest) = core::mem::take(&mut self.balances)
+                .into_iter()
+                .partition(|balance: &SandboxBalancePatch| predicate(&balance.account_id));
+            self.balances = rest;
+            Self { records, balances }
         }
 
         pub fn merge(&mut self, other: SandboxStatePatch) {
             self.records.extend(other.records);
+            self.balances.extend(other.balances);
         }
-    }
-
-    impl IntoIterator for SandboxStatePatch {
-        type Item = StateRecord;
-        type IntoIter = std::vec::IntoIter<StateRecord>;
 
-        fn into_iter(self) -> Self::IntoIter {
-            self.records.into_iter()
+        /// Returns the records followed by the balances to set on top of
+        /// them.
+        pub fn into_parts(self) -> (Vec<StateRecord>, Vec<SandboxBalancePatch>) {
+            (self.records, self.balances)
         }
     }
 }
 
 #[cfg(not(feature = "sandbox"))]
 pub mod state_patch {
+    use super::SandboxBalancePatch;
     use crate::state_record::StateRecord;
+    use crate::types::AccountId;
 
     #[derive(Default)]
     pub struct SandboxStatePatch;
@@ -65,16 +113,14 @@ pub mod state_patch {
             Self
         }
         #[inline(always)]
+        pub fn take_accounts(&mut self, _predicate: impl Fn(&AccountId) -> bool) -> Self {
+            Self
+        }
+        #[inline(always)]
         pub fn merge(&self, _other: SandboxStatePatch) {}
-    }
-
-    impl IntoIterator for SandboxStatePatch {
-        type Item = StateRecord;
-        type IntoIter = std::iter::Empty<StateRecord>;
-
         #[inline(always)]
-        fn into_iter(self) -> Self::IntoIter {
-            std::iter::empty()
+        pub fn into_parts(self) -> (Vec<StateRecord>, Vec<SandboxBalancePatch>) {
+            (vec![], vec![])
         }
     }
 }
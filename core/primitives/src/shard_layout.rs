@@ -219,6 +219,24 @@ impl ShardLayout {
         }
     }
 
+    /// Accounts whose sub-accounts are kept in shards of their own.  Empty for
+    /// V0 layouts.
+    pub fn fixed_shards(&self) -> &[AccountId] {
+        match self {
+            Self::V0(_) => &[],
+            Self::V1(v1) => &v1.fixed_shards,
+        }
+    }
+
+    /// Accounts at which the ranges of the remaining shards start.  Empty for
+    /// V0 layouts.
+    pub fn boundary_accounts(&self) -> &[AccountId] {
+        match self {
+            Self::V0(_) => &[],
+            Self::V1(v1) => &v1.boundary_accounts,
+        }
+    }
+
     /// Returns shard uids for all shards in the shard layout
     pub fn get_shard_uids(&self) -> Vec<ShardUId> {
         (0..self.num_shards()).map(|x| ShardUId::from_shard_id_and_layout(x, self)).collect()
@@ -189,6 +189,23 @@ impl ShardStateSyncResponseV1 {
     }
 }
 
+/// Trie nodes and values added to the state of a shard between the ends of
+/// two epochs, built from the trie changes of the chunks in between.  A node
+/// which has the state at the end of the earlier epoch needs only these to
+/// get the state at the end of the later one.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ShardStateDelta {
+    /// Serialized trie nodes and values.
+    pub nodes: Vec<Vec<u8>>,
+}
+
+impl ShardStateDelta {
+    /// Total size of the nodes and values in bytes.
+    pub fn size(&self) -> u64 {
+        self.nodes.iter().map(|value| value.len() as u64).sum()
+    }
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Eq, PartialEq, Debug, Clone)]
 pub struct EpochSyncFinalizationResponse {
     pub cur_epoch_header: BlockHeader,
@@ -46,6 +46,10 @@ struct MockClockPerState {
     utc_call_count: u64,
     /// Number of times `Clock::instant()` method was called since we started mocking.
     instant_call_count: u64,
+    /// Timestamp returned by `Clock::utc()` once `utc_list` is exhausted.
+    frozen_utc: Option<DateTime<Utc>>,
+    /// Timestamp returned by `Clock::instant()` once `instant_list` is exhausted.
+    frozen_instant: Option<Instant>,
 }
 
 /// Stores the mocking state.
@@ -93,6 +97,34 @@ impl MockClockGuard {
         });
     }
 
+    /// Sets the timestamp returned by `Clock::utc()` whenever there are no
+    /// timestamps queued with `Self::add_utc`.  Unlike queued timestamps it
+    /// can be returned any number of times, which allows driving code from a
+    /// virtual clock that only moves when the test advances it.
+    pub fn set_utc(&self, mock_date: DateTime<chrono::Utc>) {
+        MockClockPerThread::with(|clock| match &mut clock.mock {
+            Some(clock) => {
+                clock.frozen_utc = Some(mock_date);
+            }
+            None => {
+                panic!("Use MockClockGuard in your test");
+            }
+        });
+    }
+
+    /// Sets the timestamp returned by `Clock::instant()` whenever there are no
+    /// timestamps queued with `Self::add_instant`.  See `Self::set_utc`.
+    pub fn set_instant(&self, mock_date: Instant) {
+        MockClockPerThread::with(|clock| match &mut clock.mock {
+            Some(clock) => {
+                clock.frozen_instant = Some(mock_date);
+            }
+            None => {
+                panic!("Use MockClockGuard in your test");
+            }
+        });
+    }
+
     /// Returns number of calls  to `Self::utc` since `Self::mock()` was called.
     pub fn utc_call_count(&self) -> u64 {
         MockClockPerThread::with(|clock| match &mut clock.mock {
@@ -146,12 +178,13 @@ impl Clock {
     }
 
     /// This methods gets current time as `std::Instant`
-    /// unless it's mocked, then returns time added by `Self::add_utc(...)`
+    /// unless it's mocked, then returns time added by `Self::add_instant(...)`
+    /// or, if none is queued, the one set by `Self::set_instant(...)`
     pub fn instant() -> Instant {
         MockClockPerThread::with(|clock| match &mut clock.mock {
             Some(clock) => {
                 clock.instant_call_count += 1;
-                let x = clock.instant_list.pop_front();
+                let x = clock.instant_list.pop_front().or(clock.frozen_instant);
                 match x {
                     Some(t) => t,
                     None => {
@@ -163,13 +196,14 @@ impl Clock {
         })
     }
 
-    /// This methods gets current time as `std::Instant`
-    /// unless it's mocked, then returns time added by `Self::add_instant(...)`
+    /// This methods gets current time as `DateTime<Utc>`
+    /// unless it's mocked, then returns time added by `Self::add_utc(...)`
+    /// or, if none is queued, the one set by `Self::set_utc(...)`
     pub fn utc() -> DateTime<chrono::Utc> {
         MockClockPerThread::with(|clock| match &mut clock.mock {
             Some(clock) => {
                 clock.utc_call_count += 1;
-                let x = clock.utc_list.pop_front();
+                let x = clock.utc_list.pop_front().or(clock.frozen_utc);
                 match x {
                     Some(t) => t,
                     None => {
@@ -277,6 +311,22 @@ mod tests {
         assert_eq!(mock_clock_guard.instant_call_count(), 0);
     }
 
+    #[test]
+    fn test_clock_frozen_instant() {
+        let mock_clock_guard = MockClockGuard::default();
+
+        let instant_now = Instant::now();
+        mock_clock_guard.set_instant(instant_now);
+        mock_clock_guard.add_instant(instant_now.add(Duration::from_secs(1)));
+        assert_eq!(Clock::instant(), instant_now.add(Duration::from_secs(1)));
+        assert_eq!(Clock::instant(), instant_now);
+        assert_eq!(Clock::instant(), instant_now);
+        mock_clock_guard.set_instant(instant_now.add(Duration::from_secs(2)));
+        assert_eq!(Clock::instant(), instant_now.add(Duration::from_secs(2)));
+
+        assert_eq!(mock_clock_guard.instant_call_count(), 4);
+    }
+
     #[test]
     fn test_threading() {
         thread::spawn(|| {
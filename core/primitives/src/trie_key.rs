@@ -418,6 +418,34 @@ pub mod trie_key_parsers {
         res.extend(prefix);
         res
     }
+
+    /// Returns prefixes covering the raw keys of all the records of the
+    /// account, one per column.  They also cover keys of accounts whose ids
+    /// start with `account_id`, use [`parse_account_id_from_raw_key`] to tell
+    /// them apart.
+    pub fn get_raw_prefixes_for_account(account_id: &AccountId) -> Vec<Vec<u8>> {
+        col::NON_DELAYED_RECEIPT_COLUMNS
+            .iter()
+            .map(|&(col, _)| {
+                let mut res = Vec::with_capacity(col.len() + account_id.len());
+                res.push(col);
+                res.extend(account_id.as_bytes());
+                res
+            })
+            .collect()
+    }
+
+    /// Returns name of the column of the raw key, e.g. `ContractData`.
+    pub fn get_column_name(raw_key: &[u8]) -> Option<&'static str> {
+        match *raw_key.first()? {
+            col::DELAYED_RECEIPT_INDICES => Some("DelayedReceiptIndices"),
+            col::DELAYED_RECEIPT => Some("DelayedReceipt"),
+            key_col => col::NON_DELAYED_RECEIPT_COLUMNS
+                .iter()
+                .find(|(col, _)| *col == key_col)
+                .map(|(_, name)| *name),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -59,7 +59,7 @@ pub struct AccountInfo {
 /// NOTE: Currently, this type is only used in the view_client and RPC to be able to transparently
 /// pretty-serialize the bytes arrays as base64-encoded strings (see `serialize.rs`).
 #[derive(
-    Debug, Clone, PartialEq, Eq, DeriveAsRef, DeriveFrom, BorshSerialize, BorshDeserialize,
+    Debug, Default, Clone, PartialEq, Eq, DeriveAsRef, DeriveFrom, BorshSerialize, BorshDeserialize,
 )]
 #[as_ref(forward)]
 pub struct StoreKey(Vec<u8>);
@@ -213,13 +213,87 @@ impl StateChangesForSplitStates {
 /// key that was updated -> list of updates with the corresponding indexing event.
 pub type RawStateChanges = std::collections::BTreeMap<Vec<u8>, RawStateChangesWithTrieKey>;
 
+/// Kind of state changes selected by `StateChangesRequest::FilteredChanges`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateChangesKindFilter {
+    Account,
+    AccessKey,
+    Data,
+    ContractCode,
+}
+
+impl StateChangesKindFilter {
+    pub const ALL: [StateChangesKindFilter; 4] =
+        [Self::Account, Self::AccessKey, Self::Data, Self::ContractCode];
+}
+
 #[derive(Debug)]
 pub enum StateChangesRequest {
-    AccountChanges { account_ids: Vec<AccountId> },
-    SingleAccessKeyChanges { keys: Vec<AccountWithPublicKey> },
-    AllAccessKeyChanges { account_ids: Vec<AccountId> },
-    ContractCodeChanges { account_ids: Vec<AccountId> },
-    DataChanges { account_ids: Vec<AccountId>, key_prefix: StoreKey },
+    AccountChanges {
+        account_ids: Vec<AccountId>,
+    },
+    SingleAccessKeyChanges {
+        keys: Vec<AccountWithPublicKey>,
+    },
+    AllAccessKeyChanges {
+        account_ids: Vec<AccountId>,
+    },
+    ContractCodeChanges {
+        account_ids: Vec<AccountId>,
+    },
+    DataChanges {
+        account_ids: Vec<AccountId>,
+        key_prefix: StoreKey,
+    },
+    /// Changes of the given kinds, all of them if `kinds` is empty, of the
+    /// accounts.  Data changes are limited to the keys starting with
+    /// `key_prefix`.
+    FilteredChanges {
+        account_ids: Vec<AccountId>,
+        kinds: Vec<StateChangesKindFilter>,
+        key_prefix: StoreKey,
+    },
+}
+
+impl StateChangesRequest {
+    /// Splits the request into requests for a single account (or a single
+    /// access key), which together return the same changes in the same order.
+    pub fn split_by_account(self) -> Vec<StateChangesRequest> {
+        match self {
+            Self::AccountChanges { account_ids } => account_ids
+                .into_iter()
+                .map(|account_id| Self::AccountChanges { account_ids: vec![account_id] })
+                .collect(),
+            Self::SingleAccessKeyChanges { keys } => keys
+                .into_iter()
+                .map(|key| Self::SingleAccessKeyChanges { keys: vec![key] })
+                .collect(),
+            Self::AllAccessKeyChanges { account_ids } => account_ids
+                .into_iter()
+                .map(|account_id| Self::AllAccessKeyChanges { account_ids: vec![account_id] })
+                .collect(),
+            Self::ContractCodeChanges { account_ids } => account_ids
+                .into_iter()
+                .map(|account_id| Self::ContractCodeChanges { account_ids: vec![account_id] })
+                .collect(),
+            Self::DataChanges { account_ids, key_prefix } => account_ids
+                .into_iter()
+                .map(|account_id| Self::DataChanges {
+                    account_ids: vec![account_id],
+                    key_prefix: key_prefix.clone(),
+                })
+                .collect(),
+            Self::FilteredChanges { account_ids, kinds, key_prefix } => account_ids
+                .into_iter()
+                .map(|account_id| Self::FilteredChanges {
+                    account_ids: vec![account_id],
+                    kinds: kinds.clone(),
+                    key_prefix: key_prefix.clone(),
+                })
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -817,6 +891,17 @@ impl From<Finality> for BlockReference {
     }
 }
 
+/// Which block to pick when looking up a block by timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockTimestampMatch {
+    /// The block with the timestamp closest to the given one.
+    #[default]
+    Closest,
+    /// The first block with the timestamp equal to or after the given one.
+    AtOrAfter,
+}
+
 #[derive(Default, BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
 pub struct ValidatorStats {
     pub produced: NumBlocks,
@@ -966,3 +1051,32 @@ impl ops::Sub for TrieNodesCount {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StateChangesKindFilter, StateChangesRequest, StoreKey};
+
+    #[test]
+    fn test_split_state_changes_request_by_account() {
+        let request = StateChangesRequest::FilteredChanges {
+            account_ids: vec!["alice.near".parse().unwrap(), "bob.near".parse().unwrap()],
+            kinds: vec![StateChangesKindFilter::Data],
+            key_prefix: b"key".to_vec().into(),
+        };
+        let requests = request.split_by_account();
+        assert_eq!(requests.len(), 2);
+        for (request, want) in requests.iter().zip(["alice.near", "bob.near"]) {
+            match request {
+                StateChangesRequest::FilteredChanges { account_ids, kinds, key_prefix } => {
+                    assert_eq!(account_ids.len(), 1);
+                    assert_eq!(account_ids[0].as_ref(), want);
+                    assert_eq!(kinds, &[StateChangesKindFilter::Data]);
+                    assert_eq!(key_prefix, &StoreKey::from(b"key".to_vec()));
+                }
+                request => panic!("unexpected request: {:?}", request),
+            }
+        }
+        let request = StateChangesRequest::AccountChanges { account_ids: vec![] };
+        assert!(request.split_by_account().is_empty());
+    }
+}
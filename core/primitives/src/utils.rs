@@ -11,7 +11,7 @@ use serde;
 use crate::hash::{hash, CryptoHash};
 use crate::receipt::Receipt;
 use crate::transaction::SignedTransaction;
-use crate::types::{CompiledContractCache, NumSeats, NumShards, ShardId};
+use crate::types::{AccountId, BlockHeight, CompiledContractCache, NumSeats, NumShards, ShardId};
 use crate::version::{
     ProtocolVersion, CORRECT_RANDOM_VALUE_PROTOCOL_VERSION, CREATE_HASH_PROTOCOL_VERSION,
     CREATE_RECEIPT_ID_SWITCH_TO_CURRENT_BLOCK_VERSION,
@@ -187,6 +187,26 @@ pub fn get_block_shard_id(block_hash: &CryptoHash, shard_id: ShardId) -> Vec<u8>
     res
 }
 
+/// Returns the prefix of the `DBCol::AccountOutcomeIds` keys of an account.
+pub fn get_account_outcome_ids_prefix(account_id: &AccountId) -> Vec<u8> {
+    let mut res = Vec::with_capacity(account_id.len() + 1);
+    res.extend_from_slice(account_id.as_bytes());
+    res.push(b',');
+    res
+}
+
+pub fn get_account_outcome_ids_key(
+    account_id: &AccountId,
+    height: BlockHeight,
+    block_hash: &CryptoHash,
+) -> Vec<u8> {
+    let mut res = get_account_outcome_ids_prefix(account_id);
+    // Big endian so that the keys of an account are sorted by height.
+    res.extend_from_slice(&height.to_be_bytes());
+    res.extend_from_slice(block_hash.as_ref());
+    res
+}
+
 pub fn get_block_shard_id_rev(
     key: &[u8],
 ) -> Result<(CryptoHash, ShardId), Box<dyn std::error::Error + Send + Sync>> {
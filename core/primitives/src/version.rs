@@ -151,6 +151,14 @@ pub enum ProtocolFeature {
     #[cfg(feature = "protocol_feature_zero_balance_account")]
     /// NEP 448: https://github.com/near/NEPs/pull/448
     ZeroBalanceAccount,
+    /// Part owners forward chunk parts to a few validators which forward them further instead of
+    /// sending them to every validator tracking the shard.
+    #[cfg(feature = "protocol_feature_chunk_part_multicast")]
+    ChunkPartMulticast,
+    /// Nodes which tracked a shard in one of the last two epochs catch up by downloading the trie
+    /// nodes added since then instead of the whole state.
+    #[cfg(feature = "protocol_feature_state_sync_delta")]
+    StateSyncDelta,
 }
 
 /// Both, outgoing and incoming tcp connections to peers, will be rejected if `peer's`
@@ -165,7 +173,7 @@ const STABLE_PROTOCOL_VERSION: ProtocolVersion = 58;
 /// Largest protocol version supported by the current binary.
 pub const PROTOCOL_VERSION: ProtocolVersion = if cfg!(feature = "nightly_protocol") {
     // On nightly, pick big enough version to support all features.
-    134
+    136
 } else {
     // Enable all stable features.
     STABLE_PROTOCOL_VERSION
@@ -241,6 +249,10 @@ impl ProtocolFeature {
             ProtocolFeature::DelegateAction => 133,
             #[cfg(feature = "protocol_feature_zero_balance_account")]
             ProtocolFeature::ZeroBalanceAccount => 134,
+            #[cfg(feature = "protocol_feature_chunk_part_multicast")]
+            ProtocolFeature::ChunkPartMulticast => 135,
+            #[cfg(feature = "protocol_feature_state_sync_delta")]
+            ProtocolFeature::StateSyncDelta => 136,
         }
     }
 }
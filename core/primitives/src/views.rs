@@ -5,6 +5,7 @@
 //! from the source structure in the relevant `From<SourceStruct>` impl.
 use std::collections::HashMap;
 use std::fmt;
+use std::num::NonZeroU32;
 use std::ops::Range;
 use std::sync::Arc;
 
@@ -47,8 +48,8 @@ use crate::transaction::{
 use crate::types::{
     AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
     EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind,
-    StateChangeValue, StateChangeWithCause, StateChangesRequest, StateRoot, StorageUsage, StoreKey,
-    StoreValue, ValidatorKickoutReason,
+    StateChangeValue, StateChangeWithCause, StateChangesKindFilter, StateChangesRequest, StateRoot,
+    StorageUsage, StoreKey, StoreValue, ValidatorKickoutReason,
 };
 use crate::version::{ProtocolVersion, Version};
 use validator_stake_view::ValidatorStakeView;
@@ -57,7 +58,7 @@ use validator_stake_view::ValidatorStakeView;
 use crate::transaction::{DelegateAction, SignedDelegateAction};
 
 /// A view of the account
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub struct AccountView {
     #[serde(with = "dec_format")]
     pub amount: Balance,
@@ -71,7 +72,7 @@ pub struct AccountView {
 }
 
 /// A view of the contract code.
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct ContractCodeView {
     #[serde(rename = "code_base64", with = "base64_format")]
     pub code: Vec<u8>,
@@ -97,6 +98,9 @@ pub struct ViewApplyState {
     pub current_protocol_version: ProtocolVersion,
     /// Cache for compiled contracts.
     pub cache: Option<Box<dyn CompiledContractCache>>,
+    /// The call is aborted at the next storage access once the deadline has
+    /// passed.
+    pub deadline: Option<std::time::Instant>,
 }
 
 impl From<&Account> for AccountView {
@@ -201,7 +205,7 @@ impl From<AccessKeyView> for AccessKey {
 }
 
 /// Item of the state, key and value are serialized in base64 and proof for inclusion of given state item.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct StateItem {
     #[serde(with = "base64_format")]
     pub key: Vec<u8>,
@@ -214,16 +218,22 @@ pub struct StateItem {
     pub proof: Vec<()>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct ViewStateResult {
     pub values: Vec<StateItem>,
     // TODO(mina86): Empty proof (i.e. sending proof when include_proof is not
     // set in the request) was deprecated in 1.30.  Add
     // `#[serde(skip(Vec::if_empty))` at 1.33 or something.
     pub proof: Vec<Arc<[u8]>>,
+    /// Set if the values were cut short by the `limit` of the request, pass
+    /// it as `after_key` to get the next page.
+    #[serde(default, with = "option_base64_format", skip_serializing_if = "Option::is_none")]
+    pub next_key: Option<Vec<u8>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default,
+)]
 pub struct CallResult {
     pub result: Vec<u8>,
     pub logs: Vec<String>,
@@ -235,20 +245,27 @@ pub struct QueryError {
     pub logs: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct AccessKeyInfoView {
     pub public_key: PublicKey,
     pub access_key: AccessKeyView,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct AccessKeyList {
     pub keys: Vec<AccessKeyInfoView>,
+    /// Set if the list was cut short by the `limit` of the request, pass it
+    /// as `after_public_key` to get the next page.  Not part of the borsh
+    /// encoding, which stays the plain list of keys; borsh clients continue
+    /// from the last key until a page is shorter than the limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[borsh_skip]
+    pub next_public_key: Option<PublicKey>,
 }
 
 impl FromIterator<AccessKeyInfoView> for AccessKeyList {
     fn from_iter<I: IntoIterator<Item = AccessKeyInfoView>>(iter: I) -> Self {
-        Self { keys: iter.into_iter().collect() }
+        Self { keys: iter.into_iter().collect(), next_public_key: None }
     }
 }
 
@@ -289,6 +306,18 @@ pub enum QueryRequest {
         prefix: StoreKey,
         #[serde(default, skip_serializing_if = "is_false")]
         include_proof: bool,
+        /// Lists the values after this key, see `ViewStateResult::next_key`.
+        #[serde(
+            rename = "after_key_base64",
+            default,
+            with = "option_base64_format",
+            skip_serializing_if = "Option::is_none"
+        )]
+        after_key: Option<Vec<u8>>,
+        /// Maximum number of values listed.  Without it all the values are
+        /// listed at once.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<NonZeroU32>,
     },
     ViewAccessKey {
         account_id: AccountId,
@@ -296,6 +325,13 @@ pub enum QueryRequest {
     },
     ViewAccessKeyList {
         account_id: AccountId,
+        /// Lists the keys after this one, see `AccessKeyList::next_public_key`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after_public_key: Option<PublicKey>,
+        /// Maximum number of keys listed.  Without it all the keys are listed
+        /// at once.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<NonZeroU32>,
     },
     CallFunction {
         account_id: AccountId,
@@ -432,6 +468,21 @@ pub struct NetworkGraphView {
     pub edges: Vec<EdgeView>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MessageTypeBandwidthView {
+    pub message_type: String,
+    pub sent_bytes: u64,
+    pub sent_messages: u64,
+    pub received_bytes: u64,
+    pub received_messages: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct NetworkBandwidthView {
+    /// Sorted by the total number of bytes, biggest first.
+    pub message_types: Vec<MessageTypeBandwidthView>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct ShardSyncDownloadView {
     pub downloads: Vec<DownloadStatusView>,
@@ -634,7 +685,7 @@ pub struct StatusResponse {
     pub detailed_debug_status: Option<DetailedDebugStatus>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 pub struct ChallengeView {
     // TODO: decide how to represent challenges in json.
 }
@@ -645,7 +696,7 @@ impl From<Challenge> for ChallengeView {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 pub struct BlockHeaderView {
     pub height: BlockHeight,
     pub prev_height: Option<BlockHeight>,
@@ -913,7 +964,7 @@ impl From<BlockHeaderInnerLiteView> for BlockHeaderInnerLite {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 pub struct ChunkHeaderView {
     pub chunk_hash: CryptoHash,
     pub prev_block_hash: CryptoHash,
@@ -996,7 +1047,7 @@ impl From<ChunkHeaderView> for ShardChunkHeader {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug)]
 pub struct BlockView {
     pub author: AccountId,
     pub header: BlockHeaderView,
@@ -1013,7 +1064,7 @@ impl BlockView {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug)]
 pub struct ChunkView {
     pub author: AccountId,
     pub header: ChunkHeaderView,
@@ -1481,6 +1532,33 @@ impl ExecutionOutcomeWithIdView {
     }
 }
 
+/// Result of simulating a transaction on top of the state of a block, see
+/// the `EXPERIMENTAL_simulate_tx` RPC.  The
+/// outcomes have no proofs and the default block hash, since the blocks in
+/// which they were executed are simulated.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransactionSimulationView {
+    pub transaction_outcome: ExecutionOutcomeWithIdView,
+    /// Outcomes of the receipts produced by the transaction, in the order in
+    /// which they were executed.
+    pub receipts_outcome: Vec<ExecutionOutcomeWithIdView>,
+    /// Gas burnt by the transaction and all of its receipts.
+    pub gas_burnt: Gas,
+    pub state_changes: StateChangesView,
+    /// Receipts which weren't executed, either because the node doesn't track
+    /// the shard of their receiver or because the simulation ran out of
+    /// blocks.
+    pub pending_receipts: Vec<ReceiptView>,
+}
+
+/// Ids of the transactions and receipts executed on an account in a block.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AccountOutcomeIdsView {
+    pub block_height: BlockHeight,
+    pub block_hash: CryptoHash,
+    pub outcome_ids: Vec<CryptoHash>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum FinalExecutionOutcomeViewEnum {
@@ -1532,6 +1610,30 @@ pub struct FinalExecutionOutcomeWithReceiptView {
     pub receipts: Vec<ReceiptView>,
 }
 
+/// Proof of an execution outcome for a light client, see the
+/// `light_client_proof` RPC method.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+pub struct ExecutionOutcomeProofView {
+    pub outcome_proof: ExecutionOutcomeWithIdView,
+    pub outcome_root_proof: MerklePath,
+    pub block_header_lite: LightClientBlockLiteView,
+    pub block_proof: MerklePath,
+}
+
+/// Final execution outcome of the transaction with the receipts and the proofs of all the
+/// outcomes against `light_client_head`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+pub struct FinalExecutionOutcomeWithProofsView {
+    #[serde(flatten)]
+    pub final_outcome: FinalExecutionOutcomeWithReceiptView,
+    pub light_client_head: CryptoHash,
+    /// Proof of `transaction_outcome`, `None` if it can't be proven against
+    /// `light_client_head` yet.
+    pub transaction_outcome_proof: Option<ExecutionOutcomeProofView>,
+    /// Proofs of `receipts_outcome`, in the same order.
+    pub receipts_outcome_proofs: Vec<Option<ExecutionOutcomeProofView>>,
+}
+
 pub mod validator_stake_view {
     use crate::types::validator_stake::ValidatorStake;
     use borsh::{BorshDeserialize, BorshSerialize};
@@ -1817,6 +1919,40 @@ pub struct GasPriceView {
     pub gas_price: Balance,
 }
 
+/// Gas price to attach to transactions, estimated from the gas prices and
+/// the utilization of the chunks of recent blocks.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GasPriceEstimateView {
+    /// Latest block the estimate is based on.
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    /// Gas price of the latest block.
+    #[serde(with = "dec_format")]
+    pub gas_price: Balance,
+    /// Number of recent blocks the estimate is based on.
+    pub num_blocks: u64,
+    /// Ratio of the gas used to the gas limit of the chunks of the blocks.
+    pub utilization: f64,
+    /// Utilization of the newer half of the blocks minus the utilization of
+    /// the older half.
+    pub utilization_trend: f64,
+    /// Gas price a few blocks ahead if the utilization of the newer half of
+    /// the blocks persists.
+    #[serde(with = "dec_format")]
+    pub projected_gas_price: Balance,
+    /// Estimates for the requested percentiles of the gas prices.
+    pub estimates: Vec<GasPricePercentileView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GasPricePercentileView {
+    pub percentile: u8,
+    /// Greater of the percentile of the gas prices and of the projected gas
+    /// price.
+    #[serde(with = "dec_format")]
+    pub gas_price: Balance,
+}
+
 /// It is a [serializable view] of [`StateChangesRequest`].
 ///
 /// [serializable view]: ./index.html
@@ -1841,6 +1977,13 @@ pub enum StateChangesRequestView {
         #[serde(rename = "key_prefix_base64", with = "base64_format")]
         key_prefix: StoreKey,
     },
+    FilteredChanges {
+        account_ids: Vec<AccountId>,
+        #[serde(default)]
+        kinds: Vec<StateChangesKindFilter>,
+        #[serde(default, rename = "key_prefix_base64", with = "base64_format")]
+        key_prefix: StoreKey,
+    },
 }
 
 impl From<StateChangesRequestView> for StateChangesRequest {
@@ -1861,6 +2004,9 @@ impl From<StateChangesRequestView> for StateChangesRequest {
             StateChangesRequestView::DataChanges { account_ids, key_prefix } => {
                 Self::DataChanges { account_ids, key_prefix }
             }
+            StateChangesRequestView::FilteredChanges { account_ids, kinds, key_prefix } => {
+                Self::FilteredChanges { account_ids, kinds, key_prefix }
+            }
         }
     }
 }
@@ -1869,7 +2015,7 @@ impl From<StateChangesRequestView> for StateChangesRequest {
 ///
 /// [serializable view]: ./index.html
 /// [`StateChangeKind`]: ../types/struct.StateChangeKind.html
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum StateChangeKindView {
     AccountTouched { account_id: AccountId },
@@ -1896,7 +2042,7 @@ impl From<StateChangeKind> for StateChangeKindView {
 pub type StateChangesKindsView = Vec<StateChangeKindView>;
 
 /// See crate::types::StateChangeCause for details.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum StateChangeCauseView {
     NotWritableToDisk,
@@ -1940,7 +2086,7 @@ impl From<StateChangeCause> for StateChangeCauseView {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "change")]
 pub enum StateChangeValueView {
     AccountUpdate {
@@ -2013,7 +2159,7 @@ impl From<StateChangeValue> for StateChangeValueView {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize)]
 pub struct StateChangeWithCauseView {
     pub cause: StateChangeCauseView,
     #[serde(flatten)]
@@ -2032,6 +2178,93 @@ pub type StateChangesView = Vec<StateChangeWithCauseView>;
 /// Maintenance windows view are a vector of maintenance window.
 pub type MaintenanceWindowsView = Vec<Range<BlockHeight>>;
 
+/// Upcoming block and chunk production slots of a validator in the current
+/// and the next epoch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorScheduleView {
+    pub account_id: AccountId,
+    pub current_epoch: ValidatorEpochScheduleView,
+    pub next_epoch: ValidatorEpochScheduleView,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorEpochScheduleView {
+    pub epoch_id: CryptoHash,
+    /// Height of the first block of the epoch.  For the next epoch it's an
+    /// estimate, the epoch starts later if the last blocks of the current
+    /// epoch aren't final in time.
+    pub epoch_start_height: BlockHeight,
+    /// Heights at which the validator produces blocks.
+    pub block_production: Vec<BlockHeight>,
+    /// Heights and shards for which the validator produces chunks.
+    pub chunk_production: Vec<ChunkProductionSlotView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkProductionSlotView {
+    pub height: BlockHeight,
+    pub shard_id: ShardId,
+}
+
+/// Projected standing of a validator if the current epoch ended at the
+/// latest block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorProjectionView {
+    pub account_id: AccountId,
+    pub epoch_id: CryptoHash,
+    pub epoch_start_height: BlockHeight,
+    /// Stake of the validator in the epoch after next, whose validators are
+    /// selected from the proposals of the current epoch.  `None` if it's not
+    /// projected to get a seat.
+    #[serde(with = "dec_format")]
+    pub projected_stake: Option<Balance>,
+    /// Projected minimum stake needed for a seat in the epoch after next.
+    #[serde(with = "dec_format")]
+    pub seat_price: Balance,
+    /// Why the validator would be kicked out, if it would be.
+    pub kickout_reason: Option<ValidatorKickoutReason>,
+    /// Reward for the current epoch given the uptime of the validator so far,
+    /// assuming the rest of the epoch takes as long per block.
+    #[serde(with = "dec_format")]
+    pub estimated_reward: Balance,
+}
+
+/// State of the vote of the block producers of the current epoch on the
+/// protocol version of the epoch after next.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolVersionVotingView {
+    pub epoch_id: CryptoHash,
+    pub epoch_start_height: BlockHeight,
+    /// Protocol version of the current epoch.
+    pub protocol_version: ProtocolVersion,
+    /// Protocol version of the next epoch, decided by the vote in the
+    /// previous epoch.
+    pub next_protocol_version: ProtocolVersion,
+    /// Protocol version the epoch after next would have if the current epoch
+    /// ended now.
+    pub projected_protocol_version: ProtocolVersion,
+    pub votes: Vec<ProtocolVersionVoteView>,
+    /// Stake of all the block producers of the current epoch.
+    #[serde(with = "dec_format")]
+    pub total_stake: Balance,
+    /// A version needs the votes of more stake than this to be adopted.
+    #[serde(with = "dec_format")]
+    pub threshold_stake: Balance,
+    /// Estimated height of the first block with a newer protocol version,
+    /// whether already decided or projected.
+    pub estimated_upgrade_height: Option<BlockHeight>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolVersionVoteView {
+    pub account_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub stake: Balance,
+    /// Latest protocol version of the block producer in the headers of its
+    /// blocks in the current epoch, `None` if it didn't produce any yet.
+    pub protocol_version: Option<ProtocolVersion>,
+}
+
 /// View that preserves JSON format of the runtime config.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RuntimeConfigView {
@@ -2678,4 +2911,56 @@ mod tests {
         let view = ExecutionMetadataView::from(metadata);
         insta::assert_json_snapshot!(view);
     }
+
+    #[test]
+    fn test_view_state_limit() {
+        let request = |limit: u32| {
+            serde_json::from_value::<super::QueryRequest>(serde_json::json!({
+                "request_type": "view_state",
+                "account_id": "test.near",
+                "prefix_base64": "",
+                "limit": limit,
+            }))
+        };
+        assert!(request(0).is_err());
+        match request(10).unwrap() {
+            super::QueryRequest::ViewState { limit, .. } => {
+                assert_eq!(limit.map(std::num::NonZeroU32::get), Some(10))
+            }
+            request => panic!("unexpected request: {:?}", request),
+        }
+    }
+
+    #[test]
+    fn test_view_access_key_list_limit() {
+        let request = |limit: u32| {
+            serde_json::from_value::<super::QueryRequest>(serde_json::json!({
+                "request_type": "view_access_key_list",
+                "account_id": "test.near",
+                "limit": limit,
+            }))
+        };
+        assert!(request(0).is_err());
+        match request(10).unwrap() {
+            super::QueryRequest::ViewAccessKeyList { limit, .. } => {
+                assert_eq!(limit.map(std::num::NonZeroU32::get), Some(10))
+            }
+            request => panic!("unexpected request: {:?}", request),
+        }
+    }
+
+    /// The borsh encoding of the list is the plain list of keys, as before
+    /// the lists were paginated.
+    #[test]
+    fn test_access_key_list_borsh() {
+        use borsh::BorshSerialize;
+        let list = super::AccessKeyList {
+            keys: vec![],
+            next_public_key: Some(near_crypto::PublicKey::empty(near_crypto::KeyType::ED25519)),
+        };
+        assert_eq!(
+            list.try_to_vec().unwrap(),
+            Vec::<super::AccessKeyInfoView>::new().try_to_vec().unwrap()
+        );
+    }
 }
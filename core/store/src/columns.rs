@@ -253,6 +253,17 @@ pub enum DBCol {
     /// *Rows*: OutcomeId (CryptoHash) || BlockHash (CryptoHash)
     /// *Column type*: ExecutionOutcomeWithProof
     TransactionResultForBlock,
+    /// Ids of the transaction and receipt outcomes executed by an account in a block.  Only
+    /// maintained if `save_account_outcome_ids` is enabled in the config.
+    /// - *Rows*: AccountId || ',' || BlockHeight (big endian u64) || BlockHash (CryptoHash)
+    /// - *Column type*: Vec<OutcomeId (CryptoHash)>
+    AccountOutcomeIds,
+    /// Parts and receipts of the chunks completed at the last few heights, used to answer
+    /// chunk part requests from peers which fell behind.  Pruned by the `ShardsManager`,
+    /// not by garbage collection.
+    /// - *Rows*: BlockHeight (big endian u64) || ChunkHash (CryptoHash)
+    /// - *Column type*: [near_primitives::sharding::PartialEncodedChunk]
+    RecentPartialChunks,
     /// Flat state contents. Used to get `ValueRef` by trie key faster than doing a trie lookup.
     /// - *Rows*: trie key (Vec<u8>)
     /// - *Column type*: ValueRef
@@ -466,6 +477,10 @@ impl DBCol {
             DBCol::HeaderHashesByHeight => &[DBKeyType::BlockHeight],
             DBCol::StateChangesForSplitStates => &[DBKeyType::BlockHash, DBKeyType::ShardId],
             DBCol::TransactionResultForBlock => &[DBKeyType::OutcomeId, DBKeyType::BlockHash],
+            DBCol::AccountOutcomeIds => {
+                &[DBKeyType::AccountId, DBKeyType::BlockHeight, DBKeyType::BlockHash]
+            }
+            DBCol::RecentPartialChunks => &[DBKeyType::BlockHeight, DBKeyType::ChunkHash],
             #[cfg(feature = "protocol_feature_flat_state")]
             DBCol::FlatState => &[DBKeyType::TrieKey],
             #[cfg(feature = "protocol_feature_flat_state")]
@@ -84,6 +84,15 @@ pub struct StoreConfig {
     #[serde(skip_serializing_if = "MigrationSnapshot::is_default")]
     pub migration_snapshot: MigrationSnapshot,
 
+    /// Whether to keep the migration snapshot after a successful migration.
+    ///
+    /// By default the snapshot is deleted once the migration finishes.  If
+    /// kept, the migration can be rolled back with `neard database
+    /// rollback-migration`.  Note that the snapshot shares files with the
+    /// database so it grows as the node runs, and that it must be deleted
+    /// before the next migration can start.
+    pub keep_migration_snapshot: bool,
+
     /// Number of threads to execute storage background migrations.
     /// Needed to create flat storage which need to happen in parallel
     /// with block processing.
@@ -221,6 +230,7 @@ impl Default for StoreConfig {
             ],
 
             migration_snapshot: Default::default(),
+            keep_migration_snapshot: false,
 
             // We checked that this number of threads doesn't impact
             // regular block processing significantly.
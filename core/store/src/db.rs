@@ -24,6 +24,15 @@ pub const LARGEST_TARGET_HEIGHT_KEY: &[u8; 21] = b"LARGEST_TARGET_HEIGHT";
 pub const GENESIS_JSON_HASH_KEY: &[u8; 17] = b"GENESIS_JSON_HASH";
 pub const GENESIS_STATE_ROOTS_KEY: &[u8; 19] = b"GENESIS_STATE_ROOTS";
 pub const COLD_HEAD_KEY: &[u8; 9] = b"COLD_HEAD";
+/// The most recent reorgs of the head, oldest first.
+pub const REORG_LOG_KEY: &[u8; 9] = b"REORG_LOG";
+/// Heights up to which archival nodes garbage collected the data their
+/// archival policy doesn't keep forever.
+pub const ARCHIVAL_TAILS_KEY: &[u8; 14] = b"ARCHIVAL_TAILS";
+/// Set once the `AccountOutcomeIds` index has been backfilled from the tail.
+pub const ACCOUNT_OUTCOME_IDS_KEY: &[u8; 19] = b"ACCOUNT_OUTCOME_IDS";
+/// Written by the health check of the node to verify the database accepts writes.
+pub const HEALTH_CHECK_KEY: &[u8; 12] = b"HEALTH_CHECK";
 
 #[derive(Default)]
 pub struct DBTransaction {
@@ -122,6 +131,20 @@ pub trait Database: Sync + Send {
     /// are returned in lexicographical order sorted by the key.
     fn iter_prefix<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a>;
 
+    /// Iterate over items in given column whose keys are within given range.
+    ///
+    /// The lower bound is inclusive and the upper bound is exclusive; `None`
+    /// means the range is unbounded on that side.  This is morally equivalent
+    /// to [`Self::iter`] with a filter discarding keys outside of the range
+    /// (but faster since the database can seek to the lower bound).  The items
+    /// are returned in lexicographical order sorted by the key.
+    fn iter_range<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> DBIterator<'a>;
+
     /// Iterate over items in given column bypassing reference count decoding if
     /// any.
     ///
@@ -135,6 +158,13 @@ pub trait Database: Sync + Send {
     /// want this method.
     fn iter_raw_bytes<'a>(&'a self, column: DBCol) -> DBIterator<'a>;
 
+    /// Iterate over items in given column whose keys start with given prefix
+    /// bypassing reference count decoding if any.
+    ///
+    /// This is to [`Self::iter_prefix`] what [`Self::iter_raw_bytes`] is to
+    /// [`Self::iter`].
+    fn iter_prefix_raw_bytes<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a>;
+
     /// Atomically apply all operations in given batch at once.
     fn write(&self, batch: DBTransaction) -> io::Result<()>;
 
@@ -149,6 +179,12 @@ pub trait Database: Sync + Send {
     /// is blocking until compaction finishes. Otherwise, this is a no-op.
     fn compact(&self) -> io::Result<()>;
 
+    /// Creates a consistent point-in-time copy of the database at given path.
+    ///
+    /// The path must not exist.  Returns an error if the database doesn’t
+    /// support checkpoints.
+    fn create_checkpoint(&self, path: &std::path::Path) -> io::Result<()>;
+
     /// Returns statistics about the database if available.
     fn get_store_statistics(&self) -> Option<StoreStatistics>;
 }
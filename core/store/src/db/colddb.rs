@@ -167,12 +167,42 @@ impl<D: Database> super::Database for ColdDB<D> {
         self.cold.iter_prefix(col, key_prefix)
     }
 
+    /// Iterates over values in a given column whose keys are in the range.
+    ///
+    /// Columns whose keys are adjusted in cold storage (see [`get_cold_key`])
+    /// or which are reference counted can't be iterated over by range, for
+    /// those the iterator yields an error.
+    fn iter_range<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> DBIterator<'a> {
+        if Self::is_hot_column(col) {
+            return self.hot.iter_range(col, lower_bound, upper_bound);
+        }
+        if col.is_rc() || has_cold_key(col) {
+            let err = std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("iter_range on cold storage is not supported for {col}"),
+            );
+            return Box::new(std::iter::once(Err(err)));
+        }
+        self.cold.iter_range(col, lower_bound, upper_bound)
+    }
+
     /// Unimplemented; always panics.
     fn iter_raw_bytes<'a>(&'a self, _column: DBCol) -> DBIterator<'a> {
         // We’re actually never call iter_raw_bytes on cold store.
         unreachable!();
     }
 
+    /// Unimplemented; always panics.
+    fn iter_prefix_raw_bytes<'a>(&'a self, _col: DBCol, _key_prefix: &'a [u8]) -> DBIterator<'a> {
+        // We’re actually never call iter_prefix_raw_bytes on cold store.
+        unreachable!();
+    }
+
     /// Atomically applies operations in given transaction.
     ///
     /// If debug assertions are enabled, panics if there are any delete
@@ -205,6 +235,10 @@ impl<D: Database> super::Database for ColdDB<D> {
         self.cold.flush()
     }
 
+    fn create_checkpoint(&self, path: &std::path::Path) -> std::io::Result<()> {
+        self.cold.create_checkpoint(path)
+    }
+
     fn get_store_statistics(&self) -> Option<crate::StoreStatistics> {
         self.cold.get_store_statistics()
     }
@@ -250,6 +284,19 @@ fn get_cold_key<'a>(col: DBCol, key: &[u8], buffer: &'a mut [u8; 32]) -> Option<
     }
 }
 
+/// Whether keys of the column are adjusted in cold storage by [`get_cold_key`].
+fn has_cold_key(col: DBCol) -> bool {
+    matches!(
+        col,
+        DBCol::BlockHeight
+            | DBCol::BlockPerHeight
+            | DBCol::ChunkHashesByHeight
+            | DBCol::ProcessedBlockHeights
+            | DBCol::HeaderHashesByHeight
+            | DBCol::State
+    )
+}
+
 /// Adjusts cold storage key as described in [`get_cold_key`].
 fn adjust_key(col: DBCol, key: &mut Vec<u8>) {
     let mut buffer = [0; 32];
@@ -513,6 +560,29 @@ mod test {
         "###);
     }
 
+    /// Tests that range iteration works on columns whose keys aren't adjusted
+    /// and yields an error on the other ones.
+    #[test]
+    fn test_iter_range() {
+        let db = create_test_db();
+        let col = DBCol::AccountOutcomeIds;
+        let keys = [[0u8; 8], [1u8; 8], [2u8; 8]];
+        let ops = keys.iter().map(|key| set(col, key)).collect();
+        db.write(DBTransaction { ops }).unwrap();
+
+        let got = db
+            .iter_range(col, Some(&keys[1]), None)
+            .map(|item| item.unwrap().0.to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![keys[1].to_vec(), keys[2].to_vec()], got);
+
+        for col in [DBCol::BlockHeight, DBCol::Transactions] {
+            let got = db.iter_range(col, None, None).collect::<Vec<_>>();
+            assert_eq!(1, got.len());
+            assert!(got[0].is_err(), "{col}");
+        }
+    }
+
     /// Tests that stripping and adding refcount works correctly.
     #[test]
     fn test_refcount() {
@@ -220,6 +220,24 @@ impl RocksDB {
         let iter = self.db.iterator_cf_opt(cf_handle, read_options, IteratorMode::Start);
         RocksDBIterator(iter)
     }
+
+    fn iter_raw_bytes_range<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> RocksDBIterator<'a> {
+        let cf_handle = self.cf_handle(col).unwrap();
+        let mut read_options = rocksdb_read_options();
+        if let Some(lower_bound) = lower_bound {
+            read_options.set_iterate_lower_bound(lower_bound);
+        }
+        if let Some(upper_bound) = upper_bound {
+            read_options.set_iterate_upper_bound(upper_bound);
+        }
+        let iter = self.db.iterator_cf_opt(cf_handle, read_options, IteratorMode::Start);
+        RocksDBIterator(iter)
+    }
 }
 
 struct RocksDBIterator<'a>(rocksdb::DBIteratorWithThreadMode<'a, DB>);
@@ -285,6 +303,20 @@ impl Database for RocksDB {
         refcount::iter_with_rc_logic(col, iter)
     }
 
+    fn iter_prefix_raw_bytes<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a> {
+        Box::new(self.iter_raw_bytes_prefix(col, key_prefix))
+    }
+
+    fn iter_range<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> DBIterator<'a> {
+        let iter = self.iter_raw_bytes_range(col, lower_bound, upper_bound);
+        refcount::iter_with_rc_logic(col, iter)
+    }
+
     fn write(&self, transaction: DBTransaction) -> io::Result<()> {
         let mut batch = WriteBatch::default();
         for op in transaction.ops {
@@ -340,6 +372,12 @@ impl Database for RocksDB {
         Ok(())
     }
 
+    fn create_checkpoint(&self, path: &Path) -> io::Result<()> {
+        ::rocksdb::checkpoint::Checkpoint::new(&self.db)
+            .and_then(|checkpoint| checkpoint.create_checkpoint(path))
+            .map_err(into_other)
+    }
+
     /// Trying to get
     /// 1. RocksDB statistics
     /// 2. Selected RockdDB properties for column families
@@ -500,6 +538,30 @@ impl RocksDB {
         Some(metadata::DbMetadata::read(&db)).transpose()
     }
 
+    /// Returns size of live SST files of each column of the database.
+    ///
+    /// Like [`Self::get_metadata`], this doesn’t modify the database and works
+    /// on databases with older versions.  Only column families which exist in
+    /// the database are opened; columns which don’t exist are reported as
+    /// empty.
+    pub(crate) fn get_column_sizes(
+        path: &Path,
+        config: &StoreConfig,
+    ) -> io::Result<enum_map::EnumMap<DBCol, u64>> {
+        let existing = DB::list_cf(&Options::default(), path).map_err(into_other)?;
+        let cols: Vec<DBCol> = DBCol::iter()
+            .filter(|col| existing.iter().any(|name| name == col_name(*col)))
+            .collect();
+        let db = Self::open_with_columns(path, config, Mode::ReadOnly, Temperature::Hot, &cols)?;
+        let mut sizes = enum_map::EnumMap::default();
+        for (col, handle) in db.cf_handles() {
+            let prop = ::rocksdb::properties::LIVE_SST_FILES_SIZE;
+            sizes[col] =
+                db.db.property_int_value_cf(handle, prop).map_err(into_other)?.unwrap_or(0);
+        }
+        Ok(sizes)
+    }
+
     /// Gets every int property in CF_PROPERTY_NAMES for every column in DBCol.
     fn get_cf_statistics(&self, result: &mut StoreStatistics) {
         for prop_name in CF_PROPERTY_NAMES {
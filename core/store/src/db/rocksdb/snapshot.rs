@@ -112,6 +112,116 @@ impl Snapshot {
             Ok(())
         }
     }
+
+    /// Leaves the checkpoint on the file system so that the migration can be
+    /// rolled back with [`restore`].
+    ///
+    /// Does nothing if the object has been created via [`Self::none`].
+    pub fn keep(mut self) {
+        if let Some(path) = self.0.take() {
+            tracing::info!(target: "db", snapshot_path=%path.display(),
+                           "Keeping the database snapshot; use `neard database \
+                            rollback-migration` to restore it or delete it \
+                            before the next migration");
+        }
+    }
+}
+
+/// Checks whether the database at `db_path` can be replaced with the migration
+/// snapshot.
+///
+/// Returns version of the snapshot or `None` if migration snapshots are
+/// disabled or there is no snapshot.  Fails if the database is in use or if
+/// a previous rollback has been interrupted (see [`restore`]).
+///
+/// To tell whether the database is in use, it’s opened in read-write mode,
+/// which takes its lock.  Opening may make RocksDB write its own files, e.g.
+/// a new write-ahead log, but the data and the snapshot aren’t modified.
+pub(crate) fn check_restore(
+    db_path: &std::path::Path,
+    config: &crate::StoreConfig,
+    temp: Temperature,
+) -> io::Result<Option<crate::metadata::DbVersion>> {
+    let tmp_path = rollback_path(db_path);
+    if tmp_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} exists which means a previous rollback has been interrupted; \
+                 if {} is missing or incomplete, the directory holds the restored \
+                 database and should be moved in its place, otherwise it should \
+                 be deleted",
+                tmp_path.display(),
+                db_path.display()
+            ),
+        ));
+    }
+    let snapshot_path = match config.migration_snapshot.get_path(db_path) {
+        Some(snapshot_path) => snapshot_path,
+        None => return Ok(None),
+    };
+    let metadata = match super::RocksDB::get_metadata(&snapshot_path, config)? {
+        Some(metadata) => metadata,
+        None => return Ok(None),
+    };
+    // RocksDB locks the database while it’s open so this fails if the node is
+    // running.
+    core::mem::drop(super::RocksDB::open(db_path, config, crate::Mode::ReadWriteExisting, temp)?);
+    Ok(Some(metadata.version))
+}
+
+/// Replaces the database at `db_path` with the migration snapshot.
+///
+/// Path to the snapshot is determined from `config` the same way as in
+/// [`Snapshot::new`].  Returns version of the restored database or `None` if
+/// migration snapshots are disabled or there is no snapshot.  Fails if the
+/// database is in use.
+///
+/// The snapshot is first hard-linked (or copied if that’s not possible) into
+/// a temporary `<db>.rollback` directory next to the database which then
+/// replaces the database directory.  This way an interrupted rollback doesn’t
+/// leave a partial database behind.  The snapshot is deleted once it’s
+/// restored.  If the temporary directory already exists the function fails
+/// without touching anything since, depending on where the previous rollback
+/// was interrupted, it may hold the only copy of the restored database.
+pub(crate) fn restore(
+    db_path: &std::path::Path,
+    config: &crate::StoreConfig,
+    temp: Temperature,
+) -> io::Result<Option<crate::metadata::DbVersion>> {
+    let version = match check_restore(db_path, config, temp)? {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+    // check_restore returned a version so the snapshot path is set.
+    let snapshot_path = config.migration_snapshot.get_path(db_path).unwrap();
+
+    tracing::info!(target: "db", snapshot_path=%snapshot_path.display(),
+                   version, "Restoring database snapshot");
+    let tmp_path = rollback_path(db_path);
+    std::fs::create_dir_all(&tmp_path)?;
+    for entry in std::fs::read_dir(&snapshot_path)? {
+        let entry = entry?;
+        let dest = tmp_path.join(entry.file_name());
+        if std::fs::hard_link(entry.path(), &dest).is_err() {
+            std::fs::copy(entry.path(), &dest)?;
+        }
+    }
+    // With the default configuration the snapshot lives inside of the database
+    // directory so it’s removed together with it.
+    std::fs::remove_dir_all(db_path)?;
+    std::fs::rename(&tmp_path, db_path)?;
+    if snapshot_path.exists() {
+        std::fs::remove_dir_all(&snapshot_path)?;
+    }
+    Ok(Some(version))
+}
+
+/// Returns path of the temporary directory used by [`restore`].
+fn rollback_path(db_path: &std::path::Path) -> std::path::PathBuf {
+    let mut tmp_path = db_path.as_os_str().to_owned();
+    tmp_path.push(".rollback");
+    std::path::PathBuf::from(tmp_path)
 }
 
 impl std::ops::Drop for Snapshot {
@@ -204,3 +314,78 @@ fn test_snapshot_recovery() {
 
     snapshot.remove().unwrap();
 }
+
+/// Tests that a kept snapshot replaces the database on restore.
+#[test]
+fn test_snapshot_restore() {
+    const KEY: &[u8] = b"key";
+    const COL: crate::DBCol = crate::DBCol::BlockMisc;
+
+    let (_tmpdir, opener) = crate::NodeStorage::test_opener();
+
+    // Nothing to restore if there’s no snapshot.
+    assert_eq!(None, restore(&opener.path(), &opener.config(), Temperature::Hot).unwrap());
+
+    {
+        let store = opener.open().unwrap().get_store(crate::Temperature::Hot);
+        let mut update = store.store_update();
+        update.set_raw_bytes(COL, KEY, b"value");
+        update.commit().unwrap();
+    }
+
+    Snapshot::new(&opener.path(), &opener.config(), Temperature::Hot).unwrap().keep();
+
+    {
+        let store = opener.open().unwrap().get_store(crate::Temperature::Hot);
+        let mut update = store.store_update();
+        update.set_raw_bytes(COL, KEY, b"changed");
+        update.commit().unwrap();
+    }
+
+    let version = restore(&opener.path(), &opener.config(), Temperature::Hot).unwrap();
+    assert_eq!(Some(crate::metadata::DB_VERSION), version);
+    let snapshot_path = opener.config().migration_snapshot.get_path(&opener.path()).unwrap();
+    assert!(!snapshot_path.exists());
+
+    let store = opener.open().unwrap().get_store(crate::Temperature::Hot);
+    assert_eq!(Some(&b"value"[..]), store.get(COL, KEY).unwrap().as_deref());
+}
+
+/// Tests that restore refuses to run over a leftover rollback directory.
+#[test]
+fn test_snapshot_restore_interrupted() {
+    let (_tmpdir, opener) = crate::NodeStorage::test_opener();
+    core::mem::drop(opener.open().unwrap());
+    Snapshot::new(&opener.path(), &opener.config(), Temperature::Hot).unwrap().keep();
+
+    let tmp_path = rollback_path(&opener.path());
+    std::fs::create_dir_all(&tmp_path).unwrap();
+    std::fs::write(tmp_path.join("CURRENT"), b"data").unwrap();
+
+    let err = restore(&opener.path(), &opener.config(), Temperature::Hot).unwrap_err();
+    assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+    assert_eq!(b"data".to_vec(), std::fs::read(tmp_path.join("CURRENT")).unwrap());
+    let snapshot_path = opener.config().migration_snapshot.get_path(&opener.path()).unwrap();
+    assert!(snapshot_path.exists());
+}
+
+/// Tests that rollback doesn’t touch hot storage if cold snapshot is missing.
+#[test]
+fn test_rollback_migration_missing_cold_snapshot() {
+    use assert_matches::assert_matches;
+
+    let dir = tempfile::tempdir().unwrap();
+    let hot_config = crate::StoreConfig::test_config();
+    let mut cold_config = crate::StoreConfig::test_config();
+    cold_config.path = Some(dir.path().join("cold"));
+    let opener = crate::NodeStorage::opener(dir.path(), &hot_config, Some(&cold_config));
+    core::mem::drop(opener.open().unwrap());
+
+    Snapshot::new(opener.path(), &hot_config, Temperature::Hot).unwrap().keep();
+    assert_matches!(
+        opener.rollback_migration().unwrap_err(),
+        crate::StoreOpenerError::HotColdSnapshotMismatch
+    );
+    let snapshot_path = hot_config.migration_snapshot.get_path(opener.path()).unwrap();
+    assert!(snapshot_path.exists());
+}
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::io;
+use std::ops::Bound;
 use std::sync::{Arc, RwLock};
 
 use crate::db::{refcount, DBIterator, DBOp, DBSlice, DBTransaction, Database};
@@ -39,11 +40,30 @@ impl Database for TestDB {
     }
 
     fn iter_prefix<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a> {
+        refcount::iter_with_rc_logic(col, self.iter_prefix_raw_bytes(col, key_prefix))
+    }
+
+    fn iter_prefix_raw_bytes<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a> {
         let iterator = self.db.read().unwrap()[col]
             .range(key_prefix.to_vec()..)
             .take_while(move |(k, _)| k.starts_with(&key_prefix))
             .map(|(k, v)| Ok((k.clone().into_boxed_slice(), v.clone().into_boxed_slice())))
             .collect::<Vec<io::Result<_>>>();
+        Box::new(iterator.into_iter())
+    }
+
+    fn iter_range<'a>(
+        &'a self,
+        col: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> DBIterator<'a> {
+        let lower = lower_bound.map_or(Bound::Unbounded, |bound| Bound::Included(bound.to_vec()));
+        let upper = upper_bound.map_or(Bound::Unbounded, |bound| Bound::Excluded(bound.to_vec()));
+        let iterator = self.db.read().unwrap()[col]
+            .range((lower, upper))
+            .map(|(k, v)| Ok((k.clone().into_boxed_slice(), v.clone().into_boxed_slice())))
+            .collect::<Vec<io::Result<_>>>();
         refcount::iter_with_rc_logic(col, iterator.into_iter())
     }
 
@@ -92,6 +112,10 @@ impl Database for TestDB {
         Ok(())
     }
 
+    fn create_checkpoint(&self, _path: &std::path::Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "checkpoints of TestDB are not supported"))
+    }
+
     fn compact(&self) -> io::Result<()> {
         Ok(())
     }
@@ -0,0 +1,171 @@
+//! Snapshots of the database used for backups.
+//!
+//! A snapshot is a directory with a RocksDB checkpoint of the hot database in
+//! its `data` subdirectory and a manifest describing it.  The manifest records
+//! the chain and the database version so that a snapshot isn’t restored into
+//! a node of another chain or a binary which can’t open it.
+use std::io;
+use std::path::{Path, PathBuf};
+
+use near_primitives::block::Tip;
+use near_primitives::types::BlockHeight;
+
+use crate::metadata::{DbMetadata, DbVersion, DB_VERSION};
+use crate::{DBCol, Store, HEAD_KEY};
+
+/// Name of the manifest file in the snapshot directory.
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Name of the subdirectory of the snapshot directory with the checkpoint.
+const DATA_DIRNAME: &str = "data";
+
+/// Description of a snapshot stored alongside of it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotManifest {
+    /// Chain id of the node the snapshot was taken of.
+    pub chain_id: String,
+    /// Height of the head when the snapshot was started.  The snapshot may
+    /// contain a few blocks more if the node was running.
+    pub head_height: BlockHeight,
+    /// Version of the database in the snapshot.
+    pub db_version: DbVersion,
+}
+
+impl SnapshotManifest {
+    /// Reads the manifest of the snapshot in given directory.
+    pub fn read(snapshot_dir: &Path) -> io::Result<Self> {
+        let path = snapshot_dir.join(MANIFEST_FILENAME);
+        let file = std::fs::File::open(&path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        serde_json::from_reader(io::BufReader::new(file))
+            .map_err(|err| other_error(format!("{}: {}", path.display(), err)))
+    }
+
+    fn write(&self, snapshot_dir: &Path) -> io::Result<()> {
+        let file = std::fs::File::create(snapshot_dir.join(MANIFEST_FILENAME))?;
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::from)
+    }
+}
+
+/// Creates a snapshot of the store in `output` which must not exist.
+///
+/// The store is flushed before the checkpoint is created, which is consistent
+/// even if the node keeps writing to the store in the meantime.
+pub fn create_snapshot(
+    store: &Store,
+    output: &Path,
+    chain_id: &str,
+) -> io::Result<SnapshotManifest> {
+    if output.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", output.display()),
+        ));
+    }
+    let db_version = DbMetadata::read(&*store.storage)?.version;
+    let head_height = store.get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?.map_or(0, |tip| tip.height);
+    std::fs::create_dir_all(output)?;
+    store.flush()?;
+    store.create_checkpoint(&output.join(DATA_DIRNAME))?;
+    let manifest = SnapshotManifest { chain_id: chain_id.to_string(), head_height, db_version };
+    manifest.write(output)?;
+    Ok(manifest)
+}
+
+/// Restores the snapshot in `input` as the database at `db_path`.
+///
+/// Fails if the snapshot is of another chain than `chain_id`, if its database
+/// version is newer than this binary supports, or if `db_path` exists and
+/// isn’t an empty directory.  Files are hard-linked when the snapshot is on
+/// the same filesystem and copied otherwise.
+pub fn restore_snapshot(
+    input: &Path,
+    db_path: &Path,
+    chain_id: &str,
+) -> io::Result<SnapshotManifest> {
+    let manifest = SnapshotManifest::read(input)?;
+    if manifest.chain_id != chain_id {
+        return Err(other_error(format!(
+            "snapshot is of chain {} but the node is configured for chain {}",
+            manifest.chain_id, chain_id
+        )));
+    }
+    if manifest.db_version > DB_VERSION {
+        return Err(other_error(format!(
+            "snapshot has database version {} but this binary supports at most version {}",
+            manifest.db_version, DB_VERSION
+        )));
+    }
+    let data = input.join(DATA_DIRNAME);
+    if !data.is_dir() {
+        return Err(other_error(format!("{} doesn’t exist", data.display())));
+    }
+    if db_path.exists() {
+        if std::fs::read_dir(db_path)?.next().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and isn’t empty", db_path.display()),
+            ));
+        }
+        std::fs::remove_dir(db_path)?;
+    }
+
+    // Restore into a temporary directory first so that an interrupted restore
+    // doesn’t leave a partial database behind.
+    let mut tmp_path = db_path.as_os_str().to_owned();
+    tmp_path.push(".restoring");
+    let tmp_path = PathBuf::from(tmp_path);
+    if tmp_path.exists() {
+        std::fs::remove_dir_all(&tmp_path)?;
+    }
+    std::fs::create_dir_all(&tmp_path)?;
+    for entry in std::fs::read_dir(&data)? {
+        let entry = entry?;
+        let dest = tmp_path.join(entry.file_name());
+        if std::fs::hard_link(entry.path(), &dest).is_err() {
+            std::fs::copy(entry.path(), &dest)?;
+        }
+    }
+    std::fs::rename(&tmp_path, db_path)?;
+    Ok(manifest)
+}
+
+fn other_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_snapshot(dir: &Path, chain_id: &str, db_version: DbVersion) {
+        std::fs::create_dir_all(dir.join(DATA_DIRNAME)).unwrap();
+        std::fs::write(dir.join(DATA_DIRNAME).join("CURRENT"), b"MANIFEST-000001\n").unwrap();
+        let manifest =
+            SnapshotManifest { chain_id: chain_id.to_string(), head_height: 42, db_version };
+        manifest.write(dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_snapshot() {
+        let tmp = tempfile::tempdir().unwrap();
+        let snapshot = tmp.path().join("snapshot");
+        let db_path = tmp.path().join("data");
+
+        fake_snapshot(&snapshot, "testnet", DB_VERSION + 1);
+        let err = restore_snapshot(&snapshot, &db_path, "testnet").unwrap_err();
+        assert!(err.to_string().contains("database version"), "{}", err);
+
+        fake_snapshot(&snapshot, "testnet", DB_VERSION);
+        let err = restore_snapshot(&snapshot, &db_path, "mainnet").unwrap_err();
+        assert!(err.to_string().contains("chain testnet"), "{}", err);
+        assert!(!db_path.exists());
+
+        let manifest = restore_snapshot(&snapshot, &db_path, "testnet").unwrap();
+        assert_eq!(manifest.head_height, 42);
+        assert_eq!(std::fs::read(db_path.join("CURRENT")).unwrap(), b"MANIFEST-000001\n");
+
+        let err = restore_snapshot(&snapshot, &db_path, "testnet").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+}
@@ -12,8 +12,9 @@ use once_cell::sync::Lazy;
 
 pub use columns::DBCol;
 pub use db::{
-    CHUNK_TAIL_KEY, COLD_HEAD_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
-    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, TAIL_KEY,
+    ACCOUNT_OUTCOME_IDS_KEY, ARCHIVAL_TAILS_KEY, CHUNK_TAIL_KEY, COLD_HEAD_KEY, FINAL_HEAD_KEY,
+    FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, HEALTH_CHECK_KEY, LARGEST_TARGET_HEIGHT_KEY,
+    LATEST_KNOWN_KEY, REORG_LOG_KEY, TAIL_KEY,
 };
 use near_crypto::PublicKey;
 use near_o11y::pretty;
@@ -35,8 +36,8 @@ pub use crate::trie::update::{TrieUpdate, TrieUpdateIterator, TrieUpdateValuePtr
 pub use crate::trie::{
     estimator, split_state, ApplyStatePartResult, KeyForStateChanges, KeyLookupMode, NibbleSlice,
     PartialStorage, PrefetchApi, PrefetchError, RawTrieNode, RawTrieNodeWithSize, ShardTries, Trie,
-    TrieAccess, TrieCache, TrieCachingStorage, TrieChanges, TrieConfig, TrieDBStorage, TrieStorage,
-    WrappedTrieChanges,
+    TrieAccess, TrieCache, TrieCachingStorage, TrieChanges, TrieConfig, TrieDBStorage,
+    TrieDiffItem, TrieStorage, WrappedTrieChanges,
 };
 pub use flat_state::FlatStateDelta;
 
@@ -44,18 +45,22 @@ pub mod cold_storage;
 mod columns;
 pub mod config;
 pub mod db;
+pub mod db_snapshot;
 pub mod flat_state;
 pub mod metadata;
 mod metrics;
 pub mod migrations;
 mod opener;
+pub mod refcount_audit;
 mod sync_utils;
 pub mod test_utils;
 mod trie;
 
 pub use crate::config::{Mode, StoreConfig};
 pub use crate::metrics::{flat_state_metrics, FLAT_STORAGE_HEAD_HEIGHT};
-pub use crate::opener::{StoreMigrator, StoreOpener, StoreOpenerError};
+pub use crate::opener::{
+    MigrationInfo, PlannedMigration, StoreMigrator, StoreOpener, StoreOpenerError,
+};
 
 /// Specifies temperature of a storage.
 ///
@@ -314,6 +319,27 @@ impl Store {
         self.storage.iter_prefix(column, key_prefix)
     }
 
+    /// Iterates over items whose keys are within `[lower_bound, upper_bound)`.
+    /// See [`Database::iter_range`].
+    pub fn iter_range<'a>(
+        &'a self,
+        column: DBCol,
+        lower_bound: Option<&[u8]>,
+        upper_bound: Option<&[u8]>,
+    ) -> DBIterator<'a> {
+        self.storage.iter_range(column, lower_bound, upper_bound)
+    }
+
+    /// Fetches raw key/value pairs with keys starting with given prefix from
+    /// the database.  See [`Self::iter_raw_bytes`].
+    pub fn iter_prefix_raw_bytes<'a>(
+        &'a self,
+        column: DBCol,
+        key_prefix: &'a [u8],
+    ) -> DBIterator<'a> {
+        self.storage.iter_prefix_raw_bytes(column, key_prefix)
+    }
+
     pub fn iter_prefix_ser<'a, T: BorshDeserialize>(
         &'a self,
         column: DBCol,
@@ -388,6 +414,12 @@ impl Store {
         self.storage.compact()
     }
 
+    /// Creates a checkpoint of the storage at given path if supported by
+    /// storage.
+    pub fn create_checkpoint(&self, path: &std::path::Path) -> io::Result<()> {
+        self.storage.create_checkpoint(path)
+    }
+
     pub fn get_store_statistics(&self) -> Option<StoreStatistics> {
         self.storage.get_store_statistics()
     }
@@ -1043,6 +1075,21 @@ mod tests {
             }
             assert_sorted(COUNT, keys);
         }
+
+        // Check that range scan produces keys within the range in proper order.
+        for bounds in PREFIXES.windows(2) {
+            let (lower, upper) = (&bounds[0][..], &bounds[1][..]);
+            let keys = collect(store.iter_range(COLUMN, Some(lower), Some(upper)));
+            for (pos, key) in keys.iter().enumerate() {
+                assert!(
+                    lower <= &key[..] && &key[..] < upper,
+                    "Expected key in {lower:?}..{upper:?} range but got {key:?} at {pos}"
+                );
+            }
+            assert_sorted(COUNT, keys);
+        }
+        assert_sorted(PREFIXES.len() * COUNT, collect(store.iter_range(COLUMN, None, None)));
+        assert_sorted(COUNT, collect(store.iter_range(COLUMN, Some(&PREFIXES[5]), None)));
     }
 
     #[test]
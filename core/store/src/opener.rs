@@ -1,9 +1,14 @@
-use crate::db::rocksdb::snapshot::{Snapshot, SnapshotError, SnapshotRemoveError};
+use crate::db::rocksdb::snapshot::{self, Snapshot, SnapshotError, SnapshotRemoveError};
 use crate::db::rocksdb::RocksDB;
 use crate::metadata::{
     set_store_metadata, set_store_version, DbKind, DbMetadata, DbVersion, DB_VERSION,
 };
-use crate::{Mode, NodeStorage, StoreConfig, Temperature};
+use crate::{DBCol, Mode, NodeStorage, StoreConfig, Temperature};
+
+/// Rough rate at which migrations process data, used to estimate how long
+/// a migration will take.  Migrations which rewrite columns are dominated by
+/// RocksDB reads and writes so the actual rate depends mostly on the disk.
+const MIGRATION_BYTES_PER_SEC: u64 = 50 * 1024 * 1024;
 
 #[derive(Debug, thiserror::Error)]
 pub enum StoreOpenerError {
@@ -27,6 +32,11 @@ pub enum StoreOpenerError {
     #[error("Hot and cold databases must either both exist or not")]
     HotColdExistenceMismatch,
 
+    /// Migration snapshot exists for hot database but not for cold or the other
+    /// way around.
+    #[error("Hot and cold migration snapshots must either both exist or not")]
+    HotColdSnapshotMismatch,
+
     /// Hot and cold databases have different versions.
     #[error(
         "Hot database version ({hot_version}) doesn’t match \
@@ -290,8 +300,10 @@ impl<'a> StoreOpener<'a> {
         } else {
             self.ensure_kind(&storage, hot_meta)?;
         }
-        snapshots.0.remove()?;
-        snapshots.1.remove()?;
+        self.hot.finish_snapshot(snapshots.0)?;
+        if let Some(ref opener) = self.cold {
+            opener.finish_snapshot(snapshots.1)?;
+        }
         Ok(storage)
     }
 
@@ -363,19 +375,7 @@ impl<'a> StoreOpener<'a> {
             });
         }
 
-        // Figure out if we have migrator which supports the database version.
-        let migrator = self.migrator.ok_or(StoreOpenerError::DbVersionMismatch {
-            got: metadata.version,
-            want: DB_VERSION,
-        })?;
-        if let Err(release) = migrator.check_support(metadata.version) {
-            return Err(StoreOpenerError::DbVersionTooOld {
-                got: metadata.version,
-                want: DB_VERSION,
-                latest_release: release,
-            });
-        }
-
+        let migrator = self.get_migrator(metadata.version)?;
         let hot_snapshot = self.hot.snapshot()?;
         let cold_snapshot = match self.cold {
             None => Snapshot::none(),
@@ -401,6 +401,86 @@ impl<'a> StoreOpener<'a> {
         Ok((hot_snapshot, cold_snapshot))
     }
 
+    /// Returns migrator which supports migrating from given database version.
+    fn get_migrator(&self, version: DbVersion) -> Result<&dyn StoreMigrator, StoreOpenerError> {
+        let migrator = self
+            .migrator
+            .ok_or(StoreOpenerError::DbVersionMismatch { got: version, want: DB_VERSION })?;
+        if let Err(release) = migrator.check_support(version) {
+            return Err(StoreOpenerError::DbVersionTooOld {
+                got: version,
+                want: DB_VERSION,
+                latest_release: release,
+            });
+        }
+        Ok(migrator)
+    }
+
+    /// Returns migrations which opening the storage in read-write mode would
+    /// perform together with an estimate of how long each would take.
+    ///
+    /// The estimate is based on the size of the columns each migration
+    /// processes in the hot database.  Doesn’t modify the database.  Returns an
+    /// empty list if the database is up to date and fails the same way opening
+    /// the storage would if it cannot be migrated.
+    pub fn plan_migrations(&self) -> Result<Vec<PlannedMigration>, StoreOpenerError> {
+        let metadata = self.hot.get_metadata()?.ok_or(StoreOpenerError::DbDoesNotExist)?;
+        if metadata.version == DB_VERSION {
+            return Ok(Vec::new());
+        } else if metadata.version > DB_VERSION {
+            return Err(StoreOpenerError::DbVersionTooNew {
+                got: metadata.version,
+                want: DB_VERSION,
+            });
+        }
+        let migrator = self.get_migrator(metadata.version)?;
+        let sizes = RocksDB::get_column_sizes(&self.hot.path, self.hot.config)?;
+        Ok((metadata.version..DB_VERSION)
+            .map(|version| {
+                let info = migrator.describe(version);
+                let bytes: u64 = info.columns.iter().map(|col| sizes[*col]).sum();
+                let estimated_duration =
+                    std::time::Duration::from_secs(bytes / MIGRATION_BYTES_PER_SEC);
+                PlannedMigration { version, info, bytes, estimated_duration }
+            })
+            .collect())
+    }
+
+    /// Replaces the databases with the snapshots kept after the last migration.
+    ///
+    /// The snapshots are only kept if `keep_migration_snapshot` is set in the
+    /// store configuration.  Returns the version of the restored hot database
+    /// or `None` if there was no snapshot to restore.  Fails if the databases
+    /// are in use.
+    ///
+    /// With cold storage configured, both snapshots are checked before either
+    /// database is touched so that hot and cold storage are never left at
+    /// different versions.
+    pub fn rollback_migration(&self) -> Result<Option<DbVersion>, StoreOpenerError> {
+        let hot_version = self.hot.check_restore_snapshot()?;
+        if let Some(ref opener) = self.cold {
+            match (hot_version, opener.check_restore_snapshot()?) {
+                (None, None) => {}
+                (Some(hot_version), Some(cold_version)) if hot_version != cold_version => {
+                    return Err(StoreOpenerError::HotColdVersionMismatch {
+                        hot_version,
+                        cold_version,
+                    })
+                }
+                (Some(_), Some(_)) => {}
+                _ => return Err(StoreOpenerError::HotColdSnapshotMismatch),
+            }
+        }
+        let version = match self.hot.restore_snapshot()? {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+        if let Some(ref opener) = self.cold {
+            opener.restore_snapshot()?;
+        }
+        Ok(Some(version))
+    }
+
     fn open_storage(
         &self,
         mode: Mode,
@@ -500,6 +580,57 @@ impl<'a> DBOpener<'a> {
     fn snapshot(&self) -> Result<Snapshot, SnapshotError> {
         Snapshot::new(&self.path, &self.config, self.temp)
     }
+
+    /// Deletes or keeps the snapshot after a successful migration depending on
+    /// `keep_migration_snapshot` configuration option.
+    fn finish_snapshot(&self, snapshot: Snapshot) -> Result<(), SnapshotRemoveError> {
+        if self.config.keep_migration_snapshot {
+            snapshot.keep();
+            Ok(())
+        } else {
+            snapshot.remove()
+        }
+    }
+
+    /// Checks whether the database can be replaced with the migration snapshot;
+    /// returns version of the snapshot if there is one.
+    fn check_restore_snapshot(&self) -> std::io::Result<Option<DbVersion>> {
+        snapshot::check_restore(&self.path, &self.config, self.temp)
+    }
+
+    /// Replaces the database with the migration snapshot if there is one.
+    fn restore_snapshot(&self) -> std::io::Result<Option<DbVersion>> {
+        snapshot::restore(&self.path, &self.config, self.temp)
+    }
+}
+
+/// Description of a single database migration.
+#[derive(Clone, Debug)]
+pub struct MigrationInfo {
+    /// Short human-readable description of what the migration does.
+    pub summary: &'static str,
+
+    /// Columns which the migration reads or rewrites.  Used to estimate how
+    /// long the migration will take.  Empty if the migration only bumps the
+    /// version or deletes whole columns.
+    pub columns: &'static [DBCol],
+}
+
+/// Migration which would be performed when opening the storage; see
+/// [`StoreOpener::plan_migrations`].
+#[derive(Clone, Debug)]
+pub struct PlannedMigration {
+    /// Version the migration starts at; it migrates to `version + 1`.
+    pub version: DbVersion,
+
+    /// Description of the migration as given by the migrator.
+    pub info: MigrationInfo,
+
+    /// Total size of the columns the migration processes.
+    pub bytes: u64,
+
+    /// Rough estimate of how long the migration will take.
+    pub estimated_duration: std::time::Duration,
 }
 
 pub trait StoreMigrator {
@@ -524,4 +655,95 @@ pub trait StoreMigrator {
     /// check support via [`Self::check_support`] method) or if it’s greater or
     /// equal to [`DB_VERSION`].
     fn migrate(&self, storage: &NodeStorage, version: DbVersion) -> anyhow::Result<()>;
+
+    /// Describes the migration from given version to the next one.
+    ///
+    /// Used to report the migrations without performing them.  Has the same
+    /// requirements on `version` as [`Self::migrate`].
+    fn describe(&self, version: DbVersion) -> MigrationInfo;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    /// Migrator supporting the last two versions, of which only the older one
+    /// processes a column.
+    struct TestMigrator;
+
+    impl StoreMigrator for TestMigrator {
+        fn check_support(&self, version: DbVersion) -> Result<(), &'static str> {
+            if version + 2 < DB_VERSION {
+                Err("1.0")
+            } else {
+                Ok(())
+            }
+        }
+
+        fn migrate(&self, _storage: &NodeStorage, _version: DbVersion) -> anyhow::Result<()> {
+            unreachable!("planning migrations doesn’t perform them")
+        }
+
+        fn describe(&self, version: DbVersion) -> MigrationInfo {
+            if version + 1 == DB_VERSION {
+                MigrationInfo { summary: "second", columns: &[] }
+            } else {
+                MigrationInfo { summary: "first", columns: &[DBCol::BlockMisc] }
+            }
+        }
+    }
+
+    /// Creates a database with some data flushed to disk and sets its version.
+    fn test_opener_at_version(version: DbVersion) -> (tempfile::TempDir, StoreOpener<'static>) {
+        let (tmpdir, opener) = NodeStorage::test_opener();
+        let storage = opener.open().unwrap();
+        let store = storage.get_hot_store();
+        let mut update = store.store_update();
+        update.set_raw_bytes(DBCol::BlockMisc, b"key", &[0; 1000]);
+        update.commit().unwrap();
+        store.flush().unwrap();
+        storage.set_version(version).unwrap();
+        (tmpdir, opener)
+    }
+
+    #[test]
+    fn test_plan_migrations() {
+        let migrator = TestMigrator;
+
+        let (_tmpdir, opener) = NodeStorage::test_opener();
+        let opener = opener.with_migrator(&migrator);
+        assert_matches!(opener.plan_migrations(), Err(StoreOpenerError::DbDoesNotExist));
+
+        let (_tmpdir, opener) = test_opener_at_version(DB_VERSION);
+        assert!(opener.with_migrator(&migrator).plan_migrations().unwrap().is_empty());
+
+        let (_tmpdir, opener) = test_opener_at_version(DB_VERSION - 2);
+        let opener = opener.with_migrator(&migrator);
+        let plan = opener.plan_migrations().unwrap();
+        assert_eq!(
+            vec![(DB_VERSION - 2, "first"), (DB_VERSION - 1, "second")],
+            plan.iter().map(|m| (m.version, m.info.summary)).collect::<Vec<_>>()
+        );
+        assert!(plan[0].bytes > 0);
+        assert_eq!(0, plan[1].bytes);
+        assert_eq!(std::time::Duration::ZERO, plan[1].estimated_duration);
+        // Nothing has been migrated.
+        assert_eq!(2, opener.plan_migrations().unwrap().len());
+
+        let (_tmpdir, opener) = test_opener_at_version(DB_VERSION - 3);
+        assert_matches!(
+            opener.with_migrator(&migrator).plan_migrations(),
+            Err(StoreOpenerError::DbVersionTooOld { latest_release: "1.0", .. })
+        );
+
+        let (_tmpdir, opener) = test_opener_at_version(DB_VERSION - 1);
+        assert_matches!(opener.plan_migrations(), Err(StoreOpenerError::DbVersionMismatch { .. }));
+
+        let (_tmpdir, opener) = test_opener_at_version(DB_VERSION + 1);
+        assert_matches!(
+            opener.with_migrator(&migrator).plan_migrations(),
+            Err(StoreOpenerError::DbVersionTooNew { .. })
+        );
+    }
 }
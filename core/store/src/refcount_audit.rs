@@ -0,0 +1,547 @@
+//! Audit of reference counts of trie nodes.
+//!
+//! Trie nodes and values are stored in [`DBCol::State`] with a reference count
+//! which is increased each time a trie update inserts them and decreased when
+//! garbage collection discards a state.  If the counts drift, for example
+//! because a crash interrupted a write, nodes which are still in use may get
+//! deleted, which leads to ‘missing trie node’ errors much later, or nodes
+//! which are no longer in use are never deleted.
+//!
+//! The expected reference count of a node is the number of paths leading to it
+//! from the state root of the shard at the head of the chain plus the number of
+//! times garbage collection of the retained blocks is still going to decrease
+//! it, that is deletions of the blocks on the canonical chain and insertions of
+//! the blocks on forks.  The audit only makes sense on a stopped node since the
+//! head must not move while the counts are computed.
+//!
+//! Only shards with state at the head of the chain are audited.  The state of
+//! shards which are being caught up for the next epoch is incomplete until the
+//! catch-up finishes, so the audit must not run while a catch-up is in progress
+//! (see [`catchup_in_progress`]).
+use std::collections::HashMap;
+use std::io;
+use std::ops::RangeInclusive;
+
+use borsh::BorshDeserialize;
+use near_primitives::block::{BlockHeader, Tip};
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::{get_block_shard_uid, get_block_shard_uid_rev, ShardUId};
+use near_primitives::types::chunk_extra::ChunkExtra;
+use near_primitives::utils::index_to_bytes;
+
+use crate::db::refcount::decode_value_with_rc;
+use crate::{DBCol, RawTrieNode, RawTrieNodeWithSize, Store, TrieChanges, HEAD_KEY};
+
+/// Node whose reference count differs from the expected one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefcountDiscrepancy {
+    /// Hash of the trie node or value.
+    pub hash: CryptoHash,
+    /// Reference count computed from the retained state.
+    pub expected: u64,
+    /// Reference count stored in the database.  Zero if the node is missing.
+    pub actual: i64,
+    /// Data of the node if it’s still in the database.
+    payload: Option<Vec<u8>>,
+}
+
+impl RefcountDiscrepancy {
+    /// Whether the node is in use but its data has already been deleted.
+    ///
+    /// Such nodes cannot be repaired; the state of the shard has to be
+    /// downloaded again with state sync.
+    pub fn is_missing(&self) -> bool {
+        self.expected > 0 && self.payload.is_none()
+    }
+}
+
+/// Result of auditing the reference counts of a single shard.
+#[derive(Clone, Debug)]
+pub struct RefcountAudit {
+    pub shard_uid: ShardUId,
+    /// State root of the shard at the head of the chain.
+    pub state_root: CryptoHash,
+    /// Number of nodes and values in the database for the shard.
+    pub nodes_checked: u64,
+    pub discrepancies: Vec<RefcountDiscrepancy>,
+}
+
+impl RefcountAudit {
+    /// Number of nodes in use whose data has already been deleted.
+    pub fn missing(&self) -> usize {
+        self.discrepancies.iter().filter(|d| d.is_missing()).count()
+    }
+}
+
+/// Maximal number of nodes whose expected reference counts are kept in memory
+/// at once.  Shards with more nodes are audited in batches of nodes whose
+/// hashes start with the same bytes, each batch traversing the trie again.
+const MAX_BATCH_NODES: u64 = 10_000_000;
+
+/// Returns whether the state of some shards is being caught up, in which case
+/// these shards are not listed by [`shards_at_head`] yet, or their state at the
+/// head may be incomplete.
+pub fn catchup_in_progress(store: &Store) -> io::Result<bool> {
+    for col in [DBCol::BlocksToCatchup, DBCol::StateDlInfos] {
+        if let Some(item) = store.iter(col).next() {
+            item?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Returns shards which have state at the head of the chain.
+///
+/// Shards which are being caught up for the next epoch are not included.
+pub fn shards_at_head(store: &Store) -> io::Result<Vec<ShardUId>> {
+    let head = get_head(store)?;
+    let mut shard_uids = Vec::new();
+    for item in store.iter_prefix(DBCol::ChunkExtra, head.last_block_hash.as_ref()) {
+        let (key, _) = item?;
+        let (_, shard_uid) = get_block_shard_uid_rev(&key).map_err(other_error)?;
+        shard_uids.push(shard_uid);
+    }
+    Ok(shard_uids)
+}
+
+/// Compares reference counts of the nodes of given shard with the expected
+/// ones.
+///
+/// Keeps the expected counts of at most about [`MAX_BATCH_NODES`] nodes in
+/// memory at once.
+pub fn audit_refcounts(store: &Store, shard_uid: ShardUId) -> io::Result<RefcountAudit> {
+    audit_refcounts_in_batches(store, shard_uid, MAX_BATCH_NODES)
+}
+
+fn audit_refcounts_in_batches(
+    store: &Store,
+    shard_uid: ShardUId,
+    max_batch_nodes: u64,
+) -> io::Result<RefcountAudit> {
+    let head = get_head(store)?;
+    let state_root = match store.get_ser::<ChunkExtra>(
+        DBCol::ChunkExtra,
+        &get_block_shard_uid(&head.last_block_hash, &shard_uid),
+    )? {
+        Some(chunk_extra) => *chunk_extra.state_root(),
+        None => {
+            return Err(other_error(format!("no state of shard {} at the head", shard_uid)));
+        }
+    };
+
+    let mut nodes = 0;
+    for item in store.iter_prefix_raw_bytes(DBCol::State, &shard_uid.to_bytes()) {
+        if item?.0.len() == 40 {
+            nodes += 1;
+        }
+    }
+    // Batches are ranges of the first byte of the hashes.
+    let num_batches = (nodes / max_batch_nodes.max(1) + 1).min(256);
+    let mut audit =
+        RefcountAudit { shard_uid, state_root, nodes_checked: 0, discrepancies: Vec::new() };
+    for batch in 0..num_batches {
+        let first = batch * 256 / num_batches;
+        let last = (batch + 1) * 256 / num_batches - 1;
+        audit_batch(store, &mut audit, first as u8..=last as u8)?;
+    }
+    Ok(audit)
+}
+
+/// Audits the nodes whose hashes start with a byte in `batch`.
+fn audit_batch(
+    store: &Store,
+    audit: &mut RefcountAudit,
+    batch: RangeInclusive<u8>,
+) -> io::Result<()> {
+    let shard_uid = audit.shard_uid;
+    let mut expected = HashMap::<CryptoHash, u64>::new();
+    count_trie_references(store, shard_uid, &audit.state_root, &batch, &mut expected)?;
+    count_pending_changes(store, shard_uid, &batch, &mut expected)?;
+
+    for first_byte in batch {
+        let prefix = [&shard_uid.to_bytes()[..], &[first_byte]].concat();
+        for item in store.iter_prefix_raw_bytes(DBCol::State, &prefix) {
+            let (key, value) = item?;
+            if key.len() != 40 {
+                continue;
+            }
+            audit.nodes_checked += 1;
+            let hash = CryptoHash::try_from(&key[8..]).map_err(other_error)?;
+            let want = expected.remove(&hash).unwrap_or(0);
+            let (payload, actual) = decode_value_with_rc(&value);
+            if actual.max(0) as u64 != want || (want == 0 && actual < 0) {
+                let payload = payload.map(<[u8]>::to_vec);
+                audit.discrepancies.push(RefcountDiscrepancy {
+                    hash,
+                    expected: want,
+                    actual,
+                    payload,
+                });
+            }
+        }
+    }
+    // Whatever is left is expected but not in the database at all.
+    audit.discrepancies.extend(expected.into_iter().map(|(hash, expected)| RefcountDiscrepancy {
+        hash,
+        expected,
+        actual: 0,
+        payload: None,
+    }));
+    Ok(())
+}
+
+/// Adds `rc` references to `hash` if it belongs to `batch`.
+fn add_references(
+    counts: &mut HashMap<CryptoHash, u64>,
+    batch: &RangeInclusive<u8>,
+    hash: CryptoHash,
+    rc: u64,
+) {
+    if batch.contains(&hash.as_ref()[0]) {
+        *counts.entry(hash).or_default() += rc;
+    }
+}
+
+/// Sets reference counts of the nodes found by [`audit_refcounts`] to the
+/// expected values.  Nodes which are no longer in use are deleted.
+///
+/// Fails without modifying the database if any of the nodes in use is missing.
+/// In that case parts of the trie couldn’t be traversed so nodes below the
+/// missing ones would be wrongly considered unused.  Returns number of repaired
+/// nodes.
+pub fn repair_refcounts(store: &Store, audit: &RefcountAudit) -> io::Result<usize> {
+    let missing = audit.missing();
+    if missing > 0 {
+        return Err(other_error(format!(
+            "{} nodes of shard {} are missing; the state must be synced again",
+            missing, audit.shard_uid
+        )));
+    }
+    let mut update = store.store_update();
+    for discrepancy in &audit.discrepancies {
+        let key = get_state_key(audit.shard_uid, &discrepancy.hash);
+        let value = match (&discrepancy.payload, discrepancy.expected) {
+            (_, 0) => Vec::new(),
+            (Some(payload), rc) => [payload.as_slice(), &(rc as i64).to_le_bytes()].concat(),
+            (None, _) => unreachable!(),
+        };
+        update.set_raw_bytes(DBCol::State, &key, &value);
+    }
+    update.commit()?;
+    Ok(audit.discrepancies.len())
+}
+
+/// Adds references to the nodes and values of `batch` reachable from `root`.
+///
+/// Nodes are counted once for each path leading to them since that’s how many
+/// times they have been inserted when the trie was built.
+fn count_trie_references(
+    store: &Store,
+    shard_uid: ShardUId,
+    root: &CryptoHash,
+    batch: &RangeInclusive<u8>,
+    counts: &mut HashMap<CryptoHash, u64>,
+) -> io::Result<()> {
+    let mut stack = Vec::new();
+    if root != &CryptoHash::default() {
+        stack.push(*root);
+    }
+    while let Some(hash) = stack.pop() {
+        add_references(counts, batch, hash, 1);
+        let bytes = match store.get(DBCol::State, &get_state_key(shard_uid, &hash))? {
+            Some(bytes) => bytes,
+            // Reported as a discrepancy by the caller.
+            None => continue,
+        };
+        let node = RawTrieNodeWithSize::decode(&bytes)
+            .map_err(|err| other_error(format!("failed to decode node {}: {}", hash, err)))?;
+        match node.node {
+            RawTrieNode::Leaf(_, _, value) => add_references(counts, batch, value, 1),
+            RawTrieNode::Branch(children, value) => {
+                stack.extend(children.iter().flatten());
+                if let Some((_, value)) = value {
+                    add_references(counts, batch, value, 1);
+                }
+            }
+            RawTrieNode::Extension(_, child) => stack.push(child),
+        }
+    }
+    Ok(())
+}
+
+/// Adds reference count changes of the nodes of `batch` which garbage
+/// collection of the retained blocks is still going to apply.
+fn count_pending_changes(
+    store: &Store,
+    shard_uid: ShardUId,
+    batch: &RangeInclusive<u8>,
+    counts: &mut HashMap<CryptoHash, u64>,
+) -> io::Result<()> {
+    for item in store.iter(DBCol::TrieChanges) {
+        let (key, value) = item?;
+        let (block_hash, key_shard_uid) = get_block_shard_uid_rev(&key).map_err(other_error)?;
+        if key_shard_uid != shard_uid {
+            continue;
+        }
+        let changes = TrieChanges::try_from_slice(&value)?;
+        let pending = if is_canonical(store, &block_hash)? {
+            changes.deletions()
+        } else {
+            changes.insertions()
+        };
+        for change in pending {
+            add_references(counts, batch, *change.hash(), u64::from(change.rc().get()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the block is on the canonical chain.
+fn is_canonical(store: &Store, block_hash: &CryptoHash) -> io::Result<bool> {
+    let header = match store.get_ser::<BlockHeader>(DBCol::BlockHeader, block_hash.as_ref())? {
+        Some(header) => header,
+        None => return Ok(false),
+    };
+    let canonical =
+        store.get_ser::<CryptoHash>(DBCol::BlockHeight, &index_to_bytes(header.height()))?;
+    Ok(canonical.as_ref() == Some(block_hash))
+}
+
+fn get_head(store: &Store) -> io::Result<Tip> {
+    store
+        .get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?
+        .ok_or_else(|| other_error("the database has no head".to_string()))
+}
+
+fn get_state_key(shard_uid: ShardUId, hash: &CryptoHash) -> [u8; 40] {
+    let mut key = [0; 40];
+    key[..8].copy_from_slice(&shard_uid.to_bytes());
+    key[8..].copy_from_slice(hash.as_ref());
+    key
+}
+
+fn other_error(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::block::Tip;
+    use near_primitives::block_header::BlockHeader;
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+    use near_primitives::time::Clock;
+    use near_primitives::types::chunk_extra::ChunkExtra;
+    use near_primitives::types::{BlockHeight, EpochId};
+    use near_primitives::utils::index_to_bytes;
+    use near_primitives::version::PROTOCOL_VERSION;
+
+    use super::{
+        audit_refcounts, audit_refcounts_in_batches, catchup_in_progress, get_state_key,
+        repair_refcounts, shards_at_head,
+    };
+    use crate::test_utils::{create_tries, test_populate_trie};
+    use crate::{DBCol, ShardTries, Store, Trie, TrieChanges, HEAD_KEY};
+
+    #[test]
+    fn test_audit_and_repair() {
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let changes = (0..50u8).map(|i| (vec![i, i / 7], Some(vec![i; 10]))).collect();
+        let root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes);
+
+        let store = tries.get_store();
+        let head_hash = hash(b"head");
+        let mut update = store.store_update();
+        let tip = Tip {
+            height: 1,
+            last_block_hash: head_hash,
+            prev_block_hash: CryptoHash::default(),
+            epoch_id: EpochId::default(),
+            next_epoch_id: EpochId::default(),
+        };
+        update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip).unwrap();
+        update
+            .set_ser(
+                DBCol::ChunkExtra,
+                &get_block_shard_uid(&head_hash, &shard_uid),
+                &ChunkExtra::new_with_only_state_root(&root),
+            )
+            .unwrap();
+        update.commit().unwrap();
+        assert_eq!(shards_at_head(&store).unwrap(), vec![shard_uid]);
+
+        let audit = audit_refcounts(&store, shard_uid).unwrap();
+        assert_eq!(audit.state_root, root);
+        assert!(audit.nodes_checked > 50);
+        assert_eq!(audit.discrepancies, vec![]);
+
+        // Leak a value of the trie and add a node which isn’t referenced.
+        let value = vec![3u8; 10];
+        let orphan = b"orphan".to_vec();
+        let mut update = store.store_update();
+        update.increment_refcount(DBCol::State, &get_state_key(shard_uid, &hash(&value)), &value);
+        update.increment_refcount(DBCol::State, &get_state_key(shard_uid, &hash(&orphan)), &orphan);
+        update.commit().unwrap();
+
+        let audit = audit_refcounts(&store, shard_uid).unwrap();
+        let mut found: Vec<_> =
+            audit.discrepancies.iter().map(|d| (d.hash, d.expected, d.actual)).collect();
+        found.sort();
+        let mut want = vec![(hash(&value), 1, 2), (hash(&orphan), 0, 1)];
+        want.sort();
+        assert_eq!(found, want);
+        assert_eq!(audit.missing(), 0);
+
+        // Auditing in batches of a few nodes gives the same result.
+        let batched = audit_refcounts_in_batches(&store, shard_uid, 10).unwrap();
+        assert_eq!(batched.nodes_checked, audit.nodes_checked);
+        let mut found: Vec<_> =
+            batched.discrepancies.iter().map(|d| (d.hash, d.expected, d.actual)).collect();
+        found.sort();
+        assert_eq!(found, want);
+
+        assert_eq!(repair_refcounts(&store, &audit).unwrap(), 2);
+        assert_eq!(audit_refcounts(&store, shard_uid).unwrap().discrepancies, vec![]);
+        assert_eq!(
+            None,
+            store.get(DBCol::State, &get_state_key(shard_uid, &hash(&orphan))).unwrap()
+        );
+
+        // Deleting a node which is in use can’t be repaired.
+        let mut update = store.store_update();
+        update.decrement_refcount(DBCol::State, &get_state_key(shard_uid, &root));
+        update.commit().unwrap();
+        let audit = audit_refcounts(&store, shard_uid).unwrap();
+        assert_eq!(audit.missing(), 1);
+        assert!(repair_refcounts(&store, &audit).is_err());
+        let batched = audit_refcounts_in_batches(&store, shard_uid, 10).unwrap();
+        assert_eq!(batched.missing(), 1);
+    }
+
+    #[test]
+    fn test_catchup_in_progress() {
+        let store = create_tries().get_store();
+        assert!(!catchup_in_progress(&store).unwrap());
+        let mut update = store.store_update();
+        update
+            .set_ser(DBCol::BlocksToCatchup, hash(b"prev").as_ref(), &vec![hash(b"block")])
+            .unwrap();
+        update.commit().unwrap();
+        assert!(catchup_in_progress(&store).unwrap());
+    }
+
+    /// Stores a block header at `height` and returns its hash.  The block is
+    /// canonical if it's the one stored for its height.
+    fn save_header(store: &Store, height: BlockHeight, canonical: bool) -> CryptoHash {
+        let header = BlockHeader::genesis(
+            PROTOCOL_VERSION,
+            height,
+            hash(&[canonical as u8]),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            1,
+            CryptoHash::default(),
+            Clock::utc(),
+            0,
+            0,
+            CryptoHash::default(),
+        );
+        let mut update = store.store_update();
+        update.set_ser(DBCol::BlockHeader, header.hash().as_ref(), &header).unwrap();
+        if canonical {
+            update.set_ser(DBCol::BlockHeight, &index_to_bytes(height), header.hash()).unwrap();
+        }
+        update.commit().unwrap();
+        *header.hash()
+    }
+
+    /// Applies the insertions of `changes` the way block processing does and
+    /// keeps the changes for garbage collection.
+    fn apply_block(
+        tries: &ShardTries,
+        shard_uid: ShardUId,
+        block_hash: &CryptoHash,
+        changes: &TrieChanges,
+    ) {
+        let mut update = tries.store_update();
+        tries.apply_insertions(changes, shard_uid, &mut update);
+        update
+            .set_ser(DBCol::TrieChanges, &get_block_shard_uid(block_hash, &shard_uid), changes)
+            .unwrap();
+        update.commit().unwrap();
+    }
+
+    /// Garbage collection of retained blocks is still going to decrease the
+    /// counts of the nodes deleted on the canonical chain and of the nodes
+    /// inserted on forks, so these must not be reported nor repaired.
+    #[test]
+    fn test_audit_with_pending_changes() {
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let store = tries.get_store();
+        let changes = (0..50u8).map(|i| (vec![i, i / 7], Some(vec![i; 10]))).collect();
+        let genesis_root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes);
+        let trie = tries.get_trie_for_shard(shard_uid, genesis_root);
+
+        let canonical_changes = trie
+            .update((0..50u8).step_by(3).map(|i| (vec![i, i / 7], Some(vec![i + 1; 10]))))
+            .unwrap();
+        let canonical = save_header(&store, 1, true);
+        apply_block(&tries, shard_uid, &canonical, &canonical_changes);
+        let fork_changes =
+            trie.update((0..50u8).step_by(5).map(|i| (vec![i, i / 7], None))).unwrap();
+        let fork = save_header(&store, 1, false);
+        apply_block(&tries, shard_uid, &fork, &fork_changes);
+
+        let head_root = canonical_changes.new_root;
+        let mut update = store.store_update();
+        let tip = Tip {
+            height: 1,
+            last_block_hash: canonical,
+            prev_block_hash: CryptoHash::default(),
+            epoch_id: EpochId::default(),
+            next_epoch_id: EpochId::default(),
+        };
+        update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip).unwrap();
+        update
+            .set_ser(
+                DBCol::ChunkExtra,
+                &get_block_shard_uid(&canonical, &shard_uid),
+                &ChunkExtra::new_with_only_state_root(&head_root),
+            )
+            .unwrap();
+        update.commit().unwrap();
+
+        let audit = audit_refcounts(&store, shard_uid).unwrap();
+        assert_eq!(audit.state_root, head_root);
+        assert_eq!(audit.discrepancies, vec![]);
+
+        // Only the leaked value is repaired.
+        let value = vec![5u8; 10];
+        let mut update = store.store_update();
+        update.increment_refcount(DBCol::State, &get_state_key(shard_uid, &hash(&value)), &value);
+        update.commit().unwrap();
+        let audit = audit_refcounts(&store, shard_uid).unwrap();
+        let found: Vec<_> =
+            audit.discrepancies.iter().map(|d| (d.hash, d.expected, d.actual)).collect();
+        assert_eq!(found, vec![(hash(&value), 1, 2)]);
+        assert_eq!(repair_refcounts(&store, &audit).unwrap(), 1);
+
+        // Once garbage collection has applied the pending changes, exactly
+        // the nodes of the state at the head are left.
+        let mut update = tries.store_update();
+        tries.apply_deletions(&canonical_changes, shard_uid, &mut update);
+        tries.revert_insertions(&fork_changes, shard_uid, &mut update);
+        update.delete(DBCol::TrieChanges, &get_block_shard_uid(&canonical, &shard_uid));
+        update.delete(DBCol::TrieChanges, &get_block_shard_uid(&fork, &shard_uid));
+        update.commit().unwrap();
+        let audit = audit_refcounts(&store, shard_uid).unwrap();
+        assert_eq!(audit.discrepancies, vec![]);
+        let trie = tries.get_trie_for_shard(shard_uid, head_root);
+        assert_eq!(trie.get(&[3, 0]).unwrap(), Some(vec![4; 10]));
+        assert_eq!(trie.get(&[5, 0]).unwrap(), Some(vec![5; 10]));
+    }
+}
@@ -0,0 +1,305 @@
+//! Comparison of two tries.
+//!
+//! Subtrees with the same hash are identical so the comparison only descends
+//! into the parts of the tries which differ.  Comparing two state roots of
+//! a shard thus reads about as many nodes as were changed between them rather
+//! than the whole state.
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::StateRoot;
+
+use crate::trie::nibble_slice::NibbleSlice;
+use crate::trie::RawTrieNode;
+use crate::{StorageError, Trie};
+
+/// A key whose value differs between two tries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieDiffItem {
+    pub key: Vec<u8>,
+    /// Value in the old trie, `None` if the key was added.
+    pub old_value: Option<Vec<u8>>,
+    /// Value in the new trie, `None` if the key was removed.
+    pub new_value: Option<Vec<u8>>,
+}
+
+/// Part of a trie starting at some position of the key.
+///
+/// Leaves and extensions span several nibbles of the key.  If the other trie
+/// branches within them, the rest of the node is compared on its own.
+#[derive(Clone, PartialEq, Eq)]
+enum Subtree {
+    Node(CryptoHash),
+    /// Remaining nibbles of a leaf and hash of its value.
+    Leaf(Vec<u8>, CryptoHash),
+    /// Remaining nibbles of an extension, never empty, and its child.
+    Extension(Vec<u8>, CryptoHash),
+}
+
+/// A subtree split into the hash of the value at its position and the
+/// subtrees at the following nibble.
+#[derive(Default)]
+struct Level {
+    value: Option<CryptoHash>,
+    children: [Option<Subtree>; 16],
+}
+
+impl Level {
+    fn set_leaf(&mut self, nibbles: &[u8], value: CryptoHash) {
+        match nibbles.split_first() {
+            None => self.value = Some(value),
+            Some((&first, rest)) => {
+                self.children[first as usize] = Some(Subtree::Leaf(rest.to_vec(), value))
+            }
+        }
+    }
+
+    fn set_extension(&mut self, nibbles: &[u8], child: CryptoHash) -> Result<(), StorageError> {
+        let (&first, rest) = nibbles.split_first().ok_or_else(|| {
+            StorageError::StorageInconsistentState("Extension node with an empty key".to_string())
+        })?;
+        self.children[first as usize] = Some(if rest.is_empty() {
+            Subtree::Node(child)
+        } else {
+            Subtree::Extension(rest.to_vec(), child)
+        });
+        Ok(())
+    }
+}
+
+impl Trie {
+    /// Returns the keys starting with `prefix` whose values differ between
+    /// this trie and the trie with root `new_root`, ordered by key.
+    ///
+    /// Nodes of both tries are read from the storage of this one, so
+    /// `new_root` must be a state root of the same shard.
+    pub fn diff(
+        &self,
+        new_root: &StateRoot,
+        prefix: &[u8],
+    ) -> Result<Vec<TrieDiffItem>, StorageError> {
+        let prefix: Vec<u8> = NibbleSlice::new(prefix).iter().collect();
+        let mut key_nibbles = Vec::with_capacity(64);
+        let mut result = Vec::new();
+        self.diff_subtrees(
+            Some(Subtree::Node(self.root)),
+            Some(Subtree::Node(*new_root)),
+            &prefix,
+            &mut key_nibbles,
+            &mut result,
+        )?;
+        Ok(result)
+    }
+
+    fn diff_subtrees(
+        &self,
+        old: Option<Subtree>,
+        new: Option<Subtree>,
+        prefix: &[u8],
+        key_nibbles: &mut Vec<u8>,
+        result: &mut Vec<TrieDiffItem>,
+    ) -> Result<(), StorageError> {
+        if old == new {
+            return Ok(());
+        }
+        let (old, new) = match (old, new) {
+            (Some(old @ (Subtree::Leaf(..) | Subtree::Extension(..))), None) => {
+                return self.diff_missing_subtree(old, true, prefix, key_nibbles, result);
+            }
+            (None, Some(new @ (Subtree::Leaf(..) | Subtree::Extension(..)))) => {
+                return self.diff_missing_subtree(new, false, prefix, key_nibbles, result);
+            }
+            (old, new) => (self.expand_subtree(old)?, self.expand_subtree(new)?),
+        };
+        let depth = key_nibbles.len();
+        if old.value != new.value && depth >= prefix.len() {
+            result.push(TrieDiffItem {
+                key: key_from_nibbles(key_nibbles),
+                old_value: old.value.map(|hash| self.retrieve_value(&hash)).transpose()?,
+                new_value: new.value.map(|hash| self.retrieve_value(&hash)).transpose()?,
+            });
+        }
+        for (idx, (old, new)) in old.children.into_iter().zip(new.children).enumerate() {
+            if depth < prefix.len() && prefix[depth] as usize != idx {
+                continue;
+            }
+            key_nibbles.push(idx as u8);
+            self.diff_subtrees(old, new, prefix, key_nibbles, result)?;
+            key_nibbles.pop();
+        }
+        Ok(())
+    }
+
+    /// Compares a leaf or an extension against a subtree missing from the
+    /// other trie, `removed` telling whether it's in the old trie.
+    ///
+    /// All the nibbles of the node are consumed at once, rather than one per
+    /// level, so a long key doesn't cost quadratic time and deep recursion.
+    fn diff_missing_subtree(
+        &self,
+        subtree: Subtree,
+        removed: bool,
+        prefix: &[u8],
+        key_nibbles: &mut Vec<u8>,
+        result: &mut Vec<TrieDiffItem>,
+    ) -> Result<(), StorageError> {
+        let depth = key_nibbles.len();
+        let (nibbles, leaf_value, child) = match subtree {
+            Subtree::Leaf(nibbles, value) => (nibbles, Some(value), None),
+            Subtree::Extension(nibbles, child) => (nibbles, None, Some(Subtree::Node(child))),
+            Subtree::Node(_) => unreachable!("only leaves and extensions span nibbles"),
+        };
+        key_nibbles.extend_from_slice(&nibbles);
+        if key_nibbles.iter().zip(prefix).all(|(nibble, expected)| nibble == expected) {
+            if let Some(value) = leaf_value {
+                if key_nibbles.len() >= prefix.len() {
+                    let value = Some(self.retrieve_value(&value)?);
+                    let (old_value, new_value) =
+                        if removed { (value, None) } else { (None, value) };
+                    result.push(TrieDiffItem {
+                        key: key_from_nibbles(key_nibbles),
+                        old_value,
+                        new_value,
+                    });
+                }
+            } else if removed {
+                self.diff_subtrees(child, None, prefix, key_nibbles, result)?;
+            } else {
+                self.diff_subtrees(None, child, prefix, key_nibbles, result)?;
+            }
+        }
+        key_nibbles.truncate(depth);
+        Ok(())
+    }
+
+    fn expand_subtree(&self, subtree: Option<Subtree>) -> Result<Level, StorageError> {
+        let mut level = Level::default();
+        match subtree {
+            None => {}
+            Some(Subtree::Node(hash)) => match self.retrieve_raw_node(&hash)? {
+                None => {}
+                Some((_, node)) => match node.node {
+                    RawTrieNode::Leaf(key, _, value) => {
+                        level.set_leaf(&decode_nibbles(&key), value);
+                    }
+                    RawTrieNode::Extension(key, child) => {
+                        level.set_extension(&decode_nibbles(&key), child)?;
+                    }
+                    RawTrieNode::Branch(children, value) => {
+                        level.value = value.map(|(_, hash)| hash);
+                        for (slot, child) in level.children.iter_mut().zip(children) {
+                            *slot = child.map(Subtree::Node);
+                        }
+                    }
+                },
+            },
+            Some(Subtree::Leaf(nibbles, value)) => level.set_leaf(&nibbles, value),
+            Some(Subtree::Extension(nibbles, child)) => level.set_extension(&nibbles, child)?,
+        }
+        Ok(level)
+    }
+
+    fn retrieve_value(&self, hash: &CryptoHash) -> Result<Vec<u8>, StorageError> {
+        Ok(self.storage.retrieve_raw_bytes(hash)?.to_vec())
+    }
+}
+
+fn key_from_nibbles(nibbles: &[u8]) -> Vec<u8> {
+    nibbles.chunks_exact(2).map(|pair| pair[0] * 16 + pair[1]).collect()
+}
+
+fn decode_nibbles(key: &[u8]) -> Vec<u8> {
+    NibbleSlice::from_encoded(key).0.iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::TrieDiffItem;
+    use crate::test_utils::{create_tries, gen_changes, simplify_changes, test_populate_trie};
+    use crate::Trie;
+    use near_primitives::shard_layout::ShardUId;
+
+    #[test]
+    fn test_diff() {
+        let mut rng = rand::thread_rng();
+        let shard_uid = ShardUId::single_shard();
+        for _ in 0..100 {
+            let tries = create_tries();
+            let old_changes = simplify_changes(&gen_changes(&mut rng, 20));
+            let old_root =
+                test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, old_changes.clone());
+            let mut new_changes = gen_changes(&mut rng, 10);
+            new_changes.extend(old_changes.iter().step_by(3).map(|(key, _)| (key.clone(), None)));
+            let new_root = test_populate_trie(&tries, &old_root, shard_uid, new_changes.clone());
+
+            let mut old_map = BTreeMap::new();
+            for (key, value) in &old_changes {
+                if let Some(value) = value {
+                    old_map.insert(key.clone(), value.clone());
+                }
+            }
+            let mut new_map = old_map.clone();
+            for (key, value) in new_changes {
+                match value {
+                    Some(value) => new_map.insert(key, value),
+                    None => new_map.remove(&key),
+                };
+            }
+
+            let trie = tries.get_trie_for_shard(shard_uid, old_root);
+            for prefix in [&b""[..], b"a", b"ab"] {
+                let mut keys: Vec<_> = old_map.keys().chain(new_map.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                let want: Vec<_> = keys
+                    .into_iter()
+                    .filter(|key| key.starts_with(prefix))
+                    .filter(|key| old_map.get(*key) != new_map.get(*key))
+                    .map(|key| TrieDiffItem {
+                        key: key.clone(),
+                        old_value: old_map.get(key).cloned(),
+                        new_value: new_map.get(key).cloned(),
+                    })
+                    .collect();
+                assert_eq!(want, trie.diff(&new_root, prefix).unwrap());
+            }
+            assert_eq!(Vec::<TrieDiffItem>::new(), trie.diff(&old_root, b"").unwrap());
+        }
+    }
+
+    /// An added or removed key is reported without descending into its leaf
+    /// one nibble at a time.
+    #[test]
+    fn test_diff_long_key() {
+        let shard_uid = ShardUId::single_shard();
+        let tries = create_tries();
+        let short_key = b"a".to_vec();
+        let long_key = vec![7; 100_000];
+        let old_root = test_populate_trie(
+            &tries,
+            &Trie::EMPTY_ROOT,
+            shard_uid,
+            vec![(short_key, Some(b"short".to_vec()))],
+        );
+        let new_root = test_populate_trie(
+            &tries,
+            &old_root,
+            shard_uid,
+            vec![(long_key.clone(), Some(b"long".to_vec()))],
+        );
+        let added = TrieDiffItem {
+            key: long_key.clone(),
+            old_value: None,
+            new_value: Some(b"long".to_vec()),
+        };
+        let old_trie = tries.get_trie_for_shard(shard_uid, old_root);
+        assert_eq!(vec![added.clone()], old_trie.diff(&new_root, b"").unwrap());
+        assert_eq!(vec![added], old_trie.diff(&new_root, &long_key[..10]).unwrap());
+        assert_eq!(Vec::<TrieDiffItem>::new(), old_trie.diff(&new_root, b"a").unwrap());
+
+        let removed =
+            TrieDiffItem { key: long_key, old_value: Some(b"long".to_vec()), new_value: None };
+        let new_trie = tries.get_trie_for_shard(shard_uid, new_root);
+        assert_eq!(vec![removed], new_trie.diff(&old_root, b"").unwrap());
+    }
+}
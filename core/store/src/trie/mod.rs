@@ -21,6 +21,7 @@ use near_primitives::types::{StateRoot, StateRootNode};
 use crate::flat_state::FlatState;
 pub use crate::trie::config::TrieConfig;
 pub(crate) use crate::trie::config::DEFAULT_SHARD_CACHE_TOTAL_SIZE_LIMIT;
+pub use crate::trie::diff::TrieDiffItem;
 use crate::trie::insert_delete::NodesStorage;
 use crate::trie::iterator::TrieIterator;
 pub use crate::trie::nibble_slice::NibbleSlice;
@@ -32,12 +33,14 @@ use crate::{FlatStateDelta, StorageError};
 pub use near_primitives::types::TrieNodesCount;
 
 mod config;
+mod diff;
 mod insert_delete;
 pub mod iterator;
 mod nibble_slice;
 mod prefetching_trie_storage;
 mod shard_tries;
 pub mod split_state;
+mod state_delta;
 mod state_parts;
 mod trie_storage;
 #[cfg(test)]
@@ -508,6 +511,10 @@ impl TrieRefcountChange {
     pub fn payload(&self) -> &[u8] {
         self.trie_node_or_value.as_slice()
     }
+
+    pub fn rc(&self) -> std::num::NonZeroU32 {
+        self.rc
+    }
 }
 
 ///
@@ -549,6 +556,10 @@ impl TrieChanges {
     pub fn insertions(&self) -> &[TrieRefcountChange] {
         self.insertions.as_slice()
     }
+
+    pub fn deletions(&self) -> &[TrieRefcountChange] {
+        self.deletions.as_slice()
+    }
 }
 
 /// Result of applying state part to Trie.
@@ -182,6 +182,21 @@ impl ShardTries {
         self.0.store.clone()
     }
 
+    /// Returns the number of entries and the approximate size in bytes of the
+    /// trie cache of every shard, or of the view caches if `is_view` is set.
+    pub fn get_cache_sizes(&self, is_view: bool) -> Vec<(ShardUId, usize, u64)> {
+        let caches = if is_view { &self.0.view_caches } else { &self.0.caches };
+        caches
+            .read()
+            .expect(POISONED_LOCK_ERR)
+            .iter()
+            .map(|(shard_uid, cache)| {
+                let cache = cache.lock();
+                (*shard_uid, cache.len(), cache.current_total_size())
+            })
+            .collect()
+    }
+
     pub(crate) fn get_db(&self) -> &Arc<dyn crate::Database> {
         &self.0.store.storage
     }
@@ -209,10 +224,18 @@ impl ShardTries {
                         }
                     }
                 }
-                DBOp::Set { col, .. }
-                | DBOp::Insert { col, .. }
-                | DBOp::Delete { col, .. }
-                | DBOp::DeleteRange { col, .. } => {
+                DBOp::DeleteRange { col, from, to } => {
+                    if *col == DBCol::State {
+                        // Delete is possible in clear_untracked_shards_state
+                        for (shard_uid, cache) in caches.iter() {
+                            let prefix = shard_uid.to_bytes();
+                            if from.as_slice() <= &prefix[..] && &prefix[..] < to.as_slice() {
+                                cache.clear();
+                            }
+                        }
+                    }
+                }
+                DBOp::Set { col, .. } | DBOp::Insert { col, .. } | DBOp::Delete { col, .. } => {
                     assert_ne!(*col, DBCol::State);
                 }
             }
@@ -0,0 +1,281 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::syncing::ShardStateDelta;
+use near_primitives::types::StateRoot;
+
+use crate::trie::{RawTrieNode, RawTrieNodeWithSize, TrieRefcountChange};
+use crate::{ShardTries, StorageError, TrieChanges, TrieDBStorage, TrieStorage};
+
+impl TrieChanges {
+    /// Sums up the trie changes of consecutive chunks of a shard, oldest
+    /// first, into the nodes and values which were added to the state after
+    /// the first chunk's `old_root` to get the last chunk's `new_root`.
+    ///
+    /// Nodes inserted and deleted within the range cancel out.  Nodes deleted
+    /// from the old state aren't part of the delta, the node applying it
+    /// works out which ones they are.
+    pub fn state_delta(changes: &[TrieChanges]) -> ShardStateDelta {
+        let mut refcounts: HashMap<&CryptoHash, (Option<&[u8]>, i64)> = HashMap::new();
+        for trie_changes in changes {
+            for insertion in &trie_changes.insertions {
+                let entry =
+                    refcounts.entry(&insertion.trie_node_or_value_hash).or_insert((None, 0));
+                entry.0 = Some(&insertion.trie_node_or_value);
+                entry.1 += i64::from(insertion.rc.get());
+            }
+            for deletion in &trie_changes.deletions {
+                let entry = refcounts.entry(&deletion.trie_node_or_value_hash).or_insert((None, 0));
+                entry.1 -= i64::from(deletion.rc.get());
+            }
+        }
+        let mut nodes: Vec<_> = refcounts
+            .into_values()
+            .filter_map(|(value, rc)| match value {
+                Some(value) if rc > 0 => Some(value.to_vec()),
+                _ => None,
+            })
+            .collect();
+        nodes.sort();
+        ShardStateDelta { nodes }
+    }
+}
+
+/// Counts how many more times each trie node and value appears in one state
+/// than in another.
+struct RefcountDiff<'a> {
+    /// Nodes and values of the delta, by hash.
+    delta: HashMap<CryptoHash, &'a [u8]>,
+    storage: TrieDBStorage,
+    /// Nodes to expand, largest memory usage first.
+    queue: BinaryHeap<(u64, CryptoHash)>,
+    /// Nodes in the queue with the number of references counted so far.
+    pending: HashMap<CryptoHash, (Arc<[u8]>, RawTrieNodeWithSize, i64)>,
+    /// Counted reference changes, with the node if it was read.
+    refcounts: HashMap<CryptoHash, (Option<Arc<[u8]>>, i64)>,
+}
+
+impl RefcountDiff<'_> {
+    /// Reads a node or value from the delta, or from storage if the delta
+    /// doesn't have it.
+    fn get(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+        match self.delta.get(hash) {
+            Some(bytes) => Ok(Arc::from(*bytes)),
+            None => self.storage.retrieve_raw_bytes(hash),
+        }
+    }
+
+    /// Adds `rc` references to the node with given hash.
+    fn push(&mut self, hash: CryptoHash, rc: i64) -> Result<(), StorageError> {
+        if hash == StateRoot::default() {
+            return Ok(());
+        }
+        if let Some((_, _, pending_rc)) = self.pending.get_mut(&hash) {
+            *pending_rc += rc;
+            return Ok(());
+        }
+        let bytes = self.get(&hash)?;
+        let node = RawTrieNodeWithSize::decode(&bytes).map_err(|err| {
+            StorageError::StorageInconsistentState(format!("invalid trie node {}: {}", hash, err))
+        })?;
+        self.queue.push((node.memory_usage, hash));
+        self.pending.insert(hash, (bytes, node, rc));
+        Ok(())
+    }
+
+    /// Counts the references of the nodes in the queue and of their
+    /// descendants.  A node's memory usage is larger than its children's, so
+    /// a node is expanded only after all its references from either state
+    /// were counted, and the subtrees which the states share, whose
+    /// references cancel out, are never expanded.
+    fn run(&mut self) -> Result<(), StorageError> {
+        while let Some((_, hash)) = self.queue.pop() {
+            let (bytes, node, rc) = self.pending.remove(&hash).unwrap();
+            if rc == 0 {
+                continue;
+            }
+            let entry = self.refcounts.entry(hash).or_insert((None, 0));
+            entry.0 = Some(bytes);
+            entry.1 += rc;
+            let value_hash = match node.node {
+                RawTrieNode::Leaf(_, _, value_hash) => Some(value_hash),
+                RawTrieNode::Branch(children, value) => {
+                    for child in children.into_iter().flatten() {
+                        self.push(child, rc)?;
+                    }
+                    value.map(|(_, value_hash)| value_hash)
+                }
+                RawTrieNode::Extension(_, child) => {
+                    self.push(child, rc)?;
+                    None
+                }
+            };
+            if let Some(value_hash) = value_hash {
+                self.refcounts.entry(value_hash).or_insert((None, 0)).1 += rc;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ShardTries {
+    /// Works out the trie changes which turn the state of a shard with root
+    /// `old_root`, present in storage, into the state with root `new_root`,
+    /// whose nodes and values are either in the delta or in storage.
+    ///
+    /// Reference counts are computed here rather than taken from the peer
+    /// which sent the delta: a node gets one reference for every time it
+    /// appears in the state, the same as when the node applies chunks itself.
+    /// Nodes of the delta which aren't part of the new state are left out.
+    /// The deletions are the references of the old state which the new one
+    /// no longer has; like the deletions of applied chunks, they are to be
+    /// applied once the old state is garbage collected.
+    ///
+    /// Fails if a node or value of the new state is neither in the delta nor
+    /// in storage.
+    pub fn apply_state_delta(
+        &self,
+        shard_uid: ShardUId,
+        old_root: &StateRoot,
+        new_root: &StateRoot,
+        delta: &ShardStateDelta,
+    ) -> Result<TrieChanges, StorageError> {
+        let mut diff = RefcountDiff {
+            delta: delta.nodes.iter().map(|value| (hash(value), value.as_slice())).collect(),
+            storage: TrieDBStorage::new(self.get_store(), shard_uid),
+            queue: BinaryHeap::new(),
+            pending: HashMap::new(),
+            refcounts: HashMap::new(),
+        };
+        diff.push(*new_root, 1)?;
+        diff.push(*old_root, -1)?;
+        diff.run()?;
+
+        let mut insertions = vec![];
+        let mut deletions = vec![];
+        for (trie_node_or_value_hash, (bytes, rc)) in std::mem::take(&mut diff.refcounts) {
+            if rc == 0 {
+                continue;
+            }
+            let trie_node_or_value = match bytes {
+                Some(bytes) => bytes.to_vec(),
+                None => diff.get(&trie_node_or_value_hash)?.to_vec(),
+            };
+            let change = TrieRefcountChange {
+                trie_node_or_value_hash,
+                trie_node_or_value,
+                rc: NonZeroU32::new(rc.unsigned_abs().min(u32::MAX as u64) as u32).unwrap(),
+            };
+            if rc > 0 {
+                insertions.push(change);
+            } else {
+                deletions.push(change);
+            }
+        }
+        // Sort so that trie changes have unique representation
+        insertions.sort();
+        deletions.sort();
+        Ok(TrieChanges { old_root: *old_root, new_root: *new_root, insertions, deletions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::types::StateRoot;
+
+    use crate::test_utils::create_tries;
+    use crate::{DBCol, ShardTries, TrieChanges};
+
+    fn update(
+        tries: &ShardTries,
+        root: StateRoot,
+        changes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    ) -> TrieChanges {
+        let shard_uid = ShardUId::single_shard();
+        let trie_changes =
+            tries.get_trie_for_shard(shard_uid, root).update(changes.into_iter()).unwrap();
+        let mut store_update = tries.store_update();
+        tries.apply_insertions(&trie_changes, shard_uid, &mut store_update);
+        store_update.commit().unwrap();
+        trie_changes
+    }
+
+    fn apply_deletions(tries: &ShardTries, trie_changes: &TrieChanges) {
+        let mut store_update = tries.store_update();
+        tries.apply_deletions(trie_changes, ShardUId::single_shard(), &mut store_update);
+        store_update.commit().unwrap();
+    }
+
+    fn state_column(tries: &ShardTries) -> Vec<(Box<[u8]>, Box<[u8]>)> {
+        tries.get_store().iter_raw_bytes(DBCol::State).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_apply_state_delta() {
+        let shard_uid = ShardUId::single_shard();
+        let tries = create_tries();
+        let changes0 = update(
+            &tries,
+            StateRoot::default(),
+            vec![
+                (b"alice".to_vec(), Some(b"1".to_vec())),
+                (b"bob".to_vec(), Some(b"2".to_vec())),
+                (b"carol".to_vec(), Some(b"3".to_vec())),
+                (b"erin".to_vec(), Some(b"3".to_vec())),
+            ],
+        );
+        let changes1 = update(
+            &tries,
+            changes0.new_root,
+            vec![(b"alice".to_vec(), Some(b"4".to_vec())), (b"dave".to_vec(), Some(b"5".to_vec()))],
+        );
+        let changes2 = update(
+            &tries,
+            changes1.new_root,
+            vec![
+                (b"bob".to_vec(), None),
+                (b"dave".to_vec(), Some(b"6".to_vec())),
+                (b"erin".to_vec(), None),
+            ],
+        );
+        let root = changes2.new_root;
+        let mut delta = TrieChanges::state_delta(&[changes1.clone(), changes2.clone()]);
+
+        // The other node only has the state before the delta.
+        let other = create_tries();
+        let mut store_update = other.store_update();
+        other.apply_insertions(&changes0, shard_uid, &mut store_update);
+        store_update.commit().unwrap();
+
+        let mut incomplete = delta.clone();
+        incomplete.nodes.pop();
+        assert!(other
+            .apply_state_delta(shard_uid, &changes0.new_root, &root, &incomplete)
+            .is_err());
+
+        // Nodes which aren't part of the new state aren't stored.
+        delta.nodes.push(b"junk".to_vec());
+        let trie_changes =
+            other.apply_state_delta(shard_uid, &changes0.new_root, &root, &delta).unwrap();
+        let mut store_update = other.store_update();
+        other.apply_insertions(&trie_changes, shard_uid, &mut store_update);
+        store_update.commit().unwrap();
+        let trie = other.get_trie_for_shard(shard_uid, root);
+        assert_eq!(trie.get(b"alice"), Ok(Some(b"4".to_vec())));
+        assert_eq!(trie.get(b"bob"), Ok(None));
+        assert_eq!(trie.get(b"carol"), Ok(Some(b"3".to_vec())));
+        assert_eq!(trie.get(b"dave"), Ok(Some(b"6".to_vec())));
+        assert_eq!(trie.get(b"erin"), Ok(None));
+
+        // Once the old states are garbage collected, both nodes store the
+        // same nodes with the same reference counts.
+        apply_deletions(&tries, &changes1);
+        apply_deletions(&tries, &changes2);
+        apply_deletions(&other, &trie_changes);
+        assert_eq!(state_column(&other), state_column(&tries));
+    }
+}
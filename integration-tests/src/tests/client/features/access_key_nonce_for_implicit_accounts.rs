@@ -508,6 +508,7 @@ fn test_processing_chunks_sanity() {
             let _ = env.clients[1].start_process_block(
                 blocks[ind].clone().into(),
                 Provenance::NONE,
+                None,
                 Arc::new(|_| {}),
             );
             if rng.gen_bool(0.5) {
@@ -755,6 +756,7 @@ fn test_chunk_forwarding_optimization() {
             let _ = test.env.clients[i].start_process_block(
                 block.clone().into(),
                 if i == 0 { Provenance::PRODUCED } else { Provenance::NONE },
+                None,
                 Arc::new(|_| {}),
             );
             let mut accepted_blocks =
@@ -844,6 +846,7 @@ fn test_processing_blocks_async() {
         let _ = env.clients[1].start_process_block(
             blocks[ind].clone().into(),
             Provenance::NONE,
+            None,
             Arc::new(|_| {}),
         );
     }
@@ -172,6 +172,7 @@ fn test_non_adversarial_case() {
             let _ = test.env.clients[i].start_process_block(
                 block.clone().into(),
                 if i == 0 { Provenance::PRODUCED } else { Provenance::NONE },
+                None,
                 Arc::new(|_| {}),
             );
             let mut accepted_blocks =
@@ -287,6 +288,7 @@ fn test_banning_chunk_producer_when_seeing_invalid_chunk_base(
             let _ = test.env.clients[i].start_process_block(
                 block.clone().into(),
                 if i == 0 { Provenance::PRODUCED } else { Provenance::NONE },
+                None,
                 Arc::new(|_| {}),
             );
             let mut accepted_blocks =
@@ -41,6 +41,7 @@ fn assert_zero_balance_account(env: &mut TestEnv, account_id: &AccountId) {
             &head.last_block_hash,
             head_block.header().epoch_id(),
             &QueryRequest::ViewAccount { account_id: account_id.clone() },
+            None,
         )
         .unwrap();
     match response.kind {
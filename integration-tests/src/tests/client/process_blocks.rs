@@ -20,7 +20,9 @@ use near_chain::{
     Block, BlockProcessingArtifact, ChainGenesis, ChainStore, ChainStoreAccess, Error, Provenance,
     RuntimeWithEpochManagerAdapter,
 };
-use near_chain_configs::{ClientConfig, Genesis, DEFAULT_GC_NUM_EPOCHS_TO_KEEP};
+use near_chain_configs::{
+    ArchivalPolicy, ClientConfig, Genesis, Retention, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
+};
 use near_chunks::{ChunkStatus, ShardsManager};
 use near_client::test_utils::{
     create_chunk_on_height, setup_client, setup_mock, setup_mock_all_validators, TestEnv,
@@ -31,6 +33,7 @@ use near_client::{
 };
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signature, Signer};
 use near_network::test_utils::{wait_or_panic, MockPeerManagerAdapter};
+use near_network::time;
 use near_network::types::{
     BlockInfo, ConnectedPeerInfo, HighestHeightPeerInfo, NetworkInfo, PeerChainInfo,
     PeerManagerMessageRequest, PeerManagerMessageResponse, PeerType,
@@ -1027,6 +1030,8 @@ fn client_sync_headers() {
                             last_block: Some(BlockInfo { height: 5, hash: hash(&[5]) }),
                             tracked_shards: vec![],
                             archival: false,
+                            earliest_height: None,
+                            protocol_version: PROTOCOL_VERSION,
                         },
                     },
                     received_bytes_per_sec: 0,
@@ -1036,6 +1041,8 @@ fn client_sync_headers() {
                     connection_established_time: near_network::time::Instant::now(),
                     peer_type: PeerType::Outbound,
                     nonce: 1,
+                    messages_to_send: 0,
+                    bytes_to_send: 0,
                 }],
                 num_connected_peers: 1,
                 peer_max_count: 1,
@@ -1046,6 +1053,8 @@ fn client_sync_headers() {
                     highest_block_hash: hash(&[5]),
                     tracked_shards: vec![],
                     archival: false,
+                    earliest_height: None,
+                    protocol_version: PROTOCOL_VERSION,
                 }],
                 sent_bytes_per_sec: 0,
                 received_bytes_per_sec: 0,
@@ -1116,6 +1125,7 @@ fn test_time_attack() {
     let vs =
         ValidatorSchedule::new().block_producers_per_epoch(vec![vec!["test1".parse().unwrap()]]);
     let mut client = setup_client(
+        time::Clock::real(),
         store,
         vs,
         Some("test1".parse().unwrap()),
@@ -1152,6 +1162,7 @@ fn test_invalid_approvals() {
     let vs =
         ValidatorSchedule::new().block_producers_per_epoch(vec![vec!["test1".parse().unwrap()]]);
     let mut client = setup_client(
+        time::Clock::real(),
         store,
         vs,
         Some("test1".parse().unwrap()),
@@ -1200,6 +1211,7 @@ fn test_invalid_gas_price() {
     let vs =
         ValidatorSchedule::new().block_producers_per_epoch(vec![vec!["test1".parse().unwrap()]]);
     let mut client = setup_client(
+        time::Clock::real(),
         store,
         vs,
         Some("test1".parse().unwrap()),
@@ -1361,6 +1373,7 @@ fn test_bad_chunk_mask() {
                 .num_shards(2)
                 .block_producers_per_epoch(vec![validators.clone()]);
             setup_client(
+                time::Clock::real(),
                 create_test_store(),
                 vs,
                 Some(account_id.clone()),
@@ -1682,6 +1695,75 @@ fn test_archival_gc_split_storage_behind() {
     test_archival_gc_common(storage, epoch_length, max_height, max_cold_head_height, false);
 }
 
+/// Tests that an archival node garbage collects blocks and state changes
+/// older than the number of epochs in its archival policy, but keeps the
+/// headers and the chunks it keeps forever.
+#[test]
+fn test_archival_policy_gc() {
+    let epoch_length = 10;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .archive(true)
+        .save_trie_changes(true)
+        .build();
+    env.clients[0].chain.store().store().set_db_kind(DbKind::Archive).unwrap();
+    env.clients[0].config.archival_policy = ArchivalPolicy {
+        blocks: Retention::Epochs(DEFAULT_GC_NUM_EPOCHS_TO_KEEP),
+        state_changes: Retention::Epochs(DEFAULT_GC_NUM_EPOCHS_TO_KEEP),
+        ..ArchivalPolicy::default()
+    };
+
+    // A transaction, so that the early blocks have state changes to collect.
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let tx = SignedTransaction::send_money(
+        1,
+        "test0".parse().unwrap(),
+        "test1".parse().unwrap(),
+        &signer,
+        100,
+        genesis_hash,
+    );
+    env.clients[0].process_tx(tx, false, false);
+
+    let max_height = epoch_length * (DEFAULT_GC_NUM_EPOCHS_TO_KEEP + 2);
+    let mut blocks = vec![env.clients[0].chain.get_block_by_height(0).unwrap()];
+    let mut num_state_changes = vec![0];
+    for i in 1..=max_height {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        let state_changes =
+            env.clients[0].chain.store().get_state_changes_with_cause_in_block(block.hash());
+        num_state_changes.push(state_changes.unwrap().len());
+        blocks.push(block);
+    }
+
+    // Like in `test_archival_gc_common`, the last block before the latest
+    // epochs is kept.
+    let gc_stop_height = max_height - epoch_length * DEFAULT_GC_NUM_EPOCHS_TO_KEEP;
+    assert!(num_state_changes[..gc_stop_height as usize].iter().any(|&num| num > 0));
+    let chain = &env.clients[0].chain;
+    for (height, block) in blocks.iter().enumerate() {
+        let height = height as BlockHeight;
+        assert!(chain.get_block_header(block.hash()).is_ok());
+        let gc = height > 0 && height < gc_stop_height;
+        assert_eq!(chain.get_block(block.hash()).is_err(), gc, "block #{}", height);
+        let state_changes =
+            chain.store().get_state_changes_with_cause_in_block(block.hash()).unwrap();
+        let expected = if gc { 0 } else { num_state_changes[height as usize] };
+        assert_eq!(state_changes.len(), expected, "state changes of block #{}", height);
+        for chunk_header in block.chunks().iter() {
+            if height > 0 && chunk_header.height_included() == height {
+                assert!(chain.get_chunk(&chunk_header.chunk_hash()).is_ok());
+            }
+        }
+    }
+}
+
 #[test]
 fn test_gc_block_skips() {
     let mut chain_genesis = ChainGenesis::test();
@@ -2143,6 +2225,7 @@ fn test_incorrect_validator_key_produce_block() {
     let mut config = ClientConfig::test(true, 10, 20, 2, false, true, true);
     config.epoch_length = chain_genesis.epoch_length;
     let mut client = Client::new(
+        time::Clock::real(),
         config,
         chain_genesis,
         runtime_adapter,
@@ -2266,6 +2349,7 @@ fn test_data_reset_before_state_sync() {
             &head.last_block_hash,
             head_block.header().epoch_id(),
             &QueryRequest::ViewAccount { account_id: "test_account".parse().unwrap() },
+            None,
         )
         .unwrap();
     assert_matches!(response.kind, QueryResponseKind::ViewAccount(_));
@@ -2280,6 +2364,7 @@ fn test_data_reset_before_state_sync() {
         &head.last_block_hash,
         head_block.header().epoch_id(),
         &QueryRequest::ViewAccount { account_id: "test_account".parse().unwrap() },
+        None,
     );
     // TODO(#3742): ViewClient still has data in cache by current design.
     assert!(response.is_ok());
@@ -2591,6 +2676,103 @@ fn test_catchup_gas_price_change() {
     assert_eq!(chunk_extra_after_sync, expected_chunk_extra);
 }
 
+/// A node which has the state of the end of an earlier epoch catches up by
+/// downloading the trie nodes added since then.  Once the old states are
+/// garbage collected, it stores the same state with the same reference counts
+/// as the node which applied the chunks.
+#[test]
+fn test_catchup_state_delta() {
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::new(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .clients_count(2)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+        .build();
+    let genesis_block = env.clients[0].chain.get_block_by_height(0).unwrap();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let mut blocks = vec![];
+    for i in 1..=6 {
+        let tx = SignedTransaction::send_money(
+            i,
+            "test0".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &signer,
+            1,
+            *genesis_block.hash(),
+        );
+        env.clients[0].process_tx(tx, false, false);
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        blocks.push(block.clone());
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        env.process_block(1, block, Provenance::NONE);
+    }
+    // The second node doesn't track the shard, it only has the genesis state.
+    let shard_uid = ShardUId::single_shard();
+    assert!(env.clients[1].chain.get_chunk_extra(blocks[4].hash(), &shard_uid).is_err());
+
+    let sync_hash = *blocks[5].hash();
+    assert!(env.clients[0].chain.check_sync_hash_validity(&sync_hash).unwrap());
+    let base_hash = env.clients[1].chain.get_state_delta_base(0, sync_hash).unwrap();
+    if cfg!(feature = "protocol_feature_flat_state") {
+        assert_eq!(base_hash, None);
+        return;
+    }
+    assert_eq!(base_hash, Some(*genesis_block.hash()));
+    let state_sync_header = env.clients[0].chain.get_state_response_header(0, sync_hash).unwrap();
+    let delta =
+        env.clients[0].chain.get_state_response_delta(0, sync_hash, *genesis_block.hash()).unwrap();
+    env.clients[1].chain.set_state_header(0, sync_hash, state_sync_header).unwrap();
+    env.clients[1].chain.set_state_delta(0, sync_hash, *genesis_block.hash(), &delta).unwrap();
+    env.clients[1].chain.set_state_finalize(0, sync_hash, Ok(())).unwrap();
+    assert_eq!(
+        env.clients[1].chain.get_chunk_extra(blocks[4].hash(), &shard_uid).unwrap(),
+        env.clients[0].chain.get_chunk_extra(blocks[4].hash(), &shard_uid).unwrap()
+    );
+
+    // Garbage collect everything but the state of the previous block of the
+    // sync block.
+    let get_trie_changes = |client: &Client, block_hash: &CryptoHash| -> TrieChanges {
+        client
+            .chain
+            .store()
+            .store()
+            .get_ser(DBCol::TrieChanges, &get_block_shard_uid(block_hash, &shard_uid))
+            .unwrap()
+            .unwrap()
+    };
+    let tries = env.clients[0].runtime_adapter.get_tries();
+    let mut store_update = tries.store_update();
+    for block in &blocks[..5] {
+        tries.apply_deletions(
+            &get_trie_changes(&env.clients[0], block.hash()),
+            shard_uid,
+            &mut store_update,
+        );
+    }
+    tries.revert_insertions(
+        &get_trie_changes(&env.clients[0], &sync_hash),
+        shard_uid,
+        &mut store_update,
+    );
+    store_update.commit().unwrap();
+    let tries = env.clients[1].runtime_adapter.get_tries();
+    let mut store_update = tries.store_update();
+    tries.apply_deletions(
+        &get_trie_changes(&env.clients[1], blocks[4].hash()),
+        shard_uid,
+        &mut store_update,
+    );
+    store_update.commit().unwrap();
+
+    let state_column = |client: &Client| -> Vec<_> {
+        client.runtime_adapter.store().iter_raw_bytes(DBCol::State).map(Result::unwrap).collect()
+    };
+    assert_eq!(state_column(&env.clients[1]), state_column(&env.clients[0]));
+}
+
 #[test]
 fn test_block_execution_outcomes() {
     init_test_logger();
@@ -3040,6 +3222,7 @@ fn test_query_final_state() {
                 last_final_block.hash(),
                 last_final_block.header().epoch_id(),
                 &QueryRequest::ViewAccount { account_id },
+                None,
             )
             .unwrap();
         match response.kind {
@@ -3601,6 +3784,7 @@ mod contract_precompilation_tests {
             block_timestamp: block.header().raw_timestamp(),
             current_protocol_version: PROTOCOL_VERSION,
             cache: Some(Box::new(caches.swap_remove(1))),
+            deadline: None,
         };
         viewer
             .call_function(
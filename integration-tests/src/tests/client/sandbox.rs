@@ -6,7 +6,9 @@ use near_chain_configs::Genesis;
 use near_client::test_utils::TestEnv;
 use near_crypto::{InMemorySigner, KeyType};
 use near_primitives::account::Account;
+use near_primitives::hash::hash;
 use near_primitives::sandbox::state_patch::SandboxStatePatch;
+use near_primitives::sandbox::SandboxBalancePatch;
 use near_primitives::state_record::StateRecord;
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction,
@@ -107,3 +109,25 @@ fn test_patch_account() {
     let test1_after = env.query_account("test1".parse().unwrap());
     assert_eq!(test1_after.amount, 10);
 }
+
+#[test]
+fn test_patch_contract_and_balance() {
+    let (mut env, _signer) = test_setup();
+    let test1 = env.query_account("test1".parse().unwrap());
+    let code = near_test_contracts::trivial_contract().to_vec();
+
+    // Neither the contract nor the balance comes with the whole account.
+    env.clients[0].chain.patch_state(SandboxStatePatch::with_balances(
+        vec![StateRecord::Contract { account_id: "test1".parse().unwrap(), code: code.clone() }],
+        vec![SandboxBalancePatch {
+            account_id: "test1".parse().unwrap(),
+            amount: Some(10),
+            locked: None,
+        }],
+    ));
+    do_blocks(&mut env, 9, 20);
+    let test1_after = env.query_account("test1".parse().unwrap());
+    assert_eq!(test1_after.amount, 10);
+    assert_eq!(test1_after.locked, test1.locked);
+    assert_eq!(test1_after.code_hash, hash(&code));
+}
@@ -155,6 +155,7 @@ impl TestShardUpgradeEnv {
                 .start_process_block(
                     MaybeValidated::from(block.clone()),
                     Provenance::NONE,
+                    None,
                     Arc::new(|_| {}),
                 )
                 .unwrap();
@@ -413,6 +414,7 @@ fn check_account(env: &mut TestEnv, account_id: &AccountId, block: &Block) {
                     block.hash(),
                     block.header().epoch_id(),
                     &QueryRequest::ViewAccount { account_id: account_id.clone() },
+                    None,
                 )
                 .unwrap();
 
@@ -429,6 +431,7 @@ fn check_account(env: &mut TestEnv, account_id: &AccountId, block: &Block) {
                         block.hash(),
                         block.header().epoch_id(),
                         &QueryRequest::ViewAccount { account_id: account_id.clone() },
+                        None,
                     )
                     .unwrap();
             }
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use near_chain::types::{SimulationLimits, SimulationResult};
+use near_chain::ChainGenesis;
+use near_chain_configs::Genesis;
+use near_client::test_utils::TestEnv;
+use near_crypto::{KeyType, PublicKey};
+use near_primitives::account::id::AccountId;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::transaction::{Action, Transaction, TransferAction};
+use near_primitives::types::Balance;
+use near_primitives::views::{QueryRequest, QueryResponseKind};
+use nearcore::config::GenesisExt;
+
+use crate::tests::client::runtimes::create_nightshade_runtimes;
+
+const DEPOSIT: Balance = 100;
+
+fn setup_env() -> TestEnv {
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let mut env = TestEnv::builder(ChainGenesis::test())
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    for height in 1..3 {
+        env.produce_block(0, height);
+    }
+    env
+}
+
+/// Simulates a transfer from test0 to test1 on top of the head, which takes
+/// two blocks: one to convert the transaction and one to execute its receipt.
+fn simulate_transfer(env: &TestEnv, limits: &SimulationLimits) -> SimulationResult {
+    let client = &env.clients[0];
+    let head = client.chain.head().unwrap();
+    let header = client.chain.get_block_header(&head.last_block_hash).unwrap();
+    let chunk_extra =
+        client.chain.get_chunk_extra(&head.last_block_hash, &ShardUId::single_shard()).unwrap();
+    let state_roots = HashMap::from([(0, *chunk_extra.state_root())]);
+    let transaction = Transaction {
+        signer_id: "test0".parse().unwrap(),
+        public_key: PublicKey::empty(KeyType::ED25519),
+        nonce: 0,
+        receiver_id: "test1".parse().unwrap(),
+        block_hash: head.last_block_hash,
+        actions: vec![Action::Transfer(TransferAction { deposit: DEPOSIT })],
+    };
+    client
+        .runtime_adapter
+        .simulate_transaction(&header, &state_roots, transaction, None, limits)
+        .unwrap()
+        .unwrap()
+}
+
+fn view_balance(env: &TestEnv, account_id: &str) -> Balance {
+    let client = &env.clients[0];
+    let head = client.chain.head().unwrap();
+    let header = client.chain.get_block_header(&head.last_block_hash).unwrap();
+    let chunk_extra =
+        client.chain.get_chunk_extra(&head.last_block_hash, &ShardUId::single_shard()).unwrap();
+    let account_id: AccountId = account_id.parse().unwrap();
+    let response = client
+        .runtime_adapter
+        .query(
+            ShardUId::single_shard(),
+            chunk_extra.state_root(),
+            head.height,
+            0,
+            &head.prev_block_hash,
+            &head.last_block_hash,
+            header.epoch_id(),
+            &QueryRequest::ViewAccount { account_id },
+            None,
+        )
+        .unwrap();
+    match response.kind {
+        QueryResponseKind::ViewAccount(view) => view.amount,
+        _ => panic!("wrong query response"),
+    }
+}
+
+#[test]
+fn test_simulate_transfer() {
+    let env = setup_env();
+    let balance = view_balance(&env, "test1");
+    let result = simulate_transfer(&env, &SimulationLimits::default());
+    assert!(result.outcomes.len() >= 2, "{:?}", result.outcomes);
+    assert!(result.pending_receipts.is_empty(), "{:?}", result.pending_receipts);
+    assert!(!result.state_changes.is_empty());
+    // Nothing gets persisted.
+    assert_eq!(view_balance(&env, "test1"), balance);
+}
+
+#[test]
+fn test_simulate_max_blocks() {
+    let env = setup_env();
+    let limits = SimulationLimits { max_blocks: 1, ..Default::default() };
+    let result = simulate_transfer(&env, &limits);
+    assert_eq!(result.outcomes.len(), 1);
+    assert_eq!(result.pending_receipts.len(), 1);
+    assert_eq!(result.pending_receipts[0].receiver_id.as_ref(), "test1");
+}
+
+#[test]
+fn test_simulate_gas_budget() {
+    let env = setup_env();
+    // Converting the transaction uses up the budget, so its receipt isn't
+    // executed.
+    let limits = SimulationLimits { max_gas: 1, ..Default::default() };
+    let result = simulate_transfer(&env, &limits);
+    assert_eq!(result.outcomes.len(), 1);
+    assert_eq!(result.pending_receipts.len(), 1);
+}
+
+#[test]
+fn test_simulate_deadline() {
+    let env = setup_env();
+    // The transaction is applied even past the deadline, so that its outcome
+    // is known, but its receipt isn't.
+    let limits = SimulationLimits { deadline: Some(Instant::now()), ..Default::default() };
+    let result = simulate_transfer(&env, &limits);
+    assert_eq!(result.outcomes.len(), 1);
+    assert_eq!(result.pending_receipts.len(), 1);
+}
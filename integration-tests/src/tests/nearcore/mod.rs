@@ -1,4 +1,5 @@
 mod node_cluster;
+mod offline_node;
 mod rpc_error_structs;
 mod rpc_nodes;
 mod run_nodes;
@@ -0,0 +1,41 @@
+use actix::System;
+use near_actix_test_utils::run_actix;
+use near_chain_configs::Genesis;
+use near_client::GetBlock;
+use near_network::tcp;
+use near_o11y::testonly::init_integration_logger;
+use near_o11y::WithSpanContextExt;
+use nearcore::config::GenesisExt;
+use nearcore::{load_test_config, start_with_config};
+use std::time::Duration;
+
+use crate::test_helpers::heavy_test;
+
+/// A node started offline serves queries, but neither produces blocks (even though it is
+/// the only validator) nor accepts connections from peers.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn offline_node() {
+    heavy_test(|| {
+        init_integration_logger();
+
+        let genesis = Genesis::test(vec!["test1".parse().unwrap()], 1);
+        let addr = tcp::ListenerAddr::reserve_for_test();
+        let mut near_config = load_test_config("test1", addr, genesis.clone());
+        near_config.client_config.min_num_peers = 0;
+        near_config.client_config.offline = true;
+
+        run_actix(async move {
+            let dir = tempfile::Builder::new().prefix("offline_node").tempdir().unwrap();
+            let nearcore::NearNode { view_client, .. } =
+                start_with_config(dir.path(), near_config).expect("start_with_config");
+
+            // Give the node some time to produce blocks, if it were to.
+            tokio::time::sleep(Duration::from_secs(3)).await;
+            let block = view_client.send(GetBlock::latest().with_span_context()).await;
+            assert_eq!(block.unwrap().unwrap().header.height, genesis.config.genesis_height);
+            assert!(tokio::net::TcpStream::connect(*addr).await.is_err());
+            System::current().stop();
+        });
+    });
+}
@@ -1,7 +1,11 @@
 use crate::tests::nearcore::node_cluster::NodeCluster;
 use actix::System;
+use near_actix_test_utils::run_actix;
+use near_chain::test_utils::ValidatorSchedule;
+use near_client::test_utils::setup_loopback_validators;
 use near_client::GetBlock;
 use near_network::test_utils::wait_or_timeout;
+use near_o11y::testonly::init_integration_logger;
 use near_o11y::WithSpanContextExt;
 use near_primitives::types::{BlockHeightDelta, NumSeats, NumShards};
 use rand::{thread_rng, Rng};
@@ -67,11 +71,29 @@ fn run_nodes_1_2_1() {
     run_heavy_nodes(1, 2, 1, 10, 30);
 }
 
-/// Runs 4 nodes that should produce blocks one after another.
+/// Runs 4 nodes that should produce blocks one after another.  The nodes talk
+/// over an in-process loopback network, so the test binds no ports.
 #[test]
-#[cfg_attr(not(feature = "expensive_tests"), ignore)]
 fn run_nodes_1_4_4() {
-    run_heavy_nodes(1, 4, 4, 8, 32);
+    init_integration_logger();
+    run_actix(async {
+        let vs = ValidatorSchedule::new().block_producers_per_epoch(vec![(1..=4)
+            .map(|i| format!("test{}", i).parse().unwrap())
+            .collect()]);
+        let (_network, connectors) = setup_loopback_validators(vs, 8, 100, 0);
+        let view_client = connectors.last().unwrap().1.clone();
+
+        wait_or_timeout(100, 40000, || async {
+            let res = view_client.send(GetBlock::latest().with_span_context()).await;
+            match &res {
+                Ok(Ok(b)) if b.header.height > 32 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        })
+        .await
+        .unwrap();
+        System::current().stop()
+    });
 }
 
 /// Run 4 nodes, 4 shards, 2 validators, other two track 2 shards.
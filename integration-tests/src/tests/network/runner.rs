@@ -1,9 +1,12 @@
 use actix::{Actor, Addr};
 use anyhow::{anyhow, bail, Context};
+use near_chain::state_parts_cache::StatePartsCache;
 use near_chain::test_utils::{KeyValueRuntime, ValidatorSchedule};
 use near_chain::{Chain, ChainGenesis};
 use near_chain_configs::ClientConfig;
-use near_client::{start_client, start_view_client};
+use near_client::{
+    start_client, start_view_client, BlockTimestampIndex, GasPriceWindow, SyncServingBudget,
+};
 use near_network::actix::ActixSystem;
 use near_network::blacklist;
 use near_network::config;
@@ -27,7 +30,7 @@ use std::future::Future;
 use std::iter::Iterator;
 use std::net::{Ipv6Addr, SocketAddr};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tracing::debug;
 
 pub(crate) type ControlFlow = std::ops::ControlFlow<()>;
@@ -66,7 +69,11 @@ fn setup_network_node(
     };
     let network_adapter = Arc::new(NetworkRecipient::default());
     let adv = near_client::adversarial::Controls::default();
+    let block_timestamp_index = Arc::new(Mutex::new(BlockTimestampIndex::new()));
+    let gas_price_window = Arc::new(Mutex::new(GasPriceWindow::new()));
+    let clock = time::Clock::real();
     let client_actor = start_client(
+        clock.clone(),
         client_config.clone(),
         chain_genesis.clone(),
         runtime.clone(),
@@ -77,18 +84,26 @@ fn setup_network_node(
         None,
         adv.clone(),
         None,
+        block_timestamp_index.clone(),
+        gas_price_window.clone(),
     )
     .0;
+    let sync_serving_budget = Arc::new(Mutex::new(SyncServingBudget::from_config(&client_config)));
+    let state_parts_cache = Arc::new(StatePartsCache::new(client_config.state_parts_cache_size));
     let view_client_actor = start_view_client(
         config.validator.as_ref().map(|v| v.account_id()),
         chain_genesis.clone(),
         runtime.clone(),
         network_adapter.clone(),
         client_config,
+        block_timestamp_index,
+        sync_serving_budget,
+        gas_price_window,
+        state_parts_cache,
         adv,
     );
     let peer_manager = PeerManagerActor::spawn(
-        time::Clock::real(),
+        clock,
         db.clone(),
         config,
         Arc::new(near_client::adapter::Adapter::new(client_actor, view_client_actor)),
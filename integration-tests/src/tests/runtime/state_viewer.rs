@@ -1,6 +1,8 @@
 use std::{collections::HashMap, io, sync::Arc};
 
 use crate::runtime_utils::{get_runtime_and_trie, get_test_trie_viewer, TEST_SHARD_UID};
+use near_crypto::{KeyType, SecretKey};
+use near_primitives::account::AccessKey;
 use near_primitives::{
     account::Account,
     hash::hash as sha256,
@@ -16,7 +18,7 @@ use near_primitives::{
     types::{EpochId, StateChangeCause},
     version::PROTOCOL_VERSION,
 };
-use near_store::{set_account, NibbleSlice, RawTrieNode, RawTrieNodeWithSize};
+use near_store::{set_access_key, set_account, NibbleSlice, RawTrieNode, RawTrieNodeWithSize};
 use node_runtime::state_viewer::errors;
 use node_runtime::state_viewer::*;
 use testlib::runtime_utils::alice_account;
@@ -111,6 +113,7 @@ fn test_view_call() {
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
+        deadline: None,
     };
     let result = viewer.call_function(
         root,
@@ -125,6 +128,35 @@ fn test_view_call() {
     assert_eq!(result.unwrap(), (10i32).to_le_bytes());
 }
 
+#[test]
+fn test_view_call_deadline_exceeded() {
+    let (viewer, root) = get_test_trie_viewer();
+
+    let mut logs = vec![];
+    let view_state = ViewApplyState {
+        block_height: 1,
+        prev_block_hash: CryptoHash::default(),
+        block_hash: CryptoHash::default(),
+        epoch_id: EpochId::default(),
+        epoch_height: 0,
+        block_timestamp: 1,
+        current_protocol_version: PROTOCOL_VERSION,
+        cache: None,
+        deadline: Some(std::time::Instant::now()),
+    };
+    let result = viewer.call_function(
+        root,
+        view_state,
+        &"test.contract".parse().unwrap(),
+        "run_test",
+        &[],
+        &mut logs,
+        &MockEpochInfoProvider::default(),
+    );
+
+    assert!(matches!(result, Err(errors::CallFunctionError::DeadlineExceeded)));
+}
+
 #[test]
 fn test_view_call_try_changing_storage() {
     let (viewer, root) = get_test_trie_viewer();
@@ -139,6 +171,7 @@ fn test_view_call_try_changing_storage() {
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
+        deadline: None,
     };
     let result = viewer.call_function(
         root,
@@ -171,6 +204,7 @@ fn test_view_call_with_args() {
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
+        deadline: None,
     };
     let view_call_result = viewer.call_function(
         root,
@@ -199,8 +233,9 @@ fn assert_view_state(
         .map(|(key, value)| StateItem { key: key.to_vec(), value: value.to_vec(), proof: vec![] })
         .collect::<Vec<_>>();
 
-    let view_state =
-        |include_proof| trie_viewer.view_state(&state_update, &alice, prefix, include_proof);
+    let view_state = |include_proof| {
+        trie_viewer.view_state(&state_update, &alice, prefix, None, None, include_proof, None)
+    };
 
     // Test without proof
     let result = view_state(false).unwrap();
@@ -359,8 +394,13 @@ fn test_view_state_too_large() {
         &Account::new(0, 0, CryptoHash::default(), 50_001),
     );
     let trie_viewer = TrieViewer::new(Some(50_000), None);
-    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false);
+    let result =
+        trie_viewer.view_state(&state_update, &alice_account(), b"", None, None, false, None);
     assert!(matches!(result, Err(errors::ViewStateError::AccountStateTooLarge { .. })));
+    // Paged queries are bounded by their limit rather than by the state size.
+    let result =
+        trie_viewer.view_state(&state_update, &alice_account(), b"", None, Some(10), false, None);
+    assert!(result.is_ok());
 }
 
 #[test]
@@ -375,10 +415,165 @@ fn test_view_state_with_large_contract() {
     );
     state_update.set(TrieKey::ContractCode { account_id: alice_account() }, contract_code);
     let trie_viewer = TrieViewer::new(Some(50_000), None);
-    let result = trie_viewer.view_state(&state_update, &alice_account(), b"", false);
+    let result =
+        trie_viewer.view_state(&state_update, &alice_account(), b"", None, None, false, None);
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_view_state_deadline_exceeded() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let state_update = tries.new_trie_update(TEST_SHARD_UID, root);
+    let trie_viewer = TrieViewer::default();
+    let deadline = Some(std::time::Instant::now());
+    let result =
+        trie_viewer.view_state(&state_update, &alice_account(), b"", None, None, false, deadline);
+    assert!(matches!(result, Err(errors::ViewStateError::DeadlineExceeded)));
+}
+
+#[test]
+fn test_view_state_pagination() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let mut state_update = tries.new_trie_update(TEST_SHARD_UID, root);
+    for i in 0..5u8 {
+        state_update.set(
+            TrieKey::ContractData { account_id: alice_account(), key: vec![b'k', i] },
+            vec![i],
+        );
+    }
+    state_update.set(
+        TrieKey::ContractData { account_id: alice_account(), key: b"other".to_vec() },
+        b"other".to_vec(),
+    );
+    state_update.commit(StateChangeCause::InitialState);
+    let trie_changes = state_update.finalize().unwrap().0;
+    let mut db_changes = tries.store_update();
+    let new_root = tries.apply_all(&trie_changes, TEST_SHARD_UID, &mut db_changes);
+    db_changes.commit().unwrap();
+
+    let state_update = tries.new_trie_update(TEST_SHARD_UID, new_root);
+    let trie_viewer = TrieViewer::default();
+    let all = trie_viewer
+        .view_state(&state_update, &alice_account(), b"k", None, None, false, None)
+        .unwrap();
+    assert_eq!(all.values.len(), 5);
+    assert_eq!(all.next_key, None);
+
+    let mut listed = vec![];
+    let mut after: Option<Vec<u8>> = None;
+    loop {
+        let page = trie_viewer
+            .view_state(
+                &state_update,
+                &alice_account(),
+                b"k",
+                after.as_deref(),
+                Some(2),
+                true,
+                None,
+            )
+            .unwrap();
+        assert!(page.values.len() <= 2);
+        // Every page comes with a proof of its own values.
+        let proof_verifier = ProofVerifier::new(page.proof).unwrap();
+        for item in &page.values {
+            assert!(proof_verifier.verify(
+                &new_root,
+                &alice_account(),
+                &item.key,
+                Some(&item.value)
+            ));
+        }
+        listed.extend(page.values);
+        match page.next_key {
+            Some(next_key) => after = Some(next_key),
+            None => break,
+        }
+    }
+    assert_eq!(listed, all.values);
+}
+
+#[test]
+fn test_view_state_default_limit() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let mut state_update = tries.new_trie_update(TEST_SHARD_UID, root);
+    let num_values = DEFAULT_VIEW_STATE_LIMIT + 1;
+    for i in 0..num_values as u32 {
+        let mut key = b"k".to_vec();
+        key.extend(i.to_be_bytes());
+        state_update.set(
+            TrieKey::ContractData { account_id: alice_account(), key },
+            i.to_le_bytes().into(),
+        );
+    }
+    state_update.commit(StateChangeCause::InitialState);
+    let trie_changes = state_update.finalize().unwrap().0;
+    let mut db_changes = tries.store_update();
+    let new_root = tries.apply_all(&trie_changes, TEST_SHARD_UID, &mut db_changes);
+    db_changes.commit().unwrap();
+
+    let state_update = tries.new_trie_update(TEST_SHARD_UID, new_root);
+    let trie_viewer = TrieViewer::default();
+    let first = trie_viewer
+        .view_state(&state_update, &alice_account(), b"k", None, None, false, None)
+        .unwrap();
+    assert_eq!(first.values.len(), DEFAULT_VIEW_STATE_LIMIT);
+    let rest = trie_viewer
+        .view_state(
+            &state_update,
+            &alice_account(),
+            b"k",
+            first.next_key.as_deref(),
+            None,
+            false,
+            None,
+        )
+        .unwrap();
+    assert_eq!(rest.values.len(), 1);
+    assert_eq!(rest.next_key, None);
+}
+
+#[test]
+fn test_view_access_keys_pagination() {
+    let (_, tries, root) = get_runtime_and_trie();
+    let mut state_update = tries.new_trie_update(TEST_SHARD_UID, root);
+    let account_id: AccountId = "relayer".parse().unwrap();
+    for _ in 0..5 {
+        let public_key = SecretKey::from_random(KeyType::ED25519).public_key();
+        set_access_key(
+            &mut state_update,
+            account_id.clone(),
+            public_key,
+            &AccessKey::full_access(),
+        );
+    }
+    state_update.commit(StateChangeCause::InitialState);
+    let trie_changes = state_update.finalize().unwrap().0;
+    let mut db_changes = tries.store_update();
+    let new_root = tries.apply_all(&trie_changes, TEST_SHARD_UID, &mut db_changes);
+    db_changes.commit().unwrap();
+    let state_update = tries.new_trie_update(TEST_SHARD_UID, new_root);
+    let trie_viewer = TrieViewer::default();
+    let (all, next) = trie_viewer.view_access_keys(&state_update, &account_id, None, None).unwrap();
+    assert_eq!(all.len(), 5);
+    assert_eq!(next, None);
+
+    let mut listed = vec![];
+    let mut after = None;
+    loop {
+        let (page, next) = trie_viewer
+            .view_access_keys(&state_update, &account_id, after.as_ref(), Some(2))
+            .unwrap();
+        assert!(page.len() <= 2);
+        listed.extend(page);
+        match next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(listed, all);
+}
+
 #[test]
 fn test_log_when_panic() {
     let (viewer, root) = get_test_trie_viewer();
@@ -391,6 +586,7 @@ fn test_log_when_panic() {
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
+        deadline: None,
     };
     let mut logs = vec![];
     viewer
@@ -77,6 +77,8 @@ impl User for RpcUser {
             account_id: account_id.clone(),
             prefix: prefix.to_vec().into(),
             include_proof: false,
+            after_key: None,
+            limit: None,
         };
         match self.query(query)?.kind {
             near_jsonrpc_primitives::types::query::QueryResponseKind::ViewState(
@@ -235,7 +235,7 @@ impl User for RuntimeUser {
     fn view_state(&self, account_id: &AccountId, prefix: &[u8]) -> Result<ViewStateResult, String> {
         let state_update = self.client.read().expect(POISONED_LOCK_ERR).get_state_update();
         self.trie_viewer
-            .view_state(&state_update, account_id, prefix, false)
+            .view_state(&state_update, account_id, prefix, None, None, false, None)
             .map_err(|err| err.to_string())
     }
 
@@ -258,6 +258,7 @@ impl User for RuntimeUser {
             block_timestamp: apply_state.block_timestamp,
             current_protocol_version: PROTOCOL_VERSION,
             cache: apply_state.cache,
+            deadline: None,
         };
         result.result = self
             .trie_viewer
@@ -0,0 +1,66 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use near_chain::ChainStore;
+use near_store::Store;
+
+use crate::NearConfig;
+
+/// Number of blocks indexed in a single database transaction.
+const BACKFILL_BATCH_SIZE: u64 = 100;
+
+/// A handle that keeps the state of the outcomes by account index backfill and
+/// can be used to stop it.
+pub struct AccountOutcomeIdsBackfillHandle {
+    join_handle: std::thread::JoinHandle<()>,
+    keep_going: Arc<AtomicBool>,
+}
+
+impl AccountOutcomeIdsBackfillHandle {
+    pub fn stop(self) {
+        self.keep_going.store(false, std::sync::atomic::Ordering::SeqCst);
+        if self.join_handle.join().is_err() {
+            tracing::error!(target: "chain", "Failed to join the account outcome ids backfill thread");
+        }
+    }
+}
+
+fn account_outcome_ids_backfill_loop(keep_going: Arc<AtomicBool>, mut chain_store: ChainStore) {
+    while keep_going.load(std::sync::atomic::Ordering::SeqCst) {
+        match chain_store.backfill_account_outcome_ids(BACKFILL_BATCH_SIZE) {
+            Ok(true) => {
+                tracing::debug!(target: "chain", "Account outcome ids backfill is done");
+                break;
+            }
+            Ok(false) => {}
+            Err(err) => {
+                tracing::error!(target: "chain", "Account outcome ids backfill failed with error: {err}");
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Spawns a background thread which backfills `DBCol::AccountOutcomeIds` with
+/// the blocks saved before `save_account_outcome_ids` got enabled, or clears
+/// the column if it's disabled.  See `ChainStore::backfill_account_outcome_ids`.
+///
+/// The backfill goes through every block from the tail to the head, which
+/// takes a while, so it runs in a native thread in batches rather than
+/// delaying the start of the node.
+pub fn spawn_account_outcome_ids_backfill(
+    config: &NearConfig,
+    store: Store,
+) -> anyhow::Result<AccountOutcomeIdsBackfillHandle> {
+    let mut chain_store = ChainStore::new(
+        store,
+        config.genesis.config.genesis_height,
+        config.client_config.save_trie_changes,
+    );
+    chain_store.set_save_account_outcome_ids(config.client_config.save_account_outcome_ids);
+    let keep_going = Arc::new(AtomicBool::new(true));
+    let keep_going_clone = keep_going.clone();
+    let join_handle = std::thread::Builder::new()
+        .name("account_outcome_ids_backfill".to_string())
+        .spawn(move || account_outcome_ids_backfill_loop(keep_going_clone, chain_store))?;
+    Ok(AccountOutcomeIdsBackfillHandle { join_handle, keep_going })
+}
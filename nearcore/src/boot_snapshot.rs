@@ -0,0 +1,575 @@
+//! Bootstrapping the database of a node from a published snapshot.
+//!
+//! A snapshot is described by a manifest, a JSON [`SnapshotManifest`] listing
+//! the files of the database with their sizes and SHA-256 checksums, and the
+//! chain id and head of the database.  It's published either as:
+//! * the manifest itself, at a URL ending with `.json`, with the files at
+//!   URLs relative to it, or
+//! * a tar archive of the database directory, with the manifest stored as
+//!   `snapshot.json` at the root of the archive.
+//!
+//! Snapshots are downloaded over HTTPS, plain HTTP has to be allowed
+//! explicitly.  Everything is downloaded to `<home>/snapshot-download` first,
+//! so that an interrupted download is resumed (with HTTP range requests) when
+//! the node is started again.  Only once all the files match their checksums
+//! and the head of the downloaded database matches the manifest, the database
+//! is moved to its place in the home directory.  A node whose database already
+//! exists is started as is.
+use anyhow::Context;
+use hyper::body::HttpBody;
+use near_chain::types::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_store::{DBCol, Mode, NodeStorage, HEAD_KEY};
+use sha2::Digest;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::NearConfig;
+
+/// Name of the directory in the home directory the snapshot is downloaded to.
+const DOWNLOAD_DIR: &str = "snapshot-download";
+/// Name of the manifest at the root of tar snapshots.
+const TAR_MANIFEST: &str = "snapshot.json";
+/// How many times a download is attempted, resuming from where the previous
+/// attempt stopped, before giving up.
+const MAX_DOWNLOAD_ATTEMPTS: usize = 5;
+
+type HttpClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct SnapshotManifest {
+    /// Chain id of the genesis the database was created with.
+    pub chain_id: String,
+    /// Head of the database.
+    pub head_height: BlockHeight,
+    pub head_hash: CryptoHash,
+    pub files: Vec<SnapshotFile>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct SnapshotFile {
+    /// Path of the file relative to the database directory.
+    pub path: PathBuf,
+    pub size: u64,
+    /// Hex-encoded SHA-256 checksum of the file.
+    pub sha256: String,
+}
+
+/// Downloads the snapshot at `url` into the database directory of the node,
+/// unless the database already exists.  If `expected_head` is given, the head
+/// of the snapshot must be that block.  `url` must be an HTTPS URL unless
+/// `allow_http` is set.
+pub async fn boot_from_snapshot(
+    home_dir: &Path,
+    config: &NearConfig,
+    url: &str,
+    expected_head: Option<CryptoHash>,
+    allow_http: bool,
+) -> anyhow::Result<()> {
+    let uri: hyper::Uri = url.parse().with_context(|| format!("parsing {}", url))?;
+    match uri.scheme_str() {
+        Some("https") => {}
+        Some("http") if allow_http => {
+            warn!(target: "near", url, "Downloading the snapshot over plain HTTP");
+        }
+        _ => anyhow::bail!("snapshot URL {} is not an HTTPS URL", url),
+    }
+    let opener = NodeStorage::opener(home_dir, &config.config.store, None);
+    let db_path = opener.path();
+    if db_path.exists() {
+        info!(target: "near", path = %db_path.display(), "Database exists, not booting from snapshot");
+        return Ok(());
+    }
+
+    let download_dir = home_dir.join(DOWNLOAD_DIR);
+    std::fs::create_dir_all(&download_dir)
+        .with_context(|| format!("creating {}", download_dir.display()))?;
+    let client = hyper::Client::builder().build::<_, hyper::Body>(hyper_tls::HttpsConnector::new());
+    info!(target: "near", url, dir = %download_dir.display(), "Booting from snapshot");
+
+    let (manifest, staged_db) = if url.ends_with(".json") {
+        download_from_manifest(&client, url, &download_dir).await?
+    } else {
+        download_tar(&client, url, &download_dir).await?
+    };
+
+    let chain_id = &config.genesis.config.chain_id;
+    anyhow::ensure!(
+        &manifest.chain_id == chain_id,
+        "snapshot is of chain {}, but the node is configured for chain {}",
+        manifest.chain_id,
+        chain_id
+    );
+    if let Some(expected_head) = expected_head {
+        anyhow::ensure!(
+            manifest.head_hash == expected_head,
+            "snapshot head is {}, expected {}",
+            manifest.head_hash,
+            expected_head
+        );
+    }
+    verify_head(home_dir, config, &staged_db, &manifest)?;
+
+    std::fs::rename(&staged_db, db_path)
+        .with_context(|| format!("moving {} to {}", staged_db.display(), db_path.display()))?;
+    if let Err(err) = std::fs::remove_dir_all(&download_dir) {
+        warn!(target: "near", path = %download_dir.display(), ?err, "Failed to remove the snapshot download directory");
+    }
+    info!(target: "near", height = manifest.head_height, hash = %manifest.head_hash, "Booted from snapshot");
+    Ok(())
+}
+
+/// Downloads the manifest at `url` and the files it lists to `<dir>/data`.
+/// Files already downloaded by a previous run are only checked.
+async fn download_from_manifest(
+    client: &HttpClient,
+    url: &str,
+    dir: &Path,
+) -> anyhow::Result<(SnapshotManifest, PathBuf)> {
+    let manifest_path = dir.join(TAR_MANIFEST);
+    // The manifest is small, so it's always downloaded anew in case it
+    // changed since the previous run.
+    let _ = std::fs::remove_file(&manifest_path);
+    download_resumable(client, url, &manifest_path).await?;
+    let manifest = read_manifest(&manifest_path)?;
+
+    let base: hyper::Uri = url.parse().with_context(|| format!("parsing {}", url))?;
+    let db_dir = dir.join("data");
+    for file in &manifest.files {
+        let path = db_dir.join(checked_relative_path(&file.path)?);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match std::fs::metadata(&path) {
+            Ok(meta) if meta.len() == file.size && verify_file(&path, file).is_ok() => continue,
+            Ok(meta) if meta.len() >= file.size => std::fs::remove_file(&path)?,
+            _ => {}
+        }
+        let file_url = resolve_url(&base, &file.path)?;
+        info!(target: "near", path = %file.path.display(), size = file.size, "Downloading snapshot file");
+        download_resumable(client, &file_url, &path).await?;
+        if let Err(err) = verify_file(&path, file) {
+            // Don't resume from a corrupted file on the next run.
+            let _ = std::fs::remove_file(&path);
+            return Err(err);
+        }
+    }
+    Ok((manifest, db_dir))
+}
+
+/// Downloads the tar archive at `url` to `<dir>/snapshot.tar`, unpacks it to
+/// `<dir>/data` and checks the unpacked files against the manifest in it.
+async fn download_tar(
+    client: &HttpClient,
+    url: &str,
+    dir: &Path,
+) -> anyhow::Result<(SnapshotManifest, PathBuf)> {
+    let tar_path = dir.join("snapshot.tar");
+    download_resumable(client, url, &tar_path).await?;
+
+    let db_dir = dir.join("data");
+    if db_dir.exists() {
+        std::fs::remove_dir_all(&db_dir)?;
+    }
+    info!(target: "near", path = %tar_path.display(), "Unpacking snapshot");
+    let manifest = unpack_tar(&tar_path, &db_dir)
+        .with_context(|| format!("unpacking {}", tar_path.display()))?;
+    for file in &manifest.files {
+        if let Err(err) = verify_file(&db_dir.join(checked_relative_path(&file.path)?), file) {
+            // Download the archive anew on the next run.
+            let _ = std::fs::remove_file(&tar_path);
+            return Err(err);
+        }
+    }
+    Ok((manifest, db_dir))
+}
+
+/// Unpacks the files of the tar archive at `tar_path` listed in its manifest
+/// to `db_dir` and returns the manifest.  Fails without unpacking anything
+/// else if the archive has entries which aren't in the manifest, so that an
+/// archive can't put arbitrary files, or links, in the database directory.
+fn unpack_tar(tar_path: &Path, db_dir: &Path) -> anyhow::Result<SnapshotManifest> {
+    let manifest = read_tar_manifest(tar_path)?;
+    let files = manifest
+        .files
+        .iter()
+        .map(|file| checked_relative_path(&file.path))
+        .collect::<anyhow::Result<HashSet<_>>>()?;
+    let mut archive = tar::Archive::new(std::fs::File::open(tar_path)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = tar_entry_path(&entry)?;
+        if path == Path::new(TAR_MANIFEST) {
+            continue;
+        }
+        match entry.header().entry_type() {
+            // Directories are created as needed by the files in them.
+            tar::EntryType::Directory if files.iter().any(|file| file.starts_with(&path)) => {
+                continue
+            }
+            tar::EntryType::Regular if files.contains(path.as_path()) => {}
+            _ => anyhow::bail!("{} is not listed in the snapshot manifest", path.display()),
+        }
+        entry.unpack_in(db_dir)?;
+    }
+    Ok(manifest)
+}
+
+/// Reads the manifest at the root of the tar archive at `tar_path`.
+fn read_tar_manifest(tar_path: &Path) -> anyhow::Result<SnapshotManifest> {
+    let mut archive = tar::Archive::new(std::fs::File::open(tar_path)?);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if tar_entry_path(&entry)? == Path::new(TAR_MANIFEST) {
+            return serde_json::from_reader(entry)
+                .with_context(|| format!("parsing {}", TAR_MANIFEST));
+        }
+    }
+    anyhow::bail!("no {} in the archive", TAR_MANIFEST)
+}
+
+/// Path of a tar entry, without the leading `./` of archives created from
+/// within the directory.
+fn tar_entry_path<R: Read>(entry: &tar::Entry<'_, R>) -> anyhow::Result<PathBuf> {
+    Ok(entry.path()?.components().filter(|c| !matches!(c, Component::CurDir)).collect())
+}
+
+/// Downloads `url` to `path`.  If `path` exists, only the rest of the file is
+/// requested, unless the server doesn't support range requests in which case
+/// the file is downloaded anew.  Failed attempts are retried the same way.
+async fn download_resumable(client: &HttpClient, url: &str, path: &Path) -> anyhow::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match download_resumable_once(client, url, path).await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < MAX_DOWNLOAD_ATTEMPTS => {
+                warn!(target: "near", url, attempt, ?err, "Snapshot download failed, resuming");
+                attempt += 1;
+            }
+            Err(err) => return Err(err.context(format!("downloading {}", url))),
+        }
+    }
+}
+
+async fn download_resumable_once(
+    client: &HttpClient,
+    url: &str,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let offset = match tokio::fs::metadata(path).await {
+        Ok(meta) => meta.len(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(err) => return Err(err.into()),
+    };
+    let mut request = hyper::Request::get(url);
+    if offset > 0 {
+        request = request.header(hyper::header::RANGE, format!("bytes={}-", offset));
+    }
+    let mut response = client.request(request.body(hyper::Body::empty())?).await?;
+    let mut file = match response.status() {
+        hyper::StatusCode::PARTIAL_CONTENT if offset > 0 => {
+            tokio::fs::OpenOptions::new().append(true).open(path).await?
+        }
+        hyper::StatusCode::OK => tokio::fs::File::create(path).await?,
+        // The file was already downloaded in full.
+        hyper::StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => return Ok(()),
+        status => anyhow::bail!("unexpected response status {}", status),
+    };
+    while let Some(chunk) = response.data().await {
+        file.write_all(&chunk?).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+fn read_manifest(path: &Path) -> anyhow::Result<SnapshotManifest> {
+    let manifest = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_slice(&manifest).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Checks that the file at `path` has the size and checksum from the manifest.
+fn verify_file(path: &Path, file: &SnapshotFile) -> anyhow::Result<()> {
+    let mut reader =
+        std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut size = 0;
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        size += n as u64;
+    }
+    anyhow::ensure!(
+        size == file.size,
+        "{} has {} bytes, expected {}",
+        file.path.display(),
+        size,
+        file.size
+    );
+    let sha256 = hex::encode(hasher.finalize());
+    anyhow::ensure!(
+        sha256.eq_ignore_ascii_case(&file.sha256),
+        "{} has checksum {}, expected {}",
+        file.path.display(),
+        sha256,
+        file.sha256
+    );
+    Ok(())
+}
+
+/// Checks that the head of the database at `db_path` is the one from the
+/// manifest.  The database must be of the version the node expects.
+fn verify_head(
+    home_dir: &Path,
+    config: &NearConfig,
+    db_path: &Path,
+    manifest: &SnapshotManifest,
+) -> anyhow::Result<()> {
+    let store_config = near_store::StoreConfig {
+        path: Some(db_path.to_path_buf()),
+        ..config.config.store.clone()
+    };
+    let storage = NodeStorage::opener(home_dir, &store_config, None)
+        .open_in_mode(Mode::ReadOnly)
+        .context("opening the snapshot database")?;
+    let head = storage
+        .get_hot_store()
+        .get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?
+        .context("snapshot database has no head")?;
+    anyhow::ensure!(
+        head.height == manifest.head_height && head.last_block_hash == manifest.head_hash,
+        "snapshot database head is {} at height {}, manifest says {} at height {}",
+        head.last_block_hash,
+        head.height,
+        manifest.head_hash,
+        manifest.head_height
+    );
+    Ok(())
+}
+
+/// Returns `path` if it's relative and stays within the directory it's
+/// relative to, so that a manifest can't make us write outside of it.
+fn checked_relative_path(path: &Path) -> anyhow::Result<&Path> {
+    anyhow::ensure!(
+        !path.as_os_str().is_empty()
+            && path.components().all(|c| matches!(c, Component::Normal(_))),
+        "invalid path in snapshot manifest: {}",
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Resolves the URL of a file listed in the manifest at `base`.
+fn resolve_url(base: &hyper::Uri, path: &Path) -> anyhow::Result<String> {
+    let path = checked_relative_path(path)?;
+    let path = path.to_str().with_context(|| format!("non UTF-8 path {}", path.display()))?;
+    let base = base.to_string();
+    let dir = match base.rfind('/') {
+        Some(pos) => &base[..=pos],
+        None => anyhow::bail!("invalid manifest URL {}", base),
+    };
+    Ok(format!("{}{}", dir, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GenesisExt;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use near_chain_configs::Genesis;
+    use near_network::tcp;
+    use near_primitives::hash::hash;
+    use std::convert::Infallible;
+    use std::sync::Arc;
+
+    /// Serves `payload` at any path, honouring `bytes=<offset>-` range
+    /// requests.  Returns the address of the server.
+    fn serve_with_ranges(payload: Vec<u8>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let payload = Arc::new(payload);
+        tokio::task::spawn(async move {
+            let make_svc = make_service_fn(move |_conn| {
+                let payload = Arc::clone(&payload);
+                let handle_request = move |request: Request<Body>| {
+                    let payload = Arc::clone(&payload);
+                    async move {
+                        let offset = request
+                            .headers()
+                            .get(hyper::header::RANGE)
+                            .and_then(|range| range.to_str().ok())
+                            .and_then(|range| range.strip_prefix("bytes="))
+                            .and_then(|range| range.strip_suffix('-'))
+                            .and_then(|offset| offset.parse::<usize>().ok());
+                        let response = match offset {
+                            None => Response::new(Body::from(payload.to_vec())),
+                            Some(offset) if offset >= payload.len() => Response::builder()
+                                .status(hyper::StatusCode::RANGE_NOT_SATISFIABLE)
+                                .body(Body::empty())
+                                .unwrap(),
+                            Some(offset) => Response::builder()
+                                .status(hyper::StatusCode::PARTIAL_CONTENT)
+                                .body(Body::from(payload[offset..].to_vec()))
+                                .unwrap(),
+                        };
+                        Ok::<_, Infallible>(response)
+                    }
+                };
+                async move { Ok::<_, Infallible>(service_fn(handle_request)) }
+            });
+            Server::from_tcp(listener).unwrap().serve(make_svc).await.unwrap();
+        });
+        format!("http://localhost:{}/snapshot.tar", port)
+    }
+
+    #[tokio::test]
+    async fn test_download_resumable() {
+        let payload: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let url = serve_with_ranges(payload.clone());
+        let client =
+            hyper::Client::builder().build::<_, hyper::Body>(hyper_tls::HttpsConnector::new());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.tar");
+
+        // Resumes a partial download.
+        std::fs::write(&path, &payload[..12_345]).unwrap();
+        download_resumable(&client, &url, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), payload);
+
+        // A complete download is left as is.
+        download_resumable(&client, &url, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), payload);
+
+        // From scratch.
+        std::fs::remove_file(&path).unwrap();
+        download_resumable(&client, &url, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("000001.sst");
+        std::fs::write(&path, b"hello").unwrap();
+        let mut file = SnapshotFile {
+            path: "000001.sst".into(),
+            size: 5,
+            sha256: "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824".into(),
+        };
+        verify_file(&path, &file).unwrap();
+        file.sha256.replace_range(..1, "3");
+        assert!(verify_file(&path, &file).is_err());
+        file.size = 6;
+        assert!(verify_file(&path, &file).is_err());
+    }
+
+    fn append_file(builder: &mut tar::Builder<Vec<u8>>, path: &str, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+
+    fn write_tar(path: &Path, files: &[(&str, &[u8])]) {
+        let manifest = SnapshotManifest {
+            chain_id: "testnet".to_string(),
+            head_height: 42,
+            head_hash: CryptoHash::default(),
+            files: vec![SnapshotFile {
+                path: "000001.sst".into(),
+                size: 5,
+                sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into(),
+            }],
+        };
+        let mut builder = tar::Builder::new(vec![]);
+        append_file(&mut builder, TAR_MANIFEST, &serde_json::to_vec(&manifest).unwrap());
+        for (path, data) in files {
+            append_file(&mut builder, path, data);
+        }
+        std::fs::write(path, builder.into_inner().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_unpack_tar() {
+        let dir = tempfile::tempdir().unwrap();
+        let tar_path = dir.path().join("snapshot.tar");
+        let db_dir = dir.path().join("data");
+
+        write_tar(&tar_path, &[("./000001.sst", b"hello")]);
+        let manifest = unpack_tar(&tar_path, &db_dir).unwrap();
+        assert_eq!(manifest.head_height, 42);
+        assert_eq!(std::fs::read(db_dir.join("000001.sst")).unwrap(), b"hello");
+        assert!(!db_dir.join(TAR_MANIFEST).exists());
+
+        // Entries which aren't in the manifest are rejected.
+        std::fs::remove_dir_all(&db_dir).unwrap();
+        write_tar(&tar_path, &[("000001.sst", b"hello"), ("OPTIONS-000002", b"evil")]);
+        let err = unpack_tar(&tar_path, &db_dir).unwrap_err();
+        assert!(err.to_string().contains("OPTIONS-000002"), "{}", err);
+        assert!(!db_dir.join("OPTIONS-000002").exists());
+    }
+
+    /// The snapshot is rejected, and the database isn't created, if it's not
+    /// of the chain of the node or doesn't have the expected head.
+    #[tokio::test]
+    async fn test_boot_from_snapshot_mismatch() {
+        let src = tempfile::tempdir().unwrap();
+        let tar_path = src.path().join("snapshot.tar");
+        write_tar(&tar_path, &[("000001.sst", b"hello")]);
+        let url = serve_with_ranges(std::fs::read(&tar_path).unwrap());
+
+        let home = tempfile::tempdir().unwrap();
+        let genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+        let mut config =
+            crate::config::load_test_config("", tcp::ListenerAddr::reserve_for_test(), genesis);
+        config.genesis.config.chain_id = "localnet".to_string();
+        let db_path =
+            NodeStorage::opener(home.path(), &config.config.store, None).path().to_owned();
+
+        let err = boot_from_snapshot(home.path(), &config, &url, None, false).await.unwrap_err();
+        assert!(err.to_string().contains("not an HTTPS URL"), "{:#}", err);
+
+        let err = boot_from_snapshot(home.path(), &config, &url, None, true).await.unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "snapshot is of chain testnet, but the node is configured for chain localnet"
+            ),
+            "{:#}",
+            err
+        );
+        assert!(!db_path.exists());
+
+        config.genesis.config.chain_id = "testnet".to_string();
+        let err = boot_from_snapshot(home.path(), &config, &url, Some(hash(b"head")), true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("snapshot head is"), "{:#}", err);
+        assert!(!db_path.exists());
+    }
+
+    #[test]
+    fn test_manifest_paths() {
+        let base: hyper::Uri =
+            "https://example.com/snapshots/mainnet/manifest.json".parse().unwrap();
+        assert_eq!(
+            resolve_url(&base, Path::new("000001.sst")).unwrap(),
+            "https://example.com/snapshots/mainnet/000001.sst"
+        );
+        assert_eq!(
+            resolve_url(&base, Path::new("sub/CURRENT")).unwrap(),
+            "https://example.com/snapshots/mainnet/sub/CURRENT"
+        );
+        for path in ["../CURRENT", "/etc/passwd", "a/../../b", "./CURRENT", ""] {
+            assert!(checked_relative_path(Path::new(path)).is_err(), "{}", path);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
@@ -16,10 +16,13 @@ use tempfile::tempdir;
 use tracing::{info, warn};
 
 use near_chain_configs::{
-    get_initial_supply, ClientConfig, GCConfig, Genesis, GenesisConfig, GenesisValidationMode,
-    LogSummaryStyle, MutableConfigValue,
+    get_initial_supply, AlertsConfig, ArchivalPolicy, ClientConfig, EmptyBlocksPolicy,
+    EpochSnapshotConfig, GCConfig, Genesis, GenesisConfig, GenesisValidationMode, LogSummaryStyle,
+    MutableConfigValue, ProtocolVersionRange,
 };
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
+#[cfg(feature = "grpc")]
+use near_grpc::GrpcConfig;
 #[cfg(feature = "json_rpc")]
 use near_jsonrpc::RpcConfig;
 use near_network::config::NetworkConfig;
@@ -36,12 +39,13 @@ use near_primitives::types::{
 };
 use near_primitives::utils::{generate_random_string, get_num_seats_per_shard};
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
-use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::RosettaRpcConfig;
 use near_telemetry::TelemetryConfig;
 
 use crate::download_file::{run_download_file, FileDownloadError};
+use crate::keys::{load_key_file, KeySource};
 
 /// Initial balance used in tests.
 pub const TESTING_INIT_BALANCE: Balance = 1_000_000_000 * NEAR_BASE;
@@ -153,6 +157,23 @@ fn default_reduce_wait_for_missing_block() -> Duration {
     Duration::from_millis(REDUCE_DELAY_FOR_MISSING_BLOCKS)
 }
 
+fn deserialize_empty_blocks_policy<'de, D>(deserializer: D) -> Result<EmptyBlocksPolicy, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Bool(bool),
+        Policy(EmptyBlocksPolicy),
+    }
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(true) => EmptyBlocksPolicy::Always,
+        Value::Bool(false) => EmptyBlocksPolicy::Never,
+        Value::Policy(policy) => policy,
+    })
+}
+
 fn default_header_sync_initial_timeout() -> Duration {
     Duration::from_secs(10)
 }
@@ -185,10 +206,34 @@ fn default_sync_height_threshold() -> u64 {
     1
 }
 
+fn default_state_sync_fallback() -> bool {
+    true
+}
+
 fn default_view_client_threads() -> usize {
     4
 }
 
+fn default_view_call_threads() -> usize {
+    2
+}
+
+fn default_view_call_max_pending() -> usize {
+    64
+}
+
+fn default_client_queue_capacity() -> usize {
+    10_000
+}
+
+fn default_view_client_queue_capacity() -> usize {
+    1_000
+}
+
+fn default_view_call_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
 fn default_doomslug_step_period() -> Duration {
     Duration::from_millis(100)
 }
@@ -197,14 +242,52 @@ fn default_view_client_throttle_period() -> Duration {
     Duration::from_secs(30)
 }
 
+fn default_state_request_limit() -> usize {
+    // Each view client thread used to serve up to 30 requests per period on
+    // its own, the limit shared by the threads keeps that throughput.
+    30 * default_view_client_threads()
+}
+
+fn default_state_request_limit_per_peer() -> usize {
+    30
+}
+
+fn default_state_request_max_in_progress() -> usize {
+    default_view_client_threads()
+}
+
+fn default_state_parts_cache_size() -> u64 {
+    10 << 30 // 10 GiB
+}
+
 fn default_trie_viewer_state_size_limit() -> Option<u64> {
     Some(50_000)
 }
 
+fn default_slow_receipt_factor() -> Option<f64> {
+    Some(10.0)
+}
+
+fn default_recent_partial_chunks_num_heights() -> BlockHeightDelta {
+    32
+}
+
+fn default_kickout_warning_margin() -> u8 {
+    10
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ConfigValidationError {
     #[error("Configuration with archive = false and save_trie_changes = false is not supported because non-archival nodes must save trie changes in order to do do garbage collection.")]
     TrieChanges,
+    #[error("Configuration with minimal_chunk_producer = true requires archive = false and empty tracked_shards and tracked_accounts, because such a node tracks only the shards it produces chunks for.")]
+    MinimalChunkProducer,
+    #[error("This binary supports protocol version {}, which is outside of expected_protocol_version_range [{}, {}] set in the config.  Please install a release supporting the expected protocol versions or update the range.", PROTOCOL_VERSION, .0.min, .0.max)]
+    ProtocolVersionRange(ProtocolVersionRange),
+    #[error("Configuration with sync_serving_bandwidth_limit or state_serving_bandwidth_limit set to 0 is not supported, because the node wouldn't serve any sync requests.  Leave them unset to disable the limits.")]
+    SyncServingBandwidthLimit,
+    #[error("Invalid rpc configuration: {0}")]
+    Rpc(String),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -222,10 +305,16 @@ pub struct Consensus {
     /// Duration to reduce the wait for each missed block by validator.
     #[serde(default = "default_reduce_wait_for_missing_block")]
     pub reduce_wait_for_missing_block: Duration,
-    /// Produce empty blocks, use `false` for testing.
-    pub produce_empty_blocks: bool,
+    /// See `ClientConfig::produce_empty_blocks`.  Booleans are accepted for
+    /// compatibility with older configs.
+    #[serde(deserialize_with = "deserialize_empty_blocks_policy")]
+    pub produce_empty_blocks: EmptyBlocksPolicy,
     /// Horizon at which instead of fetching block, fetch full state.
     pub block_fetch_horizon: BlockHeightDelta,
+    /// Whether to switch to state sync when more than `block_fetch_horizon`
+    /// blocks behind.  If disabled, the node applies all the missing blocks.
+    #[serde(default = "default_state_sync_fallback")]
+    pub state_sync_fallback: bool,
     /// Horizon to step from the latest block when fetching state.
     pub state_fetch_horizon: NumBlocks,
     /// Behind this horizon header fetch kicks in.
@@ -271,8 +360,9 @@ impl Default for Consensus {
             max_block_production_delay: Duration::from_millis(MAX_BLOCK_PRODUCTION_DELAY),
             max_block_wait_delay: Duration::from_millis(MAX_BLOCK_WAIT_DELAY),
             reduce_wait_for_missing_block: default_reduce_wait_for_missing_block(),
-            produce_empty_blocks: true,
+            produce_empty_blocks: EmptyBlocksPolicy::Always,
             block_fetch_horizon: BLOCK_FETCH_HORIZON,
+            state_sync_fallback: default_state_sync_fallback(),
             state_fetch_horizon: STATE_FETCH_HORIZON,
             block_header_fetch_horizon: BLOCK_HEADER_FETCH_HORIZON,
             catchup_step_period: Duration::from_millis(CATCHUP_STEP_PERIOD),
@@ -304,6 +394,9 @@ pub struct Config {
     #[cfg(feature = "rosetta_rpc")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rosetta_rpc: Option<RosettaRpcConfig>,
+    #[cfg(feature = "grpc")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcConfig>,
     pub telemetry: TelemetryConfig,
     pub network: near_network::config_json::Config,
     pub consensus: Consensus,
@@ -311,6 +404,9 @@ pub struct Config {
     pub tracked_shards: Vec<ShardId>,
     #[serde(skip_serializing_if = "is_false")]
     pub archive: bool,
+    /// Data kept by archival nodes, everything unless set.
+    #[serde(skip_serializing_if = "ArchivalPolicy::keeps_everything")]
+    pub archival_policy: ArchivalPolicy,
     /// If save_trie_changes is not set it will get inferred from the `archive` field as follows:
     /// save_trie_changes = !archive
     /// save_trie_changes should be set to true iff
@@ -325,14 +421,55 @@ pub struct Config {
     pub gc: GCConfig,
     #[serde(default = "default_view_client_threads")]
     pub view_client_threads: usize,
+    /// Size of the pool executing contract function calls of `query`
+    /// requests.  See `ClientConfig::view_call_threads`.
+    #[serde(default = "default_view_call_threads")]
+    pub view_call_threads: usize,
+    #[serde(default = "default_view_call_max_pending")]
+    pub view_call_max_pending: usize,
+    #[serde(default = "default_view_call_timeout")]
+    pub view_call_timeout: Duration,
+    /// See `ClientConfig::client_queue_capacity`.
+    #[serde(default = "default_client_queue_capacity")]
+    pub client_queue_capacity: usize,
+    /// See `ClientConfig::view_client_queue_capacity`.
+    #[serde(default = "default_view_client_queue_capacity")]
+    pub view_client_queue_capacity: usize,
     pub epoch_sync_enabled: bool,
     #[serde(default = "default_view_client_throttle_period")]
     pub view_client_throttle_period: Duration,
+    /// See `ClientConfig::state_request_limit`.
+    #[serde(default = "default_state_request_limit")]
+    pub state_request_limit: usize,
+    /// See `ClientConfig::state_request_limit_per_peer`.
+    #[serde(default = "default_state_request_limit_per_peer")]
+    pub state_request_limit_per_peer: usize,
+    /// See `ClientConfig::state_request_max_in_progress`.
+    #[serde(default = "default_state_request_max_in_progress")]
+    pub state_request_max_in_progress: usize,
+    /// See `ClientConfig::sync_serving_bandwidth_limit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_serving_bandwidth_limit: Option<u64>,
+    /// See `ClientConfig::state_serving_bandwidth_limit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_serving_bandwidth_limit: Option<u64>,
+    /// See `ClientConfig::state_parts_cache_size`.
+    #[serde(default = "default_state_parts_cache_size")]
+    pub state_parts_cache_size: u64,
     #[serde(default = "default_trie_viewer_state_size_limit")]
     pub trie_viewer_state_size_limit: Option<u64>,
     /// If set, overrides value in genesis configuration.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_gas_burnt_view: Option<Gas>,
+    /// See `ClientConfig::slow_receipt_factor`.
+    #[serde(default = "default_slow_receipt_factor")]
+    pub slow_receipt_factor: Option<f64>,
+    /// See `ClientConfig::shadow_protocol_version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_protocol_version: Option<ProtocolVersion>,
+    /// See `ClientConfig::message_log`.  Relative to the home directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_log: Option<PathBuf>,
     /// Different parameters to configure underlying storage.
     pub store: near_store::StoreConfig,
     /// Different parameters to configure underlying cold storage.
@@ -353,6 +490,54 @@ pub struct Config {
     pub db_migration_snapshot_path: Option<PathBuf>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub expected_shutdown: Option<BlockHeight>,
+    /// If set, the node runs block and chunk production of this validator
+    /// account in dry-run mode without broadcasting anything.  See
+    /// `ClientConfig::dry_run_validator`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run_validator: Option<AccountId>,
+    /// If set, the node keeps an index of execution outcomes by the account
+    /// they were executed on.  See `ClientConfig::save_account_outcome_ids`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub save_account_outcome_ids: bool,
+    /// If set, new chunks whose application takes longer than this are
+    /// logged.  See `ClientConfig::slow_chunk_threshold`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_chunk_threshold: Option<Duration>,
+    /// Number of threads applying the chunks of a block in parallel.  See
+    /// `ClientConfig::apply_chunks_threads`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apply_chunks_threads: Option<usize>,
+    /// Number of heights below the head for which the received parts of
+    /// chunks are kept on disk.  See
+    /// `ClientConfig::recent_partial_chunks_num_heights`.
+    #[serde(default = "default_recent_partial_chunks_num_heights")]
+    pub recent_partial_chunks_num_heights: BlockHeightDelta,
+    /// If set, the node sends alerts about missed block and chunk production,
+    /// falling behind and losing peers.  See `AlertsConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertsConfig>,
+    /// If set, the node creates a checkpoint of its database at every epoch
+    /// boundary.  See `EpochSnapshotConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch_snapshot: Option<EpochSnapshotConfig>,
+    /// If set, state parts are streamed from the debug RPC server at this
+    /// address.  See `ClientConfig::state_handover_addr`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_handover_addr: Option<String>,
+    /// If set, the node runs as a chunk-only producer tracking only the
+    /// shards it's assigned to.  See `ClientConfig::minimal_chunk_producer`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub minimal_chunk_producer: bool,
+    /// Percentage points above the kickout threshold below which the
+    /// production of the validator is warned about.  See
+    /// `ClientConfig::kickout_warning_margin`.
+    #[serde(default = "default_kickout_warning_margin")]
+    pub kickout_warning_margin: u8,
+    /// If set, the node refuses to start with a binary whose protocol version
+    /// is outside of this range.  See
+    /// `ClientConfig::expected_protocol_version_range`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_protocol_version_range: Option<ProtocolVersionRange>,
 }
 
 fn is_false(value: &bool) -> bool {
@@ -370,25 +555,53 @@ impl Default for Config {
             rpc: Some(RpcConfig::default()),
             #[cfg(feature = "rosetta_rpc")]
             rosetta_rpc: None,
+            #[cfg(feature = "grpc")]
+            grpc: None,
             telemetry: TelemetryConfig::default(),
             network: Default::default(),
             consensus: Consensus::default(),
             tracked_accounts: vec![],
             tracked_shards: vec![],
             archive: false,
+            archival_policy: ArchivalPolicy::default(),
             save_trie_changes: None,
             log_summary_style: LogSummaryStyle::Colored,
             gc: GCConfig::default(),
             epoch_sync_enabled: true,
             view_client_threads: default_view_client_threads(),
+            view_call_threads: default_view_call_threads(),
+            view_call_max_pending: default_view_call_max_pending(),
+            view_call_timeout: default_view_call_timeout(),
+            client_queue_capacity: default_client_queue_capacity(),
+            view_client_queue_capacity: default_view_client_queue_capacity(),
             view_client_throttle_period: default_view_client_throttle_period(),
+            state_request_limit: default_state_request_limit(),
+            state_request_limit_per_peer: default_state_request_limit_per_peer(),
+            state_request_max_in_progress: default_state_request_max_in_progress(),
+            sync_serving_bandwidth_limit: None,
+            state_serving_bandwidth_limit: None,
+            state_parts_cache_size: default_state_parts_cache_size(),
             trie_viewer_state_size_limit: default_trie_viewer_state_size_limit(),
             max_gas_burnt_view: None,
+            slow_receipt_factor: default_slow_receipt_factor(),
+            shadow_protocol_version: None,
+            message_log: None,
             db_migration_snapshot_path: None,
             use_db_migration_snapshot: None,
             store: near_store::StoreConfig::default(),
             cold_store: None,
             expected_shutdown: None,
+            dry_run_validator: None,
+            save_account_outcome_ids: false,
+            slow_chunk_threshold: None,
+            apply_chunks_threads: None,
+            recent_partial_chunks_num_heights: default_recent_partial_chunks_num_heights(),
+            alerts: None,
+            epoch_snapshot: None,
+            state_handover_addr: None,
+            minimal_chunk_producer: false,
+            kickout_warning_margin: default_kickout_warning_margin(),
+            expected_protocol_version_range: None,
         }
     }
 }
@@ -435,10 +648,26 @@ impl Config {
     /// `validate()` is called every time `config.json` is read.
     fn validate(&self) -> Result<(), ConfigValidationError> {
         if self.archive == false && self.save_trie_changes == Some(false) {
-            Err(ConfigValidationError::TrieChanges)
-        } else {
-            Ok(())
+            return Err(ConfigValidationError::TrieChanges);
+        }
+        if self.minimal_chunk_producer
+            && (self.archive
+                || !self.tracked_shards.is_empty()
+                || !self.tracked_accounts.is_empty())
+        {
+            return Err(ConfigValidationError::MinimalChunkProducer);
         }
+        if let Some(range) = self.expected_protocol_version_range {
+            if !range.contains(PROTOCOL_VERSION) {
+                return Err(ConfigValidationError::ProtocolVersionRange(range));
+            }
+        }
+        if self.sync_serving_bandwidth_limit == Some(0)
+            || self.state_serving_bandwidth_limit == Some(0)
+        {
+            return Err(ConfigValidationError::SyncServingBandwidthLimit);
+        }
+        Ok(())
         // TODO: Add more config validation.
         // TODO: Validate `ClientConfig` instead.
     }
@@ -457,6 +686,15 @@ impl Config {
         None
     }
 
+    /// Address serving the admin RPC methods, `rpc.debug_addr`.
+    pub fn debug_rpc_addr(&self) -> Option<String> {
+        #[cfg(feature = "json_rpc")]
+        if let Some(rpc) = &self.rpc {
+            return rpc.debug_addr.clone();
+        }
+        None
+    }
+
     #[allow(unused_variables)]
     pub fn set_rpc_addr(&mut self, addr: tcp::ListenerAddr) {
         #[cfg(feature = "json_rpc")]
@@ -576,6 +814,8 @@ pub struct NearConfig {
     pub rpc_config: Option<RpcConfig>,
     #[cfg(feature = "rosetta_rpc")]
     pub rosetta_rpc_config: Option<RosettaRpcConfig>,
+    #[cfg(feature = "grpc")]
+    pub grpc_config: Option<GrpcConfig>,
     pub telemetry_config: TelemetryConfig,
     pub genesis: Genesis,
     pub validator_signer: Option<Arc<dyn ValidatorSigner>>,
@@ -616,13 +856,17 @@ impl NearConfig {
                 state_sync_timeout: config.consensus.state_sync_timeout,
                 min_num_peers: config.consensus.min_num_peers,
                 log_summary_period: Duration::from_secs(10),
-                produce_empty_blocks: config.consensus.produce_empty_blocks,
+                produce_empty_blocks: MutableConfigValue::new(
+                    config.consensus.produce_empty_blocks,
+                    "produce_empty_blocks",
+                ),
                 epoch_length: genesis.config.epoch_length,
                 num_block_producer_seats: genesis.config.num_block_producer_seats,
                 announce_account_horizon: genesis.config.epoch_length / 2,
                 ttl_account_id_router: config.network.ttl_account_id_router,
                 // TODO(1047): this should be adjusted depending on the speed of sync of state.
                 block_fetch_horizon: config.consensus.block_fetch_horizon,
+                state_sync_fallback: config.consensus.state_sync_fallback,
                 state_fetch_horizon: config.consensus.state_fetch_horizon,
                 block_header_fetch_horizon: config.consensus.block_header_fetch_horizon,
                 catchup_step_period: config.consensus.catchup_step_period,
@@ -631,17 +875,44 @@ impl NearConfig {
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
                 archive: config.archive,
+                archival_policy: config.archival_policy,
                 save_trie_changes: config.save_trie_changes.unwrap_or(!config.archive),
                 log_summary_style: config.log_summary_style,
                 gc: config.gc,
                 view_client_threads: config.view_client_threads,
+                view_call_threads: config.view_call_threads,
+                view_call_max_pending: config.view_call_max_pending,
+                view_call_timeout: config.view_call_timeout,
+                client_queue_capacity: config.client_queue_capacity,
+                view_client_queue_capacity: config.view_client_queue_capacity,
                 epoch_sync_enabled: config.epoch_sync_enabled,
                 view_client_throttle_period: config.view_client_throttle_period,
+                state_request_limit: config.state_request_limit,
+                state_request_limit_per_peer: config.state_request_limit_per_peer,
+                state_request_max_in_progress: config.state_request_max_in_progress,
+                sync_serving_bandwidth_limit: config.sync_serving_bandwidth_limit,
+                state_serving_bandwidth_limit: config.state_serving_bandwidth_limit,
+                state_parts_cache_size: config.state_parts_cache_size,
                 trie_viewer_state_size_limit: config.trie_viewer_state_size_limit,
                 max_gas_burnt_view: config.max_gas_burnt_view,
+                slow_receipt_factor: config.slow_receipt_factor,
+                shadow_protocol_version: config.shadow_protocol_version,
+                message_log: config.message_log,
                 enable_statistics_export: config.store.enable_statistics_export,
                 client_background_migration_threads: config.store.background_migration_threads,
                 flat_storage_creation_period: config.store.flat_storage_creation_period,
+                dry_run_validator: config.dry_run_validator,
+                offline: false,
+                save_account_outcome_ids: config.save_account_outcome_ids,
+                slow_chunk_threshold: config.slow_chunk_threshold,
+                apply_chunks_threads: config.apply_chunks_threads,
+                recent_partial_chunks_num_heights: config.recent_partial_chunks_num_heights,
+                alerts: config.alerts,
+                epoch_snapshot: config.epoch_snapshot,
+                state_handover_addr: config.state_handover_addr,
+                minimal_chunk_producer: config.minimal_chunk_producer,
+                kickout_warning_margin: config.kickout_warning_margin,
+                expected_protocol_version_range: config.expected_protocol_version_range,
             },
             network_config: NetworkConfig::new(
                 config.network,
@@ -654,6 +925,8 @@ impl NearConfig {
             rpc_config: config.rpc,
             #[cfg(feature = "rosetta_rpc")]
             rosetta_rpc_config: config.rosetta_rpc,
+            #[cfg(feature = "grpc")]
+            grpc_config: config.grpc,
             genesis,
             validator_signer,
         })
@@ -1128,24 +1401,26 @@ pub fn create_testnet_configs_from_seeds(
         shard_layout,
     );
     let mut configs = vec![];
-    let first_node_addr = tcp::ListenerAddr::reserve_for_test();
+    let node_addrs = if local_ports {
+        seeds.iter().map(|_| tcp::ListenerAddr::reserve_for_test()).collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
     for i in 0..seeds.len() {
         let mut config = Config::default();
         config.rpc.get_or_insert(Default::default()).enable_debug_rpc = true;
         config.consensus.min_block_production_delay = Duration::from_millis(600);
         config.consensus.max_block_production_delay = Duration::from_millis(2000);
         if local_ports {
-            config.network.addr = if i == 0 {
-                first_node_addr.to_string()
-            } else {
-                tcp::ListenerAddr::reserve_for_test().to_string()
-            };
+            config.network.addr = node_addrs[i].to_string();
             config.set_rpc_addr(tcp::ListenerAddr::reserve_for_test());
-            config.network.boot_nodes = if i == 0 {
-                "".to_string()
-            } else {
-                format!("{}@{}", network_signers[0].public_key, first_node_addr)
-            };
+            // Every node uses all the validators as boot nodes so that the
+            // network stays connected whichever of them is down.
+            config.network.boot_nodes = (0..num_validator_seats as usize)
+                .filter(|&j| j != i)
+                .map(|j| format!("{}@{}", network_signers[j].public_key, node_addrs[j]))
+                .collect::<Vec<_>>()
+                .join(",");
             config.network.skip_sync_wait = num_validator_seats == 1;
         }
         config.archive = archive;
@@ -1199,28 +1474,110 @@ pub fn create_testnet_configs(
     (configs, validator_signers, network_signers, genesis, shard_keys)
 }
 
+/// Genesis parameters of a testnet which, when set, override the ones used by
+/// [`Genesis::test_with_seeds`].
+#[derive(Debug, Default, Clone)]
+pub struct TestnetGenesisOverrides {
+    pub epoch_length: Option<BlockHeightDelta>,
+    pub gas_limit: Option<Gas>,
+    pub min_gas_price: Option<Balance>,
+    pub max_gas_price: Option<Balance>,
+    /// Records added to the genesis state, e.g. the contract state of an
+    /// account dumped by `state-viewer dump-contract-state`.  Every added
+    /// account gets a full access key of the first validator so that the
+    /// validator key can sign its transactions.
+    pub extra_records: Vec<StateRecord>,
+}
+
+impl TestnetGenesisOverrides {
+    fn apply(&self, genesis: &mut Genesis) {
+        let config = &mut genesis.config;
+        if let Some(epoch_length) = self.epoch_length {
+            config.epoch_length = epoch_length;
+        }
+        if let Some(gas_limit) = self.gas_limit {
+            config.gas_limit = gas_limit;
+        }
+        if let Some(min_gas_price) = self.min_gas_price {
+            config.min_gas_price = min_gas_price;
+        }
+        if let Some(max_gas_price) = self.max_gas_price {
+            config.max_gas_price = max_gas_price;
+        }
+        if self.extra_records.is_empty() {
+            return;
+        }
+
+        let public_key = config.validators[0].public_key.clone();
+        let mut total_supply = config.total_supply;
+        let records = &mut genesis.force_read_records().0;
+        for record in &self.extra_records {
+            let mut record = record.clone();
+            if let StateRecord::Account { account_id, account } = &mut record {
+                assert!(
+                    !records.iter().any(|r| matches!(r,
+                        StateRecord::Account { account_id: id, .. } if id == account_id)),
+                    "Account {} from extra records already exists in genesis",
+                    account_id
+                );
+                // Only genesis validators may have stake locked.
+                account.set_amount(account.amount() + account.locked());
+                account.set_locked(0);
+                total_supply += account.amount();
+                records.push(StateRecord::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key: public_key.clone(),
+                    access_key: AccessKey::full_access(),
+                });
+            }
+            records.push(record);
+        }
+        genesis.config.total_supply = total_supply;
+    }
+}
+
+/// Creates home directories of a testnet in `dir`, one per node.
+///
+/// The first `num_validator_seats` nodes are validators, the rest are
+/// non-validators out of which the last `num_archival_nodes` are archival
+/// nodes tracking all shards.  If `archive` is set, all nodes are archival.
 pub fn init_testnet_configs(
     dir: &Path,
     num_shards: NumShards,
     num_validator_seats: NumSeats,
     num_non_validator_seats: NumSeats,
+    num_archival_nodes: NumSeats,
     prefix: &str,
     local_ports: bool,
     archive: bool,
     fixed_shards: bool,
     tracked_shards: Vec<u64>,
+    genesis_overrides: &TestnetGenesisOverrides,
 ) {
-    let (configs, validator_signers, network_signers, genesis, shard_keys) = create_testnet_configs(
-        num_shards,
-        num_validator_seats,
-        num_non_validator_seats,
-        prefix,
-        local_ports,
-        archive,
-        fixed_shards,
-        tracked_shards,
+    assert!(
+        num_archival_nodes <= num_non_validator_seats,
+        "Number of archival nodes ({}) can't exceed the number of non-validators ({})",
+        num_archival_nodes,
+        num_non_validator_seats
     );
-    for i in 0..(num_validator_seats + num_non_validator_seats) as usize {
+    let (mut configs, validator_signers, network_signers, mut genesis, shard_keys) =
+        create_testnet_configs(
+            num_shards,
+            num_validator_seats,
+            num_non_validator_seats,
+            prefix,
+            local_ports,
+            archive,
+            fixed_shards,
+            tracked_shards,
+        );
+    genesis_overrides.apply(&mut genesis);
+    let num_nodes = (num_validator_seats + num_non_validator_seats) as usize;
+    for config in &mut configs[num_nodes - num_archival_nodes as usize..] {
+        config.archive = true;
+        config.tracked_shards = (0..num_shards).collect();
+    }
+    for i in 0..num_nodes {
         let node_dir = dir.join(format!("{}{}", prefix, i));
         fs::create_dir_all(node_dir.clone()).expect("Failed to create directory");
 
@@ -1289,57 +1646,50 @@ pub fn download_config(url: &str, path: &Path) -> Result<(), FileDownloadError>
     result
 }
 
-#[derive(Deserialize)]
-struct NodeKeyFile {
-    account_id: String,
-    public_key: PublicKey,
-    secret_key: near_crypto::SecretKey,
-}
-
-impl NodeKeyFile {
-    fn from_file(path: &Path) -> std::io::Result<Self> {
-        let mut file = File::open(path)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-        Ok(serde_json::from_str(&content)?)
-    }
-}
-
-impl From<NodeKeyFile> for KeyFile {
-    fn from(this: NodeKeyFile) -> Self {
-        Self {
-            account_id: if this.account_id.is_empty() {
-                "node".to_string()
-            } else {
-                this.account_id
-            }
-            .try_into()
-            .unwrap(),
-            public_key: this.public_key,
-            secret_key: this.secret_key,
-        }
-    }
-}
-
 pub fn load_config(
     dir: &Path,
     genesis_validation: GenesisValidationMode,
 ) -> anyhow::Result<NearConfig> {
-    let config = Config::from_file(&dir.join(CONFIG_FILENAME))?;
+    let mut config = Config::from_file(&dir.join(CONFIG_FILENAME))?;
+    #[cfg(feature = "json_rpc")]
+    if let Some(tls) = config.rpc.as_mut().and_then(|rpc| rpc.tls.as_mut()) {
+        tls.cert_file = dir.join(&tls.cert_file);
+        tls.key_file = dir.join(&tls.key_file);
+    }
+    #[cfg(feature = "json_rpc")]
+    if let Some(relayer_key_file) =
+        config.rpc.as_mut().and_then(|rpc| rpc.relayer_key_file.as_mut())
+    {
+        *relayer_key_file = dir.join(&relayer_key_file);
+    }
+    // Validated once the files it refers to are resolved relative to `dir`.
+    #[cfg(feature = "json_rpc")]
+    if let Some(rpc) = &config.rpc {
+        rpc.validate().map_err(|err| ConfigValidationError::Rpc(format!("{:#}", err)))?;
+    }
+    if let Some(epoch_snapshot) = config.epoch_snapshot.as_mut() {
+        epoch_snapshot.path = dir.join(&epoch_snapshot.path);
+    }
+    if let Some(message_log) = config.message_log.as_mut() {
+        *message_log = dir.join(&message_log);
+    }
     let genesis_file = dir.join(&config.genesis_file);
-    let validator_file = dir.join(&config.validator_key_file);
-    let validator_signer = if validator_file.exists() {
-        let signer = InMemoryValidatorSigner::from_file(&validator_file).with_context(|| {
-            format!("Failed initializing validator signer from {}", validator_file.display())
-        })?;
-        Some(Arc::new(signer) as Arc<dyn ValidatorSigner>)
-    } else {
-        None
-    };
-    let node_key_path = dir.join(&config.node_key_file);
-    let network_signer = NodeKeyFile::from_file(&node_key_path).with_context(|| {
-        format!("Failed reading node key file from {}", node_key_path.display())
-    })?;
+    let validator_source = KeySource::parse(dir, &config.validator_key_file)?;
+    let validator_signer = load_key_file(&validator_source)
+        .context("Failed initializing validator signer")?
+        .map(|key| {
+            let signer = InMemoryValidatorSigner::from_signer(key.into());
+            Arc::new(signer) as Arc<dyn ValidatorSigner>
+        });
+    let node_key_source = KeySource::parse(dir, &config.node_key_file)?;
+    let network_signer = load_key_file(&node_key_source)
+        .context("Failed reading node key")?
+        .ok_or_else(|| anyhow!("Node key file {} doesn’t exist", node_key_source))?;
+    anyhow::ensure!(
+        !config.minimal_chunk_producer || validator_signer.is_some(),
+        "minimal_chunk_producer requires a validator key, {} doesn’t exist",
+        validator_source
+    );
 
     let genesis = match &config.genesis_records_file {
         Some(records_file) => {
@@ -1351,11 +1701,11 @@ pub fn load_config(
     if matches!(genesis.config.chain_id.as_ref(), "mainnet" | "testnet" | "betanet") {
         // Make sure validators tracks all shards, see
         // https://github.com/near/nearcore/issues/7388
-        anyhow::ensure!(!config.tracked_shards.is_empty(),
+        anyhow::ensure!(!config.tracked_shards.is_empty() || config.minimal_chunk_producer,
                         "Validator must track all shards. Please change `tracked_shards` field in config.json to be any non-empty vector");
     }
 
-    NearConfig::new(config, genesis, network_signer.into(), validator_signer)
+    NearConfig::new(config, genesis, network_signer, validator_signer)
 }
 
 pub fn load_test_config(seed: &str, addr: tcp::ListenerAddr, genesis: Genesis) -> NearConfig {
@@ -1460,6 +1810,50 @@ fn test_config_from_file() {
     }
 }
 
+#[test]
+fn test_produce_empty_blocks_from_json() {
+    #[derive(Deserialize)]
+    struct Wrapper {
+        #[serde(deserialize_with = "deserialize_empty_blocks_policy")]
+        produce_empty_blocks: EmptyBlocksPolicy,
+    }
+    let parse = |json: &str| {
+        serde_json::from_str::<Wrapper>(&format!("{{\"produce_empty_blocks\": {}}}", json))
+            .unwrap()
+            .produce_empty_blocks
+    };
+    assert_eq!(parse("true"), EmptyBlocksPolicy::Always);
+    assert_eq!(parse("false"), EmptyBlocksPolicy::Never);
+    assert_eq!(parse("\"always\""), EmptyBlocksPolicy::Always);
+    assert_eq!(parse("\"never\""), EmptyBlocksPolicy::Never);
+    assert_eq!(
+        parse(r#"{"if_idle_longer_than": {"secs": 60, "nanos": 0}}"#),
+        EmptyBlocksPolicy::IfIdleLongerThan(Duration::from_secs(60))
+    );
+}
+
+#[test]
+fn test_validate_minimal_chunk_producer() {
+    let mut config = Config { minimal_chunk_producer: true, ..Config::default() };
+    assert!(config.validate().is_ok());
+    config.tracked_shards = vec![0];
+    assert!(matches!(config.validate(), Err(ConfigValidationError::MinimalChunkProducer)));
+    config.tracked_shards = vec![];
+    config.archive = true;
+    assert!(matches!(config.validate(), Err(ConfigValidationError::MinimalChunkProducer)));
+}
+
+#[test]
+fn test_validate_expected_protocol_version_range() {
+    let mut config = Config::default();
+    config.expected_protocol_version_range =
+        Some(ProtocolVersionRange { min: PROTOCOL_VERSION, max: PROTOCOL_VERSION });
+    assert!(config.validate().is_ok());
+    config.expected_protocol_version_range =
+        Some(ProtocolVersionRange { min: PROTOCOL_VERSION + 1, max: PROTOCOL_VERSION + 2 });
+    assert!(matches!(config.validate(), Err(ConfigValidationError::ProtocolVersionRange(_))));
+}
+
 #[test]
 fn test_create_testnet_configs() {
     let num_shards = 4;
@@ -1523,3 +1917,95 @@ fn test_create_testnet_configs() {
     assert_eq!(genesis.config.validators.len() as u64, num_shards);
     assert_eq!(genesis.config.shard_layout.num_shards(), num_shards);
 }
+
+#[test]
+fn test_init_testnet_configs() {
+    let temp_dir = tempdir().unwrap();
+    let genesis_overrides = TestnetGenesisOverrides {
+        epoch_length: Some(42),
+        gas_limit: Some(500_000_000_000_000),
+        ..Default::default()
+    };
+    init_testnet_configs(
+        temp_dir.path(),
+        2,
+        2,
+        3,
+        2,
+        "node",
+        true,
+        false,
+        false,
+        vec![],
+        &genesis_overrides,
+    );
+
+    for i in 0..5 {
+        let node_dir = temp_dir.path().join(format!("node{}", i));
+        let config = Config::from_file(&node_dir.join(CONFIG_FILENAME)).unwrap();
+        // The last two non-validators are archival.
+        assert_eq!(config.archive, i >= 3);
+        if i >= 3 {
+            assert_eq!(config.tracked_shards, vec![0, 1]);
+        }
+        // Validators boot from the other validator, everyone else from both.
+        let expected_boot_nodes = if i < 2 { 1 } else { 2 };
+        assert_eq!(config.network.boot_nodes.split(',').count(), expected_boot_nodes);
+
+        let genesis = Genesis::from_file(
+            node_dir.join(&config.genesis_file),
+            GenesisValidationMode::UnsafeFast,
+        );
+        assert_eq!(genesis.config.epoch_length, 42);
+        assert_eq!(genesis.config.gas_limit, 500_000_000_000_000);
+    }
+}
+
+#[test]
+fn test_init_testnet_configs_with_extra_records() {
+    let temp_dir = tempdir().unwrap();
+    let account_id: AccountId = "contract.near".parse().unwrap();
+    let code = vec![1, 2, 3];
+    let genesis_overrides = TestnetGenesisOverrides {
+        extra_records: vec![
+            StateRecord::Account {
+                account_id: account_id.clone(),
+                account: Account::new(100, 20, CryptoHash::hash_bytes(&code), 0),
+            },
+            StateRecord::Contract { account_id: account_id.clone(), code },
+            StateRecord::Data {
+                account_id: account_id.clone(),
+                data_key: b"key".to_vec(),
+                value: b"value".to_vec(),
+            },
+        ],
+        ..Default::default()
+    };
+    init_testnet_configs(
+        temp_dir.path(),
+        1,
+        1,
+        0,
+        0,
+        "node",
+        true,
+        false,
+        false,
+        vec![],
+        &genesis_overrides,
+    );
+
+    let node_dir = temp_dir.path().join("node0");
+    let config = Config::from_file(&node_dir.join(CONFIG_FILENAME)).unwrap();
+    // Validates that the total supply accounts for the added balance.
+    let mut genesis =
+        Genesis::from_file(node_dir.join(&config.genesis_file), GenesisValidationMode::Full);
+    let validator_key = genesis.config.validators[0].public_key.clone();
+    let records = &genesis.force_read_records().0;
+    assert!(records.iter().any(|record| matches!(record,
+        StateRecord::Account { account_id: id, account } if id == &account_id && account.amount() == 120 && account.locked() == 0)));
+    assert!(records.iter().any(|record| matches!(record,
+        StateRecord::AccessKey { account_id: id, public_key, .. } if id == &account_id && public_key == &validator_key)));
+    assert!(records.iter().any(|record| matches!(record,
+        StateRecord::Data { account_id: id, .. } if id == &account_id)));
+}
@@ -1,5 +1,5 @@
 use crate::config::Config;
-use near_chain_configs::UpdateableClientConfig;
+use near_chain_configs::{EmptyBlocksPolicy, UpdateableClientConfig};
 use near_dyn_configs::{UpdateableConfigLoaderError, UpdateableConfigs};
 use near_o11y::log_config::LogConfig;
 use serde::Deserialize;
@@ -7,9 +7,17 @@ use std::path::{Path, PathBuf};
 
 const LOG_CONFIG_FILENAME: &str = "log_config.json";
 
+/// Values of the dynamically updateable configs given on the command line.
+/// They take precedence over the config file, also when it's reloaded.
+#[derive(Clone, Debug, Default)]
+pub struct UpdateableConfigOverrides {
+    pub produce_empty_blocks: Option<EmptyBlocksPolicy>,
+}
+
 /// This function gets called at the startup and each time a config needs to be reloaded.
 pub fn read_updateable_configs(
     home_dir: &Path,
+    overrides: &UpdateableConfigOverrides,
 ) -> Result<UpdateableConfigs, UpdateableConfigLoaderError> {
     let mut errs = vec![];
     let log_config = match read_log_config(home_dir) {
@@ -21,7 +29,7 @@ pub fn read_updateable_configs(
     };
     let updateable_client_config =
         match Config::from_file(&home_dir.join(crate::config::CONFIG_FILENAME))
-            .map(get_updateable_client_config)
+            .map(|config| get_updateable_client_config(config, overrides))
         {
             Ok(config) => Some(config),
             Err(err) => {
@@ -42,10 +50,18 @@ pub fn read_updateable_configs(
     }
 }
 
-pub fn get_updateable_client_config(config: Config) -> UpdateableClientConfig {
+pub fn get_updateable_client_config(
+    config: Config,
+    overrides: &UpdateableConfigOverrides,
+) -> UpdateableClientConfig {
     // All fields that can be updated while the node is running should be explicitly set here.
     // Keep this list in-sync with `core/dyn-configs/README.md`.
-    UpdateableClientConfig { expected_shutdown: config.expected_shutdown }
+    UpdateableClientConfig {
+        expected_shutdown: config.expected_shutdown,
+        produce_empty_blocks: overrides
+            .produce_empty_blocks
+            .unwrap_or(config.consensus.produce_empty_blocks),
+    }
 }
 
 fn read_log_config(home_dir: &Path) -> Result<Option<LogConfig>, UpdateableConfigLoaderError> {
@@ -75,3 +91,32 @@ where
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_produce_empty_blocks_override() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.consensus.produce_empty_blocks =
+            EmptyBlocksPolicy::IfIdleLongerThan(Duration::from_secs(60));
+        config.write_to_file(&home_dir.path().join(crate::config::CONFIG_FILENAME)).unwrap();
+
+        let configs =
+            read_updateable_configs(home_dir.path(), &UpdateableConfigOverrides::default())
+                .unwrap();
+        assert_eq!(
+            configs.client_config.unwrap().produce_empty_blocks,
+            EmptyBlocksPolicy::IfIdleLongerThan(Duration::from_secs(60))
+        );
+
+        // The value given on the command line survives reloading the config.
+        let overrides =
+            UpdateableConfigOverrides { produce_empty_blocks: Some(EmptyBlocksPolicy::Never) };
+        let configs = read_updateable_configs(home_dir.path(), &overrides).unwrap();
+        assert_eq!(configs.client_config.unwrap().produce_empty_blocks, EmptyBlocksPolicy::Never);
+    }
+}
@@ -0,0 +1,423 @@
+//! Loading and storing of the node and validator keys.
+//!
+//! The `node_key_file` and `validator_key_file` config options are either
+//! paths relative to the home directory or one of the following URIs:
+//!
+//! * `env:NAME` — the key file JSON is read from the environment variable
+//!   `NAME`,
+//! * `exec:COMMAND ARGS…` — the key file JSON is the output of the command,
+//!   e.g. `exec:vault kv get -field=node_key secret/near`, which makes it
+//!   possible to keep the keys in any secret manager with a CLI.
+//!
+//! Key files may hold their secret key encrypted with a passphrase instead of
+//! in plain text.  The passphrase of encrypted keys loaded by the node is
+//! taken from the [`PASSPHRASE_ENV`] environment variable.  The encryption
+//! key is derived from the passphrase with PBKDF2-HMAC-SHA256 and the secret
+//! key is encrypted with ChaCha20-Poly1305.
+use anyhow::{anyhow, bail, Context};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::Hmac;
+use near_crypto::{KeyFile, PublicKey, SecretKey};
+use near_primitives::types::AccountId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable holding the passphrase of encrypted key files.
+pub const PASSPHRASE_ENV: &str = "NEAR_KEY_PASSPHRASE";
+
+/// Account ID of node keys whose key file doesn't set one.
+const NODE_ACCOUNT_ID: &str = "node";
+
+const KDF: &str = "pbkdf2-hmac-sha256";
+const CIPHER: &str = "chacha20-poly1305";
+const KDF_ITERATIONS: u32 = 600_000;
+/// Key files with fewer iterations are rejected as too easy to brute-force.
+const MIN_KDF_ITERATIONS: u32 = 100_000;
+/// Key files with more iterations are rejected so that a crafted key file
+/// can't keep the node deriving the key for hours.
+const MAX_KDF_ITERATIONS: u32 = 10_000_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Where a key is loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    File(PathBuf),
+    Env(String),
+    Exec(Vec<String>),
+}
+
+impl KeySource {
+    /// Parses the value of a key file config option.
+    pub fn parse(home_dir: &Path, value: &str) -> anyhow::Result<Self> {
+        if let Some(name) = value.strip_prefix("env:") {
+            Ok(Self::Env(name.to_string()))
+        } else if let Some(command) = value.strip_prefix("exec:") {
+            let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+            if command.is_empty() {
+                bail!("‘{}’ doesn’t specify the command to run", value);
+            }
+            Ok(Self::Exec(command))
+        } else {
+            Ok(Self::File(home_dir.join(value)))
+        }
+    }
+
+    /// Returns the path of the key file if the key is stored in one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Reads the key file JSON, returning `None` if the key is stored in
+    /// a file which doesn’t exist.
+    fn read(&self) -> anyhow::Result<Option<String>> {
+        match self {
+            Self::File(path) => {
+                if !path.exists() {
+                    return Ok(None);
+                }
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed reading {}", path.display()))?;
+                Ok(Some(content))
+            }
+            Self::Env(name) => {
+                let content = std::env::var(name)
+                    .with_context(|| format!("Failed reading environment variable {}", name))?;
+                Ok(Some(content))
+            }
+            Self::Exec(command) => {
+                let output = Command::new(&command[0])
+                    .args(&command[1..])
+                    .output()
+                    .with_context(|| format!("Failed running {}", command[0]))?;
+                if !output.status.success() {
+                    bail!(
+                        "{} failed with {}: {}",
+                        command[0],
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+                let content = String::from_utf8(output.stdout)
+                    .with_context(|| format!("Output of {} is not UTF-8", command[0]))?;
+                Ok(Some(content))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => path.display().fmt(f),
+            Self::Env(name) => write!(f, "env:{}", name),
+            Self::Exec(command) => write!(f, "exec:{}", command.join(" ")),
+        }
+    }
+}
+
+/// Secret key encrypted with a passphrase.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct EncryptedSecretKey {
+    kdf: String,
+    iterations: u32,
+    #[serde(with = "hex")]
+    salt: Vec<u8>,
+    cipher: String,
+    #[serde(with = "hex")]
+    nonce: Vec<u8>,
+    /// Encrypted secret key followed by the authentication tag.
+    #[serde(with = "hex")]
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedSecretKey {
+    fn encrypt(secret_key: &SecretKey, passphrase: &str, iterations: u32) -> Self {
+        let mut salt = vec![0; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let mut nonce = vec![0; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let cipher = derive_cipher(passphrase, &salt, iterations);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), secret_key.to_string().as_bytes())
+            .expect("encrypting a secret key can't exceed the ChaCha20-Poly1305 limits");
+        Self {
+            kdf: KDF.to_string(),
+            iterations,
+            salt,
+            cipher: CIPHER.to_string(),
+            nonce,
+            ciphertext,
+        }
+    }
+
+    fn decrypt(&self, passphrase: &str) -> anyhow::Result<SecretKey> {
+        if self.kdf != KDF || self.cipher != CIPHER {
+            bail!("Unsupported key encryption {} with {}", self.cipher, self.kdf);
+        }
+        if !(MIN_KDF_ITERATIONS..=MAX_KDF_ITERATIONS).contains(&self.iterations) {
+            bail!(
+                "Key derivation iterations {} are outside of the allowed range {}..={}",
+                self.iterations,
+                MIN_KDF_ITERATIONS,
+                MAX_KDF_ITERATIONS
+            );
+        }
+        if self.salt.len() < SALT_LEN {
+            bail!("Key derivation salt must be at least {} bytes long", SALT_LEN);
+        }
+        if self.nonce.len() != NONCE_LEN {
+            bail!("Nonce must be {} bytes long", NONCE_LEN);
+        }
+        let plaintext = derive_cipher(passphrase, &self.salt, self.iterations)
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| anyhow!("Wrong passphrase"))?;
+        let secret_key = String::from_utf8(plaintext).context("Invalid secret key")?;
+        secret_key.parse().context("Invalid secret key")
+    }
+}
+
+/// Derives the encryption key from the passphrase with PBKDF2-HMAC-SHA256.
+fn derive_cipher(passphrase: &str, salt: &[u8], iterations: u32) -> ChaCha20Poly1305 {
+    let mut key = Key::default();
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, iterations, &mut key);
+    ChaCha20Poly1305::new(&key)
+}
+
+/// Key file as stored, with the secret key in plain text or encrypted.
+#[derive(Serialize, Deserialize)]
+struct StoredKeyFile {
+    account_id: String,
+    public_key: PublicKey,
+    #[serde(default, alias = "private_key", skip_serializing_if = "Option::is_none")]
+    secret_key: Option<SecretKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encrypted_secret_key: Option<EncryptedSecretKey>,
+}
+
+/// Key read from a key file, whose secret key may still be encrypted.
+pub struct StoredKey {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    secret_key: Option<SecretKey>,
+    encrypted_secret_key: Option<EncryptedSecretKey>,
+}
+
+impl StoredKey {
+    /// Reads the key, returning `None` if it's stored in a file which
+    /// doesn’t exist.
+    pub fn read(source: &KeySource) -> anyhow::Result<Option<Self>> {
+        let content = match source.read()? {
+            Some(content) => content,
+            None => return Ok(None),
+        };
+        let stored: StoredKeyFile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed parsing key file {}", source))?;
+        let account_id =
+            if stored.account_id.is_empty() { NODE_ACCOUNT_ID } else { &stored.account_id };
+        let account_id = account_id
+            .parse()
+            .with_context(|| format!("Invalid account ID in key file {}", source))?;
+        if stored.secret_key.is_some() == stored.encrypted_secret_key.is_some() {
+            bail!(
+                "Key file {} must have exactly one of secret_key and encrypted_secret_key",
+                source
+            );
+        }
+        Ok(Some(Self {
+            account_id,
+            public_key: stored.public_key,
+            secret_key: stored.secret_key,
+            encrypted_secret_key: stored.encrypted_secret_key,
+        }))
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted_secret_key.is_some()
+    }
+
+    /// Returns the key with the decrypted secret key.  The passphrase is only
+    /// needed if the secret key is encrypted.
+    pub fn decrypt(self, passphrase: Option<&str>) -> anyhow::Result<KeyFile> {
+        let secret_key = match (self.secret_key, self.encrypted_secret_key) {
+            (Some(secret_key), _) => secret_key,
+            (None, Some(encrypted)) => {
+                let passphrase = passphrase.ok_or_else(|| {
+                    anyhow!("Key of {} is encrypted but no passphrase was given", self.account_id)
+                })?;
+                encrypted.decrypt(passphrase)?
+            }
+            (None, None) => unreachable!("checked when reading"),
+        };
+        if secret_key.public_key() != self.public_key {
+            bail!("Secret key of {} doesn’t match public key {}", self.account_id, self.public_key);
+        }
+        Ok(KeyFile { account_id: self.account_id, public_key: self.public_key, secret_key })
+    }
+}
+
+/// Loads the key, decrypting it with the passphrase from [`PASSPHRASE_ENV`]
+/// if needed.  Returns `None` if the key is stored in a file which doesn’t
+/// exist.
+pub fn load_key_file(source: &KeySource) -> anyhow::Result<Option<KeyFile>> {
+    let key = match StoredKey::read(source)? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+    key.decrypt(passphrase.as_deref())
+        .with_context(|| format!("Failed loading key from {}", source))
+        .map(Some)
+}
+
+/// Writes the key file, with the secret key encrypted if a passphrase is
+/// given.  The file is only readable by its owner.
+pub fn write_key_file(path: &Path, key: &KeyFile, passphrase: Option<&str>) -> anyhow::Result<()> {
+    write_key_file_with_iterations(path, key, passphrase, KDF_ITERATIONS)
+}
+
+fn write_key_file_with_iterations(
+    path: &Path,
+    key: &KeyFile,
+    passphrase: Option<&str>,
+    iterations: u32,
+) -> anyhow::Result<()> {
+    let stored = match passphrase {
+        Some(passphrase) => StoredKeyFile {
+            account_id: key.account_id.to_string(),
+            public_key: key.public_key.clone(),
+            secret_key: None,
+            encrypted_secret_key: Some(EncryptedSecretKey::encrypt(
+                &key.secret_key,
+                passphrase,
+                iterations,
+            )),
+        },
+        None => StoredKeyFile {
+            account_id: key.account_id.to_string(),
+            public_key: key.public_key.clone(),
+            secret_key: Some(key.secret_key.clone()),
+            encrypted_secret_key: None,
+        },
+    };
+    let data = serde_json::to_string_pretty(&stored)?;
+    // Write to a temporary file first so that the key is never left
+    // half-written.
+    let tmp_path = path.with_extension("tmp");
+    let mut file = create_private_file(&tmp_path)
+        .with_context(|| format!("Failed creating {}", tmp_path.display()))?;
+    file.write_all(data.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed writing {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::File::options().mode(0o600).write(true).create(true).truncate(true).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::File::create(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::KeyType;
+
+    fn key_file() -> KeyFile {
+        let secret_key = SecretKey::from_random(KeyType::ED25519);
+        KeyFile {
+            account_id: "test.near".parse().unwrap(),
+            public_key: secret_key.public_key(),
+            secret_key,
+        }
+    }
+
+    #[test]
+    fn test_encrypted_key_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("key.json");
+        let key = key_file();
+        write_key_file_with_iterations(&path, &key, Some("passphrase"), MIN_KDF_ITERATIONS)
+            .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(&key.secret_key.to_string()));
+
+        let source = KeySource::parse(tmp.path(), "key.json").unwrap();
+        let stored = StoredKey::read(&source).unwrap().unwrap();
+        assert!(stored.is_encrypted());
+        assert!(stored.decrypt(None).is_err());
+        let stored = StoredKey::read(&source).unwrap().unwrap();
+        assert!(stored.decrypt(Some("wrong")).is_err());
+        let stored = StoredKey::read(&source).unwrap().unwrap();
+        let decrypted = stored.decrypt(Some("passphrase")).unwrap();
+        assert_eq!(decrypted.account_id, key.account_id);
+        assert_eq!(decrypted.secret_key, key.secret_key);
+    }
+
+    #[test]
+    fn test_kdf_iterations_bounds() {
+        let key = key_file();
+        let encrypted =
+            EncryptedSecretKey::encrypt(&key.secret_key, "passphrase", MIN_KDF_ITERATIONS);
+        for iterations in [0, MIN_KDF_ITERATIONS - 1, MAX_KDF_ITERATIONS + 1, u32::MAX] {
+            let encrypted = EncryptedSecretKey { iterations, ..encrypted.clone() };
+            assert!(encrypted.decrypt("passphrase").is_err());
+        }
+        let truncated_nonce = EncryptedSecretKey { nonce: vec![0; 4], ..encrypted.clone() };
+        assert!(truncated_nonce.decrypt("passphrase").is_err());
+        assert_eq!(encrypted.decrypt("passphrase").unwrap(), key.secret_key);
+    }
+
+    #[test]
+    fn test_key_sources() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            KeySource::parse(tmp.path(), "node_key.json").unwrap(),
+            KeySource::File(tmp.path().join("node_key.json"))
+        );
+        assert!(StoredKey::read(&KeySource::parse(tmp.path(), "missing.json").unwrap())
+            .unwrap()
+            .is_none());
+        assert!(KeySource::parse(tmp.path(), "exec:").is_err());
+
+        let key = key_file();
+        write_key_file(&tmp.path().join("key.json"), &key, None).unwrap();
+        let path = tmp.path().join("key.json");
+        let source = KeySource::parse(tmp.path(), &format!("exec:cat {}", path.display())).unwrap();
+        let loaded = load_key_file(&source).unwrap().unwrap();
+        assert_eq!(loaded.secret_key, key.secret_key);
+    }
+
+    #[test]
+    fn test_node_key_without_account_id() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key = key_file();
+        std::fs::write(
+            tmp.path().join("node_key.json"),
+            serde_json::json!({
+                "account_id": "",
+                "public_key": key.public_key,
+                "secret_key": key.secret_key,
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let source = KeySource::parse(tmp.path(), "node_key.json").unwrap();
+        let loaded = load_key_file(&source).unwrap().unwrap();
+        assert_eq!(loaded.account_id.as_ref(), NODE_ACCOUNT_ID);
+    }
+}
@@ -1,29 +1,39 @@
+use crate::account_outcome_ids::spawn_account_outcome_ids_backfill;
 use crate::cold_storage::spawn_cold_store_loop;
 pub use crate::config::{init_configs, load_config, load_test_config, NearConfig, NEAR_BASE};
 pub use crate::runtime::NightshadeRuntime;
 pub use crate::shard_tracker::TrackedConfig;
+use account_outcome_ids::AccountOutcomeIdsBackfillHandle;
 use actix::{Actor, Addr};
 use actix_rt::ArbiterHandle;
 use actix_web;
 use anyhow::Context;
 use cold_storage::ColdStoreLoopHandle;
+use near_chain::state_parts_cache::StatePartsCache;
 use near_chain::{Chain, ChainGenesis};
-use near_client::{start_client, start_view_client, ClientActor, ConfigUpdater, ViewClientActor};
+use near_client::loopback_network::{LoopbackNetwork, LoopbackNode};
+use near_client::{
+    start_client, start_view_client, BlockTimestampIndex, ClientActor, ConfigUpdater,
+    GasPriceWindow, SyncServingBudget, ViewClientActor,
+};
 use near_network::time;
-use near_network::types::NetworkRecipient;
+use near_network::types::{NetworkRecipient, OfflineNetwork, PeerInfo, PeerManagerAdapter};
 use near_network::PeerManagerActor;
 use near_primitives::block::GenesisId;
-use near_store::{DBCol, Mode, NodeStorage, StoreOpenerError, Temperature};
+use near_store::{DBCol, Mode, NodeStorage, PlannedMigration, StoreOpenerError, Temperature};
 use near_telemetry::TelemetryActor;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 use tracing::{info, trace};
+mod account_outcome_ids;
 pub mod append_only_map;
+pub mod boot_snapshot;
 mod cold_storage;
 pub mod config;
 mod download_file;
 pub mod dyn_config;
+pub mod keys;
 mod metrics;
 pub mod migrations;
 mod runtime;
@@ -53,15 +63,23 @@ pub fn get_default_home() -> PathBuf {
 /// The end goal is to get rid of `archive` option in `config.json` file and
 /// have the type of the node be determined purely based on kind of database
 /// being opened.
+///
+/// A node started offline (see `ClientConfig::offline`) doesn't modify the
+/// storage this way: it refuses to open a database which needs migrating and
+/// takes the kind of the node from the database.  It still opens it
+/// read-write though, and starting the chain may write to it.
 fn open_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result<NodeStorage> {
     let migrator = migrations::Migrator::new(near_config);
     let opener = NodeStorage::opener(
         home_dir,
         &near_config.config.store,
         near_config.config.cold_store.as_ref(),
-    )
-    .with_migrator(&migrator)
-    .expect_archive(near_config.client_config.archive);
+    );
+    let opener = if near_config.client_config.offline {
+        opener
+    } else {
+        opener.with_migrator(&migrator).expect_archive(near_config.client_config.archive)
+    };
     let storage = match opener.open() {
         Ok(storage) => Ok(storage),
         Err(StoreOpenerError::IO(err)) => {
@@ -78,6 +96,8 @@ fn open_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result
                  To set up node in that configuration, start with neither of the databases existing.",
             ))
         },
+        // Only returned by rollback_migration
+        Err(StoreOpenerError::HotColdSnapshotMismatch) => unreachable!(),
         Err(err @ StoreOpenerError::HotColdVersionMismatch { .. }) => {
             Err(anyhow::anyhow!("{err}"))
         },
@@ -92,7 +112,9 @@ fn open_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result
             Err(anyhow::anyhow!(
                 "Detected an existing database migration snapshot at ‘{}’.\n\
                  Probably a database migration got interrupted and your database is corrupted.\n\
-                 Please replace files in ‘{}’ with contents of the snapshot, delete the snapshot and try again.",
+                 Please replace files in ‘{}’ with contents of the snapshot, delete the snapshot and try again.\n\
+                 If the snapshot was kept after a successful migration (‘store.keep_migration_snapshot’), \
+                 either delete it or restore it with ‘neard database rollback-migration’.",
                 snap_path.display(),
                 opener.path().display(),
             ))
@@ -121,8 +143,14 @@ fn open_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result
         }
         // Cannot happen with Mode::ReadWrite
         Err(StoreOpenerError::DbVersionMismatchOnRead { .. }) => unreachable!(),
-        // Cannot happen when migrator is specified.
-        Err(StoreOpenerError::DbVersionMismatch { .. }) => unreachable!(),
+        // Can only happen without a migrator, i.e. when starting offline.
+        Err(StoreOpenerError::DbVersionMismatch { got, want }) => {
+            Err(anyhow::anyhow!(
+                "Database version {got} needs to be migrated to version {want}, \
+                 which isn’t done when starting with --offline.  Start the node \
+                 without --offline or run ‘neard database migrate’ first"
+            ))
+        },
         Err(StoreOpenerError::DbVersionTooOld { got, latest_release, .. }) => {
             Err(anyhow::anyhow!(
                 "Database version {got} is created by an old version \
@@ -145,6 +173,29 @@ fn open_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result
     Ok(storage)
 }
 
+/// Opens the storage performing any pending database migrations.
+pub fn migrate_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result<()> {
+    open_storage(home_dir, near_config).map(|_| ())
+}
+
+/// Returns database migrations which starting the node would perform, without
+/// performing them.
+pub fn plan_storage_migrations(
+    home_dir: &Path,
+    near_config: &NearConfig,
+) -> anyhow::Result<Vec<PlannedMigration>> {
+    let migrator = migrations::Migrator::new(near_config);
+    let opener = NodeStorage::opener(
+        home_dir,
+        &near_config.config.store,
+        near_config.config.cold_store.as_ref(),
+    )
+    .with_migrator(&migrator);
+    opener
+        .plan_migrations()
+        .with_context(|| format!("unable to read database at {}", opener.path().display()))
+}
+
 pub struct NearNode {
     pub client: Addr<ClientActor>,
     pub view_client: Addr<ViewClientActor>,
@@ -153,6 +204,9 @@ pub struct NearNode {
     /// The cold_store_loop_handle will only be set if the cold store is configured.
     /// It's a handle to a background thread that copies data from the hot store to the cold store.
     pub cold_store_loop_handle: Option<ColdStoreLoopHandle>,
+    /// Handle to a background thread that backfills the outcomes by account
+    /// index.  It's not set if the node is offline.
+    pub account_outcome_ids_backfill_handle: Option<AccountOutcomeIdsBackfillHandle>,
 }
 
 pub fn start_with_config(home_dir: &Path, config: NearConfig) -> anyhow::Result<NearNode> {
@@ -161,21 +215,75 @@ pub fn start_with_config(home_dir: &Path, config: NearConfig) -> anyhow::Result<
 
 pub fn start_with_config_and_synchronization(
     home_dir: &Path,
-    mut config: NearConfig,
+    config: NearConfig,
     // 'shutdown_signal' will notify the corresponding `oneshot::Receiver` when an instance of
     // `ClientActor` gets dropped.
     shutdown_signal: Option<broadcast::Sender<()>>,
     config_updater: Option<ConfigUpdater>,
 ) -> anyhow::Result<NearNode> {
+    start_node(home_dir, config, shutdown_signal, config_updater, None)
+}
+
+/// Same as [`start_with_config_and_synchronization`], except that the node
+/// talks to the other nodes of `network` in this process instead of starting
+/// a peer manager.  The node must be a validator, its account names it in the
+/// network.
+pub fn start_with_loopback_network(
+    home_dir: &Path,
+    config: NearConfig,
+    shutdown_signal: Option<broadcast::Sender<()>>,
+    network: &LoopbackNetwork,
+) -> anyhow::Result<NearNode> {
+    start_node(home_dir, config, shutdown_signal, None, Some(network))
+}
+
+fn start_node(
+    home_dir: &Path,
+    mut config: NearConfig,
+    shutdown_signal: Option<broadcast::Sender<()>>,
+    config_updater: Option<ConfigUpdater>,
+    loopback_network: Option<&LoopbackNetwork>,
+) -> anyhow::Result<NearNode> {
+    let loopback_network = match loopback_network {
+        Some(network) => {
+            let account_id = config
+                .validator_signer
+                .as_ref()
+                .map(|signer| signer.validator_id().clone())
+                .context("Only validators can join a loopback network")?;
+            Some((network, account_id))
+        }
+        None => None,
+    };
     let store = open_storage(home_dir, &mut config)?;
 
+    if config.client_config.offline {
+        info!(target: "neard", "Starting without networking: the node won't sync or produce blocks");
+        config.validator_signer = None;
+        config.telemetry_config.endpoints.clear();
+    }
+
     let runtime = Arc::new(NightshadeRuntime::from_config(
         home_dir,
         store.get_store(Temperature::Hot),
         &config,
     ));
 
-    let cold_store_loop_handle = spawn_cold_store_loop(&config, &store, runtime.clone())?;
+    let cold_store_loop_handle = if config.client_config.offline {
+        None
+    } else {
+        spawn_cold_store_loop(&config, &store, runtime.clone())?
+    };
+    let account_outcome_ids_backfill_handle = if config.client_config.offline {
+        None
+    } else {
+        Some(spawn_account_outcome_ids_backfill(&config, store.get_store(Temperature::Hot))?)
+    };
+    #[cfg(feature = "json_rpc")]
+    let db_snapshotter = near_client::DbSnapshotter::new(
+        store.get_store(Temperature::Hot),
+        config.client_config.chain_id.clone(),
+    );
 
     let telemetry = TelemetryActor::new(config.telemetry_config.clone()).start();
     let chain_genesis = ChainGenesis::new(&config.genesis);
@@ -186,8 +294,21 @@ pub fn start_with_config_and_synchronization(
     };
 
     let node_id = config.network_config.node_id();
-    let network_adapter = Arc::new(NetworkRecipient::default());
+    let network_recipient = Arc::new(NetworkRecipient::default());
+    let network_adapter: Arc<dyn PeerManagerAdapter> = if config.client_config.offline {
+        Arc::new(OfflineNetwork)
+    } else if let Some((network, account_id)) = &loopback_network {
+        network.peer_manager(account_id.clone())
+    } else {
+        network_recipient.clone()
+    };
     let adv = near_client::adversarial::Controls::new(config.client_config.archive);
+    let block_timestamp_index = Arc::new(Mutex::new(BlockTimestampIndex::new()));
+    let sync_serving_budget =
+        Arc::new(Mutex::new(SyncServingBudget::from_config(&config.client_config)));
+    let gas_price_window = Arc::new(Mutex::new(GasPriceWindow::new()));
+    let state_parts_cache =
+        Arc::new(StatePartsCache::new(config.client_config.state_parts_cache_size));
 
     let view_client = start_view_client(
         config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
@@ -195,32 +316,85 @@ pub fn start_with_config_and_synchronization(
         runtime.clone(),
         network_adapter.clone(),
         config.client_config.clone(),
+        block_timestamp_index.clone(),
+        sync_serving_budget.clone(),
+        gas_price_window.clone(),
+        state_parts_cache.clone(),
         adv.clone(),
     );
+    #[cfg(feature = "json_rpc")]
+    let view_call_pool = config.rpc_config.is_some().then(|| {
+        near_client::start_view_call_pool(
+            config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
+            chain_genesis.clone(),
+            runtime.clone(),
+            network_adapter.clone(),
+            config.client_config.clone(),
+            block_timestamp_index.clone(),
+            sync_serving_budget,
+            gas_price_window.clone(),
+            state_parts_cache,
+            adv.clone(),
+        )
+    });
+    let offline = config.client_config.offline;
+    let client_queue_capacity = config.client_config.client_queue_capacity;
+    let view_client_queue_capacity = config.client_config.view_client_queue_capacity;
+    let message_log = config.client_config.message_log.clone();
+    let clock = time::Clock::real();
     let (client_actor, client_arbiter_handle) = start_client(
+        clock.clone(),
         config.client_config,
         chain_genesis,
         runtime,
-        node_id,
+        node_id.clone(),
         network_adapter.clone(),
         config.validator_signer,
         telemetry,
         shutdown_signal.clone(),
         adv,
         config_updater,
+        block_timestamp_index,
+        gas_price_window,
     );
+    let mut client_adapter =
+        near_client::adapter::Adapter::new(client_actor.clone(), view_client.clone())
+            .with_queue_capacity(client_queue_capacity, view_client_queue_capacity);
+    if let Some(dir) = &message_log {
+        let (recorder, path) = near_client::message_log::MessageRecorder::create(dir)
+            .with_context(|| format!("Failed to create a message log in {}", dir.display()))?;
+        info!(target: "client", path = %path.display(), "Recording inbound messages");
+        client_adapter = client_adapter.with_recorder(recorder);
+    }
 
     #[allow(unused_mut)]
     let mut rpc_servers = Vec::new();
-    let network_actor = PeerManagerActor::spawn(
-        time::Clock::real(),
-        store.into_inner(near_store::Temperature::Hot),
-        config.network_config,
-        Arc::new(near_client::adapter::Adapter::new(client_actor.clone(), view_client.clone())),
-        genesis_id,
-    )
-    .context("PeerManager::spawn()")?;
-    network_adapter.set_recipient(network_actor.clone());
+    #[allow(unused_variables)]
+    let network_actor = if offline {
+        None
+    } else if let Some((network, account_id)) = loopback_network {
+        network.add_node(LoopbackNode {
+            peer_info: PeerInfo { id: node_id, addr: None, account_id: Some(account_id.clone()) },
+            account_id,
+            client: client_actor.clone(),
+            view_client: view_client.clone(),
+        });
+        None
+    } else {
+        let network_actor = PeerManagerActor::spawn(
+            clock,
+            store.into_inner(near_store::Temperature::Hot),
+            config.network_config,
+            Arc::new(client_adapter),
+            genesis_id,
+        )
+        .context("PeerManager::spawn()")?;
+        network_recipient.set_recipient(network_actor.clone());
+        Some(network_actor)
+    };
+
+    #[cfg(feature = "grpc")]
+    let grpc_auth = config.rpc_config.as_ref().map(|rpc| rpc.auth.clone()).unwrap_or_default();
 
     #[cfg(feature = "json_rpc")]
     if let Some(rpc_config) = config.rpc_config {
@@ -229,8 +403,10 @@ pub fn start_with_config_and_synchronization(
             config.genesis.config.clone(),
             client_actor.clone(),
             view_client.clone(),
-            Some(network_actor.clone()),
-        ));
+            view_call_pool,
+            Some(db_snapshotter),
+            network_actor,
+        )?);
     }
 
     #[cfg(feature = "rosetta_rpc")]
@@ -247,6 +423,11 @@ pub fn start_with_config_and_synchronization(
         ));
     }
 
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_config) = config.grpc_config {
+        near_grpc::start_grpc(grpc_config, &grpc_auth, view_client.clone())?;
+    }
+
     rpc_servers.shrink_to_fit();
 
     trace!(target: "diagnostic", key="log", "Starting NEAR node with diagnostic activated");
@@ -257,6 +438,7 @@ pub fn start_with_config_and_synchronization(
         rpc_servers,
         arbiters: vec![client_arbiter_handle],
         cold_store_loop_handle,
+        account_outcome_ids_backfill_handle,
     })
 }
 
@@ -1,5 +1,6 @@
 use near_o11y::metrics::{
-    linear_buckets, try_create_histogram_vec, try_create_int_gauge, HistogramVec, IntGauge,
+    linear_buckets, try_create_histogram_vec, try_create_int_counter_vec, try_create_int_gauge,
+    HistogramVec, IntCounterVec, IntGauge,
 };
 use once_cell::sync::Lazy;
 
@@ -27,6 +28,15 @@ pub static SECONDS_PER_PETAGAS: Lazy<HistogramVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static SHADOW_APPLY_CHUNK_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_shadow_apply_chunk_total",
+        "Number of chunks applied again at the shadow protocol version, by whether the result matched the regular one",
+        &["result"],
+    )
+    .unwrap()
+});
+
 pub(crate) static CONFIG_CORRECT: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge(
         "near_config_correct",
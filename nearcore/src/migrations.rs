@@ -5,7 +5,7 @@ use near_primitives::types::Gas;
 use near_primitives::utils::index_to_bytes;
 use near_store::metadata::{DbVersion, DB_VERSION};
 use near_store::migrations::BatchedStoreUpdate;
-use near_store::{DBCol, NodeStorage, Temperature};
+use near_store::{DBCol, MigrationInfo, NodeStorage, Temperature};
 
 /// Fix an issue with block ordinal (#5761)
 // This migration takes at least 3 hours to complete on mainnet
@@ -137,9 +137,8 @@ impl<'a> near_store::StoreMigrator for Migrator<'a> {
     }
 
     fn migrate(&self, storage: &NodeStorage, version: DbVersion) -> anyhow::Result<()> {
-        match version {
-            0..=26 => unreachable!(),
-            27 => {
+        match Migration::from_version(version) {
+            Migration::AddStateChangesForSplitStates => {
                 // version 27 => 28: add DBCol::StateChangesForSplitStates
                 //
                 // Does not need to do anything since open db with option
@@ -149,20 +148,120 @@ impl<'a> near_store::StoreMigrator for Migrator<'a> {
                 // don’t do anything here.
                 Ok(())
             }
-            28 => near_store::migrations::migrate_28_to_29(storage),
-            29 => near_store::migrations::migrate_29_to_30(storage),
-            30 => migrate_30_to_31(storage, &self.config),
-            31 => near_store::migrations::migrate_31_to_32(storage),
-            32 => near_store::migrations::migrate_32_to_33(storage),
-            33 => {
+            Migration::DeleteChunkIndices => near_store::migrations::migrate_28_to_29(storage),
+            Migration::ConvertToNewFormats => near_store::migrations::migrate_29_to_30(storage),
+            Migration::FixBlockOrdinals => migrate_30_to_31(storage, &self.config),
+            Migration::DeleteChunkPerHeightShard => {
+                near_store::migrations::migrate_31_to_32(storage)
+            }
+            Migration::MoveTransactionResults => near_store::migrations::migrate_32_to_33(storage),
+            Migration::SetDbKind => {
                 near_store::migrations::migrate_33_to_34(storage, self.config.client_config.archive)
             }
             #[cfg(feature = "protocol_feature_flat_state")]
-            34 => {
+            Migration::CreateFlatStorage => {
                 tracing::info!(target: "migrations", "Migrating DB version from 34 to 35. Flat storage data will be created on disk.");
                 tracing::info!(target: "migrations", "It will happen in parallel with regular block processing. ETA is 5h for RPC node and 10h for archival node.");
                 Ok(())
             }
+            // Add DBCol::AccountOutcomeIds.
+            //
+            // Does not need to do anything since the column family is created
+            // on open.  The version is bumped so that older binaries don’t try
+            // to open the db.
+            Migration::AddAccountOutcomeIds => Ok(()),
+            // Add DBCol::RecentPartialChunks.
+            //
+            // Does not need to do anything since the column family is created
+            // on open.
+            Migration::AddRecentPartialChunks => Ok(()),
+        }
+    }
+
+    fn describe(&self, version: DbVersion) -> MigrationInfo {
+        let (summary, columns): (_, &'static [DBCol]) = match Migration::from_version(version) {
+            Migration::AddStateChangesForSplitStates => {
+                ("Add StateChangesForSplitStates column", &[])
+            }
+            Migration::DeleteChunkIndices => {
+                ("Delete NextBlockWithNewChunk and LastBlockWithNewChunk columns", &[])
+            }
+            Migration::ConvertToNewFormats => (
+                "Convert ChunkExtra, BlockInfo, EpochValidatorInfo and EpochInfo to new format",
+                &[DBCol::ChunkExtra, DBCol::BlockInfo, DBCol::EpochValidatorInfo, DBCol::EpochInfo],
+            ),
+            Migration::FixBlockOrdinals
+                if self.config.client_config.archive
+                    && self.config.genesis.config.chain_id == "mainnet" =>
+            {
+                (
+                    "Fix block ordinals",
+                    &[DBCol::BlockHeight, DBCol::BlockMerkleTree, DBCol::BlockOrdinal],
+                )
+            }
+            Migration::FixBlockOrdinals => {
+                ("Fix block ordinals (no-op except on mainnet archival nodes)", &[])
+            }
+            Migration::DeleteChunkPerHeightShard => ("Delete ChunkPerHeightShard column", &[]),
+            Migration::MoveTransactionResults => (
+                "Move transaction results to TransactionResultForBlock",
+                &[DBCol::_TransactionResult],
+            ),
+            Migration::SetDbKind => ("Set database kind and delete GCCount column", &[]),
+            #[cfg(feature = "protocol_feature_flat_state")]
+            Migration::CreateFlatStorage => {
+                ("Create flat storage in the background once the node starts", &[])
+            }
+            Migration::AddAccountOutcomeIds => ("Add AccountOutcomeIds column", &[]),
+            Migration::AddRecentPartialChunks => ("Add RecentPartialChunks column", &[]),
+        };
+        MigrationInfo { summary, columns }
+    }
+}
+
+/// Migration from a database version to the next one.
+///
+/// Lets [`Migrator::migrate`] and [`Migrator::describe`] share the mapping of
+/// versions to migrations, which depends on the enabled features.
+enum Migration {
+    AddStateChangesForSplitStates,
+    DeleteChunkIndices,
+    ConvertToNewFormats,
+    FixBlockOrdinals,
+    DeleteChunkPerHeightShard,
+    MoveTransactionResults,
+    SetDbKind,
+    #[cfg(feature = "protocol_feature_flat_state")]
+    CreateFlatStorage,
+    AddAccountOutcomeIds,
+    AddRecentPartialChunks,
+}
+
+impl Migration {
+    /// Returns the migration from `version` to `version + 1`.
+    ///
+    /// **Panics** if `version` isn’t supported by the migrator or it’s greater
+    /// or equal to [`DB_VERSION`].
+    fn from_version(version: DbVersion) -> Self {
+        match version {
+            0..=26 => unreachable!(),
+            27 => Self::AddStateChangesForSplitStates,
+            28 => Self::DeleteChunkIndices,
+            29 => Self::ConvertToNewFormats,
+            30 => Self::FixBlockOrdinals,
+            31 => Self::DeleteChunkPerHeightShard,
+            32 => Self::MoveTransactionResults,
+            33 => Self::SetDbKind,
+            #[cfg(feature = "protocol_feature_flat_state")]
+            34 => Self::CreateFlatStorage,
+            #[cfg(not(feature = "protocol_feature_flat_state"))]
+            34 => Self::AddAccountOutcomeIds,
+            #[cfg(feature = "protocol_feature_flat_state")]
+            35 => Self::AddAccountOutcomeIds,
+            #[cfg(not(feature = "protocol_feature_flat_state"))]
+            35 => Self::AddRecentPartialChunks,
+            #[cfg(feature = "protocol_feature_flat_state")]
+            36 => Self::AddRecentPartialChunks,
             DB_VERSION.. => unreachable!(),
         }
     }
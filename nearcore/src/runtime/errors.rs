@@ -20,6 +20,9 @@ impl QueryError {
             node_runtime::state_viewer::errors::CallFunctionError::VMError { error_message } => {
                 Self::ContractExecutionError { error_message, block_height, block_hash }
             }
+            node_runtime::state_viewer::errors::CallFunctionError::DeadlineExceeded => {
+                Self::DeadlineExceeded { block_height, block_hash }
+            }
         }
     }
 
@@ -80,6 +83,9 @@ impl QueryError {
             node_runtime::state_viewer::errors::ViewStateError::AccountStateTooLarge {
                 requested_account_id,
             } => Self::TooLargeContractState { requested_account_id, block_height, block_hash },
+            node_runtime::state_viewer::errors::ViewStateError::DeadlineExceeded => {
+                Self::DeadlineExceeded { block_height, block_hash }
+            }
         }
     }
 
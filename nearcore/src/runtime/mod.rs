@@ -6,7 +6,8 @@ use borsh::ser::BorshSerialize;
 use borsh::BorshDeserialize;
 use errors::FromStateViewerErrors;
 use near_chain::types::{
-    ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, RuntimeAdapter, Tip,
+    ApplySplitStateResult, ApplyTransactionResult, BlockHeader, BlockHeaderInfo, RuntimeAdapter,
+    SimulationLimits, SimulationResult, Tip,
 };
 use near_chain::{Error, RuntimeWithEpochManagerAdapter};
 use near_chain_configs::{
@@ -14,7 +15,7 @@ use near_chain_configs::{
     MIN_GC_NUM_EPOCHS_TO_KEEP,
 };
 use near_client_primitives::types::StateSplitApplyingStatus;
-use near_crypto::PublicKey;
+use near_crypto::{PublicKey, Signature};
 use near_epoch_manager::{EpochManager, EpochManagerAdapter, EpochManagerHandle};
 use near_o11y::log_assert;
 use near_pool::types::PoolIterator;
@@ -36,7 +37,7 @@ use near_primitives::shard_layout::{
 use near_primitives::state_part::PartId;
 use near_primitives::state_record::{state_record_to_account_id, StateRecord};
 use near_primitives::syncing::{get_num_state_parts, STATE_PART_MEMORY_LIMIT};
-use near_primitives::transaction::SignedTransaction;
+use near_primitives::transaction::{SignedTransaction, Transaction};
 use near_primitives::types::validator_stake::ValidatorStakeIter;
 use near_primitives::types::{
     AccountId, Balance, BlockHeight, CompiledContractCache, EpochHeight, EpochId,
@@ -45,8 +46,8 @@ use near_primitives::types::{
 };
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
-    AccessKeyInfoView, CallResult, QueryRequest, QueryResponse, QueryResponseKind, ViewApplyState,
-    ViewStateResult,
+    AccessKeyInfoView, AccessKeyList, CallResult, QueryRequest, QueryResponse, QueryResponseKind,
+    ViewApplyState, ViewStateResult,
 };
 use near_store::flat_state::ChainAccessForFlatStorage;
 use near_store::flat_state::{
@@ -75,6 +76,8 @@ use std::time::Instant;
 use tracing::{debug, error, info, warn};
 
 pub mod errors;
+mod shadow;
+mod simulate;
 
 const STATE_DUMP_FILE: &str = "state_dump";
 const GENESIS_ROOTS_FILE: &str = "genesis_roots";
@@ -95,6 +98,9 @@ pub struct NightshadeRuntime {
     genesis_state_roots: Vec<StateRoot>,
     migration_data: Arc<MigrationData>,
     gc_num_epochs_to_keep: u64,
+    /// If set, chunks are additionally applied at this protocol version,
+    /// see the `shadow` module.
+    shadow_protocol_version: Option<ProtocolVersion>,
 }
 
 impl NightshadeRuntime {
@@ -109,6 +115,8 @@ impl NightshadeRuntime {
             None,
             config.config.gc.gc_num_epochs_to_keep(),
             TrieConfig::from_store_config(&config.config.store),
+            config.client_config.slow_receipt_factor,
+            config.client_config.shadow_protocol_version,
         )
     }
 
@@ -122,13 +130,15 @@ impl NightshadeRuntime {
         runtime_config_store: Option<RuntimeConfigStore>,
         gc_num_epochs_to_keep: u64,
         trie_config: TrieConfig,
+        slow_receipt_factor: Option<f64>,
+        shadow_protocol_version: Option<ProtocolVersion>,
     ) -> Self {
         let runtime_config_store = match runtime_config_store {
             Some(store) => store,
             None => NightshadeRuntime::create_runtime_config_store(&genesis.config.chain_id),
         };
 
-        let runtime = Runtime::new();
+        let runtime = Runtime::new().with_slow_receipt_factor(slow_receipt_factor);
         let trie_viewer = TrieViewer::new(trie_viewer_state_size_limit, max_gas_burnt_view);
         let genesis_config = genesis.config.clone();
         assert_eq!(
@@ -164,6 +174,7 @@ impl NightshadeRuntime {
             genesis_state_roots: state_roots,
             migration_data: Arc::new(load_migration_data(&genesis.config.chain_id)),
             gc_num_epochs_to_keep: gc_num_epochs_to_keep.max(MIN_GC_NUM_EPOCHS_TO_KEEP),
+            shadow_protocol_version,
         }
     }
 
@@ -184,6 +195,8 @@ impl NightshadeRuntime {
             Some(runtime_config_store),
             DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
             Default::default(),
+            None,
+            None,
         )
     }
 
@@ -499,6 +512,7 @@ impl NightshadeRuntime {
             },
         };
 
+        let state_root = *trie.get_root();
         let instant = Instant::now();
         let apply_result = self
             .runtime
@@ -539,6 +553,21 @@ impl NightshadeRuntime {
                 .with_label_values(&[])
                 .observe(elapsed.as_secs_f64() * 1e15 / total_gas_burnt as f64);
         }
+        if let Some(shadow_protocol_version) = self.shadow_protocol_version {
+            if shadow_protocol_version > current_protocol_version {
+                self.shadow_apply(
+                    shard_id,
+                    prev_block_hash,
+                    state_root,
+                    shadow_protocol_version,
+                    &apply_state,
+                    &validator_accounts_update,
+                    receipts,
+                    transactions,
+                    &apply_result,
+                );
+            }
+        }
         let total_balance_burnt = apply_result
             .stats
             .tx_burnt_amount
@@ -571,7 +600,9 @@ impl NightshadeRuntime {
         Ok(result)
     }
 
-    fn precompile_contracts(
+    /// Compiles the contracts and saves them in the compiled contract cache,
+    /// using at most half of the rayon threads.
+    pub fn precompile_contracts(
         &self,
         epoch_id: &EpochId,
         contract_codes: Vec<ContractCode>,
@@ -1088,6 +1119,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         block_hash: &CryptoHash,
         epoch_id: &EpochId,
         request: &QueryRequest,
+        deadline: Option<Instant>,
     ) -> Result<QueryResponse, near_chain::near_chain_primitives::error::QueryError> {
         match request {
             QueryRequest::ViewAccount { account_id } => {
@@ -1146,6 +1178,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                         &mut logs,
                         &self.epoch_manager,
                         current_protocol_version,
+                        deadline,
                     )
                     .map_err(|err| near_chain::near_chain_primitives::error::QueryError::from_call_function_error(err, block_height, *block_hash))?;
                 Ok(QueryResponse {
@@ -1157,14 +1190,17 @@ impl RuntimeAdapter for NightshadeRuntime {
                     block_hash: *block_hash,
                 })
             }
-            QueryRequest::ViewState { account_id, prefix, include_proof } => {
+            QueryRequest::ViewState { account_id, prefix, include_proof, after_key, limit } => {
                 let view_state_result = self
                     .view_state(
                         &shard_uid,
                         *state_root,
                         account_id,
                         prefix.as_ref(),
+                        after_key.as_deref(),
+                        limit.map(|limit| limit.get() as usize),
                         *include_proof,
+                        deadline,
                     )
                     .map_err(|err| {
                         near_chain::near_chain_primitives::error::QueryError::from_view_state_error(
@@ -1179,9 +1215,16 @@ impl RuntimeAdapter for NightshadeRuntime {
                     block_hash: *block_hash,
                 })
             }
-            QueryRequest::ViewAccessKeyList { account_id } => {
-                let access_key_list =
-                    self.view_access_keys(&shard_uid, *state_root, account_id).map_err(|err| {
+            QueryRequest::ViewAccessKeyList { account_id, after_public_key, limit } => {
+                let (access_key_list, next_public_key) = self
+                    .view_access_keys(
+                        &shard_uid,
+                        *state_root,
+                        account_id,
+                        after_public_key.as_ref(),
+                        limit.map(|limit| limit.get() as usize),
+                    )
+                    .map_err(|err| {
                         near_chain::near_chain_primitives::error::QueryError::from_view_access_key_error(
                             err,
                             block_height,
@@ -1189,15 +1232,16 @@ impl RuntimeAdapter for NightshadeRuntime {
                         )
                     })?;
                 Ok(QueryResponse {
-                    kind: QueryResponseKind::AccessKeyList(
-                        access_key_list
+                    kind: QueryResponseKind::AccessKeyList(AccessKeyList {
+                        keys: access_key_list
                             .into_iter()
                             .map(|(public_key, access_key)| AccessKeyInfoView {
                                 public_key,
                                 access_key: access_key.into(),
                             })
                             .collect(),
-                    ),
+                        next_public_key,
+                    }),
                     block_height,
                     block_hash: *block_hash,
                 })
@@ -1455,6 +1499,17 @@ impl RuntimeAdapter for NightshadeRuntime {
         let epoch_manager = self.epoch_manager.read();
         Ok(epoch_manager.will_shard_layout_change(parent_hash)?)
     }
+
+    fn simulate_transaction(
+        &self,
+        header: &BlockHeader,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        transaction: Transaction,
+        signature: Option<Signature>,
+        limits: &SimulationLimits,
+    ) -> Result<Result<SimulationResult, InvalidTxError>, Error> {
+        self.simulate(header, state_roots, transaction, signature, limits)
+    }
 }
 
 impl RuntimeWithEpochManagerAdapter for NightshadeRuntime {}
@@ -1496,6 +1551,7 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         logs: &mut Vec<String>,
         epoch_info_provider: &dyn EpochInfoProvider,
         current_protocol_version: ProtocolVersion,
+        deadline: Option<Instant>,
     ) -> Result<Vec<u8>, node_runtime::state_viewer::errors::CallFunctionError> {
         let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
         let view_state = ViewApplyState {
@@ -1507,6 +1563,7 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
             block_timestamp,
             current_protocol_version,
             cache: Some(Box::new(StoreCompiledContractCache::new(&self.tries.get_store()))),
+            deadline,
         };
         self.trie_viewer.call_function(
             state_update,
@@ -1535,10 +1592,14 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         shard_uid: &ShardUId,
         state_root: MerkleHash,
         account_id: &AccountId,
-    ) -> Result<Vec<(PublicKey, AccessKey)>, node_runtime::state_viewer::errors::ViewAccessKeyError>
-    {
+        after_public_key: Option<&PublicKey>,
+        limit: Option<usize>,
+    ) -> Result<
+        (Vec<(PublicKey, AccessKey)>, Option<PublicKey>),
+        node_runtime::state_viewer::errors::ViewAccessKeyError,
+    > {
         let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
-        self.trie_viewer.view_access_keys(&state_update, account_id)
+        self.trie_viewer.view_access_keys(&state_update, account_id, after_public_key, limit)
     }
 
     fn view_state(
@@ -1547,10 +1608,21 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         state_root: MerkleHash,
         account_id: &AccountId,
         prefix: &[u8],
+        after_key: Option<&[u8]>,
+        limit: Option<usize>,
         include_proof: bool,
+        deadline: Option<Instant>,
     ) -> Result<ViewStateResult, node_runtime::state_viewer::errors::ViewStateError> {
         let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
-        self.trie_viewer.view_state(&state_update, account_id, prefix, include_proof)
+        self.trie_viewer.view_state(
+            &state_update,
+            account_id,
+            prefix,
+            after_key,
+            limit,
+            include_proof,
+            deadline,
+        )
     }
 }
 
@@ -1800,6 +1872,8 @@ mod test {
                 Some(RuntimeConfigStore::free()),
                 DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
                 Default::default(),
+                None,
+                None,
             );
             let (_store, state_roots) = runtime.genesis_state();
             let genesis_hash = hash(&[0]);
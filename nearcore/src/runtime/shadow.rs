@@ -0,0 +1,238 @@
+//! Shadow execution of protocol features.
+//!
+//! With `shadow_protocol_version` set in the config, every chunk applied at
+//! an older protocol version is applied a second time as if the chain was at
+//! the shadow version, i.e. with all the protocol features and runtime config
+//! changes (such as new gas costs) up to that version enabled.  The results
+//! of the two applications are compared, divergences are logged under the
+//! `shadow` target and counted in the `near_shadow_apply_chunk_total` metric,
+//! but only the result of the regular application is used.  The shadow
+//! application doesn't change the state.
+//!
+//! To try out a protocol feature on real traffic, run a node built with the
+//! feature and set the shadow version to `ProtocolFeature::protocol_version`
+//! of the feature.  The shadow application doubles the time it takes to
+//! apply chunks, so it should only be enabled on nodes which don't validate.
+use near_chain::types::RuntimeAdapter;
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::Receipt;
+use near_primitives::runtime::migration_data::MigrationFlags;
+use near_primitives::sandbox::state_patch::SandboxStatePatch;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{ShardId, StateRoot};
+use near_primitives::version::ProtocolVersion;
+use near_store::StoreCompiledContractCache;
+use node_runtime::{ApplyResult, ApplyState, ValidatorAccountsUpdate};
+use tracing::warn;
+
+use super::NightshadeRuntime;
+use crate::metrics;
+
+/// Maximum number of divergences logged for a single chunk.
+const MAX_LOGGED_DIVERGENCES: usize = 10;
+
+impl NightshadeRuntime {
+    /// Applies the chunk again at `shadow_protocol_version` and reports how
+    /// the result differs from `apply_result`, the result of the regular
+    /// application with `apply_state`.
+    pub(super) fn shadow_apply(
+        &self,
+        shard_id: ShardId,
+        prev_block_hash: &CryptoHash,
+        state_root: StateRoot,
+        shadow_protocol_version: ProtocolVersion,
+        apply_state: &ApplyState,
+        validator_accounts_update: &Option<ValidatorAccountsUpdate>,
+        receipts: &[Receipt],
+        transactions: &[SignedTransaction],
+        apply_result: &ApplyResult,
+    ) {
+        let _span = tracing::debug_span!(
+            target: "runtime",
+            "shadow_apply",
+            shard_id,
+            height = apply_state.block_height,
+            shadow_protocol_version)
+        .entered();
+        let shadow_apply_state = ApplyState {
+            block_height: apply_state.block_height,
+            prev_block_hash: apply_state.prev_block_hash,
+            block_hash: apply_state.block_hash,
+            epoch_id: apply_state.epoch_id.clone(),
+            epoch_height: apply_state.epoch_height,
+            gas_price: apply_state.gas_price,
+            block_timestamp: apply_state.block_timestamp,
+            gas_limit: apply_state.gas_limit,
+            random_seed: apply_state.random_seed,
+            current_protocol_version: shadow_protocol_version,
+            config: self.runtime_config_store.get_config(shadow_protocol_version).clone(),
+            cache: Some(Box::new(StoreCompiledContractCache::new(&self.store))),
+            is_new_chunk: apply_state.is_new_chunk,
+            migration_data: apply_state.migration_data.clone(),
+            migration_flags: MigrationFlags {
+                is_first_block_of_version: apply_state.migration_flags.is_first_block_of_version,
+                is_first_block_with_chunk_of_version: apply_state
+                    .migration_flags
+                    .is_first_block_with_chunk_of_version,
+            },
+        };
+        let shadow_result = self
+            .get_trie_for_shard(shard_id, prev_block_hash, state_root, false)
+            .map_err(|err| err.to_string())
+            .and_then(|trie| {
+                self.runtime
+                    .apply(
+                        trie,
+                        validator_accounts_update,
+                        &shadow_apply_state,
+                        receipts,
+                        transactions,
+                        &self.epoch_manager,
+                        SandboxStatePatch::default(),
+                    )
+                    .map_err(|err| err.to_string())
+            });
+        let shadow_result = match shadow_result {
+            Ok(shadow_result) => shadow_result,
+            Err(err) => {
+                metrics::SHADOW_APPLY_CHUNK_TOTAL.with_label_values(&["error"]).inc();
+                warn!(
+                    target: "shadow",
+                    shard_id,
+                    height = apply_state.block_height,
+                    %err,
+                    "Shadow application of chunk failed"
+                );
+                return;
+            }
+        };
+
+        let divergences = compare_apply_results(apply_result, &shadow_result);
+        if divergences.is_empty() {
+            metrics::SHADOW_APPLY_CHUNK_TOTAL.with_label_values(&["match"]).inc();
+            return;
+        }
+        metrics::SHADOW_APPLY_CHUNK_TOTAL.with_label_values(&["diverged"]).inc();
+        warn!(
+            target: "shadow",
+            shard_id,
+            height = apply_state.block_height,
+            protocol_version = apply_state.current_protocol_version,
+            shadow_protocol_version,
+            num_divergences = divergences.len(),
+            "Shadow application of chunk diverged"
+        );
+        for divergence in divergences.iter().take(MAX_LOGGED_DIVERGENCES) {
+            warn!(target: "shadow", shard_id, height = apply_state.block_height, "{}", divergence);
+        }
+    }
+}
+
+/// Returns descriptions of the differences between the regular and the
+/// shadow results of applying a chunk.
+fn compare_apply_results(expected: &ApplyResult, shadow: &ApplyResult) -> Vec<String> {
+    let mut divergences = vec![];
+    if expected.state_root != shadow.state_root {
+        divergences
+            .push(format!("state root: {} vs shadow {}", expected.state_root, shadow.state_root));
+    }
+    if expected.outcomes.len() != shadow.outcomes.len() {
+        divergences.push(format!(
+            "number of outcomes: {} vs shadow {}",
+            expected.outcomes.len(),
+            shadow.outcomes.len()
+        ));
+    }
+    for (outcome, shadow_outcome) in expected.outcomes.iter().zip(shadow.outcomes.iter()) {
+        let id = outcome.id;
+        if id != shadow_outcome.id {
+            divergences.push(format!("outcome id: {} vs shadow {}", id, shadow_outcome.id));
+            // The remaining outcomes don't correspond to each other.
+            break;
+        }
+        let (outcome, shadow_outcome) = (&outcome.outcome, &shadow_outcome.outcome);
+        if outcome.status != shadow_outcome.status {
+            divergences.push(format!(
+                "outcome {} status: {:?} vs shadow {:?}",
+                id, outcome.status, shadow_outcome.status
+            ));
+        }
+        if outcome.gas_burnt != shadow_outcome.gas_burnt {
+            divergences.push(format!(
+                "outcome {} gas burnt: {} vs shadow {}",
+                id, outcome.gas_burnt, shadow_outcome.gas_burnt
+            ));
+        }
+        if outcome.tokens_burnt != shadow_outcome.tokens_burnt {
+            divergences.push(format!(
+                "outcome {} tokens burnt: {} vs shadow {}",
+                id, outcome.tokens_burnt, shadow_outcome.tokens_burnt
+            ));
+        }
+        if outcome.receipt_ids != shadow_outcome.receipt_ids {
+            divergences.push(format!(
+                "outcome {} receipt ids: {:?} vs shadow {:?}",
+                id, outcome.receipt_ids, shadow_outcome.receipt_ids
+            ));
+        }
+    }
+    if expected.outgoing_receipts.len() != shadow.outgoing_receipts.len() {
+        divergences.push(format!(
+            "number of outgoing receipts: {} vs shadow {}",
+            expected.outgoing_receipts.len(),
+            shadow.outgoing_receipts.len()
+        ));
+    }
+    if expected.validator_proposals != shadow.validator_proposals {
+        divergences.push(format!(
+            "validator proposals: {:?} vs shadow {:?}",
+            expected.validator_proposals, shadow.validator_proposals
+        ));
+    }
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::hash::hash;
+    use near_primitives::transaction::{ExecutionOutcome, ExecutionOutcomeWithId};
+    use near_store::TrieChanges;
+
+    fn apply_result(state_root: StateRoot, gas_burnt: Vec<u64>) -> ApplyResult {
+        ApplyResult {
+            state_root,
+            trie_changes: TrieChanges::empty(state_root),
+            validator_proposals: vec![],
+            outgoing_receipts: vec![],
+            outcomes: gas_burnt
+                .into_iter()
+                .enumerate()
+                .map(|(i, gas_burnt)| ExecutionOutcomeWithId {
+                    id: hash(&[i as u8]),
+                    outcome: ExecutionOutcome { gas_burnt, ..Default::default() },
+                })
+                .collect(),
+            state_changes: vec![],
+            stats: Default::default(),
+            processed_delayed_receipts: vec![],
+            proof: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_apply_results() {
+        let root = hash(&[1]);
+        let result = apply_result(root, vec![10, 20]);
+        assert!(compare_apply_results(&result, &apply_result(root, vec![10, 20])).is_empty());
+
+        let divergences = compare_apply_results(&result, &apply_result(hash(&[2]), vec![10, 30]));
+        assert_eq!(divergences.len(), 2, "{:?}", divergences);
+        assert!(divergences[0].starts_with("state root"));
+        assert!(divergences[1].contains("gas burnt: 20 vs shadow 30"));
+
+        let divergences = compare_apply_results(&result, &apply_result(root, vec![10]));
+        assert_eq!(divergences.len(), 1, "{:?}", divergences);
+        assert!(divergences[0].starts_with("number of outcomes"));
+    }
+}
@@ -0,0 +1,346 @@
+//! Simulation of transactions for the `EXPERIMENTAL_simulate_tx` RPC.
+//!
+//! The transaction is applied on top of the state of a block as if it was
+//! included in the next block, and the receipts it produces are applied in
+//! the following simulated blocks, until there are none left or one of the
+//! `SimulationLimits` is reached.  All simulated blocks have the timestamp,
+//! gas price and random seed of the chosen block, and are in the epoch of the
+//! block following it.
+//!
+//! The simulation is isolated from the rest of the traffic: the delayed
+//! receipts queues of the shards are emptied beforehand, so only the
+//! transaction and its receipts get executed.  The gas limit of each chunk is
+//! the gas left in the budget of the simulation; receipts over it are delayed
+//! to the next simulated block like in a real chunk.  The trie nodes created
+//! by the simulation are kept in memory on top of the view storage, so
+//! nothing gets persisted.
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use near_chain::types::{BlockHeader, RuntimeAdapter, SimulationLimits, SimulationResult};
+use near_chain::Error;
+use near_crypto::{InMemorySigner, KeyType, SecretKey, Signature, Signer};
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::account::AccessKey;
+use near_primitives::errors::{InvalidTxError, RuntimeError, StorageError};
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::{DelayedReceiptIndices, Receipt};
+use near_primitives::runtime::migration_data::MigrationFlags;
+use near_primitives::sandbox::state_patch::SandboxStatePatch;
+use near_primitives::shard_layout::account_id_to_shard_id;
+use near_primitives::transaction::{SignedTransaction, Transaction};
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{
+    BlockHeight, EpochHeight, EpochId, Gas, ShardId, StateChangeCause, StateRoot, TrieNodesCount,
+};
+use near_primitives::version::ProtocolVersion;
+use near_store::{
+    get, get_delayed_receipt_indices, set, set_access_key, StoreCompiledContractCache, Trie,
+    TrieChanges, TrieStorage, TrieUpdate,
+};
+use node_runtime::ApplyState;
+
+use super::NightshadeRuntime;
+
+/// Trie storage which reads the nodes created by the simulation from memory
+/// and the rest from the underlying storage.
+struct SimulationTrieStorage {
+    nodes: Rc<RefCell<HashMap<CryptoHash, Arc<[u8]>>>>,
+    base: Box<dyn TrieStorage>,
+}
+
+impl TrieStorage for SimulationTrieStorage {
+    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+        if let Some(value) = self.nodes.borrow().get(hash) {
+            return Ok(value.clone());
+        }
+        self.base.retrieve_raw_bytes(hash)
+    }
+
+    fn get_trie_nodes_count(&self) -> TrieNodesCount {
+        self.base.get_trie_nodes_count()
+    }
+}
+
+/// State of the shards during a simulation.
+struct SimulationState<'a> {
+    runtime: &'a NightshadeRuntime,
+    prev_block_hash: CryptoHash,
+    nodes: Rc<RefCell<HashMap<CryptoHash, Arc<[u8]>>>>,
+    state_roots: HashMap<ShardId, StateRoot>,
+}
+
+impl SimulationState<'_> {
+    fn trie(&self, shard_id: ShardId) -> Result<Option<Trie>, Error> {
+        let state_root = match self.state_roots.get(&shard_id) {
+            Some(state_root) => *state_root,
+            None => return Ok(None),
+        };
+        let base =
+            self.runtime.get_view_trie_for_shard(shard_id, &self.prev_block_hash, state_root)?;
+        let storage = SimulationTrieStorage { nodes: self.nodes.clone(), base: base.storage };
+        Ok(Some(Trie::new(Box::new(storage), state_root, None)))
+    }
+
+    fn apply_trie_changes(&mut self, shard_id: ShardId, trie_changes: &TrieChanges) {
+        let nodes = trie_changes
+            .insertions()
+            .iter()
+            .map(|change| (*change.hash(), Arc::from(change.payload())));
+        self.nodes.borrow_mut().extend(nodes);
+        self.state_roots.insert(shard_id, trie_changes.new_root);
+    }
+
+    /// Returns the state of the shard with the indices of its delayed
+    /// receipts queue.
+    fn delayed_receipt_indices(
+        &self,
+        shard_id: ShardId,
+    ) -> Result<Option<(TrieUpdate, DelayedReceiptIndices)>, Error> {
+        let trie = match self.trie(shard_id)? {
+            Some(trie) => trie,
+            None => return Ok(None),
+        };
+        let state_update = TrieUpdate::new(Rc::new(trie));
+        let indices = get_delayed_receipt_indices(&state_update)?;
+        Ok(Some((state_update, indices)))
+    }
+
+    fn has_delayed_receipts(&self, shard_id: ShardId) -> Result<bool, Error> {
+        Ok(self
+            .delayed_receipt_indices(shard_id)?
+            .map_or(false, |(_, indices)| indices.first_index < indices.next_available_index))
+    }
+
+    /// Returns the receipts in the delayed receipts queue of the shard.
+    fn delayed_receipts(&self, shard_id: ShardId) -> Result<Vec<Receipt>, Error> {
+        let (state_update, indices) = match self.delayed_receipt_indices(shard_id)? {
+            Some(result) => result,
+            None => return Ok(vec![]),
+        };
+        (indices.first_index..indices.next_available_index)
+            .map(|index| {
+                get(&state_update, &TrieKey::DelayedReceipt { index })?
+                    .ok_or_else(|| Error::Other(format!("delayed receipt #{} is missing", index)))
+            })
+            .collect()
+    }
+
+    /// Changes the state of the shard outside of the simulated blocks.
+    fn update(
+        &mut self,
+        shard_id: ShardId,
+        f: impl FnOnce(&mut TrieUpdate) -> Result<(), StorageError>,
+    ) -> Result<(), Error> {
+        let trie = match self.trie(shard_id)? {
+            Some(trie) => trie,
+            None => return Ok(()),
+        };
+        let mut state_update = TrieUpdate::new(Rc::new(trie));
+        f(&mut state_update)?;
+        state_update.commit(StateChangeCause::InitialState);
+        let (trie_changes, _) = state_update.finalize()?;
+        self.apply_trie_changes(shard_id, &trie_changes);
+        Ok(())
+    }
+}
+
+impl NightshadeRuntime {
+    pub(super) fn simulate(
+        &self,
+        header: &BlockHeader,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        transaction: Transaction,
+        signature: Option<Signature>,
+        limits: &SimulationLimits,
+    ) -> Result<Result<SimulationResult, InvalidTxError>, Error> {
+        let _span = tracing::debug_span!(
+            target: "runtime",
+            "simulate",
+            height = header.height())
+        .entered();
+        let prev_block_hash = *header.hash();
+        let epoch_id = self.get_epoch_id_from_prev_block(&prev_block_hash)?;
+        let shard_layout = self.get_shard_layout(&epoch_id)?;
+        if shard_layout != self.get_shard_layout(header.epoch_id())? {
+            return Err(Error::Other(
+                "simulation across a change of the shard layout isn't supported".to_string(),
+            ));
+        }
+        let mut state = SimulationState {
+            runtime: self,
+            prev_block_hash,
+            nodes: Default::default(),
+            state_roots: state_roots.clone(),
+        };
+        for shard_id in state_roots.keys() {
+            state.update(*shard_id, |state_update| {
+                let indices = get_delayed_receipt_indices(&*state_update)?;
+                let indices = DelayedReceiptIndices {
+                    first_index: indices.next_available_index,
+                    next_available_index: indices.next_available_index,
+                };
+                set(state_update, TrieKey::DelayedReceiptIndices, &indices);
+                Ok(())
+            })?;
+        }
+
+        let signer_shard_id = account_id_to_shard_id(&transaction.signer_id, &shard_layout);
+        if !state.state_roots.contains_key(&signer_shard_id) {
+            return Err(Error::Other(format!(
+                "state of shard {} of the signer isn't available",
+                signer_shard_id
+            )));
+        }
+        let transaction = match signature {
+            Some(signature) => SignedTransaction::new(signature, transaction),
+            None => {
+                // The runtime always verifies signatures, so the transaction
+                // is signed with a throwaway full access key of the signer.
+                let signer = InMemorySigner::from_secret_key(
+                    transaction.signer_id.clone(),
+                    SecretKey::from_random(KeyType::ED25519),
+                );
+                state.update(signer_shard_id, |state_update| {
+                    set_access_key(
+                        state_update,
+                        signer.account_id.clone(),
+                        signer.public_key(),
+                        &AccessKey::full_access(),
+                    );
+                    Ok(())
+                })?;
+                let transaction =
+                    Transaction { public_key: signer.public_key(), nonce: 1, ..transaction };
+                let signature = signer.sign(transaction.get_hash_and_size().0.as_ref());
+                SignedTransaction::new(signature, transaction)
+            }
+        };
+
+        let epoch_height = self.get_epoch_height_from_prev_block(&prev_block_hash)?;
+        let protocol_version = self.get_epoch_protocol_version(&epoch_id)?;
+        let mut result = SimulationResult::default();
+        let mut transactions = vec![transaction];
+        let mut receipts: Vec<Receipt> = vec![];
+        // Shards with receipts delayed because of the gas limit of a chunk.
+        let mut delayed_shards = BTreeSet::<ShardId>::new();
+        let mut gas_left = limits.max_gas;
+        for height in header.height() + 1..=header.height() + limits.max_blocks {
+            if transactions.is_empty() && receipts.is_empty() && delayed_shards.is_empty() {
+                break;
+            }
+            let mut receipts_by_shard = HashMap::<ShardId, Vec<Receipt>>::new();
+            for receipt in receipts.drain(..) {
+                let shard_id = account_id_to_shard_id(&receipt.receiver_id, &shard_layout);
+                receipts_by_shard.entry(shard_id).or_default().push(receipt);
+            }
+            let mut shard_ids: BTreeSet<ShardId> = receipts_by_shard.keys().copied().collect();
+            shard_ids.extend(delayed_shards.iter().copied());
+            if !transactions.is_empty() {
+                shard_ids.insert(signer_shard_id);
+            }
+            for shard_id in shard_ids {
+                let shard_receipts = receipts_by_shard.remove(&shard_id).unwrap_or_default();
+                // The transaction itself is always applied, so that its
+                // outcome is known.
+                let out_of_budget = gas_left == 0
+                    || limits.deadline.map_or(false, |deadline| Instant::now() >= deadline);
+                if out_of_budget && transactions.is_empty() {
+                    receipts.extend(shard_receipts);
+                    continue;
+                }
+                let trie = match state.trie(shard_id)? {
+                    Some(trie) => trie,
+                    None => {
+                        result.pending_receipts.extend(shard_receipts);
+                        continue;
+                    }
+                };
+                let shard_transactions = if shard_id == signer_shard_id {
+                    std::mem::take(&mut transactions)
+                } else {
+                    vec![]
+                };
+                let apply_state = self.simulation_apply_state(
+                    header,
+                    &epoch_id,
+                    epoch_height,
+                    height,
+                    protocol_version,
+                    gas_left,
+                );
+                let apply_result = self.runtime.apply(
+                    trie,
+                    &None,
+                    &apply_state,
+                    &shard_receipts,
+                    &shard_transactions,
+                    &self.epoch_manager,
+                    SandboxStatePatch::default(),
+                );
+                let apply_result = match apply_result {
+                    Ok(apply_result) => apply_result,
+                    Err(RuntimeError::InvalidTxError(err)) => return Ok(Err(err)),
+                    Err(RuntimeError::StorageError(err)) => return Err(Error::StorageError(err)),
+                    Err(err) => return Err(Error::Other(err.to_string())),
+                };
+                state.apply_trie_changes(shard_id, &apply_result.trie_changes);
+                let gas_burnt: Gas =
+                    apply_result.outcomes.iter().map(|outcome| outcome.outcome.gas_burnt).sum();
+                gas_left = gas_left.saturating_sub(gas_burnt);
+                if state.has_delayed_receipts(shard_id)? {
+                    delayed_shards.insert(shard_id);
+                } else {
+                    delayed_shards.remove(&shard_id);
+                }
+                result.outcomes.extend(apply_result.outcomes);
+                result.state_changes.extend(apply_result.state_changes);
+                receipts.extend(apply_result.outgoing_receipts);
+            }
+            if gas_left == 0 || limits.deadline.map_or(false, |deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+        }
+        result.pending_receipts.extend(receipts);
+        for shard_id in delayed_shards {
+            result.pending_receipts.extend(state.delayed_receipts(shard_id)?);
+        }
+        Ok(Ok(result))
+    }
+
+    fn simulation_apply_state(
+        &self,
+        header: &BlockHeader,
+        epoch_id: &EpochId,
+        epoch_height: EpochHeight,
+        height: BlockHeight,
+        protocol_version: ProtocolVersion,
+        gas_limit: Gas,
+    ) -> ApplyState {
+        ApplyState {
+            block_height: height,
+            prev_block_hash: *header.hash(),
+            // The simulated blocks don't exist, so they don't have a hash.
+            block_hash: CryptoHash::default(),
+            epoch_id: epoch_id.clone(),
+            epoch_height,
+            gas_price: header.gas_price(),
+            block_timestamp: header.raw_timestamp(),
+            gas_limit: Some(gas_limit),
+            random_seed: *header.random_value(),
+            current_protocol_version: protocol_version,
+            config: self.runtime_config_store.get_config(protocol_version).clone(),
+            cache: Some(Box::new(StoreCompiledContractCache::new(&self.store))),
+            is_new_chunk: true,
+            migration_data: Arc::clone(&self.migration_data),
+            migration_flags: MigrationFlags {
+                is_first_block_of_version: false,
+                is_first_block_with_chunk_of_version: false,
+            },
+        }
+    }
+}
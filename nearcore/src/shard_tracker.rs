@@ -17,7 +17,9 @@ impl TrackedConfig {
     }
 
     pub fn from_config(config: &ClientConfig) -> Self {
-        if config.tracked_shards.is_empty() {
+        if config.minimal_chunk_producer {
+            TrackedConfig::new_empty()
+        } else if config.tracked_shards.is_empty() {
             TrackedConfig::Accounts(config.tracked_accounts.clone())
         } else {
             TrackedConfig::AllShards
@@ -1,8 +1,14 @@
-#[cfg(unix)]
+use crate::database::DatabaseCommand;
+use crate::genesis::GenesisCommand;
+use crate::keys::KeysCommand;
+use crate::network::NetworkCommand;
+use crate::replay::ReplayCommand;
 use anyhow::Context;
 use clap::{Args, Parser};
 use near_amend_genesis::AmendGenesisCommand;
-use near_chain_configs::GenesisValidationMode;
+use near_chain_configs::genesis_validate::check_genesis;
+use near_chain_configs::{EmptyBlocksPolicy, Genesis, GenesisRecords, GenesisValidationMode};
+use near_client::loopback_network::LoopbackNetwork;
 use near_client::ConfigUpdater;
 use near_cold_store_tool::ColdStoreCommand;
 use near_dyn_configs::{UpdateableConfigLoader, UpdateableConfigLoaderError, UpdateableConfigs};
@@ -17,9 +23,9 @@ use near_o11y::{
 use near_ping::PingCommand;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::compute_root_from_path;
-use near_primitives::types::{Gas, NumSeats, NumShards};
+use near_primitives::types::{Balance, BlockHeightDelta, Gas, NumSeats, NumShards};
 use near_state_parts::cli::StatePartsCommand;
-use near_state_viewer::StateViewerSubCommand;
+use near_state_viewer::{ForkCommand, PrecompileCommand, StateViewerSubCommand};
 use near_store::db::RocksDB;
 use near_store::Mode;
 use serde_json::Value;
@@ -83,7 +89,12 @@ impl NeardCmd {
 
         match neard_cmd.subcmd {
             NeardSubCommand::Init(cmd) => cmd.run(&home_dir)?,
-            NeardSubCommand::Localnet(cmd) => cmd.run(&home_dir),
+            NeardSubCommand::Localnet(cmd) => cmd.run(
+                &home_dir,
+                genesis_validation,
+                neard_cmd.opts.verbose_target(),
+                &neard_cmd.opts.o11y,
+            )?,
             NeardSubCommand::Run(cmd) => cmd.run(
                 &home_dir,
                 genesis_validation,
@@ -117,6 +128,30 @@ impl NeardCmd {
             NeardSubCommand::StateParts(cmd) => {
                 cmd.run()?;
             }
+            NeardSubCommand::Fork(cmd) => {
+                cmd.run(&home_dir, genesis_validation)?;
+            }
+            NeardSubCommand::Precompile(cmd) => {
+                cmd.run(&home_dir, genesis_validation)?;
+            }
+            NeardSubCommand::Keys(cmd) => {
+                cmd.run(&home_dir)?;
+            }
+            NeardSubCommand::ValidateGenesis(cmd) => {
+                cmd.run(&home_dir)?;
+            }
+            NeardSubCommand::Genesis(cmd) => {
+                cmd.run(&home_dir)?;
+            }
+            NeardSubCommand::Database(cmd) => {
+                cmd.run(&home_dir, genesis_validation)?;
+            }
+            NeardSubCommand::Replay(cmd) => {
+                cmd.run(&home_dir, genesis_validation)?;
+            }
+            NeardSubCommand::Network(cmd) => {
+                cmd.run(&home_dir, genesis_validation)?;
+            }
         };
         Ok(())
     }
@@ -226,6 +261,39 @@ pub(super) enum NeardSubCommand {
 
     /// Connects to a NEAR node and sends state parts requests after the handshake is completed.
     StateParts(StatePartsCommand),
+
+    /// Creates the home directory of a new chain whose genesis state is the
+    /// state of this node's chain at a given block, with replaced validators.
+    Fork(ForkCommand),
+
+    /// Compiles the contracts deployed in the current state into the compiled
+    /// contract cache, so that they don't have to be compiled on first use.
+    Precompile(PrecompileCommand),
+
+    /// Generates, rotates, inspects and (re-)encrypts the node, validator and
+    /// signer keys.
+    Keys(KeysCommand),
+
+    /// Checks the consistency of the genesis records, such as the total
+    /// supply and duplicate accounts, streaming them from the genesis or
+    /// records file, and prints a summary.
+    ValidateGenesis(ValidateGenesisCmd),
+
+    /// Builds genesis files of custom chains.
+    Genesis(GenesisCommand),
+
+    /// Creates snapshots of the database, also while the node is running, and
+    /// restores them.
+    Database(DatabaseCommand),
+
+    /// Replays a log of the messages received by a node, recorded when
+    /// `message_log` is set in config.json, into a client started from
+    /// genesis with the clock set to the times they were received.
+    Replay(ReplayCommand),
+
+    /// Exports and imports the peer store, and checks the reachability of
+    /// the boot nodes.
+    Network(NetworkCommand),
 }
 
 #[derive(Parser)]
@@ -381,6 +449,37 @@ pub(super) struct RunCmd {
     /// configuration will be taken.
     #[clap(long)]
     max_gas_burnt_view: Option<Gas>,
+    /// Shadow block and chunk production of the given validator account
+    /// without broadcasting anything.  Blocks and chunks are signed with
+    /// a throwaway key and the time it took to produce them is reported in
+    /// logs and metrics.  Useful to qualify hardware before staking.
+    #[clap(long)]
+    dry_run_validator: Option<near_primitives::types::AccountId>,
+    /// If the node has no database yet, download it from the snapshot
+    /// published at this URL before starting: either a JSON manifest listing
+    /// the files of the database or a tar archive of the database.  The
+    /// download is resumed if interrupted, and the files are verified against
+    /// the checksums, chain id and head in the manifest.
+    #[clap(long)]
+    boot_from_snapshot_url: Option<String>,
+    /// Hash of the block the snapshot given with ‘--boot-from-snapshot-url’
+    /// is expected to have as its head.
+    #[clap(long, requires = "boot-from-snapshot-url")]
+    boot_from_snapshot_head: Option<near_primitives::hash::CryptoHash>,
+    /// Allow ‘--boot-from-snapshot-url’ to be a plain HTTP URL.  The files are
+    /// still verified against the checksums in the manifest, but the manifest
+    /// itself can then be tampered with.
+    #[clap(long, requires = "boot-from-snapshot-url")]
+    boot_from_snapshot_allow_http: bool,
+    /// Start the node without networking, to inspect a copy of a data
+    /// directory: the storage, the runtime and the RPC are started, but the
+    /// node doesn’t connect to any peers, sync, produce blocks nor report to
+    /// telemetry.  The database isn’t migrated: a database which needs
+    /// migrating has to be migrated with `neard database migrate` first.
+    /// It’s still opened read-write and starting the chain may write to it,
+    /// so don’t point this at the data directory of a production node.
+    #[clap(long)]
+    offline: bool,
 }
 
 impl RunCmd {
@@ -400,8 +499,17 @@ impl RunCmd {
         // Set current version in client config.
         near_config.client_config.version = crate::neard_version();
         // Override some parameters from command line.
-        if let Some(produce_empty_blocks) = self.produce_empty_blocks {
-            near_config.client_config.produce_empty_blocks = produce_empty_blocks;
+        let updateable_config_overrides = nearcore::dyn_config::UpdateableConfigOverrides {
+            produce_empty_blocks: self.produce_empty_blocks.map(|produce_empty_blocks| {
+                if produce_empty_blocks {
+                    EmptyBlocksPolicy::Always
+                } else {
+                    EmptyBlocksPolicy::Never
+                }
+            }),
+        };
+        if let Some(produce_empty_blocks) = updateable_config_overrides.produce_empty_blocks {
+            near_config.client_config.produce_empty_blocks.update(produce_empty_blocks);
         }
         if let Some(boot_nodes) = self.boot_nodes {
             if !boot_nodes.is_empty() {
@@ -442,6 +550,12 @@ impl RunCmd {
         if self.max_gas_burnt_view.is_some() {
             near_config.client_config.max_gas_burnt_view = self.max_gas_burnt_view;
         }
+        if self.dry_run_validator.is_some() {
+            near_config.client_config.dry_run_validator = self.dry_run_validator;
+        }
+        if self.offline {
+            near_config.client_config.offline = true;
+        }
 
         #[cfg(feature = "sandbox")]
         {
@@ -477,26 +591,47 @@ impl RunCmd {
             .await
             .global();
 
-            let updateable_configs = nearcore::dyn_config::read_updateable_configs(home_dir)
-                .unwrap_or_else(|e| panic!("Error reading dynamic configs: {:#}", e));
+            if let Some(url) = &self.boot_from_snapshot_url {
+                nearcore::boot_snapshot::boot_from_snapshot(
+                    home_dir,
+                    &near_config,
+                    url,
+                    self.boot_from_snapshot_head,
+                    self.boot_from_snapshot_allow_http,
+                )
+                .await
+                .unwrap_or_else(|e| panic!("Error booting from snapshot: {:#}", e));
+            }
+
+            let updateable_configs = nearcore::dyn_config::read_updateable_configs(
+                home_dir,
+                &updateable_config_overrides,
+            )
+            .unwrap_or_else(|e| panic!("Error reading dynamic configs: {:#}", e));
             let mut updateable_config_loader =
                 UpdateableConfigLoader::new(updateable_configs.clone(), tx_config_update);
             let config_updater = ConfigUpdater::new(rx_config_update);
 
-            let nearcore::NearNode { rpc_servers, cold_store_loop_handle, .. } =
-                nearcore::start_with_config_and_synchronization(
-                    home_dir,
-                    near_config,
-                    Some(tx_crash),
-                    Some(config_updater),
-                )
-                .expect("start_with_config");
+            let nearcore::NearNode {
+                rpc_servers,
+                cold_store_loop_handle,
+                account_outcome_ids_backfill_handle,
+                ..
+            } = nearcore::start_with_config_and_synchronization(
+                home_dir,
+                near_config,
+                Some(tx_crash),
+                Some(config_updater),
+            )
+            .expect("start_with_config");
 
             let sig = loop {
                 let sig = wait_for_interrupt_signal(home_dir, &mut rx_crash).await;
                 if sig == "SIGHUP" {
-                    let maybe_updateable_configs =
-                        nearcore::dyn_config::read_updateable_configs(home_dir);
+                    let maybe_updateable_configs = nearcore::dyn_config::read_updateable_configs(
+                        home_dir,
+                        &updateable_config_overrides,
+                    );
                     updateable_config_loader.reload(maybe_updateable_configs);
                 } else {
                     break sig;
@@ -504,6 +639,7 @@ impl RunCmd {
             };
             warn!(target: "neard", "{}, stopping... this may take a few minutes.", sig);
             cold_store_loop_handle.map(|handle| handle.stop());
+            account_outcome_ids_backfill_handle.map(|handle| handle.stop());
             futures::future::join_all(rpc_servers.iter().map(|(name, server)| async move {
                 server.stop(true).await;
                 debug!(target: "neard", "{} server stopped", name);
@@ -564,9 +700,39 @@ pub(super) struct LocalnetCmd {
     /// Whether to configure nodes as archival.
     #[clap(long)]
     archival_nodes: bool,
+    /// Number of non-validators to configure as archival nodes tracking all
+    /// shards.  The remaining non-validators are configured as RPC nodes.
+    #[clap(long, default_value = "0", conflicts_with = "archival-nodes")]
+    num_archival_nodes: NumSeats,
     /// Comma separated list of shards to track, the word 'all' to track all shards or the word 'none' to track no shards.
     #[clap(long, default_value = "all")]
     tracked_shards: String,
+    /// Length of an epoch in blocks.
+    #[clap(long)]
+    epoch_length: Option<BlockHeightDelta>,
+    /// Maximum amount of gas a chunk can use.
+    #[clap(long)]
+    gas_limit: Option<Gas>,
+    /// Minimum gas price.
+    #[clap(long)]
+    min_gas_price: Option<Balance>,
+    /// Maximum gas price.
+    #[clap(long)]
+    max_gas_price: Option<Balance>,
+    /// Path to a JSON file with genesis records to add to the genesis state,
+    /// e.g. a contract state dumped with `view-state dump-contract-state`.
+    /// Added accounts get a full access key of the first validator.
+    #[clap(long)]
+    extra_records: Option<PathBuf>,
+    /// Number of the initialized validators to run in this process, from the
+    /// first one, instead of exiting once the localnet is initialized.  The
+    /// nodes have separate stores but share the threads of the process,
+    /// which takes much less resources than a process per node.  They talk
+    /// to each other over an in-memory network, so nodes of the localnet run
+    /// in other processes can't reach them.  The nodes share the metrics
+    /// registry of the process: every node reports the metrics of all.
+    #[clap(long)]
+    in_process: Option<NumSeats>,
 }
 
 impl LocalnetCmd {
@@ -583,23 +749,129 @@ impl LocalnetCmd {
             .collect()
     }
 
-    pub(super) fn run(self, home_dir: &Path) {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+        verbose_target: Option<&str>,
+        o11y_opts: &near_o11y::Options,
+    ) -> anyhow::Result<()> {
+        if let Some(in_process) = self.in_process {
+            anyhow::ensure!(
+                0 < in_process && in_process <= self.validators,
+                "Number of nodes to run in process ({}) must be between 1 and the number of validators ({})",
+                in_process,
+                self.validators
+            );
+        }
         let tracked_shards = Self::parse_tracked_shards(&self.tracked_shards, self.shards);
+        let genesis_overrides = nearcore::config::TestnetGenesisOverrides {
+            epoch_length: self.epoch_length,
+            gas_limit: self.gas_limit,
+            min_gas_price: self.min_gas_price,
+            max_gas_price: self.max_gas_price,
+            extra_records: self
+                .extra_records
+                .map(|path| GenesisRecords::from_file(path).0)
+                .unwrap_or_default(),
+        };
 
         nearcore::config::init_testnet_configs(
             home_dir,
             self.shards,
             self.validators,
             self.non_validators,
+            self.num_archival_nodes,
             &self.prefix,
             true,
             self.archival_nodes,
             self.fixed_shards,
             tracked_shards,
+            &genesis_overrides,
         );
+
+        if let Some(in_process) = self.in_process {
+            let home_dirs =
+                (0..in_process).map(|i| home_dir.join(format!("{}{}", self.prefix, i))).collect();
+            run_localnet_in_process(home_dirs, genesis_validation, verbose_target, o11y_opts)?;
+        }
+        Ok(())
     }
 }
 
+/// Runs the localnet validators in `home_dirs` in this process until it's
+/// interrupted or one of the nodes dies.  The nodes talk to each other over a
+/// `LoopbackNetwork` rather than the ports assigned to them by
+/// `init_testnet_configs`.
+fn run_localnet_in_process(
+    home_dirs: Vec<PathBuf>,
+    genesis_validation: GenesisValidationMode,
+    verbose_target: Option<&str>,
+    o11y_opts: &near_o11y::Options,
+) -> anyhow::Result<()> {
+    let near_configs = home_dirs
+        .iter()
+        .map(|home_dir| {
+            let mut near_config = nearcore::config::load_config(home_dir, genesis_validation)
+                .with_context(|| format!("Error loading config from {}", home_dir.display()))?;
+            near_config.client_config.version = crate::neard_version();
+            Ok(near_config)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let env_filter = make_env_filter(verbose_target)?;
+
+    let (tx_crash, mut rx_crash) = broadcast::channel::<()>(16);
+    let sys = actix::System::new();
+    sys.block_on(async move {
+        let _subscriber_guard = default_subscriber_with_opentelemetry(
+            env_filter,
+            o11y_opts,
+            near_configs[0].client_config.chain_id.clone(),
+            near_configs[0].network_config.node_key.public_key().clone(),
+            None,
+        )
+        .await
+        .global();
+
+        let network = LoopbackNetwork::new(0);
+        let mut nodes = Vec::with_capacity(near_configs.len());
+        for (home_dir, near_config) in home_dirs.iter().zip(near_configs) {
+            info!(target: "neard", home_dir = %home_dir.display(), "Starting localnet node");
+            nodes.push(
+                nearcore::start_with_loopback_network(
+                    home_dir,
+                    near_config,
+                    Some(tx_crash.clone()),
+                    &network,
+                )
+                .with_context(|| format!("Failed to start node in {}", home_dir.display()))?,
+            );
+        }
+
+        let sig = wait_for_interrupt_signal(&home_dirs[0], &mut rx_crash).await;
+        warn!(target: "neard", "{}, stopping {} nodes... this may take a few minutes.", sig, nodes.len());
+        let mut rpc_servers = vec![];
+        for node in nodes {
+            node.cold_store_loop_handle.map(|handle| handle.stop());
+            node.account_outcome_ids_backfill_handle.map(|handle| handle.stop());
+            rpc_servers.extend(node.rpc_servers);
+        }
+        futures::future::join_all(rpc_servers.iter().map(|(name, server)| async move {
+            server.stop(true).await;
+            debug!(target: "neard", "{} server stopped", name);
+        }))
+        .await;
+        actix::System::current().stop();
+        // Disable the subscriber to properly shutdown the tracer.
+        near_o11y::reload(Some("error"), None, Some(near_o11y::OpenTelemetryLevel::OFF)).unwrap();
+        anyhow::Ok(())
+    })?;
+    sys.run()?;
+    info!(target: "neard", "Waiting for RocksDB to gracefully shutdown");
+    RocksDB::block_until_all_instances_are_dropped();
+    Ok(())
+}
+
 #[derive(Args)]
 #[clap(arg_required_else_help = true)]
 pub(super) struct RecompressStorageSubCommand {
@@ -745,6 +1017,67 @@ impl VerifyProofSubCommand {
     }
 }
 
+#[derive(Parser)]
+pub(super) struct ValidateGenesisCmd {
+    /// Genesis file.  Defaults to `genesis_file` from config.json.
+    #[clap(long)]
+    genesis: Option<PathBuf>,
+    /// Records file, if the records are not in the genesis file.  Defaults to
+    /// `genesis_records_file` from config.json.
+    #[clap(long)]
+    records: Option<PathBuf>,
+}
+
+impl ValidateGenesisCmd {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let (genesis_file, records_file) = match self.genesis {
+            Some(genesis_file) => (genesis_file, self.records),
+            None => {
+                let config = nearcore::config::Config::from_file(
+                    &home_dir.join(nearcore::config::CONFIG_FILENAME),
+                )?;
+                let records_file =
+                    self.records.or(config.genesis_records_file.map(|file| home_dir.join(file)));
+                (home_dir.join(config.genesis_file), records_file)
+            }
+        };
+        // The records are validated below, streaming them from the file.
+        let genesis = match &records_file {
+            Some(records_file) => {
+                Genesis::from_files(&genesis_file, records_file, GenesisValidationMode::UnsafeFast)
+            }
+            None => Genesis::from_file(&genesis_file, GenesisValidationMode::UnsafeFast),
+        };
+        let report = check_genesis(&genesis).with_context(|| {
+            format!(
+                "Failed reading records of {}",
+                records_file.as_ref().unwrap_or(&genesis_file).display()
+            )
+        })?;
+        let summary = &report.summary;
+        println!("Chain ID: {}", genesis.config.chain_id);
+        println!("Protocol version: {}", genesis.config.protocol_version);
+        println!("Genesis height: {}", genesis.config.genesis_height);
+        println!("Validators: {}", genesis.config.validators.len());
+        println!("Total supply: {}", genesis.config.total_supply);
+        println!("Records: {}", summary.num_records);
+        println!("  accounts: {}", summary.num_accounts);
+        println!("  access keys: {}", summary.num_access_keys);
+        println!("  contracts: {} ({} bytes)", summary.num_contracts, summary.total_contract_size);
+        println!("  data: {}", summary.num_data_records);
+        println!("  other: {}", summary.num_other_records);
+        println!("Balance in records: {} ({} locked)", summary.total_balance, summary.total_locked);
+        if report.errors.is_empty() {
+            println!("Genesis is valid");
+            return Ok(());
+        }
+        for error in &report.errors {
+            println!("Error: {}", error);
+        }
+        anyhow::bail!("Found {} problems in the genesis", report.errors.len())
+    }
+}
+
 fn make_env_filter(verbose: Option<&str>) -> Result<EnvFilter, BuildEnvFilterError> {
     let env_filter = EnvFilterBuilder::from_env().verbose(verbose).finish()?;
     // Sandbox node can log to sandbox logging target via sandbox_debug_log host function.
@@ -787,6 +1120,20 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn localnet_in_process_out_of_range() {
+        let home_dir = Path::new("/nonexistent");
+        for in_process in ["0", "5"] {
+            let cmd = NeardCmd::parse_from(&["test", "localnet", "--in-process", in_process]);
+            if let NeardSubCommand::Localnet(scmd) = cmd.subcmd {
+                let opts = near_o11y::Options::default();
+                assert!(scmd.run(home_dir, GenesisValidationMode::Full, None, &opts).is_err());
+            } else {
+                panic!("incorrect subcommand");
+            }
+        }
+    }
+
     #[test]
     fn verify_proof_test() {
         assert_eq!(
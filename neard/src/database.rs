@@ -0,0 +1,400 @@
+use anyhow::{bail, Context};
+use clap::Parser;
+use near_chain_configs::GenesisValidationMode;
+use near_jsonrpc_primitives::types::db_snapshot::RpcDbSnapshotResponse;
+use near_primitives::types::ShardId;
+use near_store::db_snapshot::{create_snapshot, restore_snapshot, SnapshotManifest};
+use near_store::metadata::DB_VERSION;
+use near_store::refcount_audit;
+use near_store::{Mode, NodeStorage};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+#[derive(Parser)]
+pub(super) struct DatabaseCommand {
+    #[clap(subcommand)]
+    subcmd: DatabaseSubCommand,
+}
+
+#[derive(Parser)]
+enum DatabaseSubCommand {
+    /// Creates a consistent snapshot of the hot database: a RocksDB checkpoint
+    /// with a manifest recording the chain id, the head height and the
+    /// database version.  By default the running node is asked to create it
+    /// through its debug RPC address.
+    Snapshot(SnapshotCmd),
+    /// Restores a snapshot created by `snapshot` as the database of the node.
+    /// The node must be stopped and its database directory must not exist
+    /// or be empty.
+    Restore(RestoreCmd),
+    /// Performs pending database migrations without starting the node.  With
+    /// `--dry-run` only lists the migrations with an estimate of how long
+    /// they would take.
+    Migrate(MigrateCmd),
+    /// Restores the database snapshot kept after the last migration, undoing
+    /// the migration.  Requires `store.keep_migration_snapshot` to have been
+    /// set during the migration.  The node must be stopped.
+    RollbackMigration(RollbackMigrationCmd),
+    /// Recomputes reference counts of the trie nodes from the state at the
+    /// head of the chain and the changes still to be garbage collected, and
+    /// reports the nodes whose counts differ.  With `--fix` sets the counts
+    /// to the expected values.  The node must be stopped.
+    AuditRefcounts(AuditRefcountsCmd),
+}
+
+impl DatabaseCommand {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        match self.subcmd {
+            DatabaseSubCommand::Snapshot(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseSubCommand::Restore(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseSubCommand::Migrate(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseSubCommand::RollbackMigration(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseSubCommand::AuditRefcounts(cmd) => cmd.run(home_dir, genesis_validation),
+        }
+    }
+}
+
+#[derive(Parser)]
+struct SnapshotCmd {
+    /// Name of the snapshot directory the running node creates in its
+    /// `rpc.db_snapshot_dir`.
+    #[clap(long, required_unless_present = "offline")]
+    name: Option<String>,
+    /// Opens the database directly instead of asking the running node.  The
+    /// node must be stopped.
+    #[clap(long, requires = "output")]
+    offline: bool,
+    /// Directory to create the snapshot in with `--offline`.  Must not exist
+    /// and should be on the same filesystem as the database so that the files
+    /// can be hard-linked rather than copied.
+    #[clap(long, requires = "offline")]
+    output: Option<PathBuf>,
+    /// Address of the debug RPC of the running node.  Defaults to
+    /// `rpc.debug_addr` from config.json.
+    #[clap(long)]
+    rpc_addr: Option<String>,
+}
+
+impl SnapshotCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let (output, manifest) = match (self.offline, self.output, self.name) {
+            (true, Some(output), _) => {
+                let storage = NodeStorage::opener(home_dir, &near_config.config.store, None)
+                    .open_in_mode(Mode::ReadWriteExisting)
+                    .context("Failed to open the database; is the node running?")?;
+                let manifest = create_snapshot(
+                    &storage.get_hot_store(),
+                    &output,
+                    &near_config.genesis.config.chain_id,
+                )?;
+                (output, manifest)
+            }
+            (false, _, Some(name)) => {
+                let addr = match self.rpc_addr.or_else(|| near_config.config.debug_rpc_addr()) {
+                    Some(addr) => addr,
+                    None => bail!(
+                        "rpc.debug_addr isn't configured, use --offline if the node is stopped"
+                    ),
+                };
+                info!(target: "neard", %addr, "Asking the node to create the snapshot");
+                let body = reqwest::blocking::Client::builder()
+                    .timeout(None)
+                    .build()?
+                    .post(format!("http://{}", addr))
+                    .header("Content-Type", "application/json")
+                    .body(
+                        serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": "dontcare",
+                            "method": "EXPERIMENTAL_db_snapshot",
+                            "params": { "name": name },
+                        })
+                        .to_string(),
+                    )
+                    .send()
+                    .with_context(|| format!("Failed to reach the node at {}", addr))?
+                    .error_for_status()?
+                    .text()?;
+                let response: serde_json::Value =
+                    serde_json::from_str(&body).context("Unexpected response from the node")?;
+                if let Some(error) = response.get("error") {
+                    bail!("The node failed to create the snapshot: {}", error);
+                }
+                let response: RpcDbSnapshotResponse =
+                    serde_json::from_value(response["result"].clone())
+                        .context("Unexpected response from the node")?;
+                let manifest = SnapshotManifest {
+                    chain_id: response.chain_id,
+                    head_height: response.head_height,
+                    db_version: response.db_version,
+                };
+                (response.path, manifest)
+            }
+            _ => bail!("Either --name or --offline with --output must be given"),
+        };
+        println!(
+            "Created snapshot of chain {} at height {} with database version {} in {}",
+            manifest.chain_id,
+            manifest.head_height,
+            manifest.db_version,
+            output.display()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct RestoreCmd {
+    /// Directory of the snapshot to restore.
+    #[clap(long)]
+    input: PathBuf,
+}
+
+impl RestoreCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let opener = NodeStorage::opener(home_dir, &near_config.config.store, None);
+        let db_path = opener.path();
+        let manifest = restore_snapshot(&self.input, db_path, &near_config.genesis.config.chain_id)
+            .with_context(|| {
+                format!("Failed to restore {} into {}", self.input.display(), db_path.display())
+            })?;
+        println!(
+            "Restored snapshot of chain {} at height {} with database version {} into {}",
+            manifest.chain_id,
+            manifest.head_height,
+            manifest.db_version,
+            db_path.display()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct MigrateCmd {
+    /// Lists the migrations which would be performed without performing them.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl MigrateCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let mut near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        if self.dry_run {
+            return dry_run(home_dir, &near_config, &mut std::io::stdout());
+        }
+        nearcore::migrate_storage(home_dir, &mut near_config)?;
+        println!("Database is at version {}", DB_VERSION);
+        Ok(())
+    }
+}
+
+/// Writes the migrations `neard database migrate` would perform to `out`.
+fn dry_run(
+    home_dir: &Path,
+    near_config: &nearcore::NearConfig,
+    out: &mut impl std::io::Write,
+) -> anyhow::Result<()> {
+    let plan = nearcore::plan_storage_migrations(home_dir, near_config)?;
+    if plan.is_empty() {
+        writeln!(out, "Database is up to date at version {}", DB_VERSION)?;
+        return Ok(());
+    }
+    let mut total = std::time::Duration::ZERO;
+    for migration in &plan {
+        total += migration.estimated_duration;
+        writeln!(
+            out,
+            "{:>3} → {:<3} | {:>10} | {:>7} | {}",
+            migration.version,
+            migration.version + 1,
+            bytesize::ByteSize(migration.bytes).to_string(),
+            format_duration(migration.estimated_duration),
+            migration.info.summary
+        )?;
+    }
+    writeln!(out, "{} migrations, estimated to take {}", plan.len(), format_duration(total))?;
+    let store_config = &near_config.config.store;
+    let opener = NodeStorage::opener(home_dir, store_config, None);
+    match store_config.migration_snapshot.get_path(opener.path()) {
+        Some(path) => writeln!(
+            out,
+            "A snapshot of the database will be created at {} before migrating",
+            path.display()
+        )?,
+        None => writeln!(out, "Migration snapshots are disabled; the migration cannot be undone")?,
+    }
+    Ok(())
+}
+
+/// Formats an estimated duration with minute precision.
+fn format_duration(duration: std::time::Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes == 0 {
+        "<1m".to_string()
+    } else {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+#[derive(Parser)]
+struct RollbackMigrationCmd {}
+
+impl RollbackMigrationCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let opener = NodeStorage::opener(
+            home_dir,
+            &near_config.config.store,
+            near_config.config.cold_store.as_ref(),
+        );
+        let version = opener.rollback_migration().with_context(|| {
+            format!("Failed to roll back the database at {}", opener.path().display())
+        })?;
+        match version {
+            Some(version) => {
+                println!("Restored the database at version {}", version);
+                if version < DB_VERSION {
+                    println!(
+                        "Start a neard release which supports that version; \
+                         this binary would migrate the database again"
+                    );
+                }
+            }
+            None => {
+                bail!("No migration snapshot found for the database at {}", opener.path().display())
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct AuditRefcountsCmd {
+    /// Shard to audit.  Defaults to all shards the node has state of at the
+    /// head of the chain.
+    #[clap(long)]
+    shard_id: Option<ShardId>,
+    /// Sets reference counts of the reported nodes to the expected values and
+    /// deletes nodes which are no longer used.
+    #[clap(long)]
+    fix: bool,
+    /// Number of discrepancies to print for each shard.
+    #[clap(long, default_value = "10")]
+    show: usize,
+}
+
+impl AuditRefcountsCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let mode = if self.fix { Mode::ReadWriteExisting } else { Mode::ReadOnly };
+        let storage = NodeStorage::opener(home_dir, &near_config.config.store, None)
+            .open_in_mode(mode)
+            .context("Failed to open the database; is the node running?")?;
+        let store = storage.get_hot_store();
+        if refcount_audit::catchup_in_progress(&store)? {
+            bail!(
+                "The state of some shards is being caught up; \
+                 run the node until the catch-up finishes before auditing"
+            );
+        }
+        let shard_uids = refcount_audit::shards_at_head(&store)?
+            .into_iter()
+            .filter(|shard_uid| self.shard_id.map_or(true, |id| shard_uid.shard_id() == id))
+            .collect::<Vec<_>>();
+        if shard_uids.is_empty() {
+            bail!("No state of the requested shards at the head of the chain");
+        }
+
+        let mut unrepaired = 0;
+        for shard_uid in shard_uids {
+            info!(target: "neard", shard_uid = %shard_uid, "Auditing reference counts");
+            let audit = refcount_audit::audit_refcounts(&store, shard_uid)?;
+            let missing = audit.missing();
+            let leaked =
+                audit.discrepancies.iter().filter(|d| d.actual > d.expected as i64).count();
+            println!(
+                "Shard {} (state root {}): {} nodes checked, {} with wrong reference count \
+                 ({} too high, {} too low of which {} missing)",
+                shard_uid,
+                audit.state_root,
+                audit.nodes_checked,
+                audit.discrepancies.len(),
+                leaked,
+                audit.discrepancies.len() - leaked,
+                missing
+            );
+            for discrepancy in audit.discrepancies.iter().take(self.show) {
+                println!(
+                    "  {} expected {} actual {}{}",
+                    discrepancy.hash,
+                    discrepancy.expected,
+                    discrepancy.actual,
+                    if discrepancy.is_missing() { " (missing)" } else { "" }
+                );
+            }
+            if audit.discrepancies.is_empty() {
+                continue;
+            }
+            if !self.fix {
+                unrepaired += audit.discrepancies.len();
+            } else if missing > 0 {
+                println!(
+                    "  Not repairing: nodes in use are missing, the shard must be synced again"
+                );
+                unrepaired += audit.discrepancies.len();
+            } else {
+                let repaired = refcount_audit::repair_refcounts(&store, &audit)?;
+                println!("  Repaired {} nodes", repaired);
+            }
+        }
+        if unrepaired > 0 {
+            bail!("{} nodes have wrong reference counts", unrepaired);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_chain_configs::Genesis;
+    use nearcore::config::GenesisExt;
+
+    #[test]
+    fn test_migrate_dry_run() {
+        let home = tempfile::tempdir().unwrap();
+        let genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+        let near_config = nearcore::load_test_config(
+            "test0",
+            near_network::tcp::ListenerAddr::reserve_for_test(),
+            genesis,
+        );
+        let run = || {
+            let mut out = Vec::new();
+            dry_run(home.path(), &near_config, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let opener = NodeStorage::opener(home.path(), &near_config.config.store, None);
+        opener.open().unwrap();
+        assert_eq!(run(), format!("Database is up to date at version {}\n", DB_VERSION));
+
+        opener.open().unwrap().set_version(DB_VERSION - 1).unwrap();
+        let out = run();
+        assert!(out.starts_with(&format!("{:>3} → {:<3} | ", DB_VERSION - 1, DB_VERSION)), "{out}");
+        assert!(out.contains("1 migrations, estimated to take <1m\n"), "{out}");
+        assert!(out.contains("A snapshot of the database will be created at"), "{out}");
+        // The database is left at the old version.
+        assert_eq!(run(), out);
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser};
+use near_chain_configs::{GenesisBuilder, GenesisConfig};
+use near_crypto::PublicKey;
+use near_primitives::serialize::dec_format;
+use near_primitives::shard_layout::ShardLayout;
+use near_primitives::types::{AccountId, Balance, NumSeats, NumShards};
+use near_primitives::version::ProtocolVersion;
+use nearcore::config::GENESIS_CONFIG_FILENAME;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+pub(super) struct GenesisCommand {
+    #[clap(subcommand)]
+    subcmd: GenesisSubCommand,
+}
+
+#[derive(Parser)]
+enum GenesisSubCommand {
+    /// Builds a genesis file from a spec file.  The same spec always yields
+    /// the same genesis file.
+    Build(BuildGenesisCmd),
+}
+
+impl GenesisCommand {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        match self.subcmd {
+            GenesisSubCommand::Build(cmd) => cmd.run(home_dir),
+        }
+    }
+}
+
+/// Description of a genesis read by `neard genesis build`.
+///
+/// Balances and stakes are strings with yoctoNEAR amounts.  Contract paths are
+/// relative to the directory of the spec file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenesisSpec {
+    chain_id: String,
+    /// Defaults to the Unix epoch.
+    genesis_time: Option<DateTime<Utc>>,
+    /// Defaults to the latest protocol version of this binary.
+    protocol_version: Option<ProtocolVersion>,
+    epoch_length: Option<u64>,
+    /// Number of shards of a version 0 shard layout.  Mutually exclusive with
+    /// `shard_layout`.
+    num_shards: Option<NumShards>,
+    shard_layout: Option<ShardLayout>,
+    /// Defaults to the number of validators.
+    num_block_producer_seats: Option<NumSeats>,
+    /// Values of other fields of the genesis config, such as `gas_limit`,
+    /// `min_gas_price` or `protocol_treasury_account`.
+    #[serde(default)]
+    config: Map<String, Value>,
+    #[serde(default)]
+    accounts: Vec<AccountEntry>,
+    #[serde(default)]
+    validators: Vec<ValidatorEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountEntry {
+    account_id: AccountId,
+    #[serde(with = "dec_format")]
+    balance: Balance,
+    /// Full access keys of the account.
+    #[serde(default)]
+    public_keys: Vec<PublicKey>,
+    /// Path of the wasm file of the contract deployed to the account.
+    contract: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ValidatorEntry {
+    account_id: AccountId,
+    public_key: PublicKey,
+    #[serde(with = "dec_format")]
+    stake: Balance,
+}
+
+/// Sets the fields of the config present in `overrides`.
+fn apply_config_overrides(
+    config: &mut GenesisConfig,
+    overrides: Map<String, Value>,
+) -> anyhow::Result<()> {
+    let mut value = serde_json::to_value(&*config)?;
+    for (field, field_value) in overrides {
+        *value
+            .get_mut(&field)
+            .ok_or_else(|| anyhow!("Unknown genesis config field {}", field))? = field_value;
+    }
+    *config = serde_json::from_value(value).context("Invalid genesis config values")?;
+    Ok(())
+}
+
+impl GenesisSpec {
+    fn into_builder(self, base_dir: &Path) -> anyhow::Result<GenesisBuilder> {
+        let mut builder = GenesisBuilder::new(self.chain_id);
+        let mut overrides_result = Ok(());
+        builder = builder
+            .configure(|config| overrides_result = apply_config_overrides(config, self.config));
+        overrides_result?;
+        if let Some(genesis_time) = self.genesis_time {
+            builder = builder.genesis_time(genesis_time);
+        }
+        if let Some(protocol_version) = self.protocol_version {
+            builder = builder.protocol_version(protocol_version);
+        }
+        if let Some(epoch_length) = self.epoch_length {
+            builder = builder.epoch_length(epoch_length);
+        }
+        match (self.num_shards, self.shard_layout) {
+            (Some(_), Some(_)) => bail!("Only one of num_shards and shard_layout may be set"),
+            (Some(num_shards), None) => {
+                builder = builder.shard_layout(ShardLayout::v0(num_shards, 0))
+            }
+            (None, Some(shard_layout)) => builder = builder.shard_layout(shard_layout),
+            (None, None) => {}
+        }
+        if let Some(num_seats) = self.num_block_producer_seats {
+            builder = builder.num_block_producer_seats(num_seats);
+        }
+        for account in self.accounts {
+            builder = builder.add_account(account.account_id.clone(), account.balance);
+            for public_key in account.public_keys {
+                builder = builder.add_key(account.account_id.clone(), public_key);
+            }
+            if let Some(contract) = account.contract {
+                let path = base_dir.join(contract);
+                let code = std::fs::read(&path)
+                    .with_context(|| format!("Failed reading contract {}", path.display()))?;
+                builder = builder.add_contract(account.account_id, code);
+            }
+        }
+        for validator in self.validators {
+            builder =
+                builder.add_validator(validator.account_id, validator.public_key, validator.stake);
+        }
+        Ok(builder)
+    }
+}
+
+#[derive(Args)]
+struct BuildGenesisCmd {
+    /// JSON file describing the chain.
+    #[clap(long)]
+    spec: PathBuf,
+    /// Where to write the genesis.  Defaults to `genesis.json` in the home
+    /// directory.
+    #[clap(long)]
+    output: Option<PathBuf>,
+    /// Overwrite the output file if it exists.
+    #[clap(long)]
+    force: bool,
+}
+
+impl BuildGenesisCmd {
+    fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let output = self.output.unwrap_or_else(|| home_dir.join(GENESIS_CONFIG_FILENAME));
+        if output.exists() && !self.force {
+            bail!("{} already exists; use --force to overwrite it", output.display());
+        }
+        let spec = std::fs::read(&self.spec)
+            .with_context(|| format!("Failed reading {}", self.spec.display()))?;
+        let spec: GenesisSpec = serde_json::from_slice(&spec)
+            .with_context(|| format!("Failed parsing {}", self.spec.display()))?;
+        let base_dir = self.spec.parent().unwrap_or(Path::new(""));
+        let genesis = spec.into_builder(base_dir)?.build()?;
+        genesis.to_file(&output);
+        println!("Wrote genesis of {} to {}", genesis.config.chain_id, output.display());
+        println!("Genesis hash: {}", genesis.json_hash());
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{anyhow, bail, Context};
+use clap::{ArgEnum, Args, Parser};
+use near_crypto::{KeyFile, KeyType, SecretKey};
+use near_primitives::types::AccountId;
+use nearcore::config::{Config, CONFIG_FILENAME};
+use nearcore::keys::{write_key_file, KeySource, StoredKey, PASSPHRASE_ENV};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable holding the passphrase `reencrypt` encrypts keys with.
+const NEW_PASSPHRASE_ENV: &str = "NEAR_KEY_NEW_PASSPHRASE";
+
+#[derive(Parser)]
+pub(super) struct KeysCommand {
+    #[clap(subcommand)]
+    subcmd: KeysSubCommand,
+}
+
+#[derive(Parser)]
+enum KeysSubCommand {
+    /// Generates a new key and writes it to a key file.
+    Generate(GenerateKeyCmd),
+    /// Replaces a key with a newly generated one for the same account,
+    /// keeping the old key file as a backup.
+    Rotate(RotateKeyCmd),
+    /// Prints the account ID and the public key of a key and checks that it
+    /// can be loaded.
+    Inspect(InspectKeyCmd),
+    /// Encrypts a key file with the passphrase from `NEAR_KEY_NEW_PASSPHRASE`,
+    /// or decrypts it with `--decrypt`.  Encrypted key files are read with
+    /// the passphrase from `NEAR_KEY_PASSPHRASE`.
+    Reencrypt(ReencryptKeyCmd),
+}
+
+impl KeysCommand {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        match self.subcmd {
+            KeysSubCommand::Generate(cmd) => cmd.run(home_dir),
+            KeysSubCommand::Rotate(cmd) => cmd.run(home_dir),
+            KeysSubCommand::Inspect(cmd) => cmd.run(home_dir),
+            KeysSubCommand::Reencrypt(cmd) => cmd.run(home_dir),
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyKind {
+    Node,
+    Validator,
+    Signer,
+}
+
+#[derive(Args)]
+struct KeySelector {
+    /// Kind of the key.
+    #[clap(arg_enum)]
+    kind: KeyKind,
+    /// Key file, or for `inspect` also an `env:` or `exec:` key URI.  Defaults
+    /// to `node_key_file` or `validator_key_file` from config.json, and is
+    /// required for signer keys.
+    #[clap(long)]
+    file: Option<String>,
+}
+
+impl KeySelector {
+    fn source(&self, home_dir: &Path) -> anyhow::Result<KeySource> {
+        match (&self.file, self.kind) {
+            (Some(file), _) => KeySource::parse(Path::new(""), file),
+            (None, KeyKind::Signer) => bail!("--file is required for signer keys"),
+            (None, kind) => {
+                let config = Config::from_file(&home_dir.join(CONFIG_FILENAME))?;
+                let file = if kind == KeyKind::Node {
+                    config.node_key_file
+                } else {
+                    config.validator_key_file
+                };
+                KeySource::parse(home_dir, &file)
+            }
+        }
+    }
+
+    /// Returns the path of the key file, failing for keys which are not stored
+    /// in files.
+    fn path(&self, home_dir: &Path) -> anyhow::Result<PathBuf> {
+        let source = self.source(home_dir)?;
+        source.path().map(Path::to_path_buf).ok_or_else(|| {
+            anyhow!("Keys can only be written to files, not to {}; use --file", source)
+        })
+    }
+
+    /// Reads the key, failing if it doesn’t exist.
+    fn read(&self, home_dir: &Path) -> anyhow::Result<(KeySource, StoredKey)> {
+        let source = self.source(home_dir)?;
+        let key = StoredKey::read(&source)?
+            .ok_or_else(|| anyhow!("Key file {} doesn’t exist", source))?;
+        Ok((source, key))
+    }
+}
+
+/// Returns the passphrase from the environment variable, failing if it's not
+/// set.
+fn passphrase_from_env(name: &str) -> anyhow::Result<String> {
+    match std::env::var(name) {
+        Ok(passphrase) if !passphrase.is_empty() => Ok(passphrase),
+        _ => bail!("Set the passphrase in the {} environment variable", name),
+    }
+}
+
+/// Decrypts the key with the passphrase from `NEAR_KEY_PASSPHRASE` if needed.
+fn decrypt(source: &KeySource, key: StoredKey) -> anyhow::Result<KeyFile> {
+    let passphrase =
+        if key.is_encrypted() { Some(passphrase_from_env(PASSPHRASE_ENV)?) } else { None };
+    key.decrypt(passphrase.as_deref()).with_context(|| format!("Failed decrypting {}", source))
+}
+
+#[derive(Args)]
+struct GenerateKeyCmd {
+    #[clap(flatten)]
+    key: KeySelector,
+    /// Account ID of the key.  Defaults to `node` for node keys.
+    #[clap(long)]
+    account_id: Option<AccountId>,
+    /// Encrypt the secret key with the passphrase from `NEAR_KEY_PASSPHRASE`.
+    #[clap(long)]
+    encrypt: bool,
+    /// Overwrite the key file if it exists.
+    #[clap(long)]
+    force: bool,
+}
+
+impl GenerateKeyCmd {
+    fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let path = self.key.path(home_dir)?;
+        if path.exists() && !self.force {
+            bail!("{} already exists; use --force to overwrite it", path.display());
+        }
+        let account_id = match (self.account_id, self.key.kind) {
+            (Some(account_id), _) => account_id,
+            (None, KeyKind::Node) => "node".parse().unwrap(),
+            (None, _) => bail!("--account-id is required for {:?} keys", self.key.kind),
+        };
+        let passphrase =
+            if self.encrypt { Some(passphrase_from_env(PASSPHRASE_ENV)?) } else { None };
+        let secret_key = SecretKey::from_random(KeyType::ED25519);
+        let key = KeyFile { account_id, public_key: secret_key.public_key(), secret_key };
+        write_key_file(&path, &key, passphrase.as_deref())?;
+        println!("Generated key {} for {} in {}", key.public_key, key.account_id, path.display());
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct RotateKeyCmd {
+    #[clap(flatten)]
+    key: KeySelector,
+}
+
+impl RotateKeyCmd {
+    fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let path = self.key.path(home_dir)?;
+        let (source, stored) = self.key.read(home_dir)?;
+        let encrypted = stored.is_encrypted();
+        let old_key = decrypt(&source, stored)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let backup_path = PathBuf::from(format!("{}.{}.bak", path.display(), timestamp));
+        std::fs::copy(&path, &backup_path)
+            .with_context(|| format!("Failed backing up {}", path.display()))?;
+
+        // The new key is encrypted with the same passphrase as the old one.
+        let passphrase = if encrypted { Some(passphrase_from_env(PASSPHRASE_ENV)?) } else { None };
+        let secret_key = SecretKey::from_random(KeyType::ED25519);
+        let new_key = KeyFile {
+            account_id: old_key.account_id.clone(),
+            public_key: secret_key.public_key(),
+            secret_key,
+        };
+        write_key_file(&path, &new_key, passphrase.as_deref())?;
+        println!(
+            "Rotated key of {} from {} to {}; the old key file is in {}",
+            new_key.account_id,
+            old_key.public_key,
+            new_key.public_key,
+            backup_path.display()
+        );
+        if self.key.kind == KeyKind::Validator {
+            println!(
+                "Stake with the new key and restart the node once it becomes the validator key \
+                 of the account"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct InspectKeyCmd {
+    #[clap(flatten)]
+    key: KeySelector,
+}
+
+impl InspectKeyCmd {
+    fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let (source, stored) = self.key.read(home_dir)?;
+        println!("Source: {}", source);
+        println!("Account ID: {}", stored.account_id);
+        println!("Public key: {}", stored.public_key);
+        println!("Encrypted: {}", stored.is_encrypted());
+        if stored.is_encrypted() && std::env::var(PASSPHRASE_ENV).is_err() {
+            println!("Set {} to check the secret key", PASSPHRASE_ENV);
+            return Ok(());
+        }
+        decrypt(&source, stored)?;
+        println!("Secret key matches the public key");
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct ReencryptKeyCmd {
+    #[clap(flatten)]
+    key: KeySelector,
+    /// Store the secret key in plain text.
+    #[clap(long)]
+    decrypt: bool,
+}
+
+impl ReencryptKeyCmd {
+    fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let path = self.key.path(home_dir)?;
+        let (source, stored) = self.key.read(home_dir)?;
+        let key = decrypt(&source, stored)?;
+        let passphrase =
+            if self.decrypt { None } else { Some(passphrase_from_env(NEW_PASSPHRASE_ENV)?) };
+        write_key_file(&path, &key, passphrase.as_deref())?;
+        if self.decrypt {
+            println!("Decrypted {}", path.display());
+        } else {
+            println!("Encrypted {} with the new passphrase", path.display());
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
 mod cli;
+mod database;
+mod genesis;
+mod keys;
+mod network;
+mod replay;
 
 use self::cli::NeardCmd;
 use anyhow::Context;
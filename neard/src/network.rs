@@ -0,0 +1,223 @@
+use anyhow::{bail, Context};
+use clap::Parser;
+use near_chain_configs::GenesisValidationMode;
+use near_network::raw::{ConnectError, Connection};
+use near_network::time;
+use near_network::types::{HandshakeFailureReason, PeerInfo};
+use near_network::{export_peers_from_store, import_peers_to_store, ExportedPeer};
+use near_primitives::hash::CryptoHash;
+use near_store::{Mode, NodeStorage, Temperature};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Parser)]
+pub(super) struct NetworkCommand {
+    #[clap(subcommand)]
+    subcmd: NetworkSubCommand,
+}
+
+#[derive(Parser)]
+enum NetworkSubCommand {
+    /// Exports and imports the peers the node has learned about, to move
+    /// them between machines.
+    Peers(PeersCommand),
+    /// Attempts a handshake with each of the configured boot nodes and
+    /// reports whether it is reachable, the handshake latency and whether it
+    /// is on the same chain.  Useful to debug a node which doesn’t find
+    /// peers.
+    CheckBootNodes(CheckBootNodesCmd),
+}
+
+impl NetworkCommand {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        match self.subcmd {
+            NetworkSubCommand::Peers(cmd) => cmd.run(home_dir, genesis_validation),
+            NetworkSubCommand::CheckBootNodes(cmd) => cmd.run(home_dir, genesis_validation),
+        }
+    }
+}
+
+#[derive(Parser)]
+struct PeersCommand {
+    #[clap(subcommand)]
+    subcmd: PeersSubCommand,
+}
+
+#[derive(Parser)]
+enum PeersSubCommand {
+    /// Writes the peer store as JSON.  Banned peers and peers without a
+    /// known address are skipped.  Can be used while the node is running.
+    Export(ExportPeersCmd),
+    /// Adds the peers from a file written by `export` to the peer store.
+    /// Peers which are already known are left unchanged.  The node must be
+    /// stopped.
+    Import(ImportPeersCmd),
+}
+
+impl PeersCommand {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        match self.subcmd {
+            PeersSubCommand::Export(cmd) => cmd.run(home_dir, genesis_validation),
+            PeersSubCommand::Import(cmd) => cmd.run(home_dir, genesis_validation),
+        }
+    }
+}
+
+#[derive(Parser)]
+struct ExportPeersCmd {
+    /// File to write the peers to.  Defaults to stdout.
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+impl ExportPeersCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let storage = NodeStorage::opener(home_dir, &near_config.config.store, None)
+            .open_in_mode(Mode::ReadOnly)
+            .context("Failed to open the database")?;
+        let peers = export_peers_from_store(storage.into_inner(Temperature::Hot))?;
+        let json = serde_json::to_string_pretty(&peers)?;
+        match &self.output {
+            Some(path) => {
+                std::fs::write(path, json)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                eprintln!("Exported {} peers to {}", peers.len(), path.display());
+            }
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct ImportPeersCmd {
+    /// File written by `neard network peers export`.
+    #[clap(long)]
+    input: PathBuf,
+}
+
+impl ImportPeersCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let json = std::fs::read_to_string(&self.input)
+            .with_context(|| format!("Failed to read {}", self.input.display()))?;
+        let peers: Vec<ExportedPeer> = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse {}", self.input.display()))?;
+        let total = peers.len();
+        let storage = NodeStorage::opener(home_dir, &near_config.config.store, None)
+            .open_in_mode(Mode::ReadWriteExisting)
+            .context("Failed to open the database; is the node running?")?;
+        let added = import_peers_to_store(storage.into_inner(Temperature::Hot), peers)?;
+        println!("Imported {} peers, {} were already known", added, total - added);
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct CheckBootNodesCmd {
+    /// Genesis hash to send in the handshake.  Required unless the chain is
+    /// mainnet or testnet.
+    #[clap(long)]
+    genesis_hash: Option<String>,
+    /// Boot nodes to check, in the `<node_key>@<ip_addr>` format separated by
+    /// commas.  Defaults to `network.boot_nodes` from config.json.
+    #[clap(long)]
+    boot_nodes: Option<String>,
+    /// Seconds to wait for each boot node to complete the handshake.
+    #[clap(long, default_value = "10")]
+    timeout_seconds: u32,
+}
+
+/// Outcome of a handshake attempt with a boot node.
+enum Reachability {
+    Ok { height: u64 },
+    GenesisMismatch(String),
+    Failed(String),
+}
+
+impl CheckBootNodesCmd {
+    fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let chain_id = near_config.genesis.config.chain_id.clone();
+        let genesis_hash = match &self.genesis_hash {
+            Some(hash) => CryptoHash::from_str(hash)
+                .map_err(|err| anyhow::anyhow!("Failed to parse --genesis-hash: {}", err))?,
+            None => {
+                match near_ping::cli::CHAIN_INFO.iter().find(|info| info.chain_id == chain_id) {
+                    Some(info) => info.genesis_hash,
+                    None => bail!("--genesis-hash is required for chain {}", chain_id),
+                }
+            }
+        };
+        let boot_nodes = match &self.boot_nodes {
+            Some(boot_nodes) => boot_nodes
+                .split(',')
+                .map(|s| PeerInfo::from_str(s.trim()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to parse --boot-nodes")?,
+            None => near_config.network_config.peer_store.boot_nodes.clone(),
+        };
+        if boot_nodes.is_empty() {
+            bail!("No boot nodes configured");
+        }
+        let timeout = time::Duration::seconds(self.timeout_seconds.into());
+
+        let runtime = tokio::runtime::Runtime::new()?;
+        let results = runtime.block_on(futures::future::join_all(
+            boot_nodes
+                .iter()
+                .map(|peer_info| check_boot_node(peer_info, &chain_id, genesis_hash, timeout)),
+        ));
+
+        let mut reachable = 0;
+        for (peer_info, (result, latency)) in boot_nodes.iter().zip(results) {
+            let status = match result {
+                Reachability::Ok { height } => {
+                    reachable += 1;
+                    format!("OK, height {}", height)
+                }
+                Reachability::GenesisMismatch(genesis) => {
+                    format!("GENESIS MISMATCH, peer is on {}", genesis)
+                }
+                Reachability::Failed(err) => format!("UNREACHABLE: {}", err),
+            };
+            println!("{} | {:>8}ms | {}", peer_info, latency.whole_milliseconds(), status);
+        }
+        println!("{} of {} boot nodes reachable", reachable, boot_nodes.len());
+        Ok(())
+    }
+}
+
+async fn check_boot_node(
+    peer_info: &PeerInfo,
+    chain_id: &str,
+    genesis_hash: CryptoHash,
+    timeout: time::Duration,
+) -> (Reachability, time::Duration) {
+    let start = time::Instant::now();
+    let addr = match peer_info.addr {
+        Some(addr) => addr,
+        None => return (Reachability::Failed("no address".to_string()), time::Duration::ZERO),
+    };
+    let result =
+        Connection::connect(addr, peer_info.id.clone(), None, chain_id, genesis_hash, 0, timeout)
+            .await;
+    let latency = start.elapsed();
+    let reachability = match result {
+        Ok(conn) => Reachability::Ok { height: conn.peer_height() },
+        Err(ConnectError::GenesisMismatch(genesis))
+        | Err(ConnectError::HandshakeFailure(HandshakeFailureReason::GenesisMismatch(genesis))) => {
+            Reachability::GenesisMismatch(format!("{}/{}", genesis.chain_id, genesis.hash))
+        }
+        Err(err) => Reachability::Failed(err.to_string()),
+    };
+    (reachability, latency)
+}
@@ -0,0 +1,65 @@
+use anyhow::Context;
+use clap::Parser;
+use near_chain_configs::GenesisValidationMode;
+use near_client::message_log::MessageReplay;
+use near_store::{Mode, NodeStorage, StoreConfig};
+use nearcore::NightshadeRuntime;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Parser)]
+pub(super) struct ReplayCommand {
+    /// Message log to replay, one of the files created in the directory
+    /// configured as `message_log` in config.json.
+    #[clap(long)]
+    log: PathBuf,
+    /// Directory to create the database of the replaying client in, so that
+    /// it can be inspected afterwards.  Must not exist.  By default the
+    /// database is created in a temporary directory which is deleted once
+    /// the replay finishes.
+    #[clap(long)]
+    db_path: Option<PathBuf>,
+}
+
+impl ReplayCommand {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation)
+            .context("Failed to load config")?;
+        let tmp_dir;
+        let db_path = match &self.db_path {
+            Some(db_path) => std::env::current_dir()?.join(db_path),
+            None => {
+                tmp_dir = tempfile::Builder::new()
+                    .prefix("replay")
+                    .tempdir()
+                    .context("Failed to create a temporary directory for the database")?;
+                tmp_dir.path().join("data")
+            }
+        };
+        let config =
+            StoreConfig { path: Some(db_path.clone()), ..near_config.config.store.clone() };
+        let store = NodeStorage::opener(home_dir, &config, None)
+            .open_in_mode(Mode::Create)
+            .with_context(|| format!("Failed to create a database in {}", db_path.display()))?
+            .get_hot_store();
+        let runtime = NightshadeRuntime::from_config(home_dir, store, &near_config);
+        let mut replay = MessageReplay::new(
+            near_config.client_config.clone(),
+            &near_config.genesis,
+            Arc::new(runtime),
+        )?;
+        let count = replay
+            .replay_log(&self.log)
+            .with_context(|| format!("Failed to replay {}", self.log.display()))?;
+        let head = replay.client().chain.head()?;
+        println!(
+            "Replayed {} messages, head is at height {} with hash {}",
+            count, head.height, head.last_block_hash
+        );
+        Ok(())
+    }
+}
@@ -28,6 +28,24 @@ from proxy import NodesProxy
 
 os.environ["ADVERSARY_CONSENT"] = "1"
 
+# JSON RPC methods which neard serves only on `rpc.debug_addr`, on top of the
+# `adv_*` ones.
+ADMIN_METHODS = frozenset([
+    'client_config',
+    'network_info',
+    'EXPERIMENTAL_db_snapshot',
+    'EXPERIMENTAL_maintenance_windows',
+    'EXPERIMENTAL_protocol_version_voting',
+    'EXPERIMENTAL_split_storage_info',
+    'EXPERIMENTAL_validator_projection',
+    'EXPERIMENTAL_validator_schedule',
+])
+
+
+def is_admin_method(method):
+    return method in ADMIN_METHODS or method.startswith('adv_')
+
+
 remote_nodes = []
 remote_nodes_lock = threading.Lock()
 cleanup_remote_nodes_atexit_registered = False
@@ -204,6 +222,9 @@ class BaseNode(object):
     def wait_for_rpc(self, timeout=1):
         nretry(lambda: self.get_status(), timeout=timeout)
 
+    def debug_rpc_addr(self):
+        return self.rpc_addr()
+
     def json_rpc(self, method, params, timeout=2):
         j = {
             'method': method,
@@ -211,7 +232,9 @@ class BaseNode(object):
             'id': 'dontcare',
             'jsonrpc': '2.0'
         }
-        r = requests.post("http://%s:%s" % self.rpc_addr(),
+        addr = self.debug_rpc_addr() if is_admin_method(
+            method) else self.rpc_addr()
+        r = requests.post("http://%s:%s" % addr,
                           json=j,
                           timeout=timeout)
         r.raise_for_status()
@@ -383,6 +406,7 @@ class LocalNode(BaseNode):
         super(LocalNode, self).__init__()
         self.port = port
         self.rpc_port = rpc_port
+        self.debug_rpc_port = rpc_port + 1000
         self.near_root = str(near_root)
         self.node_dir = node_dir
         self.binary_name = binary_name or 'neard'
@@ -402,6 +426,7 @@ class LocalNode(BaseNode):
             },
             'rpc': {
                 'addr': f'0.0.0.0:{rpc_port}',
+                'debug_addr': f'127.0.0.1:{self.debug_rpc_port}',
             },
             'consensus': {
                 'min_num_peers': int(not single_node)
@@ -419,6 +444,9 @@ class LocalNode(BaseNode):
     def rpc_addr(self):
         return ("127.0.0.1", self.rpc_port)
 
+    def debug_rpc_addr(self):
+        return ("127.0.0.1", self.debug_rpc_port)
+
     def start_proxy_if_needed(self):
         if self._start_proxy is not None:
             self._proxy_local_stopped = self._start_proxy()
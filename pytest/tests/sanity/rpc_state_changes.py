@@ -567,6 +567,16 @@ def test_key_value_changes():
             "account_ids": [contract_key.account_id],
             "key_prefix_base64": base64.b64encode(key[:3]).decode('ascii'),
         },
+        # Test filtering the changes of the account by kind
+        {
+            **base_request,
+            "changes_type": "filtered_changes",
+            "account_ids": [
+                contract_key.account_id + '_non_existing1',
+                contract_key.account_id,
+            ],
+            "kinds": ["data"],
+        },
     ]:
         assert_changes_response(
             request=request,
@@ -574,6 +584,21 @@ def test_key_value_changes():
             exclude_paths={"root['changes'][0]['cause']['receipt_hash']"},
         )
 
+    # Test that a key prefix of the filtered changes applies to data only
+    assert_changes_response(
+        request={
+            **base_request,
+            "changes_type": "filtered_changes",
+            "account_ids": [contract_key.account_id],
+            "kinds": ["data", "contract_code"],
+            "key_prefix_base64":
+                base64.b64encode(struct.pack('<Q', 24)).decode('ascii'),
+        },
+        expected_response={
+            "block_hash": tx_block_hash,
+            "changes": []
+        })
+
 
 if __name__ == '__main__':
     test_changes_with_new_account_with_access_key()
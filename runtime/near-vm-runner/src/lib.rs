@@ -6,6 +6,7 @@ mod imports;
 mod instrument;
 #[cfg(all(feature = "wasmer0_vm", target_arch = "x86_64"))]
 mod memory;
+mod metrics;
 pub mod prepare;
 mod runner;
 #[cfg(test)]
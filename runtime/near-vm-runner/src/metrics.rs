@@ -0,0 +1,30 @@
+use near_o11y::metrics::{
+    exponential_buckets, try_create_histogram_vec, try_create_int_counter_vec, HistogramVec,
+    IntCounterVec,
+};
+use once_cell::sync::Lazy;
+
+static COMPILED_CONTRACT_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_vm_compiled_contract_cache_lookups_total",
+        "Number of lookups of compiled contracts in the in-memory and on-disk caches",
+        &["cache", "result"],
+    )
+    .unwrap()
+});
+
+pub(crate) static CONTRACT_COMPILE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_vm_contract_compile_time",
+        "Time taken to compile a contract which wasn't found in any cache",
+        &["vm_kind"],
+        Some(exponential_buckets(0.001, 2.0, 16).unwrap()),
+    )
+    .unwrap()
+});
+
+/// Records a lookup in the `"memory"` or `"disk"` cache of compiled contracts.
+pub(crate) fn record_compiled_contract_cache_lookup(cache: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    COMPILED_CONTRACT_CACHE_LOOKUPS.with_label_values(&[cache, result]).inc();
+}
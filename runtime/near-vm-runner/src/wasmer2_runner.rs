@@ -286,6 +286,8 @@ impl Wasmer2VM {
         code: &ContractCode,
     ) -> Result<UniversalExecutable, CompilationError> {
         let _span = tracing::debug_span!(target: "vm", "Wasmer2VM::compile_uncached").entered();
+        let _timer =
+            crate::metrics::CONTRACT_COMPILE_TIME.with_label_values(&["wasmer2"]).start_timer();
         let prepared_code = prepare::prepare_contract(code.code(), &self.config)
             .map_err(CompilationError::PrepareError)?;
 
@@ -352,6 +354,12 @@ impl Wasmer2VM {
                 .transpose()
                 .map_err(CacheError::ReadError)?
                 .flatten();
+            if cache.is_some() {
+                crate::metrics::record_compiled_contract_cache_lookup(
+                    "disk",
+                    cache_record.is_some(),
+                );
+            }
 
             let stored_artifact: Option<VMArtifact> = match cache_record {
                 None => None,
@@ -408,7 +416,13 @@ impl Wasmer2VM {
             > = once_cell::sync::Lazy::new(|| {
                 near_cache::SyncLruCache::new(crate::cache::CACHE_SIZE)
             });
-            MEM_CACHE.get_or_try_put(key, |_key| compile_or_read_from_cache())
+            let mut hit = true;
+            let result = MEM_CACHE.get_or_try_put(key, |_key| {
+                hit = false;
+                compile_or_read_from_cache()
+            });
+            crate::metrics::record_compiled_contract_cache_lookup("memory", hit);
+            result
         };
     }
 
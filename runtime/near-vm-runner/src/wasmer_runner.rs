@@ -246,6 +246,8 @@ impl Wasmer0VM {
         code: &ContractCode,
     ) -> Result<wasmer_runtime::Module, CompilationError> {
         let _span = tracing::debug_span!(target: "vm", "Wasmer0VM::compile_uncached").entered();
+        let _timer =
+            crate::metrics::CONTRACT_COMPILE_TIME.with_label_values(&["wasmer0"]).start_timer();
         let prepared_code = prepare::prepare_contract(code.code(), &self.config)
             .map_err(CompilationError::PrepareError)?;
         wasmer_runtime::compile(&prepared_code).map_err(|err| match err {
@@ -305,6 +307,12 @@ impl Wasmer0VM {
                     .transpose()
                     .map_err(CacheError::ReadError)?
                     .flatten();
+                if cache.is_some() {
+                    crate::metrics::record_compiled_contract_cache_lookup(
+                        "disk",
+                        cache_record.is_some(),
+                    );
+                }
 
                 let stored_module: Option<wasmer_runtime::Module> = match cache_record {
                     None => None,
@@ -351,7 +359,13 @@ impl Wasmer0VM {
             > = once_cell::sync::Lazy::new(|| {
                 near_cache::SyncLruCache::new(crate::cache::CACHE_SIZE)
             });
-            MEM_CACHE.get_or_try_put(key, |_key| compile_or_read_from_cache())
+            let mut hit = true;
+            let result = MEM_CACHE.get_or_try_put(key, |_key| {
+                hit = false;
+                compile_or_read_from_cache()
+            });
+            crate::metrics::record_compiled_contract_cache_lookup("memory", hit);
+            result
         };
     }
 }
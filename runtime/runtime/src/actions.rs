@@ -143,6 +143,8 @@ pub(crate) fn execute_function_call(
             match err {
                 ExternalError::StorageError(err) => err.into(),
                 ExternalError::ValidatorError(err) => RuntimeError::ValidatorError(err),
+                // View calls check the deadline themselves to report the error.
+                ExternalError::DeadlineExceeded => StorageError::StorageInternalError.into(),
             }
         }
         VMRunnerError::InconsistentStateError(err @ InconsistentStateError::IntegerOverflow) => {
@@ -8,6 +8,7 @@ use near_primitives::types::{
 };
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::ViewStateResult;
+use std::time::Instant;
 
 /// Adapter for querying runtime.
 pub trait ViewRuntimeAdapter {
@@ -41,6 +42,7 @@ pub trait ViewRuntimeAdapter {
         logs: &mut Vec<String>,
         epoch_info_provider: &dyn EpochInfoProvider,
         current_protocol_version: ProtocolVersion,
+        deadline: Option<Instant>,
     ) -> Result<Vec<u8>, crate::state_viewer::errors::CallFunctionError>;
 
     fn view_access_key(
@@ -56,7 +58,12 @@ pub trait ViewRuntimeAdapter {
         shard_uid: &ShardUId,
         state_root: MerkleHash,
         account_id: &AccountId,
-    ) -> Result<Vec<(PublicKey, AccessKey)>, crate::state_viewer::errors::ViewAccessKeyError>;
+        after_public_key: Option<&PublicKey>,
+        limit: Option<usize>,
+    ) -> Result<
+        (Vec<(PublicKey, AccessKey)>, Option<PublicKey>),
+        crate::state_viewer::errors::ViewAccessKeyError,
+    >;
 
     fn view_state(
         &self,
@@ -64,6 +71,9 @@ pub trait ViewRuntimeAdapter {
         state_root: MerkleHash,
         account_id: &AccountId,
         prefix: &[u8],
+        after_key: Option<&[u8]>,
+        limit: Option<usize>,
         include_proof: bool,
+        deadline: Option<Instant>,
     ) -> Result<ViewStateResult, crate::state_viewer::errors::ViewStateError>;
 }
@@ -10,6 +10,7 @@ use near_primitives::version::ProtocolVersion;
 use near_store::{get_code, KeyLookupMode, TrieUpdate, TrieUpdateValuePtr};
 use near_vm_errors::{AnyError, VMLogicError};
 use near_vm_logic::{External, StorageGetMode, ValuePtr};
+use std::time::Instant;
 
 pub struct RuntimeExt<'a> {
     trie_update: &'a mut TrieUpdate,
@@ -21,6 +22,7 @@ pub struct RuntimeExt<'a> {
     last_block_hash: &'a CryptoHash,
     epoch_info_provider: &'a dyn EpochInfoProvider,
     current_protocol_version: ProtocolVersion,
+    deadline: Option<Instant>,
 }
 
 /// Error used by `RuntimeExt`.
@@ -31,6 +33,9 @@ pub(crate) enum ExternalError {
     StorageError(StorageError),
     /// Error when accessing validator information. Happens inside epoch manager.
     ValidatorError(EpochError),
+    /// The deadline set with `RuntimeExt::set_deadline` has passed.  Only
+    /// view calls have a deadline.
+    DeadlineExceeded,
 }
 
 impl From<ExternalError> for VMLogicError {
@@ -72,6 +77,22 @@ impl<'a> RuntimeExt<'a> {
             last_block_hash,
             epoch_info_provider,
             current_protocol_version,
+            deadline: None,
+        }
+    }
+
+    /// Makes storage accesses fail once the deadline has passed so that the
+    /// execution is aborted.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    fn check_deadline(&self) -> ExtResult<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(ExternalError::DeadlineExceeded.into())
+            }
+            _ => Ok(()),
         }
     }
 
@@ -116,6 +137,7 @@ impl<'a> External for RuntimeExt<'a> {
         key: &[u8],
         mode: StorageGetMode,
     ) -> ExtResult<Option<Box<dyn ValuePtr + 'b>>> {
+        self.check_deadline()?;
         let storage_key = self.create_storage_key(key);
         let mode = match mode {
             StorageGetMode::FlatStorage => KeyLookupMode::FlatStorage,
@@ -134,6 +156,7 @@ impl<'a> External for RuntimeExt<'a> {
     }
 
     fn storage_has_key(&mut self, key: &[u8]) -> ExtResult<bool> {
+        self.check_deadline()?;
         let storage_key = self.create_storage_key(key);
         self.trie_update
             .get_ref(&storage_key, KeyLookupMode::FlatStorage)
@@ -2,14 +2,16 @@ use std::cmp::max;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use config::total_prepaid_send_fees;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use near_chain_configs::Genesis;
 pub use near_crypto;
 use near_crypto::PublicKey;
 pub use near_primitives;
+use near_primitives::config::ExtCosts;
 use near_primitives::contract::ContractCode;
 use near_primitives::profile::ProfileDataV3;
 pub use near_primitives::runtime::apply_state::ApplyState;
@@ -185,11 +187,23 @@ impl Default for ActionResult {
     }
 }
 
-pub struct Runtime {}
+/// Gas costs are calibrated so that 1 Tgas takes at most 1 ms to execute.
+const GAS_PER_NANOSECOND: Gas = 1_000_000;
+
+pub struct Runtime {
+    /// If set, action receipts taking longer to execute than this multiple
+    /// of the time implied by the gas they burnt are reported as slow.
+    slow_receipt_factor: Option<f64>,
+}
 
 impl Runtime {
     pub fn new() -> Self {
-        Self {}
+        Self { slow_receipt_factor: None }
+    }
+
+    pub fn with_slow_receipt_factor(mut self, slow_receipt_factor: Option<f64>) -> Self {
+        self.slow_receipt_factor = slow_receipt_factor;
+        self
     }
 
     fn print_log(log: &[LogEntry]) {
@@ -469,6 +483,7 @@ impl Runtime {
         stats: &mut ApplyStats,
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<ExecutionOutcomeWithId, RuntimeError> {
+        let start = Instant::now();
         let action_receipt = match &receipt.receipt {
             ReceiptEnum::Action(action_receipt) => action_receipt,
             _ => unreachable!("given receipt should be an action receipt"),
@@ -704,6 +719,17 @@ impl Runtime {
             };
         }
 
+        if let Some(slow_receipt_factor) = self.slow_receipt_factor {
+            Self::check_receipt_time(
+                start.elapsed(),
+                slow_receipt_factor,
+                apply_state,
+                receipt,
+                action_receipt,
+                &result,
+            );
+        }
+
         // Generating receipt IDs
         let receipt_ids = result
             .new_receipts
@@ -760,6 +786,60 @@ impl Runtime {
         })
     }
 
+    /// Reports the receipt if executing it took longer than
+    /// `slow_receipt_factor` times the time implied by the gas it burnt, which
+    /// means that some of the operations it performed are undercharged.
+    /// Returns whether the receipt was reported.
+    fn check_receipt_time(
+        elapsed: Duration,
+        slow_receipt_factor: f64,
+        apply_state: &ApplyState,
+        receipt: &Receipt,
+        action_receipt: &ActionReceipt,
+        result: &ActionResult,
+    ) -> bool {
+        // Refunds are free, so they have no time budget.
+        if result.gas_burnt == 0 {
+            return false;
+        }
+        let budget_ms =
+            result.gas_burnt as f64 / GAS_PER_NANOSECOND as f64 / 1e6 * slow_receipt_factor;
+        let elapsed_ms = elapsed.as_secs_f64() * 1e3;
+        if elapsed_ms <= budget_ms {
+            return false;
+        }
+        metrics::SLOW_RECEIPTS_TOTAL.inc();
+        let methods: Vec<&str> = action_receipt
+            .actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::FunctionCall(function_call) => Some(function_call.method_name.as_str()),
+                _ => None,
+            })
+            .collect();
+        // The number of storage operations is derived from the gas charged for
+        // their base costs.
+        let ext_costs = &apply_state.config.wasm_config.ext_costs;
+        let count = |ext: ExtCosts| {
+            result.profile.get_ext_cost(ext).checked_div(ext_costs.cost(ext)).unwrap_or_default()
+        };
+        warn!(
+            target: "runtime",
+            receipt_id = %receipt.receipt_id,
+            receiver_id = %receipt.receiver_id,
+            ?methods,
+            gas_burnt = result.gas_burnt,
+            elapsed_ms,
+            budget_ms,
+            storage_reads = count(ExtCosts::storage_read_base),
+            storage_writes = count(ExtCosts::storage_write_base),
+            storage_removes = count(ExtCosts::storage_remove_base),
+            storage_has_keys = count(ExtCosts::storage_has_key_base),
+            "Receipt took longer to execute than its gas implies"
+        );
+        true
+    }
+
     fn generate_refund_receipts(
         &self,
         current_gas_price: Balance,
@@ -1461,7 +1541,8 @@ impl Runtime {
         if state_patch.is_empty() {
             return;
         }
-        for record in state_patch {
+        let (records, balances) = state_patch.into_parts();
+        for record in records {
             match record {
                 StateRecord::Account { account_id, account } => {
                     set_account(state_update, account_id, &account);
@@ -1470,11 +1551,23 @@ impl Runtime {
                     state_update.set(TrieKey::ContractData { key: data_key, account_id }, value);
                 }
                 StateRecord::Contract { account_id, code } => {
-                    let acc = get_account(state_update, &account_id).expect("Failed to read state").expect("Code state record should be preceded by the corresponding account record");
-                    // Recompute contract code hash.
+                    let mut account = match get_account(state_update, &account_id)
+                        .expect("Failed to read state")
+                    {
+                        Some(account) => account,
+                        None => {
+                            warn!(target: "runtime", %account_id, "patch_state: skipping the contract of a non-existent account");
+                            continue;
+                        }
+                    };
+                    // Recompute contract code hash, so that the code can be
+                    // patched without patching the account as well.
                     let code = ContractCode::new(code, None);
+                    if account.code_hash() != *code.hash() {
+                        account.set_code_hash(*code.hash());
+                        set_account(state_update, account_id.clone(), &account);
+                    }
                     set_code(state_update, account_id, &code);
-                    assert_eq!(*code.hash(), acc.code_hash());
                 }
                 StateRecord::AccessKey { account_id, public_key, access_key } => {
                     set_access_key(state_update, account_id, public_key, &access_key);
@@ -1482,6 +1575,25 @@ impl Runtime {
                 _ => unimplemented!("patch_state can only patch Account, AccessKey, Contract and Data kind of StateRecord")
             }
         }
+        for balance in balances {
+            let account_id = balance.account_id;
+            let mut account = match get_account(state_update, &account_id)
+                .expect("Failed to read state")
+            {
+                Some(account) => account,
+                None => {
+                    warn!(target: "runtime", %account_id, "patch_state: skipping the balance of a non-existent account");
+                    continue;
+                }
+            };
+            if let Some(amount) = balance.amount {
+                account.set_amount(amount);
+            }
+            if let Some(locked) = balance.locked {
+                account.set_locked(locked);
+            }
+            set_account(state_update, account_id, &account);
+        }
         state_update.commit(StateChangeCause::Migration);
     }
 
@@ -1678,6 +1790,37 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_check_receipt_time() {
+        let (_, _, _, apply_state, _, _) = setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let receipt = generate_receipts(1, 1).pop().unwrap();
+        let action_receipt = match &receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => action_receipt,
+            _ => unreachable!(),
+        };
+        let check = |elapsed_us: u64, slow_receipt_factor: f64, gas_burnt: Gas| {
+            let result = ActionResult { gas_burnt, ..ActionResult::default() };
+            Runtime::check_receipt_time(
+                Duration::from_micros(elapsed_us),
+                slow_receipt_factor,
+                &apply_state,
+                &receipt,
+                action_receipt,
+                &result,
+            )
+        };
+        // 1 Tgas has a budget of 1 ms.
+        assert!(!check(999, 1.0, 10u64.pow(12)));
+        assert!(check(1001, 1.0, 10u64.pow(12)));
+        assert!(!check(1999, 2.0, 10u64.pow(12)));
+        assert!(check(2001, 2.0, 10u64.pow(12)));
+        // 300 Tgas, the most a receipt can burn, has a budget of 300 ms.
+        assert!(!check(299_000, 1.0, 300 * 10u64.pow(12)));
+        assert!(check(301_000, 1.0, 300 * 10u64.pow(12)));
+        // Refunds are never reported.
+        assert!(!check(1_000_000, 1.0, 0));
+    }
+
     #[test]
     fn test_apply_check_balance_validation_rewards() {
         let initial_locked = to_yocto(500_000);
@@ -2504,7 +2647,7 @@ pub mod estimator {
         stats: &mut ApplyStats,
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<ExecutionOutcomeWithId, RuntimeError> {
-        Runtime {}.apply_action_receipt(
+        Runtime::new().apply_action_receipt(
             state_update,
             apply_state,
             receipt,
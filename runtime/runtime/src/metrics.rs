@@ -33,6 +33,13 @@ pub static TRANSACTION_PROCESSED_FAILED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static SLOW_RECEIPTS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_slow_receipts_total",
+        "The number of action receipts which took longer to execute than the configured multiple of the time implied by their burnt gas",
+    )
+    .unwrap()
+});
 pub static PREFETCH_ENQUEUED: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
         "near_prefetch_enqueued",
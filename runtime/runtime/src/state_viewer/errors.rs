@@ -38,6 +38,8 @@ pub enum ViewStateError {
     AccountDoesNotExist { requested_account_id: near_primitives::types::AccountId },
     #[error("The state of {requested_account_id} is too large")]
     AccountStateTooLarge { requested_account_id: near_primitives::types::AccountId },
+    #[error("The deadline of the request passed while viewing the state")]
+    DeadlineExceeded,
     #[error("Internal error: #{error_message}")]
     InternalError { error_message: String },
 }
@@ -52,6 +54,8 @@ pub enum CallFunctionError {
     InternalError { error_message: String },
     #[error("VM error occurred: #{error_message}")]
     VMError { error_message: String },
+    #[error("The deadline of the request passed while executing the function call")]
+    DeadlineExceeded,
 }
 
 impl From<ViewAccountError> for ViewContractCodeError {
@@ -13,7 +13,7 @@ use near_primitives::{
         migration_data::{MigrationData, MigrationFlags},
     },
     transaction::FunctionCallAction,
-    trie_key::trie_key_parsers,
+    trie_key::{trie_key_parsers, TrieKey},
     types::{AccountId, EpochInfoProvider, Gas},
     views::{StateItem, ViewApplyState, ViewStateResult},
 };
@@ -24,6 +24,9 @@ use tracing::debug;
 
 pub mod errors;
 
+/// Number of values listed by a `view_state` query which doesn't set a limit.
+pub const DEFAULT_VIEW_STATE_LIMIT: usize = 1000;
+
 pub struct TrieViewer {
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     state_size_limit: Option<u64>,
@@ -83,50 +86,94 @@ impl TrieViewer {
         })
     }
 
+    /// Returns the access keys of the account ordered by their raw trie keys,
+    /// starting after `after_public_key` and at most `limit` of them, and the
+    /// key to continue from if there are more.  The iteration seeks right to
+    /// `after_public_key`, the keys before it aren't visited.
     pub fn view_access_keys(
         &self,
         state_update: &TrieUpdate,
         account_id: &AccountId,
-    ) -> Result<Vec<(PublicKey, AccessKey)>, errors::ViewAccessKeyError> {
+        after_public_key: Option<&PublicKey>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<(PublicKey, AccessKey)>, Option<PublicKey>), errors::ViewAccessKeyError> {
         let prefix = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
-        let raw_prefix: &[u8] = prefix.as_ref();
-        let access_keys =
-            state_update
-                .iter(&prefix)?
-                .map(|key| {
-                    let key = key?;
-                    let public_key = &key[raw_prefix.len()..];
-                    let access_key = near_store::get_access_key_raw(state_update, &key)?
-                        .ok_or_else(|| errors::ViewAccessKeyError::InternalError {
-                            error_message: "Unexpected missing key from iterator".to_string(),
-                        })?;
-                    PublicKey::try_from_slice(public_key)
-                        .map_err(|_| errors::ViewAccessKeyError::InternalError {
-                            error_message: format!(
-                                "Unexpected invalid public key {:?} received from store",
-                                public_key
-                            ),
-                        })
-                        .map(|key| (key, access_key))
-                })
-                .collect::<Result<Vec<_>, errors::ViewAccessKeyError>>();
-        access_keys
+        let after = after_public_key.map(|public_key| {
+            TrieKey::AccessKey { account_id: account_id.clone(), public_key: public_key.clone() }
+                .to_vec()
+        });
+        let mut iter = state_update.trie().iter()?;
+        match &after {
+            Some(after) => iter.seek(after)?,
+            None => iter.seek_prefix(&prefix)?,
+        }
+        let limit = limit.unwrap_or(DEFAULT_VIEW_STATE_LIMIT);
+        let mut access_keys: Vec<(PublicKey, AccessKey)> = vec![];
+        let mut next_public_key = None;
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            if after.as_ref() == Some(&key) {
+                continue;
+            }
+            if access_keys.len() == limit {
+                next_public_key = access_keys.last().map(|(key, _)| key.clone());
+                break;
+            }
+            let public_key = &key[prefix.len()..];
+            let public_key = PublicKey::try_from_slice(public_key).map_err(|_| {
+                errors::ViewAccessKeyError::InternalError {
+                    error_message: format!(
+                        "Unexpected invalid public key {:?} received from store",
+                        public_key
+                    ),
+                }
+            })?;
+            let access_key = AccessKey::try_from_slice(&value).map_err(|_| {
+                errors::ViewAccessKeyError::InternalError {
+                    error_message: format!("Unexpected invalid access key {}", public_key),
+                }
+            })?;
+            access_keys.push((public_key, access_key));
+        }
+        Ok((access_keys, next_public_key))
     }
 
+    /// Returns the contract data of the account with keys starting with
+    /// `prefix`, starting after `after_key` and at most `limit` of them, and
+    /// the key to continue from if there are more.
+    ///
+    /// Limiting the number of items bounds both the values and the proof
+    /// nodes collected, so huge states can be served in pages rather than in
+    /// a single long-running query.  The proof of each page covers the path to
+    /// `after_key` so every page can be verified on its own.
+    ///
+    /// Queries without a limit are served at most `DEFAULT_VIEW_STATE_LIMIT`
+    /// values and fail for accounts whose state exceeds `state_size_limit`,
+    /// while queries which set a limit are bounded by it and can list the
+    /// state of any account.
     pub fn view_state(
         &self,
         state_update: &TrieUpdate,
         account_id: &AccountId,
         prefix: &[u8],
+        after_key: Option<&[u8]>,
+        limit: Option<usize>,
         include_proof: bool,
+        deadline: Option<Instant>,
     ) -> Result<ViewStateResult, errors::ViewStateError> {
+        if deadline_exceeded(deadline) {
+            return Err(errors::ViewStateError::DeadlineExceeded);
+        }
         match get_account(state_update, account_id)? {
             Some(account) => {
-                let code_len = get_code(state_update, account_id, Some(account.code_hash()))?
-                    .map(|c| c.code().len() as u64)
-                    .unwrap_or_default();
-                if let Some(limit) = self.state_size_limit {
-                    if account.storage_usage().saturating_sub(code_len) > limit {
+                if let (None, Some(state_size_limit)) = (limit, self.state_size_limit) {
+                    let code_len = get_code(state_update, account_id, Some(account.code_hash()))?
+                        .map(|c| c.code().len() as u64)
+                        .unwrap_or_default();
+                    if account.storage_usage().saturating_sub(code_len) > state_size_limit {
                         return Err(errors::ViewStateError::AccountStateTooLarge {
                             requested_account_id: account_id.clone(),
                         });
@@ -140,14 +187,35 @@ impl TrieViewer {
             }
         };
 
-        let mut values = vec![];
+        let mut values: Vec<StateItem> = vec![];
         let query = trie_key_parsers::get_raw_prefix_for_contract_data(account_id, prefix);
         let acc_sep_len = query.len() - prefix.len();
         let mut iter = state_update.trie().iter()?;
         iter.remember_visited_nodes(include_proof);
-        iter.seek_prefix(&query)?;
+        let after = after_key.map(|after_key| {
+            trie_key_parsers::get_raw_prefix_for_contract_data(account_id, after_key)
+        });
+        match &after {
+            Some(after) if after > &query => iter.seek(after)?,
+            _ => iter.seek_prefix(&query)?,
+        }
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut next_key = None;
         for item in &mut iter {
+            if deadline_exceeded(deadline) {
+                return Err(errors::ViewStateError::DeadlineExceeded);
+            }
             let (key, value) = item?;
+            if !key.starts_with(&query) {
+                break;
+            }
+            if after.as_ref() == Some(&key) {
+                continue;
+            }
+            if values.len() == limit {
+                next_key = values.last().map(|item| item.key.clone());
+                break;
+            }
             values.push(StateItem {
                 key: key[acc_sep_len..].to_vec(),
                 value: value,
@@ -155,7 +223,7 @@ impl TrieViewer {
             });
         }
         let proof = iter.into_visited_nodes();
-        Ok(ViewStateResult { values, proof })
+        Ok(ViewStateResult { values, proof, next_key })
     }
 
     pub fn call_function(
@@ -169,6 +237,10 @@ impl TrieViewer {
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<Vec<u8>, errors::CallFunctionError> {
         let now = Instant::now();
+        let deadline = view_state.deadline;
+        if deadline_exceeded(deadline) {
+            return Err(errors::CallFunctionError::DeadlineExceeded);
+        }
         let root = state_update.get_root().clone();
         let mut account = get_account(&state_update, contract_id)?.ok_or_else(|| {
             errors::CallFunctionError::AccountDoesNotExist {
@@ -189,6 +261,7 @@ impl TrieViewer {
             epoch_info_provider,
             view_state.current_protocol_version,
         );
+        runtime_ext.set_deadline(deadline);
         let config_store = RuntimeConfigStore::new(None);
         let config = config_store.get_config(PROTOCOL_VERSION);
         let apply_state = ApplyState {
@@ -236,7 +309,13 @@ impl TrieViewer {
             true,
             Some(ViewConfig { max_gas_burnt: self.max_gas_burnt_view }),
         )
-        .map_err(|e| errors::CallFunctionError::InternalError { error_message: e.to_string() })?;
+        .map_err(|e| {
+            if deadline_exceeded(deadline) {
+                errors::CallFunctionError::DeadlineExceeded
+            } else {
+                errors::CallFunctionError::InternalError { error_message: e.to_string() }
+            }
+        })?;
         let elapsed = now.elapsed();
         let time_ms =
             (elapsed.as_secs() as f64 / 1_000.0) + f64::from(elapsed.subsec_nanos()) / 1_000_000.0;
@@ -258,3 +337,7 @@ impl TrieViewer {
         }
     }
 }
+
+fn deadline_exceeded(deadline: Option<Instant>) -> bool {
+    deadline.map_or(false, |deadline| Instant::now() >= deadline)
+}
@@ -28,8 +28,9 @@ pub struct AmendGenesisCommand {
     ///   "public_key": <PUBLIC_KEY>,
     ///   "amount": <STAKE>,
     /// }
+    /// Must be given unless the validators are given in --patch
     #[clap(long)]
-    validators: PathBuf,
+    validators: Option<PathBuf>,
     /// path to extra records to add to the output state. Right now only Accounts and AccessKey
     /// records are supported, and any added accounts must have zero `code_hash`
     #[clap(long)]
@@ -69,29 +70,40 @@ pub struct AmendGenesisCommand {
     /// on accounts in the output state
     #[clap(long)]
     num_extra_bytes_record: Option<u64>,
+    /// path to a JSON file with the validators, extra records and genesis config changes to
+    /// apply, see `GenesisPatch`. The other options take precedence over the genesis config
+    /// changes given there
+    #[clap(long)]
+    patch: Option<PathBuf>,
 }
 
 impl AmendGenesisCommand {
     pub fn run(self) -> anyhow::Result<()> {
-        let genesis_changes = crate::GenesisChanges {
-            chain_id: self.chain_id,
-            protocol_version: self.protocol_version,
-            num_seats: self.num_seats,
-            epoch_length: self.epoch_length,
-            transaction_validity_period: self.transaction_validity_period,
-            protocol_reward_rate: self.protocol_reward_rate,
-            block_producer_kickout_threshold: self.block_producer_kickout_threshold,
-            chunk_producer_kickout_threshold: self.chunk_producer_kickout_threshold,
+        let mut patch = match &self.patch {
+            Some(path) => crate::GenesisPatch::from_file(path)?,
+            None => crate::GenesisPatch::default(),
         };
+        let changes = &mut patch.genesis;
+        changes.chain_id = self.chain_id.or(changes.chain_id.take());
+        changes.protocol_version = self.protocol_version.or(changes.protocol_version);
+        changes.num_seats = self.num_seats.or(changes.num_seats);
+        changes.epoch_length = self.epoch_length.or(changes.epoch_length);
+        changes.transaction_validity_period =
+            self.transaction_validity_period.or(changes.transaction_validity_period);
+        changes.protocol_reward_rate = self.protocol_reward_rate.or(changes.protocol_reward_rate);
+        changes.block_producer_kickout_threshold =
+            self.block_producer_kickout_threshold.or(changes.block_producer_kickout_threshold);
+        changes.chunk_producer_kickout_threshold =
+            self.chunk_producer_kickout_threshold.or(changes.chunk_producer_kickout_threshold);
         crate::amend_genesis(
             &self.genesis_file_in,
             &self.genesis_file_out,
             &self.records_file_in,
             &self.records_file_out,
             self.extra_records.as_deref(),
-            &self.validators,
+            self.validators.as_deref(),
             self.shard_layout_file.as_deref(),
-            &genesis_changes,
+            &patch,
             self.num_bytes_account.unwrap_or(100),
             self.num_extra_bytes_record.unwrap_or(40),
         )
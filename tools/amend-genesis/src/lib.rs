@@ -12,6 +12,7 @@ use near_primitives_core::account::{AccessKey, Account};
 use near_primitives_core::types::{Balance, BlockHeightDelta, NumBlocks, NumSeats};
 use num_rational::Rational32;
 use serde::ser::{SerializeSeq, Serializer};
+use serde::Deserialize;
 use std::collections::{hash_map, HashMap};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
@@ -146,58 +147,69 @@ fn parse_validators(path: &Path) -> anyhow::Result<Vec<AccountInfo>> {
     Ok(validators)
 }
 
-fn parse_extra_records(
-    records_file: &Path,
+fn add_extra_record(
+    records: &mut HashMap<AccountId, AccountRecords>,
+    r: StateRecord,
     num_bytes_account: u64,
-) -> anyhow::Result<HashMap<AccountId, AccountRecords>> {
-    let reader =
-        BufReader::new(File::open(records_file).with_context(|| {
-            format!("Failed opening validators file {}", records_file.display())
-        })?);
-    let mut records = HashMap::new();
-
-    let mut result = Ok(());
-    near_chain_configs::stream_records_from_file(reader, |r| {
-        match r {
-            StateRecord::Account { account_id, account } => {
-                if account.code_hash() != CryptoHash::default() {
-                    result = Err(anyhow::anyhow!(
-                        "FIXME: accounts in --extra-records with code_hash set not supported"
-                    ));
+) -> anyhow::Result<()> {
+    match r {
+        StateRecord::Account { account_id, account } => {
+            if account.code_hash() != CryptoHash::default() {
+                anyhow::bail!(
+                    "FIXME: accounts in --extra-records with code_hash set not supported"
+                );
+            }
+            match records.entry(account_id.clone()) {
+                hash_map::Entry::Vacant(e) => {
+                    let r =
+                        AccountRecords::new(account.amount(), account.locked(), num_bytes_account);
+                    e.insert(r);
                 }
-                match records.entry(account_id.clone()) {
-                    hash_map::Entry::Vacant(e) => {
-                        let r = AccountRecords::new(
-                            account.amount(),
-                            account.locked(),
-                            num_bytes_account,
-                        );
-                        e.insert(r);
-                    }
-                    hash_map::Entry::Occupied(mut e) => {
-                        let r = e.get_mut();
-
-                        if r.account.is_some() {
-                            result = Err(anyhow::anyhow!(
-                                "account {} given twice in extra records",
-                                &account_id
-                            ));
-                        }
-                        r.set_account(account.amount(), account.locked(), num_bytes_account);
+                hash_map::Entry::Occupied(mut e) => {
+                    let r = e.get_mut();
+
+                    if r.account.is_some() {
+                        anyhow::bail!("account {} given twice in extra records", &account_id);
                     }
+                    r.set_account(account.amount(), account.locked(), num_bytes_account);
                 }
             }
-            StateRecord::AccessKey { account_id, public_key, access_key } => {
-                records.entry(account_id).or_default().keys.insert(public_key, access_key);
-            }
-            _ => {
-                result = Err(anyhow::anyhow!(
-                    "FIXME: only Account and AccessKey records are supported in --extra-records"
-                ));
+        }
+        StateRecord::AccessKey { account_id, public_key, access_key } => {
+            records.entry(account_id).or_default().keys.insert(public_key, access_key);
+        }
+        _ => {
+            anyhow::bail!(
+                "FIXME: only Account and AccessKey records are supported in --extra-records"
+            );
+        }
+    };
+    Ok(())
+}
+
+fn parse_extra_records(
+    records_file: Option<&Path>,
+    patch_records: &[StateRecord],
+    num_bytes_account: u64,
+) -> anyhow::Result<HashMap<AccountId, AccountRecords>> {
+    let mut records = HashMap::new();
+
+    if let Some(records_file) = records_file {
+        let reader = BufReader::new(File::open(records_file).with_context(|| {
+            format!("Failed opening extra records file {}", records_file.display())
+        })?);
+        let mut result = Ok(());
+        near_chain_configs::stream_records_from_file(reader, |r| {
+            if result.is_ok() {
+                result = add_extra_record(&mut records, r, num_bytes_account);
             }
-        };
-    })
-    .context("Failed deserializing records from --extra-records")?;
+        })
+        .context("Failed deserializing records from --extra-records")?;
+        result?;
+    }
+    for r in patch_records {
+        add_extra_record(&mut records, r.clone(), num_bytes_account)?;
+    }
 
     Ok(records)
 }
@@ -205,27 +217,26 @@ fn parse_extra_records(
 fn wanted_records(
     validators: &[AccountInfo],
     extra_records: Option<&Path>,
+    patch_records: &[StateRecord],
     num_bytes_account: u64,
 ) -> anyhow::Result<HashMap<AccountId, AccountRecords>> {
     let mut records = validator_records(validators, num_bytes_account)?;
 
-    if let Some(path) = extra_records {
-        let extra = parse_extra_records(path, num_bytes_account)?;
+    let extra = parse_extra_records(extra_records, patch_records, num_bytes_account)?;
 
-        for (account_id, account_records) in extra {
-            match records.entry(account_id) {
-                hash_map::Entry::Occupied(mut e) => {
-                    let validator_records = e.get_mut();
+    for (account_id, account_records) in extra {
+        match records.entry(account_id) {
+            hash_map::Entry::Occupied(mut e) => {
+                let validator_records = e.get_mut();
 
-                    if let Some(account) = &account_records.account {
-                        set_total_balance(validator_records.account.as_mut().unwrap(), account);
-                        validator_records.amount_needed = false;
-                    }
-                    validator_records.keys.extend(account_records.keys);
-                }
-                hash_map::Entry::Vacant(e) => {
-                    e.insert(account_records);
+                if let Some(account) = &account_records.account {
+                    set_total_balance(validator_records.account.as_mut().unwrap(), account);
+                    validator_records.amount_needed = false;
                 }
+                validator_records.keys.extend(account_records.keys);
+            }
+            hash_map::Entry::Vacant(e) => {
+                e.insert(account_records);
             }
         }
     }
@@ -233,7 +244,8 @@ fn wanted_records(
     Ok(records)
 }
 
-#[derive(Default)]
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GenesisChanges {
     pub chain_id: Option<String>,
     pub protocol_version: Option<ProtocolVersion>,
@@ -245,6 +257,39 @@ pub struct GenesisChanges {
     pub chunk_producer_kickout_threshold: Option<u8>,
 }
 
+/// All the changes to make to a genesis/records file in one JSON file, e.g.
+/// {
+///   "validators": [{"account_id": "node0", "public_key": "ed25519:...", "amount": "..."}],
+///   "records": [{"Account": {"account_id": "node0", "account": {...}}}],
+///   "genesis": {"chain_id": "recovery", "epoch_length": 1000}
+/// }
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisPatch {
+    /// Validators to put in the output genesis, like in the `--validators`
+    /// file.  Validators with a public key not in the input records get it
+    /// added as a full access key.
+    #[serde(default)]
+    pub validators: Option<Vec<AccountInfo>>,
+    /// Account and access key records to add to the output state, like in
+    /// the `--extra-records` file.  An account record for an existing account
+    /// sets its balance.
+    #[serde(default)]
+    pub records: Vec<StateRecord>,
+    /// Fields of the genesis config to change.
+    #[serde(default)]
+    pub genesis: GenesisChanges,
+}
+
+impl GenesisPatch {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let patch = std::fs::read_to_string(path)
+            .with_context(|| format!("failed reading from {}", path.display()))?;
+        serde_json::from_str(&patch)
+            .with_context(|| format!("failed deserializing from {}", path.display()))
+    }
+}
+
 /// Amend a genesis/records file created by `dump-state`.
 pub fn amend_genesis(
     genesis_file_in: &Path,
@@ -252,12 +297,21 @@ pub fn amend_genesis(
     records_file_in: &Path,
     records_file_out: &Path,
     extra_records: Option<&Path>,
-    validators: &Path,
+    validators: Option<&Path>,
     shard_layout_file: Option<&Path>,
-    genesis_changes: &GenesisChanges,
+    patch: &GenesisPatch,
     num_bytes_account: u64,
     num_extra_bytes_record: u64,
 ) -> anyhow::Result<()> {
+    let validators = match (validators, &patch.validators) {
+        (Some(_), Some(_)) => {
+            anyhow::bail!("validators given both with --validators and in the patch")
+        }
+        (Some(path), None) => parse_validators(path)?,
+        (None, Some(validators)) => validators.clone(),
+        (None, None) => anyhow::bail!("validators must be given with --validators or in the patch"),
+    };
+    let genesis_changes = &patch.genesis;
     let mut genesis = Genesis::from_file(genesis_file_in, GenesisValidationMode::UnsafeFast);
 
     let shard_layout = if let Some(path) = shard_layout_file {
@@ -280,8 +334,7 @@ pub fn amend_genesis(
     let mut records_ser = serde_json::Serializer::new(records_out);
     let mut records_seq = records_ser.serialize_seq(None).unwrap();
 
-    let validators = parse_validators(validators)?;
-    let mut wanted = wanted_records(&validators, extra_records, num_bytes_account)?;
+    let mut wanted = wanted_records(&validators, extra_records, &patch.records, num_bytes_account)?;
     let mut total_supply = 0;
 
     near_chain_configs::stream_records_from_file(reader, |mut r| {
@@ -365,7 +418,7 @@ pub fn amend_genesis(
 #[cfg(test)]
 mod test {
     use anyhow::Context;
-    use near_chain_configs::{get_initial_supply, Genesis, GenesisConfig};
+    use near_chain_configs::{get_initial_supply, Genesis, GenesisConfig, GenesisValidationMode};
     use near_primitives::hash::CryptoHash;
     use near_primitives::shard_layout::ShardLayout;
     use near_primitives::state_record::StateRecord;
@@ -582,7 +635,7 @@ mod test {
         // check that the resulting genesis and records files match what's in self.want_records
         // right now we aren't testing that other kinds of records appearing in the input records file
         // will make it into the output, but that part is pretty simple
-        fn run(&self) -> anyhow::Result<()> {
+        fn run(&self, use_patch: bool) -> anyhow::Result<()> {
             let ParsedTestCase {
                 genesis,
                 records_file_in,
@@ -609,20 +662,39 @@ mod test {
             serde_json::to_writer(&mut genesis_file_in, &genesis)
                 .context("failed writing to --genesis-file-in")?;
 
+            // With a patch, the validators and extra records are given in it
+            // instead of the separate files.
+            let patch = if use_patch {
+                crate::GenesisPatch {
+                    validators: Some(validators_in),
+                    records: extra_records,
+                    genesis: crate::GenesisChanges {
+                        epoch_length: Some(500),
+                        ..Default::default()
+                    },
+                }
+            } else {
+                crate::GenesisPatch::default()
+            };
             crate::amend_genesis(
                 genesis_file_in.path(),
                 genesis_file_out.path(),
                 records_file_in.path(),
                 records_file_out.path(),
-                Some(extra_records_file.path()),
-                validators_file.path(),
+                Some(extra_records_file.path()).filter(|_| !use_patch),
+                Some(validators_file.path()).filter(|_| !use_patch),
                 None,
-                &crate::GenesisChanges::default(),
+                &patch,
                 100,
                 40,
             )
             .context("amend_genesis() failed")?;
 
+            let got_genesis =
+                Genesis::from_file(genesis_file_out.path(), GenesisValidationMode::UnsafeFast);
+            let want_epoch_length = if use_patch { 500 } else { genesis.config.epoch_length };
+            assert_eq!(got_genesis.config.epoch_length, want_epoch_length);
+
             let got_records = std::fs::read_to_string(records_file_out.path())
                 .context("failed reading from --records-file-out")?;
             let got_records: Vec<StateRecord> = serde_json::from_str(&got_records)
@@ -933,7 +1005,14 @@ mod test {
     #[test]
     fn test_amend_genesis() {
         for t in TEST_CASES.iter() {
-            t.run().unwrap();
+            t.run(false).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_amend_genesis_patch() {
+        for t in TEST_CASES.iter() {
+            t.run(true).unwrap();
         }
     }
 }
@@ -1,28 +1,49 @@
 use std::sync::Arc;
 
-use crate::concurrency::{Ctx, Scope};
 use crate::network;
-use anyhow::Context;
+use anyhow::{bail, Context};
 use log::info;
+use near_concurrency::{Ctx, Scope};
 use std::sync::atomic::Ordering;
 use tokio::time;
 
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+
+// Summary of a run, checked against the --assert-* thresholds.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub running_time: time::Duration,
+    pub headers: u64,
+    pub blocks: u64,
+    pub chunks: u64,
+    // 99th percentile of the latency of all the requests which got a response.
+    pub p99_latency: Option<time::Duration>,
+}
+
+impl Summary {
+    pub fn per_sec(&self, count: u64) -> f64 {
+        count as f64 / self.running_time.as_secs_f64()
+    }
+}
 
 // run() fetches the chain (headers,blocks and chunks)
 // starting with block having hash = <start_block_hash> and
 // ending with the current tip of the chain (snapshotted once
 // at the start of the routine, so that the amount of work
-// is bounded).
+// is bounded), or with the block at <to_height> if set.
 pub async fn run(
     ctx: Ctx,
     network: Arc<network::Network>,
     start_block_hash: CryptoHash,
     block_limit: u64,
-) -> anyhow::Result<()> {
+    to_height: Option<BlockHeight>,
+) -> anyhow::Result<Summary> {
     info!("SYNC start");
-    let peers = network.info(&ctx).await?;
-    let target_height = peers.highest_height_peers[0].highest_block_height as i64;
+    let target_height = match to_height {
+        Some(to_height) => to_height as i64,
+        None => network.info(&ctx).await?.highest_height_peers[0].highest_block_height as i64,
+    };
     info!("SYNC target_height = {}", target_height);
 
     let start_time = time::Instant::now();
@@ -56,6 +77,9 @@ pub async fn run(
                     target_height - last_height
                 );
                 for h in headers {
+                    if h.height() as i64 > target_height {
+                        break;
+                    }
                     blocks_count += 1;
                     if blocks_count == block_limit {
                         return anyhow::Ok(());
@@ -93,5 +117,71 @@ pub async fn run(
     info!("fetched {} header batches ({:.2} per second)", headers, headers as f64 / t);
     info!("fetched {} blocks ({:.2} per second)", blocks, blocks as f64 / t);
     info!("fetched {} chunks ({:.2} per second)", chunks, chunks as f64 / t);
-    return res;
+    res?;
+    Ok(Summary {
+        running_time: total_time,
+        headers,
+        blocks,
+        chunks,
+        p99_latency: network.latency_percentile(99),
+    })
+}
+
+// run_headers_only() fetches just the headers, starting after the block
+// having hash = <start_block_hash>, until it catches up with the highest
+// height reported by the peers, which is refreshed after every batch, or
+// until <to_height> if set.
+// Every batch is checked to extend the chain fetched so far.
+pub async fn run_headers_only(
+    ctx: Ctx,
+    network: Arc<network::Network>,
+    start_block_hash: CryptoHash,
+    to_height: Option<BlockHeight>,
+) -> anyhow::Result<Summary> {
+    info!("HEADERS start");
+    let start_time = time::Instant::now();
+    let mut last_hash = start_block_hash;
+    let mut last_height = 0;
+    let mut headers_count: u64 = 0;
+    loop {
+        let target_height = match to_height {
+            Some(to_height) => to_height,
+            None => network.info(&ctx).await?.highest_height_peers[0].highest_block_height,
+        };
+        if last_height >= target_height {
+            break;
+        }
+        let mut headers = network.fetch_block_headers(&ctx, &last_hash).await?;
+        headers.sort_by_key(|h| h.height());
+        anyhow::ensure!(!headers.is_empty(), "no headers");
+        headers.retain(|h| h.height() <= target_height);
+        if headers.is_empty() {
+            break;
+        }
+        for h in &headers {
+            if h.prev_hash() != &last_hash || h.height() <= last_height {
+                bail!("header {} at height {} doesn't extend {}", h.hash(), h.height(), last_hash);
+            }
+            last_hash = h.hash().clone();
+            last_height = h.height();
+        }
+        headers_count += headers.len() as u64;
+        let t = start_time.elapsed().as_secs_f64();
+        info!(
+            "HEADERS last_height = {}, {} headers left, {:.2} headers per second",
+            last_height,
+            target_height.saturating_sub(last_height),
+            headers_count as f64 / t
+        );
+    }
+    let total_time = start_time.elapsed();
+    let t = total_time.as_secs_f64();
+    info!("running time: {:.2}s", t);
+    info!("fetched {} headers ({:.2} per second)", headers_count, headers_count as f64 / t);
+    Ok(Summary {
+        running_time: total_time,
+        headers: headers_count,
+        p99_latency: network.latency_percentile(99),
+        ..Summary::default()
+    })
 }
@@ -1,15 +1,22 @@
-mod concurrency;
 mod fetch_chain;
 mod network;
+mod peer_probe;
+mod peer_stats;
+mod rate_limits;
+mod thresholds;
+mod tx_status_probe;
 
+use std::num::{NonZeroU32, NonZeroU64};
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
 use clap::Parser;
 use openssl_probe;
 
-use concurrency::{Ctx, Scope};
+use near_concurrency::{Ctx, Scope};
 use network::Network;
+use rate_limits::{Rate, RateLimits};
+use thresholds::Thresholds;
 
 use near_chain_configs::Genesis;
 use near_network::time;
@@ -18,6 +25,7 @@ use near_network::PeerManagerActor;
 use near_o11y::tracing::{error, info};
 use near_primitives::block::GenesisId;
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, BlockHeight};
 use nearcore::config;
 use nearcore::config::NearConfig;
 
@@ -34,9 +42,15 @@ fn genesis_hash(chain_id: &str) -> CryptoHash {
     .unwrap();
 }
 
-pub fn start_with_config(config: NearConfig, qps_limit: u32) -> anyhow::Result<Arc<Network>> {
+pub fn start_with_config(
+    config: NearConfig,
+    rate_limits: RateLimits,
+    archival_only: bool,
+    fetch_timeout: std::time::Duration,
+) -> anyhow::Result<Arc<Network>> {
     let network_adapter = Arc::new(NetworkRecipient::default());
-    let network = Network::new(&config, network_adapter.clone(), qps_limit);
+    let network =
+        Network::new(&config, network_adapter.clone(), rate_limits, archival_only, fetch_timeout);
 
     let network_actor = PeerManagerActor::spawn(
         time::Clock::real(),
@@ -70,23 +84,90 @@ fn download_configs(chain_id: &str, dir: &std::path::Path) -> anyhow::Result<Nea
     NearConfig::new(config, genesis, (&node_signer).into(), None)
 }
 
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Fetch headers, blocks and chunks.
+    Full,
+    /// Fetch only the headers, to measure header sync alone.
+    HeadersOnly,
+}
+
 #[derive(Parser, Debug)]
 struct Cmd {
     #[clap(long)]
     pub chain_id: String,
+    #[clap(long, required_unless_present = "from-height")]
+    pub start_block_hash: Option<String>,
+    /// Fetch the historical range of blocks starting at this height, instead
+    /// of starting after --start-block-hash. There has to be a block at this
+    /// height. In range mode only archival peers are used.
+    #[clap(long, conflicts_with = "start-block-hash")]
+    pub from_height: Option<BlockHeight>,
+    /// Stop fetching at this height, instead of at the head of the peers or
+    /// after --block-limit blocks. In range mode only archival peers are used.
     #[clap(long)]
-    pub start_block_hash: String,
+    pub to_height: Option<BlockHeight>,
+    /// Limit of requests per second, shared by all the kinds of requests.
     #[clap(long, default_value = "200")]
-    pub qps_limit: u32,
+    pub qps_limit: NonZeroU32,
+    /// Limit of header requests per second, on top of --qps-limit.
+    #[clap(long)]
+    pub headers_qps_limit: Option<NonZeroU32>,
+    /// Limit of block requests per second, on top of --qps-limit.
+    #[clap(long)]
+    pub blocks_qps_limit: Option<NonZeroU32>,
+    /// Limit of chunk requests per second, on top of --qps-limit.
+    #[clap(long)]
+    pub chunks_qps_limit: Option<NonZeroU32>,
+    /// Maximal number of requests of a kind sent at once after a period of
+    /// inactivity. Defaults to the per second limit of the kind.
+    #[clap(long)]
+    pub burst_limit: Option<NonZeroU64>,
+    /// Read commands changing the rate limits from stdin while the test runs,
+    /// one per line: "<class> <qps> [<burst>]", where <class> is one of
+    /// global, headers, blocks, chunks or other.
+    #[clap(long)]
+    pub admin_stdin: bool,
     #[clap(long, default_value = "2000")]
     pub block_limit: u64,
+    /// Time in seconds after which a request for a header, block or chunk
+    /// which no peer responded to fails the test.
+    #[clap(long, default_value = "60")]
+    pub fetch_timeout: u64,
+    /// What to fetch.  In headers-only mode, headers are fetched up to the
+    /// head of the peers regardless of --block-limit.
+    #[clap(long, arg_enum, default_value = "full")]
+    pub mode: Mode,
+    /// Transactions, as <signer account id>:<hash>, whose status is
+    /// periodically requested from the known block producers over routed
+    /// messages, to measure the latency of the responses.
+    #[clap(long)]
+    pub tx_status: Vec<String>,
+    /// Interval between the rounds of TxStatus requests, in seconds.
+    #[clap(long, default_value = "5")]
+    pub tx_status_period: u64,
+    #[clap(flatten)]
+    pub thresholds: Thresholds,
+}
+
+fn parse_tx(tx: &str) -> anyhow::Result<(AccountId, CryptoHash)> {
+    let (account_id, hash) =
+        tx.split_once(':').with_context(|| format!("expected <account id>:<hash>, got {tx}"))?;
+    Ok((account_id.parse()?, hash.parse::<CryptoHash>().map_err(|x| anyhow!(x.to_string()))?))
 }
 
 impl Cmd {
     fn parse_and_run() -> anyhow::Result<()> {
         let cmd = Self::parse();
-        let start_block_hash =
-            cmd.start_block_hash.parse::<CryptoHash>().map_err(|x| anyhow!(x.to_string()))?;
+        let start_block_hash = match &cmd.start_block_hash {
+            Some(hash) => Some(hash.parse::<CryptoHash>().map_err(|x| anyhow!(x.to_string()))?),
+            None => None,
+        };
+        let archival_only = cmd.from_height.is_some() || cmd.to_height.is_some();
+        let txs =
+            cmd.tx_status.iter().map(|tx| parse_tx(tx)).collect::<anyhow::Result<Vec<_>>>()?;
+        let tx_status_period = std::time::Duration::from_secs(cmd.tx_status_period);
+        let thresholds = cmd.thresholds.clone();
 
         let mut cache_dir = dirs::cache_dir().context("dirs::cache_dir() = None")?;
         cache_dir.push("near_configs");
@@ -106,26 +187,86 @@ impl Cmd {
         let rt_ = Arc::new(tokio::runtime::Runtime::new()?);
         let rt = rt_;
         return actix::System::new().block_on(async move {
-            let network =
-                start_with_config(near_config, cmd.qps_limit).context("start_with_config")?;
+            let rate = |qps| Rate::new(qps, cmd.burst_limit);
+            let rate_limits = RateLimits::new(
+                rate(cmd.qps_limit),
+                cmd.headers_qps_limit.map(rate),
+                cmd.blocks_qps_limit.map(rate),
+                cmd.chunks_qps_limit.map(rate),
+            );
+            let fetch_timeout = std::time::Duration::from_secs(cmd.fetch_timeout);
+            let network = start_with_config(near_config, rate_limits, archival_only, fetch_timeout)
+                .context("start_with_config")?;
 
             // We execute the chain_sync on a totally separate set of system threads to minimize
             // the interaction with actix.
-            rt.spawn(async move {
-                Scope::run(&Ctx::background(), move |ctx, s| async move {
-                    s.spawn_weak(|ctx| async move {
-                        ctx.wrap(tokio::signal::ctrl_c()).await?.unwrap();
-                        info!("Got CTRL+C, stopping...");
-                        return Err(anyhow!("Got CTRL+C"));
-                    });
-                    fetch_chain::run(ctx.clone(), network, start_block_hash, cmd.block_limit)
-                        .await?;
-                    info!("Fetch completed");
-                    anyhow::Ok(())
+            let summary = rt
+                .spawn(async move {
+                    Scope::run(&Ctx::background(), move |ctx, s| async move {
+                        s.spawn_weak_labeled("ctrl_c", |ctx| async move {
+                            ctx.wrap(tokio::signal::ctrl_c()).await?.unwrap();
+                            info!("Got CTRL+C, stopping...");
+                            return Err(anyhow!("Got CTRL+C"));
+                        });
+                        if cmd.admin_stdin {
+                            let network = network.clone();
+                            s.spawn_weak_labeled("admin", move |ctx| {
+                                rate_limits::run_admin(ctx, network)
+                            });
+                        }
+                        if !txs.is_empty() {
+                            let network = network.clone();
+                            s.spawn_weak_labeled("tx_status_probe", move |ctx| {
+                                tx_status_probe::run(ctx, network, txs, tx_status_period)
+                            });
+                        }
+                        // The fetch starts after the start block, so in range mode
+                        // it starts at the parent of the block at --from-height.
+                        let start_block_hash = match (start_block_hash, cmd.from_height) {
+                            (Some(hash), _) => hash,
+                            (None, Some(height)) => {
+                                let header = network.fetch_header_at_height(&ctx, height).await?;
+                                info!("block at height {} is {}", height, header.hash());
+                                *header.prev_hash()
+                            }
+                            (None, None) => unreachable!("enforced by clap"),
+                        };
+                        let peers = peer_probe::discover(&ctx, &network, start_block_hash).await?;
+                        let summary = match cmd.mode {
+                            Mode::Full => {
+                                fetch_chain::run(
+                                    ctx.clone(),
+                                    network.clone(),
+                                    start_block_hash,
+                                    cmd.block_limit,
+                                    cmd.to_height,
+                                )
+                                .await?
+                            }
+                            Mode::HeadersOnly => {
+                                fetch_chain::run_headers_only(
+                                    ctx.clone(),
+                                    network.clone(),
+                                    start_block_hash,
+                                    cmd.to_height,
+                                )
+                                .await?
+                            }
+                        };
+                        info!("Fetch completed");
+                        peer_probe::log_stats(&peers, &network);
+                        network.peer_stats.log_bandwidth_report(summary.running_time);
+                        anyhow::Ok(summary)
+                    })
+                    .await
                 })
-                .await
-            })
-            .await??;
+                .await??;
+            if !thresholds.is_empty() {
+                let (passed, report) = thresholds.check(&summary);
+                // The report goes to stdout, apart from the logs, to be parsed by pipelines.
+                println!("{}", report);
+                anyhow::ensure!(passed, "thresholds not met");
+            }
             return Ok(());
         });
     }
@@ -145,5 +286,6 @@ fn main() {
     openssl_probe::init_ssl_cert_env_vars();
     if let Err(e) = Cmd::parse_and_run() {
         error!("Cmd::parse_and_run(): {:#}", e);
+        std::process::exit(1);
     }
 }
@@ -1,9 +1,14 @@
-use crate::concurrency::{Ctx, Once, RateLimiter, Scope, WeakMap};
+use crate::peer_stats::{self, PeerStatsMap};
+use crate::rate_limits::{RateLimits, RequestClass};
+use anyhow::Context as _;
 use log::info;
+use near_concurrency::{Ctx, Once, Scope, WeakMap};
+use near_network::client::DataResponse;
 use near_network::time;
 use near_network::types::{
-    AccountIdOrPeerTrackingShard, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
-    PartialEncodedChunkResponseMsg, ReasonForBan, StateResponseInfo,
+    AccountIdOrPeerTrackingShard, GarbageCollectedData, PartialEncodedChunkForwardMsg,
+    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, ReasonForBan,
+    StateDeltaResponseInfo, StateResponseInfo,
 };
 use near_network::types::{
     FullPeerInfo, NetworkInfo, NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest,
@@ -17,7 +22,7 @@ use near_primitives::sharding::ShardChunkHeader;
 use near_primitives::sharding::{ChunkHash, PartialEncodedChunk};
 use near_primitives::time::Clock;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, EpochId, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
 use nearcore::config::NearConfig;
 use rand::seq::SliceRandom;
@@ -38,6 +43,8 @@ pub struct Stats {
     pub block_done: AtomicU64,
     pub chunk_start: AtomicU64,
     pub chunk_done: AtomicU64,
+    pub tx_status_start: AtomicU64,
+    pub tx_status_done: AtomicU64,
 }
 
 // NetworkData contains the mutable private data of the Network struct.
@@ -55,7 +62,12 @@ pub struct Network {
     pub block_headers: Arc<WeakMap<CryptoHash, Once<Vec<BlockHeader>>>>,
     pub blocks: Arc<WeakMap<CryptoHash, Once<Block>>>,
     pub chunks: Arc<WeakMap<ChunkHash, Once<PartialEncodedChunkResponseMsg>>>,
+    pub tx_statuses: Arc<WeakMap<CryptoHash, Once<FinalExecutionOutcomeView>>>,
+    pub headers_at_height: Arc<WeakMap<BlockHeight, Once<BlockHeader>>>,
     data: Mutex<NetworkData>,
+    // Latencies of the requests which got a response.
+    latencies: Mutex<Vec<tokio::time::Duration>>,
+    pub peer_stats: PeerStatsMap,
 
     // client_config.min_num_peers
     min_peers: usize,
@@ -65,14 +77,21 @@ pub struct Network {
     parts_per_chunk: u64,
 
     request_timeout: tokio::time::Duration,
-    rate_limiter: RateLimiter,
+    // Time after which a fetch_*() call gives up waiting for the response.
+    fetch_timeout: tokio::time::Duration,
+    pub rate_limits: RateLimits,
+    // Whether to send requests only to archival peers, for fetching
+    // historical data.
+    archival_only: bool,
 }
 
 impl Network {
     pub fn new(
         config: &NearConfig,
         network_adapter: Arc<dyn PeerManagerAdapter>,
-        qps_limit: u32,
+        rate_limits: RateLimits,
+        archival_only: bool,
+        fetch_timeout: tokio::time::Duration,
     ) -> Arc<Network> {
         Arc::new(Network {
             stats: Default::default(),
@@ -92,30 +111,36 @@ impl Network {
                 }),
                 info_futures: Default::default(),
             }),
+            latencies: Default::default(),
+            peer_stats: Default::default(),
             blocks: WeakMap::new(),
             block_headers: WeakMap::new(),
             chunks: WeakMap::new(),
+            tx_statuses: WeakMap::new(),
+            headers_at_height: WeakMap::new(),
 
             min_peers: config.client_config.min_num_peers,
             parts_per_chunk: config.genesis.config.num_block_producer_seats,
-            rate_limiter: RateLimiter::new(
-                tokio::time::Duration::from_secs(1) / qps_limit,
-                qps_limit as u64,
-            ),
+            rate_limits,
+            archival_only,
             request_timeout: tokio::time::Duration::from_secs(2),
+            fetch_timeout,
         })
     }
 
     // keep_sending() sends periodically (every self.request_timeout)
     // a NetworkRequest produced by <new_req> in an infinite loop.
-    // The requests are distributed uniformly among all the available peers.
+    // The fetch_*() calls using it give up after self.fetch_timeout.
+    // The requests are distributed uniformly among all the available peers,
+    // or only the archival ones if archival_only is set.
     // - keep_sending() completes as soon as ctx expires.
-    // - keep_sending() respects the global rate limits, so the actual frequency
-    //   of the sends may be lower than expected.
+    // - keep_sending() respects the rate limits of <class> and the global ones,
+    //   so the actual frequency of the sends may be lower than expected.
     // - keep_sending() may pause if the number of connected peers is too small.
     fn keep_sending(
         self: &Arc<Self>,
         ctx: &Ctx,
+        class: RequestClass,
         new_req: impl Fn(FullPeerInfo) -> NetworkRequests + Send,
     ) -> impl Future<Output = anyhow::Result<()>> + Send {
         let self_ = self.clone();
@@ -123,23 +148,63 @@ impl Network {
         async move {
             loop {
                 let mut peers = self_.info(&ctx).await?.connected_peers.clone();
+                if self_.archival_only {
+                    peers.retain(|p| p.full_peer_info.chain_info.archival);
+                    if peers.is_empty() {
+                        info!("no archival peers connected");
+                        ctx.wait(self_.request_timeout).await?;
+                        continue;
+                    }
+                }
                 peers.shuffle(&mut thread_rng());
                 for peer in peers {
                     // TODO: rate limit per peer.
-                    self_.rate_limiter.allow(&ctx).await?;
-                    self_.network_adapter.do_send(
-                        PeerManagerMessageRequest::NetworkRequests(new_req(
-                            peer.full_peer_info.clone(),
-                        ))
-                        .with_span_context(),
-                    );
-                    self_.stats.msgs_sent.fetch_add(1, Ordering::Relaxed);
+                    self_.rate_limits.allow(&ctx, class).await?;
+                    self_.do_send(new_req(peer.full_peer_info.clone()));
                     ctx.wait(self_.request_timeout).await?;
                 }
             }
         }
     }
 
+    // send() sends a single request, respecting the rate limits of
+    // RequestClass::Other and the global ones.
+    pub async fn send(self: &Arc<Self>, ctx: &Ctx, req: NetworkRequests) -> anyhow::Result<()> {
+        self.rate_limits.allow(ctx, RequestClass::Other).await?;
+        self.do_send(req);
+        Ok(())
+    }
+
+    fn do_send(&self, req: NetworkRequests) {
+        let size = peer_stats::request_size(&req);
+        self.peer_stats.update(peer_stats::request_peer(&req), |s| s.bytes_requested += size);
+        self.network_adapter
+            .do_send(PeerManagerMessageRequest::NetworkRequests(req).with_span_context());
+        self.stats.msgs_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // measure() awaits <fut> and records its latency if it succeeds.
+    async fn measure<T, E>(&self, fut: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+        let start = tokio::time::Instant::now();
+        let res = fut.await;
+        if res.is_ok() {
+            self.latencies.lock().unwrap().push(start.elapsed());
+        }
+        res
+    }
+
+    // latency_percentile() returns the <p>-th percentile of the latencies of
+    // the requests which got a response, or None if there were none.
+    pub fn latency_percentile(&self, p: usize) -> Option<tokio::time::Duration> {
+        let mut latencies = self.latencies.lock().unwrap().clone();
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort();
+        let idx = ((latencies.len() * p + 99) / 100).clamp(1, latencies.len()) - 1;
+        Some(latencies[idx])
+    }
+
     // info() fetches the state of the newest available NetworkInfo.
     // It blocks if the number of connected peers is too small.
     pub async fn info(self: &Arc<Self>, ctx: &Ctx) -> anyhow::Result<Arc<NetworkInfo>> {
@@ -165,24 +230,60 @@ impl Network {
         ctx: &Ctx,
         hash: &CryptoHash,
     ) -> anyhow::Result<Vec<BlockHeader>> {
-        Scope::run(ctx, {
+        Scope::run(&ctx.with_timeout(self.fetch_timeout), {
             let self_ = self.clone();
             let hash = hash.clone();
             move |ctx, s| async move {
                 self_.stats.header_start.fetch_add(1, Ordering::Relaxed);
                 let recv = self_.block_headers.get_or_insert(&hash, || Once::new());
                 s.spawn_weak(|ctx| {
-                    self_.keep_sending(&ctx, move |peer| NetworkRequests::BlockHeadersRequest {
-                        hashes: vec![hash.clone()],
-                        peer_id: peer.peer_info.id,
+                    self_.keep_sending(&ctx, RequestClass::Headers, move |peer| {
+                        NetworkRequests::BlockHeadersRequest {
+                            hashes: vec![hash.clone()],
+                            peer_id: peer.peer_info.id,
+                        }
                     })
                 });
-                let res = ctx.wrap(recv.wait()).await;
+                let res = self_.measure(ctx.wrap(recv.wait())).await;
                 self_.stats.header_done.fetch_add(1, Ordering::Relaxed);
                 anyhow::Ok(res?)
             }
         })
         .await
+        .with_context(|| format!("fetching the headers after {hash}"))
+    }
+
+    // fetch_header_at_height() fetches the header of the block at the given
+    // height, e.g. to start fetching the chain from there.
+    // Peers which don't support requesting headers by height never respond,
+    // in which case it fails after self.fetch_timeout.
+    pub async fn fetch_header_at_height(
+        self: &Arc<Self>,
+        ctx: &Ctx,
+        height: BlockHeight,
+    ) -> anyhow::Result<BlockHeader> {
+        Scope::run(&ctx.with_timeout(self.fetch_timeout), {
+            let self_ = self.clone();
+            move |ctx, s| async move {
+                let recv = self_.headers_at_height.get_or_insert(&height, || Once::new());
+                s.spawn_weak(|ctx| {
+                    self_.keep_sending(&ctx, RequestClass::Headers, move |peer| {
+                        NetworkRequests::BlockHeadersAtHeightsRequest {
+                            heights: vec![height],
+                            peer_id: peer.peer_info.id,
+                        }
+                    })
+                });
+                anyhow::Ok(ctx.wrap(recv.wait()).await?)
+            }
+        })
+        .await
+        .with_context(|| {
+            format!(
+                "fetching the header at height {height}; if the peers don't support \
+                 requesting headers by height, use --start-block-hash instead"
+            )
+        })
     }
 
     // fetch_block() fetches a block with a given hash.
@@ -191,24 +292,27 @@ impl Network {
         ctx: &Ctx,
         hash: &CryptoHash,
     ) -> anyhow::Result<Block> {
-        Scope::run(ctx, {
+        Scope::run(&ctx.with_timeout(self.fetch_timeout), {
             let self_ = self.clone();
             let hash = hash.clone();
             move |ctx, s| async move {
                 self_.stats.block_start.fetch_add(1, Ordering::Relaxed);
                 let recv = self_.blocks.get_or_insert(&hash, || Once::new());
                 s.spawn_weak(|ctx| {
-                    self_.keep_sending(&ctx, move |peer| NetworkRequests::BlockRequest {
-                        hash: hash.clone(),
-                        peer_id: peer.peer_info.id,
+                    self_.keep_sending(&ctx, RequestClass::Blocks, move |peer| {
+                        NetworkRequests::BlockRequest {
+                            hash: hash.clone(),
+                            peer_id: peer.peer_info.id,
+                        }
                     })
                 });
-                let res = ctx.wrap(recv.wait()).await;
+                let res = self_.measure(ctx.wrap(recv.wait())).await;
                 self_.stats.block_done.fetch_add(1, Ordering::Relaxed);
                 anyhow::Ok(res?)
             }
         })
         .await
+        .with_context(|| format!("fetching the block {hash}"))
     }
 
     // fetch_chunk fetches a chunk for the given chunk header.
@@ -217,7 +321,7 @@ impl Network {
         ctx: &Ctx,
         ch: &ShardChunkHeader,
     ) -> anyhow::Result<PartialEncodedChunkResponseMsg> {
-        Scope::run(ctx, {
+        Scope::run(&ctx.with_timeout(self.fetch_timeout), {
             let self_ = self.clone();
             let ch = ch.clone();
             move |ctx, s| async move {
@@ -225,14 +329,15 @@ impl Network {
                 // TODO: consider converting wrapping these atomic counters into sth like a Span.
                 self_.stats.chunk_start.fetch_add(1, Ordering::Relaxed);
                 s.spawn_weak(|ctx| {
-                    self_.keep_sending(&ctx, {
+                    self_.keep_sending(&ctx, RequestClass::Chunks, {
                         let ppc = self_.parts_per_chunk;
+                        let archival_only = self_.archival_only;
                         move |peer| NetworkRequests::PartialEncodedChunkRequest {
                             target: AccountIdOrPeerTrackingShard {
                                 account_id: peer.peer_info.account_id,
                                 prefer_peer: true,
                                 shard_id: ch.shard_id(),
-                                only_archival: false,
+                                only_archival: archival_only,
                                 min_height: ch.height_included(),
                             },
                             request: PartialEncodedChunkRequestMsg {
@@ -244,12 +349,36 @@ impl Network {
                         }
                     })
                 });
-                let res = ctx.wrap(recv.wait()).await;
+                let res = self_.measure(ctx.wrap(recv.wait())).await;
                 self_.stats.chunk_done.fetch_add(1, Ordering::Relaxed);
                 anyhow::Ok(res?)
             }
         })
         .await
+        .with_context(|| format!("fetching the chunk {:?}", ch.chunk_hash()))
+    }
+
+    // fetch_tx_status() sends a single TxStatus request, routed to the node
+    // of the given account, and waits for the response. Unlike the other
+    // requests it is not repeated, so that the response can be attributed to
+    // the account; use a ctx with a timeout.
+    pub async fn fetch_tx_status(
+        self: &Arc<Self>,
+        ctx: &Ctx,
+        target: &AccountId,
+        signer_account_id: &AccountId,
+        tx_hash: &CryptoHash,
+    ) -> anyhow::Result<FinalExecutionOutcomeView> {
+        let recv = self.tx_statuses.get_or_insert(tx_hash, || Once::new());
+        self.stats.tx_status_start.fetch_add(1, Ordering::Relaxed);
+        self.send(
+            ctx,
+            NetworkRequests::TxStatus(target.clone(), signer_account_id.clone(), tx_hash.clone()),
+        )
+        .await?;
+        let res = ctx.wrap(recv.wait()).await;
+        self.stats.tx_status_done.fetch_add(1, Ordering::Relaxed);
+        anyhow::Ok(res?)
     }
 }
 
@@ -263,13 +392,16 @@ impl near_network::client::Client for Network {
         None
     }
 
-    async fn tx_status_response(&self, _tx_result: FinalExecutionOutcomeView) {}
+    async fn tx_status_response(&self, tx_result: FinalExecutionOutcomeView) {
+        self.tx_statuses.get(&tx_result.transaction.hash.clone()).map(|p| p.set(tx_result));
+    }
 
     async fn state_request_header(
         &self,
         _shard_id: ShardId,
         _sync_hash: CryptoHash,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        _peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
         Ok(None)
     }
 
@@ -278,12 +410,27 @@ impl near_network::client::Client for Network {
         _shard_id: ShardId,
         _sync_hash: CryptoHash,
         _part_id: u64,
-    ) -> Result<Option<StateResponseInfo>, ReasonForBan> {
+        _peer_id: PeerId,
+    ) -> Result<Option<DataResponse<StateResponseInfo>>, ReasonForBan> {
         Ok(None)
     }
 
     async fn state_response(&self, _info: StateResponseInfo) {}
 
+    async fn data_garbage_collected(&self, _data: GarbageCollectedData, _peer_id: PeerId) {}
+
+    async fn state_request_delta(
+        &self,
+        _shard_id: ShardId,
+        _sync_hash: CryptoHash,
+        _from_hash: CryptoHash,
+        _peer_id: PeerId,
+    ) -> Result<Option<StateDeltaResponseInfo>, ReasonForBan> {
+        Ok(None)
+    }
+
+    async fn state_delta_response(&self, _info: StateDeltaResponseInfo) {}
+
     async fn block_approval(&self, _approval: Approval, _peer_id: PeerId) {}
 
     async fn transaction(&self, _transaction: SignedTransaction, _is_forwarded: bool) {}
@@ -299,7 +446,10 @@ impl near_network::client::Client for Network {
         &self,
         resp: PartialEncodedChunkResponseMsg,
         _timestamp: time::Instant,
+        author: PeerId,
+        msg_len: usize,
     ) {
+        self.peer_stats.update(Some(&author), |s| s.bytes_received += msg_len as u64);
         self.chunks.get(&resp.chunk_hash.clone()).map(|p| p.set(resp));
     }
 
@@ -307,7 +457,7 @@ impl near_network::client::Client for Network {
 
     async fn partial_encoded_chunk_forward(&self, _msg: PartialEncodedChunkForwardMsg) {}
 
-    async fn block_request(&self, _hash: CryptoHash) -> Option<Box<Block>> {
+    async fn block_request(&self, _hash: CryptoHash) -> Option<DataResponse<Box<Block>>> {
         None
     }
 
@@ -315,15 +465,35 @@ impl near_network::client::Client for Network {
         None
     }
 
-    async fn block(&self, block: Block, _peer_id: PeerId, _was_requested: bool) {
+    async fn block_headers_at_heights_request(
+        &self,
+        _heights: Vec<BlockHeight>,
+    ) -> Option<Vec<BlockHeader>> {
+        None
+    }
+
+    async fn block(&self, block: Block, peer_id: PeerId, _was_requested: bool) {
+        let size = peer_stats::size(&block);
+        self.peer_stats.update(Some(&peer_id), |s| {
+            s.blocks += 1;
+            s.bytes_received += size;
+        });
         self.blocks.get(&block.hash().clone()).map(|p| p.set(block));
     }
 
     async fn block_headers(
         &self,
         headers: Vec<BlockHeader>,
-        _peer_id: PeerId,
+        peer_id: PeerId,
     ) -> Result<(), ReasonForBan> {
+        let size = peer_stats::size(&headers);
+        self.peer_stats.update(Some(&peer_id), |s| {
+            s.header_batches += 1;
+            s.bytes_received += size;
+        });
+        for h in &headers {
+            self.headers_at_height.get(&h.height()).map(|p| p.set(h.clone()));
+        }
         if let Some(h) = headers.iter().min_by_key(|h| h.height()) {
             let hash = h.prev_hash().clone();
             self.block_headers.get(&hash).map(|p| p.set(headers));
@@ -352,3 +522,57 @@ impl near_network::client::Client for Network {
         Ok(accounts.into_iter().map(|a| a.0).collect())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limits::Rate;
+    use near_chain_configs::Genesis;
+    use near_network::types::NetworkRecipient;
+    use std::num::NonZeroU32;
+
+    fn make_network() -> Arc<Network> {
+        let signer = near_crypto::InMemorySigner::from_random(
+            "node".parse().unwrap(),
+            near_crypto::KeyType::ED25519,
+        );
+        let config = NearConfig::new(
+            nearcore::config::Config::default(),
+            Genesis::default(),
+            (&signer).into(),
+            None,
+        )
+        .unwrap();
+        // No peer is connected, so nobody responds.
+        Network::new(
+            &config,
+            Arc::new(NetworkRecipient::<actix::Addr<near_network::PeerManagerActor>>::default()),
+            RateLimits::new(Rate::new(NonZeroU32::new(100).unwrap(), None), None, None, None),
+            true,
+            tokio::time::Duration::from_millis(100),
+        )
+    }
+
+    #[tokio::test]
+    async fn fetch_header_at_height_times_out() {
+        let network = make_network();
+        let err = network.fetch_header_at_height(&Ctx::background(), 7).await.unwrap_err();
+        assert!(format!("{err:#}").contains("header at height 7"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn chunk_response_bandwidth_is_attributed_to_author() {
+        use near_network::client::Client as _;
+        let network = make_network();
+        let author = PeerId::random();
+        let resp = PartialEncodedChunkResponseMsg {
+            chunk_hash: ChunkHash(CryptoHash::default()),
+            parts: vec![],
+            receipts: vec![],
+        };
+        let now = time::Clock::real().now();
+        network.partial_encoded_chunk_response(resp, now, author.clone(), 1234).await;
+        assert_eq!(network.peer_stats.get(&author).bytes_received, 1234);
+        assert!(!network.peer_stats.snapshot().contains_key(&None));
+    }
+}
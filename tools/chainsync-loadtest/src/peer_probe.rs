@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::network;
+use crate::peer_stats::PeerStats;
+use log::info;
+use near_concurrency::Ctx;
+use tokio::time;
+
+use near_network::types::NetworkRequests;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use near_primitives::types::{BlockHeight, ShardId};
+
+// How long to wait for the responses to the probe requests.
+const PROBE_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+
+// PeerCapabilities describes a connected peer, as advertised in its handshake
+// and checked with probe requests. Support for epoch sync is not probed,
+// since its messages are deprecated in the network protocol.
+#[derive(Debug, Clone)]
+pub struct PeerCapabilities {
+    pub peer_id: PeerId,
+    pub archival: bool,
+    pub tracked_shards: Vec<ShardId>,
+    pub height: Option<BlockHeight>,
+    // Whether the peer responded with the start block.
+    pub serves_start_block: bool,
+    // Whether the peer responded with the headers after the start block.
+    pub serves_headers: bool,
+}
+
+impl PeerCapabilities {
+    // class() is the type of the peer by which the stats are segmented.
+    pub fn class(&self) -> &'static str {
+        match (self.archival, self.serves_start_block) {
+            (true, true) => "archival",
+            (true, false) => "archival-unresponsive",
+            (false, true) => "non-archival",
+            (false, false) => "non-archival-unresponsive",
+        }
+    }
+}
+
+// discover() sends a request for the start block and one for the headers
+// after it to every connected peer, and classifies the peers by their
+// advertised info and by whether they responded within PROBE_TIMEOUT.
+pub async fn discover(
+    ctx: &Ctx,
+    network: &Arc<network::Network>,
+    start_block_hash: CryptoHash,
+) -> anyhow::Result<Vec<PeerCapabilities>> {
+    let peers = network.info(ctx).await?.connected_peers.clone();
+    info!("PROBE probing {} peers", peers.len());
+    let before = network.peer_stats.snapshot();
+    for peer in &peers {
+        let peer_id = peer.full_peer_info.peer_info.id.clone();
+        network
+            .send(
+                ctx,
+                NetworkRequests::BlockRequest { hash: start_block_hash, peer_id: peer_id.clone() },
+            )
+            .await?;
+        network
+            .send(
+                ctx,
+                NetworkRequests::BlockHeadersRequest { hashes: vec![start_block_hash], peer_id },
+            )
+            .await?;
+    }
+    ctx.wait(PROBE_TIMEOUT).await?;
+    let after = network.peer_stats.snapshot();
+
+    let mut capabilities = vec![];
+    for peer in peers {
+        let info = peer.full_peer_info;
+        let id = Some(info.peer_info.id.clone());
+        let before = before.get(&id).cloned().unwrap_or_default();
+        let after = after.get(&id).cloned().unwrap_or_default();
+        let peer = PeerCapabilities {
+            peer_id: info.peer_info.id,
+            archival: info.chain_info.archival,
+            tracked_shards: info.chain_info.tracked_shards,
+            height: info.chain_info.last_block.map(|b| b.height),
+            serves_start_block: after.blocks > before.blocks,
+            serves_headers: after.header_batches > before.header_batches,
+        };
+        info!("PROBE {:?} class = {}", peer, peer.class());
+        capabilities.push(peer);
+    }
+    Ok(capabilities)
+}
+
+// log_stats() logs the responses received from the probed peers, summed up
+// per class of the peers.
+pub fn log_stats(peers: &[PeerCapabilities], network: &network::Network) {
+    let mut classes = BTreeMap::<&str, (usize, PeerStats)>::new();
+    for peer in peers {
+        let (count, total) = classes.entry(peer.class()).or_default();
+        let peer_stats = network.peer_stats.get(&peer.peer_id);
+        *count += 1;
+        total.blocks += peer_stats.blocks;
+        total.header_batches += peer_stats.header_batches;
+    }
+    for (class, (count, total)) in classes {
+        info!(
+            "peers of class {}: {} peers, {} blocks, {} header batches received",
+            class, count, total.blocks, total.header_batches
+        );
+    }
+}
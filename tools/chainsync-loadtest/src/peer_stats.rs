@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use borsh::BorshSerialize;
+use log::info;
+use tokio::time;
+
+use near_network::types::NetworkRequests;
+use near_primitives::network::PeerId;
+
+// PeerStats counts the responses received from a single peer and estimates
+// the data exchanged with it.
+#[derive(Default, Debug, Clone)]
+pub struct PeerStats {
+    pub blocks: u64,
+    pub header_batches: u64,
+    // Estimated from the borsh size of the request and response payloads,
+    // without the overhead of the network protocol, except for the chunk
+    // responses, for which the size of the received message is known.
+    pub bytes_requested: u64,
+    pub bytes_received: u64,
+}
+
+// PeerStatsMap keeps PeerStats per peer. Chunk requests are routed to the
+// account tracking the shard rather than to a peer, so they are accounted
+// under the None key.
+#[derive(Default)]
+pub struct PeerStatsMap(Mutex<HashMap<Option<PeerId>, PeerStats>>);
+
+impl PeerStatsMap {
+    pub fn update(&self, peer_id: Option<&PeerId>, f: impl FnOnce(&mut PeerStats)) {
+        f(self.0.lock().unwrap().entry(peer_id.cloned()).or_default());
+    }
+
+    pub fn get(&self, peer_id: &PeerId) -> PeerStats {
+        self.0.lock().unwrap().get(&Some(peer_id.clone())).cloned().unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> HashMap<Option<PeerId>, PeerStats> {
+        self.0.lock().unwrap().clone()
+    }
+
+    // log_bandwidth_report() logs the data exchanged with every peer, most
+    // received first, and the totals, averaged over <running_time>.
+    pub fn log_bandwidth_report(&self, running_time: time::Duration) {
+        let t = running_time.as_secs_f64();
+        let mib = |bytes: u64| bytes as f64 / (1024. * 1024.);
+        let mut stats: Vec<_> = self.snapshot().into_iter().collect();
+        stats.sort_by_key(|(_, s)| std::cmp::Reverse(s.bytes_received));
+        let mut total = PeerStats::default();
+        for (peer_id, s) in &stats {
+            let peer = peer_id.as_ref().map_or("<routed>".to_string(), |p| p.to_string());
+            info!(
+                "BANDWIDTH {}: requested {:.2} MiB, received {:.2} MiB ({:.2} MiB/s)",
+                peer,
+                mib(s.bytes_requested),
+                mib(s.bytes_received),
+                mib(s.bytes_received) / t
+            );
+            total.bytes_requested += s.bytes_requested;
+            total.bytes_received += s.bytes_received;
+        }
+        info!(
+            "BANDWIDTH total: requested {:.2} MiB, received {:.2} MiB ({:.2} MiB/s)",
+            mib(total.bytes_requested),
+            mib(total.bytes_received),
+            mib(total.bytes_received) / t
+        );
+    }
+}
+
+// size() is the borsh size of <value>.
+pub fn size(value: &impl BorshSerialize) -> u64 {
+    value.try_to_vec().map_or(0, |v| v.len() as u64)
+}
+
+// request_peer() is the peer to which a request is sent directly, if any.
+pub fn request_peer(req: &NetworkRequests) -> Option<&PeerId> {
+    match req {
+        NetworkRequests::BlockRequest { peer_id, .. } => Some(peer_id),
+        NetworkRequests::BlockHeadersRequest { peer_id, .. } => Some(peer_id),
+        _ => None,
+    }
+}
+
+// request_size() estimates the size of the payload of a request sent by the
+// loadtest.
+pub fn request_size(req: &NetworkRequests) -> u64 {
+    match req {
+        NetworkRequests::BlockRequest { hash, .. } => size(hash),
+        NetworkRequests::BlockHeadersRequest { hashes, .. } => size(hashes),
+        NetworkRequests::PartialEncodedChunkRequest { request, .. } => size(request),
+        NetworkRequests::TxStatus(_, signer_account_id, tx_hash) => {
+            size(&(signer_account_id, tx_hash))
+        }
+        _ => 0,
+    }
+}
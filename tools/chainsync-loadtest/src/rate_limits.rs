@@ -0,0 +1,212 @@
+use std::num::{NonZeroU32, NonZeroU64};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::network;
+use anyhow::{anyhow, Context};
+use log::{info, warn};
+use near_concurrency::{Ctx, RateLimiter};
+use tokio::sync::mpsc;
+use tokio::time;
+
+// RequestClass is the kind of traffic a request belongs to. Every class is
+// rate limited separately, on top of the global limit shared by all of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestClass {
+    Headers,
+    Blocks,
+    Chunks,
+    // Probes and TxStatus requests.
+    Other,
+}
+
+impl FromStr for RequestClass {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "headers" => Self::Headers,
+            "blocks" => Self::Blocks,
+            "chunks" => Self::Chunks,
+            "other" => Self::Other,
+            _ => return Err(anyhow!("unknown request class {s}")),
+        })
+    }
+}
+
+// Rate is a limit of requests per second, which allows sending up to <burst>
+// requests at once after a period of inactivity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rate {
+    pub qps: NonZeroU32,
+    pub burst: NonZeroU64,
+}
+
+impl Rate {
+    // new() allows bursts of up to a second worth of requests, unless <burst>
+    // is set.
+    pub fn new(qps: NonZeroU32, burst: Option<NonZeroU64>) -> Rate {
+        Rate { qps, burst: burst.unwrap_or(qps.into()) }
+    }
+
+    fn interval(&self) -> time::Duration {
+        time::Duration::from_secs(1) / self.qps.get()
+    }
+}
+
+fn new_limiter(rate: Rate) -> RateLimiter {
+    RateLimiter::new(rate.interval(), rate.burst.get())
+}
+
+// RateLimits limits the rate of requests per RequestClass and globally.
+pub struct RateLimits {
+    global: RateLimiter,
+    headers: RateLimiter,
+    blocks: RateLimiter,
+    chunks: RateLimiter,
+    other: RateLimiter,
+}
+
+impl RateLimits {
+    // new() creates the limits, with the classes without a limit of their own
+    // limited only by the global limit.
+    pub fn new(
+        global: Rate,
+        headers: Option<Rate>,
+        blocks: Option<Rate>,
+        chunks: Option<Rate>,
+    ) -> RateLimits {
+        RateLimits {
+            global: new_limiter(global),
+            headers: new_limiter(headers.unwrap_or(global)),
+            blocks: new_limiter(blocks.unwrap_or(global)),
+            chunks: new_limiter(chunks.unwrap_or(global)),
+            other: new_limiter(global),
+        }
+    }
+
+    fn limiter(&self, class: Option<RequestClass>) -> &RateLimiter {
+        match class {
+            None => &self.global,
+            Some(RequestClass::Headers) => &self.headers,
+            Some(RequestClass::Blocks) => &self.blocks,
+            Some(RequestClass::Chunks) => &self.chunks,
+            Some(RequestClass::Other) => &self.other,
+        }
+    }
+
+    // allow() waits for a permit of the class, then for a global one.
+    pub async fn allow(&self, ctx: &Ctx, class: RequestClass) -> anyhow::Result<()> {
+        self.limiter(Some(class)).allow(ctx).await?;
+        self.global.allow(ctx).await
+    }
+
+    // set_rate() changes the limit of a class, or the global one if <class>
+    // is None.
+    pub async fn set_rate(
+        &self,
+        ctx: &Ctx,
+        class: Option<RequestClass>,
+        rate: Rate,
+    ) -> anyhow::Result<()> {
+        self.limiter(class).set_rate(ctx, rate.interval(), rate.burst.get()).await
+    }
+}
+
+// parse_command() parses an admin command of the form
+// "<class> <qps> [<burst>]", where <class> is "global" or a RequestClass.
+fn parse_command(line: &str) -> anyhow::Result<(Option<RequestClass>, Rate)> {
+    let mut words = line.split_whitespace();
+    let (class, qps) =
+        words.next().zip(words.next()).context("expected <class> <qps> [<burst>]")?;
+    let class = match class {
+        "global" => None,
+        class => Some(class.parse()?),
+    };
+    let qps = qps.parse().context("qps has to be a positive integer")?;
+    let burst = match words.next() {
+        Some(burst) => Some(burst.parse().context("burst has to be a positive integer")?),
+        None => None,
+    };
+    anyhow::ensure!(words.next().is_none(), "expected <class> <qps> [<burst>]");
+    Ok((class, Rate::new(qps, burst)))
+}
+
+// run_admin() reads admin commands from stdin, one per line, and applies
+// them to the rate limits of the network while the test runs. E.g.
+// "blocks 50" limits block requests to 50 per second, "blocks 50 5" also
+// limits the bursts to 5 requests.
+pub async fn run_admin(ctx: Ctx, network: Arc<network::Network>) -> anyhow::Result<()> {
+    let (send, mut recv) = mpsc::unbounded_channel();
+    // Reading stdin blocks, so it is done on a separate thread, which exits
+    // together with the process.
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            match line {
+                Ok(line) => {
+                    if send.send(line).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+    while let Some(line) = ctx.wrap(recv.recv()).await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_command(&line) {
+            Ok((class, rate)) => {
+                network.rate_limits.set_rate(&ctx, class, rate).await?;
+                info!(
+                    "ADMIN rate limit of {:?} set to {} qps, bursts of {}",
+                    class, rate.qps, rate.burst
+                );
+            }
+            Err(err) => warn!("ADMIN invalid command {:?}: {:#}", line, err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(qps: u32, burst: u64) -> Rate {
+        Rate { qps: NonZeroU32::new(qps).unwrap(), burst: NonZeroU64::new(burst).unwrap() }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("blocks 50").unwrap(), (Some(RequestClass::Blocks), rate(50, 50)));
+        assert_eq!(parse_command(" global 200\n").unwrap(), (None, rate(200, 200)));
+        assert_eq!(
+            parse_command("chunks 20 5").unwrap(),
+            (Some(RequestClass::Chunks), rate(20, 5))
+        );
+        assert!(parse_command("blocks").is_err());
+        assert!(parse_command("blocks 0").is_err());
+        assert!(parse_command("blocks 10 0").is_err());
+        assert!(parse_command("blocks 10 5 1").is_err());
+        assert!(parse_command("receipts 10").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_rate() {
+        let ctx = Ctx::background();
+        // 1 qps is slow enough for no permit to be added during the test.
+        let limits = RateLimits::new(rate(1000, 1000), Some(rate(1, 3)), None, None);
+        // Lowering the burst drops the permits above it.
+        limits.set_rate(&ctx, Some(RequestClass::Headers), rate(1, 1)).await.unwrap();
+        limits.allow(&ctx, RequestClass::Headers).await.unwrap();
+        let timeout = ctx.with_timeout(time::Duration::from_millis(100));
+        assert!(limits.allow(&timeout, RequestClass::Headers).await.is_err());
+        // Other classes are not affected.
+        limits.allow(&ctx, RequestClass::Blocks).await.unwrap();
+        // A higher rate lets the requests through again.
+        limits.set_rate(&ctx, Some(RequestClass::Headers), rate(1000, 1)).await.unwrap();
+        let timeout = ctx.with_timeout(time::Duration::from_secs(1));
+        limits.allow(&timeout, RequestClass::Headers).await.unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use crate::fetch_chain::Summary;
+
+// Thresholds which a run has to meet when the tool is used in performance
+// regression pipelines. If any of them is not met, the tool exits with a
+// nonzero code.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Thresholds {
+    /// Minimum number of blocks fetched per second.
+    #[clap(long)]
+    pub assert_min_blocks_per_sec: Option<f64>,
+    /// Minimum number of chunks fetched per second.
+    #[clap(long)]
+    pub assert_min_chunks_per_sec: Option<f64>,
+    /// Minimum number of headers (header batches in full mode) fetched per
+    /// second.
+    #[clap(long)]
+    pub assert_min_headers_per_sec: Option<f64>,
+    /// Maximum 99th percentile of the latency of requests, in milliseconds.
+    #[clap(long)]
+    pub assert_max_p99_latency_ms: Option<f64>,
+}
+
+impl Thresholds {
+    pub fn is_empty(&self) -> bool {
+        self.assert_min_blocks_per_sec.is_none()
+            && self.assert_min_chunks_per_sec.is_none()
+            && self.assert_min_headers_per_sec.is_none()
+            && self.assert_max_p99_latency_ms.is_none()
+    }
+
+    // check() returns a JSON report with the measured value of every metric
+    // which has a threshold, and whether all of them were met.
+    pub fn check(&self, summary: &Summary) -> (bool, serde_json::Value) {
+        let p99_latency_ms = summary.p99_latency.map(|l| l.as_secs_f64() * 1000.);
+        let checks = [
+            (
+                "blocks_per_sec",
+                self.assert_min_blocks_per_sec,
+                Some(summary.per_sec(summary.blocks)),
+                true,
+            ),
+            (
+                "chunks_per_sec",
+                self.assert_min_chunks_per_sec,
+                Some(summary.per_sec(summary.chunks)),
+                true,
+            ),
+            (
+                "headers_per_sec",
+                self.assert_min_headers_per_sec,
+                Some(summary.per_sec(summary.headers)),
+                true,
+            ),
+            ("p99_latency_ms", self.assert_max_p99_latency_ms, p99_latency_ms, false),
+        ];
+        let mut passed = true;
+        let mut results = vec![];
+        for (metric, threshold, actual, is_min) in checks {
+            let threshold = match threshold {
+                Some(threshold) => threshold,
+                None => continue,
+            };
+            // A metric which couldn't be measured fails its check.
+            let ok = actual.map_or(false, |actual| {
+                if is_min {
+                    actual >= threshold
+                } else {
+                    actual <= threshold
+                }
+            });
+            passed &= ok;
+            results.push(serde_json::json!({
+                "metric": metric,
+                "kind": if is_min { "min" } else { "max" },
+                "threshold": threshold,
+                "actual": actual,
+                "passed": ok,
+            }));
+        }
+        (passed, serde_json::json!({ "passed": passed, "checks": results }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_check() {
+        let summary = Summary {
+            running_time: Duration::from_secs(10),
+            headers: 5,
+            blocks: 100,
+            chunks: 400,
+            p99_latency: Some(Duration::from_millis(300)),
+        };
+        assert!(Thresholds::default().check(&summary).0);
+
+        let thresholds = Thresholds {
+            assert_min_blocks_per_sec: Some(10.),
+            assert_max_p99_latency_ms: Some(500.),
+            ..Thresholds::default()
+        };
+        assert!(thresholds.check(&summary).0);
+
+        let thresholds = Thresholds {
+            assert_min_blocks_per_sec: Some(10.),
+            assert_max_p99_latency_ms: Some(200.),
+            ..Thresholds::default()
+        };
+        let (passed, report) = thresholds.check(&summary);
+        assert!(!passed);
+        assert_eq!(report["checks"][0]["passed"], true);
+        assert_eq!(report["checks"][1]["metric"], "p99_latency_ms");
+        assert_eq!(report["checks"][1]["passed"], false);
+
+        let summary = Summary { p99_latency: None, ..summary };
+        assert!(!thresholds.check(&summary).0);
+    }
+}
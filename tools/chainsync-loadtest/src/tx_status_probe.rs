@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::network;
+use log::info;
+use near_concurrency::{Ctx, Scope};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use tokio::time;
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::AccountId;
+
+// Latencies of the TxStatus responses of a single block producer.
+#[derive(Default, Debug)]
+struct Latencies {
+    responses: u64,
+    timeouts: u64,
+    total: time::Duration,
+    max: time::Duration,
+}
+
+impl Latencies {
+    fn record(&mut self, latency: Option<time::Duration>) {
+        match latency {
+            Some(latency) => {
+                self.responses += 1;
+                self.total += latency;
+                self.max = self.max.max(latency);
+            }
+            None => self.timeouts += 1,
+        }
+    }
+
+    fn average(&self) -> time::Duration {
+        self.total.checked_div(self.responses as u32).unwrap_or_default()
+    }
+}
+
+// run() sends, every <period>, a TxStatus request for each of the
+// transactions, given as (signer account id, hash), to a random known block
+// producer over a routed message, and measures the latency of the responses
+// per producer. Requests without a response within <period> count as
+// timeouts. Runs until ctx gets cancelled.
+pub async fn run(
+    ctx: Ctx,
+    network: Arc<network::Network>,
+    txs: Vec<(AccountId, CryptoHash)>,
+    period: time::Duration,
+) -> anyhow::Result<()> {
+    info!("TX_STATUS probing {} transactions every {:?}", txs.len(), period);
+    let latencies = Arc::new(Mutex::new(HashMap::<AccountId, Latencies>::new()));
+    loop {
+        let round_start = time::Instant::now();
+        let producers: Vec<AccountId> = network
+            .info(&ctx)
+            .await?
+            .known_producers
+            .iter()
+            .map(|p| p.account_id.clone())
+            .collect();
+        if producers.is_empty() {
+            info!("TX_STATUS no known block producers yet");
+        } else {
+            Scope::run(&ctx, {
+                let network = network.clone();
+                let txs = txs.clone();
+                let latencies = latencies.clone();
+                |_ctx, s| async move {
+                    for (signer_account_id, tx_hash) in txs {
+                        let target = producers.choose(&mut thread_rng()).unwrap().clone();
+                        let network = network.clone();
+                        let latencies = latencies.clone();
+                        s.spawn(move |ctx, _s| async move {
+                            let ctx = ctx.with_timeout(period);
+                            let start = time::Instant::now();
+                            let latency = network
+                                .fetch_tx_status(&ctx, &target, &signer_account_id, &tx_hash)
+                                .await
+                                .ok()
+                                .map(|_| start.elapsed());
+                            latencies.lock().unwrap().entry(target).or_default().record(latency);
+                            anyhow::Ok(())
+                        });
+                    }
+                    anyhow::Ok(())
+                }
+            })
+            .await?;
+            let latencies = latencies.lock().unwrap();
+            let mut accounts: Vec<_> = latencies.keys().collect();
+            accounts.sort();
+            for account_id in accounts {
+                let l = &latencies[account_id];
+                info!(
+                    "TX_STATUS {}: {} responses, {} timeouts, avg latency {:?}, max latency {:?}",
+                    account_id,
+                    l.responses,
+                    l.timeouts,
+                    l.average(),
+                    l.max
+                );
+            }
+        }
+        ctx.wait_until(round_start + period).await?;
+    }
+}
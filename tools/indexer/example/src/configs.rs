@@ -18,11 +18,22 @@ pub(crate) struct Opts {
 #[derive(Parser, Debug)]
 pub(crate) enum SubCommand {
     /// Run NEAR Indexer Example. Start observe the network
-    Run,
+    Run(RunArgs),
     /// Initialize necessary configs
     Init(InitConfigArgs),
 }
 
+#[derive(Parser, Debug)]
+pub(crate) struct RunArgs {
+    /// Publish the blocks to the NATS server at this address instead of
+    /// logging them.
+    #[clap(long)]
+    pub nats_address: Option<String>,
+    /// Subject to publish the blocks on.
+    #[clap(long, default_value = "near.blocks")]
+    pub nats_subject: String,
+}
+
 #[derive(Parser, Debug)]
 pub(crate) struct InitConfigArgs {
     /// chain/network id (localnet, testnet, devnet, betanet)
@@ -270,7 +270,7 @@ fn main() -> Result<()> {
     let home_dir = opts.home_dir.unwrap_or(near_indexer::get_default_home());
 
     match opts.subcmd {
-        SubCommand::Run => {
+        SubCommand::Run(args) => {
             let indexer_config = near_indexer::IndexerConfig {
                 home_dir,
                 sync_mode: near_indexer::SyncModeEnum::FromInterruption,
@@ -280,8 +280,19 @@ fn main() -> Result<()> {
             let system = actix::System::new();
             system.block_on(async move {
                 let indexer = near_indexer::Indexer::new(indexer_config).expect("Indexer::new()");
-                let stream = indexer.streamer();
-                actix::spawn(listen_blocks(stream));
+                match args.nats_address {
+                    Some(address) => {
+                        let config =
+                            near_indexer::NatsSinkConfig { address, subject: args.nats_subject };
+                        near_indexer::NatsSink::open(&indexer, config)
+                            .and_then(|sink| sink.start(&indexer))
+                            .expect("NatsSink::start()");
+                    }
+                    None => {
+                        let stream = indexer.streamer();
+                        actix::spawn(listen_blocks(stream));
+                    }
+                }
             });
             system.run()?;
         }
@@ -21,8 +21,10 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::time::Clock;
 use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::version::PROTOCOL_VERSION;
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
@@ -45,11 +47,29 @@ pub struct MockIncomingRequestsConfig {
     chunk_request: Option<MockIncomingRequestConfig>,
 }
 
+// Per-request-type overrides of `MockNetworkConfig::response_delay`, which make it
+// possible to model peers that serve e.g. headers much faster than chunks.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct MockResponseDelaysConfig {
+    // Delay before replying to BlockRequest
+    block: Option<Duration>,
+    // Delay before replying to BlockHeadersRequest
+    block_headers: Option<Duration>,
+    // Delay before replying to PartialEncodedChunkRequest
+    chunk: Option<Duration>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct MockNetworkConfig {
     #[serde(default = "default_delay")]
     // How long we'll wait until sending replies to the client
     pub response_delay: Duration,
+    #[serde(default)]
+    // Overrides of `response_delay` for specific request types
+    pub response_delays: MockResponseDelaysConfig,
+    #[serde(default = "default_num_peers")]
+    // Number of simulated peers, all of which serve the same chain history
+    pub num_peers: NonZeroUsize,
     pub incoming_requests: Option<MockIncomingRequestsConfig>,
 }
 
@@ -72,9 +92,18 @@ fn default_delay() -> Duration {
     MOCK_DEFAULT_NETWORK_DELAY
 }
 
+fn default_num_peers() -> NonZeroUsize {
+    NonZeroUsize::new(1).unwrap()
+}
+
 impl Default for MockNetworkConfig {
     fn default() -> Self {
-        Self { response_delay: default_delay(), incoming_requests: None }
+        Self {
+            response_delay: default_delay(),
+            response_delays: MockResponseDelaysConfig::default(),
+            num_peers: default_num_peers(),
+            incoming_requests: None,
+        }
     }
 }
 
@@ -188,6 +217,9 @@ impl IncomingRequests {
 /// MockPeerManagerActor mocks PeerManagerActor and responds to messages from ClientActor.
 /// Instead of sending these messages out to other peers, it simulates a network and reads
 /// the needed block and chunk content from storage.
+/// It can simulate any number of peers, all of which serve the same chain
+/// history, and reply to each kind of request with its own delay, which makes it
+/// usable for deterministic sync benchmarks against a real ClientActor.
 /// MockPeerManagerActor has the following responsibilities
 /// - Responds to the requests sent from ClientActor, including
 ///     BlockRequest, BlockHeadersRequest and PartialEncodedChunkRequest
@@ -204,6 +236,8 @@ pub struct MockPeerManagerActor {
     block_production_delay: Duration,
     /// Simulated network delay
     network_delay: Duration,
+    /// Per-request-type overrides of `network_delay`
+    response_delays: MockResponseDelaysConfig,
     /// The simulated peers will stop producing new blocks at this height
     target_height: BlockHeight,
     incoming_requests: IncomingRequests,
@@ -221,37 +255,48 @@ impl MockPeerManagerActor {
         network_config: &MockNetworkConfig,
     ) -> Self {
         let start_block_hash = chain.get_block_hash_by_height(network_start_height).unwrap();
-        // for now, we only simulate one peer
-        // we will add more complicated network config in the future
-        let peer = FullPeerInfo {
-            peer_info: PeerInfo::random(),
-            chain_info: near_network::types::PeerChainInfo {
-                genesis_id: GenesisId {
-                    chain_id: genesis_config.chain_id.clone(),
-                    hash: *chain.genesis().hash(),
+        let peers: Vec<_> = (0..network_config.num_peers.get())
+            .map(|_| FullPeerInfo {
+                peer_info: PeerInfo::random(),
+                chain_info: near_network::types::PeerChainInfo {
+                    genesis_id: GenesisId {
+                        chain_id: genesis_config.chain_id.clone(),
+                        hash: *chain.genesis().hash(),
+                    },
+                    tracked_shards: (0..genesis_config.shard_layout.num_shards()).collect(),
+                    archival: false,
+                    earliest_height: None,
+                    protocol_version: PROTOCOL_VERSION,
+                    last_block: Some(BlockInfo {
+                        height: network_start_height,
+                        hash: start_block_hash,
+                    }),
                 },
-                tracked_shards: (0..genesis_config.shard_layout.num_shards()).collect(),
-                archival: false,
-                last_block: Some(BlockInfo {
-                    height: network_start_height,
-                    hash: start_block_hash,
-                }),
-            },
-        };
+            })
+            .collect();
         let network_info = NetworkInfo {
-            connected_peers: vec![ConnectedPeerInfo {
-                full_peer_info: peer.clone(),
-                received_bytes_per_sec: 0,
-                sent_bytes_per_sec: 0,
-                last_time_peer_requested: time::Instant::now(),
-                last_time_received_message: time::Instant::now(),
-                connection_established_time: time::Instant::now(),
-                peer_type: PeerType::Outbound,
-                nonce: 1,
-            }],
-            num_connected_peers: 1,
-            peer_max_count: 1,
-            highest_height_peers: vec![<FullPeerInfo as Into<Option<_>>>::into(peer).unwrap()],
+            connected_peers: peers
+                .iter()
+                .enumerate()
+                .map(|(i, peer)| ConnectedPeerInfo {
+                    full_peer_info: peer.clone(),
+                    received_bytes_per_sec: 0,
+                    sent_bytes_per_sec: 0,
+                    last_time_peer_requested: time::Instant::now(),
+                    last_time_received_message: time::Instant::now(),
+                    connection_established_time: time::Instant::now(),
+                    peer_type: PeerType::Outbound,
+                    nonce: i as u64 + 1,
+                    messages_to_send: 0,
+                    bytes_to_send: 0,
+                })
+                .collect(),
+            num_connected_peers: peers.len(),
+            peer_max_count: peers.len() as u32,
+            highest_height_peers: peers
+                .into_iter()
+                .map(|peer| <FullPeerInfo as Into<Option<_>>>::into(peer).unwrap())
+                .collect(),
             sent_bytes_per_sec: 0,
             received_bytes_per_sec: 0,
             known_producers: vec![],
@@ -271,6 +316,7 @@ impl MockPeerManagerActor {
             network_info,
             block_production_delay,
             network_delay: network_config.response_delay,
+            response_delays: network_config.response_delays.clone(),
             target_height,
             incoming_requests,
         }
@@ -399,7 +445,8 @@ impl Handler<WithSpanContext<PeerManagerMessageRequest>> for MockPeerManagerActo
         match msg {
             PeerManagerMessageRequest::NetworkRequests(request) => match request {
                 NetworkRequests::BlockRequest { hash, peer_id } => {
-                    run_later(ctx, self.network_delay, move |act, _ctx| {
+                    let delay = self.response_delays.block.unwrap_or(self.network_delay);
+                    run_later(ctx, delay, move |act, _ctx| {
                         let block = act.chain_history_access.retrieve_block(&hash).unwrap();
                         actix::spawn({
                             let client = act.client.clone();
@@ -408,7 +455,8 @@ impl Handler<WithSpanContext<PeerManagerMessageRequest>> for MockPeerManagerActo
                     });
                 }
                 NetworkRequests::BlockHeadersRequest { hashes, peer_id } => {
-                    run_later(ctx, self.network_delay, move |act, _ctx| {
+                    let delay = self.response_delays.block_headers.unwrap_or(self.network_delay);
+                    run_later(ctx, delay, move |act, _ctx| {
                         let headers = act
                             .chain_history_access
                             .retrieve_block_headers(hashes.clone())
@@ -420,18 +468,27 @@ impl Handler<WithSpanContext<PeerManagerMessageRequest>> for MockPeerManagerActo
                     });
                 }
                 NetworkRequests::PartialEncodedChunkRequest { request, .. } => {
-                    run_later(ctx, self.network_delay, move |act, _ctx| {
+                    let delay = self.response_delays.chunk.unwrap_or(self.network_delay);
+                    run_later(ctx, delay, move |act, _ctx| {
                         let response = act
                             .chain_history_access
                             .retrieve_partial_encoded_chunk(&request)
                             .unwrap();
                         actix::spawn({
                             let client = act.client.clone();
+                            let peer_id = act.network_info.connected_peers[0]
+                                .full_peer_info
+                                .peer_info
+                                .id
+                                .clone();
                             async move {
+                                // The mock network doesn't encode the messages.
                                 client
                                     .partial_encoded_chunk_response(
                                         response,
                                         Clock::instant().into(),
+                                        peer_id,
+                                        0,
                                     )
                                     .await
                             }
@@ -523,7 +580,7 @@ impl ChainHistoryAccess {
 
 #[cfg(test)]
 mod test {
-    use crate::ChainHistoryAccess;
+    use crate::{ChainHistoryAccess, MockNetworkConfig, MOCK_DEFAULT_NETWORK_DELAY};
     use near_chain::ChainGenesis;
     use near_chain::{Chain, RuntimeWithEpochManagerAdapter};
     use near_chain_configs::Genesis;
@@ -600,4 +657,21 @@ mod test {
             chain_history_access.retrieve_block_headers(vec![*block.hash()]).unwrap();
         }
     }
+
+    #[test]
+    fn test_mock_network_config_defaults() {
+        let config: MockNetworkConfig = serde_json::from_str(
+            r#"{"num_peers": 3, "response_delays": {"chunk": {"secs": 1, "nanos": 0}}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.num_peers.get(), 3);
+        assert_eq!(config.response_delay, MOCK_DEFAULT_NETWORK_DELAY);
+        assert_eq!(config.response_delays.chunk, Some(std::time::Duration::from_secs(1)));
+        assert_eq!(config.response_delays.block, None);
+    }
+
+    #[test]
+    fn test_mock_network_config_rejects_no_peers() {
+        assert!(serde_json::from_str::<MockNetworkConfig>(r#"{"num_peers": 0}"#).is_err());
+    }
 }
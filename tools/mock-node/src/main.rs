@@ -16,6 +16,7 @@ use near_network::test_utils::wait_or_timeout;
 use near_o11y::testonly::init_integration_logger;
 use near_o11y::WithSpanContextExt;
 use near_primitives::types::BlockHeight;
+use std::num::NonZeroUsize;
 use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
@@ -60,6 +61,10 @@ struct Cli {
     /// Simulated network delay (in ms)
     #[clap(short = 'd', long)]
     network_delay: Option<u64>,
+    /// Number of simulated peers serving the chain history.  Overrides
+    /// `num_peers` from `mock.json`.
+    #[clap(long)]
+    num_peers: Option<NonZeroUsize>,
     /// If specified, the binary will set up client home dir before starting the
     /// client node so head of the client chain will be the specified height
     /// when the client starts. The given height must be the last block in an
@@ -117,6 +122,9 @@ fn main() -> anyhow::Result<()> {
     if let Some(delay) = args.network_delay {
         network_config.response_delay = Duration::from_millis(delay);
     }
+    if let Some(num_peers) = args.num_peers {
+        network_config.num_peers = num_peers;
+    }
 
     let client_height = args.start_height.unwrap_or(args.client_height);
     let network_height = args.start_height.or(args.network_height);
@@ -3,12 +3,17 @@
 use crate::{MockNetworkConfig, MockPeerManagerActor};
 use actix::{Actor, Addr, Arbiter};
 use anyhow::Context;
+use near_chain::state_parts_cache::StatePartsCache;
 use near_chain::types::RuntimeAdapter;
 use near_chain::ChainStoreUpdate;
 use near_chain::{Chain, ChainGenesis, ChainStore, ChainStoreAccess, DoomslugThresholdMode};
 use near_chain_configs::GenesisConfig;
-use near_client::{start_client, start_view_client, ClientActor, ViewClientActor};
+use near_client::{
+    start_client, start_view_client, BlockTimestampIndex, ClientActor, GasPriceWindow,
+    SyncServingBudget, ViewClientActor,
+};
 use near_epoch_manager::{EpochManager, EpochManagerAdapter};
+use near_network::time;
 use near_network::types::NetworkRecipient;
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::get_num_state_parts;
@@ -20,7 +25,7 @@ use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::cmp::min;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 fn setup_runtime(
@@ -235,7 +240,10 @@ pub fn setup_mock_node(
     }
 
     let block_production_delay = config.client_config.min_block_production_delay;
+    let block_timestamp_index = Arc::new(Mutex::new(BlockTimestampIndex::new()));
+    let gas_price_window = Arc::new(Mutex::new(GasPriceWindow::new()));
     let (client, _) = start_client(
+        time::Clock::real(),
         config.client_config.clone(),
         chain_genesis.clone(),
         client_runtime.clone(),
@@ -246,6 +254,8 @@ pub fn setup_mock_node(
         None,
         adv.clone(),
         None,
+        block_timestamp_index.clone(),
+        gas_price_window.clone(),
     );
 
     let view_client = start_view_client(
@@ -254,6 +264,10 @@ pub fn setup_mock_node(
         client_runtime,
         network_adapter.clone(),
         config.client_config.clone(),
+        block_timestamp_index,
+        Arc::new(Mutex::new(SyncServingBudget::from_config(&config.client_config))),
+        gas_price_window,
+        Arc::new(StatePartsCache::new(config.client_config.state_parts_cache_size)),
         adv,
     );
 
@@ -295,7 +309,10 @@ pub fn setup_mock_node(
             client.clone(),
             view_client.clone(),
             None,
+            None,
+            None,
         )
+        .unwrap()
     });
 
     MockNode { client, view_client, servers, target_height }
@@ -240,6 +240,9 @@ fn load_snapshot(load_cmd: LoadCmd) {
         ChainConfig {
             save_trie_changes: config.client_config.save_trie_changes,
             background_migration_threads: 1,
+            save_account_outcome_ids: config.client_config.save_account_outcome_ids,
+            slow_chunk_threshold: None,
+            apply_chunks_threads: None,
         },
     )
     .unwrap();
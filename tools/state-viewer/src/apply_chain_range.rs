@@ -422,7 +422,7 @@ pub fn apply_chain_range(
  * ChunkExtra versions in database and produced by `neard` playback. Consider them equal as
  * long as the content is equal.
  */
-fn smart_equals(extra1: &ChunkExtra, extra2: &ChunkExtra) -> bool {
+pub(crate) fn smart_equals(extra1: &ChunkExtra, extra2: &ChunkExtra) -> bool {
     if (extra1.outcome_root() != extra2.outcome_root())
         || (extra1.state_root() != extra2.state_root())
         || (extra1.gas_limit() != extra2.gas_limit())
@@ -1,5 +1,7 @@
 use crate::commands::*;
+use crate::diff_state::diff_state;
 use crate::dump_state_parts::dump_state_parts;
+use crate::replay::replay_execution;
 use crate::rocksdb_stats::get_rocksdb_stats;
 use crate::{dump_state_parts, epoch_info};
 use clap::{Args, Parser, Subcommand};
@@ -9,9 +11,10 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{BlockHeight, ShardId};
 use near_store::{Mode, NodeStorage, Store, Temperature};
-use nearcore::{load_config, NearConfig};
+use nearcore::{load_config, NearConfig, NightshadeRuntime};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
 #[derive(Subcommand)]
 #[clap(subcommand_required = true, arg_required_else_help = true)]
@@ -42,12 +45,19 @@ pub enum StateViewerSubCommand {
     /// List account names with contracts deployed.
     #[clap(alias = "contract_accounts")]
     ContractAccounts(ContractAccountsCmd),
+    /// Print the keys of a shard whose values differ between the states after
+    /// two blocks, with a summary of the changes per column.  Only the parts
+    /// of the tries which differ are read.
+    DiffState(DiffStateCmd),
     /// Dump contract data in storage of given account to binary file.
     #[clap(alias = "dump_account_storage")]
     DumpAccountStorage(DumpAccountStorageCmd),
     /// Dump deployed contract code of given account to wasm file.
     #[clap(alias = "dump_code")]
     DumpCode(DumpCodeCmd),
+    /// Dump account, contract code and contract data of given account to a
+    /// genesis records file.
+    DumpContractState(DumpContractStateCmd),
     /// Generate a genesis file from the current state of the DB.
     #[clap(alias = "dump_state")]
     DumpState(DumpStateCmd),
@@ -69,7 +79,9 @@ pub enum StateViewerSubCommand {
     Peers,
     /// Looks up a certain receipt.
     Receipts(ReceiptsCmd),
-    /// Replay headers from chain.
+    /// Replay headers from chain, or with `--execute` re-execute chunks of a
+    /// range of blocks and report the first divergence from the results
+    /// recorded in the store.
     Replay(ReplayCmd),
     /// Dump stats for the RocksDB storage.
     #[clap(name = "rocksdb-stats", alias = "rocksdb_stats")]
@@ -114,8 +126,10 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::CheckBlock => check_block_chunk_existence(near_config, store),
             StateViewerSubCommand::Chunks(cmd) => cmd.run(near_config, store),
             StateViewerSubCommand::ContractAccounts(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::DiffState(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpAccountStorage(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpCode(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::DumpContractState(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpState(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpStateParts(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpStateRedis(cmd) => cmd.run(home_dir, near_config, store),
@@ -273,6 +287,37 @@ impl ContractAccountsCmd {
     }
 }
 
+#[derive(Parser)]
+pub struct DiffStateCmd {
+    /// Shard to compare.
+    #[clap(long = "shard", alias = "shard-id")]
+    shard_id: ShardId,
+    /// Height of the block with the old state.
+    #[clap(long)]
+    block_a: BlockHeight,
+    /// Height of the block with the new state.
+    #[clap(long)]
+    block_b: BlockHeight,
+    /// Only compare the records of this account.
+    #[clap(long)]
+    account_id: Option<AccountId>,
+}
+
+impl DiffStateCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        diff_state(
+            self.shard_id,
+            self.block_a,
+            self.block_b,
+            self.account_id,
+            home_dir,
+            near_config,
+            store,
+        )
+        .unwrap();
+    }
+}
+
 #[derive(Parser)]
 pub struct DumpAccountStorageCmd {
     #[clap(long)]
@@ -313,6 +358,31 @@ impl DumpCodeCmd {
     }
 }
 
+#[derive(Parser)]
+pub struct DumpContractStateCmd {
+    #[clap(long)]
+    account_id: AccountId,
+    #[clap(long, parse(from_os_str))]
+    output: PathBuf,
+    /// Height of the block whose state to dump, or `latest`.
+    #[clap(long, default_value = "latest")]
+    block_height: String,
+}
+
+impl DumpContractStateCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        dump_contract_state(
+            self.account_id,
+            &self.output,
+            self.block_height,
+            home_dir,
+            near_config,
+            store,
+        )
+        .unwrap();
+    }
+}
+
 #[derive(Parser)]
 pub struct DumpStateCmd {
     /// Optionally, can specify at which height to dump state.
@@ -456,6 +526,10 @@ pub struct EpochInfoCmd {
     /// Displays kickouts of the given validator and expected and missed blocks and chunks produced.
     #[clap(long)]
     validator_account_id: Option<String>,
+    /// Prints the validators, stake distribution, seat price, production
+    /// statistics and kickouts of the epochs as JSON.
+    #[clap(long)]
+    json: bool,
 }
 
 impl EpochInfoCmd {
@@ -463,6 +537,7 @@ impl EpochInfoCmd {
         print_epoch_info(
             self.epoch_selection,
             self.validator_account_id.map(|s| AccountId::from_str(&s).unwrap()),
+            self.json,
             home_dir,
             near_config,
             store,
@@ -498,15 +573,41 @@ impl ReceiptsCmd {
 
 #[derive(Parser)]
 pub struct ReplayCmd {
-    #[clap(long)]
-    start_index: BlockHeight,
-    #[clap(long)]
-    end_index: BlockHeight,
+    /// Height of the first block to replay.
+    #[clap(long, alias = "start-index")]
+    from_height: BlockHeight,
+    /// Height of the last block to replay, inclusive.
+    #[clap(long, alias = "end-index")]
+    to_height: BlockHeight,
+    /// Re-execute the chunks and compare the results with the recorded ones,
+    /// instead of only replaying the headers, feeding them to the epoch
+    /// manager.
+    #[clap(long)]
+    execute: bool,
+    /// Shard to re-execute.  All shards tracked by the node are re-executed if
+    /// not set.
+    #[clap(long, requires = "execute")]
+    shard_id: Option<ShardId>,
 }
 
 impl ReplayCmd {
     pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
-        replay_chain(self.start_index, self.end_index, home_dir, near_config, store);
+        if !self.execute {
+            replay_chain(self.from_height, self.to_height, home_dir, near_config, store);
+            return;
+        }
+        let runtime_adapter =
+            Arc::new(NightshadeRuntime::from_config(home_dir, store.clone(), &near_config));
+        let report = replay_execution(
+            self.from_height,
+            self.to_height,
+            self.shard_id,
+            store,
+            near_config.genesis.config.genesis_height,
+            runtime_adapter,
+        )
+        .unwrap();
+        println!("{}", report);
     }
 }
 
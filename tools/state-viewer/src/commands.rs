@@ -12,17 +12,17 @@ use near_chain::types::{ApplyTransactionResult, BlockHeaderInfo};
 use near_chain::{
     ChainStore, ChainStoreAccess, ChainStoreUpdate, Error, RuntimeWithEpochManagerAdapter,
 };
-use near_chain_configs::GenesisChangeConfig;
+use near_chain_configs::{GenesisChangeConfig, GenesisRecords};
 use near_epoch_manager::{EpochManager, EpochManagerAdapter};
 use near_network::iter_peers_from_store;
 use near_primitives::account::id::AccountId;
 use near_primitives::block::{Block, BlockHeader};
 use near_primitives::hash::CryptoHash;
-use near_primitives::shard_layout::ShardLayout;
 use near_primitives::shard_layout::ShardUId;
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
 use near_primitives::sharding::ChunkHash;
 use near_primitives::state_record::StateRecord;
-use near_primitives::trie_key::TrieKey;
+use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::{chunk_extra::ChunkExtra, BlockHeight, ShardId, StateRoot};
 use near_primitives_core::types::Gas;
 use near_store::db::Database;
@@ -224,15 +224,8 @@ pub(crate) fn dump_account_storage(
     near_config: NearConfig,
     store: Store,
 ) {
-    let block_height = if block_height == "latest" {
-        LoadTrieMode::Latest
-    } else if let Ok(height) = block_height.parse::<u64>() {
-        LoadTrieMode::Height(height)
-    } else {
-        panic!("block_height should be either number or \"latest\"")
-    };
     let (runtime, state_roots, header) =
-        load_trie_stop_at_height(store, home_dir, &near_config, block_height);
+        load_trie_stop_at_height(store, home_dir, &near_config, parse_block_height(&block_height));
     for (shard_id, state_root) in state_roots.iter().enumerate() {
         let trie = runtime
             .get_trie_for_shard(shard_id as u64, header.prev_hash(), state_root.clone(), false)
@@ -264,6 +257,58 @@ pub(crate) fn dump_account_storage(
     std::process::exit(1);
 }
 
+/// Dumps the account record, the contract code and all the contract data of
+/// an account as genesis state records, so that they can be imported into a
+/// genesis, e.g. with `neard localnet --extra-records`.
+pub(crate) fn dump_contract_state(
+    account_id: AccountId,
+    output: &Path,
+    block_height: String,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) -> anyhow::Result<()> {
+    let (runtime, state_roots, header) =
+        load_trie_stop_at_height(store, home_dir, &near_config, parse_block_height(&block_height));
+    let shard_layout = runtime.get_shard_layout(header.epoch_id())?;
+    let shard_id = account_id_to_shard_id(&account_id, &shard_layout);
+    let trie = runtime.get_trie_for_shard(
+        shard_id,
+        header.prev_hash(),
+        state_roots[shard_id as usize],
+        false,
+    )?;
+
+    let mut records = vec![];
+    for key in [
+        TrieKey::Account { account_id: account_id.clone() },
+        TrieKey::ContractCode { account_id: account_id.clone() },
+    ] {
+        let key = key.to_vec();
+        if let Some(value) = trie.get(&key)? {
+            records.extend(StateRecord::from_raw_key_value(key, value));
+        }
+    }
+    if records.is_empty() {
+        anyhow::bail!("Account {} does not exist at height {}", account_id, header.height());
+    }
+    let mut iter = trie.iter()?;
+    iter.seek_prefix(&trie_key_parsers::get_raw_prefix_for_contract_data(&account_id, &[]))?;
+    for item in iter {
+        let (key, value) = item?;
+        records.extend(StateRecord::from_raw_key_value(key, value));
+    }
+
+    GenesisRecords(records).to_file(output);
+    println!(
+        "Dumped contract state of account {} at height {} into file {}",
+        account_id,
+        header.height(),
+        output.display()
+    );
+    Ok(())
+}
+
 pub(crate) fn dump_code(
     account_id: String,
     output: &Path,
@@ -713,6 +758,7 @@ pub(crate) fn check_block_chunk_existence(near_config: NearConfig, store: Store)
 pub(crate) fn print_epoch_info(
     epoch_selection: epoch_info::EpochSelection,
     validator_account_id: Option<AccountId>,
+    json: bool,
     home_dir: &Path,
     near_config: NearConfig,
     store: Store,
@@ -729,6 +775,7 @@ pub(crate) fn print_epoch_info(
     epoch_info::print_epoch_info(
         epoch_selection,
         validator_account_id,
+        json,
         store,
         &mut chain_store,
         &mut epoch_manager,
@@ -778,7 +825,18 @@ enum LoadTrieMode {
     LastFinalFromHeight(BlockHeight),
 }
 
-fn load_trie(
+/// Parses a block height given either as a number or as `latest`.
+fn parse_block_height(block_height: &str) -> LoadTrieMode {
+    if block_height == "latest" {
+        LoadTrieMode::Latest
+    } else if let Ok(height) = block_height.parse::<u64>() {
+        LoadTrieMode::Height(height)
+    } else {
+        panic!("block_height should be either number or \"latest\"")
+    }
+}
+
+pub(crate) fn load_trie(
     store: Store,
     home_dir: &Path,
     near_config: &NearConfig,
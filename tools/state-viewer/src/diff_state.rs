@@ -0,0 +1,138 @@
+//! Comparison of the state of a shard after two blocks.
+//!
+//! The tries are compared node by node skipping the subtrees they share, so
+//! the cost depends on how much the state changed between the blocks rather
+//! than on the size of the state.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeWithEpochManagerAdapter};
+use near_primitives::serialize::to_base64;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::trie_key_parsers;
+use near_primitives::types::{AccountId, BlockHeight, ShardId, StateRoot};
+use near_store::{Store, TrieDiffItem};
+use nearcore::{NearConfig, NightshadeRuntime};
+
+pub(crate) fn diff_state(
+    shard_id: ShardId,
+    height_a: BlockHeight,
+    height_b: BlockHeight,
+    account_id: Option<AccountId>,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) -> anyhow::Result<()> {
+    let chain_store = ChainStore::new(
+        store.clone(),
+        near_config.genesis.config.genesis_height,
+        near_config.client_config.save_trie_changes,
+    );
+    let runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter> =
+        Arc::new(NightshadeRuntime::from_config(home_dir, store, &near_config));
+    let (shard_uid, root_a) =
+        state_root(&chain_store, runtime_adapter.as_ref(), height_a, shard_id)?;
+    let (shard_uid_b, root_b) =
+        state_root(&chain_store, runtime_adapter.as_ref(), height_b, shard_id)?;
+    if shard_uid != shard_uid_b {
+        anyhow::bail!(
+            "Shard {} is {} at height {} but {} at height {}; can't compare states of different \
+             shard layouts",
+            shard_id,
+            shard_uid,
+            height_a,
+            shard_uid_b,
+            height_b
+        );
+    }
+    println!("State root at height {}: {}", height_a, root_a);
+    println!("State root at height {}: {}", height_b, root_b);
+
+    let trie = runtime_adapter.get_tries().get_trie_for_shard(shard_uid, root_a);
+    let items = match &account_id {
+        None => trie.diff(&root_b, &[])?,
+        Some(account_id) => {
+            let mut items = vec![];
+            for prefix in trie_key_parsers::get_raw_prefixes_for_account(account_id) {
+                items.extend(trie.diff(&root_b, &prefix)?.into_iter().filter(|item| {
+                    trie_key_parsers::parse_account_id_from_raw_key(&item.key)
+                        .ok()
+                        .flatten()
+                        .as_ref()
+                        == Some(account_id)
+                }));
+            }
+            items
+        }
+    };
+
+    // Per column: number of added, removed and changed keys and the change of
+    // the size of the keys and values in bytes.
+    let mut summary = BTreeMap::<&str, (u64, u64, u64, i64)>::new();
+    for item in &items {
+        let column = trie_key_parsers::get_column_name(&item.key).unwrap_or("Unknown");
+        let stats = summary.entry(column).or_default();
+        let old_size = item.old_value.as_ref().map_or(0, |value| item.key.len() + value.len());
+        let new_size = item.new_value.as_ref().map_or(0, |value| item.key.len() + value.len());
+        stats.3 += new_size as i64 - old_size as i64;
+        let marker = match (&item.old_value, &item.new_value) {
+            (None, _) => {
+                stats.0 += 1;
+                '+'
+            }
+            (_, None) => {
+                stats.1 += 1;
+                '-'
+            }
+            _ => {
+                stats.2 += 1;
+                '~'
+            }
+        };
+        println!("{} {}", marker, format_item(column, item));
+    }
+
+    println!("{} keys differ", items.len());
+    for (column, (added, removed, changed, size_change)) in summary {
+        println!(
+            "{:>24}: {} added, {} removed, {} changed, {:+} bytes",
+            column, added, removed, changed, size_change
+        );
+    }
+    Ok(())
+}
+
+/// Returns the shard uid and the state root of the shard after the block at
+/// given height.
+fn state_root(
+    chain_store: &ChainStore,
+    runtime_adapter: &dyn RuntimeWithEpochManagerAdapter,
+    height: BlockHeight,
+    shard_id: ShardId,
+) -> anyhow::Result<(ShardUId, StateRoot)> {
+    let header = chain_store.get_block_header_by_height(height)?;
+    let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, header.epoch_id())?;
+    let chunk_extra = chain_store.get_chunk_extra(header.hash(), &shard_uid).map_err(|err| {
+        anyhow::anyhow!(
+            "No state of shard {} at height {}, is the shard tracked? {}",
+            shard_id,
+            height,
+            err
+        )
+    })?;
+    Ok((shard_uid, *chunk_extra.state_root()))
+}
+
+fn format_item(column: &str, item: &TrieDiffItem) -> String {
+    let account_id = trie_key_parsers::parse_account_id_from_raw_key(&item.key).ok().flatten();
+    let size = |value: &Option<Vec<u8>>| value.as_ref().map_or(0, Vec::len);
+    format!(
+        "{} {} {}: {} -> {} bytes",
+        column,
+        account_id.as_ref().map_or("-", |account_id| account_id.as_str()),
+        to_base64(&item.key),
+        size(&item.old_value),
+        size(&item.new_value)
+    )
+}
@@ -7,8 +7,17 @@ use near_primitives::account::id::AccountId;
 use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::epoch_manager::AGGREGATOR_KEY;
 use near_primitives::hash::CryptoHash;
-use near_primitives::types::{BlockHeight, EpochHeight, EpochId, ProtocolVersion, ShardId};
+use near_primitives::serialize::dec_format;
+use near_primitives::types::{
+    Balance, BlockHeight, EpochHeight, EpochId, NumBlocks, ProtocolVersion, ShardId,
+    ValidatorInfoIdentifier,
+};
+use near_primitives::views::{CurrentEpochValidatorInfo, ValidatorKickoutView};
 use near_store::{DBCol, Store};
+use nearcore::config::NEAR_BASE;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -33,6 +42,7 @@ pub(crate) enum EpochSelection {
 pub(crate) fn print_epoch_info(
     epoch_selection: EpochSelection,
     validator_account_id: Option<AccountId>,
+    json: bool,
     store: Store,
     chain_store: &mut ChainStore,
     epoch_manager: &mut EpochManager,
@@ -40,8 +50,8 @@ pub(crate) fn print_epoch_info(
 ) {
     let epoch_ids = get_epoch_ids(epoch_selection, store, chain_store, epoch_manager);
 
-    let head_block_info =
-        epoch_manager.get_block_info(&chain_store.head().unwrap().last_block_hash).unwrap();
+    let head_block_hash = chain_store.head().unwrap().last_block_hash;
+    let head_block_info = epoch_manager.get_block_info(&head_block_hash).unwrap();
     let head_epoch_height =
         epoch_manager.get_epoch_info(head_block_info.epoch_id()).unwrap().epoch_height();
     let mut epoch_infos: Vec<(EpochId, Arc<EpochInfo>)> = epoch_ids
@@ -51,8 +61,28 @@ pub(crate) fn print_epoch_info(
     // Sorted output is much easier to follow.
     epoch_infos.sort_by_key(|(_, epoch_info)| epoch_info.epoch_height());
 
+    if json {
+        let summaries: Vec<EpochSummary> = epoch_infos
+            .iter()
+            .map(|(epoch_id, epoch_info)| {
+                EpochSummary::new(
+                    epoch_id,
+                    epoch_info,
+                    head_epoch_height,
+                    &head_block_hash,
+                    epoch_manager,
+                )
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&summaries).unwrap());
+        return;
+    }
+
     for (epoch_id, epoch_info) in &epoch_infos {
         println!("-------------------------");
+        EpochSummary::new(epoch_id, epoch_info, head_epoch_height, &head_block_hash, epoch_manager)
+            .display();
+        println!("---");
         display_epoch_info(
             epoch_id,
             epoch_info,
@@ -77,6 +107,156 @@ pub(crate) fn print_epoch_info(
     println!("Found {} epochs", epoch_ids.len());
 }
 
+/// Validator set of an epoch together with the stake distribution, seat price
+/// and, for epochs which have already started, block and chunk production
+/// statistics.
+#[derive(Serialize)]
+struct EpochSummary {
+    epoch_id: EpochId,
+    epoch_height: EpochHeight,
+    protocol_version: ProtocolVersion,
+    #[serde(with = "dec_format")]
+    seat_price: Balance,
+    #[serde(with = "dec_format")]
+    total_stake: Balance,
+    validators: Vec<ValidatorSummary>,
+    /// Validators kicked out when this epoch's validator set was selected.
+    kickouts: Vec<ValidatorKickoutView>,
+}
+
+#[derive(Serialize)]
+struct ValidatorSummary {
+    account_id: AccountId,
+    #[serde(with = "dec_format")]
+    stake: Balance,
+    /// Fraction of the total stake of the epoch.
+    stake_share: f64,
+    block_producer_seats: usize,
+    shards: Vec<ShardId>,
+    /// Production statistics are `None` for epochs which haven't started yet.
+    num_produced_blocks: Option<NumBlocks>,
+    num_expected_blocks: Option<NumBlocks>,
+    num_produced_chunks: Option<NumBlocks>,
+    num_expected_chunks: Option<NumBlocks>,
+}
+
+impl EpochSummary {
+    fn new(
+        epoch_id: &EpochId,
+        epoch_info: &EpochInfo,
+        head_epoch_height: EpochHeight,
+        head_block_hash: &CryptoHash,
+        epoch_manager: &EpochManager,
+    ) -> Self {
+        // Statistics of finished epochs are kept in the epoch summary while
+        // statistics of the current epoch need to be aggregated up to the head.
+        let identifier = match epoch_info.epoch_height().cmp(&head_epoch_height) {
+            Ordering::Less => Some(ValidatorInfoIdentifier::EpochId(epoch_id.clone())),
+            Ordering::Equal => Some(ValidatorInfoIdentifier::BlockHash(*head_block_hash)),
+            Ordering::Greater => None,
+        };
+        let stats: HashMap<AccountId, CurrentEpochValidatorInfo> = identifier
+            .and_then(|identifier| epoch_manager.get_validator_info(identifier).ok())
+            .map(|info| {
+                info.current_validators
+                    .into_iter()
+                    .map(|validator| (validator.account_id.clone(), validator))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let total_stake: Balance = epoch_info.validators_iter().map(|v| v.stake()).sum();
+        let mut block_producer_seats = vec![0; epoch_info.validators_len()];
+        for validator_id in epoch_info.block_producers_settlement() {
+            block_producer_seats[*validator_id as usize] += 1;
+        }
+        let mut shards = vec![vec![]; epoch_info.validators_len()];
+        for (shard_id, validator_ids) in epoch_info.chunk_producers_settlement().iter().enumerate()
+        {
+            for validator_id in validator_ids {
+                shards[*validator_id as usize].push(shard_id as ShardId);
+            }
+        }
+
+        let validators = epoch_info
+            .validators_iter()
+            .enumerate()
+            .map(|(validator_id, validator)| {
+                let account_id = validator.account_id().clone();
+                let stats = stats.get(&account_id);
+                ValidatorSummary {
+                    stake: validator.stake(),
+                    stake_share: validator.stake() as f64 / total_stake.max(1) as f64,
+                    block_producer_seats: block_producer_seats[validator_id],
+                    shards: std::mem::take(&mut shards[validator_id]),
+                    num_produced_blocks: stats.map(|s| s.num_produced_blocks),
+                    num_expected_blocks: stats.map(|s| s.num_expected_blocks),
+                    num_produced_chunks: stats.map(|s| s.num_produced_chunks),
+                    num_expected_chunks: stats.map(|s| s.num_expected_chunks),
+                    account_id,
+                }
+            })
+            .collect();
+        let mut kickouts: Vec<ValidatorKickoutView> = epoch_info
+            .validator_kickout()
+            .iter()
+            .map(|(account_id, reason)| ValidatorKickoutView {
+                account_id: account_id.clone(),
+                reason: reason.clone(),
+            })
+            .collect();
+        kickouts.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+
+        Self {
+            epoch_id: epoch_id.clone(),
+            epoch_height: epoch_info.epoch_height(),
+            protocol_version: epoch_info.protocol_version(),
+            seat_price: epoch_info.seat_price(),
+            total_stake,
+            validators,
+            kickouts,
+        }
+    }
+
+    fn display(&self) {
+        println!(
+            "Epoch #{} {:?}, protocol version {}",
+            self.epoch_height, self.epoch_id, self.protocol_version
+        );
+        println!(
+            "Seat price: {} NEAR, total stake: {} NEAR, {} validators",
+            self.seat_price / NEAR_BASE,
+            self.total_stake / NEAR_BASE,
+            self.validators.len()
+        );
+        println!(
+            "{:<40} {:>15} {:>7} {:>6} {:>10} {:>15} {:>15}",
+            "Validator", "Stake (NEAR)", "Share", "Seats", "Shards", "Blocks", "Chunks"
+        );
+        let ratio =
+            |produced: Option<NumBlocks>, expected: Option<NumBlocks>| match (produced, expected) {
+                (Some(produced), Some(expected)) => format!("{}/{}", produced, expected),
+                _ => "-".to_string(),
+            };
+        for v in &self.validators {
+            let shards = format!("{:?}", v.shards);
+            println!(
+                "{:<40} {:>15} {:>6.2}% {:>6} {:>10} {:>15} {:>15}",
+                v.account_id.as_str(),
+                v.stake / NEAR_BASE,
+                v.stake_share * 100.0,
+                v.block_producer_seats,
+                shards,
+                ratio(v.num_produced_blocks, v.num_expected_blocks),
+                ratio(v.num_produced_chunks, v.num_expected_chunks),
+            );
+        }
+        for kickout in &self.kickouts {
+            println!("Kicked out {}: {:?}", kickout.account_id, kickout.reason);
+        }
+    }
+}
+
 fn display_block_and_chunk_producers(
     epoch_id: &EpochId,
     epoch_info: &EpochInfo,
@@ -0,0 +1,203 @@
+use crate::commands::dump_state;
+use anyhow::Context;
+use clap::Parser;
+use near_amend_genesis::{GenesisChanges, GenesisPatch};
+use near_chain_configs::{Genesis, GenesisChangeConfig, GenesisValidationMode};
+use near_crypto::{InMemorySigner, KeyType, Signer};
+use near_primitives::types::{AccountInfo, BlockHeight, BlockHeightDelta, NumBlocks};
+use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+use near_primitives::version::ProtocolVersion;
+use near_store::{Mode, NodeStorage};
+use nearcore::config::{Config, CONFIG_FILENAME, TESTING_INIT_STAKE};
+use nearcore::load_config;
+use std::path::{Path, PathBuf};
+
+const RECORDS_FILENAME: &str = "records.json";
+
+/// Create the home directory of a new chain whose genesis state is the state
+/// of this node's chain at a given block, with replaced validators.
+#[derive(Parser)]
+pub struct ForkCommand {
+    /// Height from which to take the state.  The state after the first final
+    /// block at or above this height is used.  Defaults to the head.
+    #[clap(long)]
+    height: Option<BlockHeight>,
+    /// Home directory of the new chain's node.
+    #[clap(long)]
+    output_dir: PathBuf,
+    /// Path to a JSON list of AccountInfos of the validators of the new chain,
+    /// in the format accepted by `amend-genesis --validators`.  If not set, a
+    /// single validator is created (see `--validator-key`) and its key is
+    /// written to the output directory.
+    #[clap(long)]
+    validators: Option<PathBuf>,
+    /// Path to the key file of the single validator created when
+    /// `--validators` isn't set.  By default a new random key for `node0` is
+    /// generated.
+    #[clap(long, conflicts_with = "validators")]
+    validator_key: Option<PathBuf>,
+    /// Use this node's validator key file for the single validator created
+    /// when `--validators` isn't set, instead of generating a new key.  The
+    /// key is then copied into the output directory.
+    #[clap(long, conflicts_with_all = &["validators", "validator_key"])]
+    reuse_validator_key: bool,
+    /// Path to extra records to add to the state, in the format accepted by
+    /// `amend-genesis --extra-records`.
+    #[clap(long)]
+    extra_records: Option<PathBuf>,
+    /// Chain ID of the new chain.
+    #[clap(long, default_value = "localnet")]
+    chain_id: String,
+    /// Protocol version of the new chain.  Defaults to the version of the block.
+    #[clap(long)]
+    protocol_version: Option<ProtocolVersion>,
+    /// Epoch length of the new chain.
+    #[clap(long)]
+    epoch_length: Option<BlockHeightDelta>,
+    /// Transaction validity period of the new chain.
+    #[clap(long)]
+    transaction_validity_period: Option<NumBlocks>,
+}
+
+impl ForkCommand {
+    pub fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = load_config(home_dir, genesis_validation)
+            .with_context(|| format!("Error loading config from {}", home_dir.display()))?;
+        let validator_key = match self.validator_key {
+            Some(path) => Some(path),
+            None if self.reuse_validator_key => {
+                Some(home_dir.join(&near_config.config.validator_key_file))
+            }
+            None => None,
+        };
+        let store = NodeStorage::opener(home_dir, &near_config.config.store, None)
+            .open_in_mode(Mode::ReadOnly)?
+            .get_hot_store();
+
+        // The state is dumped into a scratch directory first and then amended
+        // into the output directory.
+        let dump_dir = self.output_dir.join("fork-dump");
+        dump_state(
+            self.height,
+            true,
+            Some(dump_dir.clone()),
+            home_dir,
+            near_config,
+            store,
+            &GenesisChangeConfig::default(),
+        );
+
+        let (validators, validator_signer) = match self.validators {
+            Some(path) => (path, None),
+            None => {
+                let signer = load_or_generate_validator_key(validator_key.as_deref())?;
+                let path = dump_dir.join("validators.json");
+                let validators = vec![AccountInfo {
+                    account_id: signer.validator_id().clone(),
+                    public_key: signer.public_key(),
+                    amount: TESTING_INIT_STAKE,
+                }];
+                std::fs::write(&path, serde_json::to_vec_pretty(&validators)?)?;
+                (path, Some(signer))
+            }
+        };
+
+        let patch = GenesisPatch {
+            genesis: GenesisChanges {
+                chain_id: Some(self.chain_id),
+                protocol_version: self.protocol_version,
+                epoch_length: self.epoch_length,
+                transaction_validity_period: self.transaction_validity_period,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut config = Config {
+            genesis_records_file: Some(RECORDS_FILENAME.to_string()),
+            ..Config::default()
+        };
+        near_amend_genesis::amend_genesis(
+            &dump_dir.join(&config.genesis_file),
+            &self.output_dir.join(&config.genesis_file),
+            &dump_dir.join(RECORDS_FILENAME),
+            &self.output_dir.join(RECORDS_FILENAME),
+            self.extra_records.as_deref(),
+            Some(validators.as_path()),
+            None,
+            &patch,
+            100,
+            40,
+        )?;
+        std::fs::remove_dir_all(&dump_dir)?;
+
+        let genesis = Genesis::from_file(
+            self.output_dir.join(&config.genesis_file),
+            GenesisValidationMode::UnsafeFast,
+        );
+        config.tracked_shards = (0..genesis.config.shard_layout.num_shards()).collect();
+        if let Some(signer) = &validator_signer {
+            // The only validator doesn't need to wait for peers.
+            config.network.skip_sync_wait = true;
+            config.consensus.min_num_peers = 0;
+            signer.write_to_file(&self.output_dir.join(&config.validator_key_file))?;
+        }
+        InMemorySigner::from_random("node".parse().unwrap(), KeyType::ED25519)
+            .write_to_file(&self.output_dir.join(&config.node_key_file))?;
+        config.write_to_file(&self.output_dir.join(CONFIG_FILENAME))?;
+
+        println!("Created the home directory of the forked chain in {}", self.output_dir.display());
+        Ok(())
+    }
+}
+
+/// Loads the validator key from `path` if given, or generates a new random key
+/// for `node0`.
+fn load_or_generate_validator_key(path: Option<&Path>) -> anyhow::Result<InMemoryValidatorSigner> {
+    match path {
+        Some(path) => InMemoryValidatorSigner::from_file(path)
+            .with_context(|| format!("Error loading validator key from {}", path.display())),
+        None => {
+            Ok(InMemoryValidatorSigner::from_random("node0".parse().unwrap(), KeyType::ED25519))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load_or_generate_validator_key;
+    use near_crypto::KeyType;
+    use near_primitives::test_utils::create_test_signer;
+    use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+
+    #[test]
+    fn test_validator_key_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("validator_key.json");
+        let signer =
+            InMemoryValidatorSigner::from_random("alice".parse().unwrap(), KeyType::ED25519);
+        signer.write_to_file(&path).unwrap();
+
+        let loaded = load_or_generate_validator_key(Some(&path)).unwrap();
+        assert_eq!(signer.validator_id(), loaded.validator_id());
+        assert_eq!(signer.public_key(), loaded.public_key());
+
+        std::fs::write(&path, b"not a key").unwrap();
+        assert!(load_or_generate_validator_key(Some(&path)).is_err());
+        // An explicitly given key file must exist.
+        assert!(load_or_generate_validator_key(Some(&dir.path().join("missing.json"))).is_err());
+    }
+
+    #[test]
+    fn test_validator_key_generated() {
+        let first = load_or_generate_validator_key(None).unwrap();
+        let second = load_or_generate_validator_key(None).unwrap();
+        assert_eq!("node0", first.validator_id().as_str());
+        // The key must not be the well-known test key nor repeat.
+        assert_ne!(create_test_signer("node0").public_key(), first.public_key());
+        assert_ne!(first.public_key(), second.public_key());
+    }
+}
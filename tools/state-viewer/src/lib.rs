@@ -5,10 +5,16 @@ mod apply_chunk;
 pub mod cli;
 mod commands;
 mod contract_accounts;
+mod diff_state;
 mod dump_state_parts;
 mod epoch_info;
+mod fork;
+mod precompile;
+mod replay;
 mod rocksdb_stats;
 mod state_dump;
 mod tx_dump;
 
 pub use cli::StateViewerSubCommand;
+pub use fork::ForkCommand;
+pub use precompile::PrecompileCommand;
@@ -0,0 +1,76 @@
+use crate::commands::load_trie;
+use crate::contract_accounts::ContractAccount;
+use anyhow::Context;
+use clap::Parser;
+use near_chain_configs::GenesisValidationMode;
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives_core::contract::ContractCode;
+use near_store::{Mode, NodeStorage, Trie, TrieDBStorage};
+use nearcore::load_config;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Instant;
+
+/// Compile the contracts deployed in the current state and store them in
+/// the compiled contract cache, so that the node doesn't have to compile
+/// them on first use after a restart or an upgrade of the VM.
+#[derive(Parser)]
+pub struct PrecompileCommand {
+    /// Number of contracts handed to the runtime for compilation at once.
+    #[clap(long, default_value = "64")]
+    batch_size: usize,
+}
+
+impl PrecompileCommand {
+    pub fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = load_config(home_dir, genesis_validation)
+            .with_context(|| format!("Error loading config from {}", home_dir.display()))?;
+        // The compiled contracts are written to the store, so it can't be
+        // opened read-only.
+        let store = NodeStorage::opener(home_dir, &near_config.config.store, None)
+            .open_in_mode(Mode::ReadWrite)?
+            .get_hot_store();
+        let (runtime, state_roots, header) = load_trie(store.clone(), home_dir, &near_config);
+        let epoch_id = header.epoch_id();
+
+        let start = Instant::now();
+        let mut seen = HashSet::new();
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut num_contracts = 0;
+        for (shard_id, &state_root) in state_roots.iter().enumerate() {
+            eprintln!("Starting shard {shard_id}");
+            let shard_uid = runtime.shard_id_to_uid(shard_id as u64, epoch_id)?;
+            // Use simple non-caching storage, we don't expect many duplicate lookups while iterating.
+            let storage = TrieDBStorage::new(store.clone(), shard_uid);
+            let trie = Trie::new(Box::new(storage), state_root, None);
+
+            for contract in ContractAccount::in_trie(&trie)? {
+                let contract = match contract {
+                    Ok(contract) => contract,
+                    Err(err) => {
+                        eprintln!("{err}");
+                        continue;
+                    }
+                };
+                let code = ContractCode::new(contract.source_wasm.to_vec(), None);
+                if !seen.insert(*code.hash()) {
+                    continue;
+                }
+                batch.push(code);
+                if batch.len() >= self.batch_size {
+                    num_contracts += batch.len();
+                    runtime.precompile_contracts(epoch_id, std::mem::take(&mut batch))?;
+                    eprintln!("Compiled {num_contracts} contracts in {:?}", start.elapsed());
+                }
+            }
+        }
+        num_contracts += batch.len();
+        runtime.precompile_contracts(epoch_id, batch)?;
+        println!("Compiled {num_contracts} distinct contracts in {:?}", start.elapsed());
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+//! Re-execution of the chunks of a range of blocks, comparing the results with
+//! what the node recorded when it originally processed them.
+//!
+//! Replay stops at the first block and shard whose re-execution doesn't
+//! reproduce the recorded chunk extra or execution outcomes, and reports the
+//! recorded and replayed values of every trie key whose final value differs.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use near_chain::types::ApplyTransactionResult;
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeWithEpochManagerAdapter};
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::chunk_extra::ChunkExtra;
+use near_primitives::types::{AccountId, BlockHeight, RawStateChangesWithTrieKey, ShardId};
+use near_store::{KeyForStateChanges, Store};
+
+use crate::apply_chain_range::smart_equals;
+use crate::commands::{apply_block, resulting_chunk_extra};
+
+/// Result of replaying the chunks of a range of blocks.
+pub(crate) struct ReplayReport {
+    pub from_height: BlockHeight,
+    pub to_height: BlockHeight,
+    /// Number of chunks whose re-execution reproduced the recorded results.
+    pub num_replayed: usize,
+    /// The first chunk whose re-execution didn't reproduce the recorded results.
+    pub divergence: Option<Divergence>,
+}
+
+/// Differences between the recorded and the replayed results of a chunk.
+pub(crate) struct Divergence {
+    pub height: BlockHeight,
+    pub block_hash: CryptoHash,
+    pub shard_id: ShardId,
+    /// Descriptions of the differing chunk extra and outcomes.
+    pub mismatches: Vec<String>,
+    /// Trie keys of the shard whose final values after the block differ.
+    pub state_diff: Vec<StateDiff>,
+}
+
+/// Final values of a trie key after the block.  `None` means the key wasn't
+/// changed, `Some(None)` that it was deleted.
+pub(crate) struct StateDiff {
+    pub trie_key: TrieKey,
+    pub recorded: Option<Option<Vec<u8>>>,
+    pub replayed: Option<Option<Vec<u8>>>,
+}
+
+impl std::fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let divergence = match &self.divergence {
+            None => {
+                return write!(
+                    f,
+                    "No divergence found: replayed {} chunks in the range {}..={}",
+                    self.num_replayed, self.from_height, self.to_height
+                )
+            }
+            Some(divergence) => divergence,
+        };
+        writeln!(
+            f,
+            "Divergence at height {} (block {}) in shard {}:",
+            divergence.height, divergence.block_hash, divergence.shard_id
+        )?;
+        for mismatch in &divergence.mismatches {
+            writeln!(f, "* {}", mismatch)?;
+        }
+        writeln!(f, "State changes differing between the recorded and the replayed execution:")?;
+        for diff in &divergence.state_diff {
+            writeln!(f, "{:?}", diff.trie_key)?;
+            writeln!(f, "  recorded: {}", format_value(diff.recorded.as_ref()))?;
+            writeln!(f, "  replayed: {}", format_value(diff.replayed.as_ref()))?;
+        }
+        if divergence.state_diff.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        write!(
+            f,
+            "Replay diverged at height {} in shard {} after {} chunks reproduced correctly",
+            divergence.height, divergence.shard_id, self.num_replayed
+        )
+    }
+}
+
+/// Re-executes the chunks of the blocks from `from_height` to `to_height` of
+/// `shard_id`, or of all the shards tracked by the node, and stops at the first
+/// one whose results differ from the recorded ones.
+pub(crate) fn replay_execution(
+    from_height: BlockHeight,
+    to_height: BlockHeight,
+    shard_id: Option<ShardId>,
+    store: Store,
+    genesis_height: BlockHeight,
+    runtime_adapter: Arc<dyn RuntimeWithEpochManagerAdapter>,
+) -> anyhow::Result<ReplayReport> {
+    let mut chain_store = ChainStore::new(store.clone(), genesis_height, true);
+    let mut report = ReplayReport { from_height, to_height, num_replayed: 0, divergence: None };
+    for height in from_height..=to_height {
+        let block_hash = match chain_store.get_block_hash_by_height(height) {
+            Ok(block_hash) => block_hash,
+            // Skipped height.
+            Err(near_chain::Error::DBNotFoundErr(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let block = chain_store.get_block(&block_hash)?;
+        if *block.header().prev_hash() == CryptoHash::default() {
+            // Nothing to replay in the genesis block.
+            continue;
+        }
+        let shard_ids = match shard_id {
+            Some(shard_id) => vec![shard_id],
+            None => (0..block.chunks().len() as ShardId).collect(),
+        };
+        for shard_id in shard_ids {
+            let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, block.header().epoch_id())?;
+            let recorded_extra = match chain_store.get_chunk_extra(&block_hash, &shard_uid) {
+                Ok(chunk_extra) => chunk_extra,
+                // The node doesn't track the shard, so there is nothing to
+                // compare against.
+                Err(near_chain::Error::DBNotFoundErr(_)) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let (block, apply_result) =
+                apply_block(block_hash, shard_id, runtime_adapter.as_ref(), &mut chain_store);
+            let mismatches =
+                find_mismatches(&chain_store, &block, shard_id, &recorded_extra, &apply_result)?;
+            if mismatches.is_empty() {
+                report.num_replayed += 1;
+                continue;
+            }
+            let shard_layout = runtime_adapter.get_shard_layout(block.header().epoch_id())?;
+            let state_diff =
+                state_diff(&store, &block_hash, shard_id, &shard_layout, &apply_result)?;
+            report.divergence =
+                Some(Divergence { height, block_hash, shard_id, mismatches, state_diff });
+            return Ok(report);
+        }
+    }
+    Ok(report)
+}
+
+/// Returns descriptions of the differences between the results of replaying
+/// the chunk of `shard_id` in `block` and the results recorded in the store.
+fn find_mismatches(
+    chain_store: &ChainStore,
+    block: &Block,
+    shard_id: ShardId,
+    recorded_extra: &ChunkExtra,
+    apply_result: &ApplyTransactionResult,
+) -> anyhow::Result<Vec<String>> {
+    let chunk_header = &block.chunks()[shard_id as usize];
+    let replayed_extra = resulting_chunk_extra(apply_result, chunk_header.gas_limit());
+    let mut mismatches = vec![];
+
+    if chunk_header.height_included() != block.header().height() {
+        // Without a new chunk nothing is executed and there are no outcomes,
+        // but the state may still change, e.g. on epoch boundaries.
+        if recorded_extra.state_root() != replayed_extra.state_root() {
+            mismatches.push(format!(
+                "state root differs: recorded {}, replayed {}",
+                recorded_extra.state_root(),
+                replayed_extra.state_root()
+            ));
+        }
+        return Ok(mismatches);
+    }
+
+    if !smart_equals(recorded_extra, &replayed_extra) {
+        mismatches.push(format!(
+            "chunk extra differs:\n  recorded: {:?}\n  replayed: {:?}",
+            recorded_extra, replayed_extra
+        ));
+    }
+    let recorded_ids =
+        chain_store.get_outcomes_by_block_hash_and_shard_id(block.hash(), shard_id)?;
+    let replayed_ids = apply_result.outcomes.iter().map(|outcome| outcome.id).collect::<Vec<_>>();
+    if recorded_ids != replayed_ids {
+        mismatches.push(format!(
+            "executed transactions and receipts differ:\n  recorded: {:?}\n  replayed: {:?}",
+            recorded_ids, replayed_ids
+        ));
+        return Ok(mismatches);
+    }
+    for outcome in &apply_result.outcomes {
+        let recorded = chain_store
+            .get_outcome_by_id_and_block_hash(&outcome.id, block.hash())?
+            .map(|outcome_with_proof| outcome_with_proof.outcome);
+        if recorded.as_ref() != Some(&outcome.outcome) {
+            mismatches.push(format!(
+                "outcome of {} differs:\n  recorded: {:?}\n  replayed: {:?}",
+                outcome.id, recorded, outcome.outcome
+            ));
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Returns the trie keys of the shard whose final values after the block
+/// differ between the recorded state changes and the replayed ones.
+fn state_diff(
+    store: &Store,
+    block_hash: &CryptoHash,
+    shard_id: ShardId,
+    shard_layout: &ShardLayout,
+    apply_result: &ApplyTransactionResult,
+) -> anyhow::Result<Vec<StateDiff>> {
+    let mut replayed = final_values(apply_result.trie_changes.state_changes().iter().cloned());
+    let mut recorded = BTreeMap::new();
+    for change in KeyForStateChanges::for_block(block_hash).find_iter(store) {
+        let change = change?;
+        // Recorded changes cover all the shards tracked by the node.  Keys
+        // which don't belong to an account (e.g. delayed receipts) can't be
+        // attributed to a shard and are only compared if replay touched them.
+        let in_shard = match trie_key_account_id(&change.trie_key) {
+            Some(account_id) => account_id_to_shard_id(account_id, shard_layout) == shard_id,
+            None => replayed.contains_key(&change.trie_key.to_vec()),
+        };
+        if in_shard {
+            recorded.extend(final_values(std::iter::once(change)));
+        }
+    }
+
+    let keys = recorded.keys().chain(replayed.keys()).cloned().collect::<BTreeSet<_>>();
+    let mut diffs = vec![];
+    for key in keys {
+        let recorded = recorded.remove(&key);
+        let replayed = replayed.remove(&key);
+        if recorded.as_ref().map(|(_, value)| value) == replayed.as_ref().map(|(_, value)| value) {
+            continue;
+        }
+        let (trie_key, recorded, replayed) = match (recorded, replayed) {
+            (Some((trie_key, recorded)), replayed) => {
+                (trie_key, Some(recorded), replayed.map(|(_, value)| value))
+            }
+            (None, Some((trie_key, replayed))) => (trie_key, None, Some(replayed)),
+            (None, None) => continue,
+        };
+        diffs.push(StateDiff { trie_key, recorded, replayed });
+    }
+    Ok(diffs)
+}
+
+/// Maps raw trie keys to the trie key and the value it has after all the
+/// changes.  A `None` value means the key was deleted.
+fn final_values(
+    changes: impl Iterator<Item = RawStateChangesWithTrieKey>,
+) -> BTreeMap<Vec<u8>, (TrieKey, Option<Vec<u8>>)> {
+    changes
+        .filter_map(|RawStateChangesWithTrieKey { trie_key, changes }| {
+            let value = changes.last()?.data.clone();
+            Some((trie_key.to_vec(), (trie_key, value)))
+        })
+        .collect()
+}
+
+fn format_value(value: Option<&Option<Vec<u8>>>) -> String {
+    match value {
+        None => "unchanged".to_string(),
+        Some(None) => "deleted".to_string(),
+        Some(Some(data)) => format!("{} bytes, hash {}", data.len(), CryptoHash::hash_bytes(data)),
+    }
+}
+
+fn trie_key_account_id(trie_key: &TrieKey) -> Option<&AccountId> {
+    match trie_key {
+        TrieKey::Account { account_id }
+        | TrieKey::ContractCode { account_id }
+        | TrieKey::AccessKey { account_id, .. }
+        | TrieKey::ContractData { account_id, .. } => Some(account_id),
+        TrieKey::ReceivedData { receiver_id, .. }
+        | TrieKey::PostponedReceiptId { receiver_id, .. }
+        | TrieKey::PendingDataCount { receiver_id, .. }
+        | TrieKey::PostponedReceipt { receiver_id, .. } => Some(receiver_id),
+        TrieKey::DelayedReceiptIndices | TrieKey::DelayedReceipt { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use near_chain::{ChainGenesis, ChainStoreAccess};
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+    use near_primitives::types::chunk_extra::ChunkExtra;
+    use near_store::test_utils::create_test_store;
+    use near_store::DBCol;
+    use nearcore::config::GenesisExt;
+    use nearcore::NightshadeRuntime;
+
+    use super::replay_execution;
+
+    #[test]
+    fn test_replay_execution() {
+        let genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+        let store = create_test_store();
+        let runtime = Arc::new(NightshadeRuntime::test(Path::new("."), store.clone(), &genesis));
+        let mut chain_genesis = ChainGenesis::test();
+        chain_genesis.gas_limit = genesis.config.gas_limit;
+        let mut env = TestEnv::builder(chain_genesis)
+            .runtime_adapters(vec![runtime.clone() as Arc<_>])
+            .build();
+        for height in 1..=5 {
+            env.produce_block(0, height);
+        }
+
+        let report = replay_execution(1, 5, None, store.clone(), 0, runtime.clone()).unwrap();
+        assert!(report.divergence.is_none());
+        assert_eq!(report.num_replayed, 5);
+
+        // Replay reports the first chunk whose recorded results differ.
+        let block_hash = env.clients[0].chain.store().get_block_hash_by_height(3).unwrap();
+        let mut store_update = store.store_update();
+        store_update
+            .set_ser(
+                DBCol::ChunkExtra,
+                &get_block_shard_uid(&block_hash, &ShardUId::single_shard()),
+                &ChunkExtra::new_with_only_state_root(&CryptoHash::hash_bytes(b"tampered")),
+            )
+            .unwrap();
+        store_update.commit().unwrap();
+        let report = replay_execution(1, 5, None, store, 0, runtime).unwrap();
+        assert_eq!(report.num_replayed, 2);
+        let divergence = report.divergence.unwrap();
+        assert_eq!((divergence.height, divergence.block_hash), (3, block_hash));
+        assert!(!divergence.mismatches.is_empty());
+    }
+}
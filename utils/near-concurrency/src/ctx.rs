@@ -1,4 +1,4 @@
-use crate::concurrency::Once;
+use crate::Once;
 use std::fmt;
 use std::ops;
 use std::sync::Arc;
@@ -0,0 +1,23 @@
+//! Structured concurrency on top of tokio: contexts which propagate
+//! cancellation (`Ctx`), scopes which bound the lifetime of the futures they
+//! spawn (`Scope`) and a few synchronization primitives built on them.
+//! Originally written for chainsync-loadtest.
+
+pub mod ctx;
+mod once;
+mod rate_limiter;
+pub mod scope;
+pub mod weak_map;
+
+#[cfg(test)]
+mod ctx_test;
+#[cfg(test)]
+mod rate_limiter_test;
+#[cfg(test)]
+mod scope_test;
+
+pub use ctx::{Ctx, CtxWithCancel};
+pub use once::Once;
+pub use rate_limiter::RateLimiter;
+pub use scope::{MultiError, Scope, TaskError};
+pub use weak_map::WeakMap;
@@ -1,4 +1,4 @@
-use crate::concurrency::Ctx;
+use crate::Ctx;
 use std::sync::Arc;
 use tokio::time;
 
@@ -40,6 +40,30 @@ impl RateLimiter {
         })));
     }
 
+    // set_rate() changes the parameters of the RateLimiter (see new()).
+    // The permits already in the semaphore are kept, up to the new burst.
+    pub async fn set_rate(
+        &self,
+        ctx: &Ctx,
+        interval: time::Duration,
+        burst: u64,
+    ) -> anyhow::Result<()> {
+        if interval.is_zero() {
+            anyhow::bail!("interval has to be non-zero");
+        }
+        let mut rl = ctx.wrap(self.0.lock()).await?;
+        // Add the permits accumulated at the old rate first.
+        let now = time::Instant::now();
+        let ticks_now = rl.ticks(now);
+        let tokens = rl.tokens.wrapping_add(ticks_now.wrapping_sub(rl.ticks_processed));
+        rl.tokens = std::cmp::min(std::cmp::min(rl.burst, tokens), burst);
+        rl.interval = interval;
+        rl.burst = burst;
+        rl.start = now;
+        rl.ticks_processed = 0;
+        Ok(())
+    }
+
     // See semantics of https://pkg.go.dev/golang.org/x/time/rate
     pub async fn allow(&self, ctx: &Ctx) -> anyhow::Result<()> {
         let mut rl = ctx.wrap(self.0.lock()).await?;
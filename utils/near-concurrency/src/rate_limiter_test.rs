@@ -0,0 +1,25 @@
+use crate::{Ctx, RateLimiter};
+use tokio::time;
+
+#[tokio::test]
+async fn test_set_rate() {
+    let ctx = Ctx::background();
+    // Slow enough for no permit to be added during the test.
+    let slow = time::Duration::from_secs(3600);
+    let rl = RateLimiter::new(slow, 3);
+
+    // Lowering the burst drops the permits above it.
+    rl.set_rate(&ctx, slow, 1).await.unwrap();
+    rl.allow(&ctx).await.unwrap();
+    assert!(rl.allow(&ctx.with_timeout(time::Duration::from_millis(100))).await.is_err());
+
+    // Raising the burst doesn't add permits by itself.
+    rl.set_rate(&ctx, slow, 5).await.unwrap();
+    assert!(rl.allow(&ctx.with_timeout(time::Duration::from_millis(100))).await.is_err());
+
+    // Permits are added at the new interval.
+    rl.set_rate(&ctx, time::Duration::from_millis(1), 5).await.unwrap();
+    rl.allow(&ctx.with_timeout(time::Duration::from_secs(1))).await.unwrap();
+
+    assert!(rl.set_rate(&ctx, time::Duration::ZERO, 1).await.is_err());
+}
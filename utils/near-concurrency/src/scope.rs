@@ -1,6 +1,8 @@
-use crate::concurrency::{ctx, Ctx, CtxWithCancel};
+use crate::{ctx, Ctx, CtxWithCancel};
+use std::fmt;
 use std::future::Future;
 use std::sync::{Arc, Mutex};
+use tokio::time;
 
 // WaitGroup is an atomic counter which can be awaited to become 0.
 pub(super) struct WaitGroup {
@@ -44,8 +46,63 @@ impl WaitGroup {
     }
 }
 
+// TaskError is the error returned by a single future of a scope.
+#[derive(Debug)]
+pub struct TaskError {
+    // Label given when spawning the future, if any.
+    pub label: Option<String>,
+    // Time between spawning the future and its failure.
+    pub elapsed: time::Duration,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = self.label.as_deref().unwrap_or("<unlabeled>");
+        write!(f, "{} failed after {:.3}s: {:#}", label, self.elapsed.as_secs_f64(), self.error)
+    }
+}
+
+// MultiError is the error returned by Scope::run: the errors of all the
+// futures of the scope which failed, in the order in which they failed.
+#[derive(Debug)]
+pub struct MultiError {
+    pub errors: Vec<TaskError>,
+}
+
+impl MultiError {
+    // first() is the error which caused the scope to be cancelled.
+    pub fn first(&self) -> &TaskError {
+        &self.errors[0]
+    }
+}
+
+impl fmt::Display for MultiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} task(s) failed", self.errors.len())?;
+        for e in &self.errors {
+            write!(f, "; {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MultiError {}
+
+// is_cancelled() checks whether <e> only reports that a context got
+// cancelled, possibly in a nested scope.
+fn is_cancelled(e: &anyhow::Error) -> bool {
+    if let Some(e) = e.downcast_ref::<ctx::Error>() {
+        return e == &ctx::Error::Cancelled;
+    }
+    if let Some(e) = e.downcast_ref::<MultiError>() {
+        return e.errors.iter().all(|e| is_cancelled(&e.error));
+    }
+    false
+}
+
 struct ScopeState {
-    err: Option<anyhow::Error>,
+    errors: Vec<TaskError>,
     cancelled: bool,
 }
 
@@ -62,37 +119,64 @@ pub struct Scope {
 }
 
 impl Scope {
-    fn complete(&self, v: anyhow::Result<()>) {
+    fn complete(&self, label: Option<String>, start: time::Instant, v: anyhow::Result<()>) {
         let e = if let Err(e) = v {
             e
         } else {
             return;
         };
         let mut s = self.state.lock().unwrap();
-        if s.err.is_some() {
-            return;
-        }
         // Ignore Cancelled errors after the whole scope has been cancelled.
-        if s.cancelled
-            && e.downcast_ref::<ctx::Error>().map(|e| e == &ctx::Error::Cancelled).unwrap_or(false)
-        {
+        if s.cancelled && is_cancelled(&e) {
             return;
         }
-        s.err = Some(e);
+        s.errors.push(TaskError { label, elapsed: start.elapsed(), error: e });
         s.cancelled = true;
         self.ctx.cancel();
     }
 
+    // ctx() returns the context for a new future, labeled if <label> is set.
+    fn ctx(&self, label: Option<&str>) -> Ctx {
+        match label {
+            Some(label) => self.ctx.with_label(label),
+            None => (*self.ctx).clone(),
+        }
+    }
+
     // Spawn a "main" future in the scope.
     pub fn spawn<F>(self: &Arc<Self>, f: impl Send + FnOnce(Ctx, Arc<Self>) -> F)
     where
         F: Future<Output = anyhow::Result<()>> + Send + 'static,
     {
-        let fut = f((*self.ctx).clone(), self.clone());
+        self.spawn_inner(None, f)
+    }
+
+    // Same as spawn(), but the error of the future, if any, is reported with
+    // the given label, which is also added to its context.
+    pub fn spawn_labeled<F>(
+        self: &Arc<Self>,
+        label: &str,
+        f: impl Send + FnOnce(Ctx, Arc<Self>) -> F,
+    ) where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.spawn_inner(Some(label), f)
+    }
+
+    fn spawn_inner<F>(
+        self: &Arc<Self>,
+        label: Option<&str>,
+        f: impl Send + FnOnce(Ctx, Arc<Self>) -> F,
+    ) where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let start = time::Instant::now();
+        let fut = f(self.ctx(label), self.clone());
+        let label = label.map(str::to_string);
         let s = self.clone();
         s.main_futures.inc();
         tokio::spawn(async move {
-            s.complete(fut.await);
+            s.complete(label, start, fut.await);
             s.main_futures.dec();
         });
     }
@@ -102,11 +186,28 @@ impl Scope {
     where
         F: Future<Output = anyhow::Result<()>> + Send + 'static,
     {
-        let fut = f((*self.ctx).clone());
+        self.spawn_weak_inner(None, f)
+    }
+
+    // Same as spawn_weak(), but with a label, see spawn_labeled().
+    pub fn spawn_weak_labeled<F>(self: &Arc<Self>, label: &str, f: impl Send + FnOnce(Ctx) -> F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.spawn_weak_inner(Some(label), f)
+    }
+
+    fn spawn_weak_inner<F>(self: &Arc<Self>, label: Option<&str>, f: impl Send + FnOnce(Ctx) -> F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let start = time::Instant::now();
+        let fut = f(self.ctx(label));
+        let label = label.map(str::to_string);
         let s = self.clone();
         s.weak_futures.inc();
         tokio::spawn(async move {
-            s.complete(fut.await);
+            s.complete(label, start, fut.await);
             s.weak_futures.dec();
         });
     }
@@ -130,7 +231,9 @@ impl Scope {
     // encapsulation.
     // - All the futures of the scope are expected to complete successfully. If any of the futures
     // returns an error, the scope context will get cancelled, all still-ongoing scope futures will
-    // be notified and are expected to shutdown gracefully. The first error encountered will be returned.
+    // be notified and are expected to shutdown gracefully. All the errors encountered are returned
+    // as a MultiError, except for the context cancellation errors reported after the scope got
+    // cancelled.
     // - Scope supports 2 methods to spawn futures: spawn() which spawns "main" futures and
     // spawn_weak(), which spawns "weak" futures. "main" futures are allowed to spawn additional
     // futures, "weak" futures are not. Scope will wait for all "main" to complete, then it will
@@ -149,16 +252,17 @@ impl Scope {
     where
         F: Future<Output = anyhow::Result<T>> + Send + 'static,
     {
+        let start = time::Instant::now();
         let ctx = ctx.with_cancel();
         let s = Arc::new(Scope {
             ctx: ctx.clone(),
             main_futures: WaitGroup::new(0),
             weak_futures: WaitGroup::new(0),
-            state: Mutex::new(ScopeState { err: None, cancelled: false }),
+            state: Mutex::new(ScopeState { errors: vec![], cancelled: false }),
         });
         let res = match f((*ctx).clone(), s.clone()).await {
             Err(e) => {
-                s.complete(Err(e));
+                s.complete(None, start, Err(e));
                 None
             }
             Ok(v) => Some(v),
@@ -176,9 +280,9 @@ impl Scope {
         // TODO: add runtime verification that weak futures do not spawn anything,
         // or at least that no strong futures are spawned after cancellation.
         let mut state = s.state.lock().unwrap();
-        match state.err.take() {
-            Some(e) => Err(e),
-            None => Ok(res.unwrap()),
+        if !state.errors.is_empty() {
+            return Err(MultiError { errors: std::mem::take(&mut state.errors) }.into());
         }
+        Ok(res.unwrap())
     }
 }
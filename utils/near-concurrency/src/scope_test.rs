@@ -0,0 +1,39 @@
+use crate::scope::WaitGroup;
+use crate::{Ctx, MultiError, Scope};
+
+#[tokio::test]
+async fn test_wait_group() {
+    let wg = WaitGroup::new(0);
+    // Awaiting an empty waitgroup should return immediately.
+    wg.wait().await;
+
+    wg.inc();
+    // Create a wait() future on a non-empty wg.
+    let f = wg.wait();
+    // Make the wg empty again.
+    wg.dec();
+    // The future should return immediately, even though f.await was not active,
+    // when wg became empty.
+    f.await;
+
+    // wg is empty again, so this should return immediately.
+    wg.wait().await;
+}
+
+#[tokio::test]
+async fn test_multi_error() {
+    let res = Scope::run(&Ctx::background(), |_ctx, s| async move {
+        s.spawn_labeled("a", |_ctx, _s| async move { Err(anyhow::anyhow!("error a")) });
+        s.spawn_labeled("b", |_ctx, _s| async move { Err(anyhow::anyhow!("error b")) });
+        // Cancellation errors after the scope got cancelled are not reported.
+        s.spawn_labeled("c", |ctx, _s| async move { Err(ctx.done().await.into()) });
+        anyhow::Ok(())
+    })
+    .await;
+    let err = res.unwrap_err();
+    let err = err.downcast_ref::<MultiError>().unwrap();
+    let mut labels: Vec<_> = err.errors.iter().map(|e| e.label.as_deref().unwrap()).collect();
+    labels.sort();
+    assert_eq!(labels, vec!["a", "b"]);
+    assert!(err.to_string().starts_with("2 task(s) failed; "));
+}